@@ -1,6 +1,10 @@
 use fraction::ToPrimitive;
 
-use crate::{error::GpResult, io::primitive::*, model::song::*};
+use crate::{
+    error::{GpResult, ToPrimitiveGp},
+    io::primitive::*,
+    model::song::*,
+};
 
 //MIDI channels
 
@@ -138,6 +142,8 @@ pub const CHANNEL_DEFAULT_NAMES: [&str; 128] = [
 pub const DEFAULT_PERCUSSION_CHANNEL: u8 = 9;
 /// A MIDI channel describes playing data for a track.
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct MidiChannel {
     pub channel: u8,
     pub effect_channel: u8,
@@ -209,7 +215,7 @@ impl SongMidiOps for Song {
     /// Each channel has the following form:
     ///
     /// * **Instrument**: `int`
-    /// * **Volume**: `byte`
+    /// * **Volume**: `byte`, from 0 to 16 (see `to_channel_short`)
     /// * **Balance**: `byte`
     /// * **Chorus**: `byte`
     /// * **Reverb**: `byte`
@@ -229,12 +235,12 @@ impl SongMidiOps for Song {
             effect_channel: channel,
             ..Default::default()
         };
-        c.volume = read_signed_byte(data, seek)?;
-        c.balance = read_signed_byte(data, seek)?;
-        c.chorus = read_signed_byte(data, seek)?;
-        c.reverb = read_signed_byte(data, seek)?;
-        c.phaser = read_signed_byte(data, seek)?;
-        c.tremolo = read_signed_byte(data, seek)?;
+        c.volume = Self::to_channel_short(read_signed_byte(data, seek)?);
+        c.balance = Self::to_channel_short(read_signed_byte(data, seek)?);
+        c.chorus = Self::to_channel_short(read_signed_byte(data, seek)?);
+        c.reverb = Self::to_channel_short(read_signed_byte(data, seek)?);
+        c.phaser = Self::to_channel_short(read_signed_byte(data, seek)?);
+        c.tremolo = Self::to_channel_short(read_signed_byte(data, seek)?);
         c.set_instrument(instrument);
         //println!("Channel: {}\t Volume: {}\tBalance: {}\tInstrument={}, {}, {}", c.channel, c.volume, c.balance, instrument, c.get_instrument(), c.get_instrument_name());
         *seek += 2; //Backward compatibility with version 3.0
@@ -243,7 +249,6 @@ impl SongMidiOps for Song {
 
     /// Read MIDI channel. MIDI channel in Guitar Pro is represented by two integers. First is zero-based number of channel, second is zero-based number of channel used for effects.
     fn read_channel(&mut self, data: &[u8], seek: &mut usize) -> GpResult<usize> {
-        //TODO: fixme for writing
         let index = read_int(data, seek)? - 1;
         let effect_channel = read_int(data, seek)? - 1;
         if 0 <= index && index < self.channels.len().to_i32().unwrap() {
@@ -255,30 +260,39 @@ impl SongMidiOps for Song {
                     effect_channel.to_u8().unwrap();
             }
         }
-        Ok(index.to_usize().unwrap())
+        index.to_usize_gp("track channel")
     }
 
+    /// Write the 64 MIDI channels, the missing entries of a shorter channel table are written with the default values.
     fn write_midi_channels(&self, data: &mut Vec<u8>) {
-        for i in 0..self.channels.len() {
-            println!("writing channel: {:?}", self.channels[i]);
-            if self.channels[i].is_percussion_channel() && self.channels[i].instrument == 0 {
-                write_i32(data, -1);
-            } else {
-                write_i32(data, self.channels[i].instrument);
-            }
-            write_signed_byte(data, Self::from_channel_short(self.channels[i].volume));
-            write_signed_byte(data, Self::from_channel_short(self.channels[i].balance));
-            write_signed_byte(data, Self::from_channel_short(self.channels[i].chorus));
-            write_signed_byte(data, Self::from_channel_short(self.channels[i].reverb));
-            write_signed_byte(data, Self::from_channel_short(self.channels[i].phaser));
-            write_signed_byte(data, Self::from_channel_short(self.channels[i].tremolo));
+        for i in 0..64 {
+            let channel = self.channel_at(i);
+            write_i32(data, channel.instrument);
+            write_signed_byte(data, Self::from_channel_short(channel.volume));
+            write_signed_byte(data, Self::from_channel_short(channel.balance));
+            write_signed_byte(data, Self::from_channel_short(channel.chorus));
+            write_signed_byte(data, Self::from_channel_short(channel.reverb));
+            write_signed_byte(data, Self::from_channel_short(channel.phaser));
+            write_signed_byte(data, Self::from_channel_short(channel.tremolo));
             write_placeholder_default(data, 2); //Backward compatibility with version 3.0
         }
     }
 }
 
 impl Song {
+    /// Channel values are stored from 0 to 16 in the files, and kept from 0 to 127 in the model.
+    pub(crate) fn to_channel_short(data: i8) -> i8 {
+        ((i16::from(data) << 3) - 1).clamp(-1, 126) as i8 + 1
+    }
     fn from_channel_short(data: i8) -> i8 {
-        ((data >> 3) - 1).clamp(-128, 127) + 1
+        ((i16::from(data) + 1) >> 3).clamp(0, 16) as i8
+    }
+    /// Channel of the 64-channel table at `index`, or a default channel on this index if the table is shorter.
+    pub(crate) fn channel_at(&self, index: usize) -> MidiChannel {
+        self.channels.get(index).copied().unwrap_or(MidiChannel {
+            channel: index as u8,
+            effect_channel: index as u8,
+            ..Default::default()
+        })
     }
 }
@@ -0,0 +1,573 @@
+//! Standard MIDI File export (format 1).
+//!
+//! The first track is the conductor track: tempo changes, time and key signatures and markers.
+//! Every track of the song then gets its own track, played on the channel of its `MidiChannel`.
+//! Measures are written in score order, repeats are not expanded. Mix table changes play as tempo,
+//! program, volume and pan events, a change with a duration ramps by one step per quarter note.
+use std::collections::{BTreeMap, HashMap};
+
+use crate::audio::midi::{MidiChannel, DEFAULT_PERCUSSION_CHANNEL};
+use crate::error::{GpError, GpResult};
+use crate::model::{
+    beat::Beat,
+    effects::{BendEffect, BEND_EFFECT_MAX_POSITION},
+    enums::*,
+    key_signature::{Duration, DURATION_QUARTER_TIME},
+    mix_table::MixTableChange,
+    song::Song,
+    track::Track,
+};
+
+/// Ticks per quarter note, the resolution of the song.
+const DIVISION: i64 = DURATION_QUARTER_TIME;
+/// Pitch bend range set on every pitched channel, in semitones. Bends go up to two tones and more.
+const BEND_RANGE: i32 = 12;
+const DEFAULT_TEMPO: i32 = 120;
+
+// Meta event types
+const META_COPYRIGHT: u8 = 0x02;
+const META_TRACK_NAME: u8 = 0x03;
+const META_MARKER: u8 = 0x06;
+const META_END_OF_TRACK: u8 = 0x2f;
+const META_TEMPO: u8 = 0x51;
+const META_TIME_SIGNATURE: u8 = 0x58;
+const META_KEY_SIGNATURE: u8 = 0x59;
+
+// Controllers
+const CC_BANK_SELECT: u8 = 0;
+const CC_DATA_ENTRY: u8 = 6;
+const CC_VOLUME: u8 = 7;
+const CC_PAN: u8 = 10;
+const CC_DATA_ENTRY_LSB: u8 = 38;
+const CC_REVERB: u8 = 91;
+const CC_CHORUS: u8 = 93;
+const CC_RPN_LSB: u8 = 100;
+const CC_RPN_MSB: u8 = 101;
+
+pub trait SongMidiExportOps {
+    /// Export the song as a Standard MIDI File (format 1), one track per song track after the
+    /// conductor track.
+    fn write_midi(&self) -> GpResult<Vec<u8>>;
+}
+
+/// Order of the events sharing a tick: notes end before the next ones start, and the controllers
+/// and pitch bends of a note are sent before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum EventOrder {
+    Meta,
+    NoteOff,
+    Control,
+    PitchBend,
+    NoteOn,
+}
+
+struct Event {
+    tick: i64,
+    order: EventOrder,
+    data: Vec<u8>,
+}
+
+impl Event {
+    fn meta(tick: i64, kind: u8, payload: &[u8]) -> Self {
+        let mut data = vec![0xff, kind];
+        write_vlq(&mut data, payload.len() as u32);
+        data.extend_from_slice(payload);
+        Event {
+            tick,
+            order: EventOrder::Meta,
+            data,
+        }
+    }
+
+    fn channel(tick: i64, order: EventOrder, status: u8, channel: u8, data: &[u8]) -> Self {
+        let mut message = vec![status | channel];
+        message.extend_from_slice(data);
+        Event {
+            tick,
+            order,
+            data: message,
+        }
+    }
+
+    fn control(channel: u8, controller: u8, value: i32) -> Self {
+        let value = value.clamp(0, 127) as u8;
+        Event::channel(0, EventOrder::Control, 0xb0, channel, &[controller, value])
+    }
+}
+
+/// Write a variable-length quantity: 7 bits per byte, most significant first.
+fn write_vlq(out: &mut Vec<u8>, mut value: u32) {
+    let mut bytes = vec![(value & 0x7f) as u8];
+    value >>= 7;
+    while value > 0 {
+        bytes.push((value & 0x7f) as u8 | 0x80);
+        value >>= 7;
+    }
+    out.extend(bytes.iter().rev());
+}
+
+/// Sort the events and write them as an `MTrk` chunk.
+fn track_chunk(mut events: Vec<Event>) -> GpResult<Vec<u8>> {
+    events.sort_by_key(|e| (e.tick, e.order));
+    let mut data = Vec::new();
+    let mut tick = 0;
+    for event in events {
+        let tick_event = event.tick.max(tick);
+        write_vlq(&mut data, (tick_event - tick) as u32);
+        data.extend(event.data);
+        tick = tick_event;
+    }
+    data.extend([0x00, 0xff, META_END_OF_TRACK, 0x00]);
+    let length =
+        u32::try_from(data.len()).map_err(|_| GpError::WriteError("MIDI track too long".into()))?;
+    let mut chunk = Vec::with_capacity(data.len() + 8);
+    chunk.extend(b"MTrk");
+    chunk.extend(length.to_be_bytes());
+    chunk.extend(data);
+    Ok(chunk)
+}
+
+/// Length of a duration in ticks, dots and tuplet included.
+fn duration_ticks(duration: &Duration) -> i64 {
+    let base = DIVISION * 4 / i64::from(duration.value.max(1));
+    let mut ticks = base;
+    if duration.dotted {
+        ticks += base / 2;
+    } else if duration.double_dotted {
+        ticks += base / 2 + base / 4;
+    }
+    if !duration.is_default_tuplet() && duration.tuplet_enters > 0 {
+        ticks = ticks * i64::from(duration.tuplet_times) / i64::from(duration.tuplet_enters);
+    }
+    ticks
+}
+
+fn tempo_event(tick: i64, tempo: i32) -> Event {
+    let micros = 60_000_000 / tempo.max(1) as u32;
+    Event::meta(tick, META_TEMPO, &micros.to_be_bytes()[1..])
+}
+
+/// A note to play, before it is split into note on and note off events.
+struct PlayedNote {
+    start: i64,
+    end: i64,
+    key: u8,
+    velocity: u8,
+}
+
+/// Writes the events of a track.
+struct TrackWriter<'a> {
+    track: &'a Track,
+    channel: u8,
+    notes: Vec<PlayedNote>,
+    bends: Vec<Event>,
+    /// Staff being written, 0 for the first one and then the index in `Track::staves` plus one
+    staff: usize,
+    /// Note sounding on each (staff, voice, string), extended by tied notes
+    open: HashMap<(usize, usize, i8), usize>,
+    /// Program, volume and pan changes of the mix tables
+    controls: Vec<Event>,
+    /// Current value of the volume and pan controllers
+    controllers: HashMap<u8, i32>,
+}
+
+impl<'a> TrackWriter<'a> {
+    /// Strings and capo of the staff being written.
+    fn strings(&self) -> (&'a [(i8, i8)], i32) {
+        match self
+            .staff
+            .checked_sub(1)
+            .and_then(|s| self.track.staves.get(s))
+        {
+            Some(staff) => (&staff.strings, staff.offset),
+            None => (&self.track.strings, self.track.offset),
+        }
+    }
+
+    /// Pitch of a fret on a string, with the capo.
+    fn pitch(&self, string: i8, fret: i32) -> Option<u8> {
+        let (strings, offset) = self.strings();
+        let tuning = strings.iter().find(|s| s.0 == string)?.1;
+        let pitch = i32::from(tuning) + fret + offset;
+        (0..=127).contains(&pitch).then_some(pitch as u8)
+    }
+
+    fn write_beat(&mut self, v_idx: usize, beat: &Beat, start: i64, length: i64) {
+        if beat.status != BeatStatus::Normal {
+            for string in self.strings().0.iter().map(|s| s.0) {
+                self.open.remove(&(self.staff, v_idx, string));
+            }
+            return;
+        }
+        for note in &beat.notes {
+            let key = if self.track.percussion_track {
+                u8::try_from(note.value).ok().filter(|k| *k < 128)
+            } else {
+                self.pitch(note.string, i32::from(note.value))
+            };
+            let Some(key) = key else {
+                continue;
+            };
+            if note.kind == NoteType::Tie {
+                if let Some(&index) = self.open.get(&(self.staff, v_idx, note.string)) {
+                    self.notes[index].end = start + length;
+                    continue;
+                }
+            }
+
+            let mut start = start;
+            let mut end = start + length;
+            if let Some(grace) = &note.effect.grace {
+                let grace_length = DIVISION * 4 / i64::from(grace.duration.max(1));
+                let grace_start = if grace.is_on_beat {
+                    start
+                } else {
+                    start - grace_length
+                };
+                if let Some(grace_key) = self.pitch(note.string, i32::from(grace.fret)) {
+                    self.notes.push(PlayedNote {
+                        start: grace_start.max(0),
+                        end: grace_start + grace_length,
+                        key: grace_key,
+                        velocity: grace.velocity.clamp(1, 127) as u8,
+                    });
+                }
+                if grace.is_on_beat {
+                    start += grace_length.min(length / 2);
+                }
+            }
+            if note.kind == NoteType::Dead {
+                end = start + (end - start).min(DIVISION / 8);
+            } else if note.effect.staccato {
+                end = start + (end - start) / 2;
+            }
+            if let Some(bend) = &note.effect.bend {
+                if !self.track.percussion_track {
+                    self.write_bend(bend, start, end);
+                }
+            }
+            self.open
+                .insert((self.staff, v_idx, note.string), self.notes.len());
+            self.notes.push(PlayedNote {
+                start,
+                end,
+                key,
+                velocity: note.velocity.clamp(1, 127) as u8,
+            });
+        }
+    }
+
+    /// Program, volume and pan changes of a mix table. Mix table values go from 0 to 16.
+    fn write_mix_table(&mut self, mix: &MixTableChange, start: i64) {
+        if let Some(program) = &mix.instrument {
+            self.controls.push(Event::channel(
+                start,
+                EventOrder::Control,
+                0xc0,
+                self.channel,
+                &[program.value.min(127)],
+            ));
+        }
+        for (controller, item) in [(CC_VOLUME, &mix.volume), (CC_PAN, &mix.balance)] {
+            let Some(item) = item else {
+                continue;
+            };
+            let target = i32::from(Song::to_channel_short(item.value.min(16) as i8));
+            let from = self
+                .controllers
+                .insert(controller, target)
+                .unwrap_or(target);
+            let steps = i64::from(item.duration.max(1));
+            for step in 1..=steps {
+                let value = from + (target - from) * step as i32 / steps as i32;
+                self.controls.push(Event::channel(
+                    start + (step - 1) * DIVISION,
+                    EventOrder::Control,
+                    0xb0,
+                    self.channel,
+                    &[controller, value.clamp(0, 127) as u8],
+                ));
+            }
+        }
+    }
+
+    /// Pitch bends along a note, back to the center at its end.
+    fn write_bend(&mut self, bend: &BendEffect, start: i64, end: i64) {
+        let max_position = i64::from(BEND_EFFECT_MAX_POSITION);
+        for point in &bend.points {
+            let tick =
+                start + (end - start) * i64::from(point.position).min(max_position) / max_position;
+            // Point values are in quarter tones
+            let value = (8192 + i32::from(point.value) * 4096 / BEND_RANGE).clamp(0, 16383);
+            self.bends.push(Event::channel(
+                tick,
+                EventOrder::PitchBend,
+                0xe0,
+                self.channel,
+                &[(value & 0x7f) as u8, (value >> 7) as u8],
+            ));
+        }
+        self.bends.push(Event::channel(
+            end,
+            EventOrder::NoteOff,
+            0xe0,
+            self.channel,
+            &[0x00, 0x40],
+        ));
+    }
+
+    fn events(mut self, starts: &[i64], midi_channel: &MidiChannel) -> Vec<Event> {
+        let mut events = vec![Event::meta(0, META_TRACK_NAME, self.track.name.as_bytes())];
+        let channel = self.channel;
+        if midi_channel.bank > 0 {
+            events.push(Event::control(
+                channel,
+                CC_BANK_SELECT,
+                i32::from(midi_channel.bank),
+            ));
+        }
+        let program = self
+            .track
+            .midi_program_gpif
+            .unwrap_or(midi_channel.instrument)
+            .clamp(0, 127) as u8;
+        events.push(Event::channel(
+            0,
+            EventOrder::Control,
+            0xc0,
+            channel,
+            &[program],
+        ));
+        events.push(Event::control(
+            channel,
+            CC_VOLUME,
+            i32::from(midi_channel.volume),
+        ));
+        events.push(Event::control(
+            channel,
+            CC_PAN,
+            i32::from(midi_channel.balance),
+        ));
+        events.push(Event::control(
+            channel,
+            CC_REVERB,
+            i32::from(midi_channel.reverb),
+        ));
+        events.push(Event::control(
+            channel,
+            CC_CHORUS,
+            i32::from(midi_channel.chorus),
+        ));
+        if !self.track.percussion_track {
+            // Pitch bend sensitivity (RPN 0), then reset of the RPN
+            for (controller, value) in [
+                (CC_RPN_MSB, 0),
+                (CC_RPN_LSB, 0),
+                (CC_DATA_ENTRY, BEND_RANGE),
+                (CC_DATA_ENTRY_LSB, 0),
+                (CC_RPN_MSB, 127),
+                (CC_RPN_LSB, 127),
+            ] {
+                events.push(Event::control(channel, controller, value));
+            }
+        }
+
+        for (m_idx, measure) in self.track.measures.iter().enumerate() {
+            let Some(&measure_start) = starts.get(m_idx) else {
+                break;
+            };
+            let staves =
+                std::iter::once(&measure.voices).chain(measure.staves.iter().map(|s| &s.voices));
+            for (staff, voices) in staves.enumerate() {
+                self.staff = staff;
+                for (v_idx, voice) in voices.iter().enumerate() {
+                    let mut tick = measure_start;
+                    for beat in &voice.beats {
+                        let length = duration_ticks(&beat.duration);
+                        if let Some(mix) = &beat.effect.mix_table_change {
+                            self.write_mix_table(mix, tick);
+                        }
+                        self.write_beat(v_idx, beat, tick, length);
+                        tick += length;
+                    }
+                }
+            }
+        }
+
+        for note in &self.notes {
+            events.push(Event::channel(
+                note.start,
+                EventOrder::NoteOn,
+                0x90,
+                channel,
+                &[note.key, note.velocity],
+            ));
+            events.push(Event::channel(
+                note.end.max(note.start + 1),
+                EventOrder::NoteOff,
+                0x80,
+                channel,
+                &[note.key, 0x40],
+            ));
+        }
+        events.extend(self.bends);
+        events.extend(self.controls);
+        events
+    }
+}
+
+impl Song {
+    /// Channel settings of a track. Songs read from GP6/7 files have no channel table.
+    fn midi_channel(&self, track: &Track) -> MidiChannel {
+        let mut channel = self
+            .channels
+            .get(track.channel_index)
+            .copied()
+            .unwrap_or(MidiChannel {
+                channel: (track.channel_index % 16) as u8,
+                ..Default::default()
+            });
+        if track.percussion_track {
+            channel.channel = DEFAULT_PERCUSSION_CHANNEL;
+        }
+        channel
+    }
+
+    /// Conductor track: tempo changes from the measure headers and the mix tables, time and key
+    /// signatures and markers.
+    fn conductor_events(&self, starts: &[i64]) -> Vec<Event> {
+        let mut events = Vec::new();
+        if !self.name.is_empty() {
+            events.push(Event::meta(0, META_TRACK_NAME, self.name.as_bytes()));
+        }
+        if !self.copyright.is_empty() {
+            events.push(Event::meta(0, META_COPYRIGHT, self.copyright.as_bytes()));
+        }
+
+        let mut tempos: BTreeMap<i64, i32> = BTreeMap::new();
+        tempos.insert(
+            0,
+            if self.tempo > 0 {
+                i32::from(self.tempo)
+            } else {
+                DEFAULT_TEMPO
+            },
+        );
+        let mut ramps: Vec<(i64, i32, i64)> = Vec::new();
+        let mut previous: Option<(i8, u16, i8, bool)> = None;
+        for (header, &start) in self.measure_headers.iter().zip(starts) {
+            let time_signature = &header.time_signature;
+            let key = &header.key_signature;
+            let signature = (
+                time_signature.numerator,
+                time_signature.denominator.value,
+                key.key,
+                key.is_minor,
+            );
+            if previous.is_none_or(|p| (p.0, p.1) != (signature.0, signature.1)) {
+                let denominator = signature.1.max(1).trailing_zeros() as u8;
+                events.push(Event::meta(
+                    start,
+                    META_TIME_SIGNATURE,
+                    &[signature.0.max(1) as u8, denominator, 24, 8],
+                ));
+            }
+            if previous.is_none_or(|p| (p.2, p.3) != (signature.2, signature.3)) {
+                events.push(Event::meta(
+                    start,
+                    META_KEY_SIGNATURE,
+                    &[signature.2.clamp(-7, 7) as u8, u8::from(signature.3)],
+                ));
+            }
+            previous = Some(signature);
+            if header.tempo > 0 {
+                tempos.insert(start, header.tempo);
+            }
+            if let Some(marker) = &header.marker {
+                events.push(Event::meta(start, META_MARKER, marker.title.as_bytes()));
+            }
+        }
+        for track in &self.tracks {
+            for (measure, &start) in track.measures.iter().zip(starts) {
+                for voice in &measure.voices {
+                    let mut tick = start;
+                    for beat in &voice.beats {
+                        let tempo = beat
+                            .effect
+                            .mix_table_change
+                            .as_ref()
+                            .and_then(|m| m.tempo.as_ref());
+                        match tempo.filter(|t| t.value > 0) {
+                            Some(t) if t.duration > 0 => {
+                                ramps.push((tick, i32::from(t.value), i64::from(t.duration)))
+                            }
+                            Some(t) => {
+                                tempos.insert(tick, i32::from(t.value));
+                            }
+                            None => {}
+                        }
+                        tick += duration_ticks(&beat.duration);
+                    }
+                }
+            }
+        }
+        // Ramps go from the tempo in effect to their own tempo, one step per quarter note
+        for (tick, target, steps) in ramps {
+            let from = tempos
+                .range(..=tick)
+                .next_back()
+                .map_or(target, |(_, tempo)| *tempo);
+            for step in 1..=steps {
+                let tempo = from + (target - from) * step as i32 / steps as i32;
+                tempos.entry(tick + (step - 1) * DIVISION).or_insert(tempo);
+            }
+        }
+        let mut current = 0;
+        for (tick, tempo) in tempos {
+            if tempo != current {
+                events.push(tempo_event(tick, tempo));
+                current = tempo;
+            }
+        }
+        events
+    }
+}
+
+impl SongMidiExportOps for Song {
+    fn write_midi(&self) -> GpResult<Vec<u8>> {
+        let mut starts = Vec::with_capacity(self.measure_headers.len());
+        let mut tick = 0;
+        for header in &self.measure_headers {
+            starts.push(tick);
+            tick += header.length();
+        }
+
+        let track_count = u16::try_from(self.tracks.len() + 1)
+            .map_err(|_| GpError::WriteError("too many tracks for a MIDI file".into()))?;
+        let mut data = Vec::new();
+        data.extend(b"MThd");
+        data.extend(6u32.to_be_bytes());
+        data.extend(1u16.to_be_bytes());
+        data.extend(track_count.to_be_bytes());
+        data.extend((DIVISION as u16).to_be_bytes());
+        data.extend(track_chunk(self.conductor_events(&starts))?);
+        for track in &self.tracks {
+            let midi_channel = self.midi_channel(track);
+            let writer = TrackWriter {
+                track,
+                channel: midi_channel.channel % 16,
+                notes: Vec::new(),
+                bends: Vec::new(),
+                staff: 0,
+                open: HashMap::new(),
+                controls: Vec::new(),
+                controllers: HashMap::from([
+                    (CC_VOLUME, i32::from(midi_channel.volume)),
+                    (CC_PAN, i32::from(midi_channel.balance)),
+                ]),
+            };
+            data.extend(track_chunk(writer.events(&starts, &midi_channel))?);
+        }
+        Ok(data)
+    }
+}
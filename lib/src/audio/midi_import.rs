@@ -0,0 +1,717 @@
+//! Standard MIDI File import (formats 0 and 1).
+//!
+//! Every channel of every MIDI track holding notes becomes a track of the song, channel 10 giving
+//! percussion tracks. Note times are quantized to a 32nd note grid, or to a 16th triplet grid in the
+//! beats where it fits better, then cut into measures and durations. Tracks have a single voice: a
+//! chord lasts until the next one starts. Pitched notes are placed on the strings of the tuning.
+use std::collections::{BTreeMap, HashMap};
+
+use crate::audio::midi::{MidiChannel, CHANNEL_DEFAULT_NAMES, DEFAULT_PERCUSSION_CHANNEL};
+use crate::error::{GpError, GpResult};
+use crate::model::{
+    beat::{Beat, Voice},
+    conversion::{LossPosition, LostItem},
+    enums::*,
+    headers::{Marker, MeasureHeader},
+    key_signature::{Duration, KeySignature, TimeSignature, DURATION_QUARTER_TIME},
+    lyric::Lyrics,
+    measure::Measure,
+    note::Note,
+    song::Song,
+    track::Track,
+};
+
+/// Ticks per quarter note of the song, MIDI times are scaled to it.
+const DIVISION: i64 = DURATION_QUARTER_TIME;
+/// Quantization grids: 32nd notes, or 16th triplets.
+const STRAIGHT_GRID: i64 = DIVISION / 8;
+const TRIPLET_GRID: i64 = DIVISION / 6;
+const DEFAULT_TEMPO: i32 = 120;
+/// Standard four-string bass tuning, for the bass programs (33 to 40 in General MIDI).
+const BASS_TUNING: [(i8, i8); 4] = [(1, 43), (2, 38), (3, 33), (4, 28)];
+
+/// Durations starting on a beat, longest first.
+const BEAT_DURATIONS: [(i64, u16, bool); 5] = [
+    (DIVISION * 4, 1, false),
+    (DIVISION * 3, 2, true),
+    (DIVISION * 2, 2, false),
+    (DIVISION * 3 / 2, 4, true),
+    (DIVISION, 4, false),
+];
+/// Durations inside a beat, longest first.
+const STRAIGHT_DURATIONS: [(i64, u16, bool); 5] = [
+    (DIVISION * 3 / 4, 8, true),
+    (DIVISION / 2, 8, false),
+    (DIVISION * 3 / 8, 16, true),
+    (DIVISION / 4, 16, false),
+    (DIVISION / 8, 32, false),
+];
+/// Triplet durations inside a beat, longest first.
+const TRIPLET_DURATIONS: [(i64, u16); 3] =
+    [(DIVISION * 2 / 3, 4), (DIVISION / 3, 8), (DIVISION / 6, 16)];
+
+pub trait SongMidiImportOps {
+    /// Read a Standard MIDI File (format 0 or 1). Pitched notes are placed on a standard guitar
+    /// tuning, or on a bass tuning for the bass programs. Notes that fit on no string are listed in
+    /// `Song::read_losses`.
+    fn read_midi(&mut self, data: &[u8]) -> GpResult<()>;
+    /// Read a Standard MIDI File, placing the pitched notes of every track on `strings` (string
+    /// number, MIDI pitch of the open string) with `fret_count` frets.
+    fn read_midi_with_tuning(
+        &mut self,
+        data: &[u8],
+        strings: &[(i8, i8)],
+        fret_count: u8,
+    ) -> GpResult<()>;
+}
+
+struct MidiNote {
+    start: i64,
+    end: i64,
+    key: u8,
+    velocity: u8,
+}
+
+/// Notes of a channel in a MIDI track.
+struct MidiPart {
+    name: String,
+    channel: u8,
+    notes: Vec<MidiNote>,
+}
+
+/// First program, volume and pan found for a channel.
+#[derive(Default, Clone, Copy)]
+struct ChannelSettings {
+    program: Option<u8>,
+    volume: Option<u8>,
+    pan: Option<u8>,
+}
+
+#[derive(Default)]
+struct MidiFile {
+    /// Name of the conductor track of a format 1 file
+    title: String,
+    tempos: BTreeMap<i64, i32>,
+    time_signatures: BTreeMap<i64, (i8, u16)>,
+    keys: BTreeMap<i64, (i8, bool)>,
+    markers: BTreeMap<i64, String>,
+    channels: HashMap<u8, ChannelSettings>,
+    parts: Vec<MidiPart>,
+}
+
+struct ByteReader<'a> {
+    data: &'a [u8],
+    seek: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    fn bytes(&mut self, count: usize) -> GpResult<&'a [u8]> {
+        if self.data.len() - self.seek < count {
+            return Err(GpError::UnexpectedEof {
+                offset: self.seek,
+                needed: count,
+            });
+        }
+        self.seek += count;
+        Ok(&self.data[self.seek - count..self.seek])
+    }
+
+    fn byte(&mut self) -> GpResult<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> GpResult<u16> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> GpResult<u32> {
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    /// Variable-length quantity: 7 bits per byte, most significant first, 4 bytes at most.
+    fn vlq(&mut self) -> GpResult<u32> {
+        let mut value = 0u32;
+        for _ in 0..4 {
+            let byte = self.byte()?;
+            value = (value << 7) | u32::from(byte & 0x7f);
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(GpError::InvalidValue {
+            context: "MIDI variable-length quantity",
+            value: i64::from(value),
+        })
+    }
+
+    fn remaining(&self) -> usize {
+        self.data.len() - self.seek
+    }
+}
+
+impl MidiFile {
+    fn parse(data: &[u8]) -> GpResult<MidiFile> {
+        let mut reader = ByteReader { data, seek: 0 };
+        if reader.bytes(4)? != b"MThd" {
+            return Err(GpError::FormatError("not a MIDI file".into()));
+        }
+        let header_length = reader.u32()? as usize;
+        let mut header = ByteReader {
+            data: reader.bytes(header_length)?,
+            seek: 0,
+        };
+        let format = header.u16()?;
+        let _track_count = header.u16()?;
+        let division = header.u16()?;
+        if format > 1 {
+            return Err(GpError::FormatError(format!(
+                "MIDI file format {} is not supported",
+                format
+            )));
+        }
+        if division == 0 || division & 0x8000 != 0 {
+            return Err(GpError::FormatError(
+                "SMPTE time division is not supported".into(),
+            ));
+        }
+
+        let mut file = MidiFile::default();
+        let mut chunk_index = 0;
+        while reader.remaining() >= 8 {
+            let kind = reader.bytes(4)?;
+            let length = reader.u32()? as usize;
+            let chunk = reader.bytes(length)?;
+            if kind == b"MTrk" {
+                file.read_track(chunk, i64::from(division), format == 1 && chunk_index == 0)?;
+                chunk_index += 1;
+            }
+        }
+        Ok(file)
+    }
+
+    fn read_track(&mut self, chunk: &[u8], division: i64, conductor: bool) -> GpResult<()> {
+        let mut reader = ByteReader {
+            data: chunk,
+            seek: 0,
+        };
+        let mut tick: i64 = 0;
+        let mut time = 0;
+        let mut running_status = None;
+        let mut name = String::new();
+        let mut parts: BTreeMap<u8, Vec<MidiNote>> = BTreeMap::new();
+        let mut sounding: HashMap<(u8, u8), Vec<(i64, u8)>> = HashMap::new();
+        while reader.remaining() > 0 {
+            tick += i64::from(reader.vlq()?);
+            time = (tick * DIVISION + division / 2) / division;
+            let mut status = reader.byte()?;
+            if status < 0x80 {
+                status = running_status.ok_or_else(|| {
+                    GpError::FormatError("MIDI data byte without a status".into())
+                })?;
+                reader.seek -= 1;
+            }
+            match status {
+                0xff => {
+                    running_status = None;
+                    let kind = reader.byte()?;
+                    let length = reader.vlq()? as usize;
+                    let payload = reader.bytes(length)?;
+                    let text = || String::from_utf8_lossy(payload).trim().to_string();
+                    match (kind, payload) {
+                        (0x03, _) => name = text(),
+                        (0x06, _) => {
+                            self.markers.insert(time, text());
+                        }
+                        (0x51, [a, b, c]) => {
+                            let micros = u32::from_be_bytes([0, *a, *b, *c]).max(1);
+                            let tempo = (60_000_000.0 / f64::from(micros)).round() as i32;
+                            self.tempos.insert(time, tempo);
+                        }
+                        (0x58, [numerator, denominator, ..]) => {
+                            let denominator = 1u16 << (*denominator).min(6);
+                            self.time_signatures
+                                .insert(time, ((*numerator).clamp(1, 127) as i8, denominator));
+                        }
+                        (0x59, [key, minor, ..]) => {
+                            self.keys.insert(time, (*key as i8, *minor == 1));
+                        }
+                        (0x2f, _) => break,
+                        _ => {}
+                    }
+                }
+                0xf0 | 0xf7 => {
+                    running_status = None;
+                    let length = reader.vlq()? as usize;
+                    reader.bytes(length)?;
+                }
+                0xf1..=0xfe => {
+                    return Err(GpError::InvalidValue {
+                        context: "MIDI event status",
+                        value: i64::from(status),
+                    })
+                }
+                _ => {
+                    running_status = Some(status);
+                    let channel = status & 0x0f;
+                    let data1 = reader.byte()?;
+                    let data2 = match status & 0xf0 {
+                        0xc0 | 0xd0 => 0,
+                        _ => reader.byte()?,
+                    };
+                    let settings = self.channels.entry(channel).or_default();
+                    match (status & 0xf0, data1) {
+                        (0x90, key) if data2 > 0 => {
+                            sounding
+                                .entry((channel, key))
+                                .or_default()
+                                .push((time, data2));
+                        }
+                        (0x80 | 0x90, key) => {
+                            let started = sounding.get_mut(&(channel, key));
+                            if let Some((start, velocity)) =
+                                started.filter(|s| !s.is_empty()).map(|s| s.remove(0))
+                            {
+                                parts.entry(channel).or_default().push(MidiNote {
+                                    start,
+                                    end: time,
+                                    key,
+                                    velocity,
+                                });
+                            }
+                        }
+                        (0xb0, 7) => {
+                            settings.volume.get_or_insert(data2);
+                        }
+                        (0xb0, 10) => {
+                            settings.pan.get_or_insert(data2);
+                        }
+                        (0xc0, program) => {
+                            settings.program.get_or_insert(program);
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+        // Notes still sounding at the end of the track
+        for ((channel, key), started) in sounding {
+            for (start, velocity) in started {
+                parts.entry(channel).or_default().push(MidiNote {
+                    start,
+                    end: time.max(start + 1),
+                    key,
+                    velocity,
+                });
+            }
+        }
+        if conductor && parts.is_empty() {
+            self.title = name;
+            return Ok(());
+        }
+        for (channel, mut notes) in parts {
+            notes.sort_by_key(|n| (n.start, std::cmp::Reverse(n.key)));
+            self.parts.push(MidiPart {
+                name: name.clone(),
+                channel,
+                notes,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Snap the notes of a part to the grid: each beat uses the straight or the triplet grid, the one
+/// closest to the note starts of the beat.
+fn quantize(notes: &mut [MidiNote]) {
+    let distance = |time: i64, grid: i64| {
+        let offset = time.rem_euclid(grid);
+        offset.min(grid - offset)
+    };
+    let mut errors: HashMap<i64, (i64, i64)> = HashMap::new();
+    for note in notes.iter() {
+        let error = errors.entry(note.start.div_euclid(DIVISION)).or_default();
+        error.0 += distance(note.start, STRAIGHT_GRID);
+        error.1 += distance(note.start, TRIPLET_GRID);
+    }
+    let grid = |time: i64| match errors.get(&time.div_euclid(DIVISION)) {
+        Some((straight, triplet)) if triplet < straight => TRIPLET_GRID,
+        _ => STRAIGHT_GRID,
+    };
+    let snap = |time: i64| {
+        let grid = grid(time);
+        (time + grid / 2).div_euclid(grid) * grid
+    };
+    for note in notes.iter_mut() {
+        note.start = snap(note.start).max(0);
+        note.end = snap(note.end).max(note.start + grid(note.start));
+    }
+}
+
+/// Durations filling `[start, end)`: whole values starting on a beat, or values cut at the beats.
+fn durations(start: i64, end: i64) -> Vec<Duration> {
+    let mut result = Vec::new();
+    let mut position = start;
+    while end - position >= STRAIGHT_GRID {
+        if position % DIVISION == 0 {
+            if let Some((ticks, value, dotted)) =
+                BEAT_DURATIONS.iter().find(|d| d.0 <= end - position)
+            {
+                result.push(Duration {
+                    value: *value,
+                    dotted: *dotted,
+                    ..Default::default()
+                });
+                position += ticks;
+                continue;
+            }
+        }
+        let limit = end.min((position / DIVISION + 1) * DIVISION) - position;
+        if position % STRAIGHT_GRID != 0 || limit % STRAIGHT_GRID != 0 {
+            let Some((ticks, value)) = TRIPLET_DURATIONS.iter().find(|d| d.0 <= limit) else {
+                break;
+            };
+            result.push(Duration {
+                value: *value,
+                tuplet_enters: 3,
+                tuplet_times: 2,
+                ..Default::default()
+            });
+            position += ticks;
+        } else {
+            let Some((ticks, value, dotted)) = STRAIGHT_DURATIONS.iter().find(|d| d.0 <= limit)
+            else {
+                break;
+            };
+            result.push(Duration {
+                value: *value,
+                dotted: *dotted,
+                ..Default::default()
+            });
+            position += ticks;
+        }
+    }
+    result
+}
+
+/// Time spans of a part: chords (indexes in `chords`) or rests.
+struct Span {
+    start: i64,
+    end: i64,
+    chord: Option<usize>,
+}
+
+/// Single voice of a part: every chord lasts until the next one, or until its longest note ends.
+fn part_spans(chords: &[(i64, i64)]) -> Vec<Span> {
+    let mut spans = Vec::with_capacity(chords.len() * 2);
+    let mut position = 0;
+    for (i, &(start, end)) in chords.iter().enumerate() {
+        if start > position {
+            spans.push(Span {
+                start: position,
+                end: start,
+                chord: None,
+            });
+        }
+        let next = chords.get(i + 1).map_or(i64::MAX, |c| c.0);
+        let end = end.min(next);
+        spans.push(Span {
+            start,
+            end,
+            chord: Some(i),
+        });
+        position = end;
+    }
+    spans
+}
+
+impl Song {
+    fn read_midi_file(&mut self, file: MidiFile, tuning: Option<(&[(i8, i8)], u8)>) {
+        let mut parts = file.parts;
+        for part in &mut parts {
+            quantize(&mut part.notes);
+        }
+        let end = parts
+            .iter()
+            .flat_map(|p| &p.notes)
+            .map(|n| n.end)
+            .max()
+            .unwrap_or(0);
+
+        // Measures follow the time signatures, a change is applied at the next measure
+        let mut headers: Vec<MeasureHeader> = Vec::new();
+        let mut starts: Vec<i64> = Vec::new();
+        let mut start = 0;
+        let mut tempo = file
+            .tempos
+            .range(..=0)
+            .next_back()
+            .map_or(DEFAULT_TEMPO, |t| *t.1);
+        self.tempo = tempo.clamp(1, i32::from(i16::MAX)) as i16;
+        while start < end || headers.is_empty() {
+            let (numerator, denominator) = file
+                .time_signatures
+                .range(..=start)
+                .next_back()
+                .map_or((4, 4), |t| *t.1);
+            let (key, is_minor) = file
+                .keys
+                .range(..=start)
+                .next_back()
+                .map_or((0, false), |k| *k.1);
+            let mut header = MeasureHeader {
+                number: (headers.len() + 1) as u16,
+                time_signature: TimeSignature {
+                    numerator,
+                    denominator: Duration {
+                        value: denominator,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                key_signature: KeySignature { key, is_minor },
+                ..Default::default()
+            };
+            let length = header.length().max(STRAIGHT_GRID);
+            if let Some((_, &change)) = file.tempos.range(start..start + length).next_back() {
+                if change != tempo && !headers.is_empty() {
+                    header.tempo = change;
+                }
+                tempo = change;
+            }
+            if let Some((_, title)) = file.markers.range(start..start + length).next() {
+                header.marker = Some(Marker {
+                    title: title.clone(),
+                    ..Default::default()
+                });
+            }
+            headers.push(header);
+            starts.push(start);
+            start += length;
+        }
+        starts.push(start);
+        self.key = headers[0].key_signature.clone();
+
+        self.channels = (0..64u8)
+            .map(|channel| MidiChannel {
+                channel,
+                effect_channel: channel,
+                ..Default::default()
+            })
+            .collect();
+        self.tracks.clear();
+        self.read_losses.clear();
+        for (t_idx, part) in parts.iter().enumerate() {
+            let settings = file
+                .channels
+                .get(&part.channel)
+                .copied()
+                .unwrap_or_default();
+            let percussion = part.channel == DEFAULT_PERCUSSION_CHANNEL;
+            let program = settings.program.unwrap_or(if percussion { 0 } else { 25 });
+            let mut track = Track {
+                number: (t_idx + 1) as i32,
+                name: match (&part.name, percussion) {
+                    (name, _) if !name.is_empty() => name.clone(),
+                    (_, true) => String::from("Drums"),
+                    _ => CHANNEL_DEFAULT_NAMES[usize::from(program.min(127))].to_string(),
+                },
+                channel_index: usize::from(part.channel),
+                percussion_track: percussion,
+                ..Default::default()
+            };
+            if percussion {
+                track.strings = (1..=6).map(|s| (s, 0)).collect();
+            } else if let Some((strings, fret_count)) = tuning {
+                track.strings = strings.to_vec();
+                track.fret_count = fret_count;
+            } else if (32..40).contains(&program) {
+                track.strings = BASS_TUNING.to_vec();
+            }
+            let (measures, losses) = Self::midi_measures(&track, t_idx, part, &headers, &starts);
+            track.measures = measures;
+            self.read_losses.extend(losses);
+
+            let channel = &mut self.channels[usize::from(part.channel)];
+            channel.set_instrument(i32::from(program));
+            if let Some(volume) = settings.volume {
+                channel.volume = volume as i8;
+            }
+            if let Some(pan) = settings.pan {
+                channel.balance = pan as i8;
+            }
+            self.tracks.push(track);
+        }
+        self.measure_headers = headers;
+        if !file.title.is_empty() {
+            self.name = file.title;
+        }
+        // Guitar Pro files always hold five lyrics lines
+        self.lyrics = Lyrics {
+            track_choice: 0,
+            lines: (0..5).map(|line| (line, 1, String::new())).collect(),
+        };
+    }
+
+    /// Measures of a part: its chords placed on the strings, then cut into measures and durations.
+    /// Also returns the notes that fit on no string, by measure.
+    fn midi_measures(
+        track: &Track,
+        t_idx: usize,
+        part: &MidiPart,
+        headers: &[MeasureHeader],
+        starts: &[i64],
+    ) -> (Vec<Measure>, Vec<LostItem>) {
+        let mut chords: Vec<(i64, i64)> = Vec::new();
+        let mut chord_notes: Vec<Vec<Note>> = Vec::new();
+        let mut hand = None;
+        let mut losses = Vec::new();
+        let mut index = 0;
+        while index < part.notes.len() {
+            let start = part.notes[index].start;
+            let count = part.notes[index..]
+                .iter()
+                .take_while(|n| n.start == start)
+                .count();
+            let mut notes: Vec<&MidiNote> = part.notes[index..index + count].iter().collect();
+            index += count;
+            // Notes repeated in a chord are played once
+            notes.dedup_by_key(|n| n.key);
+            let pitches: Vec<i32> = notes.iter().map(|n| i32::from(n.key)).collect();
+            let positions: Vec<Option<(i8, i16)>> = if track.percussion_track {
+                (0..pitches.len())
+                    .map(|i| (i < 6).then(|| ((i + 1) as i8, pitches[i] as i16)))
+                    .collect()
+            } else {
+                track.fret_positions(&pitches, hand)
+            };
+            let mut placed = Vec::with_capacity(notes.len());
+            for (note, position) in notes.iter().zip(positions) {
+                let Some((string, value)) = position else {
+                    losses.push(LostItem {
+                        feature: "note that fits on no string",
+                        position: LossPosition {
+                            track: Some(t_idx + 1),
+                            measure: Some(starts.partition_point(|s| *s <= start).max(1)),
+                            ..Default::default()
+                        },
+                    });
+                    continue;
+                };
+                placed.push(Note {
+                    value,
+                    string,
+                    velocity: i16::from(note.velocity),
+                    kind: NoteType::Normal,
+                    ..Default::default()
+                });
+            }
+            if !track.percussion_track {
+                if let Some(fret) = placed.iter().map(|n| n.value).filter(|f| *f > 0).min() {
+                    hand = Some(fret);
+                }
+            }
+            let end = notes.iter().map(|n| n.end).max().unwrap_or(start);
+            chords.push((start, end));
+            chord_notes.push(placed);
+        }
+        let spans = part_spans(&chords);
+        let mut span_index = 0;
+        let mut measures = Vec::with_capacity(headers.len());
+        for (m_idx, header) in headers.iter().enumerate() {
+            let (measure_start, measure_end) = (starts[m_idx], starts[m_idx + 1]);
+            let mut voice = Voice {
+                measure_index: m_idx as i16,
+                ..Default::default()
+            };
+            let mut position = measure_start;
+            while position < measure_end {
+                // Skip the spans that ended, a rest fills the time after the last one
+                while spans.get(span_index).is_some_and(|s| s.end <= position) {
+                    span_index += 1;
+                }
+                let (end, chord, tied) = match spans.get(span_index) {
+                    Some(span) if span.start <= position => {
+                        (span.end.min(measure_end), span.chord, span.start < position)
+                    }
+                    Some(span) => (span.start.min(measure_end), None, false),
+                    None => (measure_end, None, false),
+                };
+                for (i, duration) in durations(position - measure_start, end - measure_start)
+                    .into_iter()
+                    .enumerate()
+                {
+                    let notes: Vec<Note> = match chord {
+                        Some(chord) => chord_notes[chord]
+                            .iter()
+                            .map(|n| Note {
+                                kind: if tied || i > 0 {
+                                    NoteType::Tie
+                                } else {
+                                    NoteType::Normal
+                                },
+                                ..n.clone()
+                            })
+                            .collect(),
+                        None => Vec::new(),
+                    };
+                    voice.beats.push(Beat {
+                        status: if notes.is_empty() {
+                            BeatStatus::Rest
+                        } else {
+                            BeatStatus::Normal
+                        },
+                        duration,
+                        notes,
+                        ..Default::default()
+                    });
+                }
+                position = end;
+            }
+            let empty_voice = Voice {
+                measure_index: m_idx as i16,
+                beats: vec![Beat {
+                    status: BeatStatus::Empty,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            };
+            measures.push(Measure {
+                number: m_idx + 1,
+                track_index: t_idx,
+                header_index: m_idx,
+                key_signature: header.key_signature.clone(),
+                time_signature: header.time_signature.clone(),
+                voices: vec![voice, empty_voice],
+                ..Default::default()
+            });
+        }
+        (measures, losses)
+    }
+}
+
+impl SongMidiImportOps for Song {
+    fn read_midi(&mut self, data: &[u8]) -> GpResult<()> {
+        let file = MidiFile::parse(data)?;
+        self.read_midi_file(file, None);
+        Ok(())
+    }
+
+    fn read_midi_with_tuning(
+        &mut self,
+        data: &[u8],
+        strings: &[(i8, i8)],
+        fret_count: u8,
+    ) -> GpResult<()> {
+        if strings.is_empty() {
+            return Err(GpError::InvalidValue {
+                context: "tuning string count",
+                value: 0,
+            });
+        }
+        let file = MidiFile::parse(data)?;
+        self.read_midi_file(file, Some((strings, fret_count)));
+        Ok(())
+    }
+}
@@ -1 +1,3 @@
 pub mod midi;
+pub mod midi_export;
+pub mod midi_import;
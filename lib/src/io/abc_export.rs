@@ -0,0 +1,507 @@
+//! ABC notation export of a melody: one track, or one voice of a track, as an ABC tune.
+//!
+//! Notes are written at their sounding pitch, with the capo of the track. Accidentals follow the
+//! key signature and are repeated only when they change within a bar, as ABC readers carry them to
+//! the end of the bar. Repeats and alternate endings come from the measure headers and the lyrics
+//! of the track go into `w:` lines under every line of music.
+use std::collections::HashMap;
+
+use crate::error::{GpError, GpResult};
+use crate::io::musicxml_export::{
+    duration_ticks, ending_numbers, track_syllables, tuplet_brackets, Syllable,
+};
+use crate::io::notation::duration_from_ticks;
+use crate::model::{
+    beat::{Beat, Voice},
+    enums::*,
+    key_signature::{Duration, KeySignature, DURATION_QUARTER_TIME},
+    note::Note,
+    song::Song,
+    track::Track,
+};
+
+/// Unit note length of the tunes (`L:1/8`).
+const UNIT_TICKS: i64 = DURATION_QUARTER_TIME / 2;
+/// Bars on each line of music.
+const BARS_PER_LINE: usize = 4;
+
+/// Letters of the pitch classes spelled with sharps or with flats, and their alteration.
+const SHARP_SPELLING: [(char, i8); 12] = [
+    ('C', 0),
+    ('C', 1),
+    ('D', 0),
+    ('D', 1),
+    ('E', 0),
+    ('F', 0),
+    ('F', 1),
+    ('G', 0),
+    ('G', 1),
+    ('A', 0),
+    ('A', 1),
+    ('B', 0),
+];
+const FLAT_SPELLING: [(char, i8); 12] = [
+    ('C', 0),
+    ('D', -1),
+    ('D', 0),
+    ('E', -1),
+    ('E', 0),
+    ('F', 0),
+    ('G', -1),
+    ('G', 0),
+    ('A', -1),
+    ('A', 0),
+    ('B', -1),
+    ('B', 0),
+];
+/// Order of the sharps in the key signatures, the flats come in the reverse order.
+const SHARP_ORDER: [char; 7] = ['F', 'C', 'G', 'D', 'A', 'E', 'B'];
+/// Tonic of the keys from 7 flats to 7 sharps.
+const MAJOR_KEYS: [&str; 15] = [
+    "Cb", "Gb", "Db", "Ab", "Eb", "Bb", "F", "C", "G", "D", "A", "E", "B", "F#", "C#",
+];
+const MINOR_KEYS: [&str; 15] = [
+    "Abm", "Ebm", "Bbm", "Fm", "Cm", "Gm", "Dm", "Am", "Em", "Bm", "F#m", "C#m", "G#m", "D#m",
+    "A#m",
+];
+
+pub trait SongAbcExportOps {
+    /// Export a track as an ABC tune: every voice of the track, or only the chosen voice.
+    fn write_abc(&self, track: usize, voice: Option<usize>) -> GpResult<String>;
+}
+
+fn key_name(key: &KeySignature) -> &'static str {
+    let index = (key.key.clamp(-7, 7) + 7) as usize;
+    if key.is_minor {
+        MINOR_KEYS[index]
+    } else {
+        MAJOR_KEYS[index]
+    }
+}
+
+/// Alteration of a letter in a key signature.
+fn key_alteration(key: i8, letter: char) -> i8 {
+    let count = usize::from(key.unsigned_abs().min(7));
+    if key > 0 && SHARP_ORDER[..count].contains(&letter) {
+        1
+    } else if key < 0 && SHARP_ORDER[7 - count..].contains(&letter) {
+        -1
+    } else {
+        0
+    }
+}
+
+/// Length of a duration in units of `L:`, as written after a note (`2`, `/2`, `3/2`).
+fn length(duration: &Duration) -> String {
+    let mut ticks = DURATION_QUARTER_TIME * 4 / i64::from(duration.value.max(1));
+    if duration.double_dotted {
+        ticks = ticks * 7 / 4;
+    } else if duration.dotted {
+        ticks = ticks * 3 / 2;
+    }
+    let mut numerator = ticks;
+    let mut denominator = UNIT_TICKS;
+    let divisor = gcd(numerator, denominator);
+    numerator /= divisor;
+    denominator /= divisor;
+    match (numerator, denominator) {
+        (1, 1) => String::new(),
+        (n, 1) => n.to_string(),
+        (1, 2) => String::from("/"),
+        (1, d) => format!("/{}", d),
+        (n, d) => format!("{}/{}", n, d),
+    }
+}
+
+fn gcd(a: i64, b: i64) -> i64 {
+    if b == 0 {
+        a.max(1)
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+/// ABC bar line between two measures, `None` for the start or the end of the tune.
+fn bar_line(song: &Song, before: Option<usize>, after: Option<usize>) -> String {
+    let closes = before.is_some_and(|m| song.measure_headers[m].repeat_close > 0);
+    let opens = after.is_some_and(|m| song.measure_headers[m].repeat_open);
+    let double = before.is_some_and(|m| song.measure_headers[m].double_bar);
+    let mut bar = String::from(match (closes, opens, after.is_none()) {
+        (true, true, _) => ":|:",
+        (true, false, _) => ":|",
+        (false, true, _) => "|:",
+        (false, false, true) => "|]",
+        (false, false, false) if double => "||",
+        (false, false, false) => "|",
+    });
+    if let Some(alternative) = after
+        .map(|m| song.measure_headers[m].repeat_alternative)
+        .filter(|a| *a != 0)
+    {
+        bar.push_str(&format!("[{}", ending_numbers(alternative).0));
+    }
+    bar
+}
+
+struct AbcWriter<'a> {
+    song: &'a Song,
+    track: &'a Track,
+    v_idx: usize,
+    /// Syllables by (measure, beat of the first voice)
+    syllables: HashMap<(usize, usize), Vec<Syllable>>,
+    key: i8,
+    /// Accidentals written in the current bar, by letter and octave
+    accidentals: HashMap<(char, i32), i8>,
+}
+
+impl AbcWriter<'_> {
+    fn midi_pitch(&self, fret: i16, string: i8) -> i32 {
+        let tuning = self
+            .track
+            .strings
+            .iter()
+            .find(|s| s.0 == string)
+            .map_or(0, |s| i32::from(s.1));
+        tuning + i32::from(fret) + self.track.offset
+    }
+
+    /// A pitch with the accidental it needs in the bar: `C` is the middle C, `c` the octave above.
+    fn pitch(&mut self, midi: i32) -> String {
+        let midi = midi.clamp(0, 127);
+        let spelling = if self.key < 0 {
+            &FLAT_SPELLING
+        } else {
+            &SHARP_SPELLING
+        };
+        let (letter, alteration) = spelling[midi.rem_euclid(12) as usize];
+        let octave = midi.div_euclid(12) - 5;
+        let current = self
+            .accidentals
+            .get(&(letter, octave))
+            .copied()
+            .unwrap_or_else(|| key_alteration(self.key, letter));
+        let mut pitch = String::new();
+        if alteration != current {
+            pitch.push_str(match alteration {
+                1 => "^",
+                -1 => "_",
+                _ => "=",
+            });
+            self.accidentals.insert((letter, octave), alteration);
+        }
+        if octave >= 1 {
+            pitch.push(letter.to_ascii_lowercase());
+            pitch.push_str(&"'".repeat((octave - 1) as usize));
+        } else {
+            pitch.push(letter);
+            pitch.push_str(&",".repeat(octave.unsigned_abs() as usize));
+        }
+        pitch
+    }
+
+    /// The voice of a measure, filled with rests up to the length of the measure.
+    fn beats(&self, m_idx: usize) -> Vec<Beat> {
+        let header = &self.song.measure_headers[m_idx];
+        let empty = Voice::default();
+        let voice = self
+            .track
+            .measures
+            .get(m_idx)
+            .and_then(|m| m.voices.get(self.v_idx))
+            .unwrap_or(&empty);
+        let mut beats: Vec<Beat> = voice
+            .beats
+            .iter()
+            .filter(|b| b.status != BeatStatus::Empty)
+            .cloned()
+            .collect();
+        let mut gap = header.length()
+            - beats
+                .iter()
+                .map(|b| duration_ticks(&b.duration))
+                .sum::<i64>();
+        while gap >= DURATION_QUARTER_TIME / 32 {
+            let rest = Beat {
+                status: BeatStatus::Rest,
+                duration: duration_from_ticks(gap),
+                ..Default::default()
+            };
+            gap -= duration_ticks(&rest.duration);
+            beats.push(rest);
+        }
+        beats
+    }
+
+    /// Whether the note on a string is tied to a note of the next beat.
+    fn is_tied(&self, next: Option<&Beat>, string: i8) -> bool {
+        next.is_some_and(|b| {
+            b.notes
+                .iter()
+                .any(|n| n.string == string && n.kind == NoteType::Tie)
+        })
+    }
+
+    fn write_note(&mut self, note: &Note, tied: bool) -> String {
+        let mut text = self.pitch(self.midi_pitch(note.value, note.string));
+        if tied {
+            text.push('-');
+        }
+        text
+    }
+
+    /// A beat: its grace notes, its notes or a rest and its length.
+    fn write_beat(&mut self, beat: &Beat, next: Option<&Beat>) -> String {
+        let notes: Vec<&Note> = beat
+            .notes
+            .iter()
+            .filter(|n| n.kind != NoteType::Rest)
+            .collect();
+        if beat.status != BeatStatus::Normal || notes.is_empty() {
+            return format!("z{}", length(&beat.duration));
+        }
+        let mut text = String::new();
+        let graces: Vec<(i32, bool)> = notes
+            .iter()
+            .filter_map(|n| {
+                let grace = n.effect.grace.as_ref()?;
+                Some((
+                    self.midi_pitch(grace.fret.into(), n.string),
+                    grace.is_on_beat,
+                ))
+            })
+            .collect();
+        if !graces.is_empty() {
+            // Acciaccaturas are slashed, on-beat graces are appoggiaturas
+            text.push_str(if graces[0].1 { "{" } else { "{/" });
+            for (midi, _) in &graces {
+                text.push_str(&self.pitch(*midi));
+            }
+            text.push('}');
+        }
+        let written: Vec<String> = notes
+            .iter()
+            .map(|n| self.write_note(n, self.is_tied(next, n.string)))
+            .collect();
+        match written.as_slice() {
+            [note] => text.push_str(note),
+            _ => text.push_str(&format!("[{}]", written.concat())),
+        }
+        text.push_str(&length(&beat.duration));
+        text
+    }
+
+    /// A bar of music with its `w:` syllables, by lyrics line.
+    fn write_bar(&mut self, m_idx: usize) -> (String, Vec<(u8, String)>) {
+        self.accidentals.clear();
+        let beats = self.beats(m_idx);
+        let brackets = tuplet_brackets(&beats);
+        let next_bar = (m_idx + 1 < self.song.measure_headers.len())
+            .then(|| self.beats(m_idx + 1).into_iter().next())
+            .flatten();
+        let mut items: Vec<String> = Vec::new();
+        let mut words: Vec<(u8, String)> = Vec::new();
+        for (b_idx, beat) in beats.iter().enumerate() {
+            let next = beats.get(b_idx + 1).or(next_bar.as_ref());
+            let mut item = String::new();
+            if brackets[b_idx].0 {
+                let d = &beat.duration;
+                let count = beats[b_idx..]
+                    .iter()
+                    .zip(&brackets[b_idx..])
+                    .position(|(_, b)| b.1)
+                    .map_or(1, |p| p + 1);
+                item.push_str(&format!(
+                    "({}:{}:{}",
+                    d.tuplet_enters, d.tuplet_times, count
+                ));
+            }
+            item.push_str(&self.write_beat(beat, next));
+            items.push(item);
+
+            let sung = beat.status == BeatStatus::Normal
+                && beat.notes.iter().any(|n| n.kind != NoteType::Rest);
+            if self.v_idx == 0 && sung {
+                let syllables = self.syllables.get(&(m_idx, b_idx));
+                for line in 1..=5 {
+                    let syllable = syllables.and_then(|s| s.iter().find(|s| s.0 == line));
+                    let word = match syllable {
+                        Some((_, text, syllabic)) => {
+                            let text = text.replace(' ', "~");
+                            match *syllabic {
+                                "begin" | "middle" => format!("{}-", text),
+                                _ => text,
+                            }
+                        }
+                        None => String::from("*"),
+                    };
+                    words.push((line, word));
+                }
+            }
+        }
+        (items.join(" "), words)
+    }
+
+    /// Inline fields of a measure: time, key and tempo changes.
+    fn inline_fields(&mut self, m_idx: usize, tempo: &mut i32) -> String {
+        let Some(previous) = m_idx.checked_sub(1).map(|i| &self.song.measure_headers[i]) else {
+            return String::new();
+        };
+        let header = &self.song.measure_headers[m_idx];
+        let mut fields = String::new();
+        if header.time_signature != previous.time_signature {
+            let time = &header.time_signature;
+            fields.push_str(&format!(
+                "[M:{}/{}]",
+                time.numerator, time.denominator.value
+            ));
+        }
+        if header.key_signature != previous.key_signature {
+            fields.push_str(&format!("[K:{}]", key_name(&header.key_signature)));
+            self.key = header.key_signature.key;
+        }
+        if header.tempo > 0 && header.tempo != *tempo {
+            fields.push_str(&format!("[Q:1/4={}]", header.tempo));
+            *tempo = header.tempo;
+        }
+        fields
+    }
+
+    /// The lines of music of the voice, with the lyrics under them.
+    fn write_voice(&mut self) -> String {
+        let mut text = String::new();
+        let mut tempo = self.first_tempo();
+        let count = self.song.measure_headers.len();
+        for start in (0..count).step_by(BARS_PER_LINE) {
+            let mut line = String::new();
+            let mut words: Vec<(u8, String)> = Vec::new();
+            for m_idx in start..(start + BARS_PER_LINE).min(count) {
+                if m_idx == 0 {
+                    let bar = bar_line(self.song, None, Some(0));
+                    if bar != "|" {
+                        line.push_str(&bar);
+                    }
+                }
+                line.push_str(&self.inline_fields(m_idx, &mut tempo));
+                let (bar, bar_words) = self.write_bar(m_idx);
+                line.push_str(&bar);
+                words.extend(bar_words);
+                let next = Some(m_idx + 1).filter(|m| *m < count);
+                line.push_str(&format!(" {} ", bar_line(self.song, Some(m_idx), next)));
+            }
+            text.push_str(line.trim_end());
+            text.push('\n');
+            for lyrics_line in 1..=5 {
+                let line_words: Vec<&str> = words
+                    .iter()
+                    .filter(|w| w.0 == lyrics_line)
+                    .map(|w| w.1.as_str())
+                    .collect();
+                if line_words.iter().any(|w| *w != "*") {
+                    text.push_str(&format!("w:{}\n", line_words.join(" ")));
+                }
+            }
+        }
+        text
+    }
+
+    fn first_tempo(&self) -> i32 {
+        self.song
+            .measure_headers
+            .first()
+            .map(|h| h.tempo)
+            .filter(|t| *t > 0)
+            .unwrap_or(self.song.tempo.into())
+    }
+}
+
+impl SongAbcExportOps for Song {
+    fn write_abc(&self, track: usize, voice: Option<usize>) -> GpResult<String> {
+        let t_idx = track;
+        let track = self.tracks.get(t_idx).ok_or(GpError::InvalidValue {
+            context: "ABC export track index",
+            value: t_idx as i64,
+        })?;
+        if track.percussion_track {
+            return Err(GpError::FormatError(String::from(
+                "ABC export of a percussion track",
+            )));
+        }
+        let voice_count = track
+            .measures
+            .iter()
+            .map(|m| m.voices.len())
+            .max()
+            .unwrap_or(1);
+        let voices: Vec<usize> = match voice {
+            Some(v_idx) if v_idx < voice_count.max(1) => vec![v_idx],
+            Some(v_idx) => {
+                return Err(GpError::InvalidValue {
+                    context: "ABC export voice index",
+                    value: v_idx as i64,
+                })
+            }
+            // The voices holding notes
+            None => (0..voice_count)
+                .filter(|v| {
+                    *v == 0
+                        || track.measures.iter().any(|m| {
+                            m.voices.get(*v).is_some_and(|v| {
+                                v.beats.iter().any(|b| b.status == BeatStatus::Normal)
+                            })
+                        })
+                })
+                .collect(),
+        };
+
+        let header = self.measure_headers.first().cloned().unwrap_or_default();
+        let mut abc = String::from("X:1\n");
+        let title = if self.name.is_empty() {
+            &track.name
+        } else {
+            &self.name
+        };
+        abc.push_str(&format!("T:{}\n", title));
+        if !self.subtitle.is_empty() {
+            abc.push_str(&format!("T:{}\n", self.subtitle));
+        }
+        for composer in [&self.author, &self.artist]
+            .iter()
+            .filter(|c| !c.is_empty())
+        {
+            abc.push_str(&format!("C:{}\n", composer));
+        }
+        if !self.transcriber.is_empty() {
+            abc.push_str(&format!("Z:{}\n", self.transcriber));
+        }
+        let time = &header.time_signature;
+        abc.push_str(&format!(
+            "M:{}/{}\n",
+            time.numerator, time.denominator.value
+        ));
+        abc.push_str("L:1/8\n");
+        let mut writer = AbcWriter {
+            song: self,
+            track,
+            v_idx: voices[0],
+            syllables: track_syllables(self, t_idx),
+            key: header.key_signature.key,
+            accidentals: HashMap::new(),
+        };
+        abc.push_str(&format!("Q:1/4={}\n", writer.first_tempo()));
+        if voices.len() > 1 {
+            for v_idx in &voices {
+                abc.push_str(&format!("V:{}\n", v_idx + 1));
+            }
+        }
+        abc.push_str(&format!("K:{}\n", key_name(&header.key_signature)));
+        for v_idx in voices.iter().copied() {
+            if voices.len() > 1 {
+                abc.push_str(&format!("V:{}\n", v_idx + 1));
+            }
+            writer.v_idx = v_idx;
+            writer.key = header.key_signature.key;
+            abc.push_str(&writer.write_voice());
+        }
+        Ok(abc)
+    }
+}
@@ -0,0 +1,89 @@
+//! alphaTex format of alphaTab, shared by the reader and the writer.
+//!
+//! An alphaTex document starts with the metadata of the song (`\title "..."`), ended by a dot, then
+//! lists its tracks (`\track "Name"`) with their own metadata (`\tuning`, `\instrument`, `\chord`)
+//! and their bars separated by `|`. A beat is a note `fret.string`, a chord `(0.1 2.2)` or a rest
+//! `r`. Note effects are written in braces after the note and beat effects in braces after the
+//! duration of the beat (`3.3{h}.8{tu 3}`), and `:8` sets the duration of the following beats.
+use crate::model::enums::{MeasureClef, SlideType};
+
+/// Note names of the pitch classes in tunings.
+pub(crate) const TUNING_NAMES: [&str; 12] = [
+    "c", "c#", "d", "d#", "e", "f", "f#", "g", "g#", "a", "a#", "b",
+];
+/// Tonic of the keys from 7 flats to 7 sharps.
+pub(crate) const MAJOR_KEYS: [&str; 15] = [
+    "Cb", "Gb", "Db", "Ab", "Eb", "Bb", "F", "C", "G", "D", "A", "E", "B", "F#", "C#",
+];
+pub(crate) const MINOR_KEYS: [&str; 15] = [
+    "Ab", "Eb", "Bb", "F", "C", "G", "D", "A", "E", "B", "F#", "C#", "G#", "D#", "A#",
+];
+
+/// Slide effects and their alphaTex names.
+pub(crate) const SLIDES: [(SlideType, &str); 6] = [
+    (SlideType::ShiftSlideTo, "ss"),
+    (SlideType::LegatoSlideTo, "sl"),
+    (SlideType::IntoFromBelow, "sib"),
+    (SlideType::IntoFromAbove, "sia"),
+    (SlideType::OutUpWards, "sou"),
+    (SlideType::OutDownwards, "sod"),
+];
+
+/// Notes played in the time of the usual count of a tuplet (`tu 3` is a triplet: 3 in the time of
+/// 2).
+pub(crate) fn tuplet_times(enters: u8) -> u8 {
+    match enters {
+        3 => 2,
+        5..=7 => 4,
+        9..=13 => 8,
+        n => n.saturating_sub(1).max(1),
+    }
+}
+
+/// Tuning note: `e5` is the high string of a guitar (MIDI note 64).
+pub(crate) fn tuning_name(midi: i8) -> String {
+    let midi = midi.max(0);
+    format!("{}{}", TUNING_NAMES[(midi % 12) as usize], midi / 12)
+}
+
+pub(crate) fn parse_tuning_name(name: &str) -> Option<i8> {
+    let name = name.to_ascii_lowercase();
+    let split = name.find(|c: char| c.is_ascii_digit())?;
+    let (note, octave) = name.split_at(split);
+    let octave: i8 = octave.parse().ok()?;
+    let class = match note {
+        "cb" => -1,
+        "db" => 1,
+        "eb" => 3,
+        "fb" => 4,
+        "e#" => 5,
+        "gb" => 6,
+        "ab" => 8,
+        "bb" => 10,
+        "b#" => 12,
+        _ => TUNING_NAMES.iter().position(|n| *n == note)? as i8,
+    };
+    octave
+        .checked_mul(12)
+        .and_then(|o| o.checked_add(class))
+        .filter(|m| *m >= 0)
+}
+
+pub(crate) fn clef_name(clef: &MeasureClef) -> &'static str {
+    match clef {
+        MeasureClef::Treble => "G2",
+        MeasureClef::Bass => "F4",
+        MeasureClef::Tenor => "C4",
+        MeasureClef::Alto => "C3",
+    }
+}
+
+pub(crate) fn parse_clef(name: &str) -> Option<MeasureClef> {
+    match name.to_ascii_lowercase().as_str() {
+        "g2" | "treble" => Some(MeasureClef::Treble),
+        "f4" | "bass" => Some(MeasureClef::Bass),
+        "c4" | "tenor" => Some(MeasureClef::Tenor),
+        "c3" | "alto" => Some(MeasureClef::Alto),
+        _ => None,
+    }
+}
@@ -0,0 +1,453 @@
+//! alphaTex export, the text format of alphaTab.
+//!
+//! The master bar metadata (time and key signatures, tempos, repeats, sections) is written in the
+//! bars of the first track. The grace notes of a beat become a grace beat before it, and the chords
+//! of the beats are declared with `\chord` in their track.
+use crate::io::alphatex::*;
+use crate::io::musicxml_export::duration_ticks;
+use crate::io::notation::duration_from_ticks;
+use crate::model::{
+    beat::{Beat, Voice},
+    effects::BendEffect,
+    enums::*,
+    key_signature::DURATION_QUARTER_TIME,
+    note::Note,
+    song::Song,
+    track::Track,
+};
+
+pub trait SongAlphaTexExportOps {
+    /// Export the song as an alphaTex document.
+    fn write_alphatex(&self) -> String;
+}
+
+/// An alphaTex string, with its quotes.
+fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn bend_values(bend: &BendEffect) -> String {
+    let values: Vec<String> = bend.points.iter().map(|p| p.value.to_string()).collect();
+    format!("({})", values.join(" "))
+}
+
+fn note_effects(note: &Note) -> Vec<String> {
+    let effect = &note.effect;
+    let mut effects: Vec<String> = Vec::new();
+    let flags = [
+        (effect.hammer, "h"),
+        (effect.vibrato, "v"),
+        (effect.ghost_note, "g"),
+        (effect.accentuated_note, "ac"),
+        (effect.heavy_accentuated_note, "hac"),
+        (effect.palm_mute, "pm"),
+        (effect.staccato, "st"),
+        (effect.let_ring, "lr"),
+    ];
+    effects.extend(flags.iter().filter(|f| f.0).map(|f| f.1.to_string()));
+    if let Some(harmonic) = &effect.harmonic {
+        let name = match harmonic.kind {
+            HarmonicType::Natural => "nh",
+            HarmonicType::Artificial => "ah",
+            HarmonicType::Tapped => "th",
+            HarmonicType::Pinch => "ph",
+            HarmonicType::Semi => "sh",
+        };
+        // Guitar Pro needs the fret of a tapped harmonic, the note fret is used without one
+        let fret = match harmonic.kind {
+            HarmonicType::Tapped => harmonic.fret.or(Some(note.value as i8)),
+            _ => harmonic.fret,
+        };
+        effects.push(match fret {
+            Some(fret) => format!("{} {}", name, fret),
+            None => name.to_string(),
+        });
+    }
+    if let Some(bend) = &effect.bend {
+        effects.push(format!("b {}", bend_values(bend)));
+    }
+    for slide in &effect.slides {
+        effects.extend(
+            SLIDES
+                .iter()
+                .filter(|s| s.0 == *slide)
+                .map(|s| s.1.to_string()),
+        );
+    }
+    if let Some(trill) = &effect.trill {
+        effects.push(format!("tr {} {}", trill.fret, trill.duration.value));
+    }
+    if let Some(tremolo) = &effect.tremolo_picking {
+        effects.push(format!("tp {}", tremolo.duration.value));
+    }
+    effects
+}
+
+fn beat_effects(beat: &Beat) -> Vec<String> {
+    let duration = &beat.duration;
+    let mut effects: Vec<String> = Vec::new();
+    if duration.double_dotted {
+        effects.push(String::from("dd"));
+    } else if duration.dotted {
+        effects.push(String::from("d"));
+    }
+    if !duration.is_default_tuplet() && duration.tuplet_enters > 0 {
+        let enters = duration.tuplet_enters;
+        effects.push(if duration.tuplet_times == tuplet_times(enters) {
+            format!("tu {}", enters)
+        } else {
+            format!("tu {} {}", enters, duration.tuplet_times)
+        });
+    }
+    let effect = &beat.effect;
+    if effect.fade_in {
+        effects.push(String::from("f"));
+    }
+    if effect.vibrato {
+        effects.push(String::from("v"));
+    }
+    match effect.slap_effect {
+        SlapEffect::Tapping => effects.push(String::from("tt")),
+        SlapEffect::Slapping => effects.push(String::from("s")),
+        SlapEffect::Popping => effects.push(String::from("p")),
+        SlapEffect::None => {}
+    }
+    match effect.stroke.direction {
+        BeatStrokeDirection::Up => effects.push(String::from("su")),
+        BeatStrokeDirection::Down => effects.push(String::from("sd")),
+        BeatStrokeDirection::None => {}
+    }
+    match effect.pick_stroke {
+        BeatStrokeDirection::Up => effects.push(String::from("spu")),
+        BeatStrokeDirection::Down => effects.push(String::from("spd")),
+        BeatStrokeDirection::None => {}
+    }
+    if let Some(tremolo_bar) = &effect.tremolo_bar {
+        effects.push(format!("tb {}", bend_values(tremolo_bar)));
+    }
+    if let Some(chord) = effect.chord.as_ref().filter(|c| !c.name.is_empty()) {
+        effects.push(format!("ch {}", quote(&chord.name)));
+    }
+    if !beat.text.is_empty() {
+        effects.push(format!("txt {}", quote(&beat.text)));
+    }
+    let tempo = effect
+        .mix_table_change
+        .as_ref()
+        .and_then(|m| m.tempo.as_ref())
+        .filter(|t| t.value > 0);
+    if let Some(tempo) = tempo {
+        effects.push(format!("tempo {}", tempo.value));
+    }
+    effects
+}
+
+struct TrackWriter<'a> {
+    song: &'a Song,
+    track: &'a Track,
+    /// The first track carries the metadata of the master bars
+    first_track: bool,
+    /// Duration value of the beats without their own duration
+    duration: u16,
+    tempo: i32,
+}
+
+impl TrackWriter<'_> {
+    /// `fret.string`, only the value of the note in percussion tracks.
+    fn note_position(&self, fret: String, string: i8) -> String {
+        if self.track.percussion_track {
+            fret
+        } else {
+            format!("{}.{}", fret, string)
+        }
+    }
+
+    /// A note with its effects.
+    fn note(&self, note: &Note) -> String {
+        let fret = match note.kind {
+            NoteType::Dead => String::from("x"),
+            NoteType::Tie => String::from("-"),
+            _ => note.value.to_string(),
+        };
+        let position = self.note_position(fret, note.string);
+        let effects = note_effects(note);
+        if effects.is_empty() {
+            position
+        } else {
+            format!("{}{{{}}}", position, effects.join(" "))
+        }
+    }
+
+    /// Bars of a voice, one per line.
+    fn write_voice(&mut self, v_idx: usize) -> String {
+        let mut bars = String::new();
+        self.duration = 0;
+        let mut clef = None;
+        for (m_idx, header) in self.song.measure_headers.iter().enumerate() {
+            let measure = self.track.measures.get(m_idx);
+            let mut items: Vec<String> = Vec::new();
+            if v_idx == 0 {
+                if self.first_track {
+                    items.extend(self.master_bar(m_idx));
+                }
+                let measure_clef = clef_name(measure.map_or(&MeasureClef::Treble, |m| &m.clef));
+                if clef != Some(measure_clef) {
+                    if clef.is_some() || measure_clef != "G2" {
+                        items.push(format!("\\clef {}", measure_clef));
+                    }
+                    clef = Some(measure_clef);
+                }
+            }
+            let empty = Voice::default();
+            let voice = measure.and_then(|m| m.voices.get(v_idx)).unwrap_or(&empty);
+            let mut length = 0;
+            for beat in voice.beats.iter().filter(|b| b.status != BeatStatus::Empty) {
+                items.extend(self.write_beat(beat));
+                length += duration_ticks(&beat.duration);
+            }
+            // Bars are filled with rests
+            let mut gap = header.length() - length;
+            while gap >= DURATION_QUARTER_TIME / 32 {
+                let rest = Beat {
+                    status: BeatStatus::Rest,
+                    duration: duration_from_ticks(gap),
+                    ..Default::default()
+                };
+                gap -= duration_ticks(&rest.duration);
+                items.extend(self.write_beat(&rest));
+            }
+            bars.push_str(&format!("  {} |\n", items.join(" ")));
+        }
+        bars
+    }
+
+    /// Metadata of a master bar, written at the start of the bar.
+    fn master_bar(&mut self, m_idx: usize) -> Vec<String> {
+        let headers = &self.song.measure_headers;
+        let header = &headers[m_idx];
+        let previous = m_idx.checked_sub(1).map(|i| &headers[i]);
+        let mut items = Vec::new();
+        let time_signature = &header.time_signature;
+        if previous.is_none_or(|p| p.time_signature != header.time_signature) {
+            items.push(format!(
+                "\\ts {} {}",
+                time_signature.numerator, time_signature.denominator.value
+            ));
+        }
+        let key = &header.key_signature;
+        let default_key = key.key == 0 && !key.is_minor;
+        if previous.map_or(!default_key, |p| p.key_signature != *key) {
+            let index = (key.key.clamp(-7, 7) + 7) as usize;
+            items.push(if key.is_minor {
+                format!("\\ks {}minor", MINOR_KEYS[index])
+            } else {
+                format!("\\ks {}", MAJOR_KEYS[index])
+            });
+        }
+        if previous.is_some_and(|p| p.triplet_feel != header.triplet_feel)
+            || previous.is_none() && header.triplet_feel != TripletFeel::None
+        {
+            items.push(format!(
+                "\\tf {}",
+                match header.triplet_feel {
+                    TripletFeel::None => "none",
+                    TripletFeel::Eighth => "triplet-8th",
+                    TripletFeel::Sixteenth => "triplet-16th",
+                }
+            ));
+        }
+        if header.tempo > 0 && header.tempo != self.tempo {
+            items.push(format!("\\tempo {}", header.tempo));
+            self.tempo = header.tempo;
+        }
+        if header.repeat_open {
+            items.push(String::from("\\ro"));
+        }
+        if header.repeat_close > 0 {
+            items.push(format!("\\rc {}", header.repeat_close));
+        }
+        if header.repeat_alternative != 0 {
+            let endings: Vec<String> = (0..8)
+                .filter(|i| header.repeat_alternative & (1 << i) != 0)
+                .map(|i| (i + 1).to_string())
+                .collect();
+            items.push(format!("\\ae ({})", endings.join(" ")));
+        }
+        if let Some(marker) = &header.marker {
+            items.push(format!("\\section {}", quote(marker.title.trim())));
+        }
+        items
+    }
+
+    /// A beat: its grace beat, the duration when it changes, its notes or a rest, its effects.
+    fn write_beat(&mut self, beat: &Beat) -> Vec<String> {
+        let mut items = Vec::new();
+        let notes: Vec<&Note> = beat
+            .notes
+            .iter()
+            .filter(|n| n.kind != NoteType::Rest)
+            .collect();
+        let is_rest = beat.status != BeatStatus::Normal || notes.is_empty();
+        let graces: Vec<String> = notes
+            .iter()
+            .filter_map(|n| {
+                let grace = n.effect.grace.as_ref()?;
+                let fret = if grace.is_dead {
+                    String::from("x")
+                } else {
+                    grace.fret.to_string()
+                };
+                Some(self.note_position(fret, n.string))
+            })
+            .collect();
+        if !is_rest && !graces.is_empty() {
+            let grace = notes.iter().find_map(|n| n.effect.grace.as_ref()).unwrap();
+            let value = u16::from(grace.duration.max(1));
+            let on_beat = if grace.is_on_beat { " ob" } else { "" };
+            items.push(format!("{}.{}{{gr{}}}", group(&graces), value, on_beat));
+            self.duration = value;
+        }
+
+        let content = if is_rest {
+            String::from("r")
+        } else {
+            let notes: Vec<String> = notes.iter().map(|n| self.note(n)).collect();
+            group(&notes)
+        };
+        let effects = beat_effects(beat);
+        let value = beat.duration.value;
+        if effects.is_empty() {
+            if value != self.duration {
+                items.push(format!(":{}", value));
+                self.duration = value;
+            }
+            items.push(content);
+        } else {
+            items.push(format!("{}.{}{{{}}}", content, value, effects.join(" ")));
+            self.duration = value;
+        }
+        items
+    }
+}
+
+/// Several notes between parentheses, a single note as it is.
+fn group(notes: &[String]) -> String {
+    match notes {
+        [note] => note.clone(),
+        _ => format!("({})", notes.join(" ")),
+    }
+}
+
+impl Song {
+    fn alphatex_metadata(&self) -> String {
+        let fields = [
+            ("title", &self.name),
+            ("subtitle", &self.subtitle),
+            ("artist", &self.artist),
+            ("album", &self.album),
+            ("words", &self.words),
+            ("music", &self.author),
+            ("copyright", &self.copyright),
+            ("tab", &self.transcriber),
+            ("instructions", &self.instructions),
+        ];
+        let mut metadata = String::new();
+        for (name, value) in fields.iter().filter(|f| !f.1.is_empty()) {
+            metadata.push_str(&format!("\\{} {}\n", name, quote(value)));
+        }
+        if self.notice.iter().any(|n| !n.trim().is_empty()) {
+            metadata.push_str(&format!("\\notices {}\n", quote(&self.notice.join("\n"))));
+        }
+        metadata.push_str(&format!("\\tempo {}\n.\n", self.tempo));
+        metadata
+    }
+
+    /// A track with its metadata, its chords and its voices.
+    fn alphatex_track(&self, t_idx: usize) -> String {
+        let track = &self.tracks[t_idx];
+        let mut text = format!("\\track {}", quote(&track.name));
+        if !track.short_name.is_empty() {
+            text.push_str(&format!(" {}", quote(&track.short_name)));
+        }
+        text.push('\n');
+        let channel = self.channels.get(track.channel_index);
+        if track.percussion_track {
+            text.push_str("  \\staff {score}\n  \\instrument percussion\n");
+        } else {
+            text.push_str("  \\staff {score tabs}\n");
+            let tuning: Vec<String> = track.strings.iter().map(|s| tuning_name(s.1)).collect();
+            text.push_str(&format!("  \\tuning {}\n", tuning.join(" ")));
+            let program = channel.map_or(25, |c| c.instrument.clamp(0, 127));
+            text.push_str(&format!("  \\instrument {}\n", program));
+            if track.offset > 0 {
+                text.push_str(&format!("  \\capo {}\n", track.offset));
+            }
+        }
+
+        // Chords of the beats, declared once
+        let mut chords: Vec<(&str, &Vec<i8>)> = Vec::new();
+        let beats = track
+            .measures
+            .iter()
+            .flat_map(|m| &m.voices)
+            .flat_map(|v| &v.beats);
+        for chord in beats.filter_map(|b| b.effect.chord.as_ref()) {
+            if !chord.name.is_empty() && chords.iter().all(|c| c.0 != chord.name) {
+                chords.push((&chord.name, &chord.strings));
+            }
+        }
+        for (name, strings) in chords {
+            let frets: Vec<String> = strings
+                .iter()
+                .take(track.strings.len())
+                .map(|f| match f {
+                    f if *f < 0 => String::from("x"),
+                    f => f.to_string(),
+                })
+                .collect();
+            text.push_str(&format!("  \\chord {} {}\n", quote(name), frets.join(" ")));
+        }
+
+        if usize::from(self.lyrics.track_choice) == t_idx + 1 {
+            let lines = self.lyrics.lines.iter().filter(|l| !l.2.trim().is_empty());
+            for (_, from, lyrics) in lines {
+                match from {
+                    0 | 1 => text.push_str(&format!("  \\lyrics {}\n", quote(lyrics))),
+                    _ => text.push_str(&format!("  \\lyrics {} {}\n", from, quote(lyrics))),
+                }
+            }
+        }
+
+        let mut writer = TrackWriter {
+            song: self,
+            track,
+            first_track: t_idx == 0,
+            duration: 0,
+            tempo: self.tempo.into(),
+        };
+        let second_voice = track.measures.iter().any(|m| {
+            m.voices
+                .get(1)
+                .is_some_and(|v| v.beats.iter().any(|b| b.status != BeatStatus::Empty))
+        });
+        if second_voice {
+            text.push_str("  \\voice\n");
+            text.push_str(&writer.write_voice(0));
+            text.push_str("  \\voice\n");
+            text.push_str(&writer.write_voice(1));
+        } else {
+            text.push_str(&writer.write_voice(0));
+        }
+        text
+    }
+}
+
+impl SongAlphaTexExportOps for Song {
+    fn write_alphatex(&self) -> String {
+        let mut text = self.alphatex_metadata();
+        for t_idx in 0..self.tracks.len() {
+            text.push_str(&self.alphatex_track(t_idx));
+        }
+        text
+    }
+}
@@ -0,0 +1,1024 @@
+//! alphaTex import, the text format of alphaTab.
+//!
+//! The metadata of the bars (`\ts`, `\ks`, `\ro`, `\rc`, `\ae`, `\section`, `\tempo`, `\tf`) may be
+//! written in any track: the bars of every track share the measure headers. Grace beats (`{gr}`)
+//! take no time, their notes become the grace notes of the notes of the following beat.
+use crate::audio::midi::{MidiChannel, DEFAULT_PERCUSSION_CHANNEL};
+use crate::error::{GpError, GpResult};
+use crate::io::alphatex::*;
+use crate::io::notation::{bend_from_values, duration_from_ticks};
+use crate::model::{
+    beat::{Beat, Voice},
+    chord::Chord,
+    effects::{
+        BendEffect, BendPoint, GraceEffect, HarmonicEffect, TremoloPickingEffect, TrillEffect,
+        BEND_EFFECT_MAX_POSITION,
+    },
+    enums::*,
+    headers::{Marker, MeasureHeader},
+    key_signature::*,
+    lyric::Lyrics,
+    measure::Measure,
+    mix_table::{MixTableChange, MixTableItem},
+    note::Note,
+    song::Song,
+    track::Track,
+};
+
+const DEFAULT_FRET_COUNT: u8 = 24;
+/// Standard tuning of a guitar, highest string first.
+const GUITAR_TUNING: [i8; 6] = [64, 59, 55, 50, 45, 40];
+const GUITAR_PROGRAM: i32 = 25;
+
+pub trait SongAlphaTexOps {
+    /// Read an alphaTex document, the text format of alphaTab.
+    fn read_alphatex(&mut self, text: &str) -> GpResult<()>;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    /// Metadata tag, `\title`
+    Meta(String),
+    Str(String),
+    Number(i64),
+    /// Effect names, tunings, `x` and `r`
+    Ident(String),
+    Symbol(char),
+}
+
+/// Tokens of a document with their lines, without the comments.
+fn tokenize(text: &str) -> GpResult<Vec<(Token, usize)>> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut line = 1;
+    let mut idx = 0;
+    let is_word = |c: char| c.is_alphanumeric() || matches!(c, '#' | '_' | '-');
+    while idx < chars.len() {
+        let c = chars[idx];
+        match c {
+            '\n' => {
+                line += 1;
+                idx += 1;
+            }
+            c if c.is_whitespace() => idx += 1,
+            '/' if chars.get(idx + 1) == Some(&'/') => {
+                while idx < chars.len() && chars[idx] != '\n' {
+                    idx += 1;
+                }
+            }
+            '/' if chars.get(idx + 1) == Some(&'*') => {
+                idx += 2;
+                while idx < chars.len() && !(chars[idx] == '*' && chars.get(idx + 1) == Some(&'/'))
+                {
+                    line += usize::from(chars[idx] == '\n');
+                    idx += 1;
+                }
+                idx += 2;
+            }
+            '"' | '\'' => {
+                let mut value = String::new();
+                idx += 1;
+                while idx < chars.len() && chars[idx] != c {
+                    if chars[idx] == '\\' && idx + 1 < chars.len() {
+                        idx += 1;
+                    }
+                    line += usize::from(chars[idx] == '\n');
+                    value.push(chars[idx]);
+                    idx += 1;
+                }
+                if idx >= chars.len() {
+                    return Err(GpError::FormatError(format!(
+                        "alphaTex string not closed at line {}",
+                        line
+                    )));
+                }
+                idx += 1;
+                tokens.push((Token::Str(value), line));
+            }
+            '\\' => {
+                let start = idx + 1;
+                idx = start;
+                while idx < chars.len() && is_word(chars[idx]) {
+                    idx += 1;
+                }
+                let name: String = chars[start..idx].iter().collect();
+                tokens.push((Token::Meta(name.to_ascii_lowercase()), line));
+            }
+            c if c.is_ascii_digit()
+                || c == '-' && chars.get(idx + 1).is_some_and(char::is_ascii_digit) =>
+            {
+                let start = idx;
+                idx += 1;
+                while idx < chars.len() && chars[idx].is_ascii_digit() {
+                    idx += 1;
+                }
+                let number: String = chars[start..idx].iter().collect();
+                let value = number.parse().map_err(|_| {
+                    GpError::FormatError(format!("alphaTex number out of range at line {}", line))
+                })?;
+                tokens.push((Token::Number(value), line));
+            }
+            '.' | ':' | '|' | '(' | ')' | '{' | '}' | '*' | '-' => {
+                tokens.push((Token::Symbol(c), line));
+                idx += 1;
+            }
+            c if is_word(c) => {
+                let start = idx;
+                while idx < chars.len() && is_word(chars[idx]) {
+                    idx += 1;
+                }
+                let word: String = chars[start..idx].iter().collect();
+                tokens.push((Token::Ident(word.to_ascii_lowercase()), line));
+            }
+            c => {
+                return Err(GpError::FormatError(format!(
+                    "alphaTex unexpected character '{}' at line {}",
+                    c, line
+                )))
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// Evenly spaced points of a bend or of a tremolo bar.
+fn points_effect(values: Vec<i8>, tremolo_bar: bool) -> Option<BendEffect> {
+    if !tremolo_bar {
+        return bend_from_values(values);
+    }
+    if values.iter().all(|v| *v == 0) {
+        return None;
+    }
+    let last_index = (values.len() - 1).max(1);
+    let last = values.last().copied().unwrap_or(0);
+    Some(BendEffect {
+        kind: match values.iter().copied().min() {
+            Some(min) if min < 0 && last == 0 => BendType::Dip,
+            Some(min) if min < 0 => BendType::Dive,
+            _ => BendType::ReleaseUp,
+        },
+        points: values
+            .iter()
+            .enumerate()
+            .map(|(i, value)| BendPoint {
+                position: (i * usize::from(BEND_EFFECT_MAX_POSITION) / last_index) as u8,
+                value: *value,
+                vibrato: false,
+            })
+            .collect(),
+        ..Default::default()
+    })
+}
+
+/// A track being read, with its bars for each voice.
+#[derive(Default)]
+struct TexTrack {
+    track: Track,
+    program: Option<i32>,
+    chords: Vec<(String, Vec<i8>)>,
+    voices: Vec<Vec<Voice>>,
+    clefs: Vec<MeasureClef>,
+    lyrics: Vec<(u16, String)>,
+}
+
+/// Effects of a beat, or of the grace beat before it.
+#[derive(Default)]
+struct BeatEffects {
+    beat: Beat,
+    grace: Option<bool>,
+}
+
+struct TexReader {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    headers: Vec<MeasureHeader>,
+    tracks: Vec<TexTrack>,
+    voice: usize,
+    bar: usize,
+    duration: Duration,
+    /// Notes of the grace beat, waiting for the following beat
+    graces: Vec<(i8, GraceEffect)>,
+}
+
+impl TexReader {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|t| &t.0)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).map(|t| t.0.clone());
+        self.pos += 1;
+        token
+    }
+
+    fn error<T>(&self, message: &str) -> GpResult<T> {
+        let line = self
+            .tokens
+            .get(self.pos.min(self.tokens.len().saturating_sub(1)))
+            .map_or(1, |t| t.1);
+        Err(GpError::FormatError(format!(
+            "alphaTex {} at line {}",
+            message, line
+        )))
+    }
+
+    fn eat(&mut self, symbol: char) -> bool {
+        if self.peek() == Some(&Token::Symbol(symbol)) {
+            self.pos += 1;
+            return true;
+        }
+        false
+    }
+
+    fn expect(&mut self, symbol: char) -> GpResult<()> {
+        if !self.eat(symbol) {
+            return self.error(&format!("'{}' expected", symbol));
+        }
+        Ok(())
+    }
+
+    fn number(&mut self) -> GpResult<i64> {
+        match self.next() {
+            Some(Token::Number(n)) => Ok(n),
+            _ => {
+                self.pos -= 1;
+                self.error("number expected")
+            }
+        }
+    }
+
+    fn optional_number(&mut self) -> Option<i64> {
+        match self.peek() {
+            Some(Token::Number(n)) => {
+                let n = *n;
+                self.pos += 1;
+                Some(n)
+            }
+            _ => None,
+        }
+    }
+
+    fn string(&mut self) -> GpResult<String> {
+        match self.next() {
+            Some(Token::Str(s)) | Some(Token::Ident(s)) => Ok(s),
+            Some(Token::Number(n)) => Ok(n.to_string()),
+            _ => {
+                self.pos -= 1;
+                self.error("text expected")
+            }
+        }
+    }
+
+    fn optional_string(&mut self) -> Option<String> {
+        match self.peek() {
+            Some(Token::Str(s)) => {
+                let s = s.clone();
+                self.pos += 1;
+                Some(s)
+            }
+            _ => None,
+        }
+    }
+
+    /// Values between parentheses, or a single value.
+    fn values(&mut self) -> GpResult<Vec<i64>> {
+        if !self.eat('(') {
+            return Ok(vec![self.number()?]);
+        }
+        let mut values = Vec::new();
+        while !self.eat(')') {
+            values.push(self.number()?);
+        }
+        Ok(values)
+    }
+
+    /// Arguments of an unknown tag or effect.
+    fn skip_arguments(&mut self) {
+        loop {
+            match self.peek() {
+                Some(Token::Str(_)) | Some(Token::Number(_)) => self.pos += 1,
+                Some(Token::Symbol('(')) => {
+                    while !matches!(self.next(), Some(Token::Symbol(')')) | None) {}
+                }
+                _ => break,
+            }
+        }
+    }
+
+    fn track(&mut self) -> &mut TexTrack {
+        if self.tracks.is_empty() {
+            self.new_track(String::from("Track 1"));
+        }
+        self.tracks.last_mut().unwrap()
+    }
+
+    fn new_track(&mut self, name: String) {
+        let track = Track {
+            number: (self.tracks.len() + 1) as i32,
+            name,
+            strings: GUITAR_TUNING
+                .iter()
+                .enumerate()
+                .map(|(s_idx, note)| ((s_idx + 1) as i8, *note))
+                .collect(),
+            fret_count: DEFAULT_FRET_COUNT,
+            ..Default::default()
+        };
+        self.tracks.push(TexTrack {
+            track,
+            voices: vec![Vec::new()],
+            ..Default::default()
+        });
+        self.voice = 0;
+        self.bar = 0;
+        self.duration = Duration::default();
+    }
+
+    /// Header of the current bar, created with the signatures of the previous one.
+    fn header(&mut self) -> &mut MeasureHeader {
+        while self.headers.len() <= self.bar {
+            let header = match self.headers.last() {
+                Some(previous) => MeasureHeader {
+                    time_signature: previous.time_signature.clone(),
+                    key_signature: previous.key_signature.clone(),
+                    triplet_feel: previous.triplet_feel.clone(),
+                    ..Default::default()
+                },
+                None => MeasureHeader::default(),
+            };
+            self.headers.push(MeasureHeader {
+                number: (self.headers.len() + 1) as u16,
+                ..header
+            });
+        }
+        &mut self.headers[self.bar]
+    }
+
+    /// Voice of the current bar in the current track.
+    fn voice(&mut self) -> &mut Voice {
+        let (bar, v_idx) = (self.bar, self.voice);
+        let track = self.track();
+        while track.voices.len() <= v_idx {
+            track.voices.push(Vec::new());
+        }
+        let voices = &mut track.voices[v_idx];
+        while voices.len() <= bar {
+            voices.push(Voice {
+                measure_index: voices.len() as i16,
+                ..Default::default()
+            });
+        }
+        &mut voices[bar]
+    }
+
+    /// Song metadata, read until the dot or the first track.
+    fn read_song_metadata(&mut self, song: &mut Song) -> GpResult<()> {
+        while let Some(Token::Meta(name)) = self.peek().cloned() {
+            let field = match name.as_str() {
+                "title" => &mut song.name,
+                "subtitle" => &mut song.subtitle,
+                "artist" => &mut song.artist,
+                "album" => &mut song.album,
+                "words" => &mut song.words,
+                "music" => &mut song.author,
+                "copyright" => &mut song.copyright,
+                "tab" => &mut song.transcriber,
+                "instructions" => &mut song.instructions,
+                "wordsandmusic" => {
+                    self.pos += 1;
+                    song.words = self.string()?;
+                    song.author = song.words.clone();
+                    continue;
+                }
+                "notices" => {
+                    self.pos += 1;
+                    song.notice = self.string()?.lines().map(String::from).collect();
+                    continue;
+                }
+                "tempo" => {
+                    self.pos += 1;
+                    song.tempo = self.number()?.clamp(1, 999) as i16;
+                    continue;
+                }
+                _ => break,
+            };
+            self.pos += 1;
+            *field = self.string()?;
+        }
+        self.eat('.');
+        Ok(())
+    }
+
+    /// Metadata of a track or of a bar.
+    fn read_metadata(&mut self, name: &str) -> GpResult<()> {
+        match name {
+            "track" => {
+                let name = self.optional_string();
+                let index = self.tracks.len() + 1;
+                self.new_track(name.unwrap_or_else(|| format!("Track {}", index)));
+                if let Some(short_name) = self.optional_string() {
+                    self.track().track.short_name = short_name;
+                }
+            }
+            "staff" => {
+                if self.eat('{') {
+                    while !self.eat('}') {
+                        if self.next().is_none() {
+                            return self.error("'}' expected");
+                        }
+                    }
+                }
+            }
+            "tuning" => {
+                let mut tuning = Vec::new();
+                while let Some(Token::Ident(note)) = self.peek().cloned() {
+                    match parse_tuning_name(&note) {
+                        Some(midi) => tuning.push(midi),
+                        None if note == "piano" || note == "none" => {}
+                        None => return self.error(&format!("unknown tuning '{}'", note)),
+                    }
+                    self.pos += 1;
+                }
+                if !tuning.is_empty() {
+                    self.track().track.strings = tuning
+                        .iter()
+                        .enumerate()
+                        .map(|(s_idx, note)| ((s_idx + 1) as i8, *note))
+                        .collect();
+                }
+            }
+            "instrument" => match self.next() {
+                Some(Token::Number(program)) => {
+                    self.track().program = Some(program.clamp(0, 127) as i32)
+                }
+                Some(Token::Ident(name)) | Some(Token::Str(name)) => {
+                    if name.eq_ignore_ascii_case("percussion") {
+                        self.track().track.percussion_track = true;
+                    }
+                }
+                _ => return self.error("instrument expected"),
+            },
+            "capo" => {
+                let capo = self.number()?;
+                self.track().track.offset = capo.clamp(0, 24) as i32;
+            }
+            "chord" => {
+                let name = self.string()?;
+                let mut frets = Vec::new();
+                loop {
+                    match self.peek() {
+                        Some(Token::Number(n)) => frets.push((*n).clamp(-1, 99) as i8),
+                        Some(Token::Ident(x)) if x == "x" => frets.push(-1),
+                        Some(Token::Symbol('-')) => frets.push(-1),
+                        _ => break,
+                    }
+                    self.pos += 1;
+                }
+                self.track().chords.push((name, frets));
+            }
+            "lyrics" => {
+                let from = self.optional_number().unwrap_or(1).clamp(1, 9999) as u16;
+                let text = self.string()?;
+                self.track().lyrics.push((from, text));
+            }
+            "voice" => {
+                let started = self
+                    .tracks
+                    .last()
+                    .is_some_and(|t| t.voices.iter().any(|v| !v.is_empty()));
+                if started {
+                    self.voice += 1;
+                }
+                self.bar = 0;
+                self.duration = Duration::default();
+            }
+            "ts" => {
+                let numerator = self.number()?.clamp(1, 32) as i8;
+                let denominator = self.number()?;
+                let value = [1u16, 2, 4, 8, 16, 32]
+                    .into_iter()
+                    .find(|v| i64::from(*v) == denominator)
+                    .unwrap_or(4);
+                let header = self.header();
+                header.time_signature.numerator = numerator;
+                header.time_signature.denominator.value = value;
+            }
+            "ks" => {
+                let name = self.string()?;
+                let lower = name.to_ascii_lowercase();
+                let (tonic, is_minor) = match lower.strip_suffix("minor") {
+                    Some(tonic) => (tonic, true),
+                    None => (lower.strip_suffix("major").unwrap_or(&lower), false),
+                };
+                let keys = if is_minor { &MINOR_KEYS } else { &MAJOR_KEYS };
+                let Some(index) = keys.iter().position(|k| k.to_ascii_lowercase() == tonic) else {
+                    return self.error(&format!("unknown key '{}'", name));
+                };
+                let header = self.header();
+                header.key_signature.key = index as i8 - 7;
+                header.key_signature.is_minor = is_minor;
+            }
+            "ro" => self.header().repeat_open = true,
+            "rc" => {
+                let count = self.number()?.clamp(1, 127) as i8;
+                self.header().repeat_close = count;
+            }
+            "ae" => {
+                let endings = self.values()?;
+                let mask = endings
+                    .iter()
+                    .filter(|e| (1..=8).contains(*e))
+                    .fold(0u8, |mask, e| mask | 1 << (e - 1));
+                self.header().repeat_alternative = mask;
+            }
+            "section" => {
+                let mut title = self.string()?;
+                // `\section id "Title"`
+                if let Some(text) = self.optional_string() {
+                    title = text;
+                }
+                self.header().marker = Some(Marker {
+                    title,
+                    ..Default::default()
+                });
+            }
+            "tempo" => {
+                let tempo = self.number()?.clamp(1, 999) as i32;
+                self.optional_string();
+                self.header().tempo = tempo;
+            }
+            "tf" => {
+                let feel = match self.string()?.as_str() {
+                    "triplet-8th" | "triplet8th" | "3" => TripletFeel::Eighth,
+                    "triplet-16th" | "triplet16th" | "4" => TripletFeel::Sixteenth,
+                    _ => TripletFeel::None,
+                };
+                self.header().triplet_feel = feel;
+            }
+            "clef" => {
+                let name = self.string()?;
+                let Some(clef) = parse_clef(&name) else {
+                    return self.error(&format!("unknown clef '{}'", name));
+                };
+                self.set_clef(clef);
+            }
+            _ => self.skip_arguments(),
+        }
+        Ok(())
+    }
+
+    /// Effects of a note in braces, the unknown ones are effects of the beat.
+    fn read_note_effects(&mut self, note: &mut Note, beat: &mut BeatEffects) -> GpResult<()> {
+        while !self.eat('}') {
+            let Some(Token::Ident(name)) = self.next() else {
+                self.pos -= 1;
+                return self.error("note effect expected");
+            };
+            let harmonic = match name.as_str() {
+                "nh" => Some(HarmonicType::Natural),
+                "ah" => Some(HarmonicType::Artificial),
+                "th" => Some(HarmonicType::Tapped),
+                "ph" => Some(HarmonicType::Pinch),
+                "sh" => Some(HarmonicType::Semi),
+                _ => None,
+            };
+            if let Some(kind) = harmonic {
+                // The harmonic fret, a tapped harmonic without one is played on the note fret
+                let fret = self.optional_number().map(|f| f.clamp(0, 99) as i8);
+                let fret = match kind {
+                    HarmonicType::Tapped => fret.or(Some(note.value.clamp(0, 99) as i8)),
+                    _ => fret,
+                };
+                note.effect.harmonic = Some(HarmonicEffect {
+                    kind,
+                    fret,
+                    ..Default::default()
+                });
+                continue;
+            }
+            let effect = &mut note.effect;
+            match name.as_str() {
+                "h" => effect.hammer = true,
+                "v" => effect.vibrato = true,
+                "g" => effect.ghost_note = true,
+                "ac" => effect.accentuated_note = true,
+                "hac" => effect.heavy_accentuated_note = true,
+                "pm" => effect.palm_mute = true,
+                "st" => effect.staccato = true,
+                "lr" => effect.let_ring = true,
+                "x" => note.kind = NoteType::Dead,
+                "t" | "-" => note.kind = NoteType::Tie,
+                "b" => {
+                    let values = self.values()?;
+                    let values = values.iter().map(|v| (*v).clamp(-127, 127) as i8).collect();
+                    note.effect.bend = points_effect(values, false);
+                }
+                "tr" => {
+                    let fret = self.number()?.clamp(0, 99) as i8;
+                    let value = self.optional_number().unwrap_or(16);
+                    note.effect.trill = Some(TrillEffect {
+                        fret,
+                        duration: Duration {
+                            value: duration_value(value),
+                            ..Default::default()
+                        },
+                    });
+                }
+                "tp" => {
+                    let value = self.optional_number().unwrap_or(8);
+                    note.effect.tremolo_picking = Some(TremoloPickingEffect {
+                        duration: Duration {
+                            value: duration_value(value),
+                            ..Default::default()
+                        },
+                    });
+                }
+                name => match SLIDES.iter().find(|s| s.1 == name) {
+                    Some(slide) => effect.slides.push(slide.0.clone()),
+                    None => self.read_beat_effect(name, beat)?,
+                },
+            }
+        }
+        Ok(())
+    }
+
+    fn read_beat_effect(&mut self, name: &str, effects: &mut BeatEffects) -> GpResult<()> {
+        let beat = &mut effects.beat;
+        let effect = &mut beat.effect;
+        match name {
+            "d" => beat.duration.dotted = true,
+            "dd" => beat.duration.double_dotted = true,
+            "tu" => {
+                // Any tuplet a duration can hold, from 1 to 255 notes
+                let tuplet_value = |n: i64| u8::try_from(n).ok().filter(|n| *n > 0);
+                let enters = self.number()?;
+                let Some(enters) = tuplet_value(enters) else {
+                    return self.error(&format!("tuplet {} out of range", enters));
+                };
+                let times = match self.optional_number() {
+                    Some(times) => match tuplet_value(times) {
+                        Some(times) => times,
+                        None => return self.error(&format!("tuplet {} out of range", times)),
+                    },
+                    None => tuplet_times(enters),
+                };
+                beat.duration.tuplet_enters = enters;
+                beat.duration.tuplet_times = times;
+            }
+            "gr" => {
+                let on_beat = self.peek() == Some(&Token::Ident(String::from("ob")));
+                if on_beat {
+                    self.pos += 1;
+                }
+                effects.grace = Some(on_beat);
+            }
+            "f" => effect.fade_in = true,
+            "v" => effect.vibrato = true,
+            "s" => effect.slap_effect = SlapEffect::Slapping,
+            "p" => effect.slap_effect = SlapEffect::Popping,
+            "tt" => effect.slap_effect = SlapEffect::Tapping,
+            "su" => effect.stroke.direction = BeatStrokeDirection::Up,
+            "sd" => effect.stroke.direction = BeatStrokeDirection::Down,
+            "spu" => effect.pick_stroke = BeatStrokeDirection::Up,
+            "spd" => effect.pick_stroke = BeatStrokeDirection::Down,
+            "tb" => {
+                let values = self.values()?;
+                let values = values.iter().map(|v| (*v).clamp(-127, 127) as i8).collect();
+                effects.beat.effect.tremolo_bar = points_effect(values, true);
+            }
+            "ch" => {
+                let name = self.string()?;
+                effects.beat.effect.chord = Some(Chord {
+                    name,
+                    ..Default::default()
+                });
+            }
+            "txt" => effects.beat.text = self.string()?,
+            "tempo" => {
+                let tempo = self.number()?.clamp(1, 255) as u8;
+                effects.beat.effect.mix_table_change = Some(MixTableChange {
+                    tempo: Some(MixTableItem {
+                        value: tempo,
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                });
+            }
+            _ => self.skip_arguments(),
+        }
+        Ok(())
+    }
+
+    /// A note: `fret.string`, only the value in percussion tracks, then its effects.
+    fn read_note(&mut self, beat: &mut BeatEffects) -> GpResult<Note> {
+        let percussion = self.track().track.percussion_track;
+        let mut note = Note {
+            kind: NoteType::Normal,
+            ..Default::default()
+        };
+        match self.next() {
+            Some(Token::Number(fret)) => note.value = fret.clamp(0, 127) as i16,
+            Some(Token::Ident(x)) if x == "x" => note.kind = NoteType::Dead,
+            Some(Token::Symbol('-')) => note.kind = NoteType::Tie,
+            _ => {
+                self.pos -= 1;
+                return self.error("note expected");
+            }
+        }
+        if percussion {
+            note.string = 1;
+        } else {
+            self.expect('.')?;
+            let string_count = self.track().track.strings.len() as i64;
+            let string = self.number()?;
+            if !(1..=string_count).contains(&string) {
+                return self.error(&format!("string {} out of the tuning", string));
+            }
+            note.string = string as i8;
+        }
+        if self.eat('{') {
+            self.read_note_effects(&mut note, beat)?;
+        }
+        Ok(note)
+    }
+
+    /// A beat with its notes, its duration and its effects, repeated by `*N`.
+    fn read_beat(&mut self) -> GpResult<()> {
+        let mut effects = BeatEffects::default();
+        let mut notes = Vec::new();
+        let mut rest = false;
+        if self.eat('(') {
+            while !self.eat(')') {
+                notes.push(self.read_note(&mut effects)?);
+            }
+        } else if self.peek() == Some(&Token::Ident(String::from("r"))) {
+            self.pos += 1;
+            rest = true;
+        } else {
+            notes.push(self.read_note(&mut effects)?);
+        }
+
+        let explicit = self.peek() == Some(&Token::Symbol('.'))
+            && matches!(self.tokens.get(self.pos + 1), Some((Token::Number(_), _)));
+        if explicit {
+            self.pos += 1;
+            let value = self.number()?;
+            self.duration = Duration {
+                value: duration_value(value),
+                ..Default::default()
+            };
+        }
+        let duration = effects.beat.duration.clone();
+        effects.beat.duration = self.duration.clone();
+        effects.beat.duration.dotted |= duration.dotted;
+        effects.beat.duration.double_dotted |= duration.double_dotted;
+        if !duration.is_default_tuplet() {
+            effects.beat.duration.tuplet_enters = duration.tuplet_enters;
+            effects.beat.duration.tuplet_times = duration.tuplet_times;
+        }
+        if self.eat('{') {
+            while !self.eat('}') {
+                match self.next() {
+                    Some(Token::Ident(name)) => self.read_beat_effect(&name, &mut effects)?,
+                    _ => {
+                        self.pos -= 1;
+                        return self.error("beat effect expected");
+                    }
+                }
+            }
+        }
+        let count = if self.eat('*') {
+            self.number()?.clamp(1, 256)
+        } else {
+            1
+        };
+
+        if let Some(on_beat) = effects.grace {
+            for note in &notes {
+                self.graces.push((
+                    note.string,
+                    GraceEffect {
+                        duration: effects.beat.duration.value.clamp(16, 64) as u8,
+                        fret: note.value as i8,
+                        is_dead: note.kind == NoteType::Dead,
+                        is_on_beat: on_beat,
+                        ..Default::default()
+                    },
+                ));
+            }
+            return Ok(());
+        }
+
+        let mut beat = effects.beat;
+        beat.status = if rest || notes.is_empty() {
+            BeatStatus::Rest
+        } else {
+            BeatStatus::Normal
+        };
+        // The chord of the beat takes the diagram declared in the track
+        if let Some(chord) = &mut beat.effect.chord {
+            let string_count = self.track().track.strings.len();
+            let diagram = self.track().chords.iter().find(|c| c.0 == chord.name);
+            if let Some((_, frets)) = diagram {
+                let mut strings = frets.clone();
+                strings.resize(string_count, -1);
+                chord.first_fret = Some(
+                    strings
+                        .iter()
+                        .copied()
+                        .filter(|f| *f > 0)
+                        .min()
+                        .unwrap_or(1) as u8,
+                );
+                chord.strings = strings;
+            }
+            chord.length = string_count as u8;
+            chord.show = Some(true);
+            chord.new_format = Some(false);
+        }
+        let graces = std::mem::take(&mut self.graces);
+        for mut note in notes {
+            if let Some(grace) = graces.iter().find(|g| g.0 == note.string) {
+                note.effect.grace = Some(grace.1.clone());
+            }
+            if note.kind == NoteType::Tie {
+                note.value = self.tied_value(note.string);
+            }
+            beat.notes.push(note);
+        }
+        self.header();
+        let voice = self.voice();
+        for _ in 0..count {
+            voice.beats.push(beat.clone());
+        }
+        Ok(())
+    }
+
+    /// Fret of the last note on a string in the current voice.
+    fn tied_value(&mut self, string: i8) -> i16 {
+        let (bar, v_idx) = (self.bar, self.voice);
+        let track = self.track();
+        let Some(voices) = track.voices.get(v_idx) else {
+            return 0;
+        };
+        voices[..voices.len().min(bar + 1)]
+            .iter()
+            .rev()
+            .flat_map(|v| v.beats.iter().rev())
+            .flat_map(|b| &b.notes)
+            .find(|n| n.string == string)
+            .map_or(0, |n| n.value)
+    }
+
+    fn read_body(&mut self) -> GpResult<()> {
+        while let Some(token) = self.next() {
+            match token {
+                Token::Meta(name) => self.read_metadata(&name)?,
+                Token::Symbol('|') => self.bar += 1,
+                Token::Symbol(':') => {
+                    let value = self.number()?;
+                    self.duration = Duration {
+                        value: duration_value(value),
+                        ..Default::default()
+                    };
+                }
+                Token::Symbol('.') => {}
+                _ => {
+                    self.pos -= 1;
+                    self.read_beat()?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Clef of the current bar, kept by the following bars.
+    fn set_clef(&mut self, clef: MeasureClef) {
+        let bar = self.bar;
+        let clefs = &mut self.track().clefs;
+        while clefs.len() <= bar {
+            clefs.push(clefs.last().cloned().unwrap_or(MeasureClef::Treble));
+        }
+        clefs[bar] = clef;
+    }
+}
+
+/// Duration value of a number, the nearest power of two up to the 128th note.
+fn duration_value(value: i64) -> u16 {
+    let value = value.clamp(1, 128) as u16;
+    if value.is_power_of_two() {
+        value
+    } else {
+        value.next_power_of_two() / 2
+    }
+}
+
+impl SongAlphaTexOps for Song {
+    fn read_alphatex(&mut self, text: &str) -> GpResult<()> {
+        let mut reader = TexReader {
+            tokens: tokenize(text)?,
+            pos: 0,
+            headers: Vec::new(),
+            tracks: Vec::new(),
+            voice: 0,
+            bar: 0,
+            duration: Duration::default(),
+            graces: Vec::new(),
+        };
+        self.tempo = 120;
+        reader.read_song_metadata(self)?;
+        reader.read_body()?;
+        if reader.tracks.is_empty() || reader.headers.is_empty() {
+            return Err(GpError::FormatError(String::from(
+                "alphaTex document without bars",
+            )));
+        }
+
+        if let Some(tempo) = reader.headers.first().map(|h| h.tempo).filter(|t| *t > 0) {
+            self.tempo = tempo as i16;
+        }
+        self.key = reader.headers[0].key_signature.clone();
+        self.measure_headers = reader.headers;
+
+        self.tracks.clear();
+        // Guitar Pro files always hold five lyrics lines
+        self.lyrics = Lyrics::default();
+        for line in 0..5 {
+            self.lyrics.lines.push((line, 1, String::new()));
+        }
+        self.channels = (0..64u8)
+            .map(|channel| MidiChannel {
+                channel,
+                effect_channel: channel,
+                ..Default::default()
+            })
+            .collect();
+        let mut free_channels = (0..16u8)
+            .filter(|c| *c != DEFAULT_PERCUSSION_CHANNEL)
+            .cycle();
+        for (t_idx, tex_track) in reader.tracks.into_iter().enumerate() {
+            let mut track = tex_track.track;
+            if !tex_track.lyrics.is_empty() && self.lyrics.track_choice == 0 {
+                self.lyrics.track_choice = (t_idx + 1) as u8;
+                for (line, (from, text)) in tex_track.lyrics.into_iter().take(5).enumerate() {
+                    self.lyrics.lines[line] = (line as u8, from, text);
+                }
+            }
+            for (m_idx, header) in self.measure_headers.iter().enumerate() {
+                let mut voices: Vec<Voice> = Vec::with_capacity(2);
+                for v_idx in 0..tex_track.voices.len().max(2) {
+                    let mut voice = tex_track
+                        .voices
+                        .get(v_idx)
+                        .and_then(|v| v.get(m_idx))
+                        .cloned()
+                        .unwrap_or_default();
+                    voice.measure_index = m_idx as i16;
+                    if voice.beats.is_empty() {
+                        voice.beats.push(match v_idx {
+                            0 => Beat {
+                                status: BeatStatus::Rest,
+                                duration: duration_from_ticks(header.length()),
+                                ..Default::default()
+                            },
+                            _ => Beat {
+                                status: BeatStatus::Empty,
+                                ..Default::default()
+                            },
+                        });
+                    }
+                    voices.push(voice);
+                }
+                voices.truncate(2);
+                track.measures.push(Measure {
+                    number: m_idx + 1,
+                    track_index: t_idx,
+                    header_index: m_idx,
+                    key_signature: header.key_signature.clone(),
+                    time_signature: header.time_signature.clone(),
+                    clef: tex_track
+                        .clefs
+                        .get(m_idx)
+                        .or(tex_track.clefs.last())
+                        .cloned()
+                        .unwrap_or(MeasureClef::Treble),
+                    voices,
+                    ..Default::default()
+                });
+            }
+
+            let channel = if track.percussion_track {
+                DEFAULT_PERCUSSION_CHANNEL
+            } else {
+                free_channels.next().unwrap_or(0)
+            };
+            track.channel_index = usize::from(channel);
+            if !track.percussion_track {
+                self.channels[usize::from(channel)]
+                    .set_instrument(tex_track.program.unwrap_or(GUITAR_PROGRAM));
+            }
+            self.tracks.push(track);
+        }
+        Ok(())
+    }
+}
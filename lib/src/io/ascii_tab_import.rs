@@ -0,0 +1,602 @@
+//! ASCII tablature import (plain-text guitar tabs), the inverse of the tablature printed by the CLI.
+//!
+//! A staff is a run of consecutive lines made of dashes, frets, bar lines and technique marks, with
+//! an optional tuning prefix (`e|`, `B|`, `F#|`, ...). The staves of the first system define the
+//! tracks: the system ends when a staff with the tuning of the first one comes again, and every
+//! following system continues the tracks in the same order. Plain text lines are ignored, except
+//! the palm mute marks (`PM----`, `P.M.---`) written just above a staff.
+//!
+//! Text tabs have no rhythm: every measure is read as a 4/4 measure and the durations come from the
+//! spacing of the columns, rounded to a sixteenth note grid (finer when the measure is crowded).
+use crate::audio::midi::{MidiChannel, DEFAULT_PERCUSSION_CHANNEL};
+use crate::error::{GpError, GpResult};
+use crate::io::notation::{bend_from_values, duration_from_ticks};
+use crate::model::{
+    beat::{Beat, Voice},
+    enums::*,
+    headers::MeasureHeader,
+    key_signature::*,
+    lyric::Lyrics,
+    measure::Measure,
+    note::Note,
+    song::Song,
+    track::Track,
+};
+
+/// Lines of the smallest staff: tabs of fewer strings are too close to plain text.
+const MIN_STRINGS: usize = 3;
+/// Standard tunings, highest string first, to place the tuning prefixes in octaves.
+const GUITAR_TUNING: [i8; 7] = [64, 59, 55, 50, 45, 40, 35];
+const BASS_TUNING: [i8; 5] = [43, 38, 33, 28, 23];
+const DEFAULT_FRET_COUNT: u8 = 24;
+/// Sixteenth notes of a 4/4 measure, the coarsest grid of the rhythms.
+const MEASURE_UNITS: usize = 16;
+/// Lowest note of the guitar tunings, lower tunings are played by a bass.
+const LOWEST_GUITAR_NOTE: i8 = 35;
+const GUITAR_PROGRAM: i32 = 25;
+const BASS_PROGRAM: i32 = 33;
+
+pub trait SongAsciiTabOps {
+    /// Read a plain-text tablature, one track per staff of its systems.
+    fn read_ascii_tab(&mut self, text: &str) -> GpResult<()>;
+}
+
+/// Pitch class of a note name (`E`, `f#`, `Bb`).
+fn pitch_class(name: &str) -> Option<i8> {
+    let mut chars = name.chars();
+    let step = match chars.next()?.to_ascii_uppercase() {
+        'C' => 0,
+        'D' => 2,
+        'E' => 4,
+        'F' => 5,
+        'G' => 7,
+        'A' => 9,
+        'B' => 11,
+        _ => return None,
+    };
+    let alter = match chars.next() {
+        None => 0,
+        Some('#') => 1,
+        Some('b') => -1,
+        Some(_) => return None,
+    };
+    Some((step + alter + 12) % 12)
+}
+
+/// Tuning prefix of a staff line: a note name with an optional octave.
+#[derive(Clone, Debug, PartialEq)]
+struct Label {
+    pitch_class: i8,
+    octave: Option<i8>,
+}
+
+/// Split a line into its tuning prefix and its tablature, `None` when it is not a staff line.
+fn parse_tab_line(line: &str) -> Option<(Option<Label>, usize, Vec<char>)> {
+    let chars: Vec<char> = line.trim_end().chars().collect();
+    let indent = chars.iter().take_while(|c| c.is_whitespace()).count();
+    let mut idx = indent;
+    let mut label = None;
+    if chars.get(idx).is_some_and(|c| c.is_ascii_alphabetic()) {
+        let mut end = idx + 1;
+        if chars.get(end).is_some_and(|c| *c == '#' || *c == 'b') {
+            end += 1;
+        }
+        let name: String = chars[idx..end].iter().collect();
+        let octave = chars.get(end).and_then(|c| c.to_digit(10)).map(|d| d as i8);
+        if octave.is_some() {
+            end += 1;
+        }
+        label = Some(Label {
+            pitch_class: pitch_class(&name)?,
+            octave,
+        });
+        idx = end;
+        while chars.get(idx).is_some_and(|c| *c == ' ' || *c == ':') {
+            idx += 1;
+        }
+    }
+    let body = chars[idx..].to_vec();
+    let is_tab = body.first().is_some_and(|c| *c == '|' || *c == '-')
+        && body.iter().filter(|c| **c == '-').count() >= 3
+        && body
+            .iter()
+            .all(|c| c.is_ascii_digit() || "-|hpbrxX/\\~()*:.".contains(*c));
+    is_tab.then_some((label, idx, body))
+}
+
+/// Columns of a palm mute line, from the `PM` mark to the end of its dashes.
+fn palm_mute_columns(line: &str, offset: usize) -> Vec<(usize, usize)> {
+    let chars: Vec<char> = line.chars().collect();
+    let mut ranges = Vec::new();
+    let mut idx = 0;
+    while idx < chars.len() {
+        let mark = ["PM", "P.M."]
+            .iter()
+            .find(|m| chars[idx..].starts_with(&m.chars().collect::<Vec<char>>()));
+        let Some(mark) = mark else {
+            idx += 1;
+            continue;
+        };
+        let start = idx;
+        idx += mark.len();
+        while chars.get(idx).is_some_and(|c| "-._|".contains(*c)) {
+            idx += 1;
+        }
+        if start >= offset {
+            ranges.push((start - offset, idx - offset));
+        }
+    }
+    ranges
+}
+
+/// A staff of a system: its lines, highest string first, and the palm mutes above it.
+struct Staff {
+    labels: Vec<Option<Label>>,
+    lines: Vec<Vec<char>>,
+    palm_mutes: Vec<(usize, usize)>,
+}
+
+impl Staff {
+    fn has_same_tuning(&self, other: &Staff) -> bool {
+        self.labels == other.labels
+    }
+
+    /// Tuning of the staff, from its prefixes placed in the octaves of a standard tuning.
+    fn tuning(&self) -> Vec<i8> {
+        let defaults: &[i8] = if (4..=5).contains(&self.labels.len()) {
+            &BASS_TUNING
+        } else {
+            &GUITAR_TUNING
+        };
+        let mut tuning: Vec<i16> = Vec::with_capacity(self.labels.len());
+        for (s_idx, label) in self.labels.iter().enumerate() {
+            // Strings past the standard tuning go on by fourths
+            let lowest = defaults.len() - 1;
+            let default =
+                i16::from(defaults[s_idx.min(lowest)]) - 5 * s_idx.saturating_sub(lowest) as i16;
+            let pitch = match (label, tuning.last()) {
+                (
+                    Some(Label {
+                        pitch_class,
+                        octave: Some(octave),
+                    }),
+                    _,
+                ) => (i16::from(*octave) + 1) * 12 + i16::from(*pitch_class),
+                // The highest pitch of the class under the previous string
+                (Some(label), Some(previous)) => {
+                    let below = previous - 1;
+                    below - (below - i16::from(label.pitch_class)).rem_euclid(12)
+                }
+                // The pitch of the class closest to the standard tuning
+                (Some(label), None) => {
+                    let below = default - (default - i16::from(label.pitch_class)).rem_euclid(12);
+                    if default - below > 6 {
+                        below + 12
+                    } else {
+                        below
+                    }
+                }
+                (None, _) => default,
+            };
+            tuning.push(pitch);
+        }
+        tuning.iter().map(|p| (*p).clamp(0, 127) as i8).collect()
+    }
+
+    /// Measures of the staff, split at its bar lines.
+    fn measures(&self) -> Vec<TabMeasure> {
+        let width = self.lines.iter().map(Vec::len).max().unwrap_or(0);
+        let cell = |line: &Vec<char>, col: usize| line.get(col).copied().unwrap_or('-');
+        let bars: Vec<usize> = (0..width)
+            .filter(|col| {
+                self.lines.iter().filter(|l| cell(l, *col) == '|').count() * 2 > self.lines.len()
+            })
+            .collect();
+        let mut bounds = Vec::with_capacity(bars.len() + 2);
+        if bars.first() != Some(&0) {
+            bounds.push(None);
+        }
+        bounds.extend(bars.iter().map(|b| Some(*b)));
+        bounds.push(None);
+
+        let mut measures: Vec<TabMeasure> = Vec::new();
+        for pair in bounds.windows(2) {
+            let start = pair[0].map_or(0, |b| b + 1);
+            let end = pair[1].unwrap_or(width);
+            if start >= end {
+                // Two bar lines in a row: a double bar
+                if let Some(last) = measures.last_mut() {
+                    last.double_bar = true;
+                }
+                continue;
+            }
+            let segment: Vec<&[char]> = self
+                .lines
+                .iter()
+                .map(|l| &l[start.min(l.len())..end.min(l.len())])
+                .collect();
+            let is_repeat = |col: usize| {
+                segment
+                    .iter()
+                    .any(|l| l.get(col).is_some_and(|c| *c == ':' || *c == '*'))
+            };
+            let mut measure = TabMeasure {
+                width: end - start,
+                repeat_open: is_repeat(0),
+                repeat_close: is_repeat(end - start - 1),
+                ..Default::default()
+            };
+            let palm_mutes: Vec<(usize, usize)> = self
+                .palm_mutes
+                .iter()
+                .filter(|(from, to)| *from < end && *to > start)
+                .map(|(from, to)| (from.saturating_sub(start), to - start))
+                .collect();
+            measure.read_beats(&segment, &palm_mutes);
+            // Text after the last bar line is part of the staff only when it holds notes
+            if pair[1].is_some() || bars.is_empty() || !measure.beats.is_empty() {
+                measures.push(measure);
+            }
+        }
+        measures
+    }
+}
+
+/// A note of a line, from its first column to the last column of its fret.
+struct TabNote {
+    string: i8,
+    start: usize,
+    end: usize,
+    note: Note,
+}
+
+#[derive(Default)]
+struct TabBeat {
+    column: usize,
+    notes: Vec<Note>,
+}
+
+#[derive(Default)]
+struct TabMeasure {
+    width: usize,
+    beats: Vec<TabBeat>,
+    repeat_open: bool,
+    repeat_close: bool,
+    double_bar: bool,
+}
+
+/// Fret written from `idx`, at most two digits.
+fn read_fret(line: &[char], idx: &mut usize) -> Option<i16> {
+    let digits: String = line[*idx..]
+        .iter()
+        .take(2)
+        .take_while(|c| c.is_ascii_digit())
+        .collect();
+    if digits.is_empty() {
+        return None;
+    }
+    *idx += digits.len();
+    digits.parse().ok()
+}
+
+/// Notes of a line of a measure, with their effects.
+fn read_line_notes(line: &[char], string: i8, palm_mutes: &[(usize, usize)]) -> Vec<TabNote> {
+    let mut notes: Vec<TabNote> = Vec::new();
+    let mut slide_in = None;
+    let mut idx = 0;
+    while idx < line.len() {
+        let c = line[idx];
+        let start = idx;
+        let mut note = Note {
+            string,
+            kind: NoteType::Normal,
+            ..Default::default()
+        };
+        if c == 'x' || c == 'X' {
+            note.kind = NoteType::Dead;
+            idx += 1;
+        } else if let Some(fret) = read_fret(line, &mut idx) {
+            note.value = fret;
+        } else {
+            match c {
+                '/' => slide_in = Some(SlideType::IntoFromBelow),
+                '\\' => slide_in = Some(SlideType::IntoFromAbove),
+                _ => slide_in = None,
+            }
+            idx += 1;
+            continue;
+        }
+        let end = idx - 1;
+        note.effect.ghost_note = start > 0 && line[start - 1] == '(';
+        note.effect.slides.extend(slide_in.take());
+        note.effect.palm_mute = palm_mutes
+            .iter()
+            .any(|(from, to)| (*from..*to).contains(&start));
+
+        // Technique marks following the fret
+        let next_is_fret = |idx: usize| line.get(idx).is_some_and(char::is_ascii_digit);
+        while idx < line.len() {
+            match line[idx] {
+                'h' | 'p' if next_is_fret(idx + 1) => {
+                    note.effect.hammer = true;
+                    idx += 1;
+                    break;
+                }
+                '/' | '\\' if next_is_fret(idx + 1) => {
+                    note.effect.slides.push(SlideType::ShiftSlideTo);
+                    idx += 1;
+                    break;
+                }
+                '/' => {
+                    note.effect.slides.push(SlideType::OutUpWards);
+                    idx += 1;
+                }
+                '\\' => {
+                    note.effect.slides.push(SlideType::OutDownwards);
+                    idx += 1;
+                }
+                '~' => {
+                    note.effect.vibrato = true;
+                    idx += 1;
+                }
+                ')' => idx += 1,
+                'b' => {
+                    idx += 1;
+                    // Bent and released frets, a full bend without them
+                    let fret = note.value;
+                    let bent = read_fret(line, &mut idx).unwrap_or(fret + 2);
+                    let mut values = vec![0, ((bent - fret) * 2) as i8];
+                    if line.get(idx) == Some(&'r') {
+                        idx += 1;
+                        let released = read_fret(line, &mut idx).unwrap_or(fret);
+                        values.push(((released - fret) * 2) as i8);
+                    }
+                    note.effect.bend = bend_from_values(values);
+                }
+                _ => break,
+            }
+        }
+        notes.push(TabNote {
+            string,
+            start,
+            end,
+            note,
+        });
+    }
+    notes
+}
+
+impl TabMeasure {
+    /// Beats of the measure: notes of the lines whose columns overlap.
+    fn read_beats(&mut self, segment: &[&[char]], palm_mutes: &[(usize, usize)]) {
+        let mut notes: Vec<TabNote> = segment
+            .iter()
+            .enumerate()
+            .flat_map(|(s_idx, line)| read_line_notes(line, (s_idx + 1) as i8, palm_mutes))
+            .collect();
+        notes.sort_by_key(|n| (n.start, n.string));
+        let mut end = 0;
+        for note in notes {
+            match self.beats.last_mut() {
+                Some(beat)
+                    if note.start <= end && beat.notes.iter().all(|n| n.string != note.string) =>
+                {
+                    end = end.max(note.end);
+                    beat.notes.push(note.note);
+                }
+                _ => {
+                    end = note.end;
+                    self.beats.push(TabBeat {
+                        column: note.start,
+                        notes: vec![note.note],
+                    });
+                }
+            }
+        }
+    }
+
+    /// Beats of the voice of the measure, with durations from the spacing of the columns.
+    fn voice(&self, header: &MeasureHeader) -> Voice {
+        let mut voice = Voice::default();
+        if self.beats.is_empty() {
+            voice.beats.push(Beat {
+                status: BeatStatus::Rest,
+                duration: duration_from_ticks(header.length()),
+                ..Default::default()
+            });
+            return voice;
+        }
+        let count = self.beats.len();
+        let grid = count.next_power_of_two().max(MEASURE_UNITS);
+        let unit_ticks = header.length() / grid as i64;
+        // The first beat starts the measure, unless the space before it is a rest
+        let first = self.beats[0].column;
+        let origin = if first * 4 < self.width { first } else { 0 };
+        // The last beat lasts at least as long as the previous one, tabs often end on it
+        let last = self.beats[count - 1].column - origin;
+        let last_gap = match count {
+            1 => 0,
+            _ => self.beats[count - 1].column - self.beats[count - 2].column,
+        };
+        let span = (self.width - origin).max(last + last_gap).max(1);
+        let mut positions: Vec<usize> = Vec::with_capacity(count);
+        for (b_idx, beat) in self.beats.iter().enumerate() {
+            let position = ((beat.column - origin) * grid + span / 2) / span;
+            let after = positions.last().map_or(0, |p| p + 1);
+            positions.push(position.max(after).min(grid - (count - b_idx)));
+        }
+        if positions[0] > 0 {
+            push_beats(&mut voice, None, positions[0] as i64 * unit_ticks);
+        }
+        for (b_idx, beat) in self.beats.iter().enumerate() {
+            let next = positions.get(b_idx + 1).copied().unwrap_or(grid);
+            let ticks = (next - positions[b_idx]) as i64 * unit_ticks;
+            push_beats(&mut voice, Some(&beat.notes), ticks);
+        }
+        voice
+    }
+}
+
+/// Beats lasting `ticks`: the notes on the longest duration fitting in them, then rests.
+fn push_beats(voice: &mut Voice, notes: Option<&Vec<Note>>, mut ticks: i64) {
+    let whole = DURATION_QUARTER_TIME * 4;
+    let lengths: Vec<i64> = [1, 2, 4, 8, 16, 32, 64, 128]
+        .iter()
+        .flat_map(|v| [whole / v * 3 / 2, whole / v])
+        .collect();
+    let mut notes = notes.filter(|n| !n.is_empty());
+    while ticks > 0 {
+        let Some(length) = lengths.iter().copied().find(|l| *l <= ticks) else {
+            break;
+        };
+        let mut beat = Beat {
+            duration: duration_from_ticks(length),
+            ..Default::default()
+        };
+        match notes.take() {
+            Some(notes) => beat.notes = notes.clone(),
+            None => beat.status = BeatStatus::Rest,
+        }
+        voice.beats.push(beat);
+        ticks -= length;
+    }
+}
+
+/// Staves of a text, with the palm mute line above each of them.
+fn read_staves(text: &str) -> Vec<Staff> {
+    let mut staves: Vec<Staff> = Vec::new();
+    let mut current: Option<Staff> = None;
+    let mut previous_line: Option<&str> = None;
+    for line in text.lines() {
+        match parse_tab_line(line) {
+            Some((label, offset, body)) => {
+                let staff = current.get_or_insert_with(|| Staff {
+                    labels: Vec::new(),
+                    lines: Vec::new(),
+                    palm_mutes: previous_line
+                        .map(|l| palm_mute_columns(l, offset))
+                        .unwrap_or_default(),
+                });
+                staff.labels.push(label);
+                staff.lines.push(body);
+            }
+            None => {
+                staves.extend(current.take().filter(|s| s.lines.len() >= MIN_STRINGS));
+                previous_line = Some(line);
+            }
+        }
+    }
+    staves.extend(current.filter(|s| s.lines.len() >= MIN_STRINGS));
+    staves
+}
+
+impl SongAsciiTabOps for Song {
+    fn read_ascii_tab(&mut self, text: &str) -> GpResult<()> {
+        let staves = read_staves(text);
+        if staves.is_empty() {
+            return Err(GpError::FormatError(
+                "no tablature staff found in the text".into(),
+            ));
+        }
+        // The first system ends when the tuning of its first staff comes again
+        let track_count = staves
+            .iter()
+            .skip(1)
+            .position(|s| s.has_same_tuning(&staves[0]))
+            .map_or(staves.len(), |p| p + 1);
+        let systems: Vec<Vec<TabMeasure>> = staves.iter().map(Staff::measures).collect();
+
+        // Measure headers of every system, with the bar lines of all its staves
+        let mut headers: Vec<MeasureHeader> = Vec::new();
+        let mut track_measures: Vec<Vec<Option<&TabMeasure>>> = vec![Vec::new(); track_count];
+        for system in systems.chunks(track_count) {
+            let measure_count = system.iter().map(Vec::len).max().unwrap_or(0);
+            for m_idx in 0..measure_count {
+                let measures: Vec<&TabMeasure> =
+                    system.iter().filter_map(|s| s.get(m_idx)).collect();
+                headers.push(MeasureHeader {
+                    number: (headers.len() + 1) as u16,
+                    repeat_open: measures.iter().any(|m| m.repeat_open),
+                    repeat_close: if measures.iter().any(|m| m.repeat_close) {
+                        2
+                    } else {
+                        -1
+                    },
+                    double_bar: measures.iter().any(|m| m.double_bar),
+                    ..Default::default()
+                });
+                for (t_idx, measures) in track_measures.iter_mut().enumerate() {
+                    measures.push(system.get(t_idx).and_then(|s| s.get(m_idx)));
+                }
+            }
+        }
+
+        self.measure_headers = headers;
+        self.tracks.clear();
+        // Guitar Pro files always hold five lyrics lines
+        self.lyrics = Lyrics::default();
+        for line in 0..5 {
+            self.lyrics.lines.push((line, 1, String::new()));
+        }
+        self.channels = (0..64u8)
+            .map(|channel| MidiChannel {
+                channel,
+                effect_channel: channel,
+                ..Default::default()
+            })
+            .collect();
+        let mut free_channels = (0..16u8)
+            .filter(|c| *c != DEFAULT_PERCUSSION_CHANNEL)
+            .cycle();
+        for (t_idx, measures) in track_measures.iter().enumerate() {
+            let tuning = staves[t_idx].tuning();
+            let mut track = Track {
+                number: (t_idx + 1) as i32,
+                name: format!("Track {}", t_idx + 1),
+                strings: tuning
+                    .iter()
+                    .enumerate()
+                    .map(|(s_idx, note)| ((s_idx + 1) as i8, *note))
+                    .collect(),
+                fret_count: DEFAULT_FRET_COUNT,
+                ..Default::default()
+            };
+            for (m_idx, (header, measure)) in self.measure_headers.iter().zip(measures).enumerate()
+            {
+                let mut voice = match measure {
+                    Some(measure) => measure.voice(header),
+                    None => TabMeasure::default().voice(header),
+                };
+                voice.measure_index = m_idx as i16;
+                let empty = Voice {
+                    measure_index: m_idx as i16,
+                    beats: vec![Beat {
+                        status: BeatStatus::Empty,
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                };
+                track.measures.push(Measure {
+                    number: m_idx + 1,
+                    track_index: t_idx,
+                    header_index: m_idx,
+                    key_signature: header.key_signature.clone(),
+                    time_signature: header.time_signature.clone(),
+                    voices: vec![voice, empty],
+                    ..Default::default()
+                });
+            }
+
+            let channel = free_channels.next().unwrap_or(0);
+            track.channel_index = usize::from(channel);
+            let lowest = tuning.iter().copied().min().unwrap_or(LOWEST_GUITAR_NOTE);
+            self.channels[usize::from(channel)].set_instrument(if lowest < LOWEST_GUITAR_NOTE {
+                BASS_PROGRAM
+            } else {
+                GUITAR_PROGRAM
+            });
+            self.tracks.push(track);
+        }
+        Ok(())
+    }
+}
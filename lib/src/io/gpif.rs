@@ -1,11 +1,16 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename = "GPIF")]
 pub struct Gpif {
     /// GP7 uses "GPVersion", GP6 uses "GPRevision"
-    #[serde(rename = "GPVersion", default)]
+    #[serde(rename = "GPVersion", default, skip_serializing_if = "Option::is_none")]
     pub version: Option<String>,
-    #[serde(rename = "GPRevision", default)]
+    #[serde(
+        rename = "GPRevision",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
     pub revision: Option<String>,
     #[serde(rename = "Score")]
     pub score: Score,
@@ -25,13 +30,27 @@ pub struct Gpif {
     pub notes: NotesWrapper,
     #[serde(rename = "Rhythms")]
     pub rhythms: RhythmsWrapper,
+    /// Document the model was read from, used to write back what the model does not map
+    #[serde(skip)]
+    pub source: Option<GpifSource>,
+}
+
+/// Content of the file a `Gpif` was read from.
+#[derive(Debug, Clone, Default)]
+pub struct GpifSource {
+    /// Content of `score.gpif`
+    pub xml: String,
+    /// The other entries of the container (stylesheets, layout, part configuration, ...)
+    pub files: Vec<(String, Vec<u8>)>,
+    /// Number of tracks of the source document, the part configuration depends on it
+    pub track_count: usize,
 }
 
 // ---------------------------------------------------------------------------
 // Score metadata
 // ---------------------------------------------------------------------------
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct Score {
     #[serde(default)]
@@ -57,146 +76,459 @@ pub struct Score {
 }
 
 // ---------------------------------------------------------------------------
-// MasterTrack (tempo automations)
+// MasterTrack (tempo automations) and track automations
 // ---------------------------------------------------------------------------
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct MasterTrack {
     #[serde(rename = "Tracks", default)]
     pub tracks_count: String,
-    #[serde(rename = "Automations", default)]
+    #[serde(
+        rename = "Automations",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
     pub automations: Option<AutomationsWrapper>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct AutomationsWrapper {
     #[serde(rename = "Automation", default)]
     pub automations: Vec<Automation>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct Automation {
     #[serde(rename = "Type", default)]
     pub automation_type: String,
-    #[serde(rename = "Value", default)]
-    pub value: String,
+    #[serde(rename = "Linear", default, skip_serializing_if = "Option::is_none")]
+    pub linear: Option<String>,
     #[serde(rename = "Bar", default)]
     pub bar: i32,
+    /// Position in the bar, from 0 (start) to 1 (end)
     #[serde(rename = "Position", default)]
-    pub position: i32,
+    pub position: f64,
+    #[serde(rename = "Visible", default, skip_serializing_if = "Option::is_none")]
+    pub visible: Option<String>,
+    #[serde(rename = "Value", default)]
+    pub value: String,
 }
 
 // ---------------------------------------------------------------------------
 // Tracks
 // ---------------------------------------------------------------------------
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct TracksWrapper {
     #[serde(rename = "Track", default)]
     pub tracks: Vec<Track>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct Track {
     #[serde(rename = "@id", default)]
     pub id: i32,
+    /// Position of the matching track in the source document, see `gpif_merge`
+    #[serde(skip)]
+    pub source_index: Option<usize>,
     #[serde(rename = "Name", default)]
     pub name: String,
     #[serde(rename = "ShortName", default)]
     pub short_name: String,
-    #[serde(rename = "Color", default)]
+    #[serde(rename = "Color", default, skip_serializing_if = "Option::is_none")]
     pub color: Option<String>,
     /// GP6: track-level properties (Tuning, DiagramCollection, etc.)
-    #[serde(rename = "Properties", default)]
+    #[serde(
+        rename = "Properties",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
     pub properties: Option<TrackPropertiesWrapper>,
     /// GP7: staves with per-staff properties
-    #[serde(rename = "Staves", default)]
+    #[serde(rename = "Staves", default, skip_serializing_if = "Option::is_none")]
     pub staves: Option<StavesWrapper>,
-    #[serde(rename = "GeneralMidi", default)]
+    /// GP6: reference to a built-in instrument (e.g. "e-gtr6")
+    #[serde(
+        rename = "Instrument",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub instrument: Option<InstrumentRef>,
+    /// GP7: instrument definition used to pick the staff layout
+    #[serde(
+        rename = "InstrumentSet",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub instrument_set: Option<InstrumentSet>,
+    /// GP7: sound bank entries, the MIDI program lives here
+    #[serde(rename = "Sounds", default, skip_serializing_if = "Option::is_none")]
+    pub sounds: Option<SoundsWrapper>,
+    /// GP7: MIDI port and channels
+    #[serde(
+        rename = "MidiConnection",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub midi_connection: Option<MidiConnection>,
+    /// GP6: MIDI program, port and channels
+    #[serde(
+        rename = "GeneralMidi",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
     pub general_midi: Option<GeneralMidi>,
-    #[serde(rename = "Transpose", default)]
+    /// GP7: `RSE` when the track plays through RSE rather than MIDI
+    #[serde(
+        rename = "AudioEngineState",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub audio_engine_state: Option<String>,
+    #[serde(rename = "Transpose", default, skip_serializing_if = "Option::is_none")]
     pub transpose: Option<Transpose>,
+    /// Lyrics lines of the track
+    #[serde(rename = "Lyrics", default, skip_serializing_if = "Option::is_none")]
+    pub lyrics: Option<TrackLyrics>,
+    /// Mixer settings, the volume and pan automations live in the channel strip
+    #[serde(rename = "RSE", default, skip_serializing_if = "Option::is_none")]
+    pub rse: Option<TrackRseSettings>,
+    /// GP7: sound changes
+    #[serde(
+        rename = "Automations",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub automations: Option<AutomationsWrapper>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TrackRseSettings {
+    #[serde(
+        rename = "ChannelStrip",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub channel_strip: Option<ChannelStrip>,
+    /// GP6: sound bank patch of the track
+    #[serde(rename = "Bank", default, skip_serializing_if = "Option::is_none")]
+    pub bank: Option<String>,
+    /// GP6: effect chains of the track, the first one is played
+    #[serde(
+        rename = "EffectChains",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub effect_chains: Option<EffectChains>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct EffectChains {
+    #[serde(rename = "EffectChain", default)]
+    pub chains: Vec<EffectChain>,
+}
+
+/// Effects applied to a sound. GP6 chains are named and hold their effects in a rail, GP7 sounds
+/// hold their effects directly.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct EffectChain {
+    #[serde(rename = "Name", default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(rename = "Rail", default, skip_serializing_if = "Option::is_none")]
+    pub rail: Option<EffectRail>,
+    #[serde(rename = "Effect", default, skip_serializing_if = "Vec::is_empty")]
+    pub effects: Vec<Effect>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct EffectRail {
+    #[serde(rename = "@name", default)]
+    pub name: String,
+    #[serde(rename = "Effect", default)]
+    pub effects: Vec<Effect>,
+}
+
+/// Effect slot of a chain, empty slots have no id.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Effect {
+    #[serde(rename = "@id", default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(
+        rename = "Parameters",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub parameters: Option<String>,
+}
+
+/// Track mixer. Automations of type `DSPParam_12` are the volume and `DSPParam_11` the pan,
+/// both from 0 to 1.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ChannelStrip {
+    /// 16 values from 0 to 1, 0.5 being neutral: the equalizer bands, the pan (11) and the
+    /// volume (12) at the start of the song
+    #[serde(
+        rename = "Parameters",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub parameters: Option<String>,
+    #[serde(
+        rename = "Automations",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub automations: Option<AutomationsWrapper>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct TrackPropertiesWrapper {
     #[serde(rename = "Property", default)]
     pub properties: Vec<Property>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct StavesWrapper {
     #[serde(rename = "Staff", default)]
     pub staves: Vec<Staff>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct Staff {
-    #[serde(rename = "Properties", default)]
+    #[serde(
+        rename = "Properties",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
     pub properties: Option<StaffPropertiesWrapper>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct StaffPropertiesWrapper {
     #[serde(rename = "Property", default)]
     pub properties: Vec<Property>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct GeneralMidi {
-    #[serde(rename = "Program", default)]
+    #[serde(rename = "Program", default, skip_serializing_if = "Option::is_none")]
     pub program: Option<i32>,
-    #[serde(rename = "Port", default)]
+    #[serde(rename = "Port", default, skip_serializing_if = "Option::is_none")]
     pub port: Option<i32>,
-    #[serde(rename = "PrimaryChannel", default)]
+    #[serde(
+        rename = "PrimaryChannel",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
     pub primary_channel: Option<i32>,
-    #[serde(rename = "SecondaryChannel", default)]
+    #[serde(
+        rename = "SecondaryChannel",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
     pub secondary_channel: Option<i32>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
+pub struct InstrumentRef {
+    #[serde(rename = "@ref", default)]
+    pub r#ref: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct MidiConnection {
+    #[serde(rename = "Port", default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<i32>,
+    #[serde(
+        rename = "PrimaryChannel",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub primary_channel: Option<i32>,
+    #[serde(
+        rename = "SecondaryChannel",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub secondary_channel: Option<i32>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct InstrumentSet {
+    #[serde(rename = "Name", default)]
+    pub name: String,
+    #[serde(rename = "Type", default)]
+    pub instrument_type: String,
+    #[serde(rename = "LineCount", default)]
+    pub line_count: i32,
+    #[serde(rename = "Elements", default, skip_serializing_if = "Option::is_none")]
+    pub elements: Option<ElementsWrapper>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ElementsWrapper {
+    #[serde(rename = "Element", default)]
+    pub elements: Vec<Element>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Element {
+    #[serde(rename = "Name", default)]
+    pub name: String,
+    #[serde(rename = "Type", default)]
+    pub element_type: String,
+    #[serde(
+        rename = "Articulations",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub articulations: Option<ArticulationsWrapper>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ArticulationsWrapper {
+    #[serde(rename = "Articulation", default)]
+    pub articulations: Vec<Articulation>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Articulation {
+    #[serde(rename = "Name", default)]
+    pub name: String,
+    #[serde(rename = "StaffLine", default)]
+    pub staff_line: i32,
+    #[serde(rename = "Noteheads", default)]
+    pub noteheads: String,
+    #[serde(rename = "TechniquePlacement", default)]
+    pub technique_placement: String,
+    #[serde(rename = "OutputMidiNumber", default)]
+    pub output_midi_number: i32,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SoundsWrapper {
+    #[serde(rename = "Sound", default)]
+    pub sounds: Vec<Sound>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Sound {
+    #[serde(rename = "Name", default)]
+    pub name: String,
+    #[serde(rename = "Label", default)]
+    pub label: String,
+    #[serde(rename = "Path", default)]
+    pub path: String,
+    #[serde(rename = "Role", default)]
+    pub role: String,
+    #[serde(rename = "MIDI", default, skip_serializing_if = "Option::is_none")]
+    pub midi: Option<SoundMidi>,
+    #[serde(rename = "RSE", default, skip_serializing_if = "Option::is_none")]
+    pub rse: Option<SoundRse>,
+}
+
+/// RSE sound of a GP7 track sound.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SoundRse {
+    #[serde(
+        rename = "SoundbankPatch",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub soundbank_patch: Option<String>,
+    #[serde(
+        rename = "EffectChain",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub effect_chain: Option<EffectChain>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SoundMidi {
+    #[serde(rename = "LSB", default)]
+    pub lsb: i32,
+    #[serde(rename = "MSB", default)]
+    pub msb: i32,
+    #[serde(rename = "Program", default)]
+    pub program: i32,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
 pub struct Transpose {
-    #[serde(rename = "Chromatic", default)]
+    #[serde(rename = "Chromatic", default, skip_serializing_if = "Option::is_none")]
     pub chromatic: Option<i32>,
-    #[serde(rename = "Octave", default)]
+    #[serde(rename = "Octave", default, skip_serializing_if = "Option::is_none")]
     pub octave: Option<i32>,
 }
 
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TrackLyrics {
+    #[serde(
+        rename = "@dispatched",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub dispatched: Option<String>,
+    #[serde(rename = "Line", default)]
+    pub lines: Vec<LyricsLine>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct LyricsLine {
+    #[serde(rename = "Text", default)]
+    pub text: String,
+    /// Index of the bar the line starts on, from 0
+    #[serde(rename = "Offset", default)]
+    pub offset: i32,
+}
+
 // ---------------------------------------------------------------------------
 // MasterBars (measure headers)
 // ---------------------------------------------------------------------------
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct MasterBarsWrapper {
     #[serde(rename = "MasterBar", default)]
     pub master_bars: Vec<MasterBar>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct MasterBar {
-    #[serde(rename = "Key", default)]
+    /// Position of the matching master bar in the source document, see `gpif_merge`
+    #[serde(skip)]
+    pub source_index: Option<usize>,
+    #[serde(rename = "Key", default, skip_serializing_if = "Option::is_none")]
     pub key: Option<Key>,
     #[serde(rename = "Time", default)]
     pub time: String,
     #[serde(rename = "Bars", default)]
     pub bars: String,
-    #[serde(rename = "Repeat", default)]
+    #[serde(rename = "Repeat", default, skip_serializing_if = "Option::is_none")]
     pub repeat: Option<Repeat>,
-    #[serde(rename = "AlternateEndings", default)]
+    #[serde(
+        rename = "AlternateEndings",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
     pub alternate_endings: Option<String>,
-    #[serde(rename = "DoubleBar", default)]
+    #[serde(rename = "DoubleBar", default, skip_serializing_if = "Option::is_none")]
     pub double_bar: Option<String>,
-    #[serde(rename = "Section", default)]
+    #[serde(rename = "Section", default, skip_serializing_if = "Option::is_none")]
     pub section: Option<Section>,
-    #[serde(rename = "Fermatas", default)]
+    #[serde(rename = "Fermatas", default, skip_serializing_if = "Option::is_none")]
     pub fermatas: Option<FermatasWrapper>,
-    #[serde(rename = "FreeTime", default)]
+    #[serde(rename = "FreeTime", default, skip_serializing_if = "Option::is_none")]
     pub free_time: Option<String>,
-    #[serde(rename = "Directions", default)]
+    #[serde(
+        rename = "Directions",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
     pub directions: Option<DirectionsWrapper>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct Key {
     #[serde(rename = "AccidentalCount", default)]
     pub accidental_count: i32,
@@ -213,7 +545,7 @@ impl Default for Key {
     }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct Repeat {
     #[serde(rename = "@start", default)]
     pub start: String,
@@ -223,33 +555,33 @@ pub struct Repeat {
     pub count: i32,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct Section {
-    #[serde(rename = "Letter", default)]
+    #[serde(rename = "Letter", default, skip_serializing_if = "Option::is_none")]
     pub letter: Option<String>,
-    #[serde(rename = "Text", default)]
+    #[serde(rename = "Text", default, skip_serializing_if = "Option::is_none")]
     pub text: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct DirectionsWrapper {
-    #[serde(rename = "Target", default)]
+    #[serde(rename = "Target", default, skip_serializing_if = "Option::is_none")]
     pub target: Option<String>,
-    #[serde(rename = "Jump", default)]
+    #[serde(rename = "Jump", default, skip_serializing_if = "Option::is_none")]
     pub jump: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct FermatasWrapper {
     #[serde(rename = "Fermata", default)]
     pub fermatas: Vec<Fermata>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct Fermata {
-    #[serde(rename = "Type", default)]
+    #[serde(rename = "Type", default, skip_serializing_if = "Option::is_none")]
     pub fermata_type: Option<String>,
-    #[serde(rename = "Offset", default)]
+    #[serde(rename = "Offset", default, skip_serializing_if = "Option::is_none")]
     pub offset: Option<String>,
 }
 
@@ -257,21 +589,25 @@ pub struct Fermata {
 // Bars (per-track measures)
 // ---------------------------------------------------------------------------
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct BarsWrapper {
     #[serde(rename = "Bar", default)]
     pub bars: Vec<Bar>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct Bar {
     #[serde(rename = "@id", default)]
     pub id: i32,
     #[serde(rename = "Voices", default)]
     pub voices: String,
-    #[serde(rename = "Clef", default)]
+    #[serde(rename = "Clef", default, skip_serializing_if = "Option::is_none")]
     pub clef: Option<String>,
-    #[serde(rename = "SimileMark", default)]
+    #[serde(
+        rename = "SimileMark",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
     pub simile_mark: Option<String>,
 }
 
@@ -279,13 +615,13 @@ pub struct Bar {
 // Voices
 // ---------------------------------------------------------------------------
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct VoicesWrapper {
     #[serde(rename = "Voice", default)]
     pub voices: Vec<Voice>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct Voice {
     #[serde(rename = "@id", default)]
     pub id: i32,
@@ -297,58 +633,94 @@ pub struct Voice {
 // Beats
 // ---------------------------------------------------------------------------
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct BeatsWrapper {
     #[serde(rename = "Beat", default)]
     pub beats: Vec<Beat>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct Beat {
     #[serde(rename = "@id", default)]
     pub id: i32,
-    #[serde(rename = "Notes", default)]
+    /// Id of an item of the track `DiagramCollection`/`ChordCollection`
+    #[serde(rename = "Chord", default, skip_serializing_if = "Option::is_none")]
+    pub chord: Option<String>,
+    #[serde(rename = "Notes", default, skip_serializing_if = "Option::is_none")]
     pub notes: Option<String>,
-    #[serde(rename = "Rhythm", default)]
+    #[serde(rename = "Rhythm", default, skip_serializing_if = "Option::is_none")]
     pub rhythm: Option<RhythmRef>,
-    #[serde(rename = "Dynamic", default)]
+    #[serde(rename = "Dynamic", default, skip_serializing_if = "Option::is_none")]
     pub dynamic: Option<String>,
-    #[serde(rename = "GraceNotes", default)]
+    #[serde(rename = "Hairpin", default, skip_serializing_if = "Option::is_none")]
+    pub hairpin: Option<String>,
+    #[serde(
+        rename = "GraceNotes",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
     pub grace_notes: Option<String>,
     /// Note: "Fadding" is a typo in the upstream GP6 XML format (should be "Fading").
-    #[serde(rename = "Fadding", default)]
+    #[serde(rename = "Fadding", default, skip_serializing_if = "Option::is_none")]
     pub fadding: Option<String>,
-    #[serde(rename = "Tremolo", default)]
+    #[serde(rename = "Whammy", default, skip_serializing_if = "Option::is_none")]
+    pub whammy: Option<Whammy>,
+    #[serde(rename = "Tremolo", default, skip_serializing_if = "Option::is_none")]
     pub tremolo: Option<String>,
-    #[serde(rename = "Wah", default)]
+    #[serde(rename = "Wah", default, skip_serializing_if = "Option::is_none")]
     pub wah: Option<String>,
-    #[serde(rename = "FreeText", default)]
+    #[serde(rename = "Ottavia", default, skip_serializing_if = "Option::is_none")]
+    pub ottavia: Option<String>,
+    #[serde(rename = "FreeText", default, skip_serializing_if = "Option::is_none")]
     pub free_text: Option<String>,
-    #[serde(rename = "Properties", default)]
+    #[serde(
+        rename = "Properties",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
     pub properties: Option<BeatPropertiesWrapper>,
 }
 
-#[derive(Debug, Deserialize)]
+/// Whammy bar curve, values are in 1/100 semitone and offsets in percent of the beat.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Whammy {
+    #[serde(rename = "@originValue", default)]
+    pub origin_value: f64,
+    #[serde(rename = "@middleValue", default)]
+    pub middle_value: f64,
+    #[serde(rename = "@destinationValue", default)]
+    pub destination_value: f64,
+    #[serde(rename = "@originOffset", default)]
+    pub origin_offset: f64,
+    #[serde(rename = "@middleOffset1", default)]
+    pub middle_offset1: f64,
+    #[serde(rename = "@middleOffset2", default)]
+    pub middle_offset2: f64,
+    #[serde(rename = "@destinationOffset", default)]
+    pub destination_offset: f64,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
 pub struct BeatPropertiesWrapper {
     #[serde(rename = "Property", default)]
     pub properties: Vec<BeatProperty>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct BeatProperty {
     #[serde(rename = "@name", default)]
     pub name: String,
-    #[serde(rename = "Direction", default)]
+    #[serde(rename = "Direction", default, skip_serializing_if = "Option::is_none")]
     pub direction: Option<String>,
-    #[serde(rename = "Enable", default)]
+    #[serde(rename = "Enable", default, skip_serializing_if = "Option::is_none")]
     pub enable: Option<EnableTag>,
-    #[serde(rename = "Float", default)]
+    #[serde(rename = "Float", default, skip_serializing_if = "Option::is_none")]
     pub float: Option<f64>,
-    #[serde(rename = "Flags", default)]
+    #[serde(rename = "Flags", default, skip_serializing_if = "Option::is_none")]
     pub flags: Option<i32>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct RhythmRef {
     #[serde(rename = "@ref", default)]
     pub r#ref: i32,
@@ -358,35 +730,39 @@ pub struct RhythmRef {
 // Notes
 // ---------------------------------------------------------------------------
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct NotesWrapper {
     #[serde(rename = "Note", default)]
     pub notes: Vec<Note>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct Note {
     #[serde(rename = "@id", default)]
     pub id: i32,
     #[serde(rename = "Properties")]
     pub properties: NoteProperties,
-    #[serde(rename = "Tie", default)]
+    #[serde(rename = "Tie", default, skip_serializing_if = "Option::is_none")]
     pub tie: Option<TieInfo>,
-    #[serde(rename = "Vibrato", default)]
+    #[serde(rename = "Vibrato", default, skip_serializing_if = "Option::is_none")]
     pub vibrato: Option<String>,
-    #[serde(rename = "LetRing", default)]
+    #[serde(rename = "LetRing", default, skip_serializing_if = "Option::is_none")]
     pub let_ring: Option<EnableTag>,
-    #[serde(rename = "AntiAccent", default)]
+    #[serde(
+        rename = "AntiAccent",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
     pub anti_accent: Option<String>,
-    #[serde(rename = "Accent", default)]
+    #[serde(rename = "Accent", default, skip_serializing_if = "Option::is_none")]
     pub accent: Option<i32>,
-    #[serde(rename = "Trill", default)]
+    #[serde(rename = "Trill", default, skip_serializing_if = "Option::is_none")]
     pub trill: Option<i32>,
-    #[serde(rename = "Ornament", default)]
+    #[serde(rename = "Ornament", default, skip_serializing_if = "Option::is_none")]
     pub ornament: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct TieInfo {
     #[serde(rename = "@origin", default)]
     pub origin: String,
@@ -395,83 +771,210 @@ pub struct TieInfo {
 }
 
 /// An empty self-closing tag used as a presence flag (e.g., `<Enable />`, `<LetRing />`).
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct EnableTag;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct NoteProperties {
     #[serde(rename = "Property", default)]
     pub properties: Vec<Property>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct Property {
     #[serde(rename = "@name", default)]
     pub name: String,
     // Value sub-elements — each property uses at most one of these
-    #[serde(rename = "Fret", default)]
+    #[serde(rename = "Fret", default, skip_serializing_if = "Option::is_none")]
     pub fret: Option<i32>,
-    #[serde(rename = "String", default)]
+    #[serde(rename = "String", default, skip_serializing_if = "Option::is_none")]
     pub string: Option<i32>,
-    #[serde(rename = "Pitch", default)]
+    #[serde(rename = "Pitch", default, skip_serializing_if = "Option::is_none")]
     pub pitch: Option<Pitch>,
-    #[serde(rename = "Number", default)]
+    #[serde(rename = "Number", default, skip_serializing_if = "Option::is_none")]
     pub number: Option<i32>,
-    #[serde(rename = "Enable", default)]
+    #[serde(rename = "Enable", default, skip_serializing_if = "Option::is_none")]
     pub enable: Option<EnableTag>,
-    #[serde(rename = "Float", default)]
+    #[serde(rename = "Float", default, skip_serializing_if = "Option::is_none")]
     pub float: Option<f64>,
-    #[serde(rename = "Flags", default)]
+    #[serde(rename = "Flags", default, skip_serializing_if = "Option::is_none")]
     pub flags: Option<i32>,
-    #[serde(rename = "HFret", default)]
+    #[serde(rename = "HFret", default, skip_serializing_if = "Option::is_none")]
     pub hfret: Option<f64>,
-    #[serde(rename = "HType", default)]
+    #[serde(rename = "HType", default, skip_serializing_if = "Option::is_none")]
     pub htype: Option<String>,
-    #[serde(rename = "Pitches", default)]
+    #[serde(rename = "Pitches", default, skip_serializing_if = "Option::is_none")]
     pub pitches: Option<String>,
-    #[serde(rename = "Direction", default)]
+    #[serde(rename = "Direction", default, skip_serializing_if = "Option::is_none")]
     pub direction: Option<String>,
-}
-
-#[derive(Debug, Deserialize)]
+    /// GP6: semitone of a "Tone" property, used by notes without string and fret
+    #[serde(rename = "Step", default, skip_serializing_if = "Option::is_none")]
+    pub step: Option<i32>,
+    /// GP6: percussion element of the track instrument
+    #[serde(rename = "Element", default, skip_serializing_if = "Option::is_none")]
+    pub element: Option<i32>,
+    #[serde(rename = "Variation", default, skip_serializing_if = "Option::is_none")]
+    pub variation: Option<i32>,
+    /// Chord diagrams and names of a `DiagramCollection` or `ChordCollection` property
+    #[serde(rename = "Items", default, skip_serializing_if = "Option::is_none")]
+    pub items: Option<DiagramItems>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
 pub struct Pitch {
     #[serde(rename = "Step", default)]
     pub step: String,
     #[serde(rename = "Octave", default)]
     pub octave: i32,
-    #[serde(rename = "Accidental", default)]
+    #[serde(
+        rename = "Accidental",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
     pub accidental: Option<String>,
 }
 
+// ---------------------------------------------------------------------------
+// Chord diagrams
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct DiagramItems {
+    #[serde(rename = "Item", default)]
+    pub items: Vec<DiagramItem>,
+}
+
+/// A chord of the track, referenced by beats through its id
+#[derive(Debug, Deserialize, Serialize)]
+pub struct DiagramItem {
+    #[serde(rename = "@id", default)]
+    pub id: i32,
+    #[serde(rename = "@name", default)]
+    pub name: String,
+    #[serde(rename = "Diagram", default, skip_serializing_if = "Option::is_none")]
+    pub diagram: Option<Diagram>,
+    #[serde(rename = "Chord", default, skip_serializing_if = "Option::is_none")]
+    pub chord: Option<DiagramChord>,
+}
+
+/// Fret diagram, strings are numbered from 0 (lowest) and frets other than 0 (open) are
+/// relative to `baseFret`
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Diagram {
+    #[serde(rename = "@stringCount", default)]
+    pub string_count: i32,
+    #[serde(rename = "@fretCount", default)]
+    pub fret_count: i32,
+    #[serde(rename = "@baseFret", default)]
+    pub base_fret: i32,
+    #[serde(rename = "Fret", default)]
+    pub frets: Vec<DiagramFret>,
+    #[serde(rename = "Fingering", default, skip_serializing_if = "Option::is_none")]
+    pub fingering: Option<DiagramFingering>,
+    #[serde(rename = "Property", default)]
+    pub properties: Vec<DiagramProperty>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct DiagramFret {
+    #[serde(rename = "@string", default)]
+    pub string: i32,
+    #[serde(rename = "@fret", default)]
+    pub fret: i32,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct DiagramFingering {
+    #[serde(rename = "Position", default)]
+    pub positions: Vec<FingerPosition>,
+}
+
+/// Finger of a diagram string, unused strings have the fret 4294967295
+#[derive(Debug, Deserialize, Serialize)]
+pub struct FingerPosition {
+    #[serde(rename = "@finger", default)]
+    pub finger: String,
+    #[serde(rename = "@fret", default)]
+    pub fret: i64,
+    #[serde(rename = "@string", default)]
+    pub string: i32,
+}
+
+/// Diagram display flag (ShowName, ShowDiagram, ShowFingering)
+#[derive(Debug, Deserialize, Serialize)]
+pub struct DiagramProperty {
+    #[serde(rename = "@name", default)]
+    pub name: String,
+    #[serde(rename = "@value", default)]
+    pub value: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct DiagramChord {
+    #[serde(rename = "KeyNote", default, skip_serializing_if = "Option::is_none")]
+    pub key_note: Option<ChordNote>,
+    #[serde(rename = "BassNote", default, skip_serializing_if = "Option::is_none")]
+    pub bass_note: Option<ChordNote>,
+    #[serde(rename = "Degree", default)]
+    pub degrees: Vec<ChordDegree>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ChordNote {
+    #[serde(rename = "@step", default)]
+    pub step: String,
+    #[serde(rename = "@accidental", default)]
+    pub accidental: String,
+}
+
+/// Interval of the chord, e.g. `Third`/`Minor` or `Fifth`/`Diminished`
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ChordDegree {
+    #[serde(rename = "@interval", default)]
+    pub interval: String,
+    #[serde(rename = "@alteration", default)]
+    pub alteration: String,
+    #[serde(rename = "@omitted", default)]
+    pub omitted: bool,
+}
+
 // ---------------------------------------------------------------------------
 // Rhythms
 // ---------------------------------------------------------------------------
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct RhythmsWrapper {
     #[serde(rename = "Rhythm", default)]
     pub rhythms: Vec<Rhythm>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct Rhythm {
     #[serde(rename = "@id", default)]
     pub id: i32,
     #[serde(rename = "NoteValue", default)]
     pub note_value: String,
-    #[serde(rename = "AugmentationDot", default)]
+    #[serde(
+        rename = "AugmentationDot",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
     pub augmentation_dot: Option<AugmentationDot>,
-    #[serde(rename = "PrimaryTuplet", default)]
+    #[serde(
+        rename = "PrimaryTuplet",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
     pub primary_tuplet: Option<PrimaryTuplet>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct AugmentationDot {
     #[serde(rename = "@count", default)]
     pub count: i32,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct PrimaryTuplet {
     #[serde(rename = "@num", default)]
     pub num: i32,
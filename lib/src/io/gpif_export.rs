@@ -0,0 +1,1423 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::audio::midi::CHANNEL_DEFAULT_NAMES;
+use crate::io::gpif::*;
+use crate::model::{
+    beat::Beat as SongBeat,
+    chord::Chord,
+    chord::PitchClass,
+    effects::*,
+    enums::*,
+    headers::MeasureHeader,
+    key_signature::*,
+    mix_table::{MixTableChange, MixTableItem},
+    note::Note as SongNote,
+    rse::RseEffect,
+    song::*,
+    track::Track as SongTrack,
+};
+
+pub trait SongGpifExportOps {
+    fn write_gpif(&self, version: (u8, u8, u8)) -> Gpif;
+}
+
+// ---------------------------------------------------------------------------
+// Helper functions
+// ---------------------------------------------------------------------------
+
+/// Convert Duration.value to a GPIF note value string.
+fn duration_to_note_value(value: u16) -> &'static str {
+    match value {
+        1 => "Whole",
+        2 => "Half",
+        8 => "Eighth",
+        16 => "16th",
+        32 => "32nd",
+        64 => "64th",
+        128 => "128th",
+        _ => "Quarter",
+    }
+}
+
+/// Convert a MIDI velocity to the closest GPIF dynamic string.
+fn velocity_to_dynamic(velocity: i16) -> &'static str {
+    const DYNAMICS: [&str; 8] = ["PPP", "PP", "P", "MP", "MF", "F", "FF", "FFF"];
+    let step = ((velocity - MIN_VELOCITY) as f32 / VELOCITY_INCREMENT as f32).round();
+    DYNAMICS[step.clamp(0.0, 7.0) as usize]
+}
+
+/// Build the slide flags bitmask, see `parse_slide_flags` in the GPIF reader for the encoding.
+fn slide_flags(slides: &[SlideType]) -> i32 {
+    slides.iter().fold(0, |flags, slide| {
+        flags
+            | match slide {
+                SlideType::ShiftSlideTo => 0x01,
+                SlideType::LegatoSlideTo => 0x02,
+                SlideType::OutDownwards => 0x04,
+                SlideType::OutUpWards => 0x08,
+                SlideType::IntoFromBelow => 0x10,
+                SlideType::IntoFromAbove => 0x20,
+                SlideType::None => 0,
+            }
+    })
+}
+
+fn harmonic_type_name(kind: &HarmonicType) -> &'static str {
+    match kind {
+        HarmonicType::Natural => "Natural",
+        HarmonicType::Artificial => "Artificial",
+        HarmonicType::Tapped => "Tap",
+        HarmonicType::Pinch => "Pinch",
+        HarmonicType::Semi => "Semi",
+    }
+}
+
+/// Convert a direction sign to its GPIF name. The boolean is `true` for targets
+/// (Coda, Segno, Fine...) and `false` for jumps (Da Capo, Da Segno...).
+fn direction_sign_name(sign: &DirectionSign) -> (&'static str, bool) {
+    match sign {
+        DirectionSign::Coda => ("Coda", true),
+        DirectionSign::DoubleCoda => ("DoubleCoda", true),
+        DirectionSign::Segno => ("Segno", true),
+        DirectionSign::SegnoSegno => ("SegnoSegno", true),
+        DirectionSign::Fine => ("Fine", true),
+        DirectionSign::DaCapo => ("DaCapo", false),
+        DirectionSign::DaCapoAlCoda => ("DaCapoAlCoda", false),
+        DirectionSign::DaCapoAlDoubleCoda => ("DaCapoAlDoubleCoda", false),
+        DirectionSign::DaCapoAlFine => ("DaCapoAlFine", false),
+        DirectionSign::DaSegno => ("DaSegno", false),
+        DirectionSign::DaSegnoAlCoda => ("DaSegnoAlCoda", false),
+        DirectionSign::DaSegnoAlDoubleCoda => ("DaSegnoAlDoubleCoda", false),
+        DirectionSign::DaSegnoAlFine => ("DaSegnoAlFine", false),
+        DirectionSign::DaSegnoSegno => ("DaSegnoSegno", false),
+        DirectionSign::DaSegnoSegnoAlCoda => ("DaSegnoSegnoAlCoda", false),
+        DirectionSign::DaSegnoSegnoAlDoubleCoda => ("DaSegnoSegnoAlDoubleCoda", false),
+        DirectionSign::DaSegnoSegnoAlFine => ("DaSegnoSegnoAlFine", false),
+        DirectionSign::DaCoda => ("DaCoda", false),
+        DirectionSign::DaDoubleCoda => ("DaDoubleCoda", false),
+    }
+}
+
+/// Convert a tremolo picking duration to the GPIF speed string.
+fn tremolo_speed(value: u16) -> &'static str {
+    match value {
+        16 => "1/4",
+        32 => "1/8",
+        _ => "1/2",
+    }
+}
+
+/// Origin, middle and destination values (in 1/100 semitone) of a bend curve.
+fn bend_values(bend: &BendEffect) -> (f64, f64, f64) {
+    let value = |p: Option<&BendPoint>| p.map_or(0.0, |p| p.value as f64 * GP_BEND_SEMITONE as f64);
+    let origin = value(bend.points.first());
+    let destination = value(bend.points.last());
+    let middle = (origin + destination) / 2.0;
+    (origin, middle, destination)
+}
+
+fn property(name: &str) -> Property {
+    Property {
+        name: name.to_string(),
+        fret: None,
+        string: None,
+        pitch: None,
+        number: None,
+        enable: None,
+        float: None,
+        flags: None,
+        hfret: None,
+        htype: None,
+        pitches: None,
+        direction: None,
+        step: None,
+        element: None,
+        variation: None,
+        items: None,
+    }
+}
+
+fn enabled_property(name: &str) -> Property {
+    Property {
+        enable: Some(EnableTag),
+        ..property(name)
+    }
+}
+
+fn float_property(name: &str, value: f64) -> Property {
+    Property {
+        float: Some(value),
+        ..property(name)
+    }
+}
+
+fn beat_property(name: &str) -> BeatProperty {
+    BeatProperty {
+        name: name.to_string(),
+        direction: None,
+        enable: None,
+        float: None,
+        flags: None,
+    }
+}
+
+fn ottavia_name(octave: &Octave) -> Option<&'static str> {
+    match octave {
+        Octave::None => None,
+        Octave::Ottava => Some("8va"),
+        Octave::OttavaBassa => Some("8vb"),
+        Octave::Quindicesima => Some("15ma"),
+        Octave::QuindicesimaBassa => Some("15mb"),
+    }
+}
+
+fn stroke_direction_name(direction: &BeatStrokeDirection) -> Option<String> {
+    match direction {
+        BeatStrokeDirection::Down => Some("Down".to_string()),
+        BeatStrokeDirection::Up => Some("Up".to_string()),
+        BeatStrokeDirection::None => None,
+    }
+}
+
+fn join_ids(ids: &[i32]) -> String {
+    ids.iter()
+        .map(|id| id.to_string())
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// Capo, fret count and tuning of a staff. GPIF lists the pitches from the lowest string.
+fn staff_properties(strings: &[(i8, i8)], offset: i32, fret_count: u8) -> Vec<Property> {
+    let mut strings = strings.to_vec();
+    strings.sort_by_key(|s| s.0);
+    let pitches: Vec<String> = strings.iter().rev().map(|s| s.1.to_string()).collect();
+    vec![
+        Property {
+            fret: Some(offset),
+            ..property("CapoFret")
+        },
+        Property {
+            number: Some(fret_count as i32),
+            ..property("FretCount")
+        },
+        Property {
+            pitches: Some(pitches.join(" ")),
+            ..property("Tuning")
+        },
+    ]
+}
+
+/// Distinct chords of a track and its staves, beats refer to them by their position.
+fn track_chords(track: &SongTrack) -> Vec<&Chord> {
+    let mut chords: Vec<&Chord> = Vec::new();
+    let beats = track
+        .measures
+        .iter()
+        .flat_map(|m| {
+            m.voices
+                .iter()
+                .chain(m.staves.iter().flat_map(|s| &s.voices))
+        })
+        .flat_map(|v| &v.beats);
+    for chord in beats.filter_map(|b| b.effect.chord.as_ref()) {
+        if !chords.contains(&chord) {
+            chords.push(chord);
+        }
+    }
+    chords
+}
+
+fn diagram_item(id: usize, chord: &Chord) -> DiagramItem {
+    DiagramItem {
+        id: id as i32,
+        name: chord.name.clone(),
+        diagram: chord
+            .first_fret
+            .map(|first_fret| chord_diagram(chord, first_fret)),
+        chord: (chord.root.is_some() || chord.bass.is_some() || chord.kind.is_some()).then(|| {
+            DiagramChord {
+                key_note: chord.root.as_ref().map(chord_note),
+                bass_note: chord.bass.as_ref().map(chord_note),
+                degrees: chord_degrees(chord),
+            }
+        }),
+    }
+}
+
+/// Fret diagram of a chord, the frets are written relative to the base fret.
+fn chord_diagram(chord: &Chord, first_fret: u8) -> Diagram {
+    let string_count = chord.strings.len();
+    let lowest = chord.strings.iter().copied().filter(|f| *f > 0).min();
+    let base_fret = (i32::from(first_fret) - 1)
+        .min(lowest.map_or(i32::MAX, |f| i32::from(f) - 1))
+        .max(0);
+    let relative = |fret: i8| match fret {
+        0 => 0,
+        f => i32::from(f) - base_fret,
+    };
+    // Diagram strings are numbered from the lowest one
+    let frets = chord
+        .strings
+        .iter()
+        .enumerate()
+        .rev()
+        .filter(|(_, fret)| **fret >= 0)
+        .map(|(i, &fret)| DiagramFret {
+            string: (string_count - 1 - i) as i32,
+            fret: relative(fret),
+        })
+        .collect();
+    let fingering = (!chord.fingerings.is_empty()).then(|| DiagramFingering {
+        positions: chord
+            .strings
+            .iter()
+            .enumerate()
+            .rev()
+            .map(|(i, &fret)| FingerPosition {
+                finger: match chord.fingerings.get(i) {
+                    Some(Fingering::Thumb) => "Thumb",
+                    Some(Fingering::Index) => "Index",
+                    Some(Fingering::Middle) => "Middle",
+                    Some(Fingering::Annular) => "Ring",
+                    Some(Fingering::Little) => "Pinky",
+                    _ => "None",
+                }
+                .to_string(),
+                fret: if fret < 0 {
+                    i64::from(u32::MAX)
+                } else {
+                    relative(fret).into()
+                },
+                string: (string_count - 1 - i) as i32,
+            })
+            .collect(),
+    });
+    Diagram {
+        string_count: string_count as i32,
+        fret_count: 5,
+        base_fret,
+        frets,
+        fingering,
+        properties: chord
+            .show
+            .map(|show| DiagramProperty {
+                name: "ShowFingering".to_string(),
+                value: show.to_string(),
+            })
+            .into_iter()
+            .collect(),
+    }
+}
+
+fn chord_note(pitch: &PitchClass) -> ChordNote {
+    const STEPS: [&str; 12] = ["C", "C", "D", "D", "E", "F", "F", "G", "G", "A", "A", "B"];
+    let accidental = match pitch.accidental {
+        2 => "DoubleSharp",
+        1 => "Sharp",
+        -1 => "Flat",
+        -2 => "DoubleFlat",
+        _ => "Natural",
+    };
+    ChordNote {
+        step: STEPS[usize::try_from(pitch.just.rem_euclid(12)).unwrap_or(0)].to_string(),
+        accidental: accidental.to_string(),
+    }
+}
+
+/// Intervals of a chord, the reverse of what `read_gpif` derives the chord type from.
+fn chord_degrees(chord: &Chord) -> Vec<ChordDegree> {
+    let Some(kind) = &chord.kind else {
+        return Vec::new();
+    };
+    let alteration_name = |alteration: &ChordAlteration, perfect: &'static str| match alteration {
+        ChordAlteration::Perfect => perfect,
+        ChordAlteration::Diminished if perfect == "Major" => "Minor",
+        ChordAlteration::Diminished => "Diminished",
+        ChordAlteration::Augmented => "Augmented",
+    };
+    let third = match kind {
+        ChordType::Minor
+        | ChordType::MinorSeventh
+        | ChordType::MinorMajor
+        | ChordType::MinorSixth
+        | ChordType::Diminished => Some("Minor"),
+        ChordType::SuspendedSecond
+        | ChordType::SuspendedFourth
+        | ChordType::SeventhSuspendedSecond
+        | ChordType::SeventhSuspendedFourth
+        | ChordType::Power => None,
+        _ => Some("Major"),
+    };
+    let fifth = match kind {
+        ChordType::Diminished => Some("Diminished"),
+        ChordType::Augmented => Some("Augmented"),
+        _ => chord.fifth.as_ref().map(|a| alteration_name(a, "Perfect")),
+    };
+    let seventh = match kind {
+        ChordType::Seventh
+        | ChordType::MinorSeventh
+        | ChordType::SeventhSuspendedSecond
+        | ChordType::SeventhSuspendedFourth => Some("Minor"),
+        ChordType::MajorSeventh | ChordType::MinorMajor => Some("Major"),
+        _ => None,
+    };
+    let extension = match chord.extension {
+        Some(ChordExtension::Ninth) => 1,
+        Some(ChordExtension::Eleventh) => 2,
+        Some(ChordExtension::Thirteenth) => 3,
+        _ => 0,
+    };
+    let perfect = ChordAlteration::Perfect;
+    let degrees = [
+        (
+            "Second",
+            matches!(
+                kind,
+                ChordType::SuspendedSecond | ChordType::SeventhSuspendedSecond
+            )
+            .then_some("Major"),
+        ),
+        ("Third", third),
+        (
+            "Fourth",
+            matches!(
+                kind,
+                ChordType::SuspendedFourth | ChordType::SeventhSuspendedFourth
+            )
+            .then_some("Perfect"),
+        ),
+        ("Fifth", fifth),
+        (
+            "Sixth",
+            matches!(kind, ChordType::Sixth | ChordType::MinorSixth).then_some("Major"),
+        ),
+        ("Seventh", seventh),
+        (
+            "Ninth",
+            (extension >= 1)
+                .then(|| alteration_name(chord.ninth.as_ref().unwrap_or(&perfect), "Major")),
+        ),
+        (
+            "Eleventh",
+            (extension >= 2)
+                .then(|| alteration_name(chord.eleventh.as_ref().unwrap_or(&perfect), "Perfect")),
+        ),
+        ("Thirteenth", (extension >= 3).then_some("Major")),
+    ];
+    degrees
+        .into_iter()
+        .filter_map(|(interval, alteration)| {
+            alteration.map(|alteration| ChordDegree {
+                interval: interval.to_string(),
+                alteration: alteration.to_string(),
+                omitted: false,
+            })
+        })
+        .collect()
+}
+
+/// Value of an automation from a tick on, GPIF ramps linearly to the next value when `linear` is set.
+#[derive(Clone, Copy)]
+struct AutomationPoint {
+    value: f64,
+    linear: bool,
+    visible: bool,
+}
+
+/// Mix table change of a beat: tick, value, duration of the ramp in ticks and visibility.
+type BeatChange = (i64, f64, i64, bool);
+
+/// Points of one automation type: the `fixed` values, then the changes of the beats laid over them.
+/// A change with a duration ramps from the value in effect to its own value, the ramp ends on the
+/// next point or on a point of its own.
+fn automation_points(
+    fixed: Vec<(i64, f64)>,
+    visible: bool,
+    changes: &[BeatChange],
+) -> BTreeMap<i64, AutomationPoint> {
+    let point = |value: f64, visible: bool| AutomationPoint {
+        value,
+        linear: false,
+        visible,
+    };
+    let mut points: BTreeMap<i64, AutomationPoint> = fixed
+        .into_iter()
+        .map(|(tick, value)| (tick, point(value, visible)))
+        .collect();
+    for &(tick, value, _, visible) in changes.iter().filter(|c| c.2 == 0) {
+        points.insert(tick, point(value, visible));
+    }
+    for &(tick, value, duration, visible) in changes.iter().filter(|c| c.2 > 0) {
+        let current = points
+            .range(..=tick)
+            .next_back()
+            .map_or(value, |(_, p)| p.value);
+        points.entry(tick).or_insert(point(current, visible)).linear = true;
+        if points.range(tick + 1..=tick + duration).next().is_none() {
+            points.insert(tick + duration, point(value, visible));
+        }
+    }
+    points
+}
+
+// ---------------------------------------------------------------------------
+// Writer state
+// ---------------------------------------------------------------------------
+
+/// Collects the flat GPIF collections while walking the song, assigning ids as it goes.
+#[derive(Default)]
+struct GpifCollector {
+    bars: Vec<Bar>,
+    voices: Vec<Voice>,
+    beats: Vec<Beat>,
+    notes: Vec<Note>,
+    rhythms: Vec<Rhythm>,
+    rhythm_ids: HashMap<(u16, bool, bool, u8, u8), i32>,
+}
+
+impl GpifCollector {
+    fn rhythm(&mut self, duration: &Duration) -> i32 {
+        let key = (
+            duration.value,
+            duration.dotted,
+            duration.double_dotted,
+            duration.tuplet_enters,
+            duration.tuplet_times,
+        );
+        if let Some(id) = self.rhythm_ids.get(&key) {
+            return *id;
+        }
+        let id = self.rhythms.len() as i32;
+        let dots = if duration.double_dotted {
+            2
+        } else if duration.dotted {
+            1
+        } else {
+            0
+        };
+        let tuplet = (duration.tuplet_enters, duration.tuplet_times) != (1, 1)
+            && duration.tuplet_enters > 0
+            && duration.tuplet_times > 0;
+        self.rhythms.push(Rhythm {
+            id,
+            note_value: duration_to_note_value(duration.value).to_string(),
+            augmentation_dot: (dots > 0).then_some(AugmentationDot { count: dots }),
+            primary_tuplet: tuplet.then_some(PrimaryTuplet {
+                num: duration.tuplet_enters as i32,
+                den: duration.tuplet_times as i32,
+            }),
+        });
+        self.rhythm_ids.insert(key, id);
+        id
+    }
+
+    fn push_beat(&mut self, mut beat: Beat, notes: Vec<Note>) -> i32 {
+        let mut note_ids: Vec<i32> = Vec::with_capacity(notes.len());
+        for mut note in notes {
+            note.id = self.notes.len() as i32;
+            note_ids.push(note.id);
+            self.notes.push(note);
+        }
+        beat.id = self.beats.len() as i32;
+        beat.notes = (!note_ids.is_empty()).then(|| join_ids(&note_ids));
+        self.beats.push(beat);
+        self.beats.len() as i32 - 1
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Main conversion
+// ---------------------------------------------------------------------------
+
+impl SongGpifExportOps for Song {
+    /// Build the GPIF document of the song. `version` selects the GP6 (`(6, x, x)`) or the GP7 flavour
+    /// of the track description, the rest of the document is shared.
+    fn write_gpif(&self, version: (u8, u8, u8)) -> Gpif {
+        let gp7 = version.0 >= 7;
+        let mut collector = GpifCollector::default();
+
+        // Bars, voices, beats and notes, measure by measure so that the bar ids follow the master bars
+        let measure_count = self
+            .tracks
+            .iter()
+            .map(|t| t.measures.len())
+            .max()
+            .unwrap_or(0)
+            .max(self.measure_headers.len());
+        let mut master_bar_ids: Vec<Vec<i32>> = vec![Vec::new(); measure_count];
+        for track in &self.tracks {
+            // GP6 has a single staff per track
+            let staff_tracks: Vec<SongTrack> = if gp7 {
+                track.staves.iter().map(|s| track.staff_view(s)).collect()
+            } else {
+                Vec::new()
+            };
+            // Last note seen on each string of each voice of each staff, used to flag tie origins
+            let mut last_notes: Vec<HashMap<(usize, i8), usize>> =
+                vec![HashMap::new(); staff_tracks.len() + 1];
+            let mut velocities = vec![FORTE; staff_tracks.len() + 1];
+            let chords = track_chords(track);
+            for (m_idx, bar_ids) in master_bar_ids.iter_mut().enumerate() {
+                let measure = track.measures.get(m_idx);
+                for staff in 0..=staff_tracks.len() {
+                    let (staff_track, voices, clef) = match staff {
+                        0 => (track, measure.map(|m| &m.voices), measure.map(|m| &m.clef)),
+                        _ => {
+                            let m_staff = measure.and_then(|m| m.staves.get(staff - 1));
+                            let (voices, clef) =
+                                (m_staff.map(|s| &s.voices), m_staff.map(|s| &s.clef));
+                            (&staff_tracks[staff - 1], voices, clef)
+                        }
+                    };
+                    let last_notes = &mut last_notes[staff];
+                    let velocity = &mut velocities[staff];
+                    bar_ids.push(collector.bars.len() as i32);
+                    let bar_id = collector.bars.len() as i32;
+                    let mut voice_ids = [-1i32; 4];
+                    for (v_idx, voice) in voices.into_iter().flatten().take(4).enumerate() {
+                        if voice.beats.is_empty() {
+                            continue;
+                        }
+                        let mut beat_ids: Vec<i32> = Vec::with_capacity(voice.beats.len());
+                        for beat in &voice.beats {
+                            if let Some((g_beat, g_notes)) =
+                                self.attached_grace_beat(staff_track, beat, &mut collector)
+                            {
+                                beat_ids.push(collector.push_beat(g_beat, g_notes));
+                            }
+                            let (mut g_beat, g_notes) =
+                                self.convert_beat(staff_track, beat, velocity, &mut collector);
+                            g_beat.chord = beat
+                                .effect
+                                .chord
+                                .as_ref()
+                                .and_then(|c| chords.iter().position(|x| *x == c))
+                                .map(|id| id.to_string());
+                            let first_note = collector.notes.len();
+                            beat_ids.push(collector.push_beat(g_beat, g_notes));
+                            for (n_idx, note) in beat.notes.iter().enumerate() {
+                                let key = (v_idx, note.string);
+                                if note.kind == NoteType::Tie {
+                                    if let Some(&prev) = last_notes.get(&key) {
+                                        let tie =
+                                            collector.notes[prev].tie.get_or_insert(TieInfo {
+                                                origin: String::new(),
+                                                destination: "false".to_string(),
+                                            });
+                                        tie.origin = "true".to_string();
+                                    }
+                                }
+                                last_notes.insert(key, first_note + n_idx);
+                            }
+                        }
+                        voice_ids[v_idx] = collector.voices.len() as i32;
+                        collector.voices.push(Voice {
+                            id: voice_ids[v_idx],
+                            beats: join_ids(&beat_ids),
+                        });
+                    }
+                    collector.bars.push(Bar {
+                        id: bar_id,
+                        voices: join_ids(&voice_ids),
+                        clef: Some(
+                            match clef {
+                                Some(MeasureClef::Bass) => "F4",
+                                Some(MeasureClef::Tenor) => "C4",
+                                Some(MeasureClef::Alto) => "C3",
+                                _ if track.percussion_track => "Neutral",
+                                _ => "G2",
+                            }
+                            .to_string(),
+                        ),
+                        simile_mark: measure.and_then(|m| m.simile_mark.clone()),
+                    });
+                }
+            }
+        }
+
+        let master_bars = master_bar_ids
+            .iter()
+            .enumerate()
+            .map(|(m_idx, bar_ids)| {
+                let header = self.measure_headers.get(m_idx).cloned().unwrap_or_default();
+                write_master_bar(&header, bar_ids)
+            })
+            .collect();
+
+        Gpif {
+            version: gp7.then(|| "7".to_string()),
+            revision: Some(if gp7 { "12021" } else { "11621" }.to_string()),
+            score: Score {
+                title: self.name.clone(),
+                sub_title: self.subtitle.clone(),
+                artist: self.artist.clone(),
+                album: self.album.clone(),
+                words: self.words.clone(),
+                music: if self.author.is_empty() {
+                    self.writer.clone()
+                } else {
+                    self.author.clone()
+                },
+                copyright: self.copyright.clone(),
+                tabber: self.transcriber.clone(),
+                instructions: if self.comments.is_empty() {
+                    self.instructions.clone()
+                } else {
+                    self.comments.clone()
+                },
+                notices: self.notice.join("\n"),
+            },
+            master_track: MasterTrack {
+                tracks_count: join_ids(&(0..self.tracks.len() as i32).collect::<Vec<i32>>()),
+                automations: Some(AutomationsWrapper {
+                    automations: self.tempo_automations(),
+                }),
+            },
+            tracks: TracksWrapper {
+                tracks: self
+                    .tracks
+                    .iter()
+                    .enumerate()
+                    .map(|(t_idx, track)| self.write_gpif_track(t_idx, track, gp7))
+                    .collect(),
+            },
+            master_bars: MasterBarsWrapper { master_bars },
+            bars: BarsWrapper {
+                bars: collector.bars,
+            },
+            voices: VoicesWrapper {
+                voices: collector.voices,
+            },
+            beats: BeatsWrapper {
+                beats: collector.beats,
+            },
+            notes: NotesWrapper {
+                notes: collector.notes,
+            },
+            rhythms: RhythmsWrapper {
+                rhythms: collector.rhythms,
+            },
+            // The source document can only be merged back into a document of the same flavour
+            source: self
+                .gpif_source
+                .clone()
+                .filter(|_| (self.version.number.0 >= 7) == gp7),
+        }
+    }
+}
+
+fn empty_beat() -> Beat {
+    Beat {
+        id: 0,
+        chord: None,
+        notes: None,
+        rhythm: None,
+        dynamic: None,
+        hairpin: None,
+        grace_notes: None,
+        fadding: None,
+        whammy: None,
+        tremolo: None,
+        wah: None,
+        ottavia: None,
+        free_text: None,
+        properties: None,
+    }
+}
+
+fn empty_note() -> Note {
+    Note {
+        id: 0,
+        properties: NoteProperties {
+            properties: Vec::new(),
+        },
+        tie: None,
+        vibrato: None,
+        let_ring: None,
+        anti_accent: None,
+        accent: None,
+        trill: None,
+        ornament: None,
+    }
+}
+
+/// Effect slots of a chain, the reverse of `rse_effects` in the GPIF reader.
+fn effect_slots(effects: &[RseEffect]) -> Vec<Effect> {
+    effects
+        .iter()
+        .map(|e| Effect {
+            id: (!e.id.is_empty()).then(|| e.id.clone()),
+            parameters: (!e.parameters.is_empty()).then(|| {
+                let values: Vec<String> = e.parameters.iter().map(f32::to_string).collect();
+                values.join(" ")
+            }),
+        })
+        .collect()
+}
+
+/// GP7 sound of a track: the RSE sound when the track has one, its MIDI instrument otherwise.
+fn track_sound(track: &SongTrack, program: i32) -> Sound {
+    let rse = &track.rse;
+    let midi = Some(SoundMidi {
+        lsb: 0,
+        msb: 0,
+        program,
+    });
+    if rse.instrument.effect.is_empty() {
+        let instrument_name = CHANNEL_DEFAULT_NAMES[program as usize].to_string();
+        return Sound {
+            name: instrument_name.clone(),
+            label: instrument_name.clone(),
+            path: format!("Midi/{}", instrument_name),
+            role: "User".to_string(),
+            midi,
+            rse: None,
+        };
+    }
+    Sound {
+        name: rse.instrument.effect.clone(),
+        label: rse.instrument.effect.clone(),
+        path: rse.instrument.effect_category.clone(),
+        role: if rse.sound_role.is_empty() {
+            "User".to_string()
+        } else {
+            rse.sound_role.clone()
+        },
+        midi,
+        rse: (!rse.sound_bank_patch.is_empty() || !rse.effects.is_empty()).then(|| SoundRse {
+            soundbank_patch: Some(rse.sound_bank_patch.clone()),
+            effect_chain: Some(EffectChain {
+                name: None,
+                rail: None,
+                effects: effect_slots(&rse.effects),
+            }),
+        }),
+    }
+}
+
+/// GP6 built-in instrument name, e.g. "e-gtr6" or "e-bass4".
+fn instrument_ref(track: &SongTrack, program: i32) -> String {
+    if track.percussion_track {
+        "drumkit".to_string()
+    } else if (32..40).contains(&program) {
+        format!("e-bass{}", track.strings.len())
+    } else {
+        format!("e-gtr{}", track.strings.len())
+    }
+}
+
+/// Minimal GP7 instrument definition: a pitched five lines staff, or a drum kit.
+fn instrument_set(track: &SongTrack, program: i32) -> InstrumentSet {
+    let (name, instrument_type, element) = if track.percussion_track {
+        ("Drums", "drumKit", ("Drums", "percussion"))
+    } else if (32..40).contains(&program) {
+        ("Electric Bass", "electricBass", ("Pitched", "pitched"))
+    } else {
+        ("Electric Guitar", "electricGuitar", ("Pitched", "pitched"))
+    };
+    InstrumentSet {
+        name: name.to_string(),
+        instrument_type: instrument_type.to_string(),
+        line_count: 5,
+        elements: Some(ElementsWrapper {
+            elements: vec![Element {
+                name: element.0.to_string(),
+                element_type: element.1.to_string(),
+                articulations: (!track.percussion_track).then(|| ArticulationsWrapper {
+                    articulations: vec![Articulation {
+                        name: String::new(),
+                        staff_line: 0,
+                        noteheads: "noteheadBlack noteheadHalf noteheadWhole".to_string(),
+                        technique_placement: "outside".to_string(),
+                        output_midi_number: 0,
+                    }],
+                }),
+            }],
+        }),
+    }
+}
+
+fn write_master_bar(header: &MeasureHeader, bar_ids: &[i32]) -> MasterBar {
+    let mut alternate_endings: Vec<String> = Vec::new();
+    for i in 0..8 {
+        if header.repeat_alternative & (1 << i) != 0 {
+            alternate_endings.push((i + 1).to_string());
+        }
+    }
+    let direction = header.direction.as_ref().map(direction_sign_name);
+    MasterBar {
+        source_index: header.gpif_index,
+        key: Some(Key {
+            accidental_count: header.key_signature.key as i32,
+            mode: if header.key_signature.is_minor {
+                "Minor"
+            } else {
+                "Major"
+            }
+            .to_string(),
+        }),
+        time: format!(
+            "{}/{}",
+            header.time_signature.numerator, header.time_signature.denominator.value
+        ),
+        bars: join_ids(bar_ids),
+        repeat: (header.repeat_open || header.repeat_close > 0).then(|| Repeat {
+            start: header.repeat_open.to_string(),
+            end: (header.repeat_close > 0).to_string(),
+            count: header.repeat_close.max(0) as i32,
+        }),
+        alternate_endings: (!alternate_endings.is_empty()).then(|| alternate_endings.join(" ")),
+        double_bar: header.double_bar.then(String::new),
+        section: header.marker.as_ref().map(|m| Section {
+            letter: None,
+            text: Some(m.title.clone()),
+        }),
+        fermatas: (!header.fermatas.is_empty()).then(|| FermatasWrapper {
+            fermatas: header
+                .fermatas
+                .iter()
+                .map(|(kind, offset)| Fermata {
+                    fermata_type: Some(kind.clone()),
+                    offset: Some(offset.clone()),
+                })
+                .collect(),
+        }),
+        free_time: header.free_time.then(String::new),
+        directions: direction.map(|(name, is_target)| DirectionsWrapper {
+            target: is_target.then(|| name.to_string()),
+            jump: (!is_target).then(|| name.to_string()),
+        }),
+    }
+}
+
+impl Song {
+    /// Tempo automations of the master track: the song tempo on the first bar, the measure headers
+    /// carrying a tempo, then the tempo changes of the beats of all tracks.
+    fn tempo_automations(&self) -> Vec<Automation> {
+        let starts = self.measure_starts();
+        let mut fixed = vec![(DURATION_QUARTER_TIME, f64::from(self.tempo))];
+        for (header, &start) in self.measure_headers.iter().zip(&starts).skip(1) {
+            if header.tempo > 0 {
+                fixed.push((start, f64::from(header.tempo)));
+            }
+        }
+        let changes: Vec<BeatChange> = self
+            .tracks
+            .iter()
+            .flat_map(|t| self.beat_mix_changes(t, &starts))
+            .filter_map(|(tick, mtc)| {
+                let tempo = mtc.tempo.as_ref().filter(|t| t.value > 0)?;
+                Some((
+                    tick,
+                    f64::from(tempo.value),
+                    i64::from(tempo.duration) * DURATION_QUARTER_TIME,
+                    !mtc.hide_tempo,
+                ))
+            })
+            .collect();
+        let points = automation_points(fixed, true, &changes);
+        self.automations("Tempo", points, &starts, |tempo| {
+            format!("{} 2", tempo.round())
+        })
+    }
+
+    /// Volume (`DSPParam_12`) and pan (`DSPParam_11`) automations of a track channel strip: the
+    /// channel settings at the start, then the mix table changes of the beats.
+    fn channel_strip_automations(&self, track: &SongTrack) -> Vec<Automation> {
+        let channel = self.channel_at(track.channel_index);
+        let starts = self.measure_starts();
+        let changes = self.beat_mix_changes(track, &starts);
+        let strip =
+            |kind: &str, initial: i8, item: fn(&MixTableChange) -> Option<&MixTableItem>| {
+                // Mix table values go from 0 to 16, channel values from 0 to 127
+                let changes: Vec<BeatChange> = changes
+                    .iter()
+                    .filter_map(|(tick, mtc)| {
+                        item(mtc).map(|i| {
+                            let duration = i64::from(i.duration) * DURATION_QUARTER_TIME;
+                            (*tick, f64::from(i.value) / 16.0, duration, false)
+                        })
+                    })
+                    .collect();
+                let fixed = vec![(DURATION_QUARTER_TIME, f64::from(initial) / 127.0)];
+                let points = automation_points(fixed, false, &changes);
+                self.automations(kind, points, &starts, |value| {
+                    ((value * 1000.0).round() / 1000.0).to_string()
+                })
+            };
+        let mut automations = strip("DSPParam_11", channel.balance, |m| m.balance.as_ref());
+        automations.extend(strip("DSPParam_12", channel.volume, |m| m.volume.as_ref()));
+        automations
+    }
+
+    /// Parameters of a track channel strip: three equalizer bands of frequency, gain and width,
+    /// switched on when a gain is set, then the pan and the volume of the channel.
+    fn channel_strip_parameters(&self, track: &SongTrack) -> String {
+        let channel = self.channel_at(track.channel_index);
+        let mut parameters = [0.5; 16];
+        let knobs = &track.rse.equalizer.knobs;
+        for (band, knob) in knobs.iter().take(3).enumerate() {
+            // Gains go from -12 to 12 dB
+            parameters[band * 3 + 1] = ((knob + 12.0) / 24.0).clamp(0.0, 1.0);
+        }
+        parameters[9] = if knobs.iter().any(|k| *k != 0.0) {
+            1.0
+        } else {
+            0.0
+        };
+        parameters[11] = f32::from(channel.balance) / 127.0;
+        parameters[12] = f32::from(channel.volume) / 127.0;
+        parameters
+            .iter()
+            .map(|p| ((p * 1000.0).round() / 1000.0).to_string())
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+
+    /// Start tick of each measure. Headers read from Guitar Pro 3-5 files keep a start of 0.
+    fn measure_starts(&self) -> Vec<i64> {
+        let mut starts = Vec::with_capacity(self.measure_headers.len());
+        let mut tick = DURATION_QUARTER_TIME;
+        for header in &self.measure_headers {
+            starts.push(tick);
+            tick += header.length();
+        }
+        starts
+    }
+
+    /// Mix table changes of the beats of a track, with their tick.
+    fn beat_mix_changes<'a>(
+        &self,
+        track: &'a SongTrack,
+        starts: &[i64],
+    ) -> Vec<(i64, &'a MixTableChange)> {
+        let mut changes = Vec::new();
+        for (measure, &start) in track.measures.iter().zip(starts) {
+            for voice in &measure.voices {
+                let mut tick = start;
+                for beat in &voice.beats {
+                    if let Some(mtc) = &beat.effect.mix_table_change {
+                        changes.push((tick, mtc));
+                    }
+                    tick += i64::from(beat.duration.time());
+                }
+            }
+        }
+        changes
+    }
+
+    fn automations(
+        &self,
+        kind: &str,
+        points: BTreeMap<i64, AutomationPoint>,
+        starts: &[i64],
+        value: impl Fn(f64) -> String,
+    ) -> Vec<Automation> {
+        points
+            .into_iter()
+            .map(|(tick, point)| {
+                let (bar, position) = self.automation_position(tick, starts);
+                Automation {
+                    automation_type: kind.to_string(),
+                    linear: Some(point.linear.to_string()),
+                    bar,
+                    position,
+                    visible: Some(point.visible.to_string()),
+                    value: value(point.value),
+                }
+            })
+            .collect()
+    }
+
+    /// Bar of a tick and its position in the bar, from 0 to 1.
+    fn automation_position(&self, tick: i64, starts: &[i64]) -> (i32, f64) {
+        let m_idx = starts.iter().rposition(|&s| s <= tick).unwrap_or(0);
+        match self.measure_headers.get(m_idx).zip(starts.get(m_idx)) {
+            Some((header, &start)) => {
+                let position = (tick - start) as f64 / header.length().max(1) as f64;
+                (m_idx as i32, position.clamp(0.0, 1.0))
+            }
+            None => (0, 0.0),
+        }
+    }
+
+    fn write_gpif_track(&self, t_idx: usize, track: &SongTrack, gp7: bool) -> Track {
+        let channel = self.channels.get(track.channel_index);
+        let program = track
+            .midi_program_gpif
+            .or_else(|| channel.map(|c| c.instrument))
+            .unwrap_or(25)
+            .clamp(0, 127);
+        // GPIF channels are numbered within their port, ports from 0
+        let primary_channel = channel.map_or(track.channel_index as i32, |c| c.channel as i32) % 16;
+        let secondary_channel = channel.map_or(primary_channel, |c| c.effect_channel as i32 % 16);
+        let port = i32::from(track.port.saturating_sub(1));
+
+        let mut properties = staff_properties(&track.strings, track.offset, track.fret_count);
+
+        // Chord names without a diagram are written as diagram items as well
+        let items = track_chords(track)
+            .into_iter()
+            .enumerate()
+            .map(|(id, chord)| diagram_item(id, chord))
+            .collect();
+        properties.push(Property {
+            items: Some(DiagramItems { items }),
+            ..property("DiagramCollection")
+        });
+        properties.push(Property {
+            items: Some(DiagramItems { items: Vec::new() }),
+            ..property("ChordCollection")
+        });
+
+        // GP6 keeps the tuning on the track, GP7 on its staves
+        let (track_properties, staves) = if gp7 {
+            let other_staves = track.staves.iter().map(|s| Staff {
+                properties: Some(StaffPropertiesWrapper {
+                    properties: staff_properties(&s.strings, s.offset, s.fret_count),
+                }),
+            });
+            let staff = Staff {
+                properties: Some(StaffPropertiesWrapper { properties }),
+            };
+            (
+                None,
+                Some(StavesWrapper {
+                    staves: std::iter::once(staff).chain(other_staves).collect(),
+                }),
+            )
+        } else {
+            (Some(TrackPropertiesWrapper { properties }), None)
+        };
+
+        Track {
+            id: t_idx as i32,
+            source_index: track.gpif_index,
+            name: track.name.clone(),
+            short_name: track.short_name.clone(),
+            color: Some(format!(
+                "{} {} {}",
+                (track.color >> 16) & 0xff,
+                (track.color >> 8) & 0xff,
+                track.color & 0xff
+            )),
+            properties: track_properties,
+            staves,
+            instrument: (!gp7).then(|| InstrumentRef {
+                r#ref: instrument_ref(track, program),
+            }),
+            instrument_set: gp7.then(|| instrument_set(track, program)),
+            sounds: gp7.then(|| SoundsWrapper {
+                sounds: vec![track_sound(track, program)],
+            }),
+            midi_connection: gp7.then_some(MidiConnection {
+                port: Some(port),
+                primary_channel: Some(primary_channel),
+                secondary_channel: Some(secondary_channel),
+            }),
+            general_midi: (!gp7).then_some(GeneralMidi {
+                program: Some(program),
+                port: Some(port),
+                primary_channel: Some(primary_channel),
+                secondary_channel: Some(secondary_channel),
+            }),
+            audio_engine_state: (gp7 && track.use_rse).then(|| "RSE".to_string()),
+            transpose: Some(Transpose {
+                chromatic: Some(track.transpose_chromatic),
+                octave: Some(track.transpose_octave),
+            }),
+            lyrics: (usize::from(self.lyrics.track_choice) == t_idx + 1)
+                .then(|| self.gpif_lyrics()),
+            rse: Some(TrackRseSettings {
+                channel_strip: Some(ChannelStrip {
+                    parameters: Some(self.channel_strip_parameters(track)),
+                    automations: Some(AutomationsWrapper {
+                        automations: self.channel_strip_automations(track),
+                    }),
+                }),
+                bank: (!gp7 && !track.rse.sound_bank_patch.is_empty())
+                    .then(|| track.rse.sound_bank_patch.clone()),
+                effect_chains: (!gp7 && !track.rse.instrument.effect.is_empty()).then(|| {
+                    EffectChains {
+                        chains: vec![EffectChain {
+                            name: Some(track.rse.instrument.effect.clone()),
+                            rail: Some(EffectRail {
+                                name: "FirstRail".to_string(),
+                                effects: effect_slots(&track.rse.effects),
+                            }),
+                            effects: Vec::new(),
+                        }],
+                    }
+                }),
+            }),
+            automations: None,
+        }
+    }
+
+    /// The five lyrics lines of the song, GPIF counts their start bar from 0.
+    fn gpif_lyrics(&self) -> TrackLyrics {
+        let lines = (0..5u8)
+            .map(|i| match self.lyrics.lines.iter().find(|l| l.0 == i) {
+                Some(l) => LyricsLine {
+                    text: l.2.clone(),
+                    offset: i32::from(l.1.max(1)) - 1,
+                },
+                None => LyricsLine {
+                    text: String::new(),
+                    offset: 0,
+                },
+            })
+            .collect();
+        TrackLyrics {
+            dispatched: Some("true".to_string()),
+            lines,
+        }
+    }
+
+    /// GP5 attaches grace notes to the main note while GPIF stores them in a beat of their own.
+    fn attached_grace_beat(
+        &self,
+        track: &SongTrack,
+        beat: &SongBeat,
+        collector: &mut GpifCollector,
+    ) -> Option<(Beat, Vec<Note>)> {
+        if beat.is_grace || beat.notes.iter().all(|n| n.effect.grace.is_none()) {
+            return None;
+        }
+        let string_count = track.strings.len() as i8;
+        let mut on_beat = false;
+        let mut velocity = None;
+        let notes = beat
+            .notes
+            .iter()
+            .filter_map(|n| n.effect.grace.as_ref().map(|g| (n, g)))
+            .map(|(n, grace)| {
+                on_beat |= grace.is_on_beat;
+                velocity.get_or_insert(grace.velocity);
+                let mut props = vec![
+                    Property {
+                        string: Some((string_count - n.string) as i32),
+                        ..property("String")
+                    },
+                    Property {
+                        fret: Some(grace.fret as i32),
+                        ..property("Fret")
+                    },
+                ];
+                if grace.is_dead {
+                    props.push(enabled_property("Muted"));
+                }
+                match grace.transition {
+                    GraceEffectTransition::Hammer => props.push(enabled_property("HopoOrigin")),
+                    GraceEffectTransition::Slide => props.push(Property {
+                        flags: Some(0x02),
+                        ..property("Slide")
+                    }),
+                    _ => {}
+                }
+                Note {
+                    properties: NoteProperties { properties: props },
+                    ..empty_note()
+                }
+            })
+            .collect();
+        let g_beat = Beat {
+            rhythm: Some(RhythmRef {
+                r#ref: collector.rhythm(&Duration {
+                    value: DURATION_THIRTY_SECOND as u16,
+                    ..Default::default()
+                }),
+            }),
+            dynamic: Some(velocity_to_dynamic(velocity.unwrap_or(FORTE)).to_string()),
+            grace_notes: Some(if on_beat { "OnBeat" } else { "BeforeBeat" }.to_string()),
+            ..empty_beat()
+        };
+        Some((g_beat, notes))
+    }
+
+    fn convert_beat(
+        &self,
+        track: &SongTrack,
+        beat: &SongBeat,
+        velocity: &mut i16,
+        collector: &mut GpifCollector,
+    ) -> (Beat, Vec<Note>) {
+        if let Some(first) = beat.notes.first() {
+            *velocity = first.velocity;
+        }
+        let mut g_beat = Beat {
+            rhythm: Some(RhythmRef {
+                r#ref: collector.rhythm(&beat.duration),
+            }),
+            dynamic: Some(velocity_to_dynamic(*velocity).to_string()),
+            ..empty_beat()
+        };
+        if beat.is_grace {
+            let on_beat = beat.notes[0]
+                .effect
+                .grace
+                .as_ref()
+                .is_some_and(|g| g.is_on_beat);
+            g_beat.grace_notes = Some(if on_beat { "OnBeat" } else { "BeforeBeat" }.to_string());
+        }
+        if !beat.text.is_empty() {
+            g_beat.free_text = Some(beat.text.clone());
+        }
+        if beat.effect.fade_in {
+            g_beat.fadding = Some("FadeIn".to_string());
+        }
+        g_beat.ottavia = ottavia_name(&beat.octave).map(str::to_string);
+        g_beat.hairpin = beat.hairpin.clone();
+
+        let mut props: Vec<BeatProperty> = Vec::new();
+        if let Some(direction) = stroke_direction_name(&beat.effect.stroke.direction) {
+            props.push(BeatProperty {
+                direction: Some(direction),
+                ..beat_property("Brush")
+            });
+        }
+        if beat.effect.has_rasgueado {
+            props.push(BeatProperty {
+                enable: Some(EnableTag),
+                ..beat_property("Rasgueado")
+            });
+        }
+        if let Some(direction) = stroke_direction_name(&beat.effect.pick_stroke) {
+            props.push(BeatProperty {
+                direction: Some(direction),
+                ..beat_property("PickStroke")
+            });
+        }
+        if let Some(tremolo_bar) = &beat.effect.tremolo_bar {
+            let (origin, middle, destination) = bend_values(tremolo_bar);
+            g_beat.whammy = Some(Whammy {
+                origin_value: origin,
+                middle_value: middle,
+                destination_value: destination,
+                origin_offset: 0.0,
+                middle_offset1: 50.0,
+                middle_offset2: 50.0,
+                destination_offset: 100.0,
+            });
+            props.push(BeatProperty {
+                enable: Some(EnableTag),
+                ..beat_property("WhammyBar")
+            });
+        }
+        if !props.is_empty() {
+            g_beat.properties = Some(BeatPropertiesWrapper { properties: props });
+        }
+        if let Some(tremolo) = beat
+            .notes
+            .iter()
+            .find_map(|n| n.effect.tremolo_picking.as_ref())
+        {
+            g_beat.tremolo = Some(tremolo_speed(tremolo.duration.value).to_string());
+        }
+
+        let notes = beat
+            .notes
+            .iter()
+            .map(|n| self.convert_note(track, beat, n))
+            .collect();
+        (g_beat, notes)
+    }
+
+    fn convert_note(&self, track: &SongTrack, beat: &SongBeat, note: &SongNote) -> Note {
+        let string_count = track.strings.len() as i8;
+        let effect = &note.effect;
+        let mut props = vec![
+            Property {
+                string: Some((string_count - note.string) as i32),
+                ..property("String")
+            },
+            Property {
+                fret: Some(note.value as i32),
+                ..property("Fret")
+            },
+        ];
+        let open_string = track
+            .strings
+            .iter()
+            .find(|s| s.0 == note.string)
+            .map(|s| s.1 as i32);
+        if let Some(open_string) = open_string {
+            props.push(Property {
+                number: Some(open_string + note.value as i32),
+                ..property("Midi")
+            });
+        }
+        if effect.palm_mute {
+            props.push(enabled_property("PalmMuted"));
+        }
+        if note.kind == NoteType::Dead {
+            props.push(enabled_property("Muted"));
+        }
+        if effect.hammer {
+            props.push(enabled_property("HopoOrigin"));
+        }
+        if !effect.slides.is_empty() {
+            props.push(Property {
+                flags: Some(slide_flags(&effect.slides)),
+                ..property("Slide")
+            });
+        }
+        if let Some(harmonic) = &effect.harmonic {
+            props.push(Property {
+                htype: Some(harmonic_type_name(&harmonic.kind).to_string()),
+                ..property("HarmonicType")
+            });
+            if let Some(fret) = harmonic.fret {
+                props.push(Property {
+                    hfret: Some(fret as f64),
+                    ..property("HarmonicFret")
+                });
+            }
+        }
+        if let Some(bend) = &effect.bend {
+            let (origin, middle, destination) = bend_values(bend);
+            props.push(enabled_property("Bended"));
+            props.push(float_property("BendOriginValue", origin));
+            props.push(float_property("BendMiddleValue", middle));
+            props.push(float_property("BendDestinationValue", destination));
+            props.push(float_property("BendOriginOffset", 0.0));
+            props.push(float_property("BendDestinationOffset", 100.0));
+        }
+
+        let mut accent = 0;
+        if effect.staccato {
+            accent |= 0x01;
+        }
+        if effect.heavy_accentuated_note {
+            accent |= 0x04;
+        }
+        if effect.accentuated_note {
+            accent |= 0x08;
+        }
+
+        Note {
+            properties: NoteProperties { properties: props },
+            tie: (note.kind == NoteType::Tie).then(|| TieInfo {
+                origin: "false".to_string(),
+                destination: "true".to_string(),
+            }),
+            vibrato: if effect.vibrato {
+                Some("Slight".to_string())
+            } else if beat.effect.vibrato {
+                Some("Wide".to_string())
+            } else {
+                None
+            },
+            let_ring: effect.let_ring.then_some(EnableTag),
+            anti_accent: effect.ghost_note.then(|| "Normal".to_string()),
+            accent: (accent != 0).then_some(accent),
+            // GPIF stores the pitch of the trill note
+            trill: effect
+                .trill
+                .as_ref()
+                .map(|t| open_string.unwrap_or(0) + t.fret as i32),
+            ornament: effect.ornament.clone(),
+            ..empty_note()
+        }
+    }
+}
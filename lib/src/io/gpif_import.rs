@@ -1,16 +1,22 @@
 use std::collections::HashMap;
 
+use crate::audio::midi::{MidiChannel, DEFAULT_PERCUSSION_CHANNEL};
 use crate::io::gpif::*;
 use crate::model::{
     beat::{Beat as SongBeat, Voice as SongVoice},
+    chord::{Barre, Chord, PitchClass},
+    conversion::{LossPosition, LostItem},
     effects::*,
     enums::*,
     headers::{Marker, MeasureHeader},
     key_signature::*,
-    measure::Measure,
+    lyric::Lyrics,
+    measure::{Measure, MeasureStaff},
+    mix_table::{MixTableChange, MixTableItem},
     note::Note as SongNote,
+    rse::{RseEffect, RseEqualizer},
     song::*,
-    track::Track as SongTrack,
+    track::{Track as SongTrack, TrackStaff},
 };
 
 pub trait SongGpifOps {
@@ -116,6 +122,140 @@ fn parse_harmonic_type(htype: &str) -> HarmonicEffect {
     }
 }
 
+/// Parse a GPIF tremolo picking speed ("1/2", "1/4", "1/8") into a duration value.
+fn parse_tremolo_speed(s: &str) -> Option<u16> {
+    match s {
+        "1/2" => Some(DURATION_EIGHTH as u16),
+        "1/4" => Some(DURATION_SIXTEENTH as u16),
+        "1/8" => Some(DURATION_THIRTY_SECOND as u16),
+        _ => None,
+    }
+}
+
+/// MIDI pitch of an element of a GP6 built-in percussion kit, for the notes that do not store
+/// it. Elements are in the order of the kit, a missing variation falls back to the first one.
+fn gp6_percussion_pitch(instrument: &str, element: i32, variation: Option<i32>) -> Option<i16> {
+    let elements: &[&[i16]] = match instrument {
+        "drmkt" => &[
+            &[35],         // kick
+            &[38, 38, 37], // snare: hit, rim shot, side stick
+            &[56],         // cowbell low
+            &[56],         // cowbell medium
+            &[56],         // cowbell high
+            &[41],         // tom very low
+            &[43],         // tom low
+            &[45],         // tom medium
+            &[47],         // tom high
+            &[48],         // tom very high
+            &[42, 46, 46], // hi-hat: closed, half, open
+            &[44],         // pedal hi-hat
+            &[49],         // crash medium
+            &[57],         // crash high
+            &[55],         // splash
+            &[51, 59, 53], // ride: middle, edge, bell
+            &[52],         // china
+        ],
+        "bngKit" => &[&[60], &[61]],
+        "cngKit" => &[&[64], &[63, 63, 62]],
+        "tmblKit" => &[&[65], &[66]],
+        "agogoKit" => &[&[67], &[68]],
+        "whstlKit" => &[&[71], &[72]],
+        "wdblckKit" => &[&[76], &[77]],
+        "cuicaKit" => &[&[78], &[79]],
+        _ => return None,
+    };
+    let variations = elements.get(usize::try_from(element).ok()?)?;
+    let variation = usize::try_from(variation.unwrap_or(0)).unwrap_or(0);
+    variations.get(variation).or(variations.first()).copied()
+}
+
+/// Put the notes of a beat that have no string (GP6 keyboard and percussion notes) on the free
+/// strings, highest pitch first. Percussion notes keep their pitch as value, the other ones get
+/// the fret of the highest string that can play them. Notes left without a string, or below the
+/// free strings, are dropped and their count returned.
+fn place_unfretted_notes(beat: &mut SongBeat, track: &SongTrack) -> usize {
+    let mut unplaced: Vec<SongNote> = Vec::new();
+    beat.notes.retain(|n| {
+        if n.string == 0 {
+            unplaced.push(n.clone());
+        }
+        n.string != 0
+    });
+    let mut dropped = 0;
+    let mut free: Vec<(i8, i8)> = track
+        .strings
+        .iter()
+        .filter(|s| beat.notes.iter().all(|n| n.string != s.0))
+        .copied()
+        .collect();
+    free.sort_by_key(|s| s.0);
+    unplaced.sort_by_key(|n| std::cmp::Reverse(n.value));
+    for mut note in unplaced {
+        let index = if track.percussion_track {
+            (!free.is_empty()).then_some(0)
+        } else {
+            free.iter().position(|s| i16::from(s.1) <= note.value)
+        };
+        let Some(index) = index else {
+            dropped += 1;
+            continue;
+        };
+        let (string, open) = free.remove(index);
+        note.string = string;
+        if !track.percussion_track {
+            let shift = i16::from(open);
+            note.value -= shift;
+            if let Some(trill) = &mut note.effect.trill {
+                trill.fret -= shift as i8;
+            }
+        }
+        beat.notes.push(note);
+    }
+    dropped
+}
+
+/// Attach the grace notes of a GPIF grace beat to the notes of the following beat, as Guitar
+/// Pro 3-5 store them. A grace note goes to the note on its string, or else to the first note
+/// without a grace note, with the fret that keeps its pitch. Other grace notes are dropped.
+fn attach_grace_notes(grace_beat: &SongBeat, beat: &mut SongBeat, strings: &[(i8, i8)]) {
+    let open = |string: i8| {
+        strings
+            .iter()
+            .find(|s| s.0 == string)
+            .map_or(0, |s| i16::from(s.1))
+    };
+    for grace_note in &grace_beat.notes {
+        let Some(grace) = &grace_note.effect.grace else {
+            continue;
+        };
+        let index = beat
+            .notes
+            .iter()
+            .position(|n| n.string == grace_note.string)
+            .or_else(|| beat.notes.iter().position(|n| n.effect.grace.is_none()));
+        if let Some(note) = index.map(|i| &mut beat.notes[i]) {
+            let fret = grace_note.value + open(grace_note.string) - open(note.string);
+            if fret >= 0 {
+                note.effect.grace = Some(GraceEffect {
+                    fret: fret as i8,
+                    ..grace.clone()
+                });
+            }
+        }
+    }
+}
+
+/// Parse a GPIF ottava ("8va", "8vb", "15ma", "15mb").
+fn parse_ottavia(s: &str) -> Octave {
+    match s {
+        "8va" => Octave::Ottava,
+        "8vb" => Octave::OttavaBassa,
+        "15ma" => Octave::Quindicesima,
+        "15mb" => Octave::QuindicesimaBassa,
+        _ => Octave::None,
+    }
+}
+
 /// Parse direction string to DirectionSign enum.
 fn parse_direction_sign(s: &str) -> Option<DirectionSign> {
     match s {
@@ -181,7 +321,105 @@ fn build_bend_effect(origin: f64, destination: f64) -> BendEffect {
     bend
 }
 
+/// Effects of a chain, empty slots included.
+fn rse_effects(effects: &[Effect]) -> Vec<RseEffect> {
+    effects
+        .iter()
+        .map(|e| RseEffect {
+            id: e.id.clone().unwrap_or_default(),
+            parameters: e
+                .parameters
+                .iter()
+                .flat_map(|p| p.split_whitespace())
+                .filter_map(|v| v.parse().ok())
+                .collect(),
+        })
+        .collect()
+}
+
+/// Track equalizer of a channel strip. Its parameters start with three bands of frequency, gain
+/// and width: the gains go to the knobs of the equalizer, from -12 to 12 dB with 0.5 being flat.
+fn channel_strip_equalizer(strip: &ChannelStrip) -> Option<RseEqualizer> {
+    let parameters: Vec<f32> = strip
+        .parameters
+        .iter()
+        .flat_map(|p| p.split_whitespace())
+        .filter_map(|v| v.parse().ok())
+        .collect();
+    let knobs = [1, 4, 7]
+        .iter()
+        .map(|i| {
+            let value = parameters.get(*i)?.clamp(0.0, 1.0);
+            Some((value * 240.0 - 120.0).round() / 10.0)
+        })
+        .collect::<Option<Vec<f32>>>()?;
+    Some(RseEqualizer {
+        knobs,
+        ..Default::default()
+    })
+}
+
+/// RSE sound of a track. GP6 keeps the sound bank and the effect chains in the track RSE
+/// settings, GP7 in each sound of the track: the one selected at the start is read. The equalizer
+/// comes from the channel strip of both.
+fn read_track_sound(track: &mut SongTrack, g_track: &Track) {
+    let rse = &mut track.rse;
+    let strip = g_track.rse.as_ref().and_then(|r| r.channel_strip.as_ref());
+    if let Some(equalizer) = strip.and_then(channel_strip_equalizer) {
+        rse.equalizer = equalizer;
+    }
+    if let Some(gm) = &g_track.general_midi {
+        let settings = g_track.rse.as_ref();
+        let chain = settings
+            .and_then(|r| r.effect_chains.as_ref())
+            .and_then(|c| c.chains.first());
+        rse.instrument.instrument = gm.program.map_or(-1, |p| p.clamp(0, 127) as i16);
+        rse.instrument.effect = chain.and_then(|c| c.name.clone()).unwrap_or_default();
+        rse.effects = chain
+            .and_then(|c| c.rail.as_ref())
+            .map(|r| rse_effects(&r.effects))
+            .unwrap_or_default();
+        rse.sound_bank_patch = settings.and_then(|r| r.bank.clone()).unwrap_or_default();
+        track.use_rse = !track.rse.sound_bank_patch.is_empty();
+        return;
+    }
+
+    let sounds = g_track.sounds.iter().flat_map(|s| &s.sounds);
+    let selected = g_track
+        .automations
+        .iter()
+        .flat_map(|a| &a.automations)
+        .find(|a| a.automation_type == "Sound" && a.bar == 0 && a.position == 0.0)
+        .map(|a| a.value.trim());
+    let Some(sound) = sounds
+        .clone()
+        .find(|s| Some(format!("{};{};{}", s.path, s.name, s.role).as_str()) == selected)
+        .or_else(|| sounds.clone().next())
+    else {
+        return;
+    };
+    rse.instrument.instrument = sound
+        .midi
+        .as_ref()
+        .map_or(-1, |m| m.program.clamp(0, 127) as i16);
+    rse.instrument.effect = sound.name.clone();
+    rse.instrument.effect_category = sound.path.clone();
+    rse.sound_role = sound.role.clone();
+    if let Some(sound_rse) = &sound.rse {
+        rse.sound_bank_patch = sound_rse.soundbank_patch.clone().unwrap_or_default();
+        rse.effects = sound_rse
+            .effect_chain
+            .as_ref()
+            .map(|c| rse_effects(&c.effects))
+            .unwrap_or_default();
+    }
+    track.use_rse = g_track.audio_engine_state.as_deref() == Some("RSE");
+}
+
 /// Extract tuning pitches from a property list.
+///
+/// GPIF lists the pitches from the lowest string to the highest one, while the
+/// model numbers strings from 1 (highest) like the binary formats do.
 fn extract_tuning(properties: &[Property]) -> Vec<(i8, i8)> {
     for prop in properties {
         if prop.name == "Tuning" {
@@ -192,6 +430,7 @@ fn extract_tuning(properties: &[Property]) -> Vec<(i8, i8)> {
                     .collect();
                 return pitches
                     .iter()
+                    .rev()
                     .enumerate()
                     .map(|(i, &pitch)| ((i + 1) as i8, pitch))
                     .collect();
@@ -201,12 +440,321 @@ fn extract_tuning(properties: &[Property]) -> Vec<(i8, i8)> {
     Vec::new()
 }
 
+/// Read the capo and fret count of a staff from its property list.
+fn read_fret_properties(properties: &[Property], offset: &mut i32, fret_count: &mut u8) {
+    for prop in properties {
+        match prop.name.as_str() {
+            "CapoFret" => *offset = prop.fret.unwrap_or(0),
+            "FretCount" => {
+                if let Some(n) = prop.number {
+                    *fret_count = n as u8;
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Convert a GPIF bar clef. Percussion bars use the `Neutral` clef, read as treble.
+fn parse_clef(clef: Option<&str>) -> MeasureClef {
+    match clef {
+        Some("F4") => MeasureClef::Bass,
+        Some("C4") => MeasureClef::Tenor,
+        Some("C3") => MeasureClef::Alto,
+        _ => MeasureClef::Treble,
+    }
+}
+
+/// Read the chords of a track from its `DiagramCollection` and `ChordCollection` properties,
+/// keyed by the id beats refer to.
+///
+/// Diagram strings are numbered from 0 (lowest string) while `Chord.strings` starts with the
+/// highest string, as in the binary formats. Barres are not stored, a finger pressing several
+/// strings on the same fret is read as one.
+fn extract_chords(properties: &[Property], string_count: usize, chords: &mut HashMap<i32, Chord>) {
+    let collections = properties
+        .iter()
+        .filter(|p| p.name == "DiagramCollection" || p.name == "ChordCollection")
+        .filter_map(|p| p.items.as_ref());
+    for item in collections.flat_map(|items| &items.items) {
+        let chord = chords.entry(item.id).or_insert_with(|| Chord {
+            length: string_count as u8,
+            strings: vec![-1; string_count],
+            new_format: Some(true),
+            ..Default::default()
+        });
+        if chord.name.is_empty() {
+            chord.name = item.name.clone();
+        }
+        if let Some(diagram) = &item.diagram {
+            apply_diagram(chord, diagram);
+        }
+        if let Some(info) = &item.chord {
+            chord.root = info.key_note.as_ref().and_then(chord_pitch_class);
+            chord.bass = info.bass_note.as_ref().and_then(chord_pitch_class);
+            chord.sharp = chord.root.as_ref().map(|r| r.sharp);
+            if !info.degrees.is_empty() {
+                apply_degrees(chord, &info.degrees);
+            }
+        }
+    }
+}
+
+fn apply_diagram(chord: &mut Chord, diagram: &Diagram) {
+    let string_count = diagram.string_count.max(0) as usize;
+    if string_count == 0 {
+        return;
+    }
+    // GPIF string -> index in `Chord.strings`, highest string first
+    let index = |string: i32| {
+        usize::try_from(string)
+            .ok()
+            .filter(|s| *s < string_count)
+            .map(|s| string_count - 1 - s)
+    };
+    chord.length = string_count as u8;
+    chord.first_fret = Some((diagram.base_fret + 1).clamp(1, 255) as u8);
+    chord.strings = vec![-1; string_count];
+    // Frets are counted from the base fret, 0 stays an open string
+    for fret in &diagram.frets {
+        if let Some(i) = index(fret.string) {
+            chord.strings[i] = match fret.fret {
+                0 => 0,
+                f => (diagram.base_fret + f).clamp(0, 127) as i8,
+            };
+        }
+    }
+
+    if let Some(fingering) = &diagram.fingering {
+        chord.fingerings = vec![Fingering::Open; string_count];
+        let mut fingers: Vec<(Fingering, i8, Vec<i8>)> = Vec::new();
+        for position in &fingering.positions {
+            let finger = match position.finger.as_str() {
+                "Thumb" => Fingering::Thumb,
+                "Index" => Fingering::Index,
+                "Middle" => Fingering::Middle,
+                "Ring" => Fingering::Annular,
+                "Pinky" => Fingering::Little,
+                _ => continue,
+            };
+            let (Some(i), Ok(fret)) = (index(position.string), i8::try_from(position.fret)) else {
+                continue;
+            };
+            chord.fingerings[i] = finger.clone();
+            let fret = (diagram.base_fret + i32::from(fret)).clamp(0, 127) as i8;
+            let string = (i + 1) as i8;
+            match fingers.iter_mut().find(|f| f.0 == finger && f.1 == fret) {
+                Some(f) => f.2.push(string),
+                None => fingers.push((finger, fret, vec![string])),
+            }
+        }
+        chord.barres = fingers
+            .into_iter()
+            .filter(|f| f.2.len() > 1)
+            .map(|(_, fret, strings)| Barre {
+                fret,
+                start: strings.iter().copied().max().unwrap_or(0),
+                end: strings.iter().copied().min().unwrap_or(0),
+            })
+            .collect();
+        chord.barres.sort_by_key(|b| b.fret);
+    }
+
+    for property in &diagram.properties {
+        if property.name == "ShowFingering" {
+            chord.show = Some(property.value == "true");
+        }
+    }
+}
+
+fn chord_pitch_class(note: &ChordNote) -> Option<PitchClass> {
+    let just = match note.step.as_str() {
+        "C" => 0,
+        "D" => 2,
+        "E" => 4,
+        "F" => 5,
+        "G" => 7,
+        "A" => 9,
+        "B" => 11,
+        _ => return None,
+    };
+    let accidental = match note.accidental.as_str() {
+        "Sharp" => 1,
+        "DoubleSharp" => 2,
+        "Flat" => -1,
+        "DoubleFlat" => -2,
+        _ => 0,
+    };
+    let mut pitch = PitchClass::from(just, Some(accidental), None);
+    pitch.value = pitch.value.rem_euclid(12);
+    pitch.note = pitch.to_string();
+    Some(pitch)
+}
+
+/// Derive the chord type, extension and alterations from the intervals of a GPIF chord.
+fn apply_degrees(chord: &mut Chord, degrees: &[ChordDegree]) {
+    let degree = |interval: &str| {
+        degrees
+            .iter()
+            .find(|d| d.interval == interval && !d.omitted)
+            .map(|d| d.alteration.as_str())
+    };
+    let alteration = |interval: &str| {
+        degree(interval).map(|a| match a {
+            "Diminished" | "Minor" => ChordAlteration::Diminished,
+            "Augmented" => ChordAlteration::Augmented,
+            _ => ChordAlteration::Perfect,
+        })
+    };
+    let fifth = alteration("Fifth");
+    let seventh = degree("Seventh");
+    let sixth = degree("Sixth").is_some();
+    chord.kind = Some(match (degree("Third"), seventh) {
+        (Some("Minor"), None) if fifth == Some(ChordAlteration::Diminished) => {
+            ChordType::Diminished
+        }
+        (Some("Minor"), Some("Major")) => ChordType::MinorMajor,
+        (Some("Minor"), Some(_)) => ChordType::MinorSeventh,
+        (Some("Minor"), None) if sixth => ChordType::MinorSixth,
+        (Some("Minor"), None) => ChordType::Minor,
+        (Some(_), None) if fifth == Some(ChordAlteration::Augmented) => ChordType::Augmented,
+        (Some(_), Some("Major")) => ChordType::MajorSeventh,
+        (Some(_), Some(_)) => ChordType::Seventh,
+        (Some(_), None) if sixth => ChordType::Sixth,
+        (Some(_), None) => ChordType::Major,
+        (None, _) if degree("Fourth").is_some() => match seventh {
+            Some(_) => ChordType::SeventhSuspendedFourth,
+            None => ChordType::SuspendedFourth,
+        },
+        (None, _) if degree("Second").is_some() => match seventh {
+            Some(_) => ChordType::SeventhSuspendedSecond,
+            None => ChordType::SuspendedSecond,
+        },
+        (None, _) => ChordType::Power,
+    });
+    let extension = if degree("Thirteenth").is_some() {
+        ChordExtension::Thirteenth
+    } else if degree("Eleventh").is_some() {
+        ChordExtension::Eleventh
+    } else if degree("Ninth").is_some() {
+        ChordExtension::Ninth
+    } else {
+        ChordExtension::None
+    };
+    chord.add = Some(extension != ChordExtension::None && seventh.is_none());
+    chord.extension = Some(extension);
+    chord.fifth = fifth;
+    chord.ninth = alteration("Ninth");
+    chord.eleventh = alteration("Eleventh");
+}
+
+/// Tempo of a tempo automation in quarter notes per minute. The value is followed by its beat
+/// unit: 1 eighth, 2 quarter, 3 dotted quarter, 4 half and 5 dotted half.
+fn automation_tempo(automation: &Automation) -> Option<f64> {
+    let mut parts = automation.value.split_whitespace();
+    let tempo = parts.next()?.parse::<f64>().ok()?;
+    let unit = match parts.next().and_then(|u| u.parse::<i32>().ok()) {
+        Some(1) => 0.5,
+        Some(3) => 1.5,
+        Some(4) => 2.0,
+        Some(5) => 3.0,
+        _ => 1.0,
+    };
+    Some(tempo * unit)
+}
+
+/// Beat of the first non-empty voice playing at a position of the bar, with its start.
+fn automation_beat(
+    measure: &Measure,
+    header: &MeasureHeader,
+    position: f64,
+) -> Option<(usize, usize, i64)> {
+    let tick = header.start + (header.length() as f64 * position.clamp(0.0, 1.0)).round() as i64;
+    let v_idx = measure.voices.iter().position(|v| !v.beats.is_empty())?;
+    let beats = &measure.voices[v_idx].beats;
+    let b_idx = beats
+        .iter()
+        .rposition(|b| b.start.is_some_and(|s| s <= tick))
+        .unwrap_or(0);
+    Some((v_idx, b_idx, beats[b_idx].start.unwrap_or(header.start)))
+}
+
+/// Track, measure, voice and beat indexes of an automation, with the start of the beat.
+type AutomationBeat = (usize, usize, usize, usize, i64);
+
+/// Put the automations of one type on the beats of a track as mix table changes, or of the first
+/// of `tracks` with a beat in the bar. `skip` tells the values kept elsewhere, unless they start a
+/// ramp. A linear automation ramps to the value of the next one, its duration is counted in
+/// quarter notes as in Guitar Pro 5, and the next one is not repeated unless it starts a ramp.
+fn apply_automations(
+    tracks: &mut [SongTrack],
+    headers: &[MeasureHeader],
+    automations: &[&Automation],
+    skip: impl Fn(&Automation) -> bool,
+    value: impl Fn(&Automation) -> Option<u8>,
+    set: impl Fn(&mut MixTableChange, MixTableItem, &Automation),
+) {
+    let mut automations = automations.to_vec();
+    automations.sort_by(|a, b| {
+        (a.bar, a.position)
+            .partial_cmp(&(b.bar, b.position))
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    let beats: Vec<Option<AutomationBeat>> = automations
+        .iter()
+        .map(|a| {
+            let m_idx = usize::try_from(a.bar).ok()?;
+            let header = headers.get(m_idx)?;
+            tracks.iter().enumerate().find_map(|(t_idx, track)| {
+                let (v_idx, b_idx, start) =
+                    automation_beat(track.measures.get(m_idx)?, header, a.position)?;
+                Some((t_idx, m_idx, v_idx, b_idx, start))
+            })
+        })
+        .collect();
+    for (i, auto) in automations.iter().enumerate() {
+        let linear = auto.linear.as_deref() == Some("true");
+        let Some((t_idx, m_idx, v_idx, b_idx, start)) = beats[i] else {
+            continue;
+        };
+        // The end of a ramp is reached by the ramp itself
+        let ends_ramp = i > 0 && automations[i - 1].linear.as_deref() == Some("true");
+        if (skip(auto) || ends_ramp) && !linear {
+            continue;
+        }
+        let next = automations
+            .get(i + 1)
+            .zip(beats.get(i + 1).copied().flatten());
+        let (target, duration) = match next.filter(|_| linear) {
+            Some((next, (.., next_start))) => (value(next), next_start - start),
+            None => (value(auto), 0),
+        };
+        let Some(target) = target else {
+            continue;
+        };
+        let beat = &mut tracks[t_idx].measures[m_idx].voices[v_idx].beats[b_idx];
+        let item = MixTableItem {
+            value: target,
+            duration: (duration / DURATION_QUARTER_TIME).clamp(0, 255) as u8,
+            all_tracks: false,
+        };
+        set(
+            beat.effect
+                .mix_table_change
+                .get_or_insert_with(MixTableChange::default),
+            item,
+            auto,
+        );
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Main conversion
 // ---------------------------------------------------------------------------
 
 impl SongGpifOps for Song {
     fn read_gpif(&mut self, gpif: &Gpif) {
+        self.gpif_source = gpif.source.clone();
         // 1. Metadata
         self.name = gpif.score.title.clone();
         self.subtitle = gpif.score.sub_title.clone();
@@ -224,22 +772,25 @@ impl SongGpifOps for Song {
         }
 
         // 2. Tempo from MasterTrack automations
-        if let Some(automations) = &gpif.master_track.automations {
-            for auto in &automations.automations {
-                if auto.automation_type == "Tempo" && auto.bar == 0 {
-                    if let Some(tempo_str) = auto.value.split_whitespace().next() {
-                        self.tempo = match tempo_str.parse::<f64>() {
-                            Ok(v) => v as i16,
-                            Err(_) => {
-                                eprintln!(
-                                    "Warning: failed to parse tempo '{}', defaulting to 120",
-                                    tempo_str
-                                );
-                                120
-                            }
-                        };
+        let tempo_automations: Vec<&Automation> = gpif
+            .master_track
+            .automations
+            .iter()
+            .flat_map(|a| &a.automations)
+            .filter(|a| a.automation_type == "Tempo")
+            .collect();
+        for auto in &tempo_automations {
+            if auto.bar == 0 && auto.position == 0.0 {
+                self.tempo = match automation_tempo(auto) {
+                    Some(v) => v as i16,
+                    None => {
+                        eprintln!(
+                            "Warning: failed to parse tempo '{}', defaulting to 120",
+                            auto.value
+                        );
+                        120
                     }
-                }
+                };
             }
         }
 
@@ -252,14 +803,25 @@ impl SongGpifOps for Song {
         let rhythms_map: HashMap<i32, &Rhythm> =
             gpif.rhythms.rhythms.iter().map(|r| (r.id, r)).collect();
 
-        // 4. Measure Headers (MasterBars) — also collects per-track bar IDs
+        // 4. Measure Headers (MasterBars) — also collects per-staff bar IDs. A master bar lists
+        // one bar per staff, the staves of each track in turn.
         self.measure_headers.clear();
         let num_tracks = gpif.tracks.tracks.len();
-        let mut track_bar_ids: Vec<Vec<i32>> = vec![Vec::new(); num_tracks];
-
+        let staff_counts: Vec<usize> = gpif
+            .tracks
+            .tracks
+            .iter()
+            .map(|t| t.staves.as_ref().map_or(1, |s| s.staves.len().max(1)))
+            .collect();
+        let mut track_bar_ids: Vec<Vec<Vec<i32>>> =
+            staff_counts.iter().map(|&n| vec![Vec::new(); n]).collect();
+
+        let mut start = DURATION_QUARTER_TIME;
         for (mh_idx, mb) in gpif.master_bars.master_bars.iter().enumerate() {
             let mut mh = MeasureHeader {
                 number: (mh_idx + 1) as u16,
+                start,
+                gpif_index: Some(mh_idx),
                 ..Default::default()
             };
 
@@ -276,14 +838,10 @@ impl SongGpifOps for Song {
                 mh.key_signature.is_minor = key.mode == "Minor";
             }
 
-            // Tempo at this bar
-            if let Some(automations) = &gpif.master_track.automations {
-                for auto in &automations.automations {
-                    if auto.automation_type == "Tempo" && auto.bar == mh_idx as i32 {
-                        if let Some(tempo_str) = auto.value.split_whitespace().next() {
-                            mh.tempo = tempo_str.parse::<f64>().unwrap_or(0.0) as i32;
-                        }
-                    }
+            // Tempo at the start of this bar, the other changes go to the beats
+            for auto in &tempo_automations {
+                if auto.bar == mh_idx as i32 && auto.position == 0.0 {
+                    mh.tempo = automation_tempo(auto).unwrap_or(0.0) as i32;
                 }
             }
 
@@ -345,14 +903,15 @@ impl SongGpifOps for Song {
                 }
             }
 
-            // Per-track bar IDs
-            let bar_ids = parse_ids(&mb.bars);
-            for (t_idx, &bar_id) in bar_ids.iter().enumerate() {
-                if t_idx < num_tracks {
-                    track_bar_ids[t_idx].push(bar_id);
+            // Per-staff bar IDs
+            let mut bar_ids = parse_ids(&mb.bars).into_iter();
+            for staves in track_bar_ids.iter_mut() {
+                for staff_bar_ids in staves.iter_mut() {
+                    staff_bar_ids.push(bar_ids.next().unwrap_or(-1));
                 }
             }
 
+            start += mh.length();
             self.measure_headers.push(mh);
         }
 
@@ -360,12 +919,16 @@ impl SongGpifOps for Song {
 
         // 5. Tracks
         self.tracks.clear();
+        let mut read_losses = Vec::new();
+        self.lyrics = Lyrics::default();
+        let mut track_channels: Vec<(u8, Option<(u8, u8)>)> = Vec::with_capacity(num_tracks);
 
         for (t_idx, g_track) in gpif.tracks.tracks.iter().enumerate() {
             let mut track = SongTrack {
                 name: g_track.name.clone(),
                 short_name: g_track.short_name.clone(),
                 number: (t_idx + 1) as i32,
+                gpif_index: Some(t_idx),
                 ..Default::default()
             };
 
@@ -381,47 +944,194 @@ impl SongGpifOps for Song {
             }
 
             // Tuning: GP6 track-level properties, GP7 staves
+            let mut tuning = Vec::new();
             if let Some(props) = &g_track.properties {
-                track.strings = extract_tuning(&props.properties);
+                tuning = extract_tuning(&props.properties);
             }
-            if track.strings.is_empty() {
+            if tuning.is_empty() {
                 if let Some(staves) = &g_track.staves {
                     for staff in &staves.staves {
                         if let Some(props) = &staff.properties {
-                            track.strings = extract_tuning(&props.properties);
-                            if !track.strings.is_empty() {
+                            tuning = extract_tuning(&props.properties);
+                            if !tuning.is_empty() {
                                 break;
                             }
                         }
                     }
                 }
             }
-            if track.strings.is_empty() {
-                track.strings = vec![(1, 64), (2, 59), (3, 55), (4, 50), (5, 45), (6, 40)];
+            let has_tuning = !tuning.is_empty();
+            if has_tuning {
+                track.strings = tuning;
             }
 
-            track.fret_count = 24;
-
-            // MIDI
-            if let Some(gm) = &g_track.general_midi {
-                if let Some(ch) = gm.primary_channel {
-                    track.channel_index = ch as usize;
-                    track.percussion_track = ch == 9;
-                }
-                track.midi_program_gpif = gm.program;
-                if let Some(port) = gm.port {
-                    track.port = port as u8;
+            // Chord diagrams and names of the track and its staves
+            let mut chords: HashMap<i32, Chord> = HashMap::new();
+            let string_count = track.strings.len();
+            if let Some(props) = &g_track.properties {
+                extract_chords(&props.properties, string_count, &mut chords);
+            }
+            for staff in g_track.staves.iter().flat_map(|s| &s.staves) {
+                if let Some(props) = &staff.properties {
+                    extract_chords(&props.properties, string_count, &mut chords);
                 }
             }
 
+            track.fret_count = 24;
+            let staff_properties = match (&g_track.properties, &g_track.staves) {
+                (Some(props), _) => Some(&props.properties),
+                (None, Some(staves)) => staves
+                    .staves
+                    .first()
+                    .and_then(|staff| staff.properties.as_ref())
+                    .map(|props| &props.properties),
+                (None, None) => None,
+            };
+            if let Some(properties) = staff_properties {
+                read_fret_properties(properties, &mut track.offset, &mut track.fret_count);
+            }
+
+            // MIDI: GP6 uses GeneralMidi, GP7 splits it into MidiConnection and Sounds
+            let (primary_channel, secondary_channel, port) =
+                match (&g_track.general_midi, &g_track.midi_connection) {
+                    (Some(gm), _) => (gm.primary_channel, gm.secondary_channel, gm.port),
+                    (None, Some(mc)) => (mc.primary_channel, mc.secondary_channel, mc.port),
+                    (None, None) => (None, None, None),
+                };
+            track.percussion_track = primary_channel == Some(DEFAULT_PERCUSSION_CHANNEL.into());
+            // GPIF ports are 0-based
+            let port = port.unwrap_or(0).clamp(0, 3) as u8;
+            let channels = primary_channel.map(|primary| {
+                let secondary = secondary_channel.unwrap_or(primary);
+                (primary.clamp(0, 15) as u8, secondary.clamp(0, 15) as u8)
+            });
+            track_channels.push((port, channels));
+            track.midi_program_gpif = match &g_track.general_midi {
+                Some(gm) => gm.program,
+                None => g_track
+                    .sounds
+                    .as_ref()
+                    .and_then(|s| s.sounds.first())
+                    .and_then(|s| s.midi.as_ref())
+                    .map(|m| m.program),
+            };
+            read_track_sound(&mut track, g_track);
+
             // Transpose
             if let Some(tr) = &g_track.transpose {
                 track.transpose_chromatic = tr.chromatic.unwrap_or(0);
                 track.transpose_octave = tr.octave.unwrap_or(0);
             }
 
-            // Current dynamic (persists across beats)
-            let mut current_velocity: i16 = FORTE;
+            // Lyrics: Guitar Pro 3-5 keep a single set of lines, the first track with text is read
+            if self.lyrics.lines.is_empty() {
+                let lyrics = g_track
+                    .lyrics
+                    .as_ref()
+                    .filter(|l| l.lines.iter().any(|line| !line.text.trim().is_empty()));
+                if let Some(lyrics) = lyrics {
+                    self.lyrics.track_choice = (t_idx + 1) as u8;
+                    for (line, l) in lyrics.lines.iter().take(5).enumerate() {
+                        let start = (l.offset.max(0) + 1) as u16;
+                        self.lyrics.lines.push((line as u8, start, l.text.clone()));
+                    }
+                }
+            }
+
+            // Drum tracks without a tuning get the six untuned strings of Guitar Pro
+            if track.percussion_track && !has_tuning {
+                track.strings = (1..=6).map(|s| (s, 0)).collect();
+            }
+            let instrument = g_track.instrument.as_ref().map_or("", |i| i.r#ref.as_str());
+
+            // Other staves (GP7), such as the bass staff of a piano. A staff without a tuning
+            // shares the one of the track.
+            for staff in g_track.staves.iter().flat_map(|s| s.staves.iter().skip(1)) {
+                let mut t_staff = TrackStaff {
+                    strings: track.strings.clone(),
+                    ..Default::default()
+                };
+                if let Some(props) = &staff.properties {
+                    let tuning = extract_tuning(&props.properties);
+                    if !tuning.is_empty() {
+                        t_staff.strings = tuning;
+                    }
+                    read_fret_properties(
+                        &props.properties,
+                        &mut t_staff.offset,
+                        &mut t_staff.fret_count,
+                    );
+                }
+                track.staves.push(t_staff);
+            }
+            let staff_tracks: Vec<SongTrack> =
+                track.staves.iter().map(|s| track.staff_view(s)).collect();
+
+            // Current dynamic of each staff (persists across beats)
+            let mut current_velocities: Vec<i16> = vec![FORTE; staff_tracks.len() + 1];
+            let mut read_voices = |bar: &Bar,
+                                   track: &SongTrack,
+                                   velocity: &mut i16,
+                                   m_idx: usize| {
+                let mut voices = Vec::new();
+                let start = self.measure_headers[m_idx].start;
+                for &vid in &parse_ids(&bar.voices) {
+                    if vid < 0 {
+                        continue;
+                    }
+                    let mut s_voice = SongVoice::default();
+
+                    if let Some(g_voice) = voices_map.get(&vid) {
+                        let beat_ids = parse_ids(&g_voice.beats);
+                        let mut start = start;
+                        let mut grace_beat: Option<SongBeat> = None;
+
+                        for &bid in &beat_ids {
+                            if let Some(g_beat) = beats_map.get(&bid) {
+                                let mut s_beat = convert_beat(
+                                    g_beat,
+                                    &rhythms_map,
+                                    &notes_map,
+                                    velocity,
+                                    track,
+                                    instrument,
+                                );
+                                let dropped = place_unfretted_notes(&mut s_beat, track);
+                                let position = LossPosition {
+                                    track: Some(t_idx + 1),
+                                    measure: Some(m_idx + 1),
+                                    voice: Some(voices.len() + 1),
+                                    beat: Some(s_voice.beats.len() + 1),
+                                };
+                                for _ in 0..dropped {
+                                    read_losses.push(LostItem {
+                                        feature: "note that fits on no string",
+                                        position: position.clone(),
+                                    });
+                                }
+                                s_beat.effect.chord = g_beat
+                                    .chord
+                                    .as_ref()
+                                    .and_then(|id| id.trim().parse::<i32>().ok())
+                                    .and_then(|id| chords.get(&id))
+                                    .cloned();
+                                if s_beat.is_grace {
+                                    grace_beat = Some(s_beat);
+                                    continue;
+                                }
+                                if let Some(grace_beat) = grace_beat.take() {
+                                    attach_grace_notes(&grace_beat, &mut s_beat, &track.strings);
+                                }
+                                s_beat.start = Some(start);
+                                start += i64::from(s_beat.duration.time());
+                                s_voice.beats.push(s_beat);
+                            }
+                        }
+                    }
+                    voices.push(s_voice);
+                }
+                voices
+            };
 
             // Measures
             for m_idx in 0..num_measures {
@@ -432,49 +1142,184 @@ impl SongGpifOps for Song {
                 };
 
                 if m_idx < self.measure_headers.len() {
+                    measure.start = self.measure_headers[m_idx].start;
                     measure.time_signature = self.measure_headers[m_idx].time_signature.clone();
                     measure.key_signature = self.measure_headers[m_idx].key_signature.clone();
                 }
 
-                let bar_id = if m_idx < track_bar_ids[t_idx].len() {
-                    track_bar_ids[t_idx][m_idx]
-                } else {
-                    -1
+                let bar = |staff: usize| {
+                    let bar_id = track_bar_ids[t_idx][staff].get(m_idx).copied();
+                    bars_map.get(&bar_id.unwrap_or(-1)).copied()
                 };
-
-                if let Some(bar) = bars_map.get(&bar_id) {
+                if let Some(bar) = bar(0) {
                     measure.simile_mark = bar.simile_mark.clone();
-                    let voice_ids = parse_ids(&bar.voices);
-                    measure.voices.clear();
-
-                    for &vid in &voice_ids {
-                        if vid < 0 {
-                            continue;
-                        }
-                        let mut s_voice = SongVoice::default();
-
-                        if let Some(g_voice) = voices_map.get(&vid) {
-                            let beat_ids = parse_ids(&g_voice.beats);
-
-                            for &bid in &beat_ids {
-                                if let Some(g_beat) = beats_map.get(&bid) {
-                                    let s_beat = convert_beat(
-                                        g_beat,
-                                        &rhythms_map,
-                                        &notes_map,
-                                        &mut current_velocity,
-                                    );
-                                    s_voice.beats.push(s_beat);
-                                }
-                            }
-                        }
-                        measure.voices.push(s_voice);
+                    measure.clef = parse_clef(bar.clef.as_deref());
+                    measure.voices = read_voices(bar, &track, &mut current_velocities[0], m_idx);
+                }
+                for (staff, staff_track) in staff_tracks.iter().enumerate() {
+                    let mut m_staff = MeasureStaff::default();
+                    if let Some(bar) = bar(staff + 1) {
+                        m_staff.clef = parse_clef(bar.clef.as_deref());
+                        let velocity = &mut current_velocities[staff + 1];
+                        m_staff.voices = read_voices(bar, staff_track, velocity, m_idx);
                     }
+                    measure.staves.push(m_staff);
                 }
                 track.measures.push(measure);
             }
             self.tracks.push(track);
         }
+        self.read_losses = read_losses;
+
+        for line in self.lyrics.lines.len()..5 {
+            self.lyrics.lines.push((line as u8, 1, String::new()));
+        }
+
+        // 6. Automations. Tempo changes inside a bar and tempo ramps go to the beats of the first
+        // track playing in the bar, volume, pan and sound changes to the beats of their track. The
+        // values at the start of the song are the settings of the track channel.
+        apply_automations(
+            &mut self.tracks,
+            &self.measure_headers,
+            &tempo_automations,
+            |a| a.position == 0.0,
+            |a| automation_tempo(a).map(|t| t.round().clamp(0.0, 255.0) as u8),
+            |mtc, item, a| {
+                mtc.tempo = Some(item);
+                mtc.hide_tempo = a.visible.as_deref() == Some("false");
+            },
+        );
+        let mut track_mix: Vec<(Option<i8>, Option<i8>)> = Vec::with_capacity(num_tracks);
+        for (track, g_track) in self.tracks.iter_mut().zip(&gpif.tracks.tracks) {
+            let strip: Vec<&Automation> = g_track
+                .rse
+                .iter()
+                .flat_map(|r| &r.channel_strip)
+                .flat_map(|c| &c.automations)
+                .flat_map(|a| &a.automations)
+                .collect();
+            let of_type = |kind: &str| -> Vec<&Automation> {
+                strip
+                    .iter()
+                    .copied()
+                    .filter(|a| a.automation_type == kind)
+                    .collect()
+            };
+            let (volumes, pans) = (of_type("DSPParam_12"), of_type("DSPParam_11"));
+            let at_start = |a: &Automation| a.bar == 0 && a.position == 0.0;
+            // Mix table values go from 0 to 16, channel values from 0 to 127
+            let mix_value = |a: &Automation| {
+                let value = a.value.trim().parse::<f64>().ok()?;
+                Some((value * 16.0).round().clamp(0.0, 16.0) as u8)
+            };
+            let channel_value = |automations: &[&Automation]| {
+                let value = automations.iter().find(|a| at_start(a))?.value.trim();
+                let value = value.parse::<f64>().ok()?;
+                Some((value * 127.0).round().clamp(0.0, 127.0) as i8)
+            };
+            track_mix.push((channel_value(&volumes), channel_value(&pans)));
+            let headers = &self.measure_headers;
+            apply_automations(
+                std::slice::from_mut(track),
+                headers,
+                &volumes,
+                at_start,
+                mix_value,
+                |mtc, item, _| mtc.volume = Some(item),
+            );
+            apply_automations(
+                std::slice::from_mut(track),
+                headers,
+                &pans,
+                at_start,
+                mix_value,
+                |mtc, item, _| mtc.balance = Some(item),
+            );
+
+            // GP7 sound changes, the value is the path, name and role of one of the track sounds
+            let sounds: Vec<&Automation> = g_track
+                .automations
+                .iter()
+                .flat_map(|a| &a.automations)
+                .filter(|a| a.automation_type == "Sound")
+                .collect();
+            let program = |a: &Automation| {
+                g_track
+                    .sounds
+                    .iter()
+                    .flat_map(|s| &s.sounds)
+                    .find(|s| format!("{};{};{}", s.path, s.name, s.role) == a.value.trim())
+                    .and_then(|s| s.midi.as_ref())
+                    .map(|m| m.program.clamp(0, 127))
+            };
+            if let Some(program) = sounds.iter().find(|a| at_start(a)).and_then(|a| program(a)) {
+                track.midi_program_gpif = Some(program);
+            }
+            apply_automations(
+                std::slice::from_mut(track),
+                headers,
+                &sounds,
+                at_start,
+                |a| program(a).map(|p| p as u8),
+                |mtc, item, _| mtc.instrument = Some(item),
+            );
+        }
+
+        // 7. MIDI channels, 64 as in Guitar Pro files. Tracks sharing a channel with another
+        // instrument, or without one, get the next free channel.
+        self.channels = (0..64u8)
+            .map(|channel| MidiChannel {
+                channel,
+                effect_channel: channel,
+                ..Default::default()
+            })
+            .collect();
+        let mut used_channels: Vec<(usize, i32)> = Vec::new();
+        let tracks = self.tracks.iter_mut().zip(&track_channels).zip(&track_mix);
+        for ((track, &(port, channels)), mix) in tracks {
+            let first = usize::from(port) * 16;
+            let program = if track.percussion_track {
+                0
+            } else {
+                track.midi_program_gpif.unwrap_or(25).clamp(0, 127)
+            };
+            let wanted = channels.map(|(primary, secondary)| {
+                (first + usize::from(primary), first + usize::from(secondary))
+            });
+            let (index, effect_index) = if track.percussion_track {
+                let index = first + usize::from(DEFAULT_PERCUSSION_CHANNEL);
+                (index, index)
+            } else {
+                match wanted {
+                    Some((index, effect_index))
+                        if !used_channels.iter().any(|u| u.0 == index && u.1 != program) =>
+                    {
+                        (index, effect_index)
+                    }
+                    _ => {
+                        let free = (0..64)
+                            .find(|c| {
+                                c % 16 != usize::from(DEFAULT_PERCUSSION_CHANNEL)
+                                    && !used_channels.iter().any(|u| u.0 == *c)
+                            })
+                            .unwrap_or(first);
+                        (free, free)
+                    }
+                }
+            };
+            used_channels.push((index, program));
+            track.channel_index = index;
+            track.port = (index / 16 + 1) as u8;
+            let midi = &mut self.channels[index];
+            midi.set_instrument(program);
+            midi.effect_channel = effect_index as u8;
+            if let Some(volume) = mix.0 {
+                midi.volume = volume;
+            }
+            if let Some(balance) = mix.1 {
+                midi.balance = balance;
+            }
+        }
     }
 }
 
@@ -483,6 +1328,8 @@ fn convert_beat(
     rhythms_map: &HashMap<i32, &Rhythm>,
     notes_map: &HashMap<i32, &Note>,
     current_velocity: &mut i16,
+    track: &SongTrack,
+    instrument: &str,
 ) -> SongBeat {
     let mut s_beat = SongBeat::default();
 
@@ -511,6 +1358,7 @@ fn convert_beat(
 
     // Grace notes
     let is_grace_beat = g_beat.grace_notes.is_some();
+    s_beat.is_grace = is_grace_beat;
     let grace_on_beat = g_beat.grace_notes.as_deref() == Some("OnBeat");
 
     // Text
@@ -525,6 +1373,12 @@ fn convert_beat(
         }
     }
 
+    // Ottava and hairpin
+    if let Some(ottavia) = &g_beat.ottavia {
+        s_beat.octave = parse_ottavia(ottavia);
+    }
+    s_beat.hairpin = g_beat.hairpin.clone();
+
     // Wah effect
     if let Some(wah_str) = &g_beat.wah {
         if wah_str == "Open" {
@@ -533,14 +1387,25 @@ fn convert_beat(
     }
 
     // Tremolo bar
-    if let Some(tremolo_str) = &g_beat.tremolo {
-        if let Ok(val) = tremolo_str.parse::<f64>() {
-            if val != 0.0 {
-                s_beat.effect.tremolo_bar = Some(build_bend_effect(0.0, val));
-            }
-        }
+    if let Some(whammy) = &g_beat.whammy {
+        s_beat.effect.tremolo_bar = Some(build_bend_effect(
+            whammy.origin_value,
+            whammy.destination_value,
+        ));
     }
 
+    // Tremolo picking, applied to every note of the beat
+    let tremolo_picking = g_beat
+        .tremolo
+        .as_deref()
+        .and_then(parse_tremolo_speed)
+        .map(|value| TremoloPickingEffect {
+            duration: Duration {
+                value,
+                ..Default::default()
+            },
+        });
+
     // Beat properties
     if let Some(beat_props) = &g_beat.properties {
         for bp in &beat_props.properties {
@@ -584,8 +1449,15 @@ fn convert_beat(
 
             for &nid in &note_ids {
                 if let Some(g_note) = notes_map.get(&nid) {
-                    let s_note =
-                        convert_note(g_note, *current_velocity, is_grace_beat, grace_on_beat);
+                    let mut s_note = convert_note(
+                        g_note,
+                        *current_velocity,
+                        is_grace_beat,
+                        grace_on_beat,
+                        track,
+                        instrument,
+                    );
+                    s_note.effect.tremolo_picking = tremolo_picking.clone();
                     s_beat.notes.push(s_note);
                 }
             }
@@ -594,7 +1466,6 @@ fn convert_beat(
             s_beat.status = BeatStatus::Rest;
         }
     }
-
     s_beat
 }
 
@@ -603,15 +1474,24 @@ fn convert_note(
     velocity: i16,
     is_grace_beat: bool,
     grace_on_beat: bool,
+    track: &SongTrack,
+    instrument: &str,
 ) -> SongNote {
     let mut s_note = SongNote {
         velocity,
         kind: NoteType::Normal,
         ..Default::default()
     };
+    let string_count = track.strings.len() as i8;
 
     let mut bend_origin: Option<f64> = None;
     let mut bend_dest: Option<f64> = None;
+    let mut harmonic_fret: Option<f64> = None;
+    let mut midi: Option<i16> = None;
+    let mut tone: Option<i32> = None;
+    let mut octave: Option<i32> = None;
+    let mut element: Option<i32> = None;
+    let mut variation: Option<i32> = None;
 
     for prop in &g_note.properties.properties {
         match prop.name.as_str() {
@@ -621,14 +1501,13 @@ fn convert_note(
                 }
             }
             "String" => {
+                // GPIF strings are 0-based from the lowest one
                 if let Some(s) = prop.string {
-                    s_note.string = s as i8;
+                    s_note.string = string_count - s as i8;
                 }
             }
-            "PalmMuted" => {
-                if prop.enable.is_some() {
-                    s_note.effect.palm_mute = true;
-                }
+            "PalmMuted" if prop.enable.is_some() => {
+                s_note.effect.palm_mute = true;
             }
             "BendOriginValue" => {
                 bend_origin = prop.float;
@@ -647,26 +1526,57 @@ fn convert_note(
                 }
             }
             "HarmonicFret" => {
-                if let Some(hfret) = prop.hfret {
-                    if let Some(ref mut h) = s_note.effect.harmonic {
-                        h.fret = Some(hfret as i8);
-                    }
-                }
+                harmonic_fret = prop.hfret;
             }
-            "HopoOrigin" | "HopoDestination" => {
-                if prop.enable.is_some() {
-                    s_note.effect.hammer = true;
-                }
+            "Midi" => {
+                midi = prop.number.map(|n| n as i16);
             }
-            "Dead" | "Muted" => {
-                if prop.enable.is_some() {
-                    s_note.kind = NoteType::Dead;
-                }
+            "Tone" => {
+                tone = prop.step;
+            }
+            "Octave" => {
+                octave = prop.number;
+            }
+            "Element" => {
+                element = prop.element;
+            }
+            "Variation" => {
+                variation = prop.variation;
+            }
+            "HopoOrigin" | "HopoDestination" if prop.enable.is_some() => {
+                s_note.effect.hammer = true;
+            }
+            "Dead" | "Muted" if prop.enable.is_some() => {
+                s_note.kind = NoteType::Dead;
             }
             _ => {}
         }
     }
 
+    // The harmonic fret property comes before the harmonic type
+    if let (Some(h), Some(hfret)) = (&mut s_note.effect.harmonic, harmonic_fret) {
+        h.fret = Some(hfret.round() as i8);
+    }
+
+    // Pitch: keyboard notes of GP6 only have a tone and an octave, percussion notes an element
+    // of the track instrument. Notes without a string keep their pitch as value until
+    // `place_unfretted_notes` puts them on a string.
+    let pitch = midi
+        .or_else(|| tone.zip(octave).map(|(t, o)| (o * 12 + t) as i16))
+        .or_else(|| element.and_then(|e| gp6_percussion_pitch(instrument, e, variation)))
+        .or_else(|| {
+            track
+                .strings
+                .iter()
+                .find(|s| s.0 == s_note.string)
+                .map(|s| s.1 as i16 + s_note.value)
+        });
+    if s_note.string == 0 {
+        if let Some(pitch) = pitch {
+            s_note.value = pitch;
+        }
+    }
+
     // Bend
     if let (Some(orig), Some(dest)) = (bend_origin, bend_dest) {
         if orig != 0.0 || dest != 0.0 {
@@ -714,11 +1624,18 @@ fn convert_note(
         s_note.effect.ornament = Some(orn.clone());
     }
 
-    // Trill
-    if let Some(trill_fret) = g_note.trill {
+    // Trill: GPIF stores the pitch of the other note and no speed, Guitar Pro uses sixteenths
+    if let Some(trill) = g_note.trill {
+        let fret = match pitch {
+            Some(pitch) => s_note.value + trill as i16 - pitch,
+            None => trill as i16,
+        };
         s_note.effect.trill = Some(TrillEffect {
-            fret: trill_fret as i8,
-            duration: Duration::default(),
+            fret: fret as i8,
+            duration: Duration {
+                value: DURATION_SIXTEENTH as u16,
+                ..Default::default()
+            },
         });
     }
 
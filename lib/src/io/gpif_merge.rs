@@ -0,0 +1,398 @@
+//! Merge of a generated GPIF document into the document it was read from.
+//!
+//! The typed model only maps part of a GPIF file: stylesheets, layout, sounds, RSE settings and
+//! many properties are dropped when the file is read. To write them back, the source document is
+//! walked along the generated one:
+//! * elements found in both documents are merged, the generated content wins,
+//! * elements only found in the source are kept, unless they are *owned* by the model: those are
+//!   always written by the exporter when the song has them, so their absence means they were removed,
+//! * elements only found in the generated document are inserted after their previous sibling.
+//!
+//! Tracks and master bars are matched with the source item they were read from, bars, voices, beats
+//! and notes through their ids, mapped by position within the matched items.
+use std::collections::{HashMap, VecDeque};
+
+use crate::error::{GpError, GpResult};
+use crate::io::gpif::{Bar, Beat, Gpif, MasterBar, Voice};
+use crate::io::xml::XmlElement;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Role {
+    /// Written by the exporter whenever the song has it, dropped from the source otherwise
+    Owned,
+    /// Not mapped (or only partially) by the model: the source version is written back as is
+    Kept,
+    /// Merged child by child
+    Merged,
+}
+
+/// Items referenced by id from other parts of the document, with the wrapper holding them.
+const ID_ITEMS: [(&str, &str); 5] = [
+    ("Tracks", "Track"),
+    ("Bars", "Bar"),
+    ("Voices", "Voice"),
+    ("Beats", "Beat"),
+    ("Notes", "Note"),
+];
+
+/// Note properties written by the exporter whenever the song has them.
+const NOTE_PROPERTIES: [&str; 14] = [
+    "String",
+    "Fret",
+    "PalmMuted",
+    "Muted",
+    "Dead",
+    "HopoOrigin",
+    "Slide",
+    "HarmonicType",
+    "Bended",
+    "BendOriginValue",
+    "BendOriginOffset",
+    "BendMiddleValue",
+    "BendDestinationValue",
+    "BendDestinationOffset",
+];
+/// Beat properties written by the exporter.
+const BEAT_PROPERTIES: [&str; 3] = ["Brush", "Rasgueado", "PickStroke"];
+/// Track and staff properties written by the exporter.
+const TRACK_PROPERTIES: [&str; 5] = [
+    "CapoFret",
+    "FretCount",
+    "Tuning",
+    "DiagramCollection",
+    "ChordCollection",
+];
+
+fn role(path: &[&str], child: &XmlElement) -> Role {
+    let parent = path.last().copied().unwrap_or_default();
+    match (parent, child.name.as_str()) {
+        ("GPIF", "GPVersion" | "GPRevision")
+        | ("Track", "Instrument" | "InstrumentSet")
+        | ("Sound", "Name" | "Label" | "Path" | "Role")
+        | ("MIDI", "LSB" | "MSB")
+        | ("ChannelStrip", "Parameters") => Role::Kept,
+        ("Tracks", "Track")
+        | ("MasterBars", "MasterBar")
+        | ("Bars", "Bar")
+        | ("Voices", "Voice")
+        | ("Beats", "Beat")
+        | ("Notes", "Note")
+        | ("Rhythms", "Rhythm")
+        | ("Track", "Color")
+        | (
+            "MasterBar",
+            "Key" | "Repeat" | "AlternateEndings" | "DoubleBar" | "Section" | "Fermatas"
+            | "FreeTime" | "Directions",
+        )
+        | ("Section", "Text")
+        | ("Directions", "Target" | "Jump")
+        | ("Bar", "SimileMark")
+        | (
+            "Beat",
+            "Chord" | "Notes" | "Rhythm" | "Dynamic" | "Hairpin" | "GraceNotes" | "Whammy"
+            | "Tremolo" | "Ottavia" | "FreeText",
+        )
+        | (
+            "Note",
+            "Tie" | "Vibrato" | "LetRing" | "AntiAccent" | "Accent" | "Trill" | "Ornament",
+        ) => Role::Owned,
+        ("Automations", "Automation") => {
+            // Tempo on the master track, volume and pan in the track channel strip
+            let owned: &[&str] = match path.iter().rev().nth(1).copied() {
+                Some("ChannelStrip") => &["DSPParam_11", "DSPParam_12"],
+                _ => &["Tempo"],
+            };
+            if child
+                .child("Type")
+                .is_some_and(|t| owned.contains(&t.text().trim()))
+            {
+                Role::Owned
+            } else {
+                Role::Merged
+            }
+        }
+        ("Properties", "Property") => {
+            let owned: &[&str] = match path.iter().rev().nth(1).copied() {
+                Some("Note") => &NOTE_PROPERTIES,
+                Some("Beat") => &BEAT_PROPERTIES,
+                Some("Track" | "Staff") => &TRACK_PROPERTIES,
+                _ => &[],
+            };
+            if owned.contains(&child.attribute("name").unwrap_or_default()) {
+                Role::Owned
+            } else {
+                Role::Merged
+            }
+        }
+        _ => Role::Merged,
+    }
+}
+
+/// Source id to generated id, per item kind.
+#[derive(Default)]
+struct IdMaps {
+    maps: HashMap<&'static str, HashMap<i32, i32>>,
+}
+
+impl IdMaps {
+    /// Pair the items of both documents: tracks and master bars with the source item they were
+    /// read from, then for each paired master bar the bars of each paired track, their voice slots,
+    /// the beats of each voice and the notes of each beat. Items without a counterpart in the
+    /// source document are left unpaired.
+    fn new(source: &Gpif, generated: &Gpif) -> Self {
+        fn ids(list: &str) -> impl Iterator<Item = i32> + '_ {
+            list.split_whitespace().filter_map(|id| id.parse().ok())
+        }
+        /// Bar ids of a master bar, one list per track with a bar per staff.
+        fn track_bars(gpif: &Gpif, master_bar: &MasterBar) -> Vec<Vec<i32>> {
+            let mut bars = ids(&master_bar.bars);
+            gpif.tracks
+                .tracks
+                .iter()
+                .map(|t| {
+                    let staves = t.staves.as_ref().map_or(1, |s| s.staves.len().max(1));
+                    bars.by_ref().take(staves).collect()
+                })
+                .collect()
+        }
+        fn bars(gpif: &Gpif) -> HashMap<i32, &Bar> {
+            gpif.bars.bars.iter().map(|b| (b.id, b)).collect()
+        }
+        fn voices(gpif: &Gpif) -> HashMap<i32, &Voice> {
+            gpif.voices.voices.iter().map(|v| (v.id, v)).collect()
+        }
+        fn beats(gpif: &Gpif) -> HashMap<i32, &Beat> {
+            gpif.beats.beats.iter().map(|b| (b.id, b)).collect()
+        }
+
+        let mut maps = IdMaps::default();
+        let tracks: Vec<(usize, usize)> = generated
+            .tracks
+            .tracks
+            .iter()
+            .enumerate()
+            .filter_map(|(g, track)| {
+                let s = track.source_index?;
+                maps.insert("Track", source.tracks.tracks.get(s)?.id, track.id);
+                Some((s, g))
+            })
+            .collect();
+        let (source_bars, generated_bars) = (bars(source), bars(generated));
+        let (source_voices, generated_voices) = (voices(source), voices(generated));
+        let (source_beats, generated_beats) = (beats(source), beats(generated));
+        for (g, g_master_bar) in generated.master_bars.master_bars.iter().enumerate() {
+            let Some(s) = g_master_bar.source_index else {
+                continue;
+            };
+            let Some(s_master_bar) = source.master_bars.master_bars.get(s) else {
+                continue;
+            };
+            maps.insert("MasterBar", s as i32, g as i32);
+            let s_track_bars = track_bars(source, s_master_bar);
+            let g_track_bars = track_bars(generated, g_master_bar);
+            let bar_pairs = tracks
+                .iter()
+                .flat_map(|&(s, g)| s_track_bars[s].iter().zip(&g_track_bars[g]));
+            for (s_bar, g_bar) in bar_pairs {
+                let (Some(s_bar), Some(g_bar)) =
+                    (source_bars.get(s_bar), generated_bars.get(g_bar))
+                else {
+                    continue;
+                };
+                maps.insert("Bar", s_bar.id, g_bar.id);
+                for (s_voice, g_voice) in ids(&s_bar.voices).zip(ids(&g_bar.voices)) {
+                    let (Some(s_voice), Some(g_voice)) =
+                        (source_voices.get(&s_voice), generated_voices.get(&g_voice))
+                    else {
+                        continue;
+                    };
+                    maps.insert("Voice", s_voice.id, g_voice.id);
+                    for (s_beat, g_beat) in ids(&s_voice.beats).zip(ids(&g_voice.beats)) {
+                        let (Some(s_beat), Some(g_beat)) =
+                            (source_beats.get(&s_beat), generated_beats.get(&g_beat))
+                        else {
+                            continue;
+                        };
+                        maps.insert("Beat", s_beat.id, g_beat.id);
+                        let s_notes = s_beat.notes.as_deref().unwrap_or_default();
+                        let g_notes = g_beat.notes.as_deref().unwrap_or_default();
+                        for (s_note, g_note) in ids(s_notes).zip(ids(g_notes)) {
+                            maps.insert("Note", s_note, g_note);
+                        }
+                    }
+                }
+            }
+        }
+        maps
+    }
+
+    fn insert(&mut self, item: &'static str, source: i32, generated: i32) {
+        self.maps
+            .entry(item)
+            .or_default()
+            .entry(source)
+            .or_insert(generated);
+    }
+
+    fn get(&self, item: &str, source: i32) -> Option<i32> {
+        self.maps.get(item)?.get(&source).copied()
+    }
+}
+
+/// Key used to pair the children of two matched elements. Children sharing a key are paired in order.
+/// Source ids are translated with `ids`, elements without a key are never paired.
+fn match_key(
+    parent: &str,
+    index: usize,
+    child: &XmlElement,
+    ids: Option<&IdMaps>,
+) -> Option<String> {
+    // Master bars have no id, their position in the score is used instead
+    if parent == "MasterBars" && child.name == "MasterBar" {
+        let index = match ids {
+            Some(ids) => ids.get("MasterBar", index as i32)?,
+            None => index as i32,
+        };
+        return Some(format!("MasterBar#{}", index));
+    }
+    if let Some((_, item)) = ID_ITEMS
+        .iter()
+        .find(|(p, c)| *p == parent && *c == child.name)
+    {
+        let id: i32 = child.attribute("id")?.trim().parse().ok()?;
+        let id = match ids {
+            Some(ids) => ids.get(item, id)?,
+            None => id,
+        };
+        return Some(format!("{}#{}", item, id));
+    }
+    match (parent, child.name.as_str()) {
+        // Rhythms are shared between beats, the generated ones replace them
+        ("Rhythms", "Rhythm") => None,
+        (_, "Property") => Some(format!(
+            "Property@{}",
+            child.attribute("name").unwrap_or_default()
+        )),
+        (_, "Automation") => {
+            let text = |name: &str| {
+                child
+                    .child(name)
+                    .map(|e| e.text().trim())
+                    .unwrap_or_default()
+            };
+            Some(format!(
+                "Automation:{}:{}:{}",
+                text("Type"),
+                text("Bar"),
+                text("Position")
+            ))
+        }
+        _ => Some(child.name.clone()),
+    }
+}
+
+/// Copy of a source element that has no counterpart in the generated document, without its owned
+/// descendants.
+fn strip_owned<'a>(element: &'a XmlElement, path: &mut Vec<&'a str>) -> XmlElement {
+    path.push(&element.name);
+    let mut children = Vec::with_capacity(element.children.len());
+    for child in &element.children {
+        match role(path, child) {
+            Role::Owned => {}
+            Role::Kept => children.push(child.clone()),
+            Role::Merged => children.push(strip_owned(child, path)),
+        }
+    }
+    path.pop();
+    XmlElement {
+        name: element.name.clone(),
+        attributes: element.attributes.clone(),
+        text: element.text.clone(),
+        children,
+    }
+}
+
+fn merge_element<'a>(
+    source: &'a XmlElement,
+    generated: &XmlElement,
+    ids: &IdMaps,
+    path: &mut Vec<&'a str>,
+) -> XmlElement {
+    let mut merged = XmlElement {
+        name: generated.name.clone(),
+        attributes: generated.attributes.clone(),
+        text: generated.text.clone(),
+        children: Vec::with_capacity(source.children.len().max(generated.children.len())),
+    };
+    for (name, value) in &source.attributes {
+        if generated.attribute(name).is_none() {
+            merged.attributes.push((name.clone(), value.clone()));
+        }
+    }
+
+    let mut generated_keys: HashMap<String, VecDeque<usize>> = HashMap::new();
+    for (i, child) in generated.children.iter().enumerate() {
+        if let Some(key) = match_key(&generated.name, i, child, None) {
+            generated_keys.entry(key).or_default().push_back(i);
+        }
+    }
+    let pairs: Vec<Option<usize>> = source
+        .children
+        .iter()
+        .enumerate()
+        .map(|(i, c)| {
+            let key = match_key(&source.name, i, c, Some(ids))?;
+            generated_keys.get_mut(&key)?.pop_front()
+        })
+        .collect();
+    let mut paired = vec![false; generated.children.len()];
+    for &g in pairs.iter().flatten() {
+        paired[g] = true;
+    }
+
+    path.push(&source.name);
+    let mut next = 0;
+    for (child, pair) in source.children.iter().zip(pairs) {
+        match (pair, role(path, child)) {
+            (Some(g), role) => {
+                // Generated children without counterpart go before the next paired one
+                while next < g {
+                    if !paired[next] {
+                        merged.children.push(generated.children[next].clone());
+                    }
+                    next += 1;
+                }
+                merged.children.push(match role {
+                    Role::Kept => child.clone(),
+                    _ => merge_element(child, &generated.children[g], ids, path),
+                });
+            }
+            (None, Role::Owned) => {}
+            (None, Role::Kept) => merged.children.push(child.clone()),
+            (None, Role::Merged) => merged.children.push(strip_owned(child, path)),
+        }
+    }
+    path.pop();
+    for (g, child) in generated.children.iter().enumerate().skip(next) {
+        if !paired[g] {
+            merged.children.push(child.clone());
+        }
+    }
+    merged
+}
+
+/// Merge the generated document `generated` (serialized as `generated_xml`) into the `source`
+/// document it was built from, and return the resulting XML.
+pub(crate) fn merge_source(
+    generated: &Gpif,
+    generated_xml: &str,
+    source: &str,
+) -> GpResult<String> {
+    let typed_source: Gpif = quick_xml::de::from_str(source)
+        .map_err(|e| GpError::WriteError(format!("XML parse error in source: {}", e)))?;
+    let ids = IdMaps::new(&typed_source, generated);
+    let source = XmlElement::parse(source)?;
+    let generated = XmlElement::parse(generated_xml)?;
+    let mut path = Vec::new();
+    Ok(merge_element(&source, &generated, &ids, &mut path).to_document())
+}
@@ -1,25 +1,132 @@
-use crate::error::GpResult;
-use crate::io::gpif::Gpif;
+use crate::error::{GpError, GpResult};
+use crate::io::gpif::{Gpif, GpifSource};
+use crate::io::gpif_merge::merge_source;
 use quick_xml::de::from_str;
-use std::io::{Cursor, Read};
-use zip::ZipArchive;
+use std::io::{Cursor, Read, Write};
+use zip::{write::SimpleFileOptions, ZipArchive, ZipWriter};
+
+const GP_SCORE_PATH: &str = "Content/score.gpif";
+const GP_PART_CONFIGURATION_PATH: &str = "Content/PartConfiguration";
+const GPX_SCORE_PATH: &str = "score.gpif";
+const GPX_PART_CONFIGURATION_PATH: &str = "PartConfiguration";
 
 /// Reads a .gp (GP7+) file which is a ZIP archive containing 'Content/score.gpif'.
+/// The other entries of the archive are kept in `Gpif::source`.
 pub fn read_gp(data: &[u8]) -> GpResult<Gpif> {
     let cursor = Cursor::new(data);
     let mut zip = ZipArchive::new(cursor).map_err(|e| format!("Zip error: {}", e))?;
 
-    // Standard path for GP7 files
-    let mut file = zip
-        .by_name("Content/score.gpif")
-        .map_err(|e| format!("Could not find score.gpif: {}", e))?;
+    let mut contents: Option<String> = None;
+    let mut files = Vec::new();
+    for i in 0..zip.len() {
+        let mut file = zip.by_index(i).map_err(|e| format!("Zip error: {}", e))?;
+        if file.is_dir() {
+            continue;
+        }
+        let name = file.name().to_string();
+        // Standard path for GP7 files
+        if name == GP_SCORE_PATH {
+            let mut xml = String::new();
+            file.read_to_string(&mut xml)
+                .map_err(|e| format!("Read error: {}", e))?;
+            contents = Some(xml);
+        } else {
+            let mut content = Vec::new();
+            file.read_to_end(&mut content)
+                .map_err(|e| format!("Read error: {}", e))?;
+            files.push((name, content));
+        }
+    }
+    let contents = contents.ok_or_else(|| format!("Could not find {}", GP_SCORE_PATH))?;
+
+    let mut gpif: Gpif = from_str(&contents).map_err(|e| format!("XML Parse error: {}", e))?;
+    gpif.source = Some(GpifSource {
+        track_count: gpif.tracks.tracks.len(),
+        xml: contents,
+        files,
+    });
+    Ok(gpif)
+}
 
-    let mut contents = String::new();
-    file.read_to_string(&mut contents)
-        .map_err(|e| format!("Read error: {}", e))?;
+/// Serialize a GPIF document to XML. When the document was read from a file, the content of that
+/// file which is not mapped by the model is merged back.
+pub fn write_gpif(gpif: &Gpif) -> GpResult<String> {
+    let xml = quick_xml::se::to_string(gpif)
+        .map_err(|e| GpError::WriteError(format!("XML serialize error: {}", e)))?;
+    match &gpif.source {
+        Some(source) => merge_source(gpif, &xml, &source.xml),
+        None => Ok(format!(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n{}",
+            xml
+        )),
+    }
+}
 
-    let gpif: Gpif = from_str(&contents).map_err(|e| format!("XML Parse error: {}", e))?;
-    Ok(gpif)
+/// Build the `Content/PartConfiguration` entry: one score view showing every track, then one view
+/// per track. Each track is displayed with standard notation and tablature (flags `0x01 | 0x02`).
+fn part_configuration(track_count: usize) -> Vec<u8> {
+    let mut data = Vec::with_capacity(8 + 6 * (track_count + 1));
+    data.extend_from_slice(&(track_count as i32 + 1).to_be_bytes());
+    data.push(0); // multi-rest
+    data.extend_from_slice(&(track_count as i32).to_be_bytes());
+    data.extend(std::iter::repeat_n(0x03, track_count));
+    for _ in 0..track_count {
+        data.push(0);
+        data.extend_from_slice(&1i32.to_be_bytes());
+        data.push(0x03);
+    }
+    data.extend_from_slice(&1i32.to_be_bytes());
+    data
+}
+
+/// Entries written next to the score: those of the source file when there is one, the part
+/// configuration being rebuilt if the number of tracks changed.
+fn side_files(gpif: &Gpif, part_configuration_path: &str) -> Vec<(String, Vec<u8>)> {
+    let track_count = gpif.tracks.tracks.len();
+    match &gpif.source {
+        Some(source) => source
+            .files
+            .iter()
+            .map(|(name, content)| {
+                if name == part_configuration_path && source.track_count != track_count {
+                    (name.clone(), part_configuration(track_count))
+                } else {
+                    (name.clone(), content.clone())
+                }
+            })
+            .collect(),
+        None => vec![(
+            part_configuration_path.to_string(),
+            part_configuration(track_count),
+        )],
+    }
+}
+
+/// Writes a .gp (GP7+) file: a ZIP archive containing 'Content/score.gpif'.
+pub fn write_gp(gpif: &Gpif) -> GpResult<Vec<u8>> {
+    let xml = write_gpif(gpif)?;
+    let mut entries = side_files(gpif, GP_PART_CONFIGURATION_PATH);
+    if !entries.iter().any(|(name, _)| name == "VERSION") {
+        entries.insert(0, ("VERSION".to_string(), b"7.0".to_vec()));
+    }
+    let position = entries
+        .iter()
+        .position(|(name, _)| name == "VERSION")
+        .unwrap_or(0)
+        + 1;
+    entries.insert(position, (GP_SCORE_PATH.to_string(), xml.into_bytes()));
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default();
+    for (name, content) in entries {
+        zip.start_file(name, options)
+            .map_err(|e| GpError::WriteError(format!("Zip error: {}", e)))?;
+        zip.write_all(&content)?;
+    }
+    let cursor = zip
+        .finish()
+        .map_err(|e| GpError::WriteError(format!("Zip error: {}", e)))?;
+    Ok(cursor.into_inner())
 }
 
 // ---------------------------------------------------------------------------
@@ -225,13 +332,14 @@ fn parse_bcfs(data: &[u8]) -> Result<Vec<BcfsFile>, String> {
 }
 
 /// Reads a .gpx (GP6) file which is a BCFZ/BCFS container holding 'score.gpif'.
+/// The other files of the container are kept in `Gpif::source`.
 pub fn read_gpx(data: &[u8]) -> GpResult<Gpif> {
     let decompressed = decompress_bcfz(data)?;
-    let files = parse_bcfs(&decompressed)?;
+    let mut files = parse_bcfs(&decompressed)?;
 
-    let score_file = files
+    let score_index = files
         .iter()
-        .find(|f| f.name == "score.gpif")
+        .position(|f| f.name == GPX_SCORE_PATH)
         .ok_or_else(|| {
             let names: Vec<&str> = files.iter().map(|f| f.name.as_str()).collect();
             format!(
@@ -239,12 +347,253 @@ pub fn read_gpx(data: &[u8]) -> GpResult<Gpif> {
                 names
             )
         })?;
+    let score_file = files.remove(score_index);
 
-    let xml_str = std::str::from_utf8(&score_file.data)
+    let xml_str = String::from_utf8(score_file.data)
         .map_err(|e| format!("UTF-8 error in score.gpif: {}", e))?;
 
-    let gpif: Gpif =
-        from_str(xml_str).map_err(|e| format!("XML parse error in score.gpif: {}", e))?;
+    let mut gpif: Gpif =
+        from_str(&xml_str).map_err(|e| format!("XML parse error in score.gpif: {}", e))?;
+    gpif.source = Some(GpifSource {
+        track_count: gpif.tracks.tracks.len(),
+        xml: xml_str,
+        // The directory data sector is also seen as an entry, its name is not printable
+        files: files
+            .into_iter()
+            .filter(|f| !f.name.chars().any(char::is_control))
+            .map(|f| (f.name, f.data))
+            .collect(),
+    });
 
     Ok(gpif)
 }
+
+/// Bit-level writer for BCFZ compression, the counterpart of `BitStream`.
+/// Writes bits MSB-first within each byte.
+struct BitWriter {
+    data: Vec<u8>,
+    bit_position: usize,
+}
+
+impl BitWriter {
+    fn new() -> Self {
+        BitWriter {
+            data: Vec::new(),
+            bit_position: 0,
+        }
+    }
+
+    /// Write a single bit (MSB-first within the current byte).
+    fn write_bit(&mut self, bit: u8) {
+        if self.bit_position.is_multiple_of(8) {
+            self.data.push(0);
+        }
+        if bit != 0 {
+            let last = self.data.len() - 1;
+            self.data[last] |= 1 << (7 - (self.bit_position % 8));
+        }
+        self.bit_position += 1;
+    }
+
+    /// Write the `count` lowest bits of `value`, big-endian (MSB first).
+    fn write_bits(&mut self, value: u32, count: usize) {
+        for i in (0..count).rev() {
+            self.write_bit(((value >> i) & 1) as u8);
+        }
+    }
+
+    /// Write the `count` lowest bits of `value`, little-endian (LSB first / "reversed").
+    fn write_bits_reversed(&mut self, value: u32, count: usize) {
+        for i in 0..count {
+            self.write_bit(((value >> i) & 1) as u8);
+        }
+    }
+}
+
+/// Longest back-reference distance and length: both are stored on at most 15 bits.
+const BCFZ_MAX_WORD: usize = 0x7FFF;
+/// Shortest match worth a back-reference, a literal byte costs about 9 bits.
+const BCFZ_MIN_MATCH: usize = 4;
+/// How many previous positions sharing the same prefix are tried for each match.
+const BCFZ_MAX_CHAIN: usize = 64;
+
+/// Number of bits needed to store `value`.
+fn bit_length(value: usize) -> usize {
+    (usize::BITS - value.leading_zeros()) as usize
+}
+
+/// Compress raw BCFS data into a BCFZ buffer, the inverse of `decompress_bcfz`.
+///
+/// Uses a greedy LZ77 search with hash chains on 3-byte prefixes. Unmatched bytes are emitted as
+/// literal chunks of up to 3 bytes.
+fn compress_bcfz(data: &[u8]) -> Vec<u8> {
+    let mut bits = BitWriter::new();
+    let mut head: std::collections::HashMap<[u8; 3], usize> = std::collections::HashMap::new();
+    let mut previous: Vec<usize> = vec![usize::MAX; data.len()];
+    let mut literals: Vec<u8> = Vec::with_capacity(3);
+
+    fn flush_literals(bits: &mut BitWriter, literals: &mut Vec<u8>) {
+        for chunk in literals.chunks(3) {
+            bits.write_bit(0);
+            bits.write_bits_reversed(chunk.len() as u32, 2);
+            for &byte in chunk {
+                bits.write_bits(byte as u32, 8);
+            }
+        }
+        literals.clear();
+    }
+
+    let mut pos = 0;
+    while pos < data.len() {
+        let mut best_len = 0;
+        let mut best_offset = 0;
+        if pos + 3 <= data.len() {
+            let key = [data[pos], data[pos + 1], data[pos + 2]];
+            let mut candidate = head.get(&key).copied().unwrap_or(usize::MAX);
+            let mut chain = 0;
+            while candidate != usize::MAX && chain < BCFZ_MAX_CHAIN {
+                let offset = pos - candidate;
+                if offset > BCFZ_MAX_WORD {
+                    break;
+                }
+                let max_len = (data.len() - pos).min(BCFZ_MAX_WORD);
+                let mut len = 0;
+                // Overlapping copies are allowed, as in the decompressor
+                while len < max_len && data[candidate + (len % offset)] == data[pos + len] {
+                    len += 1;
+                }
+                if len > best_len {
+                    best_len = len;
+                    best_offset = offset;
+                }
+                candidate = previous[candidate];
+                chain += 1;
+            }
+        }
+
+        let step = if best_len >= BCFZ_MIN_MATCH {
+            flush_literals(&mut bits, &mut literals);
+            let word_size = bit_length(best_offset.max(best_len));
+            bits.write_bit(1);
+            bits.write_bits(word_size as u32, 4);
+            bits.write_bits_reversed(best_offset as u32, word_size);
+            bits.write_bits_reversed(best_len as u32, word_size);
+            best_len
+        } else {
+            literals.push(data[pos]);
+            if literals.len() == 3 {
+                flush_literals(&mut bits, &mut literals);
+            }
+            1
+        };
+        for p in pos..(pos + step) {
+            if p + 3 <= data.len() {
+                let key = [data[p], data[p + 1], data[p + 2]];
+                if let Some(last) = head.insert(key, p) {
+                    previous[p] = last;
+                }
+            }
+        }
+        pos += step;
+    }
+    flush_literals(&mut bits, &mut literals);
+
+    let mut output = Vec::with_capacity(bits.data.len() + 8);
+    output.extend_from_slice(BCFZ_MAGIC);
+    output.extend_from_slice(&(data.len() as i32).to_le_bytes());
+    output.extend_from_slice(&bits.data);
+    output
+}
+
+/// Offset of the block index table in a BCFS file entry sector.
+const BCFS_BLOCKS_OFFSET: usize = 0x94;
+
+/// Build a BCFS virtual filesystem holding the given files, the inverse of `parse_bcfs`.
+///
+/// The layout follows the one written by Guitar Pro 6: sector 0 is the allocation bitmap, sector 1
+/// the root directory entry and sector 2 its content (the sector index of every file entry). Each
+/// file then gets an entry sector (type 2, name, size and block index table) followed by its data
+/// sectors.
+pub(crate) fn build_bcfs(files: &[(&str, &[u8])]) -> Result<Vec<u8>, String> {
+    fn write_entry(sector: &mut [u8], kind: i32, name: &str, size: usize, blocks: &[usize]) {
+        sector[0..4].copy_from_slice(&kind.to_le_bytes());
+        let name = name.as_bytes();
+        let name_len = name.len().min(127);
+        sector[4..4 + name_len].copy_from_slice(&name[..name_len]);
+        sector[0x88..0x8C].copy_from_slice(&1i32.to_le_bytes());
+        sector[0x8C..0x90].copy_from_slice(&(size as i32).to_le_bytes());
+        for (i, block) in blocks.iter().enumerate() {
+            let offset = BCFS_BLOCKS_OFFSET + i * 4;
+            sector[offset..offset + 4].copy_from_slice(&(*block as i32).to_le_bytes());
+        }
+    }
+    let max_blocks = (SECTOR_SIZE - BCFS_BLOCKS_OFFSET) / 4 - 1;
+    // The root directory content is a single sector of entry indexes
+    if files.len() > SECTOR_SIZE / 4 {
+        return Err(format!(
+            "BCFS: too many files ({}) for the root directory",
+            files.len()
+        ));
+    }
+
+    let mut sectors: Vec<Vec<u8>> = vec![vec![0u8; SECTOR_SIZE]; 3];
+    let mut entries: Vec<usize> = Vec::with_capacity(files.len());
+    for (name, content) in files {
+        let entry_index = sectors.len();
+        sectors.push(vec![0u8; SECTOR_SIZE]);
+        let mut blocks = Vec::new();
+        for chunk in content.chunks(SECTOR_SIZE) {
+            blocks.push(sectors.len());
+            let mut sector = chunk.to_vec();
+            sector.resize(SECTOR_SIZE, 0);
+            sectors.push(sector);
+        }
+        if blocks.len() > max_blocks {
+            return Err(format!(
+                "BCFS: '{}' is too large ({} bytes) for a single entry",
+                name,
+                content.len()
+            ));
+        }
+        write_entry(&mut sectors[entry_index], 2, name, content.len(), &blocks);
+        entries.push(entry_index);
+    }
+
+    // Root directory: its content lists the sector of every file entry
+    write_entry(&mut sectors[1], 1, "/", entries.len() * 4, &[2]);
+    for (i, entry) in entries.iter().enumerate() {
+        sectors[2][i * 4..i * 4 + 4].copy_from_slice(&(*entry as i32).to_le_bytes());
+    }
+
+    // Allocation bitmap: bit `n` (LSB first) is cleared when sector `n + 1` is in use
+    let used = sectors.len() - 1;
+    if used > SECTOR_SIZE * 8 {
+        return Err("BCFS: too many sectors for the allocation bitmap".to_string());
+    }
+    let bitmap = &mut sectors[0];
+    bitmap.fill(0xFF);
+    for n in 0..used {
+        bitmap[n / 8] &= !(1 << (n % 8));
+    }
+
+    let mut output = Vec::with_capacity(4 + sectors.len() * SECTOR_SIZE);
+    output.extend_from_slice(BCFS_MAGIC);
+    for sector in sectors {
+        output.extend_from_slice(&sector);
+    }
+    Ok(output)
+}
+
+/// Writes a .gpx (GP6) file: a BCFZ compressed BCFS container holding 'score.gpif'.
+pub fn write_gpx(gpif: &Gpif) -> GpResult<Vec<u8>> {
+    let xml = write_gpif(gpif)?;
+    let side_files = side_files(gpif, GPX_PART_CONFIGURATION_PATH);
+    let mut files: Vec<(&str, &[u8])> = vec![(GPX_SCORE_PATH, xml.as_bytes())];
+    files.extend(
+        side_files
+            .iter()
+            .map(|(name, content)| (name.as_str(), content.as_slice())),
+    );
+    let bcfs = build_bcfs(&files).map_err(GpError::WriteError)?;
+    Ok(compress_bcfz(&bcfs))
+}
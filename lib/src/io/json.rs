@@ -0,0 +1,36 @@
+//! JSON form of the songs, with the `serde` feature.
+//!
+//! The JSON shape follows the model: one object per structure with its fields in snake case, the
+//! enums as the names of their variants (`"Natural"`), the tuples as arrays (`[1, 64]` for a string
+//! and its tuning) and the optional values as `null`. The state of the readers and the GP6/GP7
+//! document the song was read from are not written. The `json-schema` feature adds the JSON Schema
+//! of this shape, published in `schema/song.schema.json`.
+use crate::error::{GpError, GpResult};
+use crate::model::song::Song;
+
+pub trait SongJsonOps {
+    /// Serialize the song as a JSON document.
+    fn write_json(&self) -> GpResult<String>;
+    /// Read a song from a JSON document written by `write_json`.
+    fn read_json(&mut self, text: &str) -> GpResult<()>;
+}
+
+impl SongJsonOps for Song {
+    fn write_json(&self) -> GpResult<String> {
+        serde_json::to_string(self)
+            .map_err(|e| GpError::FormatError(format!("JSON serialization failed: {}", e)))
+    }
+
+    fn read_json(&mut self, text: &str) -> GpResult<()> {
+        *self = serde_json::from_str(text)
+            .map_err(|e| GpError::FormatError(format!("invalid song JSON: {}", e)))?;
+        Ok(())
+    }
+}
+
+/// JSON Schema of the documents written by `write_json`.
+#[cfg(feature = "json-schema")]
+pub fn song_json_schema() -> String {
+    let schema = schemars::schema_for!(Song);
+    serde_json::to_string_pretty(&schema).unwrap_or_default()
+}
@@ -0,0 +1,685 @@
+//! LilyPond export (`.ly` files), for engraving with LilyPond 2.24 or later.
+//!
+//! The music of every track is written once in a variable, then engraved on a standard staff and a
+//! tablature staff built from the strings of the track. Percussion tracks get a drum staff. Time and
+//! key signatures, tempos, markers, repeats and alternate endings live in a `global` variable
+//! shared by all the staves. Notes carry their string number, so that the tablature keeps the
+//! fingering of the song.
+use crate::error::GpResult;
+use crate::io::musicxml_export::{duration_ticks, ending_numbers, tuplet_brackets};
+use crate::io::notation::duration_from_ticks;
+use crate::model::{
+    beat::{Beat, Voice},
+    effects::BendEffect,
+    enums::*,
+    key_signature::{Duration, DURATION_QUARTER_TIME},
+    note::Note,
+    song::Song,
+    track::Track,
+};
+
+/// Version of the LilyPond syntax written.
+const LILYPOND_VERSION: &str = "2.24.0";
+/// Lowest note of the guitar tunings, lower tunings are written in bass clef.
+const LOWEST_GUITAR_NOTE: i8 = 35;
+
+/// Note names of the pitch classes, spelled with sharps or with flats.
+const SHARP_NAMES: [&str; 12] = [
+    "c", "cis", "d", "dis", "e", "f", "fis", "g", "gis", "a", "ais", "b",
+];
+const FLAT_NAMES: [&str; 12] = [
+    "c", "des", "d", "ees", "e", "f", "ges", "g", "aes", "a", "bes", "b",
+];
+/// Tonic of the keys from 7 flats to 7 sharps, major then minor.
+const MAJOR_KEYS: [&str; 15] = [
+    "ces", "ges", "des", "aes", "ees", "bes", "f", "c", "g", "d", "a", "e", "b", "fis", "cis",
+];
+const MINOR_KEYS: [&str; 15] = [
+    "aes", "ees", "bes", "f", "c", "g", "d", "a", "e", "b", "fis", "cis", "gis", "dis", "ais",
+];
+/// Drum names of LilyPond for the General MIDI percussion notes from 35.
+const DRUM_NAMES: [&str; 47] = [
+    "bda", "bd", "ss", "sna", "hc", "sne", "tomfl", "hhc", "tomfh", "hhp", "toml", "hho", "tomml",
+    "tommh", "cymca", "tomh", "cymra", "cymch", "rb", "tamb", "cyms", "cb", "cymcb", "vibs",
+    "cymrb", "boh", "bol", "cghm", "cgho", "cgl", "timh", "timl", "agh", "agl", "cab", "mar",
+    "whs", "whl", "guis", "guil", "cl", "wbh", "wbl", "cuim", "cuio", "trim", "tri",
+];
+const FIRST_DRUM_NOTE: i16 = 35;
+
+pub trait SongLilyPondExportOps {
+    /// Export the song as a LilyPond document (`.ly`). Every played note must be on a string of its
+    /// track.
+    fn write_lilypond(&self) -> GpResult<String>;
+}
+
+// ---------------------------------------------------------------------------
+// Helper functions
+// ---------------------------------------------------------------------------
+
+/// A LilyPond string, with its quotes.
+fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Absolute pitch of a MIDI note: `c'` is the middle C.
+fn pitch(midi: i32, key: i8) -> String {
+    let names = if key < 0 { &FLAT_NAMES } else { &SHARP_NAMES };
+    let midi = midi.clamp(0, 127);
+    let octave = midi.div_euclid(12) - 4;
+    let marks = if octave < 0 { "," } else { "'" };
+    format!(
+        "{}{}",
+        names[midi.rem_euclid(12) as usize],
+        marks.repeat(octave.unsigned_abs() as usize)
+    )
+}
+
+fn drum_name(value: i16) -> &'static str {
+    usize::try_from(value - FIRST_DRUM_NOTE)
+        .ok()
+        .and_then(|i| DRUM_NAMES.get(i))
+        .copied()
+        .unwrap_or("sn")
+}
+
+/// Duration of a note: its value and its dots.
+fn duration(duration: &Duration) -> String {
+    let dots = if duration.double_dotted {
+        ".."
+    } else if duration.dotted {
+        "."
+    } else {
+        ""
+    };
+    format!("{}{}", duration.value.max(1), dots)
+}
+
+fn gcd(a: i64, b: i64) -> i64 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+/// Length of `ticks` as a scaled whole note, for skips and measure rests.
+fn scaled_whole(ticks: i64) -> String {
+    let whole = DURATION_QUARTER_TIME * 4;
+    let divisor = gcd(ticks, whole).max(1);
+    match (ticks / divisor, whole / divisor) {
+        (1, 1) => String::from("1"),
+        (n, 1) => format!("1*{}", n),
+        (n, d) => format!("1*{}/{}", n, d),
+    }
+}
+
+/// Name of a track variable: letters only, as LilyPond wants.
+fn variable_name(t_idx: usize) -> String {
+    let mut letters = String::new();
+    let mut n = t_idx + 1;
+    while n > 0 {
+        n -= 1;
+        letters.insert(0, char::from(b'A' + (n % 26) as u8));
+        n /= 26;
+    }
+    format!("track{}", letters)
+}
+
+fn clef(clef: &MeasureClef, bass: bool) -> &'static str {
+    match clef {
+        MeasureClef::Treble if bass => "bass_8",
+        MeasureClef::Treble => "treble_8",
+        MeasureClef::Bass => "bass_8",
+        MeasureClef::Tenor => "tenor",
+        MeasureClef::Alto => "alto",
+    }
+}
+
+fn direction_sign(sign: &DirectionSign) -> String {
+    let glyph = |name: &str| format!("\\mark \\markup {{ \\musicglyph \"scripts.{}\" }}", name);
+    let words = match sign {
+        DirectionSign::Segno | DirectionSign::SegnoSegno => return glyph("segno"),
+        DirectionSign::Coda | DirectionSign::DoubleCoda => return glyph("coda"),
+        DirectionSign::Fine => "Fine",
+        DirectionSign::DaCapo => "D.C.",
+        DirectionSign::DaCapoAlCoda => "D.C. al Coda",
+        DirectionSign::DaCapoAlDoubleCoda => "D.C. al Double Coda",
+        DirectionSign::DaCapoAlFine => "D.C. al Fine",
+        DirectionSign::DaSegno => "D.S.",
+        DirectionSign::DaSegnoAlCoda => "D.S. al Coda",
+        DirectionSign::DaSegnoAlDoubleCoda => "D.S. al Double Coda",
+        DirectionSign::DaSegnoAlFine => "D.S. al Fine",
+        DirectionSign::DaSegnoSegno => "D.S.S.",
+        DirectionSign::DaSegnoSegnoAlCoda => "D.S.S. al Coda",
+        DirectionSign::DaSegnoSegnoAlDoubleCoda => "D.S.S. al Double Coda",
+        DirectionSign::DaSegnoSegnoAlFine => "D.S.S. al Fine",
+        DirectionSign::DaCoda => "To Coda",
+        DirectionSign::DaDoubleCoda => "To Double Coda",
+    };
+    format!("\\mark \\markup {{ \\italic {} }}", quote(words))
+}
+
+/// `\bendAfter` amount of a bend, in semitones: bends go up, tremolo bar dives go down.
+fn bend_amount(bend: &BendEffect) -> Option<i32> {
+    let highest = bend.points.iter().map(|p| i32::from(p.value)).max()?;
+    let lowest = bend.points.iter().map(|p| i32::from(p.value)).min()?;
+    let quarter_tones = if highest > 0 { highest } else { lowest };
+    let semitones = (quarter_tones + quarter_tones.signum()) / 2;
+    (semitones != 0).then_some(semitones)
+}
+
+// ---------------------------------------------------------------------------
+// Track writer
+// ---------------------------------------------------------------------------
+
+struct TrackWriter<'a> {
+    song: &'a Song,
+    track: &'a Track,
+    /// A slur of hammer-ons and pull-offs is open, by voice
+    slurs: [bool; 2],
+    key: i8,
+}
+
+impl<'a> TrackWriter<'a> {
+    fn new(song: &'a Song, t_idx: usize) -> Self {
+        TrackWriter {
+            song,
+            track: &song.tracks[t_idx],
+            slurs: [false; 2],
+            key: 0,
+        }
+    }
+
+    fn is_bass(&self) -> bool {
+        self.track
+            .strings
+            .iter()
+            .any(|(_, note)| *note < LOWEST_GUITAR_NOTE)
+    }
+
+    /// The music of the track, one measure per line.
+    fn write_music(&mut self) -> String {
+        let mut music = String::new();
+        let mut current_clef = None;
+        for m_idx in 0..self.song.measure_headers.len() {
+            let header = &self.song.measure_headers[m_idx];
+            self.key = header.key_signature.key;
+            let measure = self.track.measures.get(m_idx);
+            let mut line = String::from("  ");
+            if !self.track.percussion_track {
+                let measure_clef = clef(
+                    measure.map_or(&MeasureClef::Treble, |m| &m.clef),
+                    self.is_bass(),
+                );
+                if current_clef != Some(measure_clef) {
+                    // Tablature staves keep their own clef
+                    line.push_str(&format!("\\tag #'notation \\clef {} ", quote(measure_clef)));
+                    current_clef = Some(measure_clef);
+                }
+            }
+            let voices: Vec<&Voice> = measure
+                .map(|m| {
+                    m.voices
+                        .iter()
+                        .enumerate()
+                        .filter(|(v_idx, v)| {
+                            *v_idx == 0 || v.beats.iter().any(|b| b.status != BeatStatus::Empty)
+                        })
+                        .map(|(_, v)| v)
+                        .collect()
+                })
+                .unwrap_or_default();
+            match voices.len() {
+                0 => line.push_str(&format!("R{}", scaled_whole(header.length()))),
+                1 => line.push_str(&self.write_voice(m_idx, 0, voices[0])),
+                _ => {
+                    let voices: Vec<String> = voices
+                        .iter()
+                        .enumerate()
+                        .map(|(v_idx, v)| format!("{{ {} }}", self.write_voice(m_idx, v_idx, v)))
+                        .collect();
+                    line.push_str(&format!("<< {} >>", voices.join(" \\\\ ")));
+                }
+            }
+            line.push_str(" |\n");
+            music.push_str(&line);
+        }
+        music
+    }
+
+    /// Beats of a voice. The first voice is completed with rests to fill the measure.
+    fn write_voice(&mut self, m_idx: usize, v_idx: usize, voice: &Voice) -> String {
+        let header = &self.song.measure_headers[m_idx];
+        if v_idx == 0 && voice.beats.iter().all(|b| b.status == BeatStatus::Empty) {
+            return format!("R{}", scaled_whole(header.length()));
+        }
+        let brackets = tuplet_brackets(&voice.beats);
+        let mut items: Vec<String> = Vec::new();
+        let mut length = 0;
+        for (b_idx, beat) in voice.beats.iter().enumerate() {
+            let mut item = String::new();
+            if brackets[b_idx].0 {
+                let d = &beat.duration;
+                item.push_str(&format!(
+                    "\\tuplet {}/{} {{ ",
+                    d.tuplet_enters, d.tuplet_times
+                ));
+            }
+            item.push_str(&self.write_beat((m_idx, v_idx, b_idx), beat));
+            if brackets[b_idx].1 {
+                item.push_str(" }");
+            }
+            items.push(item);
+            length += duration_ticks(&beat.duration);
+        }
+        if v_idx == 0 {
+            let mut gap = header.length() - length;
+            while gap >= DURATION_QUARTER_TIME / 32 {
+                let rest = duration_from_ticks(gap);
+                gap -= duration_ticks(&rest);
+                items.push(format!("r{}", duration(&rest)));
+            }
+        }
+        items.join(" ")
+    }
+
+    fn midi_pitch(&self, note: &Note) -> i32 {
+        note.real_value(&self.track.strings)
+            .unwrap_or(i32::from(note.value))
+    }
+
+    /// Note on the same string in the next beat of the voice, looking into the next measure.
+    fn next_note(&self, position: (usize, usize, usize), string: i8) -> Option<&'a Note> {
+        let (m_idx, v_idx, b_idx) = position;
+        let voice = |m: usize| self.track.measures.get(m).and_then(|m| m.voices.get(v_idx));
+        let beat = match voice(m_idx)?.beats.get(b_idx + 1) {
+            Some(beat) => beat,
+            None => voice(m_idx + 1)?.beats.first()?,
+        };
+        beat.notes
+            .iter()
+            .find(|n| n.string == string && n.kind != NoteType::Rest)
+    }
+
+    /// A note of a chord: its pitch, its string and the tie to the next note.
+    fn write_note(&self, position: (usize, usize, usize), note: &Note) -> String {
+        let mut item = String::new();
+        if note.kind == NoteType::Dead {
+            item.push_str("\\deadNote ");
+        }
+        if note.effect.ghost_note {
+            item.push_str("\\parenthesize ");
+        }
+        if self.track.percussion_track {
+            item.push_str(drum_name(note.value));
+        } else {
+            item.push_str(&pitch(self.midi_pitch(note), self.key));
+            if note.string > 0 {
+                item.push_str(&format!("\\{}", note.string));
+            }
+            if note
+                .effect
+                .harmonic
+                .as_ref()
+                .is_some_and(|h| h.kind == HarmonicType::Natural)
+            {
+                item.push_str("\\harmonic");
+            }
+        }
+        if self
+            .next_note(position, note.string)
+            .is_some_and(|n| n.kind == NoteType::Tie)
+        {
+            item.push('~');
+        }
+        item
+    }
+
+    fn write_beat(&mut self, position: (usize, usize, usize), beat: &Beat) -> String {
+        let v_idx = position.1.min(1);
+        let notes: Vec<&Note> = beat
+            .notes
+            .iter()
+            .filter(|n| n.kind != NoteType::Rest)
+            .collect();
+        if beat.status == BeatStatus::Empty {
+            return format!("s{}", duration(&beat.duration));
+        }
+        if beat.status == BeatStatus::Rest || notes.is_empty() {
+            let mut rest = format!("r{}", duration(&beat.duration));
+            if self.slurs[v_idx] {
+                rest.push(')');
+                self.slurs[v_idx] = false;
+            }
+            return rest;
+        }
+
+        let mut item = String::new();
+        // Grace notes are written before the chord they lead to
+        let graces: Vec<String> = notes
+            .iter()
+            .filter_map(|n| {
+                let grace = n.effect.grace.as_ref()?;
+                let midi = self.midi_pitch(n) - i32::from(n.value) + i32::from(grace.fret);
+                let mut note = pitch(midi, self.key);
+                if n.string > 0 && !self.track.percussion_track {
+                    note.push_str(&format!("\\{}", n.string));
+                }
+                Some(format!("<{}>{}", note, grace.duration.max(1)))
+            })
+            .collect();
+        if !graces.is_empty() {
+            let on_beat = notes
+                .iter()
+                .filter_map(|n| n.effect.grace.as_ref())
+                .any(|g| g.is_on_beat);
+            let command = if on_beat {
+                "\\appoggiatura"
+            } else {
+                "\\acciaccatura"
+            };
+            item.push_str(&format!("{} {{ {} }} ", command, graces.join(" ")));
+        }
+
+        let chord: Vec<String> = notes.iter().map(|n| self.write_note(position, n)).collect();
+        item.push_str(&format!(
+            "<{}>{}",
+            chord.join(" "),
+            duration(&beat.duration)
+        ));
+        if let Some(tremolo) = notes.iter().find_map(|n| n.effect.tremolo_picking.as_ref()) {
+            item.push_str(&format!(":{}", tremolo.duration.value.max(8)));
+        }
+        item.push_str(&self.articulations(beat, &notes));
+
+        // Hammer-ons and pull-offs are slurred to the next beat
+        let hammer = notes.iter().any(|n| n.effect.hammer);
+        if hammer && !self.slurs[v_idx] {
+            item.push('(');
+            self.slurs[v_idx] = true;
+        } else if !hammer && self.slurs[v_idx] {
+            item.push(')');
+            self.slurs[v_idx] = false;
+        }
+        item
+    }
+
+    /// Articulations, techniques and texts of a beat, written after its duration.
+    fn articulations(&self, beat: &Beat, notes: &[&Note]) -> String {
+        let any = |f: fn(&Note) -> bool| notes.iter().any(|n| f(n));
+        let mut items: Vec<String> = Vec::new();
+        let scripts = [
+            (any(|n| n.effect.staccato), "-."),
+            (any(|n| n.effect.accentuated_note), "->"),
+            (any(|n| n.effect.heavy_accentuated_note), "-^"),
+            (any(|n| n.effect.trill.is_some()), "\\trill"),
+            (any(|n| n.effect.vibrato) || beat.effect.vibrato, "\\prall"),
+            (
+                beat.effect.stroke.direction != BeatStrokeDirection::None,
+                "\\arpeggio",
+            ),
+            (
+                any(|n| {
+                    n.effect
+                        .slides
+                        .iter()
+                        .any(|s| matches!(s, SlideType::ShiftSlideTo | SlideType::LegatoSlideTo))
+                }),
+                "\\glissando",
+            ),
+        ];
+        items.extend(scripts.iter().filter(|s| s.0).map(|s| s.1.to_string()));
+        let bend = notes
+            .iter()
+            .find_map(|n| n.effect.bend.as_ref())
+            .or(beat.effect.tremolo_bar.as_ref());
+        if let Some(amount) = bend.and_then(bend_amount) {
+            items.push(format!("\\bendAfter #{:+}", amount));
+        }
+        let texts = [
+            (any(|n| n.effect.palm_mute), "P.M."),
+            (any(|n| n.effect.let_ring), "let ring"),
+            (beat.effect.slap_effect == SlapEffect::Tapping, "T"),
+            (beat.effect.slap_effect == SlapEffect::Slapping, "S"),
+            (beat.effect.slap_effect == SlapEffect::Popping, "P"),
+        ];
+        items.extend(
+            texts
+                .iter()
+                .filter(|t| t.0)
+                .map(|t| format!("^{}", quote(t.1))),
+        );
+        if !beat.text.is_empty() {
+            items.push(format!("^{}", quote(&beat.text)));
+        }
+        items.concat()
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Score
+// ---------------------------------------------------------------------------
+
+impl Song {
+    fn lilypond_header(&self) -> String {
+        let composer = if self.author.is_empty() {
+            &self.artist
+        } else {
+            &self.author
+        };
+        let fields = [
+            ("title", &self.name),
+            ("subtitle", &self.subtitle),
+            ("composer", composer),
+            ("poet", &self.words),
+            ("arranger", &self.transcriber),
+            ("copyright", &self.copyright),
+        ];
+        let mut header = String::from("\\header {\n");
+        for (name, value) in fields.iter().filter(|f| !f.1.is_empty()) {
+            header.push_str(&format!("  {} = {}\n", name, quote(value)));
+        }
+        header.push_str("  tagline = ##f\n}\n");
+        header
+    }
+
+    /// Tempo changes of the mix tables of the first track in a measure, by position.
+    fn lilypond_tempo_changes(&self, m_idx: usize) -> Vec<(i64, i32)> {
+        let mut changes = Vec::new();
+        let measure = self.tracks.first().and_then(|t| t.measures.get(m_idx));
+        for voice in measure.iter().flat_map(|m| &m.voices) {
+            let mut position = 0;
+            for beat in &voice.beats {
+                let tempo = beat
+                    .effect
+                    .mix_table_change
+                    .as_ref()
+                    .and_then(|m| m.tempo.as_ref())
+                    .map(|t| i32::from(t.value))
+                    .filter(|t| *t > 0);
+                if let Some(tempo) = tempo {
+                    changes.push((position, tempo));
+                }
+                position += duration_ticks(&beat.duration);
+            }
+        }
+        changes.sort_by_key(|c| c.0);
+        changes.dedup_by_key(|c| c.0);
+        changes
+    }
+
+    /// Signatures, tempos, markers and repeats of the measures, as skips shared by every staff.
+    fn lilypond_global(&self) -> String {
+        let headers = &self.measure_headers;
+        let mut global = String::from("global = {\n");
+        let mut tempo = 0;
+        for (m_idx, header) in headers.iter().enumerate() {
+            let previous = m_idx.checked_sub(1).map(|i| &headers[i]);
+            let mut items: Vec<String> = Vec::new();
+            let time_signature = &header.time_signature;
+            if previous.is_none_or(|p| p.time_signature != header.time_signature) {
+                items.push(format!(
+                    "\\time {}/{}",
+                    time_signature.numerator, time_signature.denominator.value
+                ));
+            }
+            let key = &header.key_signature;
+            if previous.is_none_or(|p| p.key_signature != *key) {
+                let (keys, mode) = if key.is_minor {
+                    (&MINOR_KEYS, "minor")
+                } else {
+                    (&MAJOR_KEYS, "major")
+                };
+                items.push(format!(
+                    "\\key {} \\{}",
+                    keys[(key.key.clamp(-7, 7) + 7) as usize],
+                    mode
+                ));
+            }
+
+            // Bar line between this measure and the previous one
+            let closes = previous.is_some_and(|p| p.repeat_close > 0);
+            let bar = match (closes, header.repeat_open) {
+                (true, true) => Some(":|.|:"),
+                (true, false) => Some(":|."),
+                (false, true) => Some(".|:"),
+                _ if previous.is_some_and(|p| p.double_bar) => Some("||"),
+                _ => None,
+            };
+            if let Some(bar) = bar {
+                items.push(format!("\\bar {}", quote(bar)));
+            }
+            let alternative = header.repeat_alternative;
+            let ending_stops = previous.is_some_and(|p| {
+                p.repeat_alternative != 0
+                    && (p.repeat_alternative != alternative || p.repeat_close > 0)
+            });
+            let ending_starts = alternative != 0
+                && previous
+                    .is_none_or(|p| p.repeat_alternative != alternative || p.repeat_close > 0);
+            let mut commands = Vec::new();
+            if ending_stops {
+                commands.push(String::from("(volta #f)"));
+            }
+            if ending_starts {
+                commands.push(format!("(volta {})", quote(&ending_numbers(alternative).1)));
+            }
+            if !commands.is_empty() {
+                items.push(format!(
+                    "\\set Score.repeatCommands = #'({})",
+                    commands.join(" ")
+                ));
+            }
+            if let Some(marker) = &header.marker {
+                items.push(format!(
+                    "\\mark \\markup {{ \\box {} }}",
+                    quote(marker.title.trim())
+                ));
+            }
+            if let Some(sign) = &header.direction {
+                items.push(direction_sign(sign));
+            }
+
+            // Skips of the measure, cut at the tempo changes
+            let mut changes = self.lilypond_tempo_changes(m_idx);
+            let measure_tempo = match header.tempo {
+                t if t > 0 => t,
+                _ if m_idx == 0 => i32::from(self.tempo),
+                _ => 0,
+            };
+            if measure_tempo > 0 && changes.first().is_none_or(|c| c.0 > 0) {
+                changes.insert(0, (0, measure_tempo));
+            }
+            let mut position = 0;
+            for (at, change) in changes {
+                let at = at.min(header.length());
+                if at > position {
+                    items.push(format!("s{}", scaled_whole(at - position)));
+                    position = at;
+                }
+                if change != tempo && position < header.length() {
+                    items.push(format!("\\tempo 4 = {}", change));
+                    tempo = change;
+                }
+            }
+            items.push(format!("s{}", scaled_whole(header.length() - position)));
+            global.push_str(&format!("  {} |\n", items.join(" ")));
+        }
+        let last = headers.last();
+        if last.is_some_and(|h| h.repeat_close > 0) {
+            global.push_str("  \\bar \":|.\"\n");
+        } else if !headers.is_empty() {
+            global.push_str("  \\bar \"|.\"\n");
+        }
+        if last.is_some_and(|h| h.repeat_alternative != 0) {
+            global.push_str("  \\set Score.repeatCommands = #'((volta #f))\n");
+        }
+        global.push_str("}\n");
+        global
+    }
+
+    /// Staves of a track: notation and tablature, or a drum staff for percussion tracks.
+    fn lilypond_staves(&self, t_idx: usize) -> String {
+        let track = &self.tracks[t_idx];
+        let music = variable_name(t_idx);
+        let name = quote(&track.name);
+        if track.percussion_track {
+            return format!(
+                "    \\new DrumStaff \\with {{ instrumentName = {} }} << \\global \\{} >>\n",
+                name, music
+            );
+        }
+        // Tunings are written from the lowest string
+        let tuning: Vec<String> = track
+            .strings
+            .iter()
+            .rev()
+            .map(|(_, note)| pitch(i32::from(*note), 0))
+            .collect();
+        format!(
+            concat!(
+                "    \\new StaffGroup \\with {{ instrumentName = {} }} <<\n",
+                "      \\new Staff \\with {{ \\omit StringNumber }} << \\global \\{} >>\n",
+                "      \\new TabStaff \\with {{\n",
+                "        stringTunings = \\stringTuning <{}>\n",
+                "      }} << \\global \\removeWithTag #'notation \\{} >>\n",
+                "    >>\n"
+            ),
+            name,
+            music,
+            tuning.join(" "),
+            music
+        )
+    }
+}
+
+impl SongLilyPondExportOps for Song {
+    fn write_lilypond(&self) -> GpResult<String> {
+        self.check_note_strings()?;
+        let mut ly = format!("\\version {}\n\n", quote(LILYPOND_VERSION));
+        ly.push_str(&self.lilypond_header());
+        ly.push('\n');
+        ly.push_str(&self.lilypond_global());
+        for t_idx in 0..self.tracks.len() {
+            let mut writer = TrackWriter::new(self, t_idx);
+            let music = writer.write_music();
+            let mode = if self.tracks[t_idx].percussion_track {
+                "\\drummode "
+            } else {
+                ""
+            };
+            ly.push_str(&format!(
+                "\n{} = {}{{\n{}}}\n",
+                variable_name(t_idx),
+                mode,
+                music
+            ));
+        }
+        ly.push_str("\n\\score {\n  <<\n");
+        for t_idx in 0..self.tracks.len() {
+            ly.push_str(&self.lilypond_staves(t_idx));
+        }
+        ly.push_str("  >>\n  \\layout { }\n}\n");
+        Ok(ly)
+    }
+}
@@ -1,4 +1,24 @@
+pub mod abc_export;
+pub mod alphatex;
+pub mod alphatex_export;
+pub mod alphatex_import;
+pub mod ascii_tab_import;
 pub mod gpif;
+pub mod gpif_export;
 pub mod gpif_import;
+pub(crate) mod gpif_merge;
 pub mod gpx;
+#[cfg(feature = "serde")]
+pub mod json;
+pub mod lilypond_export;
+pub mod musescore_export;
+pub mod musescore_import;
+pub mod musicxml_export;
+pub mod musicxml_import;
+pub(crate) mod notation;
+pub mod powertab_import;
 pub mod primitive;
+pub mod tuxguitar;
+pub mod tuxguitar_export;
+pub mod tuxguitar_import;
+pub(crate) mod xml;
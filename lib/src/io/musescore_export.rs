@@ -0,0 +1,813 @@
+//! MuseScore export, in the format of MuseScore 3 that MuseScore 3 and 4 both open.
+//!
+//! Every track becomes a part. Pitched tracks get a standard notation staff and a tablature staff
+//! linked to it, built from the strings of the track. Percussion tracks get a percussion staff
+//! played with the drum set of MuseScore.
+use std::collections::{HashMap, HashSet};
+use std::io::{Cursor, Write};
+
+use zip::{write::SimpleFileOptions, ZipWriter};
+
+use crate::error::{GpError, GpResult};
+use crate::io::musicxml_export::{
+    duration_ticks, ending_numbers, note_type, track_syllables, tuplet_brackets, Syllable,
+};
+use crate::io::notation::duration_from_ticks;
+use crate::io::xml::XmlElement;
+use crate::model::{
+    beat::{Beat, Voice},
+    enums::*,
+    key_signature::DURATION_QUARTER_TIME,
+    note::Note,
+    song::Song,
+    track::Track,
+};
+
+/// Name of the score in compressed files.
+const MSCZ_SCORE_PATH: &str = "score.mscx";
+/// Ticks per quarter note of MuseScore 3.
+const MUSESCORE_DIVISION: i32 = 480;
+
+/// Tonal pitch class of each pitch class, spelled with sharps or with flats.
+const SHARP_TPC: [i32; 12] = [14, 21, 16, 23, 18, 13, 20, 15, 22, 17, 24, 19];
+const FLAT_TPC: [i32; 12] = [14, 9, 16, 11, 18, 13, 8, 15, 10, 17, 12, 19];
+
+pub trait SongMuseScoreExportOps {
+    /// Export the song as a MuseScore document (`.mscx`). Every played note must be on a string of
+    /// its track.
+    fn write_mscx(&self) -> GpResult<String>;
+    /// Export the song as a compressed MuseScore file (`.mscz`).
+    fn write_mscz(&self) -> GpResult<Vec<u8>>;
+}
+
+// ---------------------------------------------------------------------------
+// Helper functions
+// ---------------------------------------------------------------------------
+
+fn text(name: &str, value: impl ToString) -> XmlElement {
+    XmlElement::with_text(name, value)
+}
+
+fn tpc(midi: i32, key: i8) -> i32 {
+    let table = if key < 0 { &FLAT_TPC } else { &SHARP_TPC };
+    table[midi.rem_euclid(12) as usize]
+}
+
+/// MuseScore clef type: guitars and basses sound an octave below the written notes.
+fn clef_type(clef: &MeasureClef) -> &'static str {
+    match clef {
+        MeasureClef::Treble => "G8vb",
+        MeasureClef::Bass => "F8vb",
+        MeasureClef::Tenor => "C4",
+        MeasureClef::Alto => "C3",
+    }
+}
+
+/// Dots and duration type of a chord or a rest.
+fn push_duration(element: &mut XmlElement, value: u16, dotted: bool, double_dotted: bool) {
+    if double_dotted {
+        element.children.push(text("dots", 2));
+    } else if dotted {
+        element.children.push(text("dots", 1));
+    }
+    element
+        .children
+        .push(text("durationType", note_type(value)));
+}
+
+/// A `Marker` for segno, coda and fine signs, or a `Jump` for the other navigation signs.
+fn direction_sign(sign: &DirectionSign) -> XmlElement {
+    let marker = |label: &str, content: XmlElement| {
+        XmlElement::new("Marker")
+            .with_child(XmlElement::new("text").with_child(content))
+            .with_child(text("label", label))
+    };
+    let (words, jump_to, play_until, continue_at) = match sign {
+        DirectionSign::Segno => return marker("segno", text("sym", "segno")),
+        DirectionSign::SegnoSegno => return marker("varsegno", text("sym", "segnoSerpent1")),
+        DirectionSign::Coda => return marker("codab", text("sym", "coda")),
+        DirectionSign::DoubleCoda => return marker("varcoda", text("sym", "codaSquare")),
+        DirectionSign::Fine => {
+            return XmlElement::new("Marker")
+                .with_child(text("text", "Fine"))
+                .with_child(text("label", "fine"))
+        }
+        DirectionSign::DaCoda | DirectionSign::DaDoubleCoda => {
+            return XmlElement::new("Marker")
+                .with_child(text("text", "To Coda"))
+                .with_child(text("label", "coda"))
+        }
+        DirectionSign::DaCapo => ("D.C.", "start", "end", ""),
+        DirectionSign::DaCapoAlCoda => ("D.C. al Coda", "start", "coda", "codab"),
+        DirectionSign::DaCapoAlDoubleCoda => ("D.C. al Double Coda", "start", "coda", "varcoda"),
+        DirectionSign::DaCapoAlFine => ("D.C. al Fine", "start", "fine", ""),
+        DirectionSign::DaSegno => ("D.S.", "segno", "end", ""),
+        DirectionSign::DaSegnoAlCoda => ("D.S. al Coda", "segno", "coda", "codab"),
+        DirectionSign::DaSegnoAlDoubleCoda => ("D.S. al Double Coda", "segno", "coda", "varcoda"),
+        DirectionSign::DaSegnoAlFine => ("D.S. al Fine", "segno", "fine", ""),
+        DirectionSign::DaSegnoSegno => ("D.S.S.", "varsegno", "end", ""),
+        DirectionSign::DaSegnoSegnoAlCoda => ("D.S.S. al Coda", "varsegno", "coda", "codab"),
+        DirectionSign::DaSegnoSegnoAlDoubleCoda => {
+            ("D.S.S. al Double Coda", "varsegno", "coda", "varcoda")
+        }
+        DirectionSign::DaSegnoSegnoAlFine => ("D.S.S. al Fine", "varsegno", "fine", ""),
+    };
+    XmlElement::new("Jump")
+        .with_child(text("text", words))
+        .with_child(text("jumpTo", jump_to))
+        .with_child(text("playUntil", play_until))
+        .with_child(text("continueAt", continue_at))
+}
+
+fn tempo_element(tempo: i32) -> XmlElement {
+    // Quarter notes per second
+    XmlElement::new("Tempo")
+        .with_child(text("tempo", f64::from(tempo) / 60.0))
+        .with_child(text("followText", 1))
+        .with_child(text("text", format!("\u{2669} = {}", tempo)))
+}
+
+// ---------------------------------------------------------------------------
+// Staff writer
+// ---------------------------------------------------------------------------
+
+/// Notes a tie starts from, by (measure, voice, beat, string): a tied note continues the last note
+/// played on its string in the voice, however many beats before.
+fn tie_starts(track: &Track) -> HashSet<(usize, usize, usize, i8)> {
+    let mut starts = HashSet::new();
+    let voice_count = track.measures.iter().map(|m| m.voices.len()).max();
+    for v_idx in 0..voice_count.unwrap_or(0) {
+        let mut last: HashMap<i8, (usize, usize)> = HashMap::new();
+        for (m_idx, measure) in track.measures.iter().enumerate() {
+            let Some(voice) = measure.voices.get(v_idx) else {
+                continue;
+            };
+            for (b_idx, beat) in voice.beats.iter().enumerate() {
+                for note in beat.notes.iter().filter(|n| n.kind != NoteType::Rest) {
+                    if note.kind == NoteType::Tie {
+                        if let Some((m, b)) = last.get(&note.string) {
+                            starts.insert((*m, v_idx, *b, note.string));
+                        }
+                    }
+                    last.insert(note.string, (m_idx, b_idx));
+                }
+            }
+        }
+    }
+    starts
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StaffKind {
+    Notation,
+    Tablature,
+    Percussion,
+}
+
+struct StaffWriter<'a> {
+    song: &'a Song,
+    track: &'a Track,
+    kind: StaffKind,
+    /// The first staff of the score carries the tempo, markers, navigation signs and endings
+    first_staff: bool,
+    /// Syllables by (measure, beat of the first voice), sung on the first staff of the part
+    lyrics: HashMap<(usize, usize), Vec<Syllable>>,
+    /// Next id of a tuplet or a spanner, unique in the score
+    next_id: usize,
+    /// Notes a tie starts from, by (measure, voice, beat, string)
+    tie_starts: HashSet<(usize, usize, usize, i8)>,
+    /// Ties started and not ended yet, by (voice, string)
+    ties: HashMap<(usize, i8), usize>,
+    /// Alternate ending being written, and the one to stop at the start of the next measure
+    volta: Option<usize>,
+    volta_stop: Option<usize>,
+    tempo: i32,
+    key: i8,
+}
+
+impl<'a> StaffWriter<'a> {
+    fn new(song: &'a Song, t_idx: usize, kind: StaffKind, next_id: usize) -> Self {
+        StaffWriter {
+            song,
+            track: &song.tracks[t_idx],
+            kind,
+            first_staff: t_idx == 0 && kind != StaffKind::Tablature,
+            lyrics: if kind == StaffKind::Tablature {
+                HashMap::new()
+            } else {
+                track_syllables(song, t_idx)
+            },
+            next_id,
+            tie_starts: tie_starts(&song.tracks[t_idx]),
+            ties: HashMap::new(),
+            volta: None,
+            volta_stop: None,
+            tempo: song.tempo.into(),
+            key: 0,
+        }
+    }
+
+    fn id(&mut self) -> usize {
+        self.next_id += 1;
+        self.next_id - 1
+    }
+
+    fn write_staff(&mut self, id: usize) -> XmlElement {
+        let mut staff = XmlElement::new("Staff").with_attribute("id", id);
+        if self.first_staff {
+            staff.children.push(self.song.musescore_title_frame());
+        }
+        for m_idx in 0..self.song.measure_headers.len() {
+            staff.children.push(self.write_measure(m_idx));
+        }
+        staff
+    }
+
+    fn write_measure(&mut self, m_idx: usize) -> XmlElement {
+        let headers = &self.song.measure_headers;
+        let header = &headers[m_idx];
+        let previous = m_idx.checked_sub(1).map(|i| &headers[i]);
+        let mut measure = XmlElement::new("Measure");
+        if header.repeat_open {
+            measure.children.push(XmlElement::new("startRepeat"));
+        }
+        if header.repeat_close > 0 {
+            // Number of times the passage is played
+            measure
+                .children
+                .push(text("endRepeat", header.repeat_close.max(2)));
+        }
+
+        let mut items = Vec::new();
+        if let Some(id) = self.volta_stop.take() {
+            items.push(XmlElement::new("endSpanner").with_attribute("id", id));
+        }
+        let track_measure = self.track.measures.get(m_idx);
+        if self.kind == StaffKind::Notation {
+            let clef = track_measure.map(|m| &m.clef);
+            let previous_clef = m_idx
+                .checked_sub(1)
+                .and_then(|i| self.track.measures.get(i))
+                .map(|m| &m.clef);
+            if let (Some(clef), Some(previous_clef)) = (clef, previous_clef) {
+                if std::mem::discriminant(clef) != std::mem::discriminant(previous_clef) {
+                    items.push(
+                        XmlElement::new("Clef")
+                            .with_child(text("concertClefType", clef_type(clef)))
+                            .with_child(text("transposingClefType", clef_type(clef))),
+                    );
+                }
+            }
+        }
+        self.key = header.key_signature.key;
+        if self.kind != StaffKind::Percussion
+            && previous.is_none_or(|p| p.key_signature != header.key_signature)
+        {
+            let mut key = XmlElement::new("KeySig").with_child(text("accidental", self.key));
+            if header.key_signature.is_minor {
+                key.children.push(text("mode", "minor"));
+            }
+            items.push(key);
+        }
+        if previous.is_none_or(|p| p.time_signature != header.time_signature) {
+            items.push(
+                XmlElement::new("TimeSig")
+                    .with_child(text("sigN", header.time_signature.numerator))
+                    .with_child(text("sigD", header.time_signature.denominator.value)),
+            );
+        }
+
+        if self.first_staff {
+            items.extend(self.header_items(m_idx));
+        }
+
+        let empty = Voice::default();
+        let mut voices: Vec<&Voice> = track_measure
+            .map(|m| m.voices.iter().collect())
+            .unwrap_or_default();
+        if voices.is_empty() {
+            voices.push(&empty);
+        }
+        for (v_idx, voice) in voices.into_iter().enumerate() {
+            if v_idx > 0 && voice.beats.iter().all(|b| b.status == BeatStatus::Empty) {
+                continue;
+            }
+            let mut element = XmlElement::new("voice");
+            if v_idx == 0 {
+                element.children.append(&mut items);
+            }
+            element
+                .children
+                .extend(self.write_voice(m_idx, v_idx, voice));
+            if v_idx == 0 {
+                element.children.extend(self.measure_end(m_idx));
+            }
+            measure.children.push(element);
+        }
+        measure
+    }
+
+    /// Tempo, rehearsal mark, navigation sign and start of an alternate ending of a measure.
+    fn header_items(&mut self, m_idx: usize) -> Vec<XmlElement> {
+        let headers = &self.song.measure_headers;
+        let header = &headers[m_idx];
+        let mut items = Vec::new();
+        if m_idx == 0 || (header.tempo > 0 && header.tempo != self.tempo) {
+            if header.tempo > 0 {
+                self.tempo = header.tempo;
+            }
+            items.push(tempo_element(self.tempo));
+        }
+        if let Some(marker) = &header.marker {
+            items.push(XmlElement::new("RehearsalMark").with_child(text("text", &marker.title)));
+        }
+        if let Some(sign) = &header.direction {
+            items.push(direction_sign(sign));
+        }
+        let alternative = header.repeat_alternative;
+        let previous = m_idx.checked_sub(1).map(|i| &headers[i]);
+        let starts = alternative != 0
+            && previous.is_none_or(|p| p.repeat_alternative != alternative || p.repeat_close > 0);
+        if starts {
+            let id = self.id();
+            let (numbers, label) = ending_numbers(alternative);
+            items.push(
+                XmlElement::new("Volta")
+                    .with_attribute("id", id)
+                    .with_child(text("endHookType", i32::from(header.repeat_close > 0)))
+                    .with_child(text("beginText", label))
+                    .with_child(text("endings", numbers)),
+            );
+            self.volta = Some(id);
+        }
+        items
+    }
+
+    /// Double bar and end of an alternate ending, after the last chord of a measure.
+    fn measure_end(&mut self, m_idx: usize) -> Vec<XmlElement> {
+        let headers = &self.song.measure_headers;
+        let header = &headers[m_idx];
+        let mut items = Vec::new();
+        let next = headers.get(m_idx + 1);
+        let volta_ends = next.is_none_or(|n| n.repeat_alternative != header.repeat_alternative)
+            || header.repeat_close > 0;
+        if volta_ends {
+            // MuseScore stops an ending where the next measure starts, or at the end of the score
+            match (self.volta.take(), next) {
+                (Some(id), None) => {
+                    items.push(XmlElement::new("endSpanner").with_attribute("id", id))
+                }
+                (id, Some(_)) => self.volta_stop = id,
+                (None, None) => {}
+            }
+        }
+        if header.double_bar {
+            items.push(XmlElement::new("BarLine").with_child(text("subtype", "double")));
+        }
+        items
+    }
+
+    /// Chords and rests of a voice. The first voice is completed with rests to fill the measure.
+    fn write_voice(&mut self, m_idx: usize, v_idx: usize, voice: &Voice) -> Vec<XmlElement> {
+        let header = &self.song.measure_headers[m_idx];
+        let mut elements = Vec::new();
+        if v_idx == 0 && voice.beats.iter().all(|b| b.status == BeatStatus::Empty) {
+            let time_signature = &header.time_signature;
+            elements.push(
+                XmlElement::new("Rest")
+                    .with_child(text("durationType", "measure"))
+                    .with_child(text(
+                        "duration",
+                        format!(
+                            "{}/{}",
+                            time_signature.numerator, time_signature.denominator.value
+                        ),
+                    )),
+            );
+            return elements;
+        }
+
+        let brackets = tuplet_brackets(&voice.beats);
+        let mut tuplet = None;
+        let mut length = 0;
+        for (b_idx, beat) in voice.beats.iter().enumerate() {
+            elements.extend(self.beat_items(beat));
+            if brackets[b_idx].0 {
+                let id = self.id();
+                let d = &beat.duration;
+                // The base note of a tuplet is its smallest note
+                let end = (b_idx..brackets.len())
+                    .find(|i| brackets[*i].1)
+                    .unwrap_or(brackets.len() - 1);
+                let base = voice.beats[b_idx..=end]
+                    .iter()
+                    .map(|b| b.duration.value)
+                    .max()
+                    .unwrap_or(d.value);
+                elements.push(
+                    XmlElement::new("Tuplet")
+                        .with_attribute("id", id)
+                        .with_child(text("normalNotes", d.tuplet_times))
+                        .with_child(text("actualNotes", d.tuplet_enters))
+                        .with_child(text("baseNote", note_type(base))),
+                );
+                tuplet = Some(id);
+            }
+            elements.extend(self.write_beat((m_idx, v_idx, b_idx), beat, tuplet));
+            if brackets[b_idx].1 {
+                tuplet = None;
+            }
+            length += duration_ticks(&beat.duration);
+        }
+        if v_idx == 0 {
+            let mut gap = header.length() - length;
+            while gap >= DURATION_QUARTER_TIME / 32 {
+                let duration = duration_from_ticks(gap);
+                gap -= duration_ticks(&duration);
+                let mut rest = XmlElement::new("Rest");
+                push_duration(
+                    &mut rest,
+                    duration.value,
+                    duration.dotted,
+                    duration.double_dotted,
+                );
+                elements.push(rest);
+            }
+        }
+        elements
+    }
+
+    /// Texts and tempo changes attached to a beat.
+    fn beat_items(&mut self, beat: &Beat) -> Vec<XmlElement> {
+        let mut items = Vec::new();
+        if !beat.text.is_empty() && self.kind != StaffKind::Tablature {
+            items.push(XmlElement::new("StaffText").with_child(text("text", &beat.text)));
+        }
+        if self.first_staff {
+            let tempo = beat
+                .effect
+                .mix_table_change
+                .as_ref()
+                .and_then(|m| m.tempo.as_ref())
+                .map(|t| i32::from(t.value));
+            if let Some(tempo) = tempo.filter(|t| *t > 0 && *t != self.tempo) {
+                self.tempo = tempo;
+                items.push(tempo_element(tempo));
+            }
+        }
+        items
+    }
+
+    fn midi_pitch(&self, note: &Note) -> i32 {
+        note.real_value(&self.track.strings)
+            .unwrap_or(i32::from(note.value))
+    }
+
+    fn write_beat(
+        &mut self,
+        position: (usize, usize, usize),
+        beat: &Beat,
+        tuplet: Option<usize>,
+    ) -> Vec<XmlElement> {
+        let duration = &beat.duration;
+        let notes: Vec<&Note> = beat
+            .notes
+            .iter()
+            .filter(|n| n.kind != NoteType::Rest)
+            .collect();
+        if beat.status != BeatStatus::Normal || notes.is_empty() {
+            let mut rest = XmlElement::new("Rest");
+            if beat.status == BeatStatus::Empty {
+                rest.children.push(text("visible", 0));
+            }
+            if let Some(id) = tuplet {
+                rest.children.push(text("Tuplet", id));
+            }
+            push_duration(
+                &mut rest,
+                duration.value,
+                duration.dotted,
+                duration.double_dotted,
+            );
+            return vec![rest];
+        }
+
+        let mut elements = Vec::new();
+        // Grace chords are written before the chord they lead to
+        for note in &notes {
+            let Some(grace) = &note.effect.grace else {
+                continue;
+            };
+            let kind = if grace.is_on_beat {
+                "appoggiatura"
+            } else {
+                "acciaccatura"
+            };
+            let mut chord = XmlElement::new("Chord").with_child(XmlElement::new(kind));
+            push_duration(&mut chord, u16::from(grace.duration), false, false);
+            let midi = self.midi_pitch(note) - i32::from(note.value) + i32::from(grace.fret);
+            let mut element = XmlElement::new("Note");
+            if grace.is_dead {
+                element.children.push(text("head", "cross"));
+            }
+            self.push_pitch(&mut element, midi, note.string, grace.fret.into());
+            elements.push(chord.with_child(element));
+        }
+
+        let mut chord = XmlElement::new("Chord");
+        if let Some(id) = tuplet {
+            chord.children.push(text("Tuplet", id));
+        }
+        push_duration(
+            &mut chord,
+            duration.value,
+            duration.dotted,
+            duration.double_dotted,
+        );
+        let (m_idx, v_idx, b_idx) = position;
+        if v_idx == 0 {
+            for (line, syllable, syllabic) in self.lyrics.get(&(m_idx, b_idx)).into_iter().flatten()
+            {
+                let mut lyrics = XmlElement::new("Lyrics");
+                if *line > 1 {
+                    lyrics.children.push(text("no", line - 1));
+                }
+                chord.children.push(
+                    lyrics
+                        .with_child(text("syllabic", syllabic))
+                        .with_child(text("text", syllable)),
+                );
+            }
+        }
+        let articulations = [
+            (
+                "articStaccatoAbove",
+                notes.iter().any(|n| n.effect.staccato),
+            ),
+            (
+                "articAccentAbove",
+                notes.iter().any(|n| n.effect.accentuated_note),
+            ),
+            (
+                "articMarcatoAbove",
+                notes.iter().any(|n| n.effect.heavy_accentuated_note),
+            ),
+        ];
+        for (subtype, _) in articulations.iter().filter(|a| a.1) {
+            chord
+                .children
+                .push(XmlElement::new("Articulation").with_child(text("subtype", subtype)));
+        }
+        for note in notes {
+            chord.children.push(self.write_note(position, note));
+        }
+        elements.push(chord);
+        elements
+    }
+
+    fn write_note(&mut self, position: (usize, usize, usize), note: &Note) -> XmlElement {
+        let (m_idx, v_idx, b_idx) = position;
+        let mut element = XmlElement::new("Note");
+        if note.kind == NoteType::Tie {
+            if let Some(id) = self.ties.remove(&(v_idx, note.string)) {
+                element
+                    .children
+                    .push(XmlElement::new("endSpanner").with_attribute("id", id));
+            }
+        }
+        if self
+            .tie_starts
+            .contains(&(m_idx, v_idx, b_idx, note.string))
+        {
+            let id = self.id();
+            self.ties.insert((v_idx, note.string), id);
+            element
+                .children
+                .push(XmlElement::new("Tie").with_attribute("id", id));
+        }
+        if note.effect.ghost_note {
+            element.children.push(text("ghost", 1));
+        }
+        if note.kind == NoteType::Dead {
+            element.children.push(text("head", "cross"));
+        }
+        self.push_pitch(&mut element, self.midi_pitch(note), note.string, note.value);
+        element
+    }
+
+    /// Pitch and spelling of a note, with its string and fret on pitched staves.
+    fn push_pitch(&self, element: &mut XmlElement, midi: i32, string: i8, fret: i16) {
+        element.children.push(text("pitch", midi.clamp(0, 127)));
+        let key = if self.kind == StaffKind::Percussion {
+            0
+        } else {
+            self.key
+        };
+        element.children.push(text("tpc", tpc(midi, key)));
+        if self.kind != StaffKind::Percussion {
+            // Strings are numbered from the highest one, starting at zero
+            element.children.push(text("fret", fret));
+            element.children.push(text("string", string - 1));
+        }
+    }
+}
+
+impl Song {
+    /// Title frame at the top of the first page.
+    fn musescore_title_frame(&self) -> XmlElement {
+        let mut frame = XmlElement::new("VBox").with_child(text("height", 10));
+        let texts = [
+            ("Title", &self.name),
+            ("Subtitle", &self.subtitle),
+            ("Composer", &self.author),
+            ("Lyricist", &self.words),
+        ];
+        for (style, value) in texts {
+            if !value.is_empty() {
+                frame.children.push(
+                    XmlElement::new("Text")
+                        .with_child(text("style", style))
+                        .with_child(text("text", value)),
+                );
+            }
+        }
+        frame
+    }
+
+    /// Part of a track: its staves, the linked tablature of pitched tracks, and its instrument.
+    fn musescore_part(&self, t_idx: usize, staff_id: usize) -> XmlElement {
+        let track = &self.tracks[t_idx];
+        let mut part = XmlElement::new("Part");
+        if track.percussion_track {
+            part.children.push(
+                XmlElement::new("Staff")
+                    .with_attribute("id", staff_id)
+                    .with_child(
+                        XmlElement::new("StaffType")
+                            .with_attribute("group", "percussion")
+                            .with_child(text("name", "perc5Line")),
+                    )
+                    .with_child(text("defaultClef", "PERC")),
+            );
+        } else {
+            let clef = track
+                .measures
+                .first()
+                .map_or("G8vb", |m| clef_type(&m.clef));
+            let string_count = track.strings.len();
+            part.children.push(
+                XmlElement::new("Staff")
+                    .with_attribute("id", staff_id)
+                    .with_child(
+                        XmlElement::new("StaffType")
+                            .with_attribute("group", "pitched")
+                            .with_child(text("name", "stdNormal")),
+                    )
+                    .with_child(text("defaultClef", clef)),
+            );
+            part.children.push(
+                XmlElement::new("Staff")
+                    .with_attribute("id", staff_id + 1)
+                    .with_child(text("linkedTo", staff_id))
+                    .with_child(
+                        XmlElement::new("StaffType")
+                            .with_attribute("group", "tablature")
+                            .with_child(text("name", format!("tab{}StrCommon", string_count)))
+                            .with_child(text("lines", string_count))
+                            .with_child(text("lineDistance", 1.5)),
+                    ),
+            );
+        }
+        part.children.push(text("trackName", &track.name));
+
+        let mut instrument =
+            XmlElement::new("Instrument").with_child(text("longName", &track.name));
+        if !track.short_name.is_empty() {
+            instrument
+                .children
+                .push(text("shortName", &track.short_name));
+        }
+        instrument.children.push(text("trackName", &track.name));
+        if track.percussion_track {
+            instrument.children.push(text("useDrumset", 1));
+        } else {
+            // Lowest string first
+            let mut strings = track.strings.clone();
+            strings.sort_by_key(|s| std::cmp::Reverse(s.0));
+            let mut string_data =
+                XmlElement::new("StringData").with_child(text("frets", track.fret_count));
+            for (_, tuning) in strings {
+                string_data.children.push(text("string", tuning));
+            }
+            instrument.children.push(string_data);
+        }
+        let channel = self.channels.get(track.channel_index);
+        let program = if track.percussion_track {
+            0
+        } else {
+            track
+                .midi_program_gpif
+                .or_else(|| channel.map(|c| c.instrument))
+                .unwrap_or(25)
+                .clamp(0, 127)
+        };
+        let mut midi = XmlElement::new("Channel");
+        if let Some(channel) = channel {
+            midi.children.push(
+                XmlElement::new("controller")
+                    .with_attribute("ctrl", 7)
+                    .with_attribute("value", channel.volume.max(0)),
+            );
+            midi.children.push(
+                XmlElement::new("controller")
+                    .with_attribute("ctrl", 10)
+                    .with_attribute("value", channel.balance.max(0)),
+            );
+        }
+        midi.children
+            .push(XmlElement::new("program").with_attribute("value", program));
+        if let Some(channel) = channel.filter(|_| !track.percussion_track) {
+            midi.children
+                .push(text("midiChannel", channel.channel % 16));
+        }
+        midi.children.push(text("synti", "Fluid"));
+        instrument.children.push(midi);
+        part.with_child(instrument)
+    }
+}
+
+impl SongMuseScoreExportOps for Song {
+    fn write_mscx(&self) -> GpResult<String> {
+        self.check_note_strings()?;
+        let mut score = XmlElement::new("Score").with_child(text("Division", MUSESCORE_DIVISION));
+        let transcriber = if self.transcriber.is_empty() {
+            &self.writer
+        } else {
+            &self.transcriber
+        };
+        let tags = [
+            ("arranger", transcriber),
+            ("composer", &self.author),
+            ("copyright", &self.copyright),
+            ("lyricist", &self.words),
+            ("source", &self.album),
+            ("subtitle", &self.subtitle),
+            ("workTitle", &self.name),
+        ];
+        for (name, value) in tags {
+            score
+                .children
+                .push(text("metaTag", value).with_attribute("name", name));
+        }
+
+        // Staves of each track, numbered from one across the score
+        let mut staves = Vec::with_capacity(self.tracks.len());
+        let mut staff_id = 1;
+        for (t_idx, track) in self.tracks.iter().enumerate() {
+            score.children.push(self.musescore_part(t_idx, staff_id));
+            if track.percussion_track {
+                staves.push((t_idx, StaffKind::Percussion, staff_id));
+                staff_id += 1;
+            } else {
+                staves.push((t_idx, StaffKind::Notation, staff_id));
+                staves.push((t_idx, StaffKind::Tablature, staff_id + 1));
+                staff_id += 2;
+            }
+        }
+        let mut next_id = 1;
+        for (t_idx, kind, id) in staves {
+            let mut writer = StaffWriter::new(self, t_idx, kind, next_id);
+            score.children.push(writer.write_staff(id));
+            next_id = writer.next_id;
+        }
+
+        let root = XmlElement::new("museScore")
+            .with_attribute("version", "3.02")
+            .with_child(score);
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        root.write(&mut xml, 0);
+        Ok(xml)
+    }
+
+    fn write_mscz(&self) -> GpResult<Vec<u8>> {
+        let container =
+            XmlElement::new("container").with_child(XmlElement::new("rootfiles").with_child(
+                XmlElement::new("rootfile").with_attribute("full-path", MSCZ_SCORE_PATH),
+            ));
+        let entries = [
+            ("META-INF/container.xml", container.to_document()),
+            (MSCZ_SCORE_PATH, self.write_mscx()?),
+        ];
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default();
+        for (name, content) in entries {
+            zip.start_file(name, options)
+                .map_err(|e| GpError::WriteError(format!("Zip error: {}", e)))?;
+            zip.write_all(content.as_bytes())?;
+        }
+        let cursor = zip
+            .finish()
+            .map_err(|e| GpError::WriteError(format!("Zip error: {}", e)))?;
+        Ok(cursor.into_inner())
+    }
+}
@@ -0,0 +1,974 @@
+//! MuseScore import (MuseScore 3 and 4 scores, plain `.mscx` or compressed `.mscz`).
+//!
+//! Every part becomes a track, read from its tablature staff when it has one, or else from its first
+//! staff. MuseScore keeps the string and the fret of the notes of string instruments, the other notes
+//! are placed on the strings from their pitch. Only the first two voices of a staff are read.
+use std::collections::HashMap;
+use std::io::{Cursor, Read};
+
+use zip::ZipArchive;
+
+use crate::audio::midi::{MidiChannel, DEFAULT_PERCUSSION_CHANNEL};
+use crate::error::{GpError, GpResult};
+use crate::io::musicxml_import::{
+    child_number, child_text, children, dynamic_to_velocity, lyrics_lines, note_type_value,
+    parse_direction_words, parse_ending_numbers, Syllable,
+};
+use crate::io::notation::duration_from_ticks;
+use crate::io::xml::XmlElement;
+use crate::model::{
+    beat::{Beat, Voice},
+    conversion::{LossPosition, LostItem},
+    effects::{GraceEffect, FORTE},
+    enums::*,
+    headers::{Marker, MeasureHeader},
+    key_signature::*,
+    lyric::Lyrics,
+    measure::Measure,
+    note::Note,
+    song::Song,
+    track::Track,
+};
+
+/// Voices kept per measure, as in Guitar Pro files.
+const MAX_VOICES: usize = 2;
+/// Standard guitar tuning, for parts without string data.
+const DEFAULT_TUNING: [(i8, i8); 6] = [(1, 64), (2, 59), (3, 55), (4, 50), (5, 45), (6, 40)];
+const DEFAULT_FRET_COUNT: u8 = 24;
+
+pub trait SongMuseScoreOps {
+    /// Read a MuseScore document, the `.mscx` file of a score.
+    fn read_mscx_document(&mut self, xml: &str) -> GpResult<()>;
+}
+
+/// Decode a MuseScore document: UTF-8, with or without a byte order mark.
+pub(crate) fn decode_mscx(data: &[u8]) -> GpResult<String> {
+    let data = data.strip_prefix(b"\xef\xbb\xbf").unwrap_or(data);
+    String::from_utf8(data.to_vec())
+        .map_err(|_| GpError::FormatError("MuseScore document is not valid UTF-8".into()))
+}
+
+fn read_zip_entry(zip: &mut ZipArchive<Cursor<&[u8]>>, name: &str) -> GpResult<Vec<u8>> {
+    let mut file = zip
+        .by_name(name)
+        .map_err(|e| GpError::FormatError(format!("MSCZ archive error ({}): {}", name, e)))?;
+    let mut content = Vec::with_capacity(file.size() as usize);
+    file.read_to_end(&mut content)?;
+    Ok(content)
+}
+
+/// Extract the score of a compressed MuseScore file (`.mscz`). The score is the first root file
+/// listed in `META-INF/container.xml`, or else the first `.mscx` file of the archive.
+pub(crate) fn read_mscz(data: &[u8]) -> GpResult<String> {
+    let mut zip = ZipArchive::new(Cursor::new(data))
+        .map_err(|e| GpError::FormatError(format!("MSCZ archive error: {}", e)))?;
+    let root_file = read_zip_entry(&mut zip, "META-INF/container.xml")
+        .ok()
+        .and_then(|container| {
+            let container = XmlElement::parse(&String::from_utf8_lossy(&container)).ok()?;
+            container
+                .child("rootfiles")?
+                .child("rootfile")?
+                .attribute("full-path")
+                .map(str::to_string)
+        });
+    let root_file = match root_file {
+        Some(root_file) => root_file,
+        None => zip
+            .file_names()
+            .find(|n| n.ends_with(".mscx"))
+            .map(str::to_string)
+            .ok_or_else(|| GpError::FormatError("MSCZ archive without a score".into()))?,
+    };
+    decode_mscx(&read_zip_entry(&mut zip, &root_file)?)
+}
+
+// ---------------------------------------------------------------------------
+// Helper functions
+// ---------------------------------------------------------------------------
+
+/// Text of an element with the one of its formatting children, e.g. `<text><b>Title</b></text>`.
+fn plain_text(element: &XmlElement) -> String {
+    let mut text = element.text().to_string();
+    for child in &element.children {
+        text.push_str(&plain_text(child));
+    }
+    text
+}
+
+/// Ticks of a fraction of a whole note, written "numerator/denominator".
+fn fraction_ticks(fraction: &str) -> Option<i64> {
+    let (numerator, denominator) = fraction.split_once('/')?;
+    let numerator: i64 = numerator.trim().parse().ok()?;
+    let denominator: i64 = denominator.trim().parse().ok()?;
+    (denominator > 0).then(|| numerator * DURATION_QUARTER_TIME * 4 / denominator)
+}
+
+fn duration_ticks(duration: &Duration) -> i64 {
+    let base = DURATION_QUARTER_TIME * 4 / i64::from(duration.value.max(1));
+    let mut ticks = base;
+    if duration.dotted {
+        ticks += base / 2;
+    } else if duration.double_dotted {
+        ticks += base / 2 + base / 4;
+    }
+    if !duration.is_default_tuplet() && duration.tuplet_enters > 0 {
+        ticks = ticks * i64::from(duration.tuplet_times) / i64::from(duration.tuplet_enters);
+    }
+    ticks
+}
+
+/// Navigation sign of a `Marker`, from its label or its type.
+fn marker_direction(marker: &XmlElement) -> Option<DirectionSign> {
+    let kind = child_text(marker, "label")
+        .filter(|l| !l.is_empty())
+        .or(child_text(marker, "subtype"))?;
+    match kind.to_lowercase().as_str() {
+        "segno" | "varsegno" => Some(DirectionSign::Segno),
+        "codab" | "varcoda" | "codetta" => Some(DirectionSign::Coda),
+        "coda" | "tocoda" | "tocodasym" => Some(DirectionSign::DaCoda),
+        "fine" => Some(DirectionSign::Fine),
+        _ => None,
+    }
+}
+
+/// Navigation sign of a `Jump`, from its text or from where it jumps to and plays until.
+fn jump_direction(jump: &XmlElement) -> DirectionSign {
+    if let Some(sign) = jump
+        .child("text")
+        .and_then(|t| parse_direction_words(&plain_text(t)))
+    {
+        return sign;
+    }
+    let segno = child_text(jump, "jumpTo").is_some_and(|j| j.contains("segno"));
+    let until = child_text(jump, "playUntil").unwrap_or_default();
+    match (segno, until) {
+        (false, "fine") => DirectionSign::DaCapoAlFine,
+        (false, until) if until.contains("coda") => DirectionSign::DaCapoAlCoda,
+        (false, _) => DirectionSign::DaCapo,
+        (true, "fine") => DirectionSign::DaSegnoAlFine,
+        (true, until) if until.contains("coda") => DirectionSign::DaSegnoAlCoda,
+        (true, _) => DirectionSign::DaSegno,
+    }
+}
+
+/// A `Spanner` of the given type, MuseScore 4 writes one at each end of ties and voltas.
+fn spanner<'a>(element: &'a XmlElement, kind: &str) -> Option<&'a XmlElement> {
+    children(element, "Spanner").find(|s| s.attribute("type") == Some(kind))
+}
+
+fn is_grace(chord: &XmlElement) -> bool {
+    chord.children.iter().any(|c| {
+        matches!(
+            c.name.as_str(),
+            "acciaccatura" | "appoggiatura" | "grace4" | "grace16" | "grace32"
+        )
+    })
+}
+
+fn is_grace_after(chord: &XmlElement) -> bool {
+    chord
+        .children
+        .iter()
+        .any(|c| c.name.starts_with("grace") && c.name.ends_with("after"))
+}
+
+// ---------------------------------------------------------------------------
+// Part reader
+// ---------------------------------------------------------------------------
+
+/// Part information: instrument, staves and string data.
+#[derive(Debug, Default)]
+struct PartInfo {
+    name: String,
+    short_name: String,
+    /// Staff read for the track: the tablature one, or else the first one
+    staff: String,
+    staves: Vec<String>,
+    percussion: bool,
+    strings: Vec<(i8, i8)>,
+    fret_count: u8,
+    channel: Option<u8>,
+    program: Option<i32>,
+    volume: Option<i8>,
+    pan: Option<i8>,
+}
+
+impl PartInfo {
+    fn new(part: &XmlElement) -> Self {
+        let empty = XmlElement::new("Instrument");
+        let instrument = part.child("Instrument").unwrap_or(&empty);
+        let name = [
+            child_text(instrument, "longName"),
+            child_text(part, "trackName"),
+            child_text(instrument, "trackName"),
+        ]
+        .into_iter()
+        .flatten()
+        .find(|n| !n.is_empty())
+        .unwrap_or_default();
+        let staff_group = |staff: &XmlElement| {
+            staff
+                .child("StaffType")
+                .and_then(|t| t.attribute("group"))
+                .unwrap_or("pitched")
+                .to_string()
+        };
+        let staves: Vec<(String, String)> = children(part, "Staff")
+            .map(|s| (s.attribute("id").unwrap_or("1").to_string(), staff_group(s)))
+            .collect();
+        let mut info = PartInfo {
+            name: name.to_string(),
+            short_name: child_text(instrument, "shortName")
+                .unwrap_or_default()
+                .to_string(),
+            staff: staves
+                .iter()
+                .find(|s| s.1 == "tablature")
+                .or(staves.first())
+                .map_or(String::from("1"), |s| s.0.clone()),
+            staves: staves.iter().map(|s| s.0.clone()).collect(),
+            percussion: child_text(instrument, "useDrumset") == Some("1")
+                || staves.iter().any(|s| s.1 == "percussion"),
+            strings: DEFAULT_TUNING.to_vec(),
+            fret_count: DEFAULT_FRET_COUNT,
+            ..Default::default()
+        };
+        if let Some(string_data) = instrument.child("StringData") {
+            // Lowest string first, the model starts with the highest one
+            let pitches: Vec<i8> = children(string_data, "string")
+                .filter_map(|s| s.text().trim().parse().ok())
+                .collect();
+            if !pitches.is_empty() {
+                info.strings = pitches
+                    .iter()
+                    .rev()
+                    .enumerate()
+                    .map(|(i, pitch)| ((i + 1) as i8, *pitch))
+                    .collect();
+            }
+            info.fret_count = child_number(string_data, "frets").unwrap_or(DEFAULT_FRET_COUNT);
+        }
+        if info.percussion {
+            info.strings = (1..=6).map(|s| (s, 0)).collect();
+        }
+        if let Some(channel) = instrument.child("Channel") {
+            info.channel = child_number::<u8>(channel, "midiChannel").filter(|c| *c < 16);
+            info.program = channel
+                .child("program")
+                .and_then(|p| p.attribute("value")?.parse().ok());
+            for controller in children(channel, "controller") {
+                let value = controller
+                    .attribute("value")
+                    .and_then(|v| v.parse::<i8>().ok());
+                match controller.attribute("ctrl") {
+                    Some("7") => info.volume = value,
+                    Some("10") => info.pan = value,
+                    _ => {}
+                }
+            }
+        }
+        info
+    }
+}
+
+/// A grace chord waiting for the chord it leads to.
+struct PendingGrace {
+    effect: GraceEffect,
+    fret: Option<i8>,
+    pitch: Option<i32>,
+}
+
+struct StaffReader<'a> {
+    track: &'a Track,
+    velocity: i16,
+    /// Tuplets of MuseScore 3, by id: the chords point to them
+    tuplets: HashMap<String, (u8, u8)>,
+    /// Ties of MuseScore 3 started and not ended yet, by id
+    ties: Vec<String>,
+    /// Fret of the previous chord, to place the next one nearby
+    hand: Option<i16>,
+    /// Beat being read, where the notes that fit on no string are reported
+    position: LossPosition,
+    /// Notes and voices the model cannot hold, for `Song::read_losses`
+    losses: Vec<LostItem>,
+}
+
+impl<'a> StaffReader<'a> {
+    fn new(track: &'a Track) -> Self {
+        StaffReader {
+            track,
+            velocity: FORTE,
+            tuplets: HashMap::new(),
+            ties: Vec::new(),
+            hand: None,
+            position: LossPosition {
+                track: Some(track.number as usize),
+                ..Default::default()
+            },
+            losses: Vec::new(),
+        }
+    }
+
+    fn read_measures(&mut self, staff: &XmlElement, headers: &[MeasureHeader]) -> Vec<Measure> {
+        let measures: Vec<&XmlElement> = children(staff, "Measure").collect();
+        let empty = XmlElement::new("Measure");
+        headers
+            .iter()
+            .enumerate()
+            .map(|(m_idx, header)| {
+                self.read_measure(
+                    measures.get(m_idx).copied().unwrap_or(&empty),
+                    m_idx,
+                    header,
+                )
+            })
+            .collect()
+    }
+
+    fn read_measure(
+        &mut self,
+        element: &XmlElement,
+        m_idx: usize,
+        header: &MeasureHeader,
+    ) -> Measure {
+        // Measures of MuseScore 2 hold the chords of a single voice
+        let mut elements: Vec<&XmlElement> = children(element, "voice").collect();
+        if elements.is_empty() {
+            elements.push(element);
+        }
+        if elements[MAX_VOICES.min(elements.len())..]
+            .iter()
+            .any(|v| v.child("Chord").is_some())
+        {
+            self.losses.push(LostItem {
+                feature: "additional voice",
+                position: LossPosition {
+                    track: self.position.track,
+                    measure: Some(m_idx + 1),
+                    ..Default::default()
+                },
+            });
+        }
+        let mut voices: Vec<Voice> = Vec::new();
+        for (v_idx, element) in elements.iter().take(MAX_VOICES).enumerate() {
+            self.position.measure = Some(m_idx + 1);
+            self.position.voice = Some(v_idx + 1);
+            voices.push(self.read_voice(element, header));
+        }
+        voices.resize_with(MAX_VOICES, Voice::default);
+        for (v_idx, voice) in voices.iter_mut().enumerate() {
+            voice.measure_index = m_idx as i16;
+            if voice.beats.is_empty() {
+                // An empty first voice holds a rest for the whole measure
+                voice.beats.push(if v_idx == 0 {
+                    Beat {
+                        status: BeatStatus::Rest,
+                        duration: duration_from_ticks(header.length()),
+                        ..Default::default()
+                    }
+                } else {
+                    Beat {
+                        status: BeatStatus::Empty,
+                        ..Default::default()
+                    }
+                });
+            }
+        }
+        Measure {
+            number: m_idx + 1,
+            track_index: (self.track.number - 1) as usize,
+            header_index: m_idx,
+            key_signature: header.key_signature.clone(),
+            time_signature: header.time_signature.clone(),
+            clef: self.clef(element),
+            voices,
+            ..Default::default()
+        }
+    }
+
+    fn clef(&self, measure: &XmlElement) -> MeasureClef {
+        let clef = children(measure, "voice")
+            .flat_map(|v| children(v, "Clef"))
+            .chain(children(measure, "Clef"))
+            .find_map(|c| child_text(c, "concertClefType").or(child_text(c, "subtype")));
+        match clef {
+            Some(clef) if clef.starts_with('F') => MeasureClef::Bass,
+            Some("C4") => MeasureClef::Tenor,
+            Some(clef) if clef.starts_with('C') => MeasureClef::Alto,
+            _ => MeasureClef::Treble,
+        }
+    }
+
+    fn read_duration(&self, element: &XmlElement, header: &MeasureHeader) -> Duration {
+        let duration_type = child_text(element, "durationType").unwrap_or("quarter");
+        if duration_type == "measure" {
+            let ticks = child_text(element, "duration")
+                .and_then(fraction_ticks)
+                .unwrap_or(header.length());
+            return duration_from_ticks(ticks);
+        }
+        let dots = child_number::<u8>(element, "dots").unwrap_or(0);
+        Duration {
+            value: note_type_value(duration_type).unwrap_or(4),
+            dotted: dots == 1,
+            double_dotted: dots >= 2,
+            ..Default::default()
+        }
+    }
+
+    fn read_voice(&mut self, element: &XmlElement, header: &MeasureHeader) -> Voice {
+        let mut voice = Voice::default();
+        let (mut position, mut end) = (0, 0);
+        // Tuplet of MuseScore 4: the chords between `Tuplet` and `endTuplet`
+        let mut tuplet = None;
+        let mut graces: Vec<PendingGrace> = Vec::new();
+        for child in &element.children {
+            match child.name.as_str() {
+                "location" => {
+                    position += child_text(child, "fractions")
+                        .and_then(fraction_ticks)
+                        .unwrap_or(0)
+                }
+                "Tuplet" => {
+                    let ratio = (
+                        child_number(child, "actualNotes").unwrap_or(1),
+                        child_number(child, "normalNotes").unwrap_or(1),
+                    );
+                    match child.attribute("id") {
+                        Some(id) => {
+                            self.tuplets.insert(id.to_string(), ratio);
+                        }
+                        None => tuplet = Some(ratio),
+                    }
+                }
+                "endTuplet" => tuplet = None,
+                "Dynamic" => {
+                    if let Some(velocity) =
+                        child_text(child, "subtype").and_then(dynamic_to_velocity)
+                    {
+                        self.velocity = velocity;
+                    }
+                }
+                "Chord" if is_grace_after(child) => {}
+                "Chord" if is_grace(child) => graces.push(self.read_grace(child)),
+                "Chord" | "Rest" => {
+                    let mut duration = self.read_duration(child, header);
+                    let ratio = match child.child("Tuplet") {
+                        Some(id) => self.tuplets.get(id.text().trim()).copied(),
+                        None => tuplet,
+                    };
+                    if let Some((enters, times)) =
+                        ratio.filter(|r| r.0 > 0 && r.1 > 0 && r.0 != r.1)
+                    {
+                        duration.tuplet_enters = enters;
+                        duration.tuplet_times = times;
+                    }
+                    // Fill the time skipped with a location with rests
+                    let mut gap = position - end;
+                    while gap >= DURATION_QUARTER_TIME / 32 {
+                        let duration = duration_from_ticks(gap);
+                        gap -= duration_ticks(&duration);
+                        voice.beats.push(Beat {
+                            status: BeatStatus::Rest,
+                            duration,
+                            ..Default::default()
+                        });
+                    }
+                    let ticks = duration_ticks(&duration);
+                    let mut beat = Beat {
+                        status: BeatStatus::Rest,
+                        duration,
+                        ..Default::default()
+                    };
+                    if child.name == "Chord" {
+                        // A note has a single grace note, the first one
+                        self.position.beat = Some(voice.beats.len() + 1);
+                        self.read_chord(child, &mut beat, graces.drain(..).next());
+                    }
+                    voice.beats.push(beat);
+                    position = position.max(end) + ticks;
+                    end = position;
+                }
+                _ => {}
+            }
+        }
+        voice
+    }
+
+    fn read_grace(&self, chord: &XmlElement) -> PendingGrace {
+        let note = chord.child("Note");
+        let duration = child_text(chord, "durationType")
+            .and_then(note_type_value)
+            .map_or(32, |v| v.clamp(16, 64) as u8);
+        PendingGrace {
+            effect: GraceEffect {
+                duration,
+                is_dead: note.and_then(|n| child_text(n, "head")) == Some("cross"),
+                is_on_beat: chord.child("appoggiatura").is_some(),
+                velocity: self.velocity,
+                ..Default::default()
+            },
+            fret: note.and_then(|n| child_number(n, "fret")),
+            pitch: note.and_then(|n| child_number(n, "pitch")),
+        }
+    }
+
+    /// String and fret of the notes of a chord: the ones written in the file when every note has
+    /// them, or else computed from the pitches.
+    fn positions(&mut self, notes: &[&XmlElement], pitches: &[i32]) -> Vec<Option<(i8, i16)>> {
+        let track = self.track;
+        if track.percussion_track {
+            return (0..pitches.len())
+                .map(|i| (i < track.strings.len()).then(|| ((i + 1) as i8, pitches[i] as i16)))
+                .collect();
+        }
+        let written: Option<Vec<(i8, i16)>> = notes
+            .iter()
+            .map(|n| {
+                let string: usize = child_number(n, "string")?;
+                let fret: i16 = child_number(n, "fret")?;
+                (string < track.strings.len() && fret >= 0).then_some(((string + 1) as i8, fret))
+            })
+            .collect();
+        let positions = match written {
+            Some(written)
+                if (1..written.len())
+                    .all(|i| !written[..i].iter().any(|w| w.0 == written[i].0)) =>
+            {
+                written.into_iter().map(Some).collect()
+            }
+            _ => track.fret_positions(pitches, self.hand),
+        };
+        if let Some(fret) = positions
+            .iter()
+            .flatten()
+            .map(|p| p.1)
+            .filter(|f| *f > 0)
+            .min()
+        {
+            self.hand = Some(fret);
+        }
+        positions
+    }
+
+    fn read_chord(&mut self, chord: &XmlElement, beat: &mut Beat, grace: Option<PendingGrace>) {
+        let notes: Vec<&XmlElement> = children(chord, "Note").collect();
+        let pitches: Vec<i32> = notes
+            .iter()
+            .map(|n| child_number(n, "pitch").unwrap_or(0))
+            .collect();
+        let positions = self.positions(&notes, &pitches);
+        let articulations: Vec<&str> = children(chord, "Articulation")
+            .filter_map(|a| child_text(a, "subtype"))
+            .collect();
+        let has_articulation = |name: &str| articulations.iter().any(|a| a.starts_with(name));
+        let mut grace = grace;
+        for (element, position) in notes.iter().zip(positions) {
+            // Ties: MuseScore 3 ends them with the id of their start, MuseScore 4 with a spanner
+            let mut tied = spanner(element, "Tie").is_some_and(|s| s.child("prev").is_some());
+            for end in children(element, "endSpanner") {
+                if let Some(index) = self
+                    .ties
+                    .iter()
+                    .position(|t| Some(t.as_str()) == end.attribute("id"))
+                {
+                    self.ties.remove(index);
+                    tied = true;
+                }
+            }
+            if let Some(id) = element.child("Tie").and_then(|t| t.attribute("id")) {
+                self.ties.push(id.to_string());
+            }
+            let Some((string, value)) = position else {
+                self.losses.push(LostItem {
+                    feature: "note that fits on no string",
+                    position: self.position.clone(),
+                });
+                continue;
+            };
+            let mut note = Note {
+                value,
+                string,
+                velocity: self.velocity,
+                kind: if tied {
+                    NoteType::Tie
+                } else {
+                    NoteType::Normal
+                },
+                ..Default::default()
+            };
+            if child_text(element, "head") == Some("cross") {
+                note.kind = NoteType::Dead;
+            }
+            note.effect.ghost_note = child_text(element, "ghost") == Some("1");
+            note.effect.staccato = has_articulation("articStaccat");
+            note.effect.accentuated_note = has_articulation("articAccent");
+            note.effect.heavy_accentuated_note = has_articulation("articMarcato");
+            if let Some(grace) = grace.take() {
+                let tuning = self
+                    .track
+                    .strings
+                    .iter()
+                    .find(|s| s.0 == note.string)
+                    .map_or(0, |s| i32::from(s.1));
+                let fret = grace.fret.unwrap_or_else(|| {
+                    grace
+                        .pitch
+                        .map_or(note.value as i8, |p| (p - tuning).clamp(0, 127) as i8)
+                });
+                note.effect.grace = Some(GraceEffect {
+                    fret,
+                    ..grace.effect
+                });
+            }
+            beat.notes.push(note);
+        }
+        if !beat.notes.is_empty() {
+            beat.status = BeatStatus::Normal;
+        }
+    }
+}
+
+/// Syllables of every measure of a staff.
+fn read_syllables(staff: &XmlElement) -> Vec<Vec<Syllable>> {
+    let mut syllables: Vec<Vec<Syllable>> = Vec::new();
+    for (m_idx, measure) in children(staff, "Measure").enumerate() {
+        let chords = children(measure, "voice")
+            .flat_map(|v| children(v, "Chord"))
+            .chain(children(measure, "Chord"));
+        for lyric in chords.flat_map(|c| children(c, "Lyrics")) {
+            let text = lyric.child("text").map(plain_text).unwrap_or_default();
+            if text.is_empty() {
+                continue;
+            }
+            if syllables.len() <= m_idx {
+                syllables.resize_with(m_idx + 1, Vec::new);
+            }
+            // Verses are numbered from 0
+            let number = child_number::<u32>(lyric, "no").unwrap_or(0) + 1;
+            syllables[m_idx].push((
+                number.to_string(),
+                text,
+                child_text(lyric, "syllabic")
+                    .unwrap_or("single")
+                    .to_string(),
+            ));
+        }
+    }
+    syllables
+}
+
+// ---------------------------------------------------------------------------
+// Main conversion
+// ---------------------------------------------------------------------------
+
+/// An alternate ending being read: its endings and where it stops.
+struct Volta {
+    endings: u8,
+    start: usize,
+    /// First measure after the ending, given by MuseScore 4
+    until: Option<usize>,
+    /// Id of the ending, MuseScore 3 stops it with an `endSpanner`
+    id: Option<String>,
+}
+
+impl Volta {
+    fn ends_in(&self, items: &[&XmlElement], m_idx: usize) -> bool {
+        match &self.id {
+            Some(id) => items
+                .iter()
+                .any(|i| i.name == "endSpanner" && i.attribute("id") == Some(id)),
+            None => self.until.is_some_and(|until| until <= m_idx),
+        }
+    }
+}
+
+/// Measure headers, read from the first staff: time and key signatures, repeats, alternate endings,
+/// tempo, markers and navigation signs. Returns the headers and the tempo at the start of the song.
+fn read_measure_headers(staff: &XmlElement, count: usize) -> (Vec<MeasureHeader>, Option<i32>) {
+    let mut headers: Vec<MeasureHeader> = Vec::with_capacity(count);
+    let mut song_tempo = None;
+    let mut volta: Option<Volta> = None;
+    let measures: Vec<&XmlElement> = children(staff, "Measure").collect();
+    let empty = XmlElement::new("Measure");
+    for m_idx in 0..count {
+        let mut header = MeasureHeader {
+            number: (m_idx + 1) as u16,
+            ..Default::default()
+        };
+        if let Some(previous) = headers.last() {
+            header.time_signature = previous.time_signature.clone();
+            header.key_signature = previous.key_signature.clone();
+        }
+        let measure = measures.get(m_idx).copied().unwrap_or(&empty);
+        let mut items: Vec<&XmlElement> = children(measure, "voice")
+            .flat_map(|v| v.children.iter())
+            .collect();
+        items.extend(measure.children.iter().filter(|c| c.name != "voice"));
+
+        // An ending stops where the measure holding its end starts, or where it ends when the end
+        // follows the chords of the measure
+        let lead = items
+            .iter()
+            .position(|i| matches!(i.name.as_str(), "Chord" | "Rest"))
+            .unwrap_or(items.len());
+        if volta
+            .as_ref()
+            .is_some_and(|v| v.start < m_idx && v.ends_in(&items[..lead], m_idx))
+        {
+            volta = None;
+        }
+        header.repeat_alternative = volta.as_ref().map_or(0, |v| v.endings);
+
+        for item in &items {
+            match item.name.as_str() {
+                "startRepeat" => header.repeat_open = true,
+                "endRepeat" => header.repeat_close = item.text().trim().parse().unwrap_or(2),
+                "TimeSig" => {
+                    if let (Some(numerator), Some(denominator)) = (
+                        child_number::<i8>(item, "sigN"),
+                        child_number::<u16>(item, "sigD"),
+                    ) {
+                        header.time_signature.numerator = numerator.max(1);
+                        header.time_signature.denominator.value = denominator;
+                    }
+                }
+                "KeySig" => {
+                    if let Some(key) =
+                        child_number::<i8>(item, "concertKey").or(child_number(item, "accidental"))
+                    {
+                        header.key_signature.key = key;
+                    }
+                    header.key_signature.is_minor = child_text(item, "mode") == Some("minor");
+                }
+                "Tempo" => {
+                    // Quarter notes per second
+                    if let Some(tempo) = child_number::<f64>(item, "tempo")
+                        .map(|t| (t * 60.0).round() as i32)
+                        .filter(|t| *t > 0)
+                    {
+                        if m_idx == 0 && song_tempo.is_none() {
+                            song_tempo = Some(tempo);
+                        }
+                        header.tempo = tempo;
+                    }
+                }
+                "RehearsalMark" => {
+                    header.marker = Some(Marker {
+                        title: item.child("text").map(plain_text).unwrap_or_default(),
+                        ..Default::default()
+                    })
+                }
+                "Marker" => header.direction = marker_direction(item).or(header.direction),
+                "Jump" => header.direction = Some(jump_direction(item)),
+                "BarLine" if child_text(item, "subtype") == Some("double") => {
+                    header.double_bar = true
+                }
+                "Volta" => {
+                    let endings = parse_ending_numbers(child_text(item, "endings").unwrap_or(""));
+                    header.repeat_alternative = endings;
+                    volta = Some(Volta {
+                        endings,
+                        start: m_idx,
+                        until: None,
+                        id: item.attribute("id").map(str::to_string),
+                    });
+                }
+                "Spanner" if item.attribute("type") == Some("Volta") => {
+                    let Some(element) = item.child("Volta") else {
+                        continue;
+                    };
+                    let endings =
+                        parse_ending_numbers(child_text(element, "endings").unwrap_or(""));
+                    let measure_count = item
+                        .child("next")
+                        .and_then(|n| n.child("location"))
+                        .and_then(|l| child_number::<usize>(l, "measures"))
+                        .unwrap_or(1)
+                        .max(1);
+                    header.repeat_alternative = endings;
+                    volta = Some(Volta {
+                        endings,
+                        start: m_idx,
+                        until: Some(m_idx + measure_count),
+                        id: None,
+                    });
+                }
+                _ => {}
+            }
+        }
+        if volta.as_ref().is_some_and(|v| {
+            let rest = if v.start == m_idx {
+                &items
+            } else {
+                &items[lead..]
+            };
+            v.ends_in(rest, m_idx)
+        }) {
+            volta = None;
+        }
+        headers.push(header);
+    }
+    (headers, song_tempo)
+}
+
+impl Song {
+    fn read_musescore_metadata(&mut self, score: &XmlElement) {
+        let tags: HashMap<&str, String> = children(score, "metaTag")
+            .map(|t| {
+                let text = plain_text(t).trim().to_string();
+                (t.attribute("name").unwrap_or_default(), text)
+            })
+            .collect();
+        let tag = |name: &str| tags.get(name).cloned().unwrap_or_default();
+        self.name = tag("workTitle");
+        self.subtitle = tag("subtitle");
+        if self.name.is_empty() {
+            self.name = tag("movementTitle");
+        } else if self.subtitle.is_empty() {
+            self.subtitle = tag("movementTitle");
+        }
+        self.author = tag("composer");
+        self.words = tag("lyricist");
+        self.transcriber = tag("arranger");
+        self.copyright = tag("copyright");
+        self.album = tag("source");
+
+        // Texts of the title frame, for the fields that are not in the metadata
+        let texts = children(score, "Staff")
+            .take(1)
+            .flat_map(|s| children(s, "VBox"))
+            .flat_map(|b| children(b, "Text"));
+        for text in texts {
+            let field = match child_text(text, "style").map(str::to_lowercase).as_deref() {
+                Some("title") => &mut self.name,
+                Some("subtitle") => &mut self.subtitle,
+                Some("composer") => &mut self.author,
+                Some("lyricist") => &mut self.words,
+                _ => continue,
+            };
+            if field.is_empty() {
+                *field = text.child("text").map(plain_text).unwrap_or_default();
+            }
+        }
+    }
+}
+
+impl SongMuseScoreOps for Song {
+    fn read_mscx_document(&mut self, xml: &str) -> GpResult<()> {
+        let root = XmlElement::parse(xml)?;
+        if root.name != "museScore" {
+            return Err(GpError::FormatError(format!(
+                "not a MuseScore document: <{}>",
+                root.name
+            )));
+        }
+        let score = root
+            .child("Score")
+            .ok_or_else(|| GpError::FormatError("MuseScore document without a score".into()))?;
+        self.read_musescore_metadata(score);
+
+        let infos: Vec<PartInfo> = children(score, "Part").map(PartInfo::new).collect();
+        let staves: HashMap<&str, &XmlElement> = children(score, "Staff")
+            .map(|s| (s.attribute("id").unwrap_or("1"), s))
+            .collect();
+        let first_staff = children(score, "Staff")
+            .next()
+            .ok_or_else(|| GpError::FormatError("MuseScore score without staves".into()))?;
+        if infos.is_empty() {
+            return Err(GpError::FormatError("MuseScore score without parts".into()));
+        }
+        let measure_count = staves
+            .values()
+            .map(|s| children(s, "Measure").count())
+            .max()
+            .unwrap_or(0)
+            .max(1);
+        let (headers, tempo) = read_measure_headers(first_staff, measure_count);
+        if let Some(tempo) = tempo {
+            self.tempo = tempo.clamp(1, i32::from(i16::MAX)) as i16;
+        }
+        self.key = headers[0].key_signature.clone();
+        self.measure_headers = headers;
+
+        // 64 channels as in Guitar Pro files, tracks without a MIDI channel get the next free one
+        self.channels = (0..64u8)
+            .map(|channel| MidiChannel {
+                channel,
+                effect_channel: channel,
+                ..Default::default()
+            })
+            .collect();
+        let mut used_channels: Vec<u8> = infos.iter().filter_map(|i| i.channel).collect();
+        let empty = XmlElement::new("Staff");
+        self.tracks.clear();
+        self.lyrics = Lyrics::default();
+        self.read_losses.clear();
+        for (t_idx, info) in infos.iter().enumerate() {
+            let mut track = Track {
+                number: (t_idx + 1) as i32,
+                name: if info.name.is_empty() {
+                    format!("Track {}", t_idx + 1)
+                } else {
+                    info.name.clone()
+                },
+                short_name: info.short_name.clone(),
+                strings: info.strings.clone(),
+                fret_count: info.fret_count,
+                percussion_track: info.percussion,
+                ..Default::default()
+            };
+            let staff = staves.get(info.staff.as_str()).copied().unwrap_or(&empty);
+            let mut reader = StaffReader::new(&track);
+            let measures = reader.read_measures(staff, &self.measure_headers);
+            self.read_losses.append(&mut reader.losses);
+            track.measures = measures;
+
+            let channel = match (info.channel, info.percussion) {
+                (_, true) => DEFAULT_PERCUSSION_CHANNEL,
+                (Some(channel), false) => channel,
+                (None, false) => {
+                    let free = (0..16u8)
+                        .find(|c| *c != DEFAULT_PERCUSSION_CHANNEL && !used_channels.contains(c))
+                        .unwrap_or(0);
+                    used_channels.push(free);
+                    free
+                }
+            };
+            track.channel_index = usize::from(channel);
+            let midi = &mut self.channels[usize::from(channel)];
+            midi.set_instrument(info.program.unwrap_or(if info.percussion { 0 } else { 25 }));
+            if let Some(volume) = info.volume {
+                midi.volume = volume;
+            }
+            if let Some(pan) = info.pan {
+                midi.balance = pan;
+            }
+
+            if self.lyrics.lines.is_empty() {
+                // Lyrics are sung on any staff of the part, the first one with lyrics is read
+                let syllables = info
+                    .staves
+                    .iter()
+                    .filter_map(|id| staves.get(id.as_str()))
+                    .map(|staff| read_syllables(staff))
+                    .find(|s| !s.is_empty())
+                    .unwrap_or_default();
+                let lines = lyrics_lines(&syllables);
+                if !lines.is_empty() {
+                    self.lyrics.track_choice = (t_idx + 1) as u8;
+                    for (line, (start, text)) in lines.into_iter().take(5).enumerate() {
+                        self.lyrics.lines.push((line as u8, start, text));
+                    }
+                }
+            }
+            self.tracks.push(track);
+        }
+        // Guitar Pro files always hold five lyrics lines
+        for line in self.lyrics.lines.len()..5 {
+            self.lyrics.lines.push((line as u8, 1, String::new()));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,1231 @@
+//! MusicXML 4.0 (partwise) export.
+//!
+//! Every track becomes a part. Pitched tracks get two staves: standard notation and tablature,
+//! the notes being written once per staff. Percussion tracks get a single percussion staff.
+use std::collections::HashMap;
+
+use crate::error::GpResult;
+use crate::io::xml::XmlElement;
+use crate::model::{
+    beat::{Beat, Voice},
+    effects::BendEffect,
+    enums::*,
+    headers::MeasureHeader,
+    key_signature::{Duration, DURATION_QUARTER_TIME},
+    note::Note,
+    song::Song,
+    track::Track,
+};
+
+/// Divisions per quarter note, the same resolution as the song.
+const DIVISIONS: i64 = DURATION_QUARTER_TIME;
+/// Voices of the tablature staff are numbered after the ones of the notation staff.
+const TAB_VOICE_OFFSET: usize = 4;
+
+const SHARP_STEPS: [(&str, i8); 12] = [
+    ("C", 0),
+    ("C", 1),
+    ("D", 0),
+    ("D", 1),
+    ("E", 0),
+    ("F", 0),
+    ("F", 1),
+    ("G", 0),
+    ("G", 1),
+    ("A", 0),
+    ("A", 1),
+    ("B", 0),
+];
+const FLAT_STEPS: [(&str, i8); 12] = [
+    ("C", 0),
+    ("D", -1),
+    ("D", 0),
+    ("E", -1),
+    ("E", 0),
+    ("F", 0),
+    ("G", -1),
+    ("G", 0),
+    ("A", -1),
+    ("A", 0),
+    ("B", -1),
+    ("B", 0),
+];
+
+pub trait SongMusicXmlExportOps {
+    /// Export the song as a MusicXML 4.0 partwise document. Every played note must be on a string
+    /// of its track.
+    fn write_musicxml(&self) -> GpResult<String>;
+}
+
+// ---------------------------------------------------------------------------
+// Helper functions
+// ---------------------------------------------------------------------------
+
+fn text(name: &str, value: impl ToString) -> XmlElement {
+    XmlElement::with_text(name, value)
+}
+
+/// Convert Duration.value to a MusicXML note type.
+pub(crate) fn note_type(value: u16) -> &'static str {
+    match value {
+        1 => "whole",
+        2 => "half",
+        8 => "eighth",
+        16 => "16th",
+        32 => "32nd",
+        64 => "64th",
+        128 => "128th",
+        _ => "quarter",
+    }
+}
+
+/// Length of a duration in divisions, dots and tuplet included.
+pub(crate) fn duration_ticks(duration: &Duration) -> i64 {
+    let base = DIVISIONS * 4 / i64::from(duration.value.max(1));
+    let mut ticks = base;
+    if duration.dotted {
+        ticks += base / 2;
+    } else if duration.double_dotted {
+        ticks += base / 2 + base / 4;
+    }
+    if !duration.is_default_tuplet() && duration.tuplet_enters > 0 {
+        ticks = ticks * i64::from(duration.tuplet_times) / i64::from(duration.tuplet_enters);
+    }
+    ticks
+}
+
+/// Spell a MIDI pitch as (step, alter, octave), with flats in flat keys.
+fn spell(midi: i32, key: i8) -> (&'static str, i8, i32) {
+    let steps = if key < 0 { &FLAT_STEPS } else { &SHARP_STEPS };
+    let (step, alter) = steps[midi.rem_euclid(12) as usize];
+    (step, alter, midi.div_euclid(12) - 1)
+}
+
+fn pitch_element(name: &str, prefix: &str, midi: i32, key: i8) -> XmlElement {
+    let (step, alter, octave) = spell(midi, key);
+    let mut pitch = XmlElement::new(name).with_child(text(&format!("{}step", prefix), step));
+    if alter != 0 && prefix.is_empty() {
+        pitch.children.push(text("alter", alter));
+    }
+    pitch.with_child(text(&format!("{}octave", prefix), octave))
+}
+
+fn direction(placement: &str, direction_type: XmlElement) -> XmlElement {
+    XmlElement::new("direction")
+        .with_attribute("placement", placement)
+        .with_child(XmlElement::new("direction-type").with_child(direction_type))
+}
+
+fn words_direction(placement: &str, words: &str) -> XmlElement {
+    direction(placement, text("words", words))
+}
+
+/// Start (text and dashes) or stop of a palm mute or let ring passage.
+fn passage_direction(words: &str, start: bool, number: usize) -> XmlElement {
+    let dashes = XmlElement::new("dashes").with_attribute("number", number);
+    if !start {
+        return direction("below", dashes.with_attribute("type", "stop"));
+    }
+    direction("below", text("words", words)).with_child(
+        XmlElement::new("direction-type").with_child(dashes.with_attribute("type", "start")),
+    )
+}
+
+fn tempo_direction(tempo: i32) -> XmlElement {
+    let metronome = XmlElement::new("metronome")
+        .with_child(text("beat-unit", "quarter"))
+        .with_child(text("per-minute", tempo));
+    direction("above", metronome)
+        .with_child(XmlElement::new("sound").with_attribute("tempo", tempo))
+}
+
+/// Navigation sign of a measure: segno and coda are drawn as symbols, jumps as text.
+fn direction_sign(sign: &DirectionSign) -> XmlElement {
+    let (words, sound) = match sign {
+        DirectionSign::Segno | DirectionSign::SegnoSegno => {
+            return direction("above", XmlElement::new("segno"))
+                .with_child(XmlElement::new("sound").with_attribute("segno", "segno"));
+        }
+        DirectionSign::Coda | DirectionSign::DoubleCoda => {
+            return direction("above", XmlElement::new("coda"))
+                .with_child(XmlElement::new("sound").with_attribute("coda", "coda"));
+        }
+        DirectionSign::Fine => ("Fine", ("fine", "yes")),
+        DirectionSign::DaCapo => ("D.C.", ("dacapo", "yes")),
+        DirectionSign::DaCapoAlCoda => ("D.C. al Coda", ("dacapo", "yes")),
+        DirectionSign::DaCapoAlDoubleCoda => ("D.C. al Double Coda", ("dacapo", "yes")),
+        DirectionSign::DaCapoAlFine => ("D.C. al Fine", ("dacapo", "yes")),
+        DirectionSign::DaSegno => ("D.S.", ("dalsegno", "segno")),
+        DirectionSign::DaSegnoAlCoda => ("D.S. al Coda", ("dalsegno", "segno")),
+        DirectionSign::DaSegnoAlDoubleCoda => ("D.S. al Double Coda", ("dalsegno", "segno")),
+        DirectionSign::DaSegnoAlFine => ("D.S. al Fine", ("dalsegno", "segno")),
+        DirectionSign::DaSegnoSegno => ("D.S.S.", ("dalsegno", "segno")),
+        DirectionSign::DaSegnoSegnoAlCoda => ("D.S.S. al Coda", ("dalsegno", "segno")),
+        DirectionSign::DaSegnoSegnoAlDoubleCoda => ("D.S.S. al Double Coda", ("dalsegno", "segno")),
+        DirectionSign::DaSegnoSegnoAlFine => ("D.S.S. al Fine", ("dalsegno", "segno")),
+        DirectionSign::DaCoda => ("To Coda", ("tocoda", "coda")),
+        DirectionSign::DaDoubleCoda => ("To Double Coda", ("tocoda", "coda")),
+    };
+    words_direction("above", words)
+        .with_child(XmlElement::new("sound").with_attribute(sound.0, sound.1))
+}
+
+/// Bends as MusicXML `bend` elements: the points are reduced to their turning points, a first
+/// point above zero is a pre-bend and every change is a bend or a release. Point values are in
+/// quarter tones, `bend-alter` in semitones.
+fn bend_elements(bend: &BendEffect) -> Vec<XmlElement> {
+    let mut values: Vec<i8> = Vec::with_capacity(bend.points.len());
+    for point in &bend.points {
+        if values.last() == Some(&point.value) {
+            continue;
+        }
+        // Keep only the turning points
+        if values.len() >= 2 {
+            let (a, b) = (values[values.len() - 2], values[values.len() - 1]);
+            if (b - a).signum() == (point.value - b).signum() {
+                values.pop();
+            }
+        }
+        values.push(point.value);
+    }
+    let mut bends = Vec::new();
+    let mut previous = 0i8;
+    for (i, value) in values.into_iter().enumerate() {
+        let alter = f32::from(value - previous) / 2.0;
+        if i == 0 {
+            if value > 0 {
+                bends.push(
+                    XmlElement::new("bend")
+                        .with_child(text("bend-alter", alter))
+                        .with_child(XmlElement::new("pre-bend")),
+                );
+            }
+        } else {
+            let mut element = XmlElement::new("bend").with_child(text("bend-alter", alter));
+            if value < previous {
+                element.children.push(XmlElement::new("release"));
+            }
+            bends.push(element);
+        }
+        previous = value;
+    }
+    bends
+}
+
+/// Split a Guitar Pro lyrics line into syllables with their MusicXML `syllabic` value.
+/// Words are separated by spaces or new lines, syllables of a word by `-`, `+` puts two syllables
+/// on the same beat and `[...]` is hidden text.
+fn lyric_syllables(line: &str) -> Vec<(String, &'static str)> {
+    let mut visible = String::with_capacity(line.len());
+    let mut hidden = false;
+    for c in line.chars() {
+        match c {
+            '[' => hidden = true,
+            ']' => hidden = false,
+            '+' if !hidden => visible.push('\u{a0}'),
+            _ if !hidden => visible.push(c),
+            _ => {}
+        }
+    }
+    let mut syllables = Vec::new();
+    // Not `split_whitespace`: the no-break spaces bind the syllables of a beat
+    let words = visible.split(|c: char| c.is_ascii_whitespace());
+    for word in words.filter(|w| !w.is_empty()) {
+        let parts: Vec<&str> = word.split('-').filter(|p| !p.is_empty()).collect();
+        for (i, part) in parts.iter().enumerate() {
+            let syllabic = match (i, parts.len()) {
+                (_, 1) => "single",
+                (0, _) => "begin",
+                (i, n) if i + 1 == n => "end",
+                _ => "middle",
+            };
+            syllables.push((part.replace('\u{a0}', " "), syllabic));
+        }
+    }
+    syllables
+}
+
+/// Tuplet bracket of each beat of a voice: `Some(true)` starts a bracket, `Some(false)` ends it.
+/// A group ends when it fills a whole number of its smallest note value, or when the ratio changes.
+pub(crate) fn tuplet_brackets(beats: &[Beat]) -> Vec<(bool, bool)> {
+    let mut brackets = vec![(false, false); beats.len()];
+    let mut group: Option<(usize, i64, u16)> = None; // first beat, ticks, smallest value
+    for (i, beat) in beats.iter().enumerate() {
+        let d = &beat.duration;
+        if d.is_default_tuplet() {
+            group = None;
+            continue;
+        }
+        let same_ratio = |j: usize| {
+            let p = &beats[j].duration;
+            p.tuplet_enters == d.tuplet_enters && p.tuplet_times == d.tuplet_times
+        };
+        let (start, ticks, value) = match group {
+            Some((start, ticks, value)) if same_ratio(start) => (start, ticks, value),
+            _ => {
+                brackets[i].0 = true;
+                (i, 0, d.value)
+            }
+        };
+        let ticks = ticks + duration_ticks(d);
+        let value = value.max(d.value);
+        let unit = DIVISIONS * 4 / i64::from(value.max(1)) * i64::from(d.tuplet_times);
+        let next_continues = beats.get(i + 1).is_some_and(|b| {
+            b.duration.tuplet_enters == d.tuplet_enters && b.duration.tuplet_times == d.tuplet_times
+        });
+        if ticks % unit == 0 || !next_continues {
+            brackets[i].1 = true;
+            group = None;
+        } else {
+            group = Some((start, ticks, value));
+        }
+    }
+    brackets
+}
+
+// ---------------------------------------------------------------------------
+// Part writer
+// ---------------------------------------------------------------------------
+
+/// A syllable bound to a beat: lyrics line, text and syllabic.
+pub(crate) type Syllable = (u8, String, &'static str);
+
+/// Syllables of the song lyrics bound to the beats of the first voice of a track, by
+/// (measure, beat). Only the track chosen for the lyrics gets any.
+pub(crate) fn track_syllables(song: &Song, t_idx: usize) -> HashMap<(usize, usize), Vec<Syllable>> {
+    let track = &song.tracks[t_idx];
+    let mut lyrics: HashMap<(usize, usize), Vec<Syllable>> = HashMap::new();
+    if usize::from(song.lyrics.track_choice) == t_idx + 1 {
+        for (line, start, content) in &song.lyrics.lines {
+            let mut syllables = lyric_syllables(content).into_iter();
+            let beats = track
+                .measures
+                .iter()
+                .enumerate()
+                .skip(usize::from(*start).saturating_sub(1))
+                .filter_map(|(m_idx, m)| m.voices.first().map(|v| (m_idx, v)))
+                .flat_map(|(m_idx, v)| v.beats.iter().enumerate().map(move |b| (m_idx, b)));
+            for (m_idx, (b_idx, beat)) in beats {
+                let sung = beat.status == BeatStatus::Normal
+                    && beat.notes.iter().any(|n| n.kind != NoteType::Tie);
+                if !sung {
+                    continue;
+                }
+                let Some((syllable, syllabic)) = syllables.next() else {
+                    break;
+                };
+                lyrics
+                    .entry((m_idx, b_idx))
+                    .or_default()
+                    .push((*line + 1, syllable, syllabic));
+            }
+        }
+    }
+    lyrics
+}
+
+struct PartWriter<'a> {
+    song: &'a Song,
+    track: &'a Track,
+    /// The first part carries the tempo, markers and navigation signs
+    first_part: bool,
+    /// Syllables by (measure, beat of the first voice)
+    lyrics: HashMap<(usize, usize), Vec<Syllable>>,
+    /// Palm mute and let ring state of each voice, to write the start and the end of passages
+    palm_mute: [bool; 4],
+    let_ring: [bool; 4],
+    tempo: i32,
+    key: i8,
+}
+
+impl<'a> PartWriter<'a> {
+    fn new(song: &'a Song, t_idx: usize) -> Self {
+        let track = &song.tracks[t_idx];
+        PartWriter {
+            song,
+            track,
+            first_part: t_idx == 0,
+            lyrics: track_syllables(song, t_idx),
+            palm_mute: [false; 4],
+            let_ring: [false; 4],
+            tempo: song.tempo.into(),
+            key: 0,
+        }
+    }
+
+    fn tab(&self) -> bool {
+        !self.track.percussion_track
+    }
+
+    /// Beat next to `b_idx` in the same voice, looking into the next or previous measure.
+    fn neighbour_beat(
+        &self,
+        m_idx: usize,
+        v_idx: usize,
+        b_idx: usize,
+        forward: bool,
+    ) -> Option<&'a Beat> {
+        let voice = |m: usize| self.track.measures.get(m).and_then(|m| m.voices.get(v_idx));
+        if forward {
+            match voice(m_idx)?.beats.get(b_idx + 1) {
+                Some(beat) => Some(beat),
+                None => voice(m_idx + 1)?.beats.first(),
+            }
+        } else if b_idx > 0 {
+            voice(m_idx)?.beats.get(b_idx - 1)
+        } else {
+            voice(m_idx.checked_sub(1)?)?.beats.last()
+        }
+    }
+
+    fn neighbour_note(
+        &self,
+        position: (usize, usize, usize),
+        string: i8,
+        forward: bool,
+    ) -> Option<&'a Note> {
+        let (m_idx, v_idx, b_idx) = position;
+        self.neighbour_beat(m_idx, v_idx, b_idx, forward)?
+            .notes
+            .iter()
+            .find(|n| n.string == string && n.kind != NoteType::Rest)
+    }
+
+    fn midi_pitch(&self, note: &Note) -> i32 {
+        note.real_value(&self.track.strings)
+            .unwrap_or(i32::from(note.value))
+    }
+
+    fn write_part(&mut self, id: &str) -> XmlElement {
+        let mut part = XmlElement::new("part").with_attribute("id", id);
+        let measure_count = self.song.measure_headers.len();
+        for m_idx in 0..measure_count {
+            part.children.push(self.write_measure(m_idx));
+        }
+        part
+    }
+
+    fn write_attributes(&self, m_idx: usize) -> Option<XmlElement> {
+        let header = &self.song.measure_headers[m_idx];
+        let previous = m_idx.checked_sub(1).map(|i| &self.song.measure_headers[i]);
+        let measure = self.track.measures.get(m_idx);
+        let previous_measure = m_idx
+            .checked_sub(1)
+            .and_then(|i| self.track.measures.get(i));
+        let key_changed = previous.is_none_or(|p| p.key_signature != header.key_signature);
+        let time_changed = previous.is_none_or(|p| p.time_signature != header.time_signature);
+        let clef_changed = match (measure, previous_measure) {
+            (Some(m), Some(p)) => {
+                std::mem::discriminant(&m.clef) != std::mem::discriminant(&p.clef)
+            }
+            _ => m_idx == 0,
+        };
+        if !key_changed && !time_changed && !clef_changed {
+            return None;
+        }
+
+        let mut attributes = XmlElement::new("attributes");
+        if m_idx == 0 {
+            attributes.children.push(text("divisions", DIVISIONS));
+        }
+        if key_changed {
+            attributes.children.push(
+                XmlElement::new("key")
+                    .with_child(text("fifths", header.key_signature.key))
+                    .with_child(text(
+                        "mode",
+                        if header.key_signature.is_minor {
+                            "minor"
+                        } else {
+                            "major"
+                        },
+                    )),
+            );
+        }
+        if time_changed {
+            attributes.children.push(
+                XmlElement::new("time")
+                    .with_child(text("beats", header.time_signature.numerator))
+                    .with_child(text("beat-type", header.time_signature.denominator.value)),
+            );
+        }
+        if m_idx == 0 && self.tab() {
+            attributes.children.push(text("staves", 2));
+        }
+        if clef_changed {
+            attributes
+                .children
+                .push(self.clef(measure.map(|m| &m.clef)));
+        }
+        if m_idx == 0 && self.tab() {
+            attributes.children.push(
+                XmlElement::new("clef")
+                    .with_attribute("number", 2)
+                    .with_child(text("sign", "TAB"))
+                    .with_child(text("line", 5)),
+            );
+            attributes.children.push(self.staff_details());
+        }
+        Some(attributes)
+    }
+
+    fn clef(&self, clef: Option<&MeasureClef>) -> XmlElement {
+        let mut element = XmlElement::new("clef");
+        if self.tab() {
+            element
+                .attributes
+                .push(("number".to_string(), "1".to_string()));
+        }
+        let (sign, line, octave_change) = match clef {
+            _ if self.track.percussion_track => ("percussion", None, false),
+            Some(MeasureClef::Bass) => ("F", Some(4), true),
+            Some(MeasureClef::Tenor) => ("C", Some(4), false),
+            Some(MeasureClef::Alto) => ("C", Some(3), false),
+            _ => ("G", Some(2), true),
+        };
+        element.children.push(text("sign", sign));
+        if let Some(line) = line {
+            element.children.push(text("line", line));
+        }
+        // Guitars and basses sound an octave below the written notes
+        if octave_change {
+            element.children.push(text("clef-octave-change", -1));
+        }
+        element
+    }
+
+    /// Lines and tuning of the tablature staff, lowest string first.
+    fn staff_details(&self) -> XmlElement {
+        let mut strings = self.track.strings.clone();
+        strings.sort_by_key(|s| std::cmp::Reverse(s.0));
+        let mut details = XmlElement::new("staff-details")
+            .with_attribute("number", 2)
+            .with_child(text("staff-lines", strings.len()));
+        for (line, (_, tuning)) in strings.iter().enumerate() {
+            let (step, alter, octave) = spell(i32::from(*tuning), 0);
+            let mut tuning = XmlElement::new("staff-tuning")
+                .with_attribute("line", line + 1)
+                .with_child(text("tuning-step", step));
+            if alter != 0 {
+                tuning.children.push(text("tuning-alter", alter));
+            }
+            details
+                .children
+                .push(tuning.with_child(text("tuning-octave", octave)));
+        }
+        if self.track.offset > 0 {
+            details.children.push(text("capo", self.track.offset));
+        }
+        details
+    }
+
+    /// Left barline: repeat start and the beginning of an alternate ending.
+    fn left_barline(&self, m_idx: usize, header: &MeasureHeader) -> Option<XmlElement> {
+        let previous = m_idx
+            .checked_sub(1)
+            .map_or(0, |i| self.song.measure_headers[i].repeat_alternative);
+        let ending_start = header.repeat_alternative != 0 && header.repeat_alternative != previous;
+        if !header.repeat_open && !ending_start {
+            return None;
+        }
+        let mut barline = XmlElement::new("barline").with_attribute("location", "left");
+        if header.repeat_open {
+            barline.children.push(text("bar-style", "heavy-light"));
+        }
+        if ending_start {
+            let (numbers, label) = ending_numbers(header.repeat_alternative);
+            barline.children.push(
+                XmlElement::with_text("ending", label)
+                    .with_attribute("number", numbers)
+                    .with_attribute("type", "start"),
+            );
+        }
+        if header.repeat_open {
+            barline
+                .children
+                .push(XmlElement::new("repeat").with_attribute("direction", "forward"));
+        }
+        Some(barline)
+    }
+
+    /// Right barline: repeat end, the end of an alternate ending or a double bar.
+    fn right_barline(&self, m_idx: usize, header: &MeasureHeader) -> Option<XmlElement> {
+        let next = self
+            .song
+            .measure_headers
+            .get(m_idx + 1)
+            .map_or(0, |h| h.repeat_alternative);
+        let ending_stop = header.repeat_alternative != 0
+            && (header.repeat_alternative != next || header.repeat_close > 0);
+        let double_bar = header.double_bar;
+        if header.repeat_close <= 0 && !ending_stop && !double_bar {
+            return None;
+        }
+        let mut barline = XmlElement::new("barline").with_attribute("location", "right");
+        barline.children.push(text(
+            "bar-style",
+            if header.repeat_close > 0 {
+                "light-heavy"
+            } else if double_bar {
+                "light-light"
+            } else {
+                "regular"
+            },
+        ));
+        if ending_stop {
+            let (numbers, _) = ending_numbers(header.repeat_alternative);
+            barline.children.push(
+                XmlElement::new("ending")
+                    .with_attribute("number", numbers)
+                    .with_attribute(
+                        "type",
+                        if header.repeat_close > 0 {
+                            "stop"
+                        } else {
+                            "discontinue"
+                        },
+                    ),
+            );
+        }
+        if header.repeat_close > 0 {
+            barline.children.push(
+                XmlElement::new("repeat")
+                    .with_attribute("direction", "backward")
+                    .with_attribute("times", header.repeat_close.max(1)),
+            );
+        }
+        Some(barline)
+    }
+
+    fn write_measure(&mut self, m_idx: usize) -> XmlElement {
+        let header = &self.song.measure_headers[m_idx];
+        let mut measure = XmlElement::new("measure").with_attribute("number", m_idx + 1);
+        measure.children.extend(self.left_barline(m_idx, header));
+        measure.children.extend(self.write_attributes(m_idx));
+        self.key = header.key_signature.key;
+
+        if self.first_part {
+            if m_idx == 0 || (header.tempo > 0 && header.tempo != self.tempo) {
+                if header.tempo > 0 {
+                    self.tempo = header.tempo;
+                }
+                measure.children.push(tempo_direction(self.tempo));
+            }
+            if let Some(marker) = &header.marker {
+                measure
+                    .children
+                    .push(direction("above", text("rehearsal", &marker.title)));
+            }
+            if let Some(sign) = &header.direction {
+                measure.children.push(direction_sign(sign));
+            }
+        }
+
+        if let Some(track_measure) = self.track.measures.get(m_idx) {
+            let voices: Vec<(usize, &Voice)> = track_measure
+                .voices
+                .iter()
+                .enumerate()
+                .filter(|(_, v)| v.beats.iter().any(|b| b.status != BeatStatus::Empty))
+                .collect();
+            let staves: &[usize] = if self.tab() { &[1, 2] } else { &[1] };
+            let mut previous_length = None;
+            for staff in staves {
+                for (v_idx, voice) in &voices {
+                    if let Some(length) = previous_length {
+                        measure
+                            .children
+                            .push(XmlElement::new("backup").with_child(text("duration", length)));
+                    }
+                    let (elements, length) = self.write_voice(m_idx, *v_idx, voice, *staff);
+                    measure.children.extend(elements);
+                    previous_length = Some(length);
+                }
+            }
+        }
+        // Passages still open at the end of the song
+        if m_idx + 1 == self.song.measure_headers.len() {
+            for v_idx in 0..4 {
+                if std::mem::take(&mut self.palm_mute[v_idx]) {
+                    let stop = passage_direction("P.M.", false, 2 * v_idx + 1);
+                    measure.children.push(stop);
+                }
+                if std::mem::take(&mut self.let_ring[v_idx]) {
+                    let stop = passage_direction("let ring", false, 2 * v_idx + 2);
+                    measure.children.push(stop);
+                }
+            }
+        }
+
+        measure.children.extend(self.right_barline(m_idx, header));
+        measure
+    }
+
+    /// Notes of a voice on a staff, with the total length of the voice.
+    fn write_voice(
+        &mut self,
+        m_idx: usize,
+        v_idx: usize,
+        voice: &Voice,
+        staff: usize,
+    ) -> (Vec<XmlElement>, i64) {
+        let voice_number = v_idx + 1 + if staff == 2 { TAB_VOICE_OFFSET } else { 0 };
+        let brackets = tuplet_brackets(&voice.beats);
+        let mut elements = Vec::new();
+        let mut length = 0;
+        for (b_idx, beat) in voice.beats.iter().enumerate() {
+            if staff == 1 {
+                elements.extend(self.beat_directions(v_idx, beat));
+            }
+            let position = (m_idx, v_idx, b_idx);
+            elements.extend(self.write_beat(position, beat, staff, voice_number, brackets[b_idx]));
+            length += duration_ticks(&beat.duration);
+        }
+        (elements, length)
+    }
+
+    /// Directions attached to a beat: text, tempo changes, palm mute and let ring passages.
+    fn beat_directions(&mut self, v_idx: usize, beat: &Beat) -> Vec<XmlElement> {
+        let mut directions = Vec::new();
+        if !beat.text.is_empty() {
+            directions.push(words_direction("above", &beat.text));
+        }
+        if self.first_part {
+            let tempo = beat
+                .effect
+                .mix_table_change
+                .as_ref()
+                .and_then(|m| m.tempo.as_ref())
+                .map(|t| i32::from(t.value));
+            if let Some(tempo) = tempo.filter(|t| *t > 0 && *t != self.tempo) {
+                self.tempo = tempo;
+                directions.push(tempo_direction(tempo));
+            }
+        }
+        if v_idx < 4 && beat.status == BeatStatus::Normal {
+            let palm_mute = beat.notes.iter().any(|n| n.effect.palm_mute);
+            if palm_mute != self.palm_mute[v_idx] {
+                directions.push(passage_direction("P.M.", palm_mute, 2 * v_idx + 1));
+            }
+            self.palm_mute[v_idx] = palm_mute;
+            let let_ring = beat.notes.iter().any(|n| n.effect.let_ring);
+            if let_ring != self.let_ring[v_idx] {
+                directions.push(passage_direction("let ring", let_ring, 2 * v_idx + 2));
+            }
+            self.let_ring[v_idx] = let_ring;
+        }
+        directions
+    }
+
+    fn write_beat(
+        &self,
+        position: (usize, usize, usize),
+        beat: &Beat,
+        staff: usize,
+        voice_number: usize,
+        bracket: (bool, bool),
+    ) -> Vec<XmlElement> {
+        let notes: Vec<&Note> = beat
+            .notes
+            .iter()
+            .filter(|n| n.kind != NoteType::Rest)
+            .collect();
+        if beat.status != BeatStatus::Normal || notes.is_empty() {
+            let mut rest = XmlElement::new("note");
+            if beat.status == BeatStatus::Empty {
+                rest.attributes
+                    .push(("print-object".to_string(), "no".to_string()));
+            }
+            rest.children.push(XmlElement::new("rest"));
+            rest.children
+                .push(text("duration", duration_ticks(&beat.duration)));
+            self.push_note_details(&mut rest, beat, voice_number);
+            if self.tab() {
+                rest.children.push(text("staff", staff));
+            }
+            if let Some(notations) = tuplet_notations(bracket) {
+                rest.children.push(notations);
+            }
+            return vec![rest];
+        }
+
+        let mut elements = Vec::new();
+        // Grace notes are written before the beat
+        let graces = notes
+            .iter()
+            .filter_map(|n| n.effect.grace.as_ref().map(|g| (n, g)));
+        for (i, (note, grace)) in graces.enumerate() {
+            let mut element = XmlElement::new("note")
+                .with_child(XmlElement::new("grace").with_attribute("slash", "yes"));
+            if i > 0 {
+                element.children.push(XmlElement::new("chord"));
+            }
+            let midi = self.midi_pitch(note) - i32::from(note.value) + i32::from(grace.fret);
+            element
+                .children
+                .push(pitch_element("pitch", "", midi, self.key));
+            element.children.push(text("voice", voice_number));
+            element
+                .children
+                .push(text("type", note_type(u16::from(grace.duration))));
+            if grace.is_dead {
+                element.children.push(text("notehead", "x"));
+            }
+            if self.tab() {
+                element.children.push(text("staff", staff));
+            }
+            if staff == 2 || !self.tab() {
+                element.children.push(
+                    XmlElement::new("notations").with_child(
+                        XmlElement::new("technical")
+                            .with_child(text("string", note.string))
+                            .with_child(text("fret", grace.fret)),
+                    ),
+                );
+            }
+            elements.push(element);
+        }
+        for (i, note) in notes.iter().enumerate() {
+            elements.push(self.write_note(position, beat, note, i, staff, voice_number, bracket));
+        }
+        elements
+    }
+
+    /// Voice, type, dots and tuplet ratio of a note or rest.
+    fn push_note_details(&self, element: &mut XmlElement, beat: &Beat, voice_number: usize) {
+        let duration = &beat.duration;
+        element.children.push(text("voice", voice_number));
+        element
+            .children
+            .push(text("type", note_type(duration.value)));
+        let dots = if duration.double_dotted {
+            2
+        } else {
+            usize::from(duration.dotted)
+        };
+        for _ in 0..dots {
+            element.children.push(XmlElement::new("dot"));
+        }
+        if !duration.is_default_tuplet() {
+            element.children.push(
+                XmlElement::new("time-modification")
+                    .with_child(text("actual-notes", duration.tuplet_enters))
+                    .with_child(text("normal-notes", duration.tuplet_times)),
+            );
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn write_note(
+        &self,
+        position: (usize, usize, usize),
+        beat: &Beat,
+        note: &Note,
+        index: usize,
+        staff: usize,
+        voice_number: usize,
+        bracket: (bool, bool),
+    ) -> XmlElement {
+        let effect = &note.effect;
+        let midi = self.midi_pitch(note);
+        let next = self.neighbour_note(position, note.string, true);
+        let previous = self.neighbour_note(position, note.string, false);
+        let tie_start = next.is_some_and(|n| n.kind == NoteType::Tie);
+        let tie_stop = note.kind == NoteType::Tie;
+
+        let mut element = XmlElement::new("note");
+        if index > 0 {
+            element.children.push(XmlElement::new("chord"));
+        }
+        if self.track.percussion_track {
+            element
+                .children
+                .push(pitch_element("unpitched", "display-", midi, 0));
+        } else {
+            element
+                .children
+                .push(pitch_element("pitch", "", midi, self.key));
+        }
+        element
+            .children
+            .push(text("duration", duration_ticks(&beat.duration)));
+        if tie_stop {
+            element
+                .children
+                .push(XmlElement::new("tie").with_attribute("type", "stop"));
+        }
+        if tie_start {
+            element
+                .children
+                .push(XmlElement::new("tie").with_attribute("type", "start"));
+        }
+        self.push_note_details(&mut element, beat, voice_number);
+        if note.kind == NoteType::Dead {
+            element.children.push(text("notehead", "x"));
+        } else if effect.ghost_note {
+            element.children.push(
+                XmlElement::with_text("notehead", "normal").with_attribute("parentheses", "yes"),
+            );
+        }
+        if self.tab() {
+            element.children.push(text("staff", staff));
+        }
+
+        let mut notations = XmlElement::new("notations");
+        if tie_stop {
+            notations
+                .children
+                .push(XmlElement::new("tied").with_attribute("type", "stop"));
+        }
+        if tie_start {
+            notations
+                .children
+                .push(XmlElement::new("tied").with_attribute("type", "start"));
+        }
+        if index == 0 {
+            if let Some(tuplet) = tuplet_notations(bracket) {
+                notations.children.extend(tuplet.children);
+            }
+        }
+
+        // Hammer-ons and pull-offs are slurred to the next note on the same string
+        let mut technical = XmlElement::new("technical");
+        let number = note.string.max(1);
+        if let Some(previous) = previous.filter(|p| p.effect.hammer) {
+            let name = if previous.value <= note.value {
+                "hammer-on"
+            } else {
+                "pull-off"
+            };
+            notations.children.push(
+                XmlElement::new("slur")
+                    .with_attribute("type", "stop")
+                    .with_attribute("number", number),
+            );
+            technical.children.push(
+                XmlElement::new(name)
+                    .with_attribute("type", "stop")
+                    .with_attribute("number", number),
+            );
+        }
+        if let Some(next) = next.filter(|_| effect.hammer) {
+            let (name, label) = if next.value >= note.value {
+                ("hammer-on", "H")
+            } else {
+                ("pull-off", "P")
+            };
+            notations.children.push(
+                XmlElement::new("slur")
+                    .with_attribute("type", "start")
+                    .with_attribute("number", number),
+            );
+            technical.children.push(
+                XmlElement::with_text(name, label)
+                    .with_attribute("type", "start")
+                    .with_attribute("number", number),
+            );
+        }
+
+        // Slides between two notes, or into and out of a note
+        let slide_to = |n: &Note| {
+            n.effect
+                .slides
+                .iter()
+                .any(|s| matches!(s, SlideType::ShiftSlideTo | SlideType::LegatoSlideTo))
+        };
+        if previous.is_some_and(slide_to) {
+            notations.children.push(
+                XmlElement::new("slide")
+                    .with_attribute("type", "stop")
+                    .with_attribute("number", number),
+            );
+        }
+        if next.is_some() && slide_to(note) {
+            notations.children.push(
+                XmlElement::new("slide")
+                    .with_attribute("type", "start")
+                    .with_attribute("line-type", "solid")
+                    .with_attribute("number", number),
+            );
+        }
+
+        let mut ornaments = XmlElement::new("ornaments");
+        if effect.trill.is_some() {
+            ornaments.children.push(XmlElement::new("trill-mark"));
+        }
+        if let Some(tremolo) = &effect.tremolo_picking {
+            let marks = match tremolo.duration.value {
+                32 => 3,
+                16 => 2,
+                _ => 1,
+            };
+            ornaments
+                .children
+                .push(XmlElement::with_text("tremolo", marks).with_attribute("type", "single"));
+        }
+        if !ornaments.children.is_empty() {
+            notations.children.push(ornaments);
+        }
+
+        technical.children.push(text("string", note.string));
+        technical.children.push(text("fret", note.value));
+        if let Some(bend) = &effect.bend {
+            technical.children.extend(bend_elements(bend));
+        }
+        if let Some(harmonic) = &effect.harmonic {
+            let kind = match harmonic.kind {
+                HarmonicType::Natural => "natural",
+                _ => "artificial",
+            };
+            technical
+                .children
+                .push(XmlElement::new("harmonic").with_child(XmlElement::new(kind)));
+        }
+        if let Some(finger) = left_hand_finger(&effect.left_hand_finger) {
+            technical.children.push(text("fingering", finger));
+        }
+        if let Some(finger) = right_hand_finger(&effect.right_hand_finger) {
+            technical.children.push(text("pluck", finger));
+        }
+        if index == 0 {
+            match beat.effect.pick_stroke {
+                BeatStrokeDirection::Down => technical.children.push(XmlElement::new("down-bow")),
+                BeatStrokeDirection::Up => technical.children.push(XmlElement::new("up-bow")),
+                BeatStrokeDirection::None => {}
+            }
+        }
+        if beat.effect.slap_effect == SlapEffect::Tapping {
+            technical.children.push(XmlElement::new("tap"));
+        }
+        notations.children.push(technical);
+
+        let mut articulations = XmlElement::new("articulations");
+        if effect.accentuated_note {
+            articulations.children.push(XmlElement::new("accent"));
+        }
+        if effect.heavy_accentuated_note {
+            articulations
+                .children
+                .push(XmlElement::new("strong-accent"));
+        }
+        if effect.staccato {
+            articulations.children.push(XmlElement::new("staccato"));
+        }
+        for slide in &effect.slides {
+            let name = match slide {
+                SlideType::IntoFromBelow => "scoop",
+                SlideType::IntoFromAbove => "plop",
+                SlideType::OutDownwards => "falloff",
+                SlideType::OutUpWards => "doit",
+                _ => continue,
+            };
+            articulations.children.push(XmlElement::new(name));
+        }
+        if !articulations.children.is_empty() {
+            notations.children.push(articulations);
+        }
+        match beat.effect.stroke.direction {
+            BeatStrokeDirection::Down => notations
+                .children
+                .push(XmlElement::new("arpeggiate").with_attribute("direction", "down")),
+            BeatStrokeDirection::Up => notations
+                .children
+                .push(XmlElement::new("arpeggiate").with_attribute("direction", "up")),
+            BeatStrokeDirection::None => {}
+        }
+        element.children.push(notations);
+
+        if index == 0 && staff == 1 {
+            let (m_idx, v_idx, b_idx) = position;
+            if v_idx == 0 {
+                for (line, syllable, syllabic) in
+                    self.lyrics.get(&(m_idx, b_idx)).into_iter().flatten()
+                {
+                    element.children.push(
+                        XmlElement::new("lyric")
+                            .with_attribute("number", line)
+                            .with_child(text("syllabic", syllabic))
+                            .with_child(text("text", syllable)),
+                    );
+                }
+            }
+        }
+        element
+    }
+}
+
+fn tuplet_notations(bracket: (bool, bool)) -> Option<XmlElement> {
+    let mut notations = XmlElement::new("notations");
+    match bracket {
+        // A single beat group has no bracket
+        (true, true) | (false, false) => return None,
+        (true, false) => notations
+            .children
+            .push(XmlElement::new("tuplet").with_attribute("type", "start")),
+        (false, true) => notations
+            .children
+            .push(XmlElement::new("tuplet").with_attribute("type", "stop")),
+    }
+    Some(notations)
+}
+
+/// Ending numbers of an alternative bitmask, as the `number` attribute and the displayed label.
+pub(crate) fn ending_numbers(alternative: u8) -> (String, String) {
+    let numbers: Vec<String> = (0..8)
+        .filter(|i| alternative & (1 << i) != 0)
+        .map(|i| (i + 1).to_string())
+        .collect();
+    let label = numbers
+        .iter()
+        .map(|n| format!("{}.", n))
+        .collect::<Vec<_>>()
+        .join(" ");
+    (numbers.join(","), label)
+}
+
+fn left_hand_finger(finger: &Fingering) -> Option<&'static str> {
+    match finger {
+        Fingering::Thumb => Some("T"),
+        Fingering::Index => Some("1"),
+        Fingering::Middle => Some("2"),
+        Fingering::Annular => Some("3"),
+        Fingering::Little => Some("4"),
+        _ => None,
+    }
+}
+
+fn right_hand_finger(finger: &Fingering) -> Option<&'static str> {
+    match finger {
+        Fingering::Thumb => Some("p"),
+        Fingering::Index => Some("i"),
+        Fingering::Middle => Some("m"),
+        Fingering::Annular => Some("a"),
+        Fingering::Little => Some("c"),
+        _ => None,
+    }
+}
+
+impl Song {
+    fn musicxml_identification(&self) -> XmlElement {
+        let mut identification = XmlElement::new("identification");
+        let transcriber = if self.transcriber.is_empty() {
+            &self.writer
+        } else {
+            &self.transcriber
+        };
+        let creators = [
+            ("composer", &self.author),
+            ("lyricist", &self.words),
+            ("artist", &self.artist),
+            ("transcriber", transcriber),
+        ];
+        for (kind, name) in creators {
+            if !name.is_empty() {
+                identification
+                    .children
+                    .push(XmlElement::with_text("creator", name).with_attribute("type", kind));
+            }
+        }
+        if !self.copyright.is_empty() {
+            identification
+                .children
+                .push(text("rights", &self.copyright));
+        }
+        identification
+            .children
+            .push(XmlElement::new("encoding").with_child(text("software", "scorelib")));
+        let mut miscellaneous = XmlElement::new("miscellaneous");
+        let notices = self.notice.join("\n");
+        let fields = [
+            ("album", &self.album),
+            ("instructions", &self.instructions),
+            ("notice", &notices),
+        ];
+        for (name, value) in fields {
+            if !value.is_empty() {
+                miscellaneous.children.push(
+                    XmlElement::with_text("miscellaneous-field", value)
+                        .with_attribute("name", name),
+                );
+            }
+        }
+        if !miscellaneous.children.is_empty() {
+            identification.children.push(miscellaneous);
+        }
+        identification
+    }
+
+    fn musicxml_score_part(&self, t_idx: usize, id: &str) -> XmlElement {
+        let track = &self.tracks[t_idx];
+        let channel = self.channels.get(track.channel_index);
+        let program = track
+            .midi_program_gpif
+            .or_else(|| channel.map(|c| c.instrument))
+            .unwrap_or(25)
+            .clamp(0, 127);
+        let midi_channel = channel.map_or(track.channel_index as i32, |c| c.channel as i32);
+        let instrument_id = format!("{}-I1", id);
+        let mut score_part = XmlElement::new("score-part")
+            .with_attribute("id", id)
+            .with_child(text("part-name", &track.name));
+        if !track.short_name.is_empty() {
+            score_part
+                .children
+                .push(text("part-abbreviation", &track.short_name));
+        }
+        score_part.children.push(
+            XmlElement::new("score-instrument")
+                .with_attribute("id", &instrument_id)
+                .with_child(text("instrument-name", &track.name)),
+        );
+        let mut midi = XmlElement::new("midi-instrument")
+            .with_attribute("id", &instrument_id)
+            .with_child(text("midi-channel", midi_channel.rem_euclid(16) + 1));
+        if !track.percussion_track {
+            midi.children.push(text("midi-program", program + 1));
+        }
+        if let Some(channel) = channel {
+            midi.children.push(text(
+                "volume",
+                (f32::from(channel.volume.max(0)) * 100.0 / 127.0).round(),
+            ));
+            midi.children.push(text(
+                "pan",
+                ((f32::from(channel.balance) - 64.0) * 90.0 / 64.0).round(),
+            ));
+        }
+        score_part.with_child(midi)
+    }
+}
+
+impl SongMusicXmlExportOps for Song {
+    fn write_musicxml(&self) -> GpResult<String> {
+        self.check_note_strings()?;
+        let mut root = XmlElement::new("score-partwise").with_attribute("version", "4.0");
+        root.children
+            .push(XmlElement::new("work").with_child(text("work-title", &self.name)));
+        if !self.subtitle.is_empty() {
+            root.children.push(text("movement-title", &self.subtitle));
+        }
+        root.children.push(self.musicxml_identification());
+
+        let ids: Vec<String> = (1..=self.tracks.len()).map(|i| format!("P{}", i)).collect();
+        let mut part_list = XmlElement::new("part-list");
+        for (t_idx, id) in ids.iter().enumerate() {
+            part_list.children.push(self.musicxml_score_part(t_idx, id));
+        }
+        root.children.push(part_list);
+        for (t_idx, id) in ids.iter().enumerate() {
+            root.children
+                .push(PartWriter::new(self, t_idx).write_part(id));
+        }
+
+        let mut xml = String::from(concat!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"no\"?>\n",
+            "<!DOCTYPE score-partwise PUBLIC \"-//Recordare//DTD MusicXML 4.0 Partwise//EN\" ",
+            "\"http://www.musicxml.org/dtds/partwise.dtd\">\n"
+        ));
+        root.write(&mut xml, 0);
+        Ok(xml)
+    }
+}
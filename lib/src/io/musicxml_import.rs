@@ -0,0 +1,1292 @@
+//! MusicXML import (partwise and timewise documents, plain `.musicxml` or compressed `.mxl`).
+//!
+//! Every part becomes a track. When a part has a tablature staff next to a notation staff, only the
+//! tablature one is read: both hold the same notes and the tablature knows the strings. Notes without
+//! `<technical><string>/<fret>` get a string and a fret computed from their pitch and the track tuning.
+use std::collections::{HashMap, VecDeque};
+use std::io::Read;
+
+use crate::audio::midi::{MidiChannel, DEFAULT_PERCUSSION_CHANNEL};
+use crate::error::{GpError, GpResult};
+use crate::io::notation::{bend_from_values, duration_from_ticks};
+use crate::io::xml::XmlElement;
+use crate::model::{
+    beat::{Beat, Voice},
+    conversion::{LossPosition, LostItem},
+    effects::*,
+    enums::*,
+    headers::{Marker, MeasureHeader},
+    key_signature::*,
+    lyric::Lyrics,
+    measure::Measure,
+    note::Note,
+    song::Song,
+    track::Track,
+};
+
+/// Voices kept per measure, as in Guitar Pro files.
+const MAX_VOICES: usize = 2;
+/// Standard guitar tuning, highest string first, for parts without tablature tuning.
+const DEFAULT_TUNING: [i8; 6] = [64, 59, 55, 50, 45, 40];
+const DEFAULT_FRET_COUNT: u8 = 24;
+
+pub trait SongMusicXmlOps {
+    /// Read a MusicXML document, `score-partwise` or `score-timewise`.
+    fn read_musicxml_document(&mut self, xml: &str) -> GpResult<()>;
+}
+
+/// Decode a MusicXML file: UTF-8 unless a byte order mark says otherwise.
+pub(crate) fn decode_musicxml(data: &[u8]) -> GpResult<String> {
+    let (encoding, bom_length) =
+        encoding_rs::Encoding::for_bom(data).unwrap_or((encoding_rs::UTF_8, 0));
+    let (text, _, had_errors) = encoding.decode(&data[bom_length..]);
+    if had_errors {
+        return Err(GpError::FormatError(format!(
+            "MusicXML document is not valid {}",
+            encoding.name()
+        )));
+    }
+    Ok(text.into_owned())
+}
+
+/// Extract the score of a compressed MusicXML archive (`.mxl`). The score is the first root file
+/// listed in `META-INF/container.xml`, or else the first MusicXML file of the archive.
+pub(crate) fn read_mxl(data: &[u8]) -> GpResult<String> {
+    let mut zip = zip::ZipArchive::new(std::io::Cursor::new(data))
+        .map_err(|e| GpError::FormatError(format!("MXL archive error: {}", e)))?;
+    let mut read_entry = |name: &str| -> GpResult<Vec<u8>> {
+        let mut file = zip
+            .by_name(name)
+            .map_err(|e| GpError::FormatError(format!("MXL archive error ({}): {}", name, e)))?;
+        let mut content = Vec::with_capacity(file.size() as usize);
+        file.read_to_end(&mut content)?;
+        Ok(content)
+    };
+    let root_file = match read_entry("META-INF/container.xml") {
+        Ok(container) => {
+            let container = XmlElement::parse(&decode_musicxml(&container)?)?;
+            container
+                .child("rootfiles")
+                .and_then(|r| r.child("rootfile"))
+                .and_then(|r| r.attribute("full-path"))
+                .map(str::to_string)
+        }
+        Err(_) => None,
+    };
+    let root_file = match root_file {
+        Some(root_file) => root_file,
+        None => zip
+            .file_names()
+            .filter(|n| !n.starts_with("META-INF/"))
+            .find(|n| n.ends_with(".musicxml") || n.ends_with(".xml"))
+            .map(str::to_string)
+            .ok_or_else(|| GpError::FormatError("MXL archive without a score".into()))?,
+    };
+    let mut file = zip
+        .by_name(&root_file)
+        .map_err(|e| GpError::FormatError(format!("MXL archive error ({}): {}", root_file, e)))?;
+    let mut content = Vec::with_capacity(file.size() as usize);
+    file.read_to_end(&mut content)?;
+    decode_musicxml(&content)
+}
+
+// ---------------------------------------------------------------------------
+// Helper functions
+// ---------------------------------------------------------------------------
+
+pub(crate) fn child_text<'a>(element: &'a XmlElement, name: &str) -> Option<&'a str> {
+    element.child(name).map(|c| c.text().trim())
+}
+
+pub(crate) fn child_number<T: std::str::FromStr>(element: &XmlElement, name: &str) -> Option<T> {
+    child_text(element, name)?.parse().ok()
+}
+
+pub(crate) fn children<'a>(
+    element: &'a XmlElement,
+    name: &'a str,
+) -> impl Iterator<Item = &'a XmlElement> {
+    element.children.iter().filter(move |c| c.name == name)
+}
+
+/// Convert a MusicXML note type to Duration.value.
+pub(crate) fn note_type_value(s: &str) -> Option<u16> {
+    match s {
+        "breve" | "long" | "maxima" | "whole" => Some(1),
+        "half" => Some(2),
+        "quarter" => Some(4),
+        "eighth" => Some(8),
+        "16th" => Some(16),
+        "32nd" => Some(32),
+        "64th" => Some(64),
+        "128th" | "256th" | "512th" | "1024th" => Some(128),
+        _ => None,
+    }
+}
+
+/// MIDI pitch of a `pitch` (or `unpitched` with `prefix` = "display-") element.
+fn parse_pitch(element: &XmlElement, prefix: &str) -> Option<i32> {
+    let step = match child_text(element, &format!("{}step", prefix))? {
+        "C" => 0,
+        "D" => 2,
+        "E" => 4,
+        "F" => 5,
+        "G" => 7,
+        "A" => 9,
+        "B" => 11,
+        _ => return None,
+    };
+    let alter = child_text(element, &format!("{}alter", prefix))
+        .and_then(|a| a.parse::<f32>().ok())
+        .map_or(0, |a| a.round() as i32);
+    let octave: i32 = child_number(element, &format!("{}octave", prefix))?;
+    Some((octave + 1) * 12 + step + alter)
+}
+
+pub(crate) fn dynamic_to_velocity(s: &str) -> Option<i16> {
+    let level = match s {
+        "pppppp" | "ppppp" | "pppp" | "ppp" => 0,
+        "pp" => 1,
+        "p" => 2,
+        "mp" => 3,
+        "mf" => 4,
+        "f" | "sf" | "sfz" | "fz" => 5,
+        "ff" => 6,
+        "fff" | "ffff" | "fffff" | "ffffff" => 7,
+        _ => return None,
+    };
+    Some(MIN_VELOCITY + VELOCITY_INCREMENT * level)
+}
+
+/// Navigation sign written as text, e.g. "D.S. al Coda".
+pub(crate) fn parse_direction_words(words: &str) -> Option<DirectionSign> {
+    let words = words.trim().to_lowercase().replace(' ', "");
+    let sign = match words.as_str() {
+        "fine" => DirectionSign::Fine,
+        "d.c." | "dacapo" => DirectionSign::DaCapo,
+        "d.c.alcoda" | "dacapoalcoda" => DirectionSign::DaCapoAlCoda,
+        "d.c.aldoublecoda" => DirectionSign::DaCapoAlDoubleCoda,
+        "d.c.alfine" | "dacapoalfine" => DirectionSign::DaCapoAlFine,
+        "d.s." | "dalsegno" => DirectionSign::DaSegno,
+        "d.s.alcoda" | "dalsegnoalcoda" => DirectionSign::DaSegnoAlCoda,
+        "d.s.aldoublecoda" => DirectionSign::DaSegnoAlDoubleCoda,
+        "d.s.alfine" | "dalsegnoalfine" => DirectionSign::DaSegnoAlFine,
+        "d.s.s." => DirectionSign::DaSegnoSegno,
+        "d.s.s.alcoda" => DirectionSign::DaSegnoSegnoAlCoda,
+        "d.s.s.aldoublecoda" => DirectionSign::DaSegnoSegnoAlDoubleCoda,
+        "d.s.s.alfine" => DirectionSign::DaSegnoSegnoAlFine,
+        "tocoda" => DirectionSign::DaCoda,
+        "todoublecoda" => DirectionSign::DaDoubleCoda,
+        _ => return None,
+    };
+    Some(sign)
+}
+
+/// Bitmask of the alternate endings listed in an `ending` number, e.g. "1, 2".
+pub(crate) fn parse_ending_numbers(numbers: &str) -> u8 {
+    numbers
+        .split([',', ' '])
+        .filter_map(|n| n.trim().parse::<u8>().ok())
+        .filter(|n| (1..=8).contains(n))
+        .fold(0, |mask, n| mask | (1 << (n - 1)))
+}
+
+/// Bend points from MusicXML `bend` elements: a pre-bend sets the first value, every other bend
+/// moves the value by its `bend-alter` (in semitones, the points are in quarter tones).
+fn parse_bend(bends: &[&XmlElement]) -> Option<BendEffect> {
+    let mut values: Vec<i8> = vec![0];
+    for bend in bends {
+        let alter = child_text(bend, "bend-alter")
+            .and_then(|a| a.parse::<f32>().ok())
+            .unwrap_or(0.0);
+        let quarter_tones = (alter * 2.0).round() as i8;
+        if bend.child("pre-bend").is_some() && values.len() == 1 {
+            values[0] = quarter_tones;
+        } else {
+            let last = *values.last().unwrap();
+            values.push(last.saturating_add(quarter_tones).max(0));
+        }
+    }
+    bend_from_values(values)
+}
+
+fn parse_left_hand_finger(s: &str) -> Option<Fingering> {
+    match s {
+        "0" | "T" | "t" => Some(Fingering::Thumb),
+        "1" => Some(Fingering::Index),
+        "2" => Some(Fingering::Middle),
+        "3" => Some(Fingering::Annular),
+        "4" => Some(Fingering::Little),
+        _ => None,
+    }
+}
+
+fn parse_right_hand_finger(s: &str) -> Option<Fingering> {
+    match s {
+        "p" => Some(Fingering::Thumb),
+        "i" => Some(Fingering::Index),
+        "m" => Some(Fingering::Middle),
+        "a" => Some(Fingering::Annular),
+        "c" | "e" | "x" => Some(Fingering::Little),
+        _ => None,
+    }
+}
+
+/// Rewrite a timewise score (measures holding parts) as a partwise one (parts holding measures).
+fn timewise_to_partwise(score: &XmlElement) -> XmlElement {
+    let mut partwise = XmlElement::new("score-partwise");
+    partwise.attributes = score.attributes.clone();
+    let mut parts: Vec<XmlElement> = Vec::new();
+    for child in &score.children {
+        if child.name != "measure" {
+            partwise.children.push(child.clone());
+            continue;
+        }
+        for part in children(child, "part") {
+            let id = part.attribute("id").unwrap_or_default();
+            let index = match parts.iter().position(|p| p.attribute("id") == Some(id)) {
+                Some(index) => index,
+                None => {
+                    parts.push(XmlElement::new("part").with_attribute("id", id));
+                    parts.len() - 1
+                }
+            };
+            parts[index].children.push(XmlElement {
+                name: "measure".to_string(),
+                attributes: child.attributes.clone(),
+                text: None,
+                children: part.children.clone(),
+            });
+        }
+    }
+    partwise.children.extend(parts);
+    partwise
+}
+
+// ---------------------------------------------------------------------------
+// Part reader
+// ---------------------------------------------------------------------------
+
+/// Score part information from the part list.
+#[derive(Debug, Default)]
+struct PartInfo {
+    name: String,
+    abbreviation: String,
+    channel: Option<u8>,
+    program: Option<i32>,
+    volume: Option<f32>,
+    pan: Option<f32>,
+    /// MIDI note of the percussion instruments, by instrument id
+    unpitched: HashMap<String, i32>,
+}
+
+impl PartInfo {
+    fn new(score_part: &XmlElement) -> Self {
+        let mut info = PartInfo {
+            name: child_text(score_part, "part-name")
+                .unwrap_or_default()
+                .to_string(),
+            abbreviation: child_text(score_part, "part-abbreviation")
+                .unwrap_or_default()
+                .to_string(),
+            ..Default::default()
+        };
+        for midi in children(score_part, "midi-instrument") {
+            if let Some(note) = child_number::<i32>(midi, "midi-unpitched") {
+                let id = midi.attribute("id").unwrap_or_default().to_string();
+                info.unpitched.insert(id, note - 1);
+            }
+            info.channel = info.channel.or(child_number::<u8>(midi, "midi-channel")
+                .filter(|c| (1..=16).contains(c))
+                .map(|c| c - 1));
+            info.program = info.program.or(child_number::<i32>(midi, "midi-program")
+                .filter(|p| (1..=128).contains(p))
+                .map(|p| p - 1));
+            info.volume = info.volume.or(child_number(midi, "volume"));
+            info.pan = info.pan.or(child_number(midi, "pan"));
+        }
+        info
+    }
+}
+
+/// A lyric syllable of a measure: lyric number, text and syllabic.
+pub(crate) type Syllable = (String, String, String);
+
+/// A grace note waiting for the note it leads to.
+struct PendingGrace {
+    effect: GraceEffect,
+    fret: Option<i8>,
+    pitch: Option<i32>,
+}
+
+/// State of a measure being read.
+#[derive(Default)]
+struct MeasureState {
+    /// Voice ids in order of appearance, with their voice and the end of their last beat
+    voices: Vec<(String, Voice, i64)>,
+    position: i64,
+    /// Grace notes waiting for the next beat, then the ones of the current beat
+    graces: VecDeque<PendingGrace>,
+    beat_graces: VecDeque<PendingGrace>,
+    /// Palm mute and let ring of the current beat
+    palm_mute: bool,
+    let_ring: bool,
+}
+
+struct PartReader<'a> {
+    info: &'a PartInfo,
+    track_index: usize,
+    /// Staff holding the tablature, read alone when the part has other staves
+    tab_staff: Option<String>,
+    strings: Vec<(i8, i8)>,
+    capo: i32,
+    percussion: bool,
+    divisions: i64,
+    /// Sounding pitch minus written pitch
+    transpose: i32,
+    clef: MeasureClef,
+    velocity: i16,
+    /// Palm mute and let ring passages, with the `dashes` number ending them (`None`: a single beat)
+    palm_mute: Option<Option<String>>,
+    let_ring: Option<Option<String>>,
+    /// Text waiting for the next beat
+    text: Option<String>,
+    /// Syllables of every measure
+    syllables: Vec<Vec<Syllable>>,
+    /// Notes and voices the model cannot hold, for `Song::read_losses`
+    losses: Vec<LostItem>,
+}
+
+impl<'a> PartReader<'a> {
+    fn new(info: &'a PartInfo, part: &XmlElement, track_index: usize) -> Self {
+        let mut reader = PartReader {
+            info,
+            track_index,
+            tab_staff: None,
+            strings: Vec::new(),
+            capo: 0,
+            percussion: info.channel == Some(DEFAULT_PERCUSSION_CHANNEL),
+            divisions: 1,
+            transpose: 0,
+            clef: MeasureClef::Treble,
+            velocity: FORTE,
+            palm_mute: None,
+            let_ring: None,
+            text: None,
+            syllables: Vec::new(),
+            losses: Vec::new(),
+        };
+        // The staves, the tuning and the capo are looked up in the attributes of the whole part
+        let mut staves = 1;
+        let mut tunings: Vec<(String, Vec<i8>)> = Vec::new();
+        for attributes in children(part, "measure").flat_map(|m| children(m, "attributes")) {
+            staves = child_number(attributes, "staves").unwrap_or(staves);
+            for clef in children(attributes, "clef") {
+                let number = clef.attribute("number").unwrap_or("1");
+                match child_text(clef, "sign") {
+                    Some("TAB") if reader.tab_staff.is_none() => {
+                        reader.tab_staff = Some(number.to_string())
+                    }
+                    Some("percussion") => reader.percussion = true,
+                    _ => {}
+                }
+            }
+            for details in children(attributes, "staff-details") {
+                let number = details.attribute("number").unwrap_or("1").to_string();
+                let mut lines: Vec<(u8, i8)> = children(details, "staff-tuning")
+                    .filter_map(|t| {
+                        let line = t.attribute("line")?.parse().ok()?;
+                        Some((line, parse_pitch(t, "tuning-")? as i8))
+                    })
+                    .collect();
+                // Line 1 is the lowest string, the model starts with the highest one
+                lines.sort_by_key(|l| std::cmp::Reverse(l.0));
+                if !lines.is_empty() {
+                    tunings.push((number, lines.into_iter().map(|l| l.1).collect()));
+                }
+                reader.capo = child_number(details, "capo").unwrap_or(reader.capo);
+            }
+        }
+        let has_notes = |staff: &str| {
+            children(part, "measure")
+                .flat_map(|m| children(m, "note"))
+                .any(|n| child_text(n, "staff").unwrap_or("1") != staff)
+        };
+        // A lone tablature staff: every note is read
+        if staves < 2 || reader.tab_staff.as_deref().is_some_and(|s| !has_notes(s)) {
+            reader.tab_staff = None;
+        }
+        let tuning = tunings
+            .iter()
+            .find(|(number, _)| Some(number) == reader.tab_staff.as_ref())
+            .or(tunings.first())
+            .map(|(_, tuning)| tuning.as_slice())
+            .unwrap_or(&DEFAULT_TUNING);
+        reader.strings = if reader.percussion {
+            (1..=6).map(|s| (s, 0)).collect()
+        } else {
+            tuning
+                .iter()
+                .enumerate()
+                .map(|(i, pitch)| ((i + 1) as i8, *pitch))
+                .collect()
+        };
+        reader
+    }
+
+    /// Staff of the notation clef: the first one that is not the tablature.
+    fn notation_staff(&self) -> &str {
+        match self.tab_staff.as_deref() {
+            Some("1") => "2",
+            _ => "1",
+        }
+    }
+
+    fn reads_staff(&self, element: &XmlElement) -> bool {
+        match &self.tab_staff {
+            Some(tab_staff) => child_text(element, "staff").unwrap_or("1") == tab_staff,
+            None => true,
+        }
+    }
+
+    /// Length of an element with a `duration`, in ticks.
+    fn ticks(&self, element: &XmlElement) -> i64 {
+        let duration: f64 = child_number(element, "duration").unwrap_or(0.0);
+        (duration * DURATION_QUARTER_TIME as f64 / self.divisions as f64).round() as i64
+    }
+
+    fn read_track(&mut self, part: &XmlElement, headers: &[MeasureHeader]) -> Track {
+        let name = match (&self.info.name, &self.info.abbreviation) {
+            (name, _) if !name.is_empty() => name.clone(),
+            (_, abbreviation) if !abbreviation.is_empty() => abbreviation.clone(),
+            _ => format!("Track {}", self.track_index + 1),
+        };
+        let mut track = Track {
+            number: (self.track_index + 1) as i32,
+            name,
+            short_name: self.info.abbreviation.clone(),
+            strings: self.strings.clone(),
+            offset: self.capo,
+            percussion_track: self.percussion,
+            fret_count: DEFAULT_FRET_COUNT,
+            ..Default::default()
+        };
+        let measures: Vec<&XmlElement> = children(part, "measure").collect();
+        for (m_idx, header) in headers.iter().enumerate() {
+            let measure = match measures.get(m_idx) {
+                Some(element) => self.read_measure(element, m_idx, header),
+                None => self.read_measure(&XmlElement::new("measure"), m_idx, header),
+            };
+            track.measures.push(measure);
+        }
+        track
+    }
+
+    fn read_measure(
+        &mut self,
+        element: &XmlElement,
+        m_idx: usize,
+        header: &MeasureHeader,
+    ) -> Measure {
+        let mut state = MeasureState::default();
+        let mut clef = None;
+        for child in &element.children {
+            match child.name.as_str() {
+                "attributes" => {
+                    self.read_attributes(child);
+                    clef.get_or_insert(self.clef.clone());
+                }
+                "direction" => self.read_direction(child),
+                "note" => self.read_note(child, m_idx, &mut state),
+                "backup" => state.position -= self.ticks(child),
+                "forward" => state.position += self.ticks(child),
+                _ => {}
+            }
+        }
+
+        let mut voices: Vec<Voice> = state.voices.into_iter().map(|v| v.1).collect();
+        voices.resize_with(MAX_VOICES, Voice::default);
+        for (v_idx, voice) in voices.iter_mut().enumerate() {
+            voice.measure_index = m_idx as i16;
+            if voice.beats.is_empty() {
+                // An empty first voice holds a rest for the whole measure
+                voice.beats.push(if v_idx == 0 {
+                    Beat {
+                        status: BeatStatus::Rest,
+                        duration: duration_from_ticks(header.length()),
+                        ..Default::default()
+                    }
+                } else {
+                    Beat {
+                        status: BeatStatus::Empty,
+                        ..Default::default()
+                    }
+                });
+            }
+        }
+        Measure {
+            number: m_idx + 1,
+            track_index: self.track_index,
+            header_index: m_idx,
+            key_signature: header.key_signature.clone(),
+            time_signature: header.time_signature.clone(),
+            clef: clef.unwrap_or(self.clef.clone()),
+            voices,
+            ..Default::default()
+        }
+    }
+
+    fn read_attributes(&mut self, attributes: &XmlElement) {
+        if let Some(divisions) = child_number::<i64>(attributes, "divisions").filter(|d| *d > 0) {
+            self.divisions = divisions;
+        }
+        if let Some(transpose) = attributes.child("transpose") {
+            self.transpose = child_number::<i32>(transpose, "chromatic").unwrap_or(0)
+                + 12 * child_number::<i32>(transpose, "octave-change").unwrap_or(0);
+        }
+        let notation_staff = self.notation_staff().to_string();
+        for clef in children(attributes, "clef") {
+            if clef.attribute("number").unwrap_or("1") != notation_staff {
+                continue;
+            }
+            self.clef = match (child_text(clef, "sign"), child_text(clef, "line")) {
+                (Some("F"), _) => MeasureClef::Bass,
+                (Some("C"), Some("4")) => MeasureClef::Tenor,
+                (Some("C"), _) => MeasureClef::Alto,
+                (Some("G"), _) => MeasureClef::Treble,
+                _ => continue,
+            };
+        }
+    }
+
+    fn read_direction(&mut self, direction: &XmlElement) {
+        let types: Vec<&XmlElement> = children(direction, "direction-type")
+            .flat_map(|t| t.children.iter())
+            .collect();
+        let dashes_start = types
+            .iter()
+            .find(|t| t.name == "dashes" && t.attribute("type") == Some("start"))
+            .map(|t| t.attribute("number").unwrap_or("1").to_string());
+        for item in &types {
+            match item.name.as_str() {
+                "words" => {
+                    let words = item.text().trim();
+                    match words.to_lowercase().as_str() {
+                        "p.m." | "pm" | "p.m" | "palm mute" => {
+                            self.palm_mute = Some(dashes_start.clone())
+                        }
+                        "let ring" | "let ring." => self.let_ring = Some(dashes_start.clone()),
+                        _ if words.is_empty() || parse_direction_words(words).is_some() => {}
+                        _ => self.text = Some(words.to_string()),
+                    }
+                }
+                "dashes" if item.attribute("type") == Some("stop") => {
+                    let number = Some(item.attribute("number").unwrap_or("1").to_string());
+                    if self.palm_mute.as_ref() == Some(&number) {
+                        self.palm_mute = None;
+                    }
+                    if self.let_ring.as_ref() == Some(&number) {
+                        self.let_ring = None;
+                    }
+                }
+                "dynamics" => {
+                    if let Some(velocity) = item
+                        .children
+                        .iter()
+                        .find_map(|d| dynamic_to_velocity(&d.name))
+                    {
+                        self.velocity = velocity;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Sounding MIDI pitch of a note.
+    fn note_pitch(&self, note: &XmlElement) -> Option<i32> {
+        if let Some(pitch) = note.child("pitch") {
+            return parse_pitch(pitch, "").map(|p| p + self.transpose);
+        }
+        let unpitched = note.child("unpitched")?;
+        note.child("instrument")
+            .and_then(|i| self.info.unpitched.get(i.attribute("id")?))
+            .copied()
+            .or_else(|| parse_pitch(unpitched, "display-"))
+    }
+
+    /// String and fret of a pitch, on a string left free by the other notes of the beat.
+    /// String and fret of a pitch on a free string, `None` when no string reaches it.
+    fn place(&self, pitch: i32, used: &[Note]) -> Option<(i8, i16)> {
+        let free = self
+            .strings
+            .iter()
+            .filter(|(string, _)| !used.iter().any(|n| n.string == *string));
+        if self.percussion {
+            let string = free.map(|s| s.0).next().unwrap_or(1);
+            return Some((string, pitch as i16));
+        }
+        let fret_count = i32::from(DEFAULT_FRET_COUNT);
+        free.filter_map(|(string, tuning)| {
+            let fret = pitch - i32::from(*tuning);
+            (0..=fret_count)
+                .contains(&fret)
+                .then_some((*string, fret as i16))
+        })
+        .min_by_key(|(_, fret)| *fret)
+    }
+
+    fn read_duration(&self, note: &XmlElement, ticks: i64) -> Duration {
+        let mut duration = match child_text(note, "type").and_then(note_type_value) {
+            Some(value) => {
+                let dots = children(note, "dot").count();
+                Duration {
+                    value,
+                    dotted: dots == 1,
+                    double_dotted: dots >= 2,
+                    ..Default::default()
+                }
+            }
+            None => duration_from_ticks(ticks),
+        };
+        if let Some(modification) = note.child("time-modification") {
+            let actual = child_number::<u8>(modification, "actual-notes").unwrap_or(1);
+            let normal = child_number::<u8>(modification, "normal-notes").unwrap_or(1);
+            if actual > 0 && normal > 0 && actual != normal {
+                duration.tuplet_enters = actual;
+                duration.tuplet_times = normal;
+            }
+        }
+        duration
+    }
+
+    fn read_grace(&self, note: &XmlElement) -> PendingGrace {
+        let starts = |name: &str| {
+            notation_items(note).any(|n| n.name == name && n.attribute("type") == Some("start"))
+        };
+        let transition = if starts("slur") || starts("hammer-on") || starts("pull-off") {
+            GraceEffectTransition::Hammer
+        } else if starts("slide") || starts("glissando") {
+            GraceEffectTransition::Slide
+        } else {
+            GraceEffectTransition::None
+        };
+        let duration = child_text(note, "type")
+            .and_then(note_type_value)
+            .map_or(32, |v| v.clamp(16, 64) as u8);
+        PendingGrace {
+            effect: GraceEffect {
+                duration,
+                is_dead: child_text(note, "notehead") == Some("x"),
+                velocity: self.velocity,
+                transition,
+                ..Default::default()
+            },
+            fret: notation_items(note)
+                .find(|n| n.name == "fret")
+                .and_then(|n| n.text().trim().parse().ok()),
+            pitch: self.note_pitch(note),
+        }
+    }
+
+    fn read_note(&mut self, element: &XmlElement, m_idx: usize, state: &mut MeasureState) {
+        if element.child("grace").is_none() && element.child("chord").is_none() {
+            self.read_lyrics(element, m_idx);
+        }
+        if !self.reads_staff(element) {
+            return;
+        }
+        if element.child("grace").is_some() {
+            let grace = self.read_grace(element);
+            state.graces.push_back(grace);
+            return;
+        }
+        let ticks = self.ticks(element);
+        let is_chord = element.child("chord").is_some();
+        let voice_id = child_text(element, "voice").unwrap_or("1").to_string();
+        let slot = match state.voices.iter().position(|v| v.0 == voice_id) {
+            Some(slot) => slot,
+            None if state.voices.len() < MAX_VOICES => {
+                state.voices.push((voice_id, Voice::default(), 0));
+                state.voices.len() - 1
+            }
+            None => {
+                let lost = LostItem {
+                    feature: "additional voice",
+                    position: LossPosition {
+                        track: Some(self.track_index + 1),
+                        measure: Some(m_idx + 1),
+                        ..Default::default()
+                    },
+                };
+                if self.losses.last() != Some(&lost) {
+                    self.losses.push(lost);
+                }
+                if !is_chord {
+                    state.position += ticks;
+                }
+                return;
+            }
+        };
+
+        let (_, voice, end) = &mut state.voices[slot];
+        if !is_chord || voice.beats.is_empty() {
+            // Fill the time skipped with `forward` or `backup` with rests
+            let mut gap = state.position - *end;
+            while gap >= DURATION_QUARTER_TIME / 32 {
+                let duration = duration_from_ticks(gap);
+                gap -= i64::from(duration.time());
+                voice.beats.push(Beat {
+                    status: BeatStatus::Rest,
+                    duration,
+                    ..Default::default()
+                });
+            }
+            voice.beats.push(Beat {
+                status: BeatStatus::Rest,
+                duration: self.read_duration(element, ticks),
+                text: self.text.take().unwrap_or_default(),
+                ..Default::default()
+            });
+            *end = state.position.max(*end) + ticks;
+            if !is_chord {
+                state.position += ticks;
+            }
+            state.beat_graces = std::mem::take(&mut state.graces);
+            // A palm mute or let ring without dashes only lasts one beat
+            state.palm_mute = self.palm_mute.is_some();
+            if self.palm_mute == Some(None) {
+                self.palm_mute = None;
+            }
+            state.let_ring = self.let_ring.is_some();
+            if self.let_ring == Some(None) {
+                self.let_ring = None;
+            }
+        }
+        if element.child("rest").is_some() {
+            return;
+        }
+
+        let beat_number = voice.beats.len();
+        let beat = voice.beats.last_mut().unwrap();
+        let pitch = self.note_pitch(element);
+        let technical: Vec<&XmlElement> = children(element, "notations")
+            .flat_map(|n| children(n, "technical"))
+            .flat_map(|t| t.children.iter())
+            .collect();
+        let technical_number = |name: &str| {
+            technical
+                .iter()
+                .find(|t| t.name == name)
+                .and_then(|t| t.text().trim().parse::<i16>().ok())
+        };
+        let placed = match (technical_number("string"), technical_number("fret")) {
+            (Some(string), Some(fret)) if string >= 1 && string as usize <= self.strings.len() => {
+                Some((string as i8, fret))
+            }
+            _ => self.place(pitch.unwrap_or(0), &beat.notes),
+        };
+        let Some((string, value)) = placed else {
+            self.losses.push(LostItem {
+                feature: "note that fits on no string",
+                position: LossPosition {
+                    track: Some(self.track_index + 1),
+                    measure: Some(m_idx + 1),
+                    voice: Some(slot + 1),
+                    beat: Some(beat_number),
+                },
+            });
+            return;
+        };
+        beat.status = BeatStatus::Normal;
+        let mut note = Note {
+            kind: NoteType::Normal,
+            velocity: self.velocity,
+            string,
+            value,
+            ..Default::default()
+        };
+
+        let tie_stop = |e: &XmlElement| e.attribute("type") == Some("stop");
+        if children(element, "tie").any(tie_stop)
+            || notation_items(element).any(|n| n.name == "tied" && tie_stop(n))
+        {
+            note.kind = NoteType::Tie;
+        }
+        match element.child("notehead") {
+            Some(head) if head.text().trim() == "x" => note.kind = NoteType::Dead,
+            Some(head) if head.attribute("parentheses") == Some("yes") => {
+                note.effect.ghost_note = true
+            }
+            _ => {}
+        }
+        note.effect.palm_mute = state.palm_mute;
+        note.effect.let_ring = state.let_ring;
+
+        let bends: Vec<&XmlElement> = technical
+            .iter()
+            .copied()
+            .filter(|t| t.name == "bend")
+            .collect();
+        note.effect.bend = parse_bend(&bends);
+        for item in &technical {
+            let starts = item.attribute("type") == Some("start");
+            match item.name.as_str() {
+                "hammer-on" | "pull-off" if starts => note.effect.hammer = true,
+                "harmonic" => {
+                    note.effect.harmonic = Some(HarmonicEffect {
+                        kind: if item.child("artificial").is_some() {
+                            HarmonicType::Artificial
+                        } else {
+                            HarmonicType::Natural
+                        },
+                        ..Default::default()
+                    })
+                }
+                "fingering" => {
+                    if let Some(finger) = parse_left_hand_finger(item.text().trim()) {
+                        note.effect.left_hand_finger = finger;
+                    }
+                }
+                "pluck" => {
+                    if let Some(finger) = parse_right_hand_finger(item.text().trim()) {
+                        note.effect.right_hand_finger = finger;
+                    }
+                }
+                "down-bow" => beat.effect.pick_stroke = BeatStrokeDirection::Down,
+                "up-bow" => beat.effect.pick_stroke = BeatStrokeDirection::Up,
+                "tap" => beat.effect.slap_effect = SlapEffect::Tapping,
+                _ => {}
+            }
+        }
+        for item in notation_items(element) {
+            let starts = item.attribute("type") == Some("start");
+            match item.name.as_str() {
+                "slide" | "glissando" if starts => note.effect.slides.push(SlideType::ShiftSlideTo),
+                "arpeggiate" => {
+                    beat.effect.stroke.direction = match item.attribute("direction") {
+                        Some("down") => BeatStrokeDirection::Down,
+                        _ => BeatStrokeDirection::Up,
+                    };
+                    beat.effect.stroke.value = DURATION_EIGHTH as u16;
+                }
+                "accent" => note.effect.accentuated_note = true,
+                "strong-accent" => note.effect.heavy_accentuated_note = true,
+                "staccato" | "staccatissimo" => note.effect.staccato = true,
+                "scoop" => note.effect.slides.push(SlideType::IntoFromBelow),
+                "plop" => note.effect.slides.push(SlideType::IntoFromAbove),
+                "falloff" => note.effect.slides.push(SlideType::OutDownwards),
+                "doit" => note.effect.slides.push(SlideType::OutUpWards),
+                "trill-mark" => {
+                    note.effect.trill = Some(TrillEffect {
+                        fret: (note.value + 2) as i8,
+                        duration: Duration {
+                            value: DURATION_SIXTEENTH as u16,
+                            ..Default::default()
+                        },
+                    })
+                }
+                "tremolo" if item.attribute("type").is_none_or(|t| t == "single") => {
+                    let value = match item.text().trim() {
+                        "3" | "4" => DURATION_THIRTY_SECOND,
+                        "2" => DURATION_SIXTEENTH,
+                        _ => DURATION_EIGHTH,
+                    };
+                    note.effect.tremolo_picking = Some(TremoloPickingEffect {
+                        duration: Duration {
+                            value: value as u16,
+                            ..Default::default()
+                        },
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(grace) = state.beat_graces.pop_front() {
+            let tuning = self
+                .strings
+                .iter()
+                .find(|s| s.0 == note.string)
+                .map_or(0, |s| i32::from(s.1));
+            let fret = grace.fret.unwrap_or_else(|| {
+                grace
+                    .pitch
+                    .map_or(note.value as i8, |p| (p - tuning).clamp(0, 127) as i8)
+            });
+            note.effect.grace = Some(GraceEffect {
+                fret,
+                ..grace.effect
+            });
+        }
+
+        beat.notes.push(note);
+    }
+
+    /// Keep the syllables sung on a note, whatever its staff.
+    fn read_lyrics(&mut self, element: &XmlElement, m_idx: usize) {
+        for lyric in children(element, "lyric") {
+            let text: Vec<&str> = children(lyric, "text").map(|t| t.text()).collect();
+            if text.is_empty() {
+                continue;
+            }
+            if self.syllables.len() <= m_idx {
+                self.syllables.resize_with(m_idx + 1, Vec::new);
+            }
+            self.syllables[m_idx].push((
+                lyric.attribute("number").unwrap_or("1").to_string(),
+                text.join(" "),
+                child_text(lyric, "syllabic")
+                    .unwrap_or("single")
+                    .to_string(),
+            ));
+        }
+    }
+}
+
+/// Lyrics lines of a part as (start measure, text), in the order of their numbers.
+pub(crate) fn lyrics_lines(syllables: &[Vec<Syllable>]) -> Vec<(u16, String)> {
+    let mut numbers: Vec<&str> = Vec::new();
+    for (number, _, _) in syllables.iter().flatten() {
+        if !numbers.contains(&number.as_str()) {
+            numbers.push(number);
+        }
+    }
+    numbers.sort_by_key(|n| n.parse::<u32>().unwrap_or(u32::MAX));
+    numbers
+        .into_iter()
+        .map(|number| {
+            let mut start = 0;
+            let mut text = String::new();
+            for (m_idx, measure) in syllables.iter().enumerate() {
+                for (_, syllable, syllabic) in measure.iter().filter(|s| s.0 == number) {
+                    if start == 0 {
+                        start = m_idx + 1;
+                    }
+                    // Words sung on one beat are bound with "+"
+                    text.push_str(&syllable.split_whitespace().collect::<Vec<_>>().join("+"));
+                    text.push_str(match syllabic.as_str() {
+                        "begin" | "middle" => "-",
+                        _ => " ",
+                    });
+                }
+            }
+            (start as u16, text)
+        })
+        .collect()
+}
+
+/// Children of the `notations` of a note and of their groups (articulations, ornaments, technical).
+fn notation_items(note: &XmlElement) -> impl Iterator<Item = &XmlElement> {
+    children(note, "notations").flat_map(|n| {
+        n.children.iter().flat_map(|c| {
+            if matches!(c.name.as_str(), "articulations" | "ornaments" | "technical") {
+                c.children.iter().collect::<Vec<_>>()
+            } else {
+                vec![c]
+            }
+        })
+    })
+}
+
+// ---------------------------------------------------------------------------
+// Main conversion
+// ---------------------------------------------------------------------------
+
+/// Measure headers, read from the first part: time and key signatures, repeats, alternate endings,
+/// tempo, markers and navigation signs. Returns the headers and the tempo at the start of the song.
+fn read_measure_headers(part: &XmlElement, count: usize) -> (Vec<MeasureHeader>, Option<i32>) {
+    let mut headers: Vec<MeasureHeader> = Vec::with_capacity(count);
+    let mut song_tempo = None;
+    let mut ending = 0u8;
+    let measures: Vec<&XmlElement> = children(part, "measure").collect();
+    for m_idx in 0..count {
+        let mut header = MeasureHeader {
+            number: (m_idx + 1) as u16,
+            ..Default::default()
+        };
+        if let Some(previous) = headers.last() {
+            header.time_signature = previous.time_signature.clone();
+            header.key_signature = previous.key_signature.clone();
+        }
+        header.repeat_alternative = ending;
+        let mut ending_stop = false;
+        let mut set_tempo = |header: &mut MeasureHeader, tempo: Option<f64>| {
+            if let Some(tempo) = tempo.map(|t| t.round() as i32).filter(|t| *t > 0) {
+                if m_idx == 0 && song_tempo.is_none() {
+                    song_tempo = Some(tempo);
+                }
+                header.tempo = tempo;
+            }
+        };
+        let empty = XmlElement::new("measure");
+        let measure = measures.get(m_idx).copied().unwrap_or(&empty);
+        for child in &measure.children {
+            match child.name.as_str() {
+                "attributes" => {
+                    if let Some(key) = child.child("key") {
+                        if let Some(fifths) = child_number::<i8>(key, "fifths") {
+                            header.key_signature.key = fifths;
+                            header.key_signature.is_minor =
+                                child_text(key, "mode") == Some("minor");
+                        }
+                    }
+                    if let Some(time) = child.child("time") {
+                        if time.child("senza-misura").is_some() {
+                            header.free_time = true;
+                        }
+                        let beats: Option<i32> = child_text(time, "beats")
+                            .and_then(|b| b.split('+').map(|n| n.trim().parse::<i32>().ok()).sum());
+                        if let (Some(beats), Some(beat_type)) =
+                            (beats, child_number::<u16>(time, "beat-type"))
+                        {
+                            header.time_signature.numerator = beats.clamp(1, 127) as i8;
+                            header.time_signature.denominator.value = beat_type;
+                        }
+                    }
+                }
+                "barline" => {
+                    let right = child.attribute("location").unwrap_or("right") == "right";
+                    if let Some(repeat) = child.child("repeat") {
+                        match repeat.attribute("direction") {
+                            Some("forward") => header.repeat_open = true,
+                            Some("backward") => {
+                                header.repeat_close = repeat
+                                    .attribute("times")
+                                    .and_then(|t| t.parse().ok())
+                                    .unwrap_or(1)
+                            }
+                            _ => {}
+                        }
+                    }
+                    if let Some(end) = child.child("ending") {
+                        let numbers = parse_ending_numbers(end.attribute("number").unwrap_or(""));
+                        match end.attribute("type") {
+                            Some("start") => {
+                                ending = numbers;
+                                header.repeat_alternative = numbers;
+                            }
+                            Some("stop" | "discontinue") => {
+                                if header.repeat_alternative == 0 {
+                                    header.repeat_alternative = numbers;
+                                }
+                                ending_stop = true;
+                            }
+                            _ => {}
+                        }
+                    }
+                    if right && child_text(child, "bar-style") == Some("light-light") {
+                        header.double_bar = true;
+                    }
+                }
+                "direction" => {
+                    let items = children(child, "direction-type").flat_map(|t| t.children.iter());
+                    for item in items {
+                        match item.name.as_str() {
+                            "rehearsal" => {
+                                header.marker = Some(Marker {
+                                    title: item.text().trim().to_string(),
+                                    ..Default::default()
+                                })
+                            }
+                            "segno" => header.direction = Some(DirectionSign::Segno),
+                            "coda" => header.direction = Some(DirectionSign::Coda),
+                            "words" => {
+                                if let Some(sign) = parse_direction_words(item.text()) {
+                                    header.direction = Some(sign);
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                    if let Some(sound) = child.child("sound") {
+                        set_tempo(
+                            &mut header,
+                            sound.attribute("tempo").and_then(|t| t.parse().ok()),
+                        );
+                        if header.direction.is_none() {
+                            header.direction = sound_direction(sound);
+                        }
+                    }
+                }
+                "sound" => set_tempo(
+                    &mut header,
+                    child.attribute("tempo").and_then(|t| t.parse().ok()),
+                ),
+                _ => {}
+            }
+        }
+        if ending_stop {
+            ending = 0;
+        }
+        headers.push(header);
+    }
+    (headers, song_tempo)
+}
+
+/// Navigation sign given by the attributes of a `sound` element.
+fn sound_direction(sound: &XmlElement) -> Option<DirectionSign> {
+    if sound.attribute("dacapo") == Some("yes") {
+        Some(DirectionSign::DaCapo)
+    } else if sound.attribute("dalsegno").is_some() {
+        Some(DirectionSign::DaSegno)
+    } else if sound.attribute("tocoda").is_some() {
+        Some(DirectionSign::DaCoda)
+    } else if sound.attribute("fine").is_some() {
+        Some(DirectionSign::Fine)
+    } else {
+        None
+    }
+}
+
+impl Song {
+    fn read_musicxml_identification(&mut self, score: &XmlElement) {
+        let work_title = score
+            .child("work")
+            .and_then(|w| child_text(w, "work-title"))
+            .unwrap_or_default();
+        let movement_title = child_text(score, "movement-title").unwrap_or_default();
+        if work_title.is_empty() {
+            self.name = movement_title.to_string();
+        } else {
+            self.name = work_title.to_string();
+            self.subtitle = movement_title.to_string();
+        }
+        let Some(identification) = score.child("identification") else {
+            return;
+        };
+        for creator in children(identification, "creator") {
+            let name = creator.text().trim().to_string();
+            let field = match creator.attribute("type") {
+                Some("composer") => &mut self.author,
+                Some("lyricist" | "poet") => &mut self.words,
+                Some("artist") => &mut self.artist,
+                Some("transcriber" | "arranger") => &mut self.transcriber,
+                _ => continue,
+            };
+            if field.is_empty() {
+                *field = name;
+            }
+        }
+        let rights: Vec<&str> = children(identification, "rights")
+            .map(|r| r.text().trim())
+            .collect();
+        self.copyright = rights.join("\n");
+        let fields = identification
+            .child("miscellaneous")
+            .into_iter()
+            .flat_map(|m| children(m, "miscellaneous-field"));
+        for field in fields {
+            let value = field.text().trim().to_string();
+            match field.attribute("name") {
+                Some("album") => self.album = value,
+                Some("instructions") => self.instructions = value,
+                Some("notice") => self.notice = value.lines().map(str::to_string).collect(),
+                _ => {}
+            }
+        }
+    }
+}
+
+impl SongMusicXmlOps for Song {
+    fn read_musicxml_document(&mut self, xml: &str) -> GpResult<()> {
+        let root = XmlElement::parse(xml)?;
+        let score = match root.name.as_str() {
+            "score-partwise" => root,
+            "score-timewise" => timewise_to_partwise(&root),
+            name => {
+                return Err(GpError::FormatError(format!(
+                    "not a MusicXML score: <{}>",
+                    name
+                )))
+            }
+        };
+        self.read_musicxml_identification(&score);
+
+        let part_list = score
+            .child("part-list")
+            .ok_or_else(|| GpError::FormatError("MusicXML score without part-list".into()))?;
+        let infos: HashMap<&str, PartInfo> = children(part_list, "score-part")
+            .map(|p| (p.attribute("id").unwrap_or_default(), PartInfo::new(p)))
+            .collect();
+        let parts: Vec<&XmlElement> = children(&score, "part").collect();
+        if parts.is_empty() {
+            return Err(GpError::FormatError("MusicXML score without parts".into()));
+        }
+        let measure_count = parts
+            .iter()
+            .map(|p| children(p, "measure").count())
+            .max()
+            .unwrap_or(0)
+            .max(1);
+        let (headers, tempo) = read_measure_headers(parts[0], measure_count);
+        if let Some(tempo) = tempo {
+            self.tempo = tempo.clamp(1, i32::from(i16::MAX)) as i16;
+        }
+        self.key = headers[0].key_signature.clone();
+        self.measure_headers = headers;
+
+        // 64 channels as in Guitar Pro files, tracks without a MIDI channel get the next free one
+        self.channels = (0..64u8)
+            .map(|channel| MidiChannel {
+                channel,
+                effect_channel: channel,
+                ..Default::default()
+            })
+            .collect();
+        let mut used_channels: Vec<u8> = infos.values().filter_map(|i| i.channel).collect();
+        let default_info = PartInfo::default();
+        self.tracks.clear();
+        self.lyrics = Lyrics::default();
+        self.read_losses.clear();
+        for (t_idx, part) in parts.iter().enumerate() {
+            let info = infos
+                .get(part.attribute("id").unwrap_or_default())
+                .unwrap_or(&default_info);
+            let mut reader = PartReader::new(info, part, t_idx);
+            let mut track = reader.read_track(part, &self.measure_headers);
+            self.read_losses.append(&mut reader.losses);
+
+            let channel = match info.channel {
+                Some(channel) => channel,
+                None if track.percussion_track => DEFAULT_PERCUSSION_CHANNEL,
+                None => {
+                    let free = (0..16u8)
+                        .find(|c| *c != DEFAULT_PERCUSSION_CHANNEL && !used_channels.contains(c))
+                        .unwrap_or(0);
+                    used_channels.push(free);
+                    free
+                }
+            };
+            track.channel_index = usize::from(channel);
+            let midi = &mut self.channels[usize::from(channel)];
+            midi.set_instrument(info.program.unwrap_or(if track.percussion_track {
+                0
+            } else {
+                25
+            }));
+            if let Some(volume) = info.volume {
+                midi.volume = (volume * 127.0 / 100.0).round().clamp(0.0, 127.0) as i8;
+            }
+            if let Some(pan) = info.pan {
+                midi.balance = (pan * 64.0 / 90.0 + 64.0).round().clamp(0.0, 127.0) as i8;
+            }
+
+            if self.lyrics.lines.is_empty() {
+                let lines = lyrics_lines(&reader.syllables);
+                if !lines.is_empty() {
+                    self.lyrics.track_choice = (t_idx + 1) as u8;
+                    for (line, (start, text)) in lines.into_iter().take(5).enumerate() {
+                        self.lyrics.lines.push((line as u8, start, text));
+                    }
+                }
+            }
+            self.tracks.push(track);
+        }
+        // Guitar Pro files always hold five lyrics lines
+        for line in self.lyrics.lines.len()..5 {
+            self.lyrics.lines.push((line as u8, 1, String::new()));
+        }
+        Ok(())
+    }
+}
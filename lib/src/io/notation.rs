@@ -0,0 +1,77 @@
+//! Durations and bends of the text and XML formats, which store lengths in ticks and bends as
+//! successive values instead of Guitar Pro points.
+use crate::model::{
+    effects::{BendEffect, BendPoint, BEND_EFFECT_MAX_POSITION, GP_BEND_SEMITONE},
+    enums::BendType,
+    key_signature::{Duration, DURATION_QUARTER_TIME},
+};
+
+/// Duration matching a length in ticks, such as a note written without a type or a gap to fill.
+pub(crate) fn duration_from_ticks(ticks: i64) -> Duration {
+    let mut duration = Duration::default();
+    for value in [1u16, 2, 4, 8, 16, 32, 64, 128] {
+        let base = DURATION_QUARTER_TIME * 4 / i64::from(value);
+        if ticks == base {
+            duration.value = value;
+            return duration;
+        }
+        if ticks == base + base / 2 {
+            duration.value = value;
+            duration.dotted = true;
+            return duration;
+        }
+        if ticks == base + base / 2 + base / 4 {
+            duration.value = value;
+            duration.double_dotted = true;
+            return duration;
+        }
+        if ticks * 3 == base * 2 {
+            duration.value = value;
+            duration.tuplet_enters = 3;
+            duration.tuplet_times = 2;
+            return duration;
+        }
+    }
+    // Longer than a whole note or irregular: the closest shorter value
+    duration.value = [1u16, 2, 4, 8, 16, 32, 64, 128]
+        .into_iter()
+        .find(|v| DURATION_QUARTER_TIME * 4 / i64::from(*v) <= ticks)
+        .unwrap_or(128);
+    duration
+}
+
+/// Type of a bend through successive values.
+pub(crate) fn bend_type(values: &[i8]) -> BendType {
+    match (values.first().is_some_and(|v| *v > 0), values.len()) {
+        (true, _) if values.last() < values.first() => BendType::PrebendRelease,
+        (true, _) => BendType::Prebend,
+        (false, n) if n >= 4 && values[n - 1] > values[n - 2] => BendType::BendReleaseBend,
+        (false, _) if values.last() == Some(&0) => BendType::BendRelease,
+        _ => BendType::Bend,
+    }
+}
+
+/// Bend through successive values in quarter tones, spread evenly over the note.
+pub(crate) fn bend_from_values(values: Vec<i8>) -> Option<BendEffect> {
+    let max = values.iter().copied().max().unwrap_or(0);
+    if max == 0 {
+        return None;
+    }
+    let kind = bend_type(&values);
+    let last_index = (values.len() - 1).max(1);
+    let points = values
+        .iter()
+        .enumerate()
+        .map(|(i, value)| BendPoint {
+            position: (i * usize::from(BEND_EFFECT_MAX_POSITION) / last_index) as u8,
+            value: *value,
+            vibrato: false,
+        })
+        .collect();
+    Some(BendEffect {
+        kind,
+        value: (f32::from(max) * GP_BEND_SEMITONE) as i16,
+        points,
+        ..Default::default()
+    })
+}
@@ -0,0 +1,1512 @@
+//! Power Tab import (`.ptb` files of Power Tab Editor 1.7).
+//!
+//! A Power Tab document holds a guitar score and a bass score. Each score lists its guitars, chord
+//! diagrams and symbols (tempo markers, dynamics, alternate endings, guitar assignments) and its
+//! systems: lines of music made of staves, barlines, chord names and rhythm slashes. Every guitar
+//! becomes a track, read from the staff it is assigned to, or else from the rhythm slashes of the
+//! system, strummed with the frets of the current chord diagram. The measures are the ones of the
+//! guitar score, multi-bar rests are read as a single measure.
+use encoding_rs::WINDOWS_1252;
+
+use crate::audio::midi::{MidiChannel, DEFAULT_PERCUSSION_CHANNEL};
+use crate::error::{GpError, GpResult};
+use crate::io::notation::{bend_from_values, duration_from_ticks};
+use crate::io::primitive::{read_byte, read_int, read_short};
+use crate::model::{
+    beat::{Beat, Voice},
+    chord::{Chord, PitchClass},
+    effects::{
+        unpack_velocity, BendEffect, GraceEffect, HarmonicEffect, TremoloPickingEffect,
+        TrillEffect, FORTE,
+    },
+    enums::*,
+    headers::{Marker, MeasureHeader},
+    key_signature::*,
+    lyric::Lyrics,
+    measure::Measure,
+    note::Note,
+    song::Song,
+    track::Track,
+};
+
+/// `ptab` in little-endian.
+const PTB_MARKER: u32 = 0x6261_7470;
+/// File version written by Power Tab Editor 1.7.
+const PTB_VERSION_1_7: u16 = 4;
+const PTB_FILE_SONG: u16 = 0;
+const DEFAULT_FRET_COUNT: u8 = 24;
+
+// Barline types
+const BAR_DOUBLE: u8 = 1;
+const BAR_REPEAT_START: u8 = 3;
+const BAR_REPEAT_END: u8 = 4;
+const BAR_DOUBLE_FINE: u8 = 5;
+
+// Flags of a position
+const POSITION_DOTTED: u32 = 0x01;
+const POSITION_DOUBLE_DOTTED: u32 = 0x02;
+const POSITION_REST: u32 = 0x04;
+const POSITION_VIBRATO: u32 = 0x08;
+const POSITION_WIDE_VIBRATO: u32 = 0x10;
+const POSITION_ARPEGGIO_UP: u32 = 0x20;
+const POSITION_ARPEGGIO_DOWN: u32 = 0x40;
+const POSITION_PICK_UP: u32 = 0x80;
+const POSITION_PICK_DOWN: u32 = 0x100;
+const POSITION_STACCATO: u32 = 0x200;
+const POSITION_MARCATO: u32 = 0x400;
+const POSITION_SFORZANDO: u32 = 0x800;
+const POSITION_TREMOLO_PICKING: u32 = 0x1000;
+const POSITION_PALM_MUTE: u32 = 0x2000;
+const POSITION_TAP: u32 = 0x4000;
+const POSITION_ACCIACCATURA: u32 = 0x8000;
+const POSITION_LET_RING: u32 = 0x40000;
+const POSITION_GROUP_START: u32 = 0x100000;
+const POSITION_GROUP_END: u32 = 0x400000;
+
+// Flags of a note
+const NOTE_TIED: u16 = 0x01;
+const NOTE_MUTED: u16 = 0x02;
+const NOTE_HAMMER_ON: u16 = 0x08;
+const NOTE_PULL_OFF: u16 = 0x10;
+const NOTE_NATURAL_HARMONIC: u16 = 0x40;
+const NOTE_GHOST: u16 = 0x80;
+
+// Symbols of a note, in the high byte
+const SYMBOL_SLIDE: u32 = 0x64;
+const SYMBOL_BEND: u32 = 0x65;
+const SYMBOL_TAPPED_HARMONIC: u32 = 0x66;
+const SYMBOL_TRILL: u32 = 0x67;
+const SYMBOL_ARTIFICIAL_HARMONIC: u32 = 0x68;
+
+// Flags of a rhythm slash
+const SLASH_DOTTED: u32 = 0x01;
+const SLASH_DOUBLE_DOTTED: u32 = 0x02;
+const SLASH_REST: u32 = 0x04;
+const SLASH_TIED: u32 = 0x08;
+const SLASH_MUTED: u32 = 0x10;
+const SLASH_PICK_UP: u32 = 0x40;
+const SLASH_PICK_DOWN: u32 = 0x80;
+
+// Chord names
+const CHORD_NO_CHORD: u8 = 0x10;
+const CHORD_BRACKETS: u8 = 0x20;
+const CHORD_NINTH: u16 = 0x01;
+const CHORD_ELEVENTH: u16 = 0x02;
+const CHORD_THIRTEENTH: u16 = 0x04;
+const CHORD_ADDED: u16 = 0xf8;
+const CHORD_FLAT_FIFTH: u16 = 0x100;
+const CHORD_SHARP_FIFTH: u16 = 0x200;
+const CHORD_FLAT_NINTH: u16 = 0x400;
+const CHORD_SHARP_NINTH: u16 = 0x800;
+const CHORD_SHARP_ELEVENTH: u16 = 0x1000;
+const CHORD_SUSPENDED_SECOND: u16 = 0x4000;
+const CHORD_SUSPENDED_FOURTH: u16 = 0x8000;
+
+/// Suffix of the chord formulas: major, minor, augmented, diminished, power chord, major 6th,
+/// minor 6th, dominant 7th, major 7th, minor 7th, augmented 7th, diminished 7th, minor major 7th
+/// and minor 7th flat 5.
+const FORMULA_SUFFIXES: [&str; 14] = [
+    "", "m", "+", "dim", "5", "6", "m6", "7", "maj7", "m7", "+7", "dim7", "m(maj7)", "m7b5",
+];
+/// Alterations and added notes written after the chord formula.
+const CHORD_MODIFICATIONS: [(u16, &str); 13] = [
+    (CHORD_FLAT_FIFTH, "b5"),
+    (CHORD_SHARP_FIFTH, "#5"),
+    (CHORD_FLAT_NINTH, "b9"),
+    (CHORD_SHARP_NINTH, "#9"),
+    (CHORD_SHARP_ELEVENTH, "#11"),
+    (0x2000, "b13"),
+    (0x08, "add2"),
+    (0x10, "add4"),
+    (0x20, "add6"),
+    (0x40, "add9"),
+    (0x80, "add11"),
+    (CHORD_SUSPENDED_SECOND, "sus2"),
+    (CHORD_SUSPENDED_FOURTH, "sus4"),
+];
+const KEY_NAMES: [&str; 12] = [
+    "C", "C#", "D", "Eb", "E", "F", "F#", "G", "Ab", "A", "Bb", "B",
+];
+/// Other spelling of the keys, chosen with the key variation.
+const ALTERNATE_KEY_NAMES: [&str; 12] = [
+    "B#", "Db", "D", "D#", "Fb", "E#", "Gb", "G", "G#", "A", "A#", "Cb",
+];
+
+pub trait SongPowerTabOps {
+    /// Read a Power Tab Editor 1.7 document (`.ptb`).
+    fn read_ptb(&mut self, data: &[u8]) -> GpResult<()>;
+}
+
+// ---------------------------------------------------------------------------
+// Document
+// ---------------------------------------------------------------------------
+
+/// Reader of the MFC archive the documents are written with: little-endian numbers, strings
+/// prefixed with their length and objects preceded by a class tag.
+struct PtbReader<'a> {
+    data: &'a [u8],
+    seek: usize,
+}
+
+impl<'a> PtbReader<'a> {
+    fn byte(&mut self) -> GpResult<u8> {
+        read_byte(self.data, &mut self.seek)
+    }
+
+    fn short(&mut self) -> GpResult<u16> {
+        Ok(read_short(self.data, &mut self.seek)? as u16)
+    }
+
+    fn int(&mut self) -> GpResult<u32> {
+        Ok(read_int(self.data, &mut self.seek)? as u32)
+    }
+
+    fn bytes(&mut self, count: usize) -> GpResult<&'a [u8]> {
+        if self.data.len() - self.seek < count {
+            return Err(GpError::UnexpectedEof {
+                offset: self.seek,
+                needed: count,
+            });
+        }
+        let bytes = &self.data[self.seek..self.seek + count];
+        self.seek += count;
+        Ok(bytes)
+    }
+
+    /// String with a byte length, or a longer one after a `0xFF` (16 bits) or `0xFFFF` (32 bits).
+    fn string(&mut self) -> GpResult<String> {
+        let mut length = usize::from(self.byte()?);
+        if length == 0xff {
+            length = usize::from(self.short()?);
+            if length == 0xffff {
+                length = self.int()? as usize;
+            }
+        }
+        let (text, _, _) = WINDOWS_1252.decode(self.bytes(length)?);
+        Ok(text.into_owned())
+    }
+
+    /// Element count of an array, 16 bits or 32 bits after `0xFFFF`.
+    fn count(&mut self) -> GpResult<usize> {
+        let count = self.short()?;
+        Ok(if count == 0xffff {
+            self.int()? as usize
+        } else {
+            usize::from(count)
+        })
+    }
+
+    /// Class tag of an object: a new class is followed by its schema and its name.
+    fn class_tag(&mut self) -> GpResult<()> {
+        match self.short()? {
+            0xffff => {
+                self.short()?;
+                let length = usize::from(self.short()?);
+                self.bytes(length)?;
+            }
+            0x7fff => {
+                self.int()?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn objects<T>(&mut self, read: impl Fn(&mut Self) -> GpResult<T>) -> GpResult<Vec<T>> {
+        let count = self.count()?;
+        let mut objects = Vec::with_capacity(count.min(1024));
+        for _ in 0..count {
+            self.class_tag()?;
+            objects.push(read(self)?);
+        }
+        Ok(objects)
+    }
+
+    /// Values of an array with a byte count.
+    fn values<T>(&mut self, read: impl Fn(&mut Self) -> GpResult<T>) -> GpResult<Vec<T>> {
+        let count = self.byte()?;
+        (0..count).map(|_| read(self)).collect()
+    }
+}
+
+#[derive(Debug, Default)]
+struct SongInfo {
+    title: String,
+    artist: String,
+    album: String,
+    composer: String,
+    lyricist: String,
+    transcriber: String,
+    copyright: String,
+    lyrics: String,
+    notes: String,
+}
+
+impl SongInfo {
+    fn read(r: &mut PtbReader) -> GpResult<SongInfo> {
+        let mut info = SongInfo::default();
+        r.byte()?; // content type
+        info.title = r.string()?;
+        info.artist = r.string()?;
+        match r.byte()? {
+            // Audio release: type, title, year and live recording
+            0 => {
+                r.byte()?;
+                info.album = r.string()?;
+                r.short()?;
+                r.byte()?;
+            }
+            // Video release: title and live recording
+            1 => {
+                info.album = r.string()?;
+                r.byte()?;
+            }
+            // Bootleg: title and date (month, day, year)
+            2 => {
+                info.album = r.string()?;
+                r.bytes(6)?;
+            }
+            _ => {}
+        }
+        // Known author
+        if r.byte()? == 0 {
+            info.composer = r.string()?;
+            info.lyricist = r.string()?;
+        }
+        r.string()?; // arranger
+        info.transcriber = r.string()?; // of the guitar score
+        let bass_transcriber = r.string()?;
+        if info.transcriber.is_empty() {
+            info.transcriber = bass_transcriber;
+        }
+        info.copyright = r.string()?;
+        info.lyrics = r.string()?;
+        info.notes = r.string()?; // of the guitar score
+        let bass_notes = r.string()?;
+        if !bass_notes.is_empty() {
+            if !info.notes.is_empty() {
+                info.notes.push('\n');
+            }
+            info.notes.push_str(&bass_notes);
+        }
+        Ok(info)
+    }
+
+    fn read_lesson(r: &mut PtbReader) -> GpResult<SongInfo> {
+        let mut info = SongInfo {
+            title: r.string()?,
+            album: r.string()?, // subtitle
+            ..Default::default()
+        };
+        r.short()?; // music style
+        r.byte()?; // level
+        info.composer = r.string()?;
+        info.notes = r.string()?;
+        info.copyright = r.string()?;
+        Ok(info)
+    }
+}
+
+#[derive(Debug)]
+struct Guitar {
+    description: String,
+    preset: u8,
+    volume: u8,
+    pan: u8,
+    reverb: u8,
+    chorus: u8,
+    tremolo: u8,
+    phaser: u8,
+    capo: u8,
+    /// MIDI notes of the strings, highest string first
+    tuning: Vec<u8>,
+}
+
+impl Guitar {
+    fn read(r: &mut PtbReader) -> GpResult<Guitar> {
+        r.byte()?; // number
+        let description = r.string()?;
+        let mut settings = [0u8; 8];
+        for setting in &mut settings {
+            *setting = r.byte()?;
+        }
+        r.string()?; // tuning name
+        r.byte()?; // notation offset and sharps
+        let tuning = r.values(|r| r.byte())?;
+        let [preset, volume, pan, reverb, chorus, tremolo, phaser, capo] = settings;
+        Ok(Guitar {
+            description,
+            preset,
+            volume,
+            pan,
+            reverb,
+            chorus,
+            tremolo,
+            phaser,
+            capo,
+            tuning,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct ChordName {
+    /// Tonic in the high byte, bass in the low byte: key and variation of the spelling
+    key: u16,
+    formula: u8,
+    modifications: u16,
+}
+
+impl ChordName {
+    fn read(r: &mut PtbReader) -> GpResult<ChordName> {
+        let name = ChordName {
+            key: r.short()?,
+            formula: r.byte()?,
+            modifications: r.short()?,
+        };
+        r.byte()?; // fret position and type
+        Ok(name)
+    }
+
+    fn tonic(&self) -> (u8, u8) {
+        let tonic = (self.key >> 8) as u8;
+        ((tonic & 0xf) % 12, (tonic & 0x30) >> 4)
+    }
+
+    fn bass(&self) -> (u8, u8) {
+        let bass = self.key as u8;
+        ((bass & 0xf) % 12, (bass & 0x30) >> 4)
+    }
+
+    fn has(&self, modification: u16) -> bool {
+        self.modifications & modification != 0
+    }
+
+    fn name(&self) -> String {
+        if self.formula & CHORD_NO_CHORD != 0 {
+            return String::from("N.C.");
+        }
+        let key_name = |(key, variation): (u8, u8)| {
+            if variation == 0 {
+                KEY_NAMES[usize::from(key)]
+            } else {
+                ALTERNATE_KEY_NAMES[usize::from(key)]
+            }
+        };
+        let mut suffix = FORMULA_SUFFIXES
+            .get(usize::from(self.formula & 0xf))
+            .copied()
+            .unwrap_or_default()
+            .to_string();
+        let extension = if self.has(CHORD_THIRTEENTH) {
+            Some("13")
+        } else if self.has(CHORD_ELEVENTH) {
+            Some("11")
+        } else if self.has(CHORD_NINTH) {
+            Some("9")
+        } else {
+            None
+        };
+        if let Some(extension) = extension {
+            suffix = match suffix.contains('7') {
+                true => suffix.replacen('7', extension, 1),
+                false => suffix + extension,
+            };
+        }
+        let mut name = format!("{}{}", key_name(self.tonic()), suffix);
+        for (modification, text) in CHORD_MODIFICATIONS {
+            if self.has(modification) {
+                name.push_str(text);
+            }
+        }
+        if self.bass() != self.tonic() {
+            name.push('/');
+            name.push_str(key_name(self.bass()));
+        }
+        if self.formula & CHORD_BRACKETS != 0 {
+            name = format!("({})", name);
+        }
+        name
+    }
+
+    /// Chord of the name, with the frets of its diagram.
+    fn chord(&self, diagram: Option<&ChordDiagram>, string_count: usize) -> Chord {
+        let formula = self.formula & 0xf;
+        let seventh = matches!(formula, 7..=13);
+        let kind = match formula {
+            _ if self.has(CHORD_SUSPENDED_SECOND) && seventh => ChordType::SeventhSuspendedSecond,
+            _ if self.has(CHORD_SUSPENDED_FOURTH) && seventh => ChordType::SeventhSuspendedFourth,
+            _ if self.has(CHORD_SUSPENDED_SECOND) => ChordType::SuspendedSecond,
+            _ if self.has(CHORD_SUSPENDED_FOURTH) => ChordType::SuspendedFourth,
+            1 => ChordType::Minor,
+            2 => ChordType::Augmented,
+            3 | 11 => ChordType::Diminished,
+            4 => ChordType::Power,
+            5 => ChordType::Sixth,
+            6 => ChordType::MinorSixth,
+            7 | 10 => ChordType::Seventh,
+            8 => ChordType::MajorSeventh,
+            9 | 13 => ChordType::MinorSeventh,
+            12 => ChordType::MinorMajor,
+            _ => ChordType::Major,
+        };
+        let extension = if self.has(CHORD_THIRTEENTH) {
+            ChordExtension::Thirteenth
+        } else if self.has(CHORD_ELEVENTH) {
+            ChordExtension::Eleventh
+        } else if self.has(CHORD_NINTH) {
+            ChordExtension::Ninth
+        } else {
+            ChordExtension::None
+        };
+        let alteration = |flat: bool, sharp: bool| match (flat, sharp) {
+            (true, _) => ChordAlteration::Diminished,
+            (_, true) => ChordAlteration::Augmented,
+            _ => ChordAlteration::Perfect,
+        };
+        let fifth = alteration(
+            self.has(CHORD_FLAT_FIFTH) || formula == 13,
+            self.has(CHORD_SHARP_FIFTH) || formula == 10,
+        );
+        let mut strings = vec![-1i8; string_count];
+        if let Some(diagram) = diagram {
+            for (string, fret) in strings.iter_mut().zip(&diagram.frets) {
+                *string = if *fret >= 0xfe { -1 } else { *fret as i8 };
+            }
+        }
+        let (tonic, tonic_variation) = self.tonic();
+        Chord {
+            length: string_count as u8,
+            sharp: Some(tonic_variation == 0),
+            root: Some(PitchClass::from(tonic as i8, None, None)),
+            kind: Some(kind),
+            extension: Some(extension),
+            bass: Some(PitchClass::from(self.bass().0 as i8, None, None)),
+            tonality: Some(fifth.clone()),
+            add: Some(self.has(CHORD_ADDED)),
+            name: self.name(),
+            fifth: Some(fifth),
+            ninth: Some(alteration(
+                self.has(CHORD_FLAT_NINTH),
+                self.has(CHORD_SHARP_NINTH),
+            )),
+            eleventh: Some(alteration(false, self.has(CHORD_SHARP_ELEVENTH))),
+            first_fret: diagram.map(|d| d.top_fret.max(1)),
+            strings,
+            show: Some(true),
+            new_format: Some(true),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug)]
+struct ChordDiagram {
+    name: ChordName,
+    top_fret: u8,
+    /// Frets from the highest string, `0xFE` and above for strings not played
+    frets: Vec<u8>,
+}
+
+impl ChordDiagram {
+    fn read(r: &mut PtbReader) -> GpResult<ChordDiagram> {
+        Ok(ChordDiagram {
+            name: ChordName::read(r)?,
+            top_fret: r.byte()?,
+            frets: r.values(|r| r.byte())?,
+        })
+    }
+}
+
+fn skip_floating_text(r: &mut PtbReader) -> GpResult<()> {
+    r.string()?;
+    r.bytes(16 + 1)?; // rectangle and flags
+    r.string()?; // font face
+    r.bytes(4 + 4 + 3 + 4)?; // size, weight, style and color
+    Ok(())
+}
+
+/// Guitars played on a staff and with the rhythm slashes of a system, from a position on.
+#[derive(Debug)]
+struct GuitarIn {
+    system: u16,
+    staff: u8,
+    position: u8,
+    staff_guitars: u8,
+    slash_guitars: u8,
+}
+
+impl GuitarIn {
+    fn read(r: &mut PtbReader) -> GpResult<GuitarIn> {
+        let (system, staff, position) = (r.short()?, r.byte()?, r.byte()?);
+        let data = r.short()?;
+        Ok(GuitarIn {
+            system,
+            staff,
+            position,
+            staff_guitars: data as u8,
+            slash_guitars: (data >> 8) as u8,
+        })
+    }
+}
+
+/// Symbol of a system: tempo marker or alternate ending.
+#[derive(Debug)]
+struct SystemSymbol {
+    system: u16,
+    position: u8,
+    data: u32,
+}
+
+impl SystemSymbol {
+    fn read(r: &mut PtbReader) -> GpResult<SystemSymbol> {
+        Ok(SystemSymbol {
+            system: r.short()?,
+            position: r.byte()?,
+            data: r.int()?,
+        })
+    }
+
+    fn read_tempo(r: &mut PtbReader) -> GpResult<SystemSymbol> {
+        let symbol = SystemSymbol::read(r)?;
+        r.string()?; // description
+        Ok(symbol)
+    }
+}
+
+/// Volume of a staff (low byte) and of the rhythm slashes (high byte), `0xFF` when not changed.
+#[derive(Debug)]
+struct Dynamic {
+    system: u16,
+    staff: u8,
+    position: u8,
+    volume: u16,
+}
+
+impl Dynamic {
+    fn read(r: &mut PtbReader) -> GpResult<Dynamic> {
+        Ok(Dynamic {
+            system: r.short()?,
+            staff: r.byte()?,
+            position: r.byte()?,
+            volume: r.short()?,
+        })
+    }
+}
+
+#[derive(Debug)]
+struct Barline {
+    position: u8,
+    /// Type in the 3 high bits, repeat count in the others
+    data: u8,
+    key: u8,
+    time: u32,
+    rehearsal_sign: Option<String>,
+}
+
+impl Barline {
+    fn read(r: &mut PtbReader) -> GpResult<Barline> {
+        let (position, data, key) = (r.byte()?, r.byte()?, r.byte()?);
+        let time = r.int()?;
+        r.byte()?; // pulses
+        let letter = r.byte()?;
+        let description = r.string()?;
+        let rehearsal_sign = letter.is_ascii_uppercase().then(|| {
+            if description.is_empty() {
+                char::from(letter).to_string()
+            } else {
+                description
+            }
+        });
+        Ok(Barline {
+            position,
+            data,
+            key,
+            time,
+            rehearsal_sign,
+        })
+    }
+
+    fn kind(&self) -> u8 {
+        self.data >> 5
+    }
+}
+
+#[derive(Debug)]
+struct Direction {
+    position: u8,
+    /// Type in the high byte
+    symbols: Vec<u16>,
+}
+
+impl Direction {
+    fn read(r: &mut PtbReader) -> GpResult<Direction> {
+        Ok(Direction {
+            position: r.byte()?,
+            symbols: r.values(|r| r.short())?,
+        })
+    }
+}
+
+#[derive(Debug)]
+struct ChordText {
+    position: u8,
+    name: ChordName,
+}
+
+impl ChordText {
+    fn read(r: &mut PtbReader) -> GpResult<ChordText> {
+        Ok(ChordText {
+            position: r.byte()?,
+            name: ChordName::read(r)?,
+        })
+    }
+}
+
+#[derive(Debug)]
+struct RhythmSlash {
+    position: u8,
+    data: u32,
+}
+
+impl RhythmSlash {
+    fn read(r: &mut PtbReader) -> GpResult<RhythmSlash> {
+        let position = r.byte()?;
+        r.byte()?; // beaming
+        Ok(RhythmSlash {
+            position,
+            data: r.int()?,
+        })
+    }
+}
+
+#[derive(Debug)]
+struct PtbNote {
+    /// String from the highest one, starting at 0
+    string: u8,
+    fret: u8,
+    data: u16,
+    symbols: Vec<u32>,
+}
+
+impl PtbNote {
+    fn read(r: &mut PtbReader) -> GpResult<PtbNote> {
+        let string_data = r.byte()?;
+        Ok(PtbNote {
+            string: string_data >> 5,
+            fret: string_data & 0x1f,
+            data: r.short()?,
+            symbols: r.values(|r| r.int())?,
+        })
+    }
+}
+
+#[derive(Debug)]
+struct Position {
+    position: u8,
+    beaming: u16,
+    /// Duration in the high byte, flags in the others
+    data: u32,
+    notes: Vec<PtbNote>,
+}
+
+impl Position {
+    fn read(r: &mut PtbReader) -> GpResult<Position> {
+        let (position, beaming, data) = (r.byte()?, r.short()?, r.int()?);
+        r.values(|r| r.int())?; // multi-bar rests
+        Ok(Position {
+            position,
+            beaming,
+            data,
+            notes: r.objects(PtbNote::read)?,
+        })
+    }
+
+    fn has(&self, flag: u32) -> bool {
+        self.data & flag != 0
+    }
+}
+
+#[derive(Debug)]
+struct Staff {
+    /// Clef in the high nibble: treble (0) or bass (1)
+    data: u8,
+    /// High and low melody
+    voices: [Vec<Position>; 2],
+}
+
+impl Staff {
+    fn read(r: &mut PtbReader) -> GpResult<Staff> {
+        let data = r.byte()?;
+        r.bytes(4)?; // spacing
+        Ok(Staff {
+            data,
+            voices: [r.objects(Position::read)?, r.objects(Position::read)?],
+        })
+    }
+}
+
+#[derive(Debug)]
+struct System {
+    start_bar: Barline,
+    end_bar: u8,
+    directions: Vec<Direction>,
+    chord_texts: Vec<ChordText>,
+    rhythm_slashes: Vec<RhythmSlash>,
+    staves: Vec<Staff>,
+    barlines: Vec<Barline>,
+}
+
+impl System {
+    fn read(r: &mut PtbReader) -> GpResult<System> {
+        r.bytes(16)?; // rectangle
+        let end_bar = r.byte()?;
+        r.bytes(4)?; // spacing
+        Ok(System {
+            start_bar: Barline::read(r)?,
+            end_bar,
+            directions: r.objects(Direction::read)?,
+            chord_texts: r.objects(ChordText::read)?,
+            rhythm_slashes: r.objects(RhythmSlash::read)?,
+            staves: r.objects(Staff::read)?,
+            barlines: r.objects(Barline::read)?,
+        })
+    }
+}
+
+#[derive(Debug)]
+struct Score {
+    guitars: Vec<Guitar>,
+    diagrams: Vec<ChordDiagram>,
+    guitar_ins: Vec<GuitarIn>,
+    tempo_markers: Vec<SystemSymbol>,
+    dynamics: Vec<Dynamic>,
+    endings: Vec<SystemSymbol>,
+    systems: Vec<System>,
+}
+
+impl Score {
+    fn read(r: &mut PtbReader) -> GpResult<Score> {
+        let guitars = r.objects(Guitar::read)?;
+        let diagrams = r.objects(ChordDiagram::read)?;
+        r.objects(skip_floating_text)?;
+        Ok(Score {
+            guitars,
+            diagrams,
+            guitar_ins: r.objects(GuitarIn::read)?,
+            tempo_markers: r.objects(SystemSymbol::read_tempo)?,
+            dynamics: r.objects(Dynamic::read)?,
+            endings: r.objects(SystemSymbol::read)?,
+            systems: r.objects(System::read)?,
+        })
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Conversion
+// ---------------------------------------------------------------------------
+
+/// A measure of a system: the positions from the barline opening it to the one closing it.
+struct Bar<'a> {
+    system: usize,
+    start: u16,
+    end: u16,
+    open: &'a Barline,
+    /// Data of the closing barline
+    close: u8,
+}
+
+impl Bar<'_> {
+    fn contains(&self, system: u16, position: u8) -> bool {
+        usize::from(system) == self.system && (self.start..self.end).contains(&u16::from(position))
+    }
+}
+
+fn bars(score: &Score) -> Vec<Bar<'_>> {
+    let mut bars = Vec::new();
+    for (s_idx, system) in score.systems.iter().enumerate() {
+        let mut barlines: Vec<&Barline> =
+            system.barlines.iter().filter(|b| b.position > 0).collect();
+        barlines.sort_by_key(|b| b.position);
+        let (mut start, mut open) = (0, &system.start_bar);
+        for barline in barlines {
+            bars.push(Bar {
+                system: s_idx,
+                start,
+                end: barline.position.into(),
+                open,
+                close: barline.data,
+            });
+            (start, open) = (barline.position.into(), barline);
+        }
+        bars.push(Bar {
+            system: s_idx,
+            start,
+            end: u16::from(u8::MAX) + 1,
+            open,
+            close: system.end_bar,
+        });
+    }
+    bars
+}
+
+fn direction_sign(symbol: u16) -> Option<DirectionSign> {
+    Some(match symbol >> 8 {
+        0 => DirectionSign::Coda,
+        1 => DirectionSign::DoubleCoda,
+        2 => DirectionSign::Segno,
+        3 => DirectionSign::SegnoSegno,
+        4 => DirectionSign::Fine,
+        5 => DirectionSign::DaCapo,
+        6 => DirectionSign::DaSegno,
+        7 => DirectionSign::DaSegnoSegno,
+        8 => DirectionSign::DaCoda,
+        9 => DirectionSign::DaDoubleCoda,
+        10 => DirectionSign::DaCapoAlCoda,
+        11 => DirectionSign::DaCapoAlDoubleCoda,
+        12 => DirectionSign::DaSegnoAlCoda,
+        13 => DirectionSign::DaSegnoAlDoubleCoda,
+        14 => DirectionSign::DaSegnoSegnoAlCoda,
+        15 => DirectionSign::DaSegnoSegnoAlDoubleCoda,
+        16 => DirectionSign::DaCapoAlFine,
+        17 => DirectionSign::DaSegnoAlFine,
+        18 => DirectionSign::DaSegnoSegnoAlFine,
+        _ => return None,
+    })
+}
+
+/// Measure headers of the bars of a score, with the tempo of the first tempo marker.
+fn read_measure_headers(score: &Score, bars: &[Bar]) -> (Vec<MeasureHeader>, Option<i32>) {
+    let mut headers: Vec<MeasureHeader> = Vec::with_capacity(bars.len());
+    let mut song_tempo = None;
+    for (b_idx, bar) in bars.iter().enumerate() {
+        let mut header = MeasureHeader {
+            number: (b_idx + 1) as u16,
+            ..Default::default()
+        };
+        if let Some(previous) = headers.last() {
+            header.time_signature = previous.time_signature.clone();
+            header.key_signature = previous.key_signature.clone();
+        }
+        let open = bar.open;
+        if open.time & 0x100000 != 0 || b_idx == 0 {
+            header.time_signature.numerator = ((open.time >> 27) + 1) as i8;
+            header.time_signature.denominator.value = 1 << ((open.time >> 24) & 0x7);
+        }
+        if open.key & 0x80 != 0 || b_idx == 0 {
+            let accidentals = (open.key & 0xf) as i8;
+            header.key_signature.key = if accidentals > 7 {
+                7 - accidentals
+            } else {
+                accidentals
+            };
+            header.key_signature.is_minor = open.key & 0x40 != 0;
+        }
+        header.repeat_open = open.kind() == BAR_REPEAT_START;
+        match bar.close >> 5 {
+            BAR_REPEAT_END => header.repeat_close = ((bar.close & 0x1f) as i8).max(2),
+            BAR_DOUBLE | BAR_DOUBLE_FINE => header.double_bar = true,
+            _ => {}
+        }
+        header.marker = open.rehearsal_sign.as_ref().map(|title| Marker {
+            title: title.clone(),
+            ..Default::default()
+        });
+        let system = &score.systems[bar.system];
+        header.direction = system
+            .directions
+            .iter()
+            .filter(|d| bar.contains(bar.system as u16, d.position))
+            .flat_map(|d| d.symbols.iter())
+            .find_map(|s| direction_sign(*s));
+        if let Some(tempo) = score
+            .tempo_markers
+            .iter()
+            .filter(|t| bar.contains(t.system, t.position))
+            .map(|t| (t.data & 0xffff) as i32)
+            .find(|t| *t > 0)
+        {
+            if song_tempo.is_none() {
+                song_tempo = Some(tempo);
+            }
+            header.tempo = tempo;
+        }
+        headers.push(header);
+    }
+
+    // An ending lasts until the repeat closing it, or a single measure when no repeat follows
+    let ending_starts: Vec<(usize, u8)> = score
+        .endings
+        .iter()
+        .filter_map(|e| {
+            let b_idx = bars.iter().position(|b| b.contains(e.system, e.position))?;
+            Some((b_idx, e.data as u8))
+        })
+        .collect();
+    for (b_idx, endings) in &ending_starts {
+        let mut until = *b_idx;
+        for (m_idx, header) in headers.iter().enumerate().skip(*b_idx) {
+            if m_idx > *b_idx && (header.repeat_open || ending_starts.iter().any(|e| e.0 == m_idx))
+            {
+                break;
+            }
+            if header.repeat_close > 0 {
+                until = m_idx;
+                break;
+            }
+        }
+        for header in &mut headers[*b_idx..=until] {
+            header.repeat_alternative = *endings;
+        }
+    }
+    (headers, song_tempo)
+}
+
+fn slash_duration(data: u32) -> Duration {
+    Duration {
+        value: 1 << ((data >> 21) & 0x7),
+        dotted: data & SLASH_DOTTED != 0,
+        double_dotted: data & SLASH_DOUBLE_DOTTED != 0,
+        ..Default::default()
+    }
+}
+
+/// Bend of a bend symbol: its type, the bent and the release pitches, in quarter tones.
+fn read_bend(symbol: u32) -> Option<BendEffect> {
+    let (bent, release) = (((symbol >> 4) & 0xf) as i8, (symbol & 0xf) as i8);
+    let values = match (symbol >> 20) & 0xf {
+        // Bend and release
+        1 => vec![0, bent, release],
+        // Bend and hold
+        2 => vec![0, bent, bent],
+        // Pre-bend
+        3 => vec![bent, bent],
+        // Pre-bend and release, gradual and immediate releases
+        4..=6 => vec![bent, release],
+        _ => vec![0, bent],
+    };
+    bend_from_values(values)
+}
+
+/// Reader of the tracks of a score: the guitars of the score on the bars of the score.
+struct ScoreReader<'a> {
+    score: &'a Score,
+    bars: Vec<Bar<'a>>,
+    /// Guitars of every staff and guitars of the rhythm slashes, by bar
+    guitars: Vec<(Vec<u8>, u8)>,
+}
+
+impl<'a> ScoreReader<'a> {
+    fn new(score: &'a Score) -> Self {
+        let bars = bars(score);
+        // Without guitar assignments, a staff holds the guitar of the same number
+        let mut staff_guitars: Vec<u8> = if score.guitar_ins.is_empty() {
+            (0..8).map(|i| 1 << i).collect()
+        } else {
+            vec![0; 8]
+        };
+        let mut slash_guitars = 0;
+        let mut guitars = Vec::with_capacity(bars.len());
+        for bar in &bars {
+            for guitar_in in score
+                .guitar_ins
+                .iter()
+                .filter(|g| bar.contains(g.system, g.position))
+            {
+                if let Some(staff) = staff_guitars.get_mut(usize::from(guitar_in.staff)) {
+                    *staff = guitar_in.staff_guitars;
+                }
+                slash_guitars = guitar_in.slash_guitars;
+            }
+            guitars.push((staff_guitars.clone(), slash_guitars));
+        }
+        ScoreReader {
+            score,
+            bars,
+            guitars,
+        }
+    }
+
+    /// Velocity of a staff, or of the rhythm slashes, at a position.
+    fn velocity(&self, system: usize, staff: Option<usize>, position: u8) -> i16 {
+        self.score
+            .dynamics
+            .iter()
+            .filter_map(|d| {
+                let volume = match staff {
+                    Some(staff) if usize::from(d.staff) != staff => return None,
+                    Some(_) => d.volume as u8,
+                    None => (d.volume >> 8) as u8,
+                };
+                ((usize::from(d.system), d.position) <= (system, position)
+                    && volume != 0xff
+                    && volume > 0)
+                    .then_some((d.system, d.position, volume))
+            })
+            .max_by_key(|d| (d.0, d.1))
+            .map_or(FORTE, |d| unpack_velocity(i16::from(d.2 / 13).clamp(1, 8)))
+    }
+
+    fn diagram(&self, name: &ChordName) -> Option<&'a ChordDiagram> {
+        self.score.diagrams.iter().find(|d| d.name == *name)
+    }
+
+    fn read_measures(
+        &self,
+        g_idx: usize,
+        track: &Track,
+        headers: &[MeasureHeader],
+    ) -> Vec<Measure> {
+        let string_count = track.strings.len();
+        let mut chord: Option<&ChordName> = None;
+        let mut measures = Vec::with_capacity(headers.len());
+        for (m_idx, header) in headers.iter().enumerate() {
+            let mut voices = vec![Voice::default(), Voice::default()];
+            let mut clef = MeasureClef::Treble;
+            if let (Some(bar), Some((staff_guitars, slash_guitars))) =
+                (self.bars.get(m_idx), self.guitars.get(m_idx))
+            {
+                let system = &self.score.systems[bar.system];
+                let chord_texts: Vec<&ChordText> = system
+                    .chord_texts
+                    .iter()
+                    .filter(|c| bar.contains(bar.system as u16, c.position))
+                    .collect();
+                let staff = staff_guitars
+                    .iter()
+                    .position(|g| g & (1 << g_idx) != 0)
+                    .filter(|s| *s < system.staves.len());
+                if let Some(s_idx) = staff {
+                    let staff = &system.staves[s_idx];
+                    if staff.data >> 4 == 1 {
+                        clef = MeasureClef::Bass;
+                    }
+                    for (v_idx, positions) in staff.voices.iter().enumerate() {
+                        let (voice, sources) = self.read_voice(bar, s_idx, positions, string_count);
+                        voices[v_idx] = voice;
+                        if v_idx == 0 {
+                            // A chord name goes to the first beat from its position on
+                            for text in &chord_texts {
+                                if let Some(b_idx) =
+                                    sources.iter().position(|p| *p >= text.position)
+                                {
+                                    voices[0].beats[b_idx].effect.chord = Some(
+                                        text.name.chord(self.diagram(&text.name), string_count),
+                                    );
+                                }
+                            }
+                        }
+                    }
+                } else if slash_guitars & (1 << g_idx) != 0 {
+                    voices[0] = self.read_slashes(bar, &chord_texts, &mut chord, string_count);
+                }
+                if let Some(text) = chord_texts.iter().max_by_key(|c| c.position) {
+                    chord = Some(&text.name);
+                }
+            }
+            for (v_idx, voice) in voices.iter_mut().enumerate() {
+                voice.measure_index = m_idx as i16;
+                if voice.beats.is_empty() {
+                    // An empty first voice holds a rest for the whole measure
+                    voice.beats.push(if v_idx == 0 {
+                        Beat {
+                            status: BeatStatus::Rest,
+                            duration: duration_from_ticks(header.length()),
+                            ..Default::default()
+                        }
+                    } else {
+                        Beat {
+                            status: BeatStatus::Empty,
+                            ..Default::default()
+                        }
+                    });
+                }
+            }
+            measures.push(Measure {
+                number: m_idx + 1,
+                track_index: (track.number - 1) as usize,
+                header_index: m_idx,
+                key_signature: header.key_signature.clone(),
+                time_signature: header.time_signature.clone(),
+                clef,
+                voices,
+                ..Default::default()
+            });
+        }
+        measures
+    }
+
+    /// Beats of the positions of a bar in a voice of a staff, with the position of every beat.
+    fn read_voice(
+        &self,
+        bar: &Bar,
+        s_idx: usize,
+        positions: &[Position],
+        string_count: usize,
+    ) -> (Voice, Vec<u8>) {
+        let mut positions: Vec<&Position> = positions
+            .iter()
+            .filter(|p| bar.contains(bar.system as u16, p.position))
+            .collect();
+        positions.sort_by_key(|p| p.position);
+        let mut voice = Voice::default();
+        let mut sources = Vec::with_capacity(positions.len());
+        let mut tuplet: Option<(u8, u8)> = None;
+        let mut grace: Option<&Position> = None;
+        for position in positions {
+            if position.has(POSITION_ACCIACCATURA) {
+                grace = Some(position);
+                continue;
+            }
+            if position.has(POSITION_GROUP_START) {
+                let played = ((position.beaming >> 5) & 0x1f) as u8;
+                let over = (position.beaming & 0x1f) as u8;
+                tuplet = (played > 0 && over > 0 && played != over).then_some((played, over));
+            }
+            let mut duration = Duration {
+                value: (position.data >> 24).clamp(1, 64) as u16,
+                dotted: position.has(POSITION_DOTTED),
+                double_dotted: position.has(POSITION_DOUBLE_DOTTED),
+                ..Default::default()
+            };
+            if let Some((enters, times)) = tuplet {
+                duration.tuplet_enters = enters;
+                duration.tuplet_times = times;
+            }
+            if position.has(POSITION_GROUP_END) {
+                tuplet = None;
+            }
+            let velocity = self.velocity(bar.system, Some(s_idx), position.position);
+            let beat = self.read_beat(position, duration, velocity, grace.take(), string_count);
+            voice.beats.push(beat);
+            sources.push(position.position);
+        }
+        (voice, sources)
+    }
+
+    fn read_beat(
+        &self,
+        position: &Position,
+        duration: Duration,
+        velocity: i16,
+        grace: Option<&Position>,
+        string_count: usize,
+    ) -> Beat {
+        let mut beat = Beat {
+            status: BeatStatus::Rest,
+            duration,
+            ..Default::default()
+        };
+        if position.has(POSITION_REST) {
+            return beat;
+        }
+        if position.has(POSITION_ARPEGGIO_UP) || position.has(POSITION_ARPEGGIO_DOWN) {
+            beat.effect.stroke.direction = if position.has(POSITION_ARPEGGIO_DOWN) {
+                BeatStrokeDirection::Down
+            } else {
+                BeatStrokeDirection::Up
+            };
+            beat.effect.stroke.value = DURATION_EIGHTH as u16;
+        }
+        if position.has(POSITION_PICK_UP) {
+            beat.effect.pick_stroke = BeatStrokeDirection::Up;
+        } else if position.has(POSITION_PICK_DOWN) {
+            beat.effect.pick_stroke = BeatStrokeDirection::Down;
+        }
+        if position.has(POSITION_TAP) {
+            beat.effect.slap_effect = SlapEffect::Tapping;
+        }
+        for ptb_note in &position.notes {
+            if usize::from(ptb_note.string) >= string_count {
+                continue;
+            }
+            let mut note = Note {
+                value: ptb_note.fret.into(),
+                string: (ptb_note.string + 1) as i8,
+                velocity,
+                kind: if ptb_note.data & NOTE_TIED != 0 {
+                    NoteType::Tie
+                } else if ptb_note.data & NOTE_MUTED != 0 {
+                    NoteType::Dead
+                } else {
+                    NoteType::Normal
+                },
+                ..Default::default()
+            };
+            let effect = &mut note.effect;
+            effect.hammer = ptb_note.data & (NOTE_HAMMER_ON | NOTE_PULL_OFF) != 0;
+            effect.ghost_note = ptb_note.data & NOTE_GHOST != 0;
+            if ptb_note.data & NOTE_NATURAL_HARMONIC != 0 {
+                effect.harmonic = Some(HarmonicEffect {
+                    kind: HarmonicType::Natural,
+                    ..Default::default()
+                });
+            }
+            effect.vibrato = position.has(POSITION_VIBRATO | POSITION_WIDE_VIBRATO);
+            effect.staccato = position.has(POSITION_STACCATO);
+            effect.heavy_accentuated_note = position.has(POSITION_MARCATO);
+            effect.accentuated_note = position.has(POSITION_SFORZANDO);
+            effect.palm_mute = position.has(POSITION_PALM_MUTE);
+            effect.let_ring = position.has(POSITION_LET_RING);
+            if position.has(POSITION_TREMOLO_PICKING) {
+                effect.tremolo_picking = Some(TremoloPickingEffect {
+                    duration: Duration {
+                        value: DURATION_SIXTEENTH.into(),
+                        ..Default::default()
+                    },
+                });
+            }
+            for symbol in &ptb_note.symbols {
+                let value = ((symbol >> 16) & 0xff) as u8;
+                match symbol >> 24 {
+                    SYMBOL_SLIDE => {
+                        match value {
+                            1 => effect.slides.push(SlideType::IntoFromBelow),
+                            2 => effect.slides.push(SlideType::IntoFromAbove),
+                            _ => {}
+                        }
+                        match (symbol >> 8) & 0xff {
+                            1 => effect.slides.push(SlideType::ShiftSlideTo),
+                            2 => effect.slides.push(SlideType::LegatoSlideTo),
+                            3 => effect.slides.push(SlideType::OutDownwards),
+                            4 => effect.slides.push(SlideType::OutUpWards),
+                            _ => {}
+                        }
+                    }
+                    SYMBOL_BEND => effect.bend = read_bend(*symbol),
+                    SYMBOL_TAPPED_HARMONIC => {
+                        effect.harmonic = Some(HarmonicEffect {
+                            kind: HarmonicType::Tapped,
+                            fret: Some(value as i8),
+                            ..Default::default()
+                        })
+                    }
+                    SYMBOL_ARTIFICIAL_HARMONIC => {
+                        effect.harmonic = Some(HarmonicEffect {
+                            kind: HarmonicType::Artificial,
+                            ..Default::default()
+                        })
+                    }
+                    SYMBOL_TRILL => {
+                        effect.trill = Some(TrillEffect {
+                            fret: value as i8,
+                            duration: Duration {
+                                value: DURATION_SIXTEENTH.into(),
+                                ..Default::default()
+                            },
+                        })
+                    }
+                    _ => {}
+                }
+            }
+            // The grace note of the string, or else the first one
+            if let Some(grace) = grace.and_then(|g| {
+                g.notes
+                    .iter()
+                    .find(|n| n.string == ptb_note.string)
+                    .or(g.notes.first())
+            }) {
+                if beat.notes.is_empty() {
+                    note.effect.grace = Some(GraceEffect {
+                        fret: grace.fret as i8,
+                        duration: DURATION_THIRTY_SECOND,
+                        is_dead: grace.data & NOTE_MUTED != 0,
+                        velocity,
+                        ..Default::default()
+                    });
+                }
+            }
+            beat.notes.push(note);
+        }
+        if !beat.notes.is_empty() {
+            beat.status = BeatStatus::Normal;
+        }
+        beat
+    }
+
+    /// Beats of the rhythm slashes of a bar, strumming the frets of the current chord diagram.
+    fn read_slashes(
+        &self,
+        bar: &Bar,
+        chord_texts: &[&'a ChordText],
+        chord: &mut Option<&'a ChordName>,
+        string_count: usize,
+    ) -> Voice {
+        let system = &self.score.systems[bar.system];
+        let mut slashes: Vec<&RhythmSlash> = system
+            .rhythm_slashes
+            .iter()
+            .filter(|s| bar.contains(bar.system as u16, s.position))
+            .collect();
+        slashes.sort_by_key(|s| s.position);
+        let mut voice = Voice::default();
+        for slash in slashes {
+            let mut beat = Beat {
+                status: BeatStatus::Rest,
+                duration: slash_duration(slash.data),
+                ..Default::default()
+            };
+            if let Some(text) = chord_texts
+                .iter()
+                .filter(|c| c.position <= slash.position)
+                .max_by_key(|c| c.position)
+            {
+                if *chord != Some(&text.name) {
+                    *chord = Some(&text.name);
+                    beat.effect.chord =
+                        Some(text.name.chord(self.diagram(&text.name), string_count));
+                }
+            }
+            let diagram = chord.and_then(|c| self.diagram(c));
+            if let (Some(diagram), false) = (diagram, slash.data & SLASH_REST != 0) {
+                let velocity = self.velocity(bar.system, None, slash.position);
+                let kind = if slash.data & SLASH_TIED != 0 {
+                    NoteType::Tie
+                } else if slash.data & SLASH_MUTED != 0 {
+                    NoteType::Dead
+                } else {
+                    NoteType::Normal
+                };
+                for (s_idx, fret) in diagram.frets.iter().take(string_count).enumerate() {
+                    if *fret < 0xfe {
+                        beat.notes.push(Note {
+                            value: (*fret).into(),
+                            string: (s_idx + 1) as i8,
+                            velocity,
+                            kind: kind.clone(),
+                            ..Default::default()
+                        });
+                    }
+                }
+                if slash.data & SLASH_PICK_UP != 0 {
+                    beat.effect.pick_stroke = BeatStrokeDirection::Up;
+                } else if slash.data & SLASH_PICK_DOWN != 0 {
+                    beat.effect.pick_stroke = BeatStrokeDirection::Down;
+                }
+            }
+            if !beat.notes.is_empty() {
+                beat.status = BeatStatus::Normal;
+            }
+            voice.beats.push(beat);
+        }
+        voice
+    }
+}
+
+impl Song {
+    fn read_ptb_metadata(&mut self, info: SongInfo) {
+        self.name = info.title;
+        self.artist = info.artist;
+        self.album = info.album;
+        self.author = info.composer;
+        self.words = info.lyricist;
+        self.transcriber = info.transcriber;
+        self.copyright = info.copyright;
+        self.notice = info
+            .notes
+            .lines()
+            .map(str::trim_end)
+            .filter(|l| !l.is_empty())
+            .map(str::to_string)
+            .collect();
+        self.lyrics = Lyrics::default();
+        if !info.lyrics.trim().is_empty() {
+            self.lyrics.track_choice = 1;
+            self.lyrics
+                .lines
+                .push((0, 1, info.lyrics.trim().to_string()));
+        }
+        // Guitar Pro files always hold five lyrics lines
+        for line in self.lyrics.lines.len()..5 {
+            self.lyrics.lines.push((line as u8, 1, String::new()));
+        }
+    }
+}
+
+impl SongPowerTabOps for Song {
+    fn read_ptb(&mut self, data: &[u8]) -> GpResult<()> {
+        let mut r = PtbReader { data, seek: 0 };
+        if r.int()? != PTB_MARKER {
+            return Err(GpError::FormatError("not a Power Tab document".into()));
+        }
+        let version = r.short()?;
+        if version != PTB_VERSION_1_7 {
+            return Err(GpError::FormatError(format!(
+                "unsupported Power Tab file version {}, only Power Tab Editor 1.7 files are read",
+                version
+            )));
+        }
+        let info = match r.short()? {
+            PTB_FILE_SONG => SongInfo::read(&mut r)?,
+            _ => SongInfo::read_lesson(&mut r)?,
+        };
+        let scores = [Score::read(&mut r)?, Score::read(&mut r)?];
+        self.read_ptb_metadata(info);
+
+        let readers = scores.each_ref().map(ScoreReader::new);
+        let main = readers
+            .iter()
+            .find(|r| !r.bars.is_empty())
+            .ok_or_else(|| GpError::FormatError("Power Tab document without systems".into()))?;
+        let (headers, tempo) = read_measure_headers(main.score, &main.bars);
+        if let Some(tempo) = tempo {
+            self.tempo = tempo.clamp(1, i32::from(i16::MAX)) as i16;
+        }
+        self.key = headers[0].key_signature.clone();
+        self.measure_headers = headers;
+
+        // 64 channels as in Guitar Pro files, every guitar gets the next free one
+        self.channels = (0..64u8)
+            .map(|channel| MidiChannel {
+                channel,
+                effect_channel: channel,
+                ..Default::default()
+            })
+            .collect();
+        let mut free_channels = (0..16u8)
+            .filter(|c| *c != DEFAULT_PERCUSSION_CHANNEL)
+            .cycle();
+        self.tracks.clear();
+        for reader in &readers {
+            for (g_idx, guitar) in reader.score.guitars.iter().enumerate() {
+                let number = self.tracks.len() + 1;
+                let mut track = Track {
+                    number: number as i32,
+                    name: if guitar.description.is_empty() {
+                        format!("Track {}", number)
+                    } else {
+                        guitar.description.clone()
+                    },
+                    offset: guitar.capo.into(),
+                    strings: guitar
+                        .tuning
+                        .iter()
+                        .enumerate()
+                        .map(|(i, note)| ((i + 1) as i8, (*note).min(127) as i8))
+                        .collect(),
+                    fret_count: DEFAULT_FRET_COUNT,
+                    ..Default::default()
+                };
+                track.measures = reader.read_measures(g_idx, &track, &self.measure_headers);
+
+                let channel = free_channels.next().unwrap_or(0);
+                track.channel_index = usize::from(channel);
+                let midi = &mut self.channels[usize::from(channel)];
+                midi.set_instrument(guitar.preset.min(127).into());
+                midi.volume = guitar.volume.min(127) as i8;
+                midi.balance = guitar.pan.min(127) as i8;
+                midi.reverb = guitar.reverb.min(127) as i8;
+                midi.chorus = guitar.chorus.min(127) as i8;
+                midi.tremolo = guitar.tremolo.min(127) as i8;
+                midi.phaser = guitar.phaser.min(127) as i8;
+                self.tracks.push(track);
+            }
+        }
+        if self.tracks.is_empty() {
+            return Err(GpError::FormatError(
+                "Power Tab document without guitars".into(),
+            ));
+        }
+        Ok(())
+    }
+}
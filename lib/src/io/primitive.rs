@@ -270,31 +270,39 @@ pub(crate) fn write_color(data: &mut Vec<u8>, value: i32) {
     write_byte(data, b);
     write_placeholder_default(data, 1);
 }
-pub(crate) fn write_byte_size_string(data: &mut Vec<u8>, value: &str) {
-    // Truncate to 255 if longer (max u8)
-    let count = value.chars().count().min(255) as u8;
-    write_byte(data, count);
-    data.extend(value.as_bytes().iter().take(count as usize));
+/// Encode a string the way `read_string` decodes it, in Windows-1252.
+fn encode_string(value: &str) -> Vec<u8> {
+    WINDOWS_1252.encode(value).0.into_owned()
+}
+
+/// Write length of the string stored in 1 byte followed by character bytes, padded with blank bytes to `size`.
+/// The string is truncated to `size` bytes.
+pub(crate) fn write_byte_size_string(data: &mut Vec<u8>, value: &str, size: usize) {
+    let bytes = encode_string(value);
+    let count = bytes.len().min(size).min(255);
+    write_byte(data, count as u8);
+    data.extend(&bytes[..count]);
+    write_placeholder_default(data, size - count);
 }
 pub(crate) fn write_int_size_string(data: &mut Vec<u8>, value: &str) {
-    let count = value.chars().count() as i32;
-    write_i32(data, count + 1);
-    data.extend(value.as_bytes());
+    let bytes = encode_string(value);
+    write_i32(data, bytes.len() as i32);
+    data.extend(bytes);
 }
 
 pub(crate) fn write_int_byte_size_string(data: &mut Vec<u8>, value: &str) {
-    let count = value.chars().count();
-    write_i32(data, count as i32 + 1);
+    let bytes = encode_string(value);
     // Truncate byte length to 255 if longer
-    write_byte(data, count.min(255) as u8);
-    data.extend(value.as_bytes());
+    let count = bytes.len().min(255);
+    write_i32(data, count as i32 + 1);
+    write_byte(data, count as u8);
+    data.extend(&bytes[..count]);
 }
 
 pub(crate) fn write_version(data: &mut Vec<u8>, version: (u8, u8, u8)) {
     for v in VERSIONS {
         if version == v.0 {
-            write_byte_size_string(data, v.2);
-            write_placeholder_default(data, 30 - v.2.len());
+            write_byte_size_string(data, v.2, 30);
             break;
         }
     }
@@ -341,7 +349,7 @@ mod test {
     #[test]
     fn test_write_byte_size_string() {
         let mut out: Vec<u8> = Vec::with_capacity(32);
-        write_byte_size_string(&mut out, "FICHIER GUITAR PRO v3.00");
+        write_byte_size_string(&mut out, "FICHIER GUITAR PRO v3.00", 24);
         let expected_result: Vec<u8> = vec![
             0x18, 0x46, 0x49, 0x43, 0x48, 0x49, 0x45, 0x52, 0x20, 0x47, 0x55, 0x49, 0x54, 0x41,
             0x52, 0x20, 0x50, 0x52, 0x4f, 0x20, 0x76, 0x33, 0x2e, 0x30, 0x30,
@@ -352,9 +360,9 @@ mod test {
     fn test_write_int_size_string() {
         let mut out: Vec<u8> = Vec::with_capacity(16);
         write_int_size_string(&mut out, "%ARTIST%");
-        // int_size_string = int(length+1), then string bytes (no byte length)
+        // int_size_string = int(length), then string bytes (no byte length), as read by `read_int_size_string`
         let expected_result: Vec<u8> = vec![
-            0x09, 0x00, 0x00, 0x00, 0x25, 0x41, 0x52, 0x54, 0x49, 0x53, 0x54, 0x25,
+            0x08, 0x00, 0x00, 0x00, 0x25, 0x41, 0x52, 0x54, 0x49, 0x53, 0x54, 0x25,
         ];
         assert_eq!(out, expected_result);
     }
@@ -0,0 +1,115 @@
+//! TuxGuitar format (`.tg` files), shared by the reader and the writer.
+//!
+//! A TuxGuitar document is a big-endian stream: the name of the format with its version, the
+//! metadata of the song, its MIDI channels, the measure headers and the tracks. Strings are
+//! written as UTF-16 code units after their length. Every element starts with flags announcing
+//! its optional parts, and the beats of a measure carry the duration and the velocity of the
+//! previous beat of their voice, so that they are only written when they change.
+use crate::model::enums::MeasureClef;
+
+/// Header of the files of TuxGuitar 1.x.
+pub(crate) const TG_FORMAT: &str = "TuxGuitar File Format - 1.2";
+/// Bank of the percussion channels.
+pub(crate) const TG_PERCUSSION_BANK: u8 = 128;
+/// Offset of the tracks is written from -24 semitones.
+pub(crate) const TG_MIN_OFFSET: i32 = -24;
+/// Voices of a beat.
+pub(crate) const TG_MAX_VOICES: usize = 2;
+
+pub(crate) const TRACK_SOLO: u8 = 0x01;
+pub(crate) const TRACK_MUTE: u8 = 0x02;
+pub(crate) const TRACK_LYRICS: u8 = 0x04;
+
+pub(crate) const MEASURE_HEADER_TIME_SIGNATURE: u8 = 0x01;
+pub(crate) const MEASURE_HEADER_TEMPO: u8 = 0x02;
+pub(crate) const MEASURE_HEADER_REPEAT_OPEN: u8 = 0x04;
+pub(crate) const MEASURE_HEADER_REPEAT_CLOSE: u8 = 0x08;
+pub(crate) const MEASURE_HEADER_REPEAT_ALTERNATIVE: u8 = 0x10;
+pub(crate) const MEASURE_HEADER_MARKER: u8 = 0x20;
+pub(crate) const MEASURE_HEADER_TRIPLET_FEEL: u8 = 0x40;
+
+pub(crate) const MEASURE_CLEF: u8 = 0x01;
+pub(crate) const MEASURE_KEY_SIGNATURE: u8 = 0x02;
+
+pub(crate) const BEAT_HAS_NEXT: u8 = 0x01;
+pub(crate) const BEAT_HAS_STROKE: u8 = 0x02;
+pub(crate) const BEAT_HAS_CHORD: u8 = 0x04;
+pub(crate) const BEAT_HAS_TEXT: u8 = 0x08;
+/// Voice flags of the first voice, shifted by 2 bits for the second one.
+pub(crate) const BEAT_HAS_VOICE: u8 = 0x10;
+pub(crate) const BEAT_HAS_VOICE_CHANGES: u8 = 0x20;
+
+pub(crate) const VOICE_HAS_NOTES: u8 = 0x01;
+pub(crate) const VOICE_NEXT_DURATION: u8 = 0x02;
+pub(crate) const VOICE_DIRECTION_UP: u8 = 0x04;
+pub(crate) const VOICE_DIRECTION_DOWN: u8 = 0x08;
+
+pub(crate) const NOTE_HAS_NEXT: u8 = 0x01;
+pub(crate) const NOTE_TIED: u8 = 0x02;
+pub(crate) const NOTE_EFFECT: u8 = 0x04;
+pub(crate) const NOTE_VELOCITY: u8 = 0x08;
+
+pub(crate) const DURATION_DOTTED: u8 = 0x01;
+pub(crate) const DURATION_DOUBLE_DOTTED: u8 = 0x02;
+/// The duration has a tuplet, despite its name in TuxGuitar.
+pub(crate) const DURATION_NO_TUPLET: u8 = 0x04;
+
+pub(crate) const EFFECT_BEND: u32 = 0x000001;
+pub(crate) const EFFECT_TREMOLO_BAR: u32 = 0x000002;
+pub(crate) const EFFECT_HARMONIC: u32 = 0x000004;
+pub(crate) const EFFECT_GRACE: u32 = 0x000008;
+pub(crate) const EFFECT_TRILL: u32 = 0x000010;
+pub(crate) const EFFECT_TREMOLO_PICKING: u32 = 0x000020;
+pub(crate) const EFFECT_VIBRATO: u32 = 0x000040;
+pub(crate) const EFFECT_DEAD: u32 = 0x000080;
+pub(crate) const EFFECT_SLIDE: u32 = 0x000100;
+pub(crate) const EFFECT_HAMMER: u32 = 0x000200;
+pub(crate) const EFFECT_GHOST: u32 = 0x000400;
+pub(crate) const EFFECT_ACCENTUATED: u32 = 0x000800;
+pub(crate) const EFFECT_HEAVY_ACCENTUATED: u32 = 0x001000;
+pub(crate) const EFFECT_PALM_MUTE: u32 = 0x002000;
+pub(crate) const EFFECT_STACCATO: u32 = 0x004000;
+pub(crate) const EFFECT_TAPPING: u32 = 0x008000;
+pub(crate) const EFFECT_SLAPPING: u32 = 0x010000;
+pub(crate) const EFFECT_POPPING: u32 = 0x020000;
+pub(crate) const EFFECT_FADE_IN: u32 = 0x040000;
+pub(crate) const EFFECT_LET_RING: u32 = 0x080000;
+
+pub(crate) const GRACE_DEAD: u8 = 0x01;
+pub(crate) const GRACE_ON_BEAT: u8 = 0x02;
+
+/// Tremolo bar values are written from -12.
+pub(crate) const TREMOLO_BAR_MIN_VALUE: i8 = -12;
+
+/// Key signature of a measure: sharps from 1 to 7, then flats from 8 to 14.
+pub(crate) fn key_to_tg(key: i8) -> u8 {
+    match key {
+        k if k < 0 => 7 + k.unsigned_abs().min(7),
+        k => k.min(7) as u8,
+    }
+}
+
+pub(crate) fn key_from_tg(key: u8) -> i8 {
+    match key {
+        8..=14 => 7 - key as i8,
+        k => k.min(7) as i8,
+    }
+}
+
+pub(crate) fn clef_to_tg(clef: &MeasureClef) -> u8 {
+    match clef {
+        MeasureClef::Treble => 1,
+        MeasureClef::Bass => 2,
+        MeasureClef::Tenor => 3,
+        MeasureClef::Alto => 4,
+    }
+}
+
+pub(crate) fn clef_from_tg(clef: u8) -> MeasureClef {
+    match clef {
+        2 => MeasureClef::Bass,
+        3 => MeasureClef::Tenor,
+        4 => MeasureClef::Alto,
+        _ => MeasureClef::Treble,
+    }
+}
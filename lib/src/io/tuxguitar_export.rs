@@ -0,0 +1,598 @@
+//! TuxGuitar export (`.tg` files of TuxGuitar 1.x).
+//!
+//! Every track gets its own TuxGuitar channel, with the settings of its MIDI channel. The voices of
+//! a measure are merged into TuxGuitar beats by their start, beat effects of Guitar Pro (tremolo
+//! bar, slap, fade in) are written on every note of the beat. Tempo changes of the mix tables are
+//! moved to the start of their measure, and only the first line of the lyrics is kept.
+use std::collections::BTreeMap;
+
+use crate::error::{GpError, GpResult};
+use crate::io::musicxml_export::duration_ticks;
+use crate::io::tuxguitar::*;
+use crate::model::{
+    beat::{Beat, Voice},
+    effects::*,
+    enums::*,
+    headers::MeasureHeader,
+    key_signature::*,
+    measure::Measure,
+    note::Note,
+    song::Song,
+    track::Track,
+};
+
+pub trait SongTuxGuitarExportOps {
+    /// Write the song as a TuxGuitar document (`.tg`).
+    fn write_tg(&self) -> GpResult<Vec<u8>>;
+}
+
+#[derive(Default)]
+struct TgWriter {
+    data: Vec<u8>,
+}
+
+impl TgWriter {
+    fn byte(&mut self, value: u8) {
+        self.data.push(value);
+    }
+
+    fn signed_byte(&mut self, value: i8) {
+        self.data.push(value as u8);
+    }
+
+    fn short(&mut self, value: i16) {
+        self.data.extend(value.to_be_bytes());
+    }
+
+    fn int(&mut self, value: i32) {
+        self.data.extend(value.to_be_bytes());
+    }
+
+    fn units(&mut self, units: &[u16]) {
+        for unit in units {
+            self.short(*unit as i16);
+        }
+    }
+
+    /// String of at most 255 UTF-16 code units, the longer ones are cut.
+    fn byte_string(&mut self, text: &str) {
+        let units: Vec<u16> = text.encode_utf16().take(usize::from(u8::MAX)).collect();
+        self.byte(units.len() as u8);
+        self.units(&units);
+    }
+
+    fn int_string(&mut self, text: &str) {
+        let units: Vec<u16> = text.encode_utf16().collect();
+        self.int(units.len() as i32);
+        self.units(&units);
+    }
+
+    fn color(&mut self, color: i32) {
+        self.data
+            .extend([(color >> 16) as u8, (color >> 8) as u8, color as u8]);
+    }
+
+    fn duration(&mut self, duration: &Duration) {
+        let mut flags = 0;
+        if duration.dotted {
+            flags |= DURATION_DOTTED;
+        }
+        if duration.double_dotted {
+            flags |= DURATION_DOUBLE_DOTTED;
+        }
+        let tuplet = !duration.is_default_tuplet() && duration.tuplet_enters > 0;
+        if tuplet {
+            flags |= DURATION_NO_TUPLET;
+        }
+        self.byte(flags);
+        self.byte(duration.value.clamp(1, 64) as u8);
+        if tuplet {
+            self.byte(duration.tuplet_enters);
+            self.byte(duration.tuplet_times);
+        }
+    }
+}
+
+/// Duration, flags and velocity of the previous beat of a voice in a measure.
+struct VoiceState {
+    duration: Duration,
+    flags: u8,
+    velocity: i16,
+}
+
+impl Default for VoiceState {
+    fn default() -> Self {
+        VoiceState {
+            duration: Duration::default(),
+            flags: 0,
+            velocity: DEFAULT_VELOCITY,
+        }
+    }
+}
+
+fn same_duration(a: &Duration, b: &Duration) -> bool {
+    a.value == b.value
+        && a.dotted == b.dotted
+        && a.double_dotted == b.double_dotted
+        && a.tuplet_enters == b.tuplet_enters
+        && a.tuplet_times == b.tuplet_times
+}
+
+/// Beats of the voices of a measure by start, a voice made of empty beats has none.
+fn beats_by_start(measure: &Measure) -> BTreeMap<i64, [Option<&Beat>; TG_MAX_VOICES]> {
+    let mut beats: BTreeMap<i64, [Option<&Beat>; TG_MAX_VOICES]> = BTreeMap::new();
+    for (v_idx, voice) in measure.voices.iter().take(TG_MAX_VOICES).enumerate() {
+        if voice.beats.iter().all(|b| b.status == BeatStatus::Empty) {
+            continue;
+        }
+        let mut start = 0;
+        for beat in &voice.beats {
+            beats.entry(start).or_default()[v_idx] = Some(beat);
+            start += duration_ticks(&beat.duration);
+        }
+    }
+    beats
+}
+
+impl TgWriter {
+    fn write_measure_header(
+        &mut self,
+        header: &MeasureHeader,
+        previous: Option<&MeasureHeader>,
+        tempo: Option<i32>,
+    ) {
+        let time_signature = &header.time_signature;
+        let mut flags = 0;
+        if previous.is_none_or(|p| {
+            p.time_signature.numerator != time_signature.numerator
+                || !same_duration(&p.time_signature.denominator, &time_signature.denominator)
+        }) {
+            flags |= MEASURE_HEADER_TIME_SIGNATURE;
+        }
+        if tempo.is_some() {
+            flags |= MEASURE_HEADER_TEMPO;
+        }
+        if header.repeat_open {
+            flags |= MEASURE_HEADER_REPEAT_OPEN;
+        }
+        if header.repeat_close > 0 {
+            flags |= MEASURE_HEADER_REPEAT_CLOSE;
+        }
+        if header.repeat_alternative != 0 {
+            flags |= MEASURE_HEADER_REPEAT_ALTERNATIVE;
+        }
+        if header.marker.is_some() {
+            flags |= MEASURE_HEADER_MARKER;
+        }
+        let triplet_feel = match header.triplet_feel {
+            TripletFeel::None => 1,
+            TripletFeel::Eighth => 2,
+            TripletFeel::Sixteenth => 3,
+        };
+        let previous_feel = previous.map_or(TripletFeel::None, |p| p.triplet_feel.clone());
+        if header.triplet_feel != previous_feel {
+            flags |= MEASURE_HEADER_TRIPLET_FEEL;
+        }
+
+        self.byte(flags);
+        if flags & MEASURE_HEADER_TIME_SIGNATURE != 0 {
+            self.signed_byte(time_signature.numerator);
+            self.duration(&time_signature.denominator);
+        }
+        if let Some(tempo) = tempo {
+            self.short(tempo.clamp(1, i32::from(i16::MAX)) as i16);
+        }
+        if header.repeat_close > 0 {
+            self.short(header.repeat_close.into());
+        }
+        if header.repeat_alternative != 0 {
+            self.byte(header.repeat_alternative);
+        }
+        if let Some(marker) = &header.marker {
+            self.byte_string(&marker.title);
+            self.color(marker.color);
+        }
+        if flags & MEASURE_HEADER_TRIPLET_FEEL != 0 {
+            self.byte(triplet_feel);
+        }
+    }
+
+    fn write_track(&mut self, song: &Song, t_idx: usize, track: &Track) {
+        let lyrics = (usize::from(song.lyrics.track_choice) == t_idx + 1)
+            .then(|| song.lyrics.lines.iter().find(|l| !l.2.trim().is_empty()))
+            .flatten();
+        let mut flags = 0;
+        if track.solo {
+            flags |= TRACK_SOLO;
+        }
+        if track.mute {
+            flags |= TRACK_MUTE;
+        }
+        if lyrics.is_some() {
+            flags |= TRACK_LYRICS;
+        }
+        self.byte(flags);
+        self.byte_string(&track.name);
+        self.short((t_idx + 1) as i16);
+        let mut previous: Option<&Measure> = None;
+        for (m_idx, header) in song.measure_headers.iter().enumerate() {
+            match track.measures.get(m_idx) {
+                Some(measure) => {
+                    self.write_measure(measure, previous);
+                    previous = Some(measure);
+                }
+                // A measure missing from the track is a rest
+                None => {
+                    self.byte(0);
+                    self.byte(BEAT_HAS_VOICE | BEAT_HAS_VOICE_CHANGES);
+                    self.byte(VOICE_NEXT_DURATION);
+                    let duration = crate::io::notation::duration_from_ticks(header.length());
+                    self.duration(&duration);
+                }
+            }
+        }
+        self.byte(track.strings.len().min(usize::from(u8::MAX)) as u8);
+        for (_, tuning) in track.strings.iter().take(usize::from(u8::MAX)) {
+            self.signed_byte(*tuning);
+        }
+        self.byte((track.offset - TG_MIN_OFFSET).clamp(0, 255) as u8);
+        self.color(track.color);
+        if let Some((_, from, text)) = lyrics {
+            self.short((*from).min(i16::MAX as u16) as i16);
+            self.int_string(text);
+        }
+    }
+
+    fn write_measure(&mut self, measure: &Measure, previous: Option<&Measure>) {
+        let clef = clef_to_tg(&measure.clef);
+        let key = key_to_tg(measure.key_signature.key);
+        let mut flags = 0;
+        if clef != previous.map_or(1, |p| clef_to_tg(&p.clef)) {
+            flags |= MEASURE_CLEF;
+        }
+        if key != previous.map_or(0, |p| key_to_tg(p.key_signature.key)) {
+            flags |= MEASURE_KEY_SIGNATURE;
+        }
+        self.byte(flags);
+
+        let beats = beats_by_start(measure);
+        let mut states: [VoiceState; TG_MAX_VOICES] = Default::default();
+        if beats.is_empty() {
+            // A beat without voices
+            self.byte(0);
+        }
+        let count = beats.len();
+        for (b_idx, voices) in beats.values().enumerate() {
+            self.write_beat(voices, &measure.voices, &mut states, b_idx + 1 < count);
+        }
+        if flags & MEASURE_CLEF != 0 {
+            self.byte(clef);
+        }
+        if flags & MEASURE_KEY_SIGNATURE != 0 {
+            self.byte(key);
+        }
+    }
+
+    fn write_beat(
+        &mut self,
+        beats: &[Option<&Beat>; TG_MAX_VOICES],
+        voices: &[Voice],
+        states: &mut [VoiceState; TG_MAX_VOICES],
+        has_next: bool,
+    ) {
+        let mut flags = if has_next { BEAT_HAS_NEXT } else { 0 };
+        // Stroke, chord and text of the first voice with a beat
+        let first = beats.iter().flatten().next().copied();
+        let stroke = first.map(|b| &b.effect.stroke);
+        let stroke = stroke.filter(|s| s.direction != BeatStrokeDirection::None);
+        let chord = first.and_then(|b| b.effect.chord.as_ref());
+        let text = first.map(|b| b.text.as_str()).filter(|t| !t.is_empty());
+        if stroke.is_some() {
+            flags |= BEAT_HAS_STROKE;
+        }
+        if chord.is_some() {
+            flags |= BEAT_HAS_CHORD;
+        }
+        if text.is_some() {
+            flags |= BEAT_HAS_TEXT;
+        }
+        let mut voice_flags = [0u8; TG_MAX_VOICES];
+        for (v_idx, beat) in beats.iter().enumerate() {
+            let Some(beat) = beat else {
+                continue;
+            };
+            let shift = v_idx * 2;
+            flags |= BEAT_HAS_VOICE << shift;
+            let state = &mut states[v_idx];
+            if beat.status == BeatStatus::Normal && !beat.notes.is_empty() {
+                voice_flags[v_idx] |= VOICE_HAS_NOTES;
+            }
+            if !same_duration(&beat.duration, &state.duration) {
+                voice_flags[v_idx] |= VOICE_NEXT_DURATION;
+            }
+            match voices[v_idx].directions {
+                VoiceDirection::Up => voice_flags[v_idx] |= VOICE_DIRECTION_UP,
+                VoiceDirection::Down => voice_flags[v_idx] |= VOICE_DIRECTION_DOWN,
+                VoiceDirection::None => {}
+            }
+            if voice_flags[v_idx] != state.flags {
+                flags |= BEAT_HAS_VOICE_CHANGES << shift;
+            }
+        }
+
+        self.byte(flags);
+        for (v_idx, beat) in beats.iter().enumerate() {
+            let Some(beat) = beat else {
+                continue;
+            };
+            let state = &mut states[v_idx];
+            if flags & (BEAT_HAS_VOICE_CHANGES << (v_idx * 2)) != 0 {
+                state.flags = voice_flags[v_idx];
+                self.byte(state.flags);
+            }
+            if state.flags & VOICE_NEXT_DURATION != 0 {
+                self.duration(&beat.duration);
+                state.duration = beat.duration.clone();
+            }
+            if state.flags & VOICE_HAS_NOTES != 0 {
+                self.write_notes(beat, state);
+            }
+        }
+        if let Some(stroke) = stroke {
+            self.signed_byte(match stroke.direction {
+                BeatStrokeDirection::Up => 1,
+                BeatStrokeDirection::Down => -1,
+                BeatStrokeDirection::None => 0,
+            });
+            self.byte(stroke.value.min(u16::from(u8::MAX)) as u8);
+        }
+        if let Some(chord) = chord {
+            let string_count = chord.strings.len().min(usize::from(u8::MAX));
+            self.byte(string_count as u8);
+            self.byte_string(&chord.name);
+            self.byte(chord.first_fret.unwrap_or(0));
+            for fret in chord.strings.iter().take(string_count) {
+                self.signed_byte((*fret).max(-1));
+            }
+        }
+        if let Some(text) = text {
+            self.byte_string(text);
+        }
+    }
+
+    fn write_notes(&mut self, beat: &Beat, state: &mut VoiceState) {
+        let count = beat.notes.len();
+        for (n_idx, note) in beat.notes.iter().enumerate() {
+            let effects = note_effects(beat, note);
+            let mut flags = 0;
+            if n_idx + 1 < count {
+                flags |= NOTE_HAS_NEXT;
+            }
+            if note.kind == NoteType::Tie {
+                flags |= NOTE_TIED;
+            }
+            if effects != 0 {
+                flags |= NOTE_EFFECT;
+            }
+            if note.velocity != state.velocity {
+                flags |= NOTE_VELOCITY;
+                state.velocity = note.velocity;
+            }
+            self.byte(flags);
+            self.byte(note.value.clamp(0, 127) as u8);
+            self.signed_byte(note.string);
+            if flags & NOTE_VELOCITY != 0 {
+                self.byte(note.velocity.clamp(0, 127) as u8);
+            }
+            if effects != 0 {
+                self.write_note_effects(beat, note, effects);
+            }
+        }
+    }
+
+    fn write_points(&mut self, bend: &BendEffect, offset: i8) {
+        let count = bend.points.len().min(usize::from(u8::MAX));
+        self.byte(count as u8);
+        for point in bend.points.iter().take(count) {
+            self.byte(point.position);
+            self.byte(point.value.saturating_sub(offset) as u8);
+        }
+    }
+
+    fn write_note_effects(&mut self, beat: &Beat, note: &Note, flags: u32) {
+        self.data.extend(&flags.to_be_bytes()[1..]);
+        let effect = &note.effect;
+        if let Some(bend) = &effect.bend {
+            self.write_points(bend, 0);
+        }
+        if let Some(tremolo_bar) = &beat.effect.tremolo_bar {
+            self.write_points(tremolo_bar, TREMOLO_BAR_MIN_VALUE);
+        }
+        if let Some(harmonic) = &effect.harmonic {
+            let kind = match harmonic.kind {
+                HarmonicType::Natural => 1,
+                HarmonicType::Artificial => 2,
+                HarmonicType::Tapped => 3,
+                HarmonicType::Pinch => 4,
+                HarmonicType::Semi => 5,
+            };
+            self.byte(kind);
+            if kind != 1 {
+                self.byte(harmonic.fret.unwrap_or(0).max(0) as u8);
+            }
+        }
+        if let Some(grace) = &effect.grace {
+            let mut grace_flags = 0;
+            if grace.is_dead {
+                grace_flags |= GRACE_DEAD;
+            }
+            if grace.is_on_beat {
+                grace_flags |= GRACE_ON_BEAT;
+            }
+            self.byte(grace_flags);
+            self.signed_byte(grace.fret);
+            // 1 for a 64th note, 2 for a 32nd note and 3 for a 16th note
+            self.byte(grace.duration.max(1).leading_zeros().clamp(1, 3) as u8);
+            self.byte(grace.velocity.clamp(0, 127) as u8);
+            self.byte(match grace.transition {
+                GraceEffectTransition::None => 0,
+                GraceEffectTransition::Slide => 1,
+                GraceEffectTransition::Bend => 2,
+                GraceEffectTransition::Hammer => 3,
+            });
+        }
+        if let Some(trill) = &effect.trill {
+            self.signed_byte(trill.fret);
+            self.byte(trill.duration.value.clamp(1, 64) as u8);
+        }
+        if let Some(tremolo_picking) = &effect.tremolo_picking {
+            self.byte(tremolo_picking.duration.value.clamp(1, 64) as u8);
+        }
+    }
+}
+
+/// Effect flags of a note, with the ones of its beat.
+fn note_effects(beat: &Beat, note: &Note) -> u32 {
+    let effect = &note.effect;
+    let flagged = [
+        (effect.bend.is_some(), EFFECT_BEND),
+        (beat.effect.tremolo_bar.is_some(), EFFECT_TREMOLO_BAR),
+        (effect.harmonic.is_some(), EFFECT_HARMONIC),
+        (effect.grace.is_some(), EFFECT_GRACE),
+        (effect.trill.is_some(), EFFECT_TRILL),
+        (effect.tremolo_picking.is_some(), EFFECT_TREMOLO_PICKING),
+        (effect.vibrato || beat.effect.vibrato, EFFECT_VIBRATO),
+        (note.kind == NoteType::Dead, EFFECT_DEAD),
+        (
+            effect.slides.iter().any(|s| *s != SlideType::None),
+            EFFECT_SLIDE,
+        ),
+        (effect.hammer, EFFECT_HAMMER),
+        (effect.ghost_note, EFFECT_GHOST),
+        (effect.accentuated_note, EFFECT_ACCENTUATED),
+        (effect.heavy_accentuated_note, EFFECT_HEAVY_ACCENTUATED),
+        (effect.palm_mute, EFFECT_PALM_MUTE),
+        (effect.staccato, EFFECT_STACCATO),
+        (
+            beat.effect.slap_effect == SlapEffect::Tapping,
+            EFFECT_TAPPING,
+        ),
+        (
+            beat.effect.slap_effect == SlapEffect::Slapping,
+            EFFECT_SLAPPING,
+        ),
+        (
+            beat.effect.slap_effect == SlapEffect::Popping,
+            EFFECT_POPPING,
+        ),
+        (beat.effect.fade_in, EFFECT_FADE_IN),
+        (effect.let_ring, EFFECT_LET_RING),
+    ];
+    flagged
+        .iter()
+        .filter(|(set, _)| *set)
+        .fold(0, |flags, (_, flag)| flags | flag)
+}
+
+impl Song {
+    /// Tempo at the start of every measure when it changes: the tempo of the measure header, or
+    /// else the first tempo change of the mix tables of the measure.
+    fn tg_tempos(&self) -> Vec<Option<i32>> {
+        let mut tempo = 0;
+        self.measure_headers
+            .iter()
+            .enumerate()
+            .map(|(m_idx, header)| {
+                let change = self
+                    .tracks
+                    .iter()
+                    .filter_map(|t| t.measures.get(m_idx))
+                    .flat_map(|m| m.voices.iter().flat_map(|v| v.beats.iter()))
+                    .filter_map(|b| b.effect.mix_table_change.as_ref())
+                    .find_map(|m| m.tempo.as_ref().map(|t| i32::from(t.value)));
+                let measure_tempo = match (m_idx, header.tempo, change) {
+                    (_, t, _) if t > 0 => t,
+                    (_, _, Some(t)) if t > 0 => t,
+                    (0, _, _) => i32::from(self.tempo).max(1),
+                    _ => tempo,
+                };
+                (measure_tempo != tempo).then(|| {
+                    tempo = measure_tempo;
+                    tempo
+                })
+            })
+            .collect()
+    }
+}
+
+impl SongTuxGuitarExportOps for Song {
+    fn write_tg(&self) -> GpResult<Vec<u8>> {
+        if self.tracks.len() > usize::from(u8::MAX) {
+            return Err(GpError::WriteError(format!(
+                "TuxGuitar documents hold at most 255 tracks, the song has {}",
+                self.tracks.len()
+            )));
+        }
+        if self.measure_headers.len() > i16::MAX as usize {
+            return Err(GpError::WriteError(format!(
+                "TuxGuitar documents hold at most {} measures, the song has {}",
+                i16::MAX,
+                self.measure_headers.len()
+            )));
+        }
+        let mut w = TgWriter::default();
+        w.byte_string(TG_FORMAT);
+        for field in [
+            &self.name,
+            &self.artist,
+            &self.album,
+            &self.author,
+            &self.date,
+            &self.copyright,
+            &self.writer,
+            &self.transcriber,
+        ] {
+            w.byte_string(field);
+        }
+        w.int_string(&self.comments);
+
+        // A channel for every track, with the settings of its MIDI channel
+        w.byte(self.tracks.len() as u8);
+        for (t_idx, track) in self.tracks.iter().enumerate() {
+            let channel = self.channels.get(track.channel_index).cloned();
+            let channel = channel.unwrap_or_default();
+            w.short((t_idx + 1) as i16);
+            w.byte(if track.percussion_track {
+                TG_PERCUSSION_BANK
+            } else {
+                channel.bank
+            });
+            w.byte(channel.instrument.clamp(0, 127) as u8);
+            for value in [
+                channel.volume,
+                channel.balance,
+                channel.chorus,
+                channel.reverb,
+                channel.phaser,
+                channel.tremolo,
+            ] {
+                w.byte(value.max(0) as u8);
+            }
+            w.byte_string(&track.name);
+            w.short(0); // parameters
+        }
+
+        w.short(self.measure_headers.len() as i16);
+        let tempos = self.tg_tempos();
+        let mut previous = None;
+        for (header, tempo) in self.measure_headers.iter().zip(tempos) {
+            w.write_measure_header(header, previous, tempo);
+            previous = Some(header);
+        }
+        w.byte(self.tracks.len() as u8);
+        for (t_idx, track) in self.tracks.iter().enumerate() {
+            w.write_track(self, t_idx, track);
+        }
+        Ok(w.data)
+    }
+}
@@ -0,0 +1,650 @@
+//! TuxGuitar import (`.tg` files of TuxGuitar 1.x).
+//!
+//! The model follows the one of TuxGuitar, a track maps to a track with its measures, strings and
+//! MIDI channel. The voices of a TuxGuitar beat start together: they are split into the voices of
+//! the measure. Note effects that are beat effects in Guitar Pro (tremolo bar, slap, fade in) are
+//! moved to the beat.
+use crate::audio::midi::{MidiChannel, DEFAULT_PERCUSSION_CHANNEL};
+use crate::error::{GpError, GpResult};
+use crate::io::notation::{bend_type, duration_from_ticks};
+use crate::io::tuxguitar::*;
+use crate::model::{
+    beat::{Beat, Voice},
+    chord::Chord,
+    effects::*,
+    enums::*,
+    headers::{Marker, MeasureHeader},
+    key_signature::*,
+    lyric::Lyrics,
+    measure::Measure,
+    note::{Note, NoteEffect},
+    song::Song,
+    track::Track,
+};
+
+pub trait SongTuxGuitarOps {
+    /// Read a TuxGuitar document (`.tg`).
+    fn read_tg(&mut self, data: &[u8]) -> GpResult<()>;
+}
+
+struct TgReader<'a> {
+    data: &'a [u8],
+    seek: usize,
+}
+
+impl TgReader<'_> {
+    fn bytes<const N: usize>(&mut self) -> GpResult<[u8; N]> {
+        let bytes = self
+            .data
+            .get(self.seek..self.seek + N)
+            .ok_or(GpError::UnexpectedEof {
+                offset: self.seek,
+                needed: N,
+            })?;
+        self.seek += N;
+        Ok(bytes.try_into().unwrap())
+    }
+
+    fn byte(&mut self) -> GpResult<u8> {
+        Ok(self.bytes::<1>()?[0])
+    }
+
+    fn signed_byte(&mut self) -> GpResult<i8> {
+        Ok(self.byte()? as i8)
+    }
+
+    fn short(&mut self) -> GpResult<i16> {
+        Ok(i16::from_be_bytes(self.bytes()?))
+    }
+
+    fn int(&mut self) -> GpResult<i32> {
+        Ok(i32::from_be_bytes(self.bytes()?))
+    }
+
+    /// Flags written on several bytes, most significant first.
+    fn flags(&mut self, count: usize) -> GpResult<u32> {
+        let mut flags = 0;
+        for _ in 0..count {
+            flags = (flags << 8) | u32::from(self.byte()?);
+        }
+        Ok(flags)
+    }
+
+    fn string(&mut self, length: usize) -> GpResult<String> {
+        let offset = self.seek;
+        let units = (0..length)
+            .map(|_| self.short().map(|u| u as u16))
+            .collect::<GpResult<Vec<u16>>>()?;
+        String::from_utf16(&units).map_err(|_| GpError::StringDecode { offset })
+    }
+
+    fn byte_string(&mut self) -> GpResult<String> {
+        let length = usize::from(self.byte()?);
+        self.string(length)
+    }
+
+    fn int_string(&mut self) -> GpResult<String> {
+        let length = self.int()?;
+        if length < 0 {
+            return Err(GpError::InvalidValue {
+                context: "TuxGuitar string length",
+                value: i64::from(length),
+            });
+        }
+        self.string(length as usize)
+    }
+
+    fn color(&mut self) -> GpResult<i32> {
+        let [r, g, b] = self.bytes()?;
+        Ok(i32::from(r) << 16 | i32::from(g) << 8 | i32::from(b))
+    }
+
+    fn duration(&mut self) -> GpResult<Duration> {
+        let flags = self.byte()?;
+        let mut duration = Duration {
+            value: u16::from(self.byte()?.max(1)),
+            dotted: flags & DURATION_DOTTED != 0,
+            double_dotted: flags & DURATION_DOUBLE_DOTTED != 0,
+            ..Default::default()
+        };
+        if flags & DURATION_NO_TUPLET != 0 {
+            duration.tuplet_enters = self.byte()?;
+            duration.tuplet_times = self.byte()?;
+        }
+        Ok(duration)
+    }
+}
+
+/// MIDI channel of a TuxGuitar song.
+struct TgChannel {
+    id: i16,
+    bank: u8,
+    program: u8,
+    volume: u8,
+    balance: u8,
+    chorus: u8,
+    reverb: u8,
+    phaser: u8,
+    tremolo: u8,
+}
+
+/// Duration, flags and velocity of the previous beat of a voice in a measure.
+struct VoiceState {
+    duration: Duration,
+    flags: u8,
+    velocity: i16,
+}
+
+impl Default for VoiceState {
+    fn default() -> Self {
+        VoiceState {
+            duration: Duration::default(),
+            flags: 0,
+            velocity: DEFAULT_VELOCITY,
+        }
+    }
+}
+
+/// Effects of a note, with the ones of its beat.
+#[derive(Default)]
+struct TgNoteEffects {
+    note: NoteEffect,
+    dead: bool,
+    tremolo_bar: Option<BendEffect>,
+    slap: Option<SlapEffect>,
+    fade_in: bool,
+}
+
+impl TgReader<'_> {
+    fn read_channel(&mut self) -> GpResult<TgChannel> {
+        let id = self.short()?;
+        let mut values = [0u8; 8];
+        for value in &mut values {
+            *value = self.byte()?;
+        }
+        let [bank, program, volume, balance, chorus, reverb, phaser, tremolo] = values;
+        self.byte_string()?; // name
+        for _ in 0..self.short()? {
+            // Parameters of the synthesizer: key and value
+            self.byte_string()?;
+            self.int_string()?;
+        }
+        Ok(TgChannel {
+            id,
+            bank,
+            program,
+            volume,
+            balance,
+            chorus,
+            reverb,
+            phaser,
+            tremolo,
+        })
+    }
+
+    fn read_measure_header(
+        &mut self,
+        number: u16,
+        previous: Option<&MeasureHeader>,
+    ) -> GpResult<MeasureHeader> {
+        let flags = self.byte()?;
+        let mut header = MeasureHeader {
+            number,
+            ..Default::default()
+        };
+        if let Some(previous) = previous {
+            header.time_signature = previous.time_signature.clone();
+            header.triplet_feel = previous.triplet_feel.clone();
+        }
+        if flags & MEASURE_HEADER_TIME_SIGNATURE != 0 {
+            header.time_signature.numerator = self.signed_byte()?;
+            header.time_signature.denominator = self.duration()?;
+        }
+        if flags & MEASURE_HEADER_TEMPO != 0 {
+            header.tempo = self.short()?.into();
+        }
+        header.repeat_open = flags & MEASURE_HEADER_REPEAT_OPEN != 0;
+        if flags & MEASURE_HEADER_REPEAT_CLOSE != 0 {
+            header.repeat_close = self.short()?.clamp(0, i16::from(i8::MAX)) as i8;
+        }
+        if flags & MEASURE_HEADER_REPEAT_ALTERNATIVE != 0 {
+            header.repeat_alternative = self.byte()?;
+        }
+        if flags & MEASURE_HEADER_MARKER != 0 {
+            header.marker = Some(Marker {
+                title: self.byte_string()?,
+                color: self.color()?,
+            });
+        }
+        if flags & MEASURE_HEADER_TRIPLET_FEEL != 0 {
+            header.triplet_feel = match self.byte()? {
+                2 => TripletFeel::Eighth,
+                3 => TripletFeel::Sixteenth,
+                _ => TripletFeel::None,
+            };
+        }
+        Ok(header)
+    }
+
+    fn read_track(
+        &mut self,
+        number: usize,
+        headers: &[MeasureHeader],
+        lyrics: &mut Lyrics,
+    ) -> GpResult<(Track, i16)> {
+        let flags = self.byte()?;
+        let mut track = Track {
+            number: number as i32,
+            name: self.byte_string()?,
+            solo: flags & TRACK_SOLO != 0,
+            mute: flags & TRACK_MUTE != 0,
+            ..Default::default()
+        };
+        let channel = self.short()?;
+        let mut measures: Vec<Measure> = Vec::with_capacity(headers.len());
+        for (m_idx, header) in headers.iter().enumerate() {
+            let measure = self.read_measure(number, m_idx, header, measures.last())?;
+            measures.push(measure);
+        }
+        track.measures = measures;
+        let string_count = self.byte()?;
+        track.strings = (1..=string_count)
+            .map(|string| Ok((string as i8, self.signed_byte()?)))
+            .collect::<GpResult<_>>()?;
+        track.offset = TG_MIN_OFFSET + i32::from(self.byte()?);
+        track.color = self.color()?;
+        if flags & TRACK_LYRICS != 0 {
+            let from = self.short()?.max(1) as u16;
+            let text = self.int_string()?;
+            if lyrics.lines.is_empty() && !text.trim().is_empty() {
+                lyrics.track_choice = number as u8;
+                lyrics.lines.push((0, from, text));
+            }
+        }
+        Ok((track, channel))
+    }
+
+    fn read_measure(
+        &mut self,
+        number: usize,
+        m_idx: usize,
+        header: &MeasureHeader,
+        previous: Option<&Measure>,
+    ) -> GpResult<Measure> {
+        let flags = self.byte()?;
+        let mut voices = vec![Voice::default(); TG_MAX_VOICES];
+        let mut states: [VoiceState; TG_MAX_VOICES] = Default::default();
+        let mut beat_flags = BEAT_HAS_NEXT;
+        while beat_flags & BEAT_HAS_NEXT != 0 {
+            beat_flags = self.byte()?;
+            self.read_beat(beat_flags, &mut voices, &mut states)?;
+        }
+        let mut measure = Measure {
+            number: m_idx + 1,
+            track_index: number - 1,
+            header_index: m_idx,
+            time_signature: header.time_signature.clone(),
+            ..Default::default()
+        };
+        if let Some(previous) = previous {
+            measure.clef = previous.clef.clone();
+            measure.key_signature = previous.key_signature.clone();
+        }
+        if flags & MEASURE_CLEF != 0 {
+            measure.clef = clef_from_tg(self.byte()?);
+        }
+        if flags & MEASURE_KEY_SIGNATURE != 0 {
+            measure.key_signature.key = key_from_tg(self.byte()?);
+        }
+        for (v_idx, voice) in voices.iter_mut().enumerate() {
+            voice.measure_index = m_idx as i16;
+            if voice.beats.is_empty() {
+                // An empty first voice holds a rest for the whole measure
+                voice.beats.push(if v_idx == 0 {
+                    Beat {
+                        status: BeatStatus::Rest,
+                        duration: duration_from_ticks(header.length()),
+                        ..Default::default()
+                    }
+                } else {
+                    Beat {
+                        status: BeatStatus::Empty,
+                        ..Default::default()
+                    }
+                });
+            }
+        }
+        measure.voices = voices;
+        Ok(measure)
+    }
+
+    fn read_beat(
+        &mut self,
+        flags: u8,
+        voices: &mut [Voice],
+        states: &mut [VoiceState; TG_MAX_VOICES],
+    ) -> GpResult<()> {
+        let mut first = None;
+        for (v_idx, state) in states.iter_mut().enumerate() {
+            let shift = v_idx * 2;
+            if flags & (BEAT_HAS_VOICE << shift) == 0 {
+                continue;
+            }
+            if flags & (BEAT_HAS_VOICE_CHANGES << shift) != 0 {
+                state.flags = self.byte()?;
+            }
+            if state.flags & VOICE_NEXT_DURATION != 0 {
+                state.duration = self.duration()?;
+            }
+            let mut beat = Beat {
+                status: BeatStatus::Rest,
+                duration: state.duration.clone(),
+                ..Default::default()
+            };
+            if state.flags & VOICE_HAS_NOTES != 0 {
+                self.read_notes(&mut beat, state)?;
+            }
+            if state.flags & VOICE_DIRECTION_UP != 0 {
+                voices[v_idx].directions = VoiceDirection::Up;
+            } else if state.flags & VOICE_DIRECTION_DOWN != 0 {
+                voices[v_idx].directions = VoiceDirection::Down;
+            }
+            voices[v_idx].beats.push(beat);
+            first.get_or_insert(v_idx);
+        }
+        // Stroke, chord and text of the beat go to its first voice
+        let mut beat = Beat::default();
+        let beat = match first {
+            Some(v_idx) => voices[v_idx].beats.last_mut().unwrap(),
+            None => &mut beat,
+        };
+        if flags & BEAT_HAS_STROKE != 0 {
+            beat.effect.stroke.direction = match self.signed_byte()? {
+                1 => BeatStrokeDirection::Up,
+                -1 => BeatStrokeDirection::Down,
+                _ => BeatStrokeDirection::None,
+            };
+            beat.effect.stroke.value = self.byte()?.into();
+        }
+        if flags & BEAT_HAS_CHORD != 0 {
+            let string_count = self.byte()?;
+            let name = self.byte_string()?;
+            let first_fret = self.byte()?;
+            let strings = (0..string_count)
+                .map(|_| self.signed_byte().map(|f| f.max(-1)))
+                .collect::<GpResult<Vec<i8>>>()?;
+            beat.effect.chord = Some(Chord {
+                length: string_count,
+                name,
+                first_fret: Some(first_fret),
+                strings,
+                show: Some(true),
+                new_format: Some(false),
+                ..Default::default()
+            });
+        }
+        if flags & BEAT_HAS_TEXT != 0 {
+            beat.text = self.byte_string()?;
+        }
+        Ok(())
+    }
+
+    fn read_notes(&mut self, beat: &mut Beat, state: &mut VoiceState) -> GpResult<()> {
+        let mut flags = NOTE_HAS_NEXT;
+        while flags & NOTE_HAS_NEXT != 0 {
+            flags = self.byte()?;
+            let mut note = Note {
+                value: self.byte()?.into(),
+                string: self.signed_byte()?,
+                kind: if flags & NOTE_TIED != 0 {
+                    NoteType::Tie
+                } else {
+                    NoteType::Normal
+                },
+                ..Default::default()
+            };
+            if flags & NOTE_VELOCITY != 0 {
+                state.velocity = self.byte()?.into();
+            }
+            note.velocity = state.velocity;
+            if flags & NOTE_EFFECT != 0 {
+                let effects = self.read_note_effects()?;
+                note.effect = effects.note;
+                if effects.dead {
+                    note.kind = NoteType::Dead;
+                }
+                if beat.effect.tremolo_bar.is_none() {
+                    beat.effect.tremolo_bar = effects.tremolo_bar;
+                }
+                if let Some(slap) = effects.slap {
+                    beat.effect.slap_effect = slap;
+                }
+                beat.effect.fade_in |= effects.fade_in;
+            }
+            beat.notes.push(note);
+        }
+        beat.status = BeatStatus::Normal;
+        Ok(())
+    }
+
+    /// Points of a bend or a tremolo bar, as (position, value).
+    fn read_points(&mut self, offset: i8) -> GpResult<BendEffect> {
+        let count = self.byte()?;
+        let points = (0..count)
+            .map(|_| {
+                Ok(BendPoint {
+                    position: self.byte()?,
+                    value: (self.byte()? as i8).saturating_add(offset),
+                    vibrato: false,
+                })
+            })
+            .collect::<GpResult<Vec<BendPoint>>>()?;
+        let values: Vec<i8> = points.iter().map(|p| p.value).collect();
+        let max = values.iter().map(|v| v.saturating_abs()).max().unwrap_or(0);
+        Ok(BendEffect {
+            kind: bend_type(&values),
+            value: (f32::from(max) * GP_BEND_SEMITONE) as i16,
+            points,
+            ..Default::default()
+        })
+    }
+
+    fn read_note_effects(&mut self) -> GpResult<TgNoteEffects> {
+        let flags = self.flags(3)?;
+        let mut effects = TgNoteEffects::default();
+        let effect = &mut effects.note;
+        if flags & EFFECT_BEND != 0 {
+            effect.bend = Some(self.read_points(0)?);
+        }
+        if flags & EFFECT_TREMOLO_BAR != 0 {
+            let mut tremolo_bar = self.read_points(TREMOLO_BAR_MIN_VALUE)?;
+            let last = tremolo_bar.points.last().map_or(0, |p| p.value);
+            tremolo_bar.kind = match tremolo_bar.points.iter().map(|p| p.value).min() {
+                Some(min) if min < 0 && last == 0 => BendType::Dip,
+                Some(min) if min < 0 => BendType::Dive,
+                _ => BendType::ReleaseUp,
+            };
+            effects.tremolo_bar = Some(tremolo_bar);
+        }
+        if flags & EFFECT_HARMONIC != 0 {
+            let kind = self.byte()?;
+            let data = if kind != 1 { Some(self.byte()?) } else { None };
+            effect.harmonic = Some(HarmonicEffect {
+                kind: match kind {
+                    2 => HarmonicType::Artificial,
+                    3 => HarmonicType::Tapped,
+                    4 => HarmonicType::Pinch,
+                    5 => HarmonicType::Semi,
+                    _ => HarmonicType::Natural,
+                },
+                fret: data.filter(|_| kind == 3).map(|d| d as i8),
+                ..Default::default()
+            });
+        }
+        if flags & EFFECT_GRACE != 0 {
+            let grace_flags = self.byte()?;
+            let fret = self.signed_byte()?;
+            let duration = self.byte()?.clamp(1, 3);
+            let velocity = self.byte()?.into();
+            let transition = match self.byte()? {
+                1 => GraceEffectTransition::Slide,
+                2 => GraceEffectTransition::Bend,
+                3 => GraceEffectTransition::Hammer,
+                _ => GraceEffectTransition::None,
+            };
+            effect.grace = Some(GraceEffect {
+                duration: 1 << (7 - duration),
+                fret,
+                is_dead: grace_flags & GRACE_DEAD != 0,
+                is_on_beat: grace_flags & GRACE_ON_BEAT != 0,
+                transition,
+                velocity,
+            });
+        }
+        if flags & EFFECT_TRILL != 0 {
+            effect.trill = Some(TrillEffect {
+                fret: self.signed_byte()?,
+                duration: Duration {
+                    value: self.byte()?.max(1).into(),
+                    ..Default::default()
+                },
+            });
+        }
+        if flags & EFFECT_TREMOLO_PICKING != 0 {
+            effect.tremolo_picking = Some(TremoloPickingEffect {
+                duration: Duration {
+                    value: self.byte()?.max(1).into(),
+                    ..Default::default()
+                },
+            });
+        }
+        effect.vibrato = flags & EFFECT_VIBRATO != 0;
+        effects.dead = flags & EFFECT_DEAD != 0;
+        if flags & EFFECT_SLIDE != 0 {
+            effect.slides.push(SlideType::ShiftSlideTo);
+        }
+        effect.hammer = flags & EFFECT_HAMMER != 0;
+        effect.ghost_note = flags & EFFECT_GHOST != 0;
+        effect.accentuated_note = flags & EFFECT_ACCENTUATED != 0;
+        effect.heavy_accentuated_note = flags & EFFECT_HEAVY_ACCENTUATED != 0;
+        effect.palm_mute = flags & EFFECT_PALM_MUTE != 0;
+        effect.staccato = flags & EFFECT_STACCATO != 0;
+        effect.let_ring = flags & EFFECT_LET_RING != 0;
+        effects.slap = if flags & EFFECT_TAPPING != 0 {
+            Some(SlapEffect::Tapping)
+        } else if flags & EFFECT_SLAPPING != 0 {
+            Some(SlapEffect::Slapping)
+        } else if flags & EFFECT_POPPING != 0 {
+            Some(SlapEffect::Popping)
+        } else {
+            None
+        };
+        effects.fade_in = flags & EFFECT_FADE_IN != 0;
+        Ok(effects)
+    }
+}
+
+impl SongTuxGuitarOps for Song {
+    fn read_tg(&mut self, data: &[u8]) -> GpResult<()> {
+        let mut r = TgReader { data, seek: 0 };
+        let format = r.byte_string()?;
+        if format != TG_FORMAT {
+            return Err(GpError::FormatError(format!(
+                "unsupported TuxGuitar document: {:?}",
+                format
+            )));
+        }
+        self.name = r.byte_string()?;
+        self.artist = r.byte_string()?;
+        self.album = r.byte_string()?;
+        self.author = r.byte_string()?;
+        self.date = r.byte_string()?;
+        self.copyright = r.byte_string()?;
+        self.writer = r.byte_string()?;
+        self.transcriber = r.byte_string()?;
+        self.comments = r.int_string()?;
+
+        let channel_count = r.byte()?;
+        let channels = (0..channel_count)
+            .map(|_| r.read_channel())
+            .collect::<GpResult<Vec<TgChannel>>>()?;
+
+        let header_count = r.short()?.max(0) as u16;
+        let mut headers: Vec<MeasureHeader> = Vec::with_capacity(header_count.into());
+        for number in 1..=header_count {
+            let header = r.read_measure_header(number, headers.last())?;
+            headers.push(header);
+        }
+        if headers.is_empty() {
+            return Err(GpError::FormatError(
+                "TuxGuitar document without measures".into(),
+            ));
+        }
+        if headers[0].tempo > 0 {
+            self.tempo = headers[0].tempo.clamp(1, i32::from(i16::MAX)) as i16;
+        }
+
+        // 64 channels as in Guitar Pro files, every TuxGuitar channel gets the next free one
+        self.channels = (0..64u8)
+            .map(|channel| MidiChannel {
+                channel,
+                effect_channel: channel,
+                ..Default::default()
+            })
+            .collect();
+        let mut used_channels: Vec<(i16, u8)> = Vec::new();
+        self.tracks.clear();
+        self.lyrics = Lyrics::default();
+        let track_count = r.byte()?;
+        for number in 1..=usize::from(track_count) {
+            let (mut track, channel_id) = r.read_track(number, &headers, &mut self.lyrics)?;
+            let channel = channels.iter().find(|c| c.id == channel_id);
+            track.percussion_track = channel.is_some_and(|c| c.bank == TG_PERCUSSION_BANK);
+            let midi_channel = match used_channels.iter().find(|c| c.0 == channel_id) {
+                Some((_, midi_channel)) => *midi_channel,
+                None if track.percussion_track => DEFAULT_PERCUSSION_CHANNEL,
+                None => {
+                    let free = (0..16u8)
+                        .find(|c| {
+                            *c != DEFAULT_PERCUSSION_CHANNEL
+                                && !used_channels.iter().any(|u| u.1 == *c)
+                        })
+                        .unwrap_or(0);
+                    used_channels.push((channel_id, free));
+                    free
+                }
+            };
+            track.channel_index = usize::from(midi_channel);
+            if let Some(channel) = channel {
+                let midi = &mut self.channels[track.channel_index];
+                midi.set_instrument(channel.program.min(127).into());
+                if !track.percussion_track {
+                    midi.bank = channel.bank;
+                }
+                midi.volume = channel.volume.min(127) as i8;
+                midi.balance = channel.balance.min(127) as i8;
+                midi.chorus = channel.chorus.min(127) as i8;
+                midi.reverb = channel.reverb.min(127) as i8;
+                midi.phaser = channel.phaser.min(127) as i8;
+                midi.tremolo = channel.tremolo.min(127) as i8;
+            }
+            track.fret_count = 24;
+            self.tracks.push(track);
+        }
+        if self.tracks.is_empty() {
+            return Err(GpError::FormatError(
+                "TuxGuitar document without tracks".into(),
+            ));
+        }
+        self.key = self.tracks[0].measures[0].key_signature.clone();
+        for (m_idx, header) in headers.iter_mut().enumerate() {
+            header.key_signature = self.tracks[0].measures[m_idx].key_signature.clone();
+        }
+        self.measure_headers = headers;
+        // Guitar Pro files always hold five lyrics lines
+        for line in self.lyrics.lines.len()..5 {
+            self.lyrics.lines.push((line as u8, 1, String::new()));
+        }
+        Ok(())
+    }
+}
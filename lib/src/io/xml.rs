@@ -0,0 +1,196 @@
+//! A small XML element tree.
+//!
+//! The serde model in `gpif.rs` only keeps what it knows about. This tree keeps everything
+//! (element order, attributes, unknown subtrees), which is needed to merge a generated document
+//! with the one it was read from. It is also used to build MusicXML documents.
+use crate::error::{GpError, GpResult};
+use quick_xml::escape::{escape, resolve_predefined_entity};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct XmlElement {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    /// Text content, `None` for elements holding only children or nothing
+    pub text: Option<String>,
+    pub children: Vec<XmlElement>,
+}
+
+impl XmlElement {
+    pub fn new(name: &str) -> Self {
+        XmlElement {
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Element holding only a text.
+    pub fn with_text(name: &str, text: impl ToString) -> Self {
+        XmlElement {
+            name: name.to_string(),
+            text: Some(text.to_string()),
+            ..Default::default()
+        }
+    }
+
+    /// Builder: add an attribute.
+    pub fn with_attribute(mut self, name: &str, value: impl ToString) -> Self {
+        self.attributes.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Builder: add a child element.
+    pub fn with_child(mut self, child: XmlElement) -> Self {
+        self.children.push(child);
+        self
+    }
+
+    /// Value of the attribute `name`, if present.
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// First child element called `name`.
+    pub fn child(&self, name: &str) -> Option<&XmlElement> {
+        self.children.iter().find(|c| c.name == name)
+    }
+
+    /// Text of the element, empty when there is none.
+    pub fn text(&self) -> &str {
+        self.text.as_deref().unwrap_or("")
+    }
+
+    /// Parse a document and return its root element. The XML declaration, comments and
+    /// processing instructions are dropped, CDATA sections are read as text.
+    pub fn parse(xml: &str) -> GpResult<XmlElement> {
+        let mut reader = Reader::from_str(xml);
+        let mut stack: Vec<XmlElement> = Vec::new();
+        let mut root: Option<XmlElement> = None;
+        let error = |e: &dyn std::fmt::Display| GpError::FormatError(format!("XML error: {}", e));
+        loop {
+            let event = reader.read_event().map_err(|e| error(&e))?;
+            match event {
+                Event::Start(ref e) | Event::Empty(ref e) => {
+                    let mut element = XmlElement::new(&String::from_utf8_lossy(e.name().as_ref()));
+                    for attribute in e.attributes() {
+                        let attribute = attribute.map_err(|e| error(&e))?;
+                        let value = attribute.unescape_value().map_err(|e| error(&e))?;
+                        element.attributes.push((
+                            String::from_utf8_lossy(attribute.key.as_ref()).into_owned(),
+                            value.into_owned(),
+                        ));
+                    }
+                    if matches!(event, Event::Start(_)) {
+                        stack.push(element);
+                    } else {
+                        close_element(&mut stack, &mut root, element);
+                    }
+                }
+                Event::End(_) => {
+                    let mut element = stack.pop().ok_or_else(|| {
+                        GpError::FormatError("XML error: unbalanced end tag".into())
+                    })?;
+                    // Whitespace between child elements is only indentation
+                    if !element.children.is_empty()
+                        || element.text.as_ref().is_some_and(|t| t.trim().is_empty())
+                    {
+                        element.text = None;
+                    }
+                    close_element(&mut stack, &mut root, element);
+                }
+                Event::Text(e) => {
+                    let text = e.xml_content().map_err(|e| error(&e))?;
+                    push_text(&mut stack, &text);
+                }
+                Event::CData(e) => {
+                    let text = e.decode().map_err(|e| error(&e))?;
+                    push_text(&mut stack, &text);
+                }
+                Event::GeneralRef(e) => {
+                    let text = match e.resolve_char_ref().map_err(|e| error(&e))? {
+                        Some(c) => c.to_string(),
+                        None => {
+                            let name = e.decode().map_err(|e| error(&e))?;
+                            resolve_predefined_entity(&name)
+                                .ok_or_else(|| error(&format!("unknown entity &{};", name)))?
+                                .to_string()
+                        }
+                    };
+                    push_text(&mut stack, &text);
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+        if !stack.is_empty() {
+            return Err(GpError::FormatError("XML error: unclosed element".into()));
+        }
+        root.ok_or_else(|| GpError::FormatError("XML error: no root element".into()))
+    }
+
+    /// Serialize the element, without XML declaration. Children are indented by two spaces.
+    pub fn write(&self, out: &mut String, depth: usize) {
+        let indent = "  ".repeat(depth);
+        out.push_str(&indent);
+        out.push('<');
+        out.push_str(&self.name);
+        for (name, value) in &self.attributes {
+            out.push_str(&format!(" {}=\"{}\"", name, escape_text(value)));
+        }
+        if self.children.is_empty() {
+            match &self.text {
+                Some(text) => {
+                    out.push('>');
+                    out.push_str(&escape_text(text));
+                    out.push_str(&format!("</{}>\n", self.name));
+                }
+                None => out.push_str(" />\n"),
+            }
+            return;
+        }
+        out.push_str(">\n");
+        for child in &self.children {
+            child.write(out, depth + 1);
+        }
+        out.push_str(&format!("{}</{}>\n", indent, self.name));
+    }
+
+    /// Serialize the element as a whole document with an XML declaration.
+    pub fn to_document(&self) -> String {
+        let mut out = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+        self.write(&mut out, 0);
+        out
+    }
+}
+
+/// Escape a text, dropping the control characters XML 1.0 does not allow.
+fn escape_text(text: &str) -> String {
+    if text
+        .chars()
+        .any(|c| c < ' ' && !matches!(c, '\t' | '\n' | '\r'))
+    {
+        let text: String = text
+            .chars()
+            .filter(|c| *c >= ' ' || matches!(c, '\t' | '\n' | '\r'))
+            .collect();
+        return escape(text.as_str()).into_owned();
+    }
+    escape(text).into_owned()
+}
+
+fn push_text(stack: &mut [XmlElement], text: &str) {
+    if let Some(element) = stack.last_mut() {
+        element.text.get_or_insert_with(String::new).push_str(text);
+    }
+}
+
+fn close_element(stack: &mut [XmlElement], root: &mut Option<XmlElement>, element: XmlElement) {
+    match stack.last_mut() {
+        Some(parent) => parent.children.push(element),
+        None => *root = Some(element),
+    }
+}
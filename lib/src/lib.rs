@@ -9,6 +9,7 @@ pub use crate::error::{GpError, GpResult};
 // Re-export core types
 pub use crate::model::beat::{Beat, Voice};
 pub use crate::model::chord::Chord;
+pub use crate::model::conversion::{LossPosition, LossReport, LostItem};
 pub use crate::model::enums::*;
 pub use crate::model::headers::MeasureHeader;
 pub use crate::model::key_signature::{KeySignature, TimeSignature};
@@ -20,9 +21,27 @@ pub use crate::model::track::Track;
 
 // Re-export traits for easy use
 pub use crate::audio::midi::SongMidiOps;
+pub use crate::audio::midi_export::SongMidiExportOps;
+pub use crate::audio::midi_import::SongMidiImportOps;
+pub use crate::io::abc_export::SongAbcExportOps;
+pub use crate::io::alphatex_export::SongAlphaTexExportOps;
+pub use crate::io::alphatex_import::SongAlphaTexOps;
+pub use crate::io::ascii_tab_import::SongAsciiTabOps;
+pub use crate::io::gpif_export::SongGpifExportOps;
 pub use crate::io::gpif_import::SongGpifOps;
+#[cfg(feature = "serde")]
+pub use crate::io::json::SongJsonOps;
+pub use crate::io::lilypond_export::SongLilyPondExportOps;
+pub use crate::io::musescore_export::SongMuseScoreExportOps;
+pub use crate::io::musescore_import::SongMuseScoreOps;
+pub use crate::io::musicxml_export::SongMusicXmlExportOps;
+pub use crate::io::musicxml_import::SongMusicXmlOps;
+pub use crate::io::powertab_import::SongPowerTabOps;
+pub use crate::io::tuxguitar_export::SongTuxGuitarExportOps;
+pub use crate::io::tuxguitar_import::SongTuxGuitarOps;
 pub use crate::model::beat::SongBeatOps;
 pub use crate::model::chord::SongChordOps;
+pub use crate::model::conversion::SongConversionOps;
 pub use crate::model::effects::SongEffectOps;
 pub use crate::model::headers::SongHeaderOps;
 pub use crate::model::lyric::SongLyricOps;
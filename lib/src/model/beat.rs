@@ -6,6 +6,8 @@ use crate::{
 
 /// Parameters of beat display
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct BeatDisplay {
     break_beam: bool,
     force_beam: bool,
@@ -31,6 +33,8 @@ impl Default for BeatDisplay {
 
 /// A stroke effect for beats.
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct BeatStroke {
     pub direction: BeatStrokeDirection,
     pub value: u16,
@@ -57,6 +61,8 @@ impl BeatStroke {
 
 /// A voice contains multiple beats
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct Voice {
     //pub measure: Measure, //circular depth?
     pub measure_index: i16,
@@ -75,6 +81,8 @@ impl Default for Voice {
 
 /// This class contains all beat effects
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct BeatEffects {
     pub stroke: BeatStroke,
     pub has_rasgueado: bool,
@@ -128,6 +136,8 @@ impl BeatEffects {
 
 /// A beat contains multiple notes
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct Beat {
     pub notes: Vec<Note>,
     pub duration: Duration,
@@ -137,6 +147,10 @@ pub struct Beat {
     pub octave: Octave,
     pub display: BeatDisplay,
     pub status: BeatStatus,
+    /// Hairpin (crescendo or decrescendo) from GPIF (GP6/GP7)
+    pub hairpin: Option<String>,
+    /// Grace beat from GPIF (GP6/GP7): its notes are the grace notes of the following beat
+    pub is_grace: bool,
 }
 impl Default for Beat {
     fn default() -> Self {
@@ -150,6 +164,8 @@ impl Default for Beat {
             octave: Octave::None,
             display: BeatDisplay::default(),
             status: BeatStatus::Normal,
+            hairpin: None,
+            is_grace: false,
         }
     }
 }
@@ -540,49 +556,7 @@ impl SongBeatOps for Song {
     }
 
     fn write_beat_v3(&self, data: &mut Vec<u8>, beat: &Beat) -> GpResult<()> {
-        let mut flags = 0u8;
-        if beat.duration.dotted {
-            flags |= 0x01;
-        }
-        if beat.effect.is_chord() {
-            flags |= 0x02;
-        }
-        if !beat.text.is_empty() {
-            flags |= 0x04;
-        }
-        if beat.effect.is_default() {
-            flags |= 0x08;
-        }
-        if let Some(mtc) = &beat.effect.mix_table_change {
-            if mtc.is_just_wah() {
-                flags |= 0x10;
-            }
-        }
-        if !beat.duration.is_default_tuplet() {
-            flags |= 0x20;
-        }
-        if beat.status != BeatStatus::Normal {
-            flags |= 0x40;
-        }
-        write_byte(data, flags);
-        if (flags & 0x40) == 0x40 {
-            write_byte(data, from_beat_status(&beat.status));
-        }
-        beat.duration.write_duration(data, flags);
-        if (flags & 0x02) == 0x02 {
-            self.write_chord(data, beat);
-        }
-        if (flags & 0x04) == 0x04 {
-            write_int_byte_size_string(data, &beat.text);
-        }
-        if (flags & 0x08) == 0x08 {
-            self.write_beat_effect_v3(data, beat)?;
-        }
-        if (flags & 0x10) == 0x10 {
-            self.write_mix_table_change(data, &beat.effect.mix_table_change, &(3, 0, 0));
-        }
-        self.write_notes(data, beat, &Vec::new(), &(3, 0, 0))?;
-        Ok(())
+        self.write_beat(data, beat, &[], &(3, 0, 0))
     }
 
     fn write_beat(
@@ -602,15 +576,16 @@ impl SongBeatOps for Song {
         if !beat.text.is_empty() {
             flags |= 0x04;
         }
-        if beat.effect.is_default() {
+        //GP3 stores the vibrato and the harmonics of the notes with the beat effects
+        if !beat.effect.is_default()
+            || (version.0 == 3 && (beat.has_vibrato() || beat.has_harmonic()))
+        {
             flags |= 0x08;
         }
-        if let Some(mtc) = &beat.effect.mix_table_change {
-            if mtc.is_just_wah() || version.0 > 4 {
-                flags |= 0x10;
-            }
+        if beat.effect.mix_table_change.is_some() {
+            flags |= 0x10;
         }
-        if !beat.duration.is_default_tuplet() {
+        if !beat.duration.is_default_tuplet() && beat.duration.is_supported() {
             flags |= 0x20;
         }
         if beat.status != BeatStatus::Normal {
@@ -622,13 +597,21 @@ impl SongBeatOps for Song {
         }
         beat.duration.write_duration(data, flags);
         if (flags & 0x02) == 0x02 {
-            self.write_chord_v4(data, beat);
+            if version.0 == 3 {
+                self.write_chord(data, beat);
+            } else {
+                self.write_chord_v4(data, beat);
+            }
         }
         if (flags & 0x04) == 0x04 {
             write_int_byte_size_string(data, &beat.text);
         }
         if (flags & 0x08) == 0x08 {
-            self.write_beat_effect_v4(data, beat, version)?;
+            if version.0 == 3 {
+                self.write_beat_effect_v3(data, beat)?;
+            } else {
+                self.write_beat_effect_v4(data, beat, version)?;
+            }
         }
         if (flags & 0x10) == 0x10 {
             self.write_mix_table_change(data, &beat.effect.mix_table_change, version);
@@ -689,18 +672,13 @@ impl SongBeatOps for Song {
             flags1 |= 0x01;
         }
         if beat.effect.vibrato {
-            flags1 |= 0x01;
+            flags1 |= 0x02;
         }
-        if beat.has_harmonic() {
-            for n in 0..beat.notes.len() {
-                if let Some(h) = &beat.notes[n].effect.harmonic {
-                    if h.kind == HarmonicType::Natural {
-                        flags1 |= 0x04;
-                    }
-                    if h.kind == HarmonicType::Artificial {
-                        flags1 |= 0x08;
-                    }
-                }
+        for n in &beat.notes {
+            match n.effect.harmonic.as_ref().map(|h| &h.kind) {
+                Some(HarmonicType::Artificial) => flags1 |= 0x08,
+                Some(_) => flags1 |= 0x04,
+                None => {}
             }
         }
         if beat.effect.fade_in {
@@ -709,15 +687,17 @@ impl SongBeatOps for Song {
         if beat.effect.is_tremolo_bar() || beat.effect.is_slap_effect() {
             flags1 |= 0x20;
         }
-        if beat.effect.stroke.direction != BeatStrokeDirection::None
-            && beat.effect.stroke.value != 0
-        {
+        if beat.effect.stroke.direction != BeatStrokeDirection::None {
             flags1 |= 0x40;
         }
         write_byte(data, flags1);
         if (flags1 & 0x20) == 0x20 {
             write_byte(data, from_slap_effect(&beat.effect.slap_effect));
-            self.write_tremolo_bar(data, &beat.effect.tremolo_bar)?;
+            if beat.effect.is_slap_effect() {
+                write_i32(data, 0);
+            } else {
+                self.write_tremolo_bar(data, &beat.effect.tremolo_bar)?;
+            }
         }
         if (flags1 & 0x40) == 0x40 {
             self.write_beat_stroke(data, &beat.effect.stroke, &(3, 0, 0))?;
@@ -732,8 +712,8 @@ impl SongBeatOps for Song {
         version: &(u8, u8, u8),
     ) -> GpResult<()> {
         let mut flags1: i8 = 0;
-        if beat.has_vibrato() {
-            flags1 |= 0x01;
+        if beat.effect.vibrato {
+            flags1 |= 0x02;
         }
         if beat.effect.fade_in {
             flags1 |= 0x10;
@@ -741,9 +721,7 @@ impl SongBeatOps for Song {
         if beat.effect.is_slap_effect() {
             flags1 |= 0x20;
         }
-        if beat.effect.stroke.direction != BeatStrokeDirection::None
-            && beat.effect.stroke.value != 0
-        {
+        if beat.effect.stroke.direction != BeatStrokeDirection::None {
             flags1 |= 0x40;
         }
         write_signed_byte(data, flags1);
@@ -769,7 +747,7 @@ impl SongBeatOps for Song {
         if (flags2 & 0x04) == 0x04 {
             self.write_bend(data, &beat.effect.tremolo_bar);
         } //write tremolo bar
-        if (flags2 & 0x40) == 0x40 {
+        if (flags1 & 0x40) == 0x40 {
             self.write_beat_stroke(data, &beat.effect.stroke, version)?;
         }
         if (flags2 & 0x02) == 0x02 {
@@ -8,6 +8,8 @@ use crate::{
 
 /// A chord annotation for beats
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct Chord {
     pub length: u8,
     pub sharp: Option<bool>,
@@ -32,6 +34,8 @@ pub struct Chord {
 
 /// A single barre
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct Barre {
     pub fret: i8,
     /// First string from the bottom of the barre
@@ -48,6 +52,8 @@ pub const FLAT_NOTES: [&str; 12] = [
 ];
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct PitchClass {
     pub note: String,
     pub just: i8,
@@ -171,9 +177,6 @@ impl SongChordOps for Song {
             strings: vec![-1; string_count.into()],
             ..Default::default()
         };
-        for _ in 0..string_count {
-            c.strings.push(-1);
-        }
         c.new_format = Some(read_bool(data, seek)?);
         if c.new_format == Some(true) {
             if self.version.number.0 == 3 {
@@ -205,9 +208,9 @@ impl SongChordOps for Song {
         if chord.first_fret.is_some() {
             for i in 0u8..6u8 {
                 let fret = read_int(data, seek)? as i8;
-                if i < chord.strings.len().to_u8().unwrap() {
-                    chord.strings.push(fret);
-                } //chord.strings[i] = fret;
+                if let Some(string) = chord.strings.get_mut(usize::from(i)) {
+                    *string = fret;
+                }
             }
         }
         Ok(())
@@ -273,9 +276,9 @@ impl SongChordOps for Song {
         chord.first_fret = Some(read_int(data, seek)?.to_u8().unwrap());
         for i in 0u8..6u8 {
             let fret = read_int(data, seek)?.to_i8().unwrap();
-            if i < chord.strings.len().to_u8().unwrap() {
-                chord.strings.push(fret);
-            } //chord.strings[i] = fret;
+            if let Some(string) = chord.strings.get_mut(usize::from(i)) {
+                *string = fret;
+            }
         }
         //barre
         let barre_count = read_int(data, seek)?.to_usize().unwrap();
@@ -340,7 +343,7 @@ impl SongChordOps for Song {
         chord.sharp = Some(read_bool(data, seek)?);
         *seek += 3;
         chord.root = Some(PitchClass::from(
-            read_byte(data, seek)?.to_i8().unwrap(),
+            read_signed_byte(data, seek)?,
             None,
             chord.sharp,
         ));
@@ -360,9 +363,9 @@ impl SongChordOps for Song {
         chord.first_fret = Some(read_int(data, seek)?.to_u8().unwrap());
         for i in 0u8..7u8 {
             let fret = read_int(data, seek)?.to_i8().unwrap();
-            if i < chord.strings.len().to_u8().unwrap() {
-                chord.strings.push(fret);
-            } //chord.strings[i] = fret;
+            if let Some(string) = chord.strings.get_mut(usize::from(i)) {
+                *string = fret;
+            }
         }
         //barre
         let barre_count = read_byte(data, seek)?.to_usize().unwrap();
@@ -398,252 +401,124 @@ impl SongChordOps for Song {
         Ok(())
     }
 
+    /// Write the chord diagram of a GP3 beat, mirroring `read_chord()`.
     fn write_chord(&self, data: &mut Vec<u8>, beat: &crate::model::beat::Beat) {
         if let Some(c) = &beat.effect.chord {
             write_bool(data, c.new_format == Some(true));
             if c.new_format == Some(true) {
                 self.write_new_format_chord(data, c);
             } else {
+                write_placeholder_default(data, 1);
                 self.write_old_format_chord(data, c);
             }
         }
     }
 
+    /// Write a new-style chord diagram in the GP3 layout, see `read_new_format_chord_v3()`.
     fn write_new_format_chord(&self, data: &mut Vec<u8>, chord: &Chord) {
         write_bool(data, chord.sharp == Some(true));
         write_placeholder_default(data, 3);
-        //root
-        if let Some(r) = &chord.root {
-            write_i32(data, r.value.to_i32().unwrap());
-        } else {
-            write_i32(data, 0);
-        }
-        //chord type
-        if let Some(t) = &chord.kind {
-            write_i32(data, from_chord_type(t).to_i32().unwrap());
-        } else {
-            write_i32(data, 0);
-        }
-        //chord extension
-        if let Some(e) = &chord.extension {
-            write_i32(data, from_chord_extension(e).to_i32().unwrap());
-        } else {
-            write_i32(data, 0);
-        }
-        //bass
-        if let Some(b) = &chord.bass {
-            write_i32(data, b.value.to_i32().unwrap());
-        } else {
-            write_i32(data, 0);
-        }
-        //tonality
-        if let Some(t) = &chord.tonality {
-            write_i32(data, from_chord_alteration(t).to_i32().unwrap());
-        } else {
-            write_i32(data, 0);
-        }
-        //
+        write_i32(data, chord.root.as_ref().map_or(-1, |r| r.value.into()));
+        write_i32(
+            data,
+            chord.kind.as_ref().map_or(0, |t| from_chord_type(t).into()),
+        );
+        write_i32(
+            data,
+            chord
+                .extension
+                .as_ref()
+                .map_or(0, |e| from_chord_extension(e).into()),
+        );
+        write_i32(data, chord.bass.as_ref().map_or(-1, |b| b.value.into()));
+        write_i32(data, chord_alteration_value(&chord.tonality).into());
         write_bool(data, chord.add == Some(true));
-        write_byte_size_string(data, &chord.name);
-        write_placeholder_default(data, 22 - chord.name.len());
-        //fifth, ninth, eleventh
-        if let Some(f) = &chord.fifth {
-            write_i32(data, from_chord_alteration(f).to_i32().unwrap());
-        } else {
-            write_i32(data, 0);
-        }
-        if let Some(n) = &chord.ninth {
-            write_i32(data, from_chord_alteration(n).to_i32().unwrap());
-        } else {
-            write_i32(data, 0);
-        }
-        if let Some(e) = &chord.eleventh {
-            write_i32(data, from_chord_alteration(e).to_i32().unwrap());
-        } else {
-            write_i32(data, 0);
-        }
-        //first fret
-        if let Some(ff) = chord.first_fret {
-            write_i32(data, ff.to_i32().unwrap());
-        } else {
-            write_i32(data, 0);
-        }
-        //strings
+        write_byte_size_string(data, &chord.name, 22);
+        write_i32(data, chord_alteration_value(&chord.fifth).into());
+        write_i32(data, chord_alteration_value(&chord.ninth).into());
+        write_i32(data, chord_alteration_value(&chord.eleventh).into());
+        write_i32(data, chord.first_fret.unwrap_or(0).into());
         for i in 0..6 {
-            if i < chord.strings.len() {
-                write_i32(data, chord.strings[i].to_i32().unwrap());
-            } else {
-                write_i32(data, -1);
-            }
-        }
-        //barre
-        let mut barres: Vec<Barre> = Vec::with_capacity(2);
-        for i in 0..2usize {
-            if i < chord.barres.len() {
-                barres.push(chord.barres[i].clone());
-            } else {
-                break;
-            }
+            write_i32(data, chord.strings.get(i).copied().unwrap_or(-1).into());
         }
+        let barres = &chord.barres[..chord.barres.len().min(2)];
         write_i32(data, barres.len().to_i32().unwrap());
-        while barres.len() < 2 {
-            barres.push(Barre {
-                fret: 0,
-                start: 0,
-                end: 0,
-            });
-        }
-        for b in barres.iter().take(2) {
-            write_i32(data, b.fret.to_i32().unwrap());
+        for i in 0..2 {
+            write_i32(data, barres.get(i).map_or(0, |b| b.fret.into()));
         }
-        for b in barres.iter().take(2) {
-            write_i32(data, b.start.to_i32().unwrap());
+        for i in 0..2 {
+            write_i32(data, barres.get(i).map_or(0, |b| b.start.into()));
         }
-        for b in barres.iter().take(2) {
-            write_i32(data, b.end.to_i32().unwrap());
+        for i in 0..2 {
+            write_i32(data, barres.get(i).map_or(0, |b| b.end.into()));
         }
-        //omissions
-        for i in 0..7usize {
-            if i < chord.omissions.len() {
-                write_bool(data, chord.omissions[i]);
-            } else {
-                write_bool(data, true);
-            }
+        for i in 0..7 {
+            write_bool(data, chord.omissions.get(i).copied().unwrap_or(true));
         }
         write_placeholder_default(data, 1);
     }
+
+    /// Write an old-style chord diagram, see `read_old_format_chord()`.
     fn write_old_format_chord(&self, data: &mut Vec<u8>, chord: &Chord) {
         write_int_byte_size_string(data, &chord.name);
-        if let Some(ff) = chord.first_fret {
-            write_i32(data, ff.to_i32().unwrap());
-        } else {
-            write_i32(data, 0);
-        } //TODO: check
+        write_i32(data, chord.first_fret.unwrap_or(0).into());
         for i in 0..6 {
-            if i < chord.strings.len() {
-                write_i32(data, chord.strings[i].to_i32().unwrap());
-            } else {
-                write_i32(data, -1);
-            }
+            write_i32(data, chord.strings.get(i).copied().unwrap_or(-1).into());
         }
     }
 
+    /// Write the chord diagram of a GP4/GP5 beat, see `read_new_format_chord_v4()`.
     fn write_chord_v4(&self, data: &mut Vec<u8>, beat: &crate::model::beat::Beat) {
-        if let Some(c) = &beat.effect.chord {
-            write_signed_byte(data, 1); //signify GP4 chord format
-            write_bool(data, c.sharp == Some(true));
-            write_placeholder_default(data, 3);
-            //root
-            if let Some(r) = &c.root {
-                write_i32(data, r.value.to_i32().unwrap());
-            } else {
-                write_i32(data, 0);
-            }
-            //chord type
-            if let Some(t) = &c.kind {
-                write_i32(data, from_chord_type(t).to_i32().unwrap());
-            } else {
-                write_i32(data, 0);
-            }
-            //chord extension
-            if let Some(e) = &c.extension {
-                write_i32(data, from_chord_extension(e).to_i32().unwrap());
-            } else {
-                write_i32(data, 0);
-            }
-            //bass
-            if let Some(b) = &c.bass {
-                write_i32(data, b.value.to_i32().unwrap());
-            } else {
-                write_i32(data, 0);
-            }
-            //tonality
-            if let Some(t) = &c.tonality {
-                write_i32(data, from_chord_alteration(t).to_i32().unwrap());
-            } else {
-                write_i32(data, 0);
-            }
-            //
-            write_bool(data, c.add == Some(true));
-            write_byte_size_string(data, &c.name);
-            write_placeholder_default(data, 22 - c.name.len());
-            //fifth, ninth, eleventh
-            if let Some(f) = &c.fifth {
-                write_i32(data, from_chord_alteration(f).to_i32().unwrap());
-            } else {
-                write_i32(data, 0);
-            }
-            if let Some(n) = &c.ninth {
-                write_i32(data, from_chord_alteration(n).to_i32().unwrap());
-            } else {
-                write_i32(data, 0);
-            }
-            if let Some(e) = &c.eleventh {
-                write_i32(data, from_chord_alteration(e).to_i32().unwrap());
-            } else {
-                write_i32(data, 0);
-            }
-            //first fret
-            if let Some(ff) = c.first_fret {
-                write_i32(data, ff.to_i32().unwrap());
-            } else {
-                write_i32(data, 0);
-            }
-            //strings
-            for i in 0..6 {
-                if i < c.strings.len() {
-                    write_i32(data, c.strings[i].to_i32().unwrap());
-                } else {
-                    write_i32(data, -1);
-                }
-            }
-            //barre
-            let mut barres: Vec<Barre> = Vec::with_capacity(2);
-            for i in 0..2usize {
-                if i < c.barres.len() {
-                    barres.push(c.barres[i].clone());
-                } else {
-                    break;
-                }
-            }
-            write_i32(data, barres.len().to_i32().unwrap());
-            while barres.len() < 5 {
-                barres.push(Barre {
-                    fret: 0,
-                    start: 0,
-                    end: 0,
-                });
-            }
-            for b in barres.iter().take(5) {
-                write_i32(data, b.fret.to_i32().unwrap());
-            }
-            for b in barres.iter().take(5) {
-                write_i32(data, b.start.to_i32().unwrap());
-            }
-            for b in barres.iter().take(5) {
-                write_i32(data, b.end.to_i32().unwrap());
-            }
-            //omissions
-            for i in 0..7usize {
-                if i < c.omissions.len() {
-                    write_bool(data, c.omissions[i]);
-                } else {
-                    write_bool(data, true);
-                }
-            }
-            write_placeholder_default(data, 1);
-            for i in 0..7 {
-                if i < c.fingerings.len() {
-                    write_signed_byte(data, from_fingering(&c.fingerings[i]));
-                } else {
-                    write_signed_byte(data, -2);
-                }
-            }
-            write_bool(data, c.show == Some(true));
+        let Some(c) = &beat.effect.chord else {
+            return;
+        };
+        write_bool(data, c.new_format == Some(true));
+        if c.new_format != Some(true) {
+            self.write_old_format_chord(data, c);
+            return;
+        }
+        write_bool(data, c.sharp == Some(true));
+        write_placeholder_default(data, 3);
+        write_signed_byte(data, c.root.as_ref().map_or(-1, |r| r.value));
+        write_byte(data, c.kind.as_ref().map_or(0, from_chord_type));
+        write_byte(data, c.extension.as_ref().map_or(0, from_chord_extension));
+        write_i32(data, c.bass.as_ref().map_or(-1, |b| b.value.into()));
+        write_i32(data, chord_alteration_value(&c.tonality).into());
+        write_bool(data, c.add == Some(true));
+        write_byte_size_string(data, &c.name, 22);
+        write_byte(data, chord_alteration_value(&c.fifth));
+        write_byte(data, chord_alteration_value(&c.ninth));
+        write_byte(data, chord_alteration_value(&c.eleventh));
+        write_i32(data, c.first_fret.unwrap_or(0).into());
+        for i in 0..7 {
+            write_i32(data, c.strings.get(i).copied().unwrap_or(-1).into());
+        }
+        let barres = &c.barres[..c.barres.len().min(5)];
+        write_byte(data, barres.len().to_u8().unwrap());
+        for i in 0..5 {
+            write_signed_byte(data, barres.get(i).map_or(0, |b| b.fret));
+        }
+        for i in 0..5 {
+            write_signed_byte(data, barres.get(i).map_or(0, |b| b.start));
+        }
+        for i in 0..5 {
+            write_signed_byte(data, barres.get(i).map_or(0, |b| b.end));
+        }
+        for i in 0..7 {
+            write_bool(data, c.omissions.get(i).copied().unwrap_or(true));
         }
+        write_placeholder_default(data, 1);
+        for i in 0..7 {
+            write_signed_byte(data, c.fingerings.get(i).map_or(-1, from_fingering));
+        }
+        write_bool(data, c.show == Some(true));
     }
 }
 
+fn chord_alteration_value(alteration: &Option<ChordAlteration>) -> u8 {
+    alteration.as_ref().map_or(0, from_chord_alteration)
+}
+
 #[cfg(test)]
 mod test {
     use crate::model::chord::PitchClass;
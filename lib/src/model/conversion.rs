@@ -0,0 +1,347 @@
+//! Conversion of a song to an older Guitar Pro format, with a report of what the format cannot hold.
+//!
+//! The song is written with `Song::write()`, which silently drops what the target version has no
+//! room for. The report lists those features, each one with the track, measure, voice and beat it
+//! was found on. Display-only attributes (beaming, tuplet brackets, track display settings) are not
+//! reported.
+use std::fmt;
+
+use crate::error::GpResult;
+use crate::model::{
+    beat::Beat,
+    enums::*,
+    headers::MeasureHeader,
+    measure::Measure,
+    mix_table::{MixTableChange, WAH_EFFECT_NONE},
+    note::Note,
+    page::PageSetup,
+    rse::{RseInstrument, RseMasterEffect, TrackRse},
+    song::Song,
+    track::Track,
+};
+
+/// Where a lost feature is found. Numbers are 1-based, levels that do not apply are `None`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LossPosition {
+    pub track: Option<usize>,
+    pub measure: Option<usize>,
+    pub voice: Option<usize>,
+    pub beat: Option<usize>,
+}
+
+impl fmt::Display for LossPosition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let levels = [
+            ("track", self.track),
+            ("measure", self.measure),
+            ("voice", self.voice),
+            ("beat", self.beat),
+        ];
+        let parts: Vec<String> = levels
+            .iter()
+            .filter_map(|(name, number)| number.map(|n| format!("{} {}", name, n)))
+            .collect();
+        if parts.is_empty() {
+            write!(f, "song")
+        } else {
+            write!(f, "{}", parts.join(", "))
+        }
+    }
+}
+
+/// A feature of the song that the target format cannot represent, or content of a file read that
+/// the model cannot hold (`Song::read_losses`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LostItem {
+    pub feature: &'static str,
+    pub position: LossPosition,
+}
+
+/// Features lost when a song is written to a given Guitar Pro version.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LossReport {
+    pub version: (u8, u8, u8),
+    pub items: Vec<LostItem>,
+}
+
+impl LossReport {
+    /// Whether the conversion keeps everything.
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    fn push(&mut self, feature: &'static str, position: &LossPosition) {
+        self.items.push(LostItem {
+            feature,
+            position: position.clone(),
+        });
+    }
+}
+
+impl fmt::Display for LossReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for item in &self.items {
+            writeln!(f, "{}: {}", item.position, item.feature)?;
+        }
+        Ok(())
+    }
+}
+
+pub trait SongConversionOps {
+    /// Write the song to a Guitar Pro 3, 4 or 5 file and report the features that are lost.
+    fn convert_to(&self, version: (u8, u8, u8)) -> GpResult<(Vec<u8>, LossReport)>;
+    /// List the features of the song that cannot be written to `version`.
+    fn loss_report(&self, version: (u8, u8, u8)) -> LossReport;
+}
+
+impl SongConversionOps for Song {
+    fn convert_to(&self, version: (u8, u8, u8)) -> GpResult<(Vec<u8>, LossReport)> {
+        let data = self.write(version, None)?;
+        Ok((data, self.loss_report(version)))
+    }
+
+    fn loss_report(&self, version: (u8, u8, u8)) -> LossReport {
+        let mut report = LossReport {
+            version,
+            items: Vec::new(),
+        };
+        song_losses(self, &mut report);
+        for (h, header) in self.measure_headers.iter().enumerate() {
+            let position = LossPosition {
+                measure: Some(h + 1),
+                ..Default::default()
+            };
+            header_losses(self, header, &position, &mut report);
+        }
+        for (t, track) in self.tracks.iter().enumerate() {
+            let mut position = LossPosition {
+                track: Some(t + 1),
+                ..Default::default()
+            };
+            track_losses(track, &position, &mut report);
+            for (m, measure) in track.measures.iter().enumerate() {
+                position.measure = Some(m + 1);
+                measure_losses(measure, &position, &mut report);
+            }
+        }
+        report
+    }
+}
+
+/// Number of voices stored in each measure.
+fn voice_count(version: (u8, u8, u8)) -> usize {
+    if version.0 >= 5 {
+        2
+    } else {
+        1
+    }
+}
+
+fn song_losses(song: &Song, report: &mut LossReport) {
+    let position = LossPosition::default();
+    let version = report.version;
+    if version.0 < 5 {
+        if song.page_setup != PageSetup::default() {
+            report.push("page setup", &position);
+        }
+        if song.master_effect != RseMasterEffect::default() {
+            report.push("RSE master effect", &position);
+        }
+        if !song.tempo_name.is_empty() && song.tempo_name != Song::default().tempo_name {
+            report.push("tempo name", &position);
+        }
+    } else if version == (5, 0, 0) {
+        let default = RseMasterEffect::default();
+        if song.master_effect.volume != default.volume
+            || song.master_effect.equalizer != default.equalizer
+        {
+            report.push("RSE master effect", &position);
+        }
+    }
+    if song.hide_tempo && version <= (5, 0, 0) {
+        report.push("hidden tempo", &position);
+    }
+    if version.0 < 4 && song.lyrics.lines.iter().any(|l| !l.2.is_empty()) {
+        report.push("lyrics", &position);
+    }
+}
+
+fn header_losses(
+    song: &Song,
+    header: &MeasureHeader,
+    position: &LossPosition,
+    report: &mut LossReport,
+) {
+    if !header.fermatas.is_empty() {
+        report.push("fermata", position);
+    }
+    if header.free_time {
+        report.push("free time", position);
+    }
+    if report.version.0 < 5 {
+        if header.triplet_feel != song.triplet_feel {
+            report.push("triplet feel change", position);
+        }
+        if header.direction.is_some() {
+            report.push("direction", position);
+        }
+    }
+}
+
+fn track_losses(track: &Track, position: &LossPosition, report: &mut LossReport) {
+    if !track.short_name.is_empty() {
+        report.push("track short name", position);
+    }
+    // guitar staves are written an octave above the sounding pitch in every format
+    if track.transpose_chromatic != 0 || !(-1..=0).contains(&track.transpose_octave) {
+        report.push("transposition", position);
+    }
+    if report.version.0 < 5 && (track.use_rse || track.rse != TrackRse::default()) {
+        report.push("RSE settings", position);
+    } else if !track.rse.sound_bank_patch.is_empty() || !track.rse.effects.is_empty() {
+        report.push("RSE sound bank and effect chain", position);
+    }
+}
+
+fn measure_losses(measure: &Measure, position: &LossPosition, report: &mut LossReport) {
+    if !matches!(measure.clef, MeasureClef::Treble) {
+        report.push("clef", position);
+    }
+    if measure.simile_mark.is_some() {
+        report.push("simile mark", position);
+    }
+    if report.version.0 < 5 && !matches!(measure.line_break, LineBreak::None) {
+        report.push("line break", position);
+    }
+    let staves = measure.staves.iter().flat_map(|s| &s.voices);
+    if staves.flat_map(|v| &v.beats).any(|b| !b.notes.is_empty()) {
+        report.push("additional staff", position);
+    }
+    let voices = voice_count(report.version);
+    let mut position = position.clone();
+    for (v, voice) in measure.voices.iter().enumerate() {
+        position.voice = Some(v + 1);
+        for (b, beat) in voice.beats.iter().enumerate() {
+            position.beat = Some(b + 1);
+            if v >= voices {
+                if !beat.notes.is_empty() {
+                    report.push("additional voice", &position);
+                }
+                continue;
+            }
+            beat_losses(beat, &position, report);
+        }
+    }
+}
+
+fn beat_losses(beat: &Beat, position: &LossPosition, report: &mut LossReport) {
+    let version = report.version;
+    if beat.hairpin.is_some() {
+        report.push("hairpin", position);
+    }
+    // Guitar Pro 3-5 store ten tuplets, the other ones are written as plain notes
+    if !beat.duration.is_supported() {
+        report.push("tuplet", position);
+    }
+    if version.0 < 5 && beat.octave != Octave::None {
+        report.push("ottava", position);
+    }
+    if version.0 < 4 {
+        if beat.effect.pick_stroke != BeatStrokeDirection::None {
+            report.push("pick stroke", position);
+        }
+        if beat.effect.has_rasgueado {
+            report.push("rasgueado", position);
+        }
+    }
+    if let Some(mix_table_change) = &beat.effect.mix_table_change {
+        mix_table_losses(mix_table_change, position, report);
+    }
+    let mut features = Vec::new();
+    for note in &beat.notes {
+        note_losses(note, version, &mut features);
+    }
+    for feature in features {
+        report.push(feature, position);
+    }
+}
+
+fn mix_table_losses(mtc: &MixTableChange, position: &LossPosition, report: &mut LossReport) {
+    let version = report.version;
+    if version.0 < 4 {
+        let items = [
+            &mtc.volume,
+            &mtc.balance,
+            &mtc.chorus,
+            &mtc.reverb,
+            &mtc.phaser,
+            &mtc.tremolo,
+        ];
+        if items.into_iter().flatten().any(|i| i.all_tracks) {
+            report.push("mix table change for all tracks", position);
+        }
+    }
+    if version.0 < 5 {
+        if mtc.wah.as_ref().is_some_and(|w| w.value != WAH_EFFECT_NONE) {
+            report.push("wah", position);
+        }
+        if mtc.use_rse || mtc.rse != RseInstrument::default() {
+            report.push("RSE instrument change", position);
+        }
+        if !mtc.tempo_name.is_empty() {
+            report.push("tempo name", position);
+        }
+    }
+}
+
+/// Collect the lost features of a note, once per beat.
+fn note_losses(note: &Note, version: (u8, u8, u8), features: &mut Vec<&'static str>) {
+    let effect = &note.effect;
+    let mut lost = vec![(effect.ornament.is_some(), "ornament")];
+    if version.0 < 4 {
+        lost.extend([
+            (effect.staccato, "staccato"),
+            (effect.palm_mute, "palm mute"),
+            (effect.tremolo_picking.is_some(), "tremolo picking"),
+            (effect.trill.is_some(), "trill"),
+            (effect.accentuated_note, "accentuated note"),
+            (effect.is_fingering(), "fingering"),
+            (
+                effect.harmonic.as_ref().is_some_and(|h| {
+                    !matches!(h.kind, HarmonicType::Natural | HarmonicType::Artificial)
+                }),
+                "harmonic type",
+            ),
+            (
+                effect
+                    .slides
+                    .iter()
+                    .any(|s| !matches!(s, SlideType::ShiftSlideTo | SlideType::LegatoSlideTo)),
+                "slide type",
+            ),
+        ]);
+    }
+    if version.0 < 5 {
+        lost.extend([
+            (effect.slides.len() > 1, "multiple slides"),
+            (
+                effect.grace.as_ref().is_some_and(|g| g.is_on_beat),
+                "grace note on the beat",
+            ),
+            (
+                effect.grace.as_ref().is_some_and(|g| g.is_dead),
+                "dead grace note",
+            ),
+            (note.swap_accidentals, "accidental spelling"),
+            (
+                (note.duration_percent - 1.0).abs() > 1e-3,
+                "note duration percent",
+            ),
+        ]);
+    }
+    for (is_lost, feature) in lost {
+        if is_lost && !features.contains(&feature) {
+            features.push(feature);
+        }
+    }
+}
@@ -8,6 +8,8 @@ use crate::{
 
 /// A single point within the BendEffect
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct BendPoint {
     pub position: u8,
     pub value: i8,
@@ -31,6 +33,8 @@ pub const GP_BEND_POSITION: f32 = 60.0;
 pub const GP_BEND_SEMITONE_LENGTH: f32 = 1.0;
 /// This effect is used to describe string bends and tremolo bars
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct BendEffect {
     pub kind: BendType,
     pub value: i16,
@@ -84,6 +88,8 @@ pub(crate) fn pack_velocity(velocity: i16) -> i8 {
 
 /// A grace note effect
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct GraceEffect {
     pub duration: u8,
     pub fret: i8,
@@ -115,6 +121,8 @@ impl GraceEffect {
 
 /// A harmonic note effect
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct HarmonicEffect {
     pub kind: HarmonicType,
     //artificial harmonic
@@ -136,6 +144,8 @@ impl Default for HarmonicEffect {
 
 /// A tremolo picking effect.
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct TremoloPickingEffect {
     pub duration: Duration,
 }
@@ -158,6 +168,8 @@ fn from_tremolo_value(value: i8) -> GpResult<u8> {
 
 /// A trill effect.
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct TrillEffect {
     pub fret: i8,
     pub duration: Duration,
@@ -385,7 +397,7 @@ impl SongEffectOps for Song {
                     field: "current_track",
                 })?;
                 he.pitch = Some(PitchClass::from(
-                    note.real_value(&self.tracks[track_idx].strings)?,
+                    (note.real_value(&self.tracks[track_idx].strings)? % 12) as i8,
                     None,
                     None,
                 ));
@@ -397,7 +409,7 @@ impl SongEffectOps for Song {
                     field: "current_track",
                 })?;
                 he.pitch = Some(PitchClass::from(
-                    note.real_value(&self.tracks[track_idx].strings)?,
+                    (note.real_value(&self.tracks[track_idx].strings)? % 12) as i8,
                     None,
                     None,
                 ));
@@ -504,7 +516,7 @@ impl SongEffectOps for Song {
     }
     fn write_grace_v5(&self, data: &mut Vec<u8>, grace: &Option<GraceEffect>) {
         let g = grace.clone().unwrap();
-        write_byte(data, g.fret.to_u8().unwrap());
+        write_signed_byte(data, g.fret);
         write_byte(data, pack_velocity(g.velocity).to_u8().unwrap());
         write_byte(
             data,
@@ -528,19 +540,22 @@ impl SongEffectOps for Song {
     ) -> GpResult<()> {
         if let Some(h) = &note.effect.harmonic {
             let mut byte = from_harmonic_type(&h.kind);
-            if h.kind != HarmonicType::Artificial {
-                if let (Some(p), Some(o)) = (&h.pitch, &h.octave) {
-                    let real_val = note.real_value(strings)?;
-                    if p.value == ((real_val + 7) % 12) && *o == Octave::Ottava {
-                        byte = 15;
-                    } else if p.value == (real_val % 12) && *o == Octave::Quindicesima {
-                        byte = 17;
-                    } else if p.value == (real_val % 12) && *o == Octave::Ottava {
-                        byte = 22;
-                    }
+            //artificial harmonics are stored as the fret of the harmonic, see `read_harmonic`
+            if h.kind == HarmonicType::Artificial {
+                byte = if h.octave == Some(Octave::Quindicesima) {
+                    17
+                } else if h.pitch.is_none()
+                    || h.pitch
+                        == Some(PitchClass::from(
+                            (note.real_value(strings)? % 12) as i8,
+                            None,
+                            None,
+                        ))
+                {
+                    22
                 } else {
-                    byte = 22;
-                }
+                    15
+                };
             }
             write_signed_byte(data, byte);
         }
@@ -554,12 +569,17 @@ impl SongEffectOps for Song {
     ) -> GpResult<()> {
         if let Some(h) = &note.effect.harmonic {
             write_signed_byte(data, from_harmonic_type(&h.kind));
-            if h.kind == HarmonicType::Artificial && (h.pitch.is_none() || h.octave.is_none()) {
-                let p = PitchClass::from(note.real_value(strings)? % 12, None, None);
-                let o = Octave::Ottava;
+            if h.kind == HarmonicType::Artificial {
+                let p = match &h.pitch {
+                    Some(p) => p.clone(),
+                    None => PitchClass::from((note.real_value(strings)? % 12) as i8, None, None),
+                };
                 write_byte(data, p.just.to_u8_gp("pitch class just")?);
                 write_signed_byte(data, p.accidental);
-                write_byte(data, from_octave(&o));
+                write_byte(
+                    data,
+                    from_octave(h.octave.as_ref().unwrap_or(&Octave::Ottava)),
+                );
             } else if h.kind == HarmonicType::Tapped {
                 let fret = h.fret.ok_or(GpError::MissingState {
                     field: "harmonic fret",
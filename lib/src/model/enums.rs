@@ -3,6 +3,8 @@ use crate::error::{GpError, GpResult};
 /// An enumeration of different triplet feels.
 #[repr(u8)]
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub enum TripletFeel {
     None,
     Eighth,
@@ -31,6 +33,8 @@ pub(crate) fn from_triplet_feel(value: &TripletFeel) -> u8 {
 #[allow(dead_code)]
 #[repr(u8)]
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub enum MeasureClef {
     Treble,
     Bass,
@@ -40,6 +44,8 @@ pub enum MeasureClef {
 /// A line break directive: `NONE: no line break`, `BREAK: break line`, `Protect the line from breaking`.
 #[repr(u8)]
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub enum LineBreak {
     None,
     Break,
@@ -63,6 +69,8 @@ pub(crate) fn from_line_break(value: &LineBreak) -> u8 {
 /// An enumeration of all supported slide types.
 #[repr(i8)]
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub enum SlideType {
     IntoFromAbove = -2, //-2
     IntoFromBelow = -1, //-1
@@ -102,6 +110,8 @@ pub(crate) fn from_slide_type(value: &SlideType) -> i8 {
 /// An enumeration of all supported slide types.
 #[repr(u8)]
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub enum NoteType {
     Rest, //0
     Normal,
@@ -130,6 +140,8 @@ pub(crate) fn from_note_type(value: &NoteType) -> u8 {
 
 #[repr(u8)]
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub enum BeatStatus {
     Empty,
     Normal,
@@ -153,6 +165,8 @@ pub(crate) fn from_beat_status(value: &BeatStatus) -> u8 {
 
 #[repr(u8)]
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub enum TupletBracket {
     None,
     Start,
@@ -162,6 +176,8 @@ pub enum TupletBracket {
 /// Octave signs
 #[repr(u8)]
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub enum Octave {
     None,
     Ottava,
@@ -195,6 +211,8 @@ pub(crate) fn from_octave(value: &Octave) -> u8 {
 /// All beat stroke directions
 #[repr(u8)]
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub enum BeatStrokeDirection {
     None,
     Up,
@@ -221,6 +239,8 @@ pub(crate) fn from_beat_stroke_direction(value: &BeatStrokeDirection) -> i8 {
 /// Characteristic of articulation
 #[repr(u8)]
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub enum SlapEffect {
     None,
     Tapping,
@@ -251,6 +271,8 @@ pub(crate) fn from_slap_effect(value: &SlapEffect) -> u8 {
 /// Voice directions indicating the direction of beams
 #[repr(u8)]
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub enum VoiceDirection {
     None,
     Up,
@@ -260,6 +282,8 @@ pub enum VoiceDirection {
 /// Type of the chord.
 #[repr(u8)]
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub enum ChordType {
     /// Major chord.
     Major,
@@ -338,6 +362,8 @@ pub(crate) fn from_chord_type(value: &ChordType) -> u8 {
 /// Tonality of the chord
 #[repr(u8)]
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub enum ChordAlteration {
     /// Perfect.
     Perfect,
@@ -368,6 +394,8 @@ pub(crate) fn from_chord_alteration(value: &ChordAlteration) -> u8 {
 /// Extension type of the chord
 #[repr(u8)]
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub enum ChordExtension {
     None,
     /// Ninth chord.
@@ -400,6 +428,8 @@ pub(crate) fn from_chord_extension(value: &ChordExtension) -> u8 {
 /// Left and right hand fingering used in tabs and chord diagram editor.
 #[repr(i8)]
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub enum Fingering {
     /// Open or muted.
     Open = -1, //-1?
@@ -443,6 +473,8 @@ pub(crate) fn from_fingering(value: &Fingering) -> i8 {
 /// All Bend presets
 #[repr(u8)]
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub enum BendType {
     /// No Preset.
     None,
@@ -513,6 +545,8 @@ pub(crate) fn from_bend_type(value: &BendType) -> i8 {
 /// All transition types for grace notes.
 #[repr(i8)]
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub enum GraceEffectTransition {
     ///No transition
     None = 0,
@@ -546,6 +580,8 @@ pub(crate) fn from_grace_effect_transition(value: &GraceEffectTransition) -> i8
 
 #[repr(u8)]
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub enum HarmonicType {
     Natural = 1, //1
     Artificial,
@@ -565,7 +601,9 @@ pub(crate) fn from_harmonic_type(value: &HarmonicType) -> i8 {
 
 /// Values of auto-accentuation on the beat found in track RSE settings
 #[repr(u8)]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub enum Accentuation {
     None,
     VerySoft,
@@ -602,6 +640,8 @@ pub(crate) fn from_accentuation(value: &Accentuation) -> u8 {
 /// A navigation sign like *Coda* (𝄌: U+1D10C) or *Segno* (𝄋 or 𝄉: U+1D10B or U+1D109).
 #[repr(u8)]
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub enum DirectionSign {
     Coda,
     DoubleCoda,
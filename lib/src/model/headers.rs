@@ -9,6 +9,8 @@ use crate::{
 };
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct Version {
     pub data: String,
     pub number: (u8, u8, u8),
@@ -16,6 +18,8 @@ pub struct Version {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct Clipboard {
     pub start_measure: i32,
     pub stop_measure: i32,
@@ -40,6 +44,8 @@ impl Default for Clipboard {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct MeasureHeader {
     pub number: u16,
     pub start: i64,
@@ -58,6 +64,10 @@ pub struct MeasureHeader {
     pub fermatas: Vec<(String, String)>,
     /// Free time (no metronome) from GPIF (GP6/GP7)
     pub free_time: bool,
+    /// Position of the master bar in the GPIF document the song was read from, used to write
+    /// back its content not mapped by the model
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub gpif_index: Option<usize>,
 }
 impl Default for MeasureHeader {
     fn default() -> Self {
@@ -80,11 +90,11 @@ impl Default for MeasureHeader {
             },
             fermatas: Vec::new(),
             free_time: false,
+            gpif_index: None,
         }
     }
 }
 impl MeasureHeader {
-    #[allow(dead_code)]
     pub(crate) fn length(&self) -> i64 {
         self.time_signature.numerator.to_i64().unwrap()
             * crate::model::key_signature::DURATION_QUARTER_TIME
@@ -98,6 +108,8 @@ impl MeasureHeader {
 
 /// A marker annotation for beats.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct Marker {
     pub title: String,
     pub color: i32,
@@ -115,7 +127,7 @@ impl Default for Marker {
 /// - then a string containing the marker's name. Finally the marker's color is written.
 fn read_marker(data: &[u8], seek: &mut usize) -> GpResult<Marker> {
     let mut marker = Marker {
-        title: read_int_size_string(data, seek)?,
+        title: read_int_byte_size_string(data, seek)?,
         ..Default::default()
     };
     marker.color = read_color(data, seek)?;
@@ -124,6 +136,8 @@ fn read_marker(data: &[u8], seek: &mut usize) -> GpResult<Marker> {
 
 /// This class can store the information about a group of measures which are repeated.
 #[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct RepeatGroup {
     /// List of measure header indexes.
     pub measure_headers: Vec<usize>,
@@ -204,7 +218,7 @@ impl SongHeaderOps for Song {
             c.stop_beat = read_int(data, seek)?;
             c.sub_bar_copy = read_int(data, seek)? != 0;
         }
-        println!("read_clipboard(): {:?}", c);
+        //println!("read_clipboard(): {:?}", c);
         Ok(Some(c))
     }
 
@@ -444,6 +458,7 @@ impl SongHeaderOps for Song {
         }
     }
 
+    /// Write a measure header, its flags are packed against the previous header the way `read_measure_header` inherits the missing values.
     fn write_measure_header(
         &self,
         data: &mut Vec<u8>,
@@ -451,139 +466,93 @@ impl SongHeaderOps for Song {
         previous: Option<usize>,
         version: &(u8, u8, u8),
     ) {
+        let mh = &self.measure_headers[header];
+        let previous = previous.map(|p| &self.measure_headers[p]);
         //pack measure header flags
         let mut flags: u8 = 0x00;
-        if let Some(p) = previous {
-            if self.measure_headers[header].time_signature.numerator
-                != self.measure_headers[p].time_signature.numerator
-            {
-                flags |= 0x01;
-            }
-            if self.measure_headers[header]
-                .time_signature
-                .denominator
-                .value
-                != self.measure_headers[p].time_signature.denominator.value
-            {
-                flags |= 0x02;
-            }
-        } else {
-            flags |= 0x01;
-            flags |= 0x02;
-            if self.measure_headers[header].repeat_open {
-                flags |= 0x04;
-            }
-            if self.measure_headers[header].repeat_close > -1 {
-                flags |= 0x08;
-            }
-            if self.measure_headers[header].repeat_alternative > 0 {
-                flags |= 0x10;
-            }
-            if self.measure_headers[header].marker.is_some() {
-                flags |= 0x20;
-            }
-        }
-        if version.0 >= 4 {
-            if previous.is_none() {
-                flags |= 0x40;
-            } else if let Some(p) = previous {
-                if self.measure_headers[header].key_signature
-                    == self.measure_headers[p].key_signature
-                {
-                    flags |= 0x40;
+        match previous {
+            Some(p) => {
+                if mh.time_signature.numerator != p.time_signature.numerator {
+                    flags |= 0x01;
                 }
-            }
-            if self.measure_headers[header].double_bar {
-                flags |= 0x80;
-            }
-        }
-        if version.0 >= 5 {
-            if let Some(p) = previous {
-                if self.measure_headers[header].time_signature
-                    != self.measure_headers[p].time_signature
-                {
+                if mh.time_signature.denominator.value != p.time_signature.denominator.value {
+                    flags |= 0x02;
+                }
+                if version.0 >= 5 && mh.time_signature.beams != p.time_signature.beams {
                     flags |= 0x03;
                 }
-                write_placeholder_default(data, 1);
+                if mh.key_signature != p.key_signature {
+                    flags |= 0x40;
+                }
             }
+            None => flags |= 0x43,
+        }
+        if mh.repeat_open {
+            flags |= 0x04;
+        }
+        if mh.repeat_close > -1 {
+            flags |= 0x08;
+        }
+        if mh.repeat_alternative > 0 {
+            flags |= 0x10;
+        }
+        if mh.marker.is_some() {
+            flags |= 0x20;
+        }
+        if mh.double_bar {
+            flags |= 0x80;
         }
         //end pack
         //write measure header values
+        if version.0 >= 5 && previous.is_some() {
+            write_placeholder_default(data, 1);
+        }
         write_byte(data, flags);
         if (flags & 0x01) == 0x01 {
-            write_signed_byte(data, self.measure_headers[header].time_signature.numerator);
+            write_signed_byte(data, mh.time_signature.numerator);
         }
         if (flags & 0x02) == 0x02 {
             write_signed_byte(
                 data,
-                self.measure_headers[header]
-                    .time_signature
-                    .denominator
-                    .value
-                    .to_i8()
-                    .unwrap(),
+                mh.time_signature.denominator.value.to_i8().unwrap_or(4),
             );
         }
         if (flags & 0x08) == 0x08 {
             write_signed_byte(
                 data,
                 if version.0 < 5 {
-                    self.measure_headers[header].repeat_close
+                    mh.repeat_close
                 } else {
-                    self.measure_headers[header].repeat_close + 1
+                    mh.repeat_close + 1
                 },
             );
         }
         if (flags & 0x10) == 0x10 {
-            //write repeat alternative
+            //write repeat alternative: GP5 stores the mask, older versions the number of the highest ending
             if version.0 == 5 {
-                write_byte(data, self.measure_headers[header].repeat_alternative);
+                write_byte(data, mh.repeat_alternative);
             } else {
-                let mut first_one = false;
-                let mut ra: u8 = 0;
-                for i in 0u8..9 - self.measure_headers[header]
-                    .repeat_alternative
-                    .leading_zeros()
-                    .to_u8()
-                    .unwrap()
-                {
-                    ra = i;
-                    if (self.measure_headers[header].repeat_alternative & 1 << i) > 0 {
-                        first_one = true;
-                    } else if first_one {
-                        break;
-                    }
-                }
-                write_byte(data, ra);
+                write_byte(data, 8 - mh.repeat_alternative.leading_zeros() as u8);
             }
         }
-        if (flags & 0x20) == 0x20 {
-            //write marker
-            if let Some(marker) = &self.measure_headers[header].marker {
-                write_int_byte_size_string(data, &marker.title);
-                write_color(data, marker.color);
-            }
+        if let Some(marker) = &mh.marker {
+            write_int_byte_size_string(data, &marker.title);
+            write_color(data, marker.color);
         }
-        if version.0 >= 4 {
-            write_signed_byte(data, self.measure_headers[header].key_signature.key);
-            write_signed_byte(
-                data,
-                i8::from(self.measure_headers[header].key_signature.is_minor),
-            );
+        if (flags & 0x40) == 0x40 {
+            write_signed_byte(data, mh.key_signature.key);
+            write_signed_byte(data, i8::from(mh.key_signature.is_minor));
         }
         if version.0 >= 5 {
             if (flags & 0x03) == 0x03 {
-                for i in 0..self.measure_headers[header].time_signature.beams.len() {
-                    write_byte(data, self.measure_headers[header].time_signature.beams[i]);
+                for i in 0..4 {
+                    write_byte(data, mh.time_signature.beams.get(i).copied().unwrap_or(2));
                 }
             }
-            if (flags & 0x10) == 0x10 {
+            if (flags & 0x10) == 0 {
                 write_placeholder_default(data, 1);
             }
-            write_byte(
-                data,
-                from_triplet_feel(&self.measure_headers[header].triplet_feel),
-            );
+            write_byte(data, from_triplet_feel(&mh.triplet_feel));
         }
     }
 
@@ -602,9 +571,9 @@ impl SongHeaderOps for Song {
     }
     fn write_directions(&self, data: &mut Vec<u8>) {
         let mut map: HashMap<DirectionSign, i16> = HashMap::with_capacity(19);
-        for i in 1..self.measure_headers.len() {
-            if let Some(d) = &self.measure_headers[i].direction {
-                map.insert(d.clone(), i.to_i16().unwrap());
+        for (i, header) in self.measure_headers.iter().enumerate() {
+            if let Some(d) = &header.direction {
+                map.insert(d.clone(), (i + 1).to_i16().unwrap_or(i16::MAX));
             }
         }
         let order: Vec<DirectionSign> = vec![
@@ -14,6 +14,8 @@ pub const DURATION_HUNDRED_TWENTY_EIGHTH: u8 = 128;
 
 /// A time signature
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct TimeSignature {
     pub numerator: i8,
     pub denominator: Duration,
@@ -66,6 +68,8 @@ pub const KEY_SIGNATURES: [&str; 34] = [
     "E# minor",
 ];
 #[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct KeySignature {
     pub key: i8,
     pub is_minor: bool,
@@ -96,6 +100,8 @@ const SUPPORTED_TUPLETS: [(u8, u8); 10] = [
 ];
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct Duration {
     pub value: u16,
     pub dotted: bool,
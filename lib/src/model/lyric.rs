@@ -13,7 +13,9 @@ pub const _MAX_LYRICS_LINE_COUNT: u8 = 5;
 ///   * " " (spaces or carry returns): separates the syllables of a word
 ///   * "+": merge two syllables for the same beat
 ///   * "\[lorem ipsum...\]": hidden text
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct Lyrics {
     pub track_choice: u8,
     pub lines: Vec<(u8, u16, String)>,
@@ -53,11 +55,20 @@ impl SongLyricOps for Song {
         }
         Ok(lyrics)
     }
+    /// Write lyrics. Lines that are missing are written empty, starting at the first measure.
     fn write_lyrics(&self, data: &mut Vec<u8>) {
         write_i32(data, self.lyrics.track_choice.to_i32().unwrap());
-        for i in 0..5 {
-            write_i32(data, self.lyrics.lines[i].1.to_i32().unwrap());
-            write_int_size_string(data, &self.lyrics.lines[i].2);
+        for i in 0.._MAX_LYRICS_LINE_COUNT {
+            match self.lyrics.lines.iter().find(|l| l.0 == i) {
+                Some(l) => {
+                    write_i32(data, l.1.to_i32().unwrap());
+                    write_int_size_string(data, &l.2);
+                }
+                None => {
+                    write_i32(data, 1);
+                    write_int_size_string(data, "");
+                }
+            }
         }
     }
 }
@@ -1,15 +1,60 @@
+use std::collections::HashMap;
+
 use fraction::ToPrimitive;
 
 use crate::error::{GpResult, ToPrimitiveGp};
 use crate::{
     io::primitive::*,
-    model::{beat::*, enums::*, key_signature::*, song::*},
+    model::{beat::*, enums::*, key_signature::*, song::*, track::Track},
 };
 
 const MAX_VOICES: usize = 2;
 
+/// Give the tied notes of a track the fret of the note they continue: the previous note on their
+/// string in the same voice, or else in any voice. While the measures are read, a tied note only
+/// finds the notes of the measures before its own one.
+fn resolve_tied_frets(track: &mut Track) {
+    let mut voice_frets: HashMap<(usize, i8), i16> = HashMap::new();
+    let mut frets: HashMap<i8, i16> = HashMap::new();
+    for measure in &mut track.measures {
+        for (v, voice) in measure.voices.iter_mut().enumerate() {
+            for note in voice.beats.iter_mut().flat_map(|b| &mut b.notes) {
+                if note.kind == NoteType::Tie {
+                    if let Some(fret) = voice_frets
+                        .get(&(v, note.string))
+                        .or_else(|| frets.get(&note.string))
+                    {
+                        note.value = *fret;
+                    }
+                }
+                voice_frets.insert((v, note.string), note.value);
+                frets.insert(note.string, note.value);
+            }
+        }
+    }
+}
+
+/// Content of a measure on a staff of its track after the first one, see `Track::staves`.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct MeasureStaff {
+    pub clef: MeasureClef,
+    pub voices: Vec<Voice>,
+}
+impl Default for MeasureStaff {
+    fn default() -> Self {
+        MeasureStaff {
+            clef: MeasureClef::Treble,
+            voices: Vec::new(),
+        }
+    }
+}
+
 /// A measure header contains metadata for measures over multiple tracks.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct Measure {
     pub number: usize,
     pub start: i64,
@@ -24,6 +69,8 @@ pub struct Measure {
     pub line_break: LineBreak,
     /// Simile mark from GPIF (GP6/GP7)
     pub simile_mark: Option<String>,
+    /// Staves of the track after the first one (GP7), the first one holds `clef` and `voices`
+    pub staves: Vec<MeasureStaff>,
     /*marker: Optional['Marker'] = None
     isRepeatOpen: bool = False
     repeatAlternative: int = 0
@@ -46,6 +93,7 @@ impl Default for Measure {
             voices: Vec::with_capacity(2),
             line_break: LineBreak::None,
             simile_mark: None,
+            staves: Vec::new(),
         }
     }
 }
@@ -127,6 +175,9 @@ impl SongMeasureOps for Song {
             }
             //println!("read_measures(), start: {} \t numerator: {} \t denominator: {} \t length: {}", start, self.measure_headers[h].time_signature.numerator, self.measure_headers[h].time_signature.denominator.value, self.measure_headers[h].length());
         }
+        for track in &mut self.tracks {
+            resolve_tied_frets(track);
+        }
         self.current_track = None;
         self.current_measure_number = None;
         Ok(())
@@ -224,10 +275,8 @@ impl SongMeasureOps for Song {
     }
 
     fn write_measures(&self, data: &mut Vec<u8>, version: &(u8, u8, u8)) -> GpResult<()> {
-        for i in 0..self.tracks.len() {
-            //self.current_track = Some(i);
-            for m in 0..self.tracks[i].measures.len() {
-                //self.current_measure_number = Some(self.tracks[i].measure.number);
+        for m in 0..self.measure_headers.len() {
+            for i in 0..self.tracks.len() {
                 self.write_measure(data, i, m, version)?;
             }
         }
@@ -242,21 +291,20 @@ impl SongMeasureOps for Song {
         measure: usize,
         version: &(u8, u8, u8),
     ) -> GpResult<()> {
-        //self.current_voice_number = Some(1);
-        if version.0 < 5 {
-            self.write_voice(data, track, measure, 0, version)?;
-        } else {
-            for v in 0..self.tracks[track].measures[measure].voices.len() {
+        let voices = self.tracks[track].measures[measure].voices.len();
+        for v in 0..if version.0 < 5 { 1 } else { MAX_VOICES } {
+            if v < voices {
                 self.write_voice(data, track, measure, v, version)?;
-            } //self.current_voice_number = Some(v+1);
-            if version.0 == 5 {
-                write_byte(
-                    data,
-                    from_line_break(&self.tracks[track].measures[measure].line_break),
-                );
+            } else {
+                write_i32(data, 0);
             }
         }
-        //self.current_voice_number = None;
+        if version.0 == 5 {
+            write_byte(
+                data,
+                from_line_break(&self.tracks[track].measures[measure].line_break),
+            );
+        }
         Ok(())
     }
     fn write_voice(
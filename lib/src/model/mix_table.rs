@@ -7,6 +7,8 @@ use crate::model::{rse::*, song::*};
 
 /// A mix table item describes a mix parameter, e.g. volume or reverb
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct MixTableItem {
     pub value: u8,
     pub duration: u8,
@@ -16,8 +18,10 @@ pub struct MixTableItem {
 
 #[allow(dead_code)]
 const WAH_EFFECT_OFF: i8 = -2;
-const WAH_EFFECT_NONE: i8 = -1;
+pub(crate) const WAH_EFFECT_NONE: i8 = -1;
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct WahEffect {
     pub value: i8,
     pub display: bool,
@@ -53,6 +57,8 @@ impl WahEffect {
 
 /// A MixTableChange describes a change in mix parameters
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct MixTableChange {
     pub instrument: Option<MixTableItem>,
     pub rse: RseInstrument,
@@ -87,20 +93,6 @@ impl Default for MixTableChange {
         }
     }
 }
-impl MixTableChange {
-    pub(crate) fn is_just_wah(&self) -> bool {
-        self.instrument.is_none()
-            && self.volume.is_none()
-            && self.balance.is_none()
-            && self.chorus.is_none()
-            && self.reverb.is_none()
-            && self.phaser.is_none()
-            && self.tremolo.is_none()
-            && self.tempo.is_none()
-            && self.wah.is_none()
-    }
-}
-
 pub trait SongMixTableOps {
     fn read_mix_table_change(&mut self, data: &[u8], seek: &mut usize) -> GpResult<MixTableChange>;
     fn read_mix_table_change_values(
@@ -302,7 +294,7 @@ impl SongMixTableOps for Song {
         if let Some(ref mut item) = mtc.tempo {
             item.duration = read_signed_byte(data, seek)?.to_u8().unwrap_or(0);
             mtc.hide_tempo = false;
-            if self.version.number >= (5, 0, 0) {
+            if self.version.number > (5, 0, 0) {
                 mtc.hide_tempo = read_bool(data, seek)?;
             }
         }
@@ -335,27 +327,27 @@ impl SongMixTableOps for Song {
         }
         if mtc.balance.is_some() {
             let mut e = mtc.balance.take().unwrap();
-            e.all_tracks = (flags & 0x01) == 0x01;
+            e.all_tracks = (flags & 0x02) == 0x02;
             mtc.balance = Some(e);
         }
         if mtc.chorus.is_some() {
             let mut e = mtc.chorus.take().unwrap();
-            e.all_tracks = (flags & 0x01) == 0x01;
+            e.all_tracks = (flags & 0x04) == 0x04;
             mtc.chorus = Some(e);
         }
         if mtc.reverb.is_some() {
             let mut e = mtc.reverb.take().unwrap();
-            e.all_tracks = (flags & 0x01) == 0x01;
+            e.all_tracks = (flags & 0x08) == 0x08;
             mtc.reverb = Some(e);
         }
         if mtc.phaser.is_some() {
             let mut e = mtc.phaser.take().unwrap();
-            e.all_tracks = (flags & 0x01) == 0x01;
+            e.all_tracks = (flags & 0x10) == 0x10;
             mtc.phaser = Some(e);
         }
         if mtc.tremolo.is_some() {
             let mut e = mtc.tremolo.take().unwrap();
-            e.all_tracks = (flags & 0x01) == 0x01;
+            e.all_tracks = (flags & 0x20) == 0x20;
             mtc.tremolo = Some(e);
         }
         if self.version.number >= (5, 0, 0) {
@@ -392,7 +384,9 @@ impl SongMixTableOps for Song {
                 } else {
                     write_signed_byte(data, WAH_EFFECT_NONE);
                 } //write wah effect
-                self.write_rse_instrument_effect(data, &mtc.rse);
+                if version > &(5, 0, 0) {
+                    self.write_rse_instrument_effect(data, &mtc.rse);
+                }
             }
         }
     }
@@ -451,70 +445,40 @@ impl SongMixTableOps for Song {
             write_signed_byte(data, -1);
         }
         //tempo
-        if let Some(i) = &mix_table_change.tempo {
-            write_signed_byte(data, i.value.to_i8().unwrap());
-        } else {
-            write_signed_byte(data, -1);
-        }
         if version.0 >= 5 {
             write_int_byte_size_string(data, &mix_table_change.tempo_name);
-            if let Some(t) = &mix_table_change.tempo {
-                write_i32(data, t.value.to_i32().unwrap());
-            } else {
-                write_i32(data, -1);
-            }
+        }
+        if let Some(t) = &mix_table_change.tempo {
+            write_i32(data, i32::from(t.value));
+        } else {
+            write_i32(data, -1);
         }
     }
+    /// Write the durations of the changed parameters, followed by the hide tempo flag since Guitar Pro 5.1.
     fn write_mix_table_change_durations(
         &self,
         data: &mut Vec<u8>,
         mix_table_change: &MixTableChange,
         version: &(u8, u8, u8),
     ) {
-        //volume
-        if let Some(i) = &mix_table_change.volume {
-            write_signed_byte(data, i.duration.to_i8().unwrap());
-        } else {
-            write_signed_byte(data, -1);
+        for item in [
+            &mix_table_change.volume,
+            &mix_table_change.balance,
+            &mix_table_change.chorus,
+            &mix_table_change.reverb,
+            &mix_table_change.phaser,
+            &mix_table_change.tremolo,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            write_signed_byte(data, item.duration.to_i8().unwrap_or(i8::MAX));
         }
-        //balance
-        if let Some(i) = &mix_table_change.balance {
-            write_signed_byte(data, i.duration.to_i8().unwrap());
-        } else {
-            write_signed_byte(data, -1);
-        }
-        //chorus
-        if let Some(i) = &mix_table_change.chorus {
-            write_signed_byte(data, i.duration.to_i8().unwrap());
-        } else {
-            write_signed_byte(data, -1);
-        }
-        //reverb
-        if let Some(i) = &mix_table_change.reverb {
-            write_signed_byte(data, i.duration.to_i8().unwrap());
-        } else {
-            write_signed_byte(data, -1);
-        }
-        //phaser
-        if let Some(i) = &mix_table_change.phaser {
-            write_signed_byte(data, i.duration.to_i8().unwrap());
-        } else {
-            write_signed_byte(data, -1);
-        }
-        //tremolo
-        if let Some(i) = &mix_table_change.tremolo {
-            write_signed_byte(data, i.duration.to_i8().unwrap());
-        } else {
-            write_signed_byte(data, -1);
-        }
-        //tempo
         if let Some(i) = &mix_table_change.tempo {
-            write_signed_byte(data, i.duration.to_i8().unwrap());
+            write_signed_byte(data, i.duration.to_i8().unwrap_or(i8::MAX));
             if version > &(5, 0, 0) {
                 write_bool(data, mix_table_change.hide_tempo);
             }
-        } else {
-            write_signed_byte(data, -1);
         }
     }
     fn write_mix_table_change_flags_v4(
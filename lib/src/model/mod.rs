@@ -1,5 +1,6 @@
 pub mod beat;
 pub mod chord;
+pub mod conversion;
 pub mod effects;
 pub mod enums;
 pub mod headers;
@@ -7,6 +7,8 @@ use crate::{
 };
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct Note {
     pub value: i16,
     pub velocity: i16,
@@ -34,21 +36,26 @@ impl Default for Note {
     }
 }
 impl Note {
-    pub(crate) fn real_value(&self, strings: &[(i8, i8)]) -> GpResult<i8> {
-        if self.string > 0 {
-            let value = self.value.to_i8_gp("note value")?;
-            let string_idx = self.string.to_usize_gp("string index")? - 1;
-            return Ok(value + strings[string_idx].1);
-        }
-        Err(GpError::InvalidValue {
-            context: "note real_value (string must be > 0)",
-            value: self.string as i64,
-        })
+    /// MIDI pitch of the note: its fret on the tuning of its string.
+    pub(crate) fn real_value(&self, strings: &[(i8, i8)]) -> GpResult<i32> {
+        let tuning = usize::try_from(self.string)
+            .ok()
+            .and_then(|s| s.checked_sub(1))
+            .and_then(|i| strings.get(i));
+        match tuning {
+            Some(s) => Ok(i32::from(self.value) + i32::from(s.1)),
+            None => Err(GpError::InvalidValue {
+                context: "note string",
+                value: self.string as i64,
+            }),
+        }
     }
 }
 
 /// Contains all effects which can be applied to one note.
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct NoteEffect {
     pub accentuated_note: bool,
     pub bend: Option<BendEffect>,
@@ -108,27 +115,27 @@ impl NoteEffect {
     pub(crate) fn is_tremollo_picking(&self) -> bool {
         self.tremolo_picking.is_some()
     }
-    pub(crate) fn is_default(&self) -> bool {
-        let d = NoteEffect::default();
-        self.left_hand_finger == d.left_hand_finger
-            && self.right_hand_finger == d.right_hand_finger
-            && self.bend == d.bend
-            && self.harmonic == d.harmonic
-            && self.grace == d.grace
-            && self.trill == d.trill
-            && self.tremolo_picking == d.tremolo_picking
-            && self.vibrato == d.vibrato
-            && self.slides == d.slides
-            && self.hammer == d.hammer
-            && self.palm_mute == d.palm_mute
-            && self.staccato == d.staccato
-            && self.let_ring == d.let_ring
-    }
     pub(crate) fn is_fingering(&self) -> bool {
         self.left_hand_finger != Fingering::Open || self.right_hand_finger != Fingering::Open
     }
 }
 
+/// Whether the note has effects written in its note effects block: GP3 keeps the vibrato and the harmonics with the beat effects.
+fn has_note_effects(effect: &NoteEffect, version: &(u8, u8, u8)) -> bool {
+    let v3 = effect.is_bend()
+        || effect.hammer
+        || !effect.slides.is_empty()
+        || effect.let_ring
+        || effect.is_grace();
+    v3 || (version.0 > 3
+        && (effect.staccato
+            || effect.palm_mute
+            || effect.is_tremollo_picking()
+            || effect.is_harmonic()
+            || effect.is_trill()
+            || effect.vibrato))
+}
+
 pub trait SongNoteOps {
     fn read_notes(
         &mut self,
@@ -274,7 +281,11 @@ impl SongNoteOps for Song {
     ) -> GpResult<()> {
         let flags = read_byte(data, seek)?;
         note.string = guitar_string.0;
+        note.effect.heavy_accentuated_note = (flags & 0x02) == 0x02;
         note.effect.ghost_note = (flags & 0x04) == 0x04;
+        if self.version.number.0 > 3 {
+            note.effect.accentuated_note = (flags & 0x40) == 0x40;
+        }
         //println!("read_note(), flags: {} \t string: {} \t ghost note: {}", flags, guitar_string.0, note.effect.ghost_note);
         if (flags & 0x20) == 0x20 {
             note.kind = get_note_type(read_byte(data, seek)?);
@@ -667,7 +678,7 @@ impl SongNoteOps for Song {
     }
     fn pack_note_flags(&self, note: &Note, version: &(u8, u8, u8)) -> u8 {
         let mut flags: u8 = 0u8;
-        if note.duration.is_some() && note.tuplet.is_some() {
+        if version.0 < 5 && note.duration.is_some() && note.tuplet.is_some() {
             flags |= 0x01;
         }
         if note.effect.heavy_accentuated_note {
@@ -676,7 +687,7 @@ impl SongNoteOps for Song {
         if note.effect.ghost_note {
             flags |= 0x04;
         }
-        if note.effect.is_default() {
+        if has_note_effects(&note.effect, version) {
             flags |= 0x08;
         }
         if note.velocity != DEFAULT_VELOCITY {
@@ -750,22 +761,22 @@ impl SongNoteOps for Song {
             flags2 |= 0x01;
         }
         if note.effect.palm_mute {
-            flags2 |= 0x01;
+            flags2 |= 0x02;
         }
         if note.effect.is_tremollo_picking() {
-            flags2 |= 0x01;
+            flags2 |= 0x04;
         }
         if !note.effect.slides.is_empty() {
-            flags2 |= 0x01;
+            flags2 |= 0x08;
         }
         if note.effect.is_harmonic() {
-            flags2 |= 0x01;
+            flags2 |= 0x10;
         }
         if note.effect.is_trill() {
-            flags2 |= 0x01;
+            flags2 |= 0x20;
         }
         if note.effect.vibrato {
-            flags2 |= 0x01;
+            flags2 |= 0x40;
         }
         write_signed_byte(data, flags2);
 
@@ -782,10 +793,11 @@ impl SongNoteOps for Song {
         if (flags2 & 0x04) == 0x04 {
             if let Some(tp) = &note.effect.tremolo_picking {
                 let duration_val = tp.duration.value.to_u8_gp("tremolo picking duration")?;
+                //the values of the sixteenth and thirty-second are swapped, see `from_tremolo_value`
                 let encoded = match duration_val {
                     DURATION_EIGHTH => 1,
-                    DURATION_SIXTEENTH => 2,
-                    DURATION_THIRTY_SECOND => 3,
+                    DURATION_SIXTEENTH => 3,
+                    DURATION_THIRTY_SECOND => 2,
                     _ => {
                         return Err(GpError::WriteError(format!(
                             "Invalid tremolo picking duration: {}",
@@ -4,7 +4,9 @@ use crate::error::GpResult;
 use crate::{io::primitive::*, model::song::*};
 
 ///A padding construct
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct Padding {
     pub right: u16,
     pub top: u16,
@@ -13,7 +15,9 @@ pub struct Padding {
 }
 
 /// A point construct using integer coordinates
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct Point {
     pub x: u16,
     pub y: u16,
@@ -48,7 +52,9 @@ pub const HEADER_FOOTER_ALL: u16 = 0x1ff;
 /// - ``%copyright%``: will be replaced with Song.copyright
 /// - ``%N%``: will be replaced with the current page number (if supported by layout)
 /// - ``%P%``: will be replaced with the number of pages (if supported by layout)
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct PageSetup {
     pub page_size: Point,
     pub page_margin: Padding,
@@ -122,18 +128,18 @@ impl SongPageOps for Song {
         self.page_setup.page_margin.bottom = read_int(data, seek)?.to_u16().unwrap();
         self.page_setup.score_size_proportion = read_int(data, seek)?.to_f32().unwrap() / 100.0;
         self.page_setup.header_and_footer = read_short(data, seek)?.to_u16().unwrap();
-        self.page_setup.title = read_int_size_string(data, seek)?;
-        self.page_setup.subtitle = read_int_size_string(data, seek)?;
-        self.page_setup.artist = read_int_size_string(data, seek)?;
-        self.page_setup.album = read_int_size_string(data, seek)?;
-        self.page_setup.words = read_int_size_string(data, seek)?;
-        self.page_setup.music = read_int_size_string(data, seek)?;
-        self.page_setup.word_and_music = read_int_size_string(data, seek)?;
-        let mut c = read_int_size_string(data, seek)?;
+        self.page_setup.title = read_int_byte_size_string(data, seek)?;
+        self.page_setup.subtitle = read_int_byte_size_string(data, seek)?;
+        self.page_setup.artist = read_int_byte_size_string(data, seek)?;
+        self.page_setup.album = read_int_byte_size_string(data, seek)?;
+        self.page_setup.words = read_int_byte_size_string(data, seek)?;
+        self.page_setup.music = read_int_byte_size_string(data, seek)?;
+        self.page_setup.word_and_music = read_int_byte_size_string(data, seek)?;
+        let mut c = read_int_byte_size_string(data, seek)?;
         c.push('\n');
-        c.push_str(&read_int_size_string(data, seek)?);
+        c.push_str(&read_int_byte_size_string(data, seek)?);
         self.page_setup.copyright = c;
-        self.page_setup.page_number = read_int_size_string(data, seek)?;
+        self.page_setup.page_number = read_int_byte_size_string(data, seek)?;
         Ok(())
     }
 
@@ -148,23 +154,12 @@ impl SongPageOps for Song {
         write_i32(
             data,
             (self.page_setup.score_size_proportion * 100f32)
-                .ceil()
+                .round()
                 .to_i32()
                 .unwrap(),
         );
 
-        write_byte(
-            data,
-            (self.page_setup.header_and_footer & 0xff).to_u8().unwrap(),
-        );
-
-        let mut flags2 = 0u8;
-        if self.page_setup.header_and_footer != 0
-            && (self.page_setup.header_and_footer & HEADER_FOOTER_PAGE_NUMBER) != 0
-        {
-            flags2 |= 0x01;
-        } //TODO: check
-        write_byte(data, flags2);
+        write_i16(data, self.page_setup.header_and_footer as i16);
         write_int_byte_size_string(data, &self.page_setup.title);
         write_int_byte_size_string(data, &self.page_setup.subtitle);
         write_int_byte_size_string(data, &self.page_setup.artist);
@@ -172,9 +167,9 @@ impl SongPageOps for Song {
         write_int_byte_size_string(data, &self.page_setup.words);
         write_int_byte_size_string(data, &self.page_setup.music);
         write_int_byte_size_string(data, &self.page_setup.word_and_music);
-        let c: Vec<&str> = self.page_setup.copyright.split('\n').collect();
-        write_int_byte_size_string(data, c[0]);
-        write_int_byte_size_string(data, c[1]);
+        let mut c = self.page_setup.copyright.splitn(2, '\n');
+        write_int_byte_size_string(data, c.next().unwrap_or_default());
+        write_int_byte_size_string(data, c.next().unwrap_or_default());
         write_int_byte_size_string(data, &self.page_setup.page_number);
     }
 }
@@ -11,7 +11,9 @@ use crate::{
 ///
 /// Attribute :attr:`RSEEqualizer.knobs` is a list of values in range from -6.0 to 5.9. Master effect has 10 knobs, track effect has 3
 /// knobs. Gain is a value in range from -6.0 to 5.9 which can be found in both master and track effects and is named as "PRE" in Guitar Pro 5.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct RseEqualizer {
     pub knobs: Vec<f32>,
     pub gain: f32,
@@ -26,7 +28,9 @@ impl Default for RseEqualizer {
 }
 
 /// Master effect as seen in "Score information"
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct RseMasterEffect {
     pub volume: f32,
     pub reverb: f32,
@@ -46,6 +50,8 @@ impl Default for RseMasterEffect {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct RseInstrument {
     pub instrument: i16,
     pub unknown: i16,
@@ -67,12 +73,36 @@ impl Default for RseInstrument {
     }
 }
 
-#[derive(Debug, Clone)]
+/// Effect of a Guitar Pro 6 or 7 effect chain, e.g. `E30_EqGEq` for the graphic equalizer.
+///
+/// Parameters are values from 0.0 to 1.0. An empty slot of the chain has no id.
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct RseEffect {
+    pub id: String,
+    pub parameters: Vec<f32>,
+}
+
+/// Track RSE settings.
+///
+/// Guitar Pro 6 and 7 sounds are kept in the same place: the MIDI program is the instrument, the
+/// name of the sound (or of the Guitar Pro 6 effect chain) is the effect and its path in the
+/// sound library is the effect category, so they survive a conversion to Guitar Pro 5. The sound
+/// bank patch, role and effect chain are only stored in GPIF files.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct TrackRse {
     pub instrument: RseInstrument,
     pub equalizer: RseEqualizer,
     pub humanize: u8,
     pub auto_accentuation: Accentuation,
+    /// Sound bank patch of the sound, e.g. `Concerto-Nylon`
+    pub sound_bank_patch: String,
+    /// Role of a Guitar Pro 7 sound: `Factory` or `User`
+    pub sound_role: String,
+    pub effects: Vec<RseEffect>,
 }
 impl Default for TrackRse {
     fn default() -> Self {
@@ -84,6 +114,9 @@ impl Default for TrackRse {
                 knobs: vec![0.0; 3],
                 ..Default::default()
             },
+            sound_bank_patch: String::new(),
+            sound_role: String::new(),
+            effects: Vec::new(),
         }
     }
 }
@@ -106,7 +139,7 @@ pub trait SongRseOps {
         instrument: &mut RseInstrument,
     ) -> GpResult<()>;
     fn write_rse_master_effect(&self, data: &mut Vec<u8>);
-    fn write_equalizer(&self, data: &mut Vec<u8>, equalizer: &RseEqualizer);
+    fn write_equalizer(&self, data: &mut Vec<u8>, equalizer: &RseEqualizer, knobs: u8);
     fn pack_volume_value(&self, value: f32) -> i8;
     fn write_master_reverb(&self, data: &mut Vec<u8>);
     fn write_track_rse(&self, data: &mut Vec<u8>, rse: &TrackRse, version: &(u8, u8, u8));
@@ -142,11 +175,12 @@ impl SongRseOps for Song {
         knobs: u8,
     ) -> GpResult<RseEqualizer> {
         let mut e = RseEqualizer::default();
-        for _ in 0..knobs {
+        for _ in 1..knobs {
             e.knobs
                 .push(self.unpack_volume_value(read_signed_byte(data, seek)?));
-        } //knobs = list(map(self.unpackVolumeValue, self.readSignedByte(count=knobsNumber)))
-        Ok(e) //return gp.RSEEqualizer(knobs=knobs[:-1], gain=knobs[-1])
+        }
+        e.gain = self.unpack_volume_value(read_signed_byte(data, seek)?);
+        Ok(e)
     }
     /// Unpack equalizer volume value. Equalizer volumes are float but stored as `SignedBytes <signed-byte>`.
     fn unpack_volume_value(&self, value: i8) -> f32 {
@@ -219,11 +253,13 @@ impl SongRseOps for Song {
             },
         );
         write_i32(data, 0); //reverb?
-        self.write_equalizer(data, &self.master_effect.equalizer);
+        self.write_equalizer(data, &self.master_effect.equalizer, 11);
     }
-    fn write_equalizer(&self, data: &mut Vec<u8>, equalizer: &RseEqualizer) {
-        for i in 0..equalizer.knobs.len() {
-            write_signed_byte(data, self.pack_volume_value(equalizer.knobs[i]));
+    /// Write the equalizer as `knobs` signed bytes, the last one being the gain. Missing bands are written flat.
+    fn write_equalizer(&self, data: &mut Vec<u8>, equalizer: &RseEqualizer, knobs: u8) {
+        for i in 1..usize::from(knobs) {
+            let knob = equalizer.knobs.get(i - 1).copied().unwrap_or(0.0);
+            write_signed_byte(data, self.pack_volume_value(knob));
         }
         write_signed_byte(data, self.pack_volume_value(equalizer.gain));
     }
@@ -242,7 +278,7 @@ impl SongRseOps for Song {
         write_placeholder_default(data, 12);
         self.write_rse_instrument(data, &rse.instrument, version);
         if version > &(5, 0, 0) {
-            self.write_equalizer(data, &rse.equalizer);
+            self.write_equalizer(data, &rse.equalizer, 4);
             self.write_rse_instrument_effect(data, &rse.instrument);
         }
     }
@@ -1,9 +1,11 @@
 use fraction::ToPrimitive;
 
 use crate::audio::midi::*;
-use crate::error::{GpResult, ToPrimitiveGp};
+use crate::error::{GpError, GpResult, ToPrimitiveGp};
+use crate::io::gpif::GpifSource;
 use crate::io::gpif_import::*;
 use crate::io::primitive::*;
+use crate::model::conversion::LostItem;
 use crate::model::enums::*;
 use crate::model::headers::*;
 use crate::model::key_signature::*;
@@ -15,6 +17,8 @@ use crate::model::track::*;
 
 // Struct utility to read file: https://stackoverflow.com/questions/55555538/what-is-the-correct-way-to-read-a-binary-file-in-chunks-of-a-fixed-size-and-stor
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct Song {
     pub version: Version,
     pub clipboard: Option<Clipboard>,
@@ -48,10 +52,22 @@ pub struct Song {
 
     pub page_setup: PageSetup,
 
+    /// GP6/GP7 document the song was read from, its content not mapped by the model is written
+    /// back by `write_gp` and `write_gpx`
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub gpif_source: Option<GpifSource>,
+    /// Content of the file read that the model cannot hold, such as notes that fit on no string
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub read_losses: Vec<LostItem>,
+
     //Used to read the file
+    #[cfg_attr(feature = "serde", serde(skip))]
     pub current_measure_number: Option<usize>,
+    #[cfg_attr(feature = "serde", serde(skip))]
     pub current_track: Option<usize>,
+    #[cfg_attr(feature = "serde", serde(skip))]
     pub current_voice_number: Option<usize>,
+    #[cfg_attr(feature = "serde", serde(skip))]
     pub current_beat_number: Option<usize>,
 }
 
@@ -93,6 +109,8 @@ impl Default for Song {
             current_beat_number: None,
 
             page_setup: PageSetup::default(),
+            gpif_source: None,
+            read_losses: Vec::new(),
 
             master_effect: RseMasterEffect::default(),
         }
@@ -184,7 +202,7 @@ impl Song {
         self.lyrics = self.read_lyrics(data, &mut seek)?; //read lyrics
         self.master_effect = self.read_rse_master_effect(data, &mut seek)?;
         self.read_page_setup(data, &mut seek)?;
-        self.tempo_name = read_int_size_string(data, &mut seek)?;
+        self.tempo_name = read_int_byte_size_string(data, &mut seek)?;
         self.tempo = read_int(data, &mut seek)?.to_i16().unwrap();
         self.hide_tempo = if self.version.number > (5, 0, 0) {
             read_bool(data, &mut seek)?
@@ -199,15 +217,9 @@ impl Song {
         let measure_count = read_int(data, &mut seek)?.to_usize().unwrap();
         let track_count = read_int(data, &mut seek)?.to_usize().unwrap();
         //println!("{} {} {} {:?}", self.tempo_name, self.tempo, self.hide_tempo, self.key.key); //OK
-        println!(
-            "Track count: {} \t Measure count: {}",
-            track_count, measure_count
-        ); //OK
         self.read_measure_headers_v5(data, &mut seek, measure_count, &directions)?;
         self.read_tracks_v5(data, &mut seek, track_count)?;
-        println!("read_gp5(), after tracks   \t seek: {}", seek);
         self.read_measures(data, &mut seek)?;
-        println!("read_gp5(), after measures \t seek: {}", seek);
         Ok(())
     }
     /// Read Guitar Pro 7+ file (.gp)
@@ -226,6 +238,63 @@ impl Song {
         self.read_gpif(&gpif);
         Ok(())
     }
+    /// Read MusicXML file (.musicxml, .xml)
+    pub fn read_musicxml(&mut self, data: &[u8]) -> GpResult<()> {
+        use crate::io::musicxml_import::{decode_musicxml, SongMusicXmlOps};
+        self.read_musicxml_document(&decode_musicxml(data)?)
+    }
+    /// Read compressed MusicXML file (.mxl)
+    pub fn read_mxl(&mut self, data: &[u8]) -> GpResult<()> {
+        use crate::io::musicxml_import::{read_mxl, SongMusicXmlOps};
+        self.read_musicxml_document(&read_mxl(data)?)
+    }
+    /// Read MuseScore file (.mscx)
+    pub fn read_mscx(&mut self, data: &[u8]) -> GpResult<()> {
+        use crate::io::musescore_import::{decode_mscx, SongMuseScoreOps};
+        self.read_mscx_document(&decode_mscx(data)?)
+    }
+    /// Read compressed MuseScore file (.mscz)
+    pub fn read_mscz(&mut self, data: &[u8]) -> GpResult<()> {
+        use crate::io::musescore_import::{read_mscz, SongMuseScoreOps};
+        self.read_mscx_document(&read_mscz(data)?)
+    }
+
+    /// Write the song as a Guitar Pro 7 file (`.gp`): a ZIP archive holding `Content/score.gpif`.
+    pub fn write_gp(&self) -> GpResult<Vec<u8>> {
+        use crate::io::gpif_export::SongGpifExportOps;
+        use crate::io::gpx::write_gp;
+        write_gp(&self.write_gpif((7, 0, 0)))
+    }
+    /// Write the song as a Guitar Pro 6 file (`.gpx`): a BCFZ compressed container holding `score.gpif`.
+    pub fn write_gpx(&self) -> GpResult<Vec<u8>> {
+        use crate::io::gpif_export::SongGpifExportOps;
+        use crate::io::gpx::write_gpx;
+        write_gpx(&self.write_gpif((6, 0, 0)))
+    }
+    /// Check that every played note is on a string of its track, for the writers that compute the
+    /// pitch of the notes from the tuning.
+    pub(crate) fn check_note_strings(&self) -> GpResult<()> {
+        for (t_idx, track) in self.tracks.iter().enumerate() {
+            for (m_idx, measure) in track.measures.iter().enumerate() {
+                let missing = measure
+                    .voices
+                    .iter()
+                    .flat_map(|v| &v.beats)
+                    .flat_map(|b| &b.notes)
+                    .filter(|n| n.kind != NoteType::Rest)
+                    .find(|n| n.real_value(&track.strings).is_err());
+                if let Some(note) = missing {
+                    return Err(GpError::WriteError(format!(
+                        "track {} measure {} has a note on string {} that the track does not have",
+                        t_idx + 1,
+                        m_idx + 1,
+                        note.string
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
 
     /// Read information (name, artist, ...)
     fn read_info(&mut self, data: &[u8], seek: &mut usize) -> GpResult<()> {
@@ -245,9 +314,9 @@ impl Song {
                                                                     //notices
         let nc = read_int(data, seek)?.to_usize().unwrap(); //notes count
         if nc > 0 {
-            for i in 0..nc {
+            for _i in 0..nc {
                 self.notice.push(read_int_byte_size_string(data, seek)?);
-                println!("  {}\t\t{}", i, self.notice[self.notice.len() - 1]);
+                //println!("  {}\t\t{}", i, self.notice[self.notice.len() - 1]);
             }
         }
         Ok(())
@@ -259,7 +328,31 @@ impl Song {
     pub const _MIN_OFFSET: i32 = -24;*/
 
     /// Write data to a Vec<u8>, you are free to use the encoded data to write it in a file or in a database or do something else.
+    /// The song is checked first: every track must have one measure per measure header and a channel in the 64-channel table.
     pub fn write(&self, version: (u8, u8, u8), clipboard: Option<bool>) -> GpResult<Vec<u8>> {
+        if !VERSIONS.iter().any(|v| v.0 == version) {
+            return Err(GpError::WriteError(format!(
+                "unknown Guitar Pro version {:?}",
+                version
+            )));
+        }
+        for (i, track) in self.tracks.iter().enumerate() {
+            if track.measures.len() != self.measure_headers.len() {
+                return Err(GpError::WriteError(format!(
+                    "track {} has {} measures for {} measure headers",
+                    i + 1,
+                    track.measures.len(),
+                    self.measure_headers.len()
+                )));
+            }
+            if track.channel_index >= 64 {
+                return Err(GpError::WriteError(format!(
+                    "track {} uses channel {} out of the 64-channel table",
+                    i + 1,
+                    track.channel_index
+                )));
+            }
+        }
         let mut data: Vec<u8> = Vec::with_capacity(8388608); //capacity of 8MB, should be sufficient
         write_version(&mut data, version);
         if clipboard.is_some_and(|c| c) && version.0 >= 4 {
@@ -288,8 +381,7 @@ impl Song {
         if version.0 >= 4 {
             write_signed_byte(&mut data, 0);
         } //octave
-        self.write_midi_channels(&mut data); //TODO: fixme for writing
-                                             //return data;
+        self.write_midi_channels(&mut data);
 
         if version.0 == 5 {
             self.write_directions(&mut data);
@@ -298,7 +390,7 @@ impl Song {
 
         write_i32(
             &mut data,
-            self.tracks[0].measures.len().to_i32_gp("measures count")?,
+            self.measure_headers.len().to_i32_gp("measures count")?,
         );
         write_i32(&mut data, self.tracks.len().to_i32_gp("tracks count")?);
         self.write_measure_headers(&mut data, &version);
@@ -9,6 +9,8 @@ use crate::{
 
 /// Settings of the track.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct TrackSettings {
     pub tablature: bool,
     pub notation: bool,
@@ -40,7 +42,30 @@ impl Default for TrackSettings {
     }
 }
 
+/// Staff of a track after the first one, such as the bass staff of a piano (GP7). The first
+/// staff is described by the track itself, the notes of this one are on its own strings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
+pub struct TrackStaff {
+    pub strings: Vec<(i8, i8)>,
+    /// Capo fret
+    pub offset: i32,
+    pub fret_count: u8,
+}
+impl Default for TrackStaff {
+    fn default() -> Self {
+        TrackStaff {
+            strings: Track::default().strings,
+            offset: 0,
+            fret_count: 24,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "json-schema", derive(schemars::JsonSchema))]
 pub struct Track {
     pub number: i32,
     pub offset: i32,
@@ -69,6 +94,12 @@ pub struct Track {
     pub transpose_chromatic: i32,
     /// Octave transposition (GP6/GP7)
     pub transpose_octave: i32,
+    /// Staves after the first one (GP7), their content is in `Measure::staves`
+    pub staves: Vec<TrackStaff>,
+    /// Position of the track in the GPIF document the song was read from, used to write back its
+    /// content not mapped by the model
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub gpif_index: Option<usize>,
 }
 impl Default for Track {
     fn default() -> Self {
@@ -96,9 +127,121 @@ impl Default for Track {
             midi_program_gpif: None,
             transpose_chromatic: 0,
             transpose_octave: 0,
+            staves: Vec::new(),
+            gpif_index: None,
         }
     }
 }
+impl Track {
+    /// The track as read on one of its other staves: the strings, capo and fret count of the staff,
+    /// without measures.
+    pub(crate) fn staff_view(&self, staff: &TrackStaff) -> Track {
+        Track {
+            number: self.number,
+            offset: staff.offset,
+            channel_index: self.channel_index,
+            solo: self.solo,
+            mute: self.mute,
+            visible: self.visible,
+            name: self.name.clone(),
+            short_name: self.short_name.clone(),
+            strings: staff.strings.clone(),
+            color: self.color,
+            percussion_track: self.percussion_track,
+            twelve_stringed_guitar_track: self.twelve_stringed_guitar_track,
+            banjo_track: self.banjo_track,
+            port: self.port,
+            fret_count: staff.fret_count,
+            indicate_tuning: self.indicate_tuning,
+            use_rse: self.use_rse,
+            rse: self.rse.clone(),
+            measures: Vec::new(),
+            settings: self.settings.clone(),
+            midi_program_gpif: self.midi_program_gpif,
+            transpose_chromatic: self.transpose_chromatic,
+            transpose_octave: self.transpose_octave,
+            staves: Vec::new(),
+            gpif_index: None,
+        }
+    }
+
+    /// String and fret of each MIDI pitch of a chord on the strings of the track, `None` for the
+    /// pitches that cannot be played. The chord with the smallest stretch is chosen, then the one
+    /// closest to the `hand` position (the fret of the previous chord), or the lowest one.
+    pub fn fret_positions(&self, pitches: &[i32], hand: Option<i16>) -> Vec<Option<(i8, i16)>> {
+        fn search(
+            track: &Track,
+            pitches: &[i32],
+            hand: Option<i16>,
+            current: &mut Vec<Option<(i8, i16)>>,
+            best: &mut (i32, Vec<Option<(i8, i16)>>),
+        ) {
+            // The position can only add to the cost of the notes placed so far
+            if fret_positions_cost(current, None, false) >= best.0 {
+                return;
+            }
+            let Some(&pitch) = pitches.get(current.len()) else {
+                let cost = fret_positions_cost(current, hand, true);
+                if cost < best.0 {
+                    *best = (cost, current.clone());
+                }
+                return;
+            };
+            for &(string, tuning) in &track.strings {
+                let fret = pitch - i32::from(tuning);
+                if !(0..=i32::from(track.fret_count)).contains(&fret)
+                    || current.iter().flatten().any(|p| p.0 == string)
+                {
+                    continue;
+                }
+                current.push(Some((string, fret as i16)));
+                search(track, pitches, hand, current, best);
+                current.pop();
+            }
+            current.push(None);
+            search(track, pitches, hand, current, best);
+            current.pop();
+        }
+        // A string plays one note: the pitches after the string count are dropped
+        let searched = &pitches[..pitches.len().min(self.strings.len())];
+        let mut best = (i32::MAX, vec![None; searched.len()]);
+        search(
+            self,
+            searched,
+            hand,
+            &mut Vec::with_capacity(searched.len()),
+            &mut best,
+        );
+        best.1.resize(pitches.len(), None);
+        best.1
+    }
+}
+
+/// Cost of a chord shape: dropped notes first, then the stretch of the hand and, with `position`,
+/// its position.
+fn fret_positions_cost(positions: &[Option<(i8, i16)>], hand: Option<i16>, position: bool) -> i32 {
+    let dropped = positions.iter().filter(|p| p.is_none()).count() as i32;
+    let frets: Vec<i32> = positions
+        .iter()
+        .flatten()
+        .map(|p| i32::from(p.1))
+        .filter(|f| *f > 0)
+        .collect();
+    let (Some(min), Some(max)) = (frets.iter().min(), frets.iter().max()) else {
+        return dropped * 1000;
+    };
+    let span = max - min;
+    let stretch = span * 10 + (span - 3).max(0) * 50;
+    if !position {
+        return dropped * 1000 + stretch;
+    }
+    let center = frets.iter().sum::<i32>() / frets.len() as i32;
+    let position = match hand {
+        Some(hand) if hand > 0 => (center - i32::from(hand)).abs() * 2,
+        _ => center,
+    };
+    dropped * 1000 + stretch + position
+}
 
 pub trait SongTrackOps {
     fn read_tracks(&mut self, data: &[u8], seek: &mut usize, track_count: usize) -> GpResult<()>;
@@ -180,8 +323,8 @@ impl SongTrackOps for Song {
         }
         //println!("tuning: {:?}", track.strings);
         track.port = read_int(data, seek)?.to_u8().unwrap();
-        let index = self.read_channel(data, seek)?;
-        if self.channels[index].channel == 9 {
+        track.channel_index = self.read_channel(data, seek)?;
+        if self.channels[track.channel_index].channel == 9 {
             track.percussion_track = true;
         }
         track.fret_count = read_int(data, seek)?.to_u8().unwrap();
@@ -240,8 +383,9 @@ impl SongTrackOps for Song {
         let flags1 = read_byte(data, seek)?;
         //println!("read_track_v5(), flags1: {} \t seek: {}", flags1, *seek);
         track.percussion_track = (flags1 & 0x01) == 0x01;
-        track.banjo_track = (flags1 & 0x02) == 0x02;
-        track.visible = (flags1 & 0x04) == 0x04;
+        track.twelve_stringed_guitar_track = (flags1 & 0x02) == 0x02;
+        track.banjo_track = (flags1 & 0x04) == 0x04;
+        track.visible = (flags1 & 0x08) == 0x08;
         track.solo = (flags1 & 0x10) == 0x10;
         track.mute = (flags1 & 0x20) == 0x20;
         track.use_rse = (flags1 & 0x40) == 0x40;
@@ -259,8 +403,8 @@ impl SongTrackOps for Song {
             }
         }
         track.port = read_int(data, seek)?.to_u8().unwrap();
-        self.read_channel(data, seek)?;
-        if self.channels[number].channel == 9 {
+        track.channel_index = self.read_channel(data, seek)?;
+        if self.channels[track.channel_index].channel == 9 {
             track.percussion_track = true;
         }
         track.fret_count = read_int(data, seek)?.to_u8().unwrap();
@@ -283,7 +427,7 @@ impl SongTrackOps for Song {
         track.settings.extend_rythmic = (flags2 & 0x0800) == 0x0800;
 
         track.rse.auto_accentuation = get_accentuation(read_byte(data, seek)?)?;
-        self.channels[number].bank = read_byte(data, seek)?;
+        self.channels[track.channel_index].bank = read_byte(data, seek)?;
         self.read_track_rse(data, seek, &mut track)?;
         self.tracks.push(track);
         Ok(())
@@ -315,8 +459,7 @@ impl SongTrackOps for Song {
             flags |= 0x04;
         }
         write_byte(data, flags);
-        write_byte_size_string(data, &self.tracks[number].name);
-        write_placeholder_default(data, 30 - self.tracks[number].name.len());
+        write_byte_size_string(data, &self.tracks[number].name, 40);
         write_i32(data, self.tracks[number].strings.len().to_i32().unwrap());
         for i in 0..7usize {
             let mut tuning = 0i8;
@@ -329,7 +472,7 @@ impl SongTrackOps for Song {
         //write channel
         write_i32(
             data,
-            self.channels[self.tracks[number].channel_index]
+            self.channel_at(self.tracks[number].channel_index)
                 .channel
                 .to_i32()
                 .unwrap()
@@ -337,7 +480,7 @@ impl SongTrackOps for Song {
         );
         write_i32(
             data,
-            self.channels[self.tracks[number].channel_index]
+            self.channel_at(self.tracks[number].channel_index)
                 .effect_channel
                 .to_i32()
                 .unwrap()
@@ -349,7 +492,7 @@ impl SongTrackOps for Song {
         write_color(data, self.tracks[number].color);
     }
     fn write_track_v5(&self, data: &mut Vec<u8>, number: usize, version: &(u8, u8, u8)) {
-        if number == 1 || version == &(5, 0, 0) {
+        if number == 0 || version == &(5, 0, 0) {
             write_placeholder_default(data, 1);
         }
         let mut flags1 = 0u8;
@@ -379,8 +522,7 @@ impl SongTrackOps for Song {
         }
         write_byte(data, flags1);
 
-        write_byte_size_string(data, &self.tracks[number].name);
-        write_placeholder_default(data, 40 - self.tracks[number].name.len());
+        write_byte_size_string(data, &self.tracks[number].name, 40);
 
         write_i32(data, self.tracks[number].strings.len().to_i32().unwrap());
         for i in 0..7usize {
@@ -394,7 +536,7 @@ impl SongTrackOps for Song {
         //write channel
         write_i32(
             data,
-            self.channels[self.tracks[number].channel_index]
+            self.channel_at(self.tracks[number].channel_index)
                 .channel
                 .to_i32()
                 .unwrap()
@@ -402,7 +544,7 @@ impl SongTrackOps for Song {
         );
         write_i32(
             data,
-            self.channels[self.tracks[number].channel_index]
+            self.channel_at(self.tracks[number].channel_index)
                 .effect_channel
                 .to_i32()
                 .unwrap()
@@ -453,7 +595,10 @@ impl SongTrackOps for Song {
             data,
             from_accentuation(&self.tracks[number].rse.auto_accentuation),
         );
-        write_byte(data, self.channels[self.tracks[number].channel_index].bank);
+        write_byte(
+            data,
+            self.channel_at(self.tracks[number].channel_index).bank,
+        );
         self.write_track_rse(data, &self.tracks[number].rse, version);
     }
 }
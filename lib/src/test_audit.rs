@@ -1,3 +1,5 @@
+use crate::io::ascii_tab_import::SongAsciiTabOps;
+use crate::io::powertab_import::SongPowerTabOps;
 use crate::Song;
 use std::fs;
 use std::path::Path;
@@ -59,6 +61,18 @@ fn test_audit_all_files() {
                 "gpx" => {
                     let _ = song.read_gpx(&data);
                 }
+                "mscx" => {
+                    let _ = song.read_mscx(&data);
+                }
+                "mscz" => {
+                    let _ = song.read_mscz(&data);
+                }
+                "ptb" => {
+                    let _ = song.read_ptb(&data);
+                }
+                "txt" => {
+                    let _ = song.read_ascii_tab(&String::from_utf8_lossy(&data));
+                }
                 _ => return "SKIP".to_string(),
             }
             "OK".to_string()
@@ -1203,6 +1203,30 @@ fn test_gpx_all_files_parse() {
     );
 }
 
+#[test]
+fn test_gpx_write_round_trip() {
+    for path in test_files("gpx") {
+        let fname = path.file_name().unwrap().to_str().unwrap().to_string();
+        let mut song = Song::default();
+        song.read_gpx(&fs::read(&path).unwrap()).unwrap();
+        let mut reread = Song::default();
+        reread.read_gpx(&song.write_gpx().unwrap()).unwrap();
+        assert_gpif_round_trip(&song, &reread, &fname);
+    }
+}
+
+#[test]
+fn test_gpx_write_container_limits() {
+    use crate::io::gpx::build_bcfs;
+    // The root directory holds 1024 entries, a file entry 986 blocks
+    let names: Vec<String> = (0..1025).map(|i| format!("Content/{}", i)).collect();
+    let files: Vec<(&str, &[u8])> = names.iter().map(|n| (n.as_str(), &b""[..])).collect();
+    assert!(build_bcfs(&files[..1024]).is_ok());
+    assert!(build_bcfs(&files).is_err());
+    let large = vec![0u8; 987 * 0x1000];
+    assert!(build_bcfs(&[("Content/score.gpif", &large[..])]).is_err());
+}
+
 // ==================== GP7 (Guitar Pro 7+) tests ====================
 
 fn read_gp7(filename: &str) -> Song {
@@ -1844,3 +1868,3060 @@ fn test_gp7_all_files_parse() {
         failures.len()
     );
 }
+
+/// Compare what the GPIF writers are expected to keep when a song is written then read back.
+fn assert_gpif_round_trip(song: &Song, reread: &Song, fname: &str) {
+    assert_eq!(song.name, reread.name, "{}", fname);
+    assert_eq!(song.tempo, reread.tempo, "{}", fname);
+    assert_eq!(song.tracks.len(), reread.tracks.len(), "{}", fname);
+    assert_eq!(
+        song.measure_headers.len(),
+        reread.measure_headers.len(),
+        "{}",
+        fname
+    );
+    for (a, b) in song.measure_headers.iter().zip(&reread.measure_headers) {
+        assert_eq!(a.tempo, b.tempo, "{}: measure {}", fname, a.number);
+        assert_eq!(
+            a.repeat_open, b.repeat_open,
+            "{}: measure {}",
+            fname, a.number
+        );
+        assert_eq!(
+            a.repeat_close, b.repeat_close,
+            "{}: measure {}",
+            fname, a.number
+        );
+        assert_eq!(a.repeat_alternative, b.repeat_alternative, "{}", fname);
+        assert_eq!(a.direction, b.direction, "{}: measure {}", fname, a.number);
+    }
+    for (a, b) in song.tracks.iter().zip(&reread.tracks) {
+        assert_eq!(a.name, b.name, "{}", fname);
+        assert_eq!(a.strings, b.strings, "{}: {}", fname, a.name);
+        assert_eq!(a.midi_program_gpif, b.midi_program_gpif, "{}", fname);
+        for (ma, mb) in a.measures.iter().zip(&b.measures) {
+            assert_eq!(
+                ma.voices.len(),
+                mb.voices.len(),
+                "{}: measure {}",
+                fname,
+                ma.number
+            );
+            for (va, vb) in ma.voices.iter().zip(&mb.voices) {
+                assert!(va.beats == vb.beats, "{}: measure {}", fname, ma.number);
+            }
+        }
+    }
+}
+
+fn test_files(extension: &str) -> Vec<std::path::PathBuf> {
+    let mut paths: Vec<_> = fs::read_dir("../test")
+        .unwrap()
+        .map(|e| e.unwrap().path())
+        .filter(|p| p.extension().is_some_and(|e| e == extension))
+        .collect();
+    paths.sort();
+    paths
+}
+
+#[test]
+fn test_gp7_write_round_trip() {
+    for path in test_files("gp") {
+        let fname = path.file_name().unwrap().to_str().unwrap().to_string();
+        let mut song = Song::default();
+        song.read_gp(&fs::read(&path).unwrap()).unwrap();
+        let mut reread = Song::default();
+        reread.read_gp(&song.write_gp().unwrap()).unwrap();
+        assert_gpif_round_trip(&song, &reread, &fname);
+    }
+}
+
+#[test]
+fn test_gp5_write_gp7() {
+    let mut song = Song::default();
+    song.read_gp5(&read_file(String::from("test/Demo v5.gp5")))
+        .unwrap();
+    let mut reread = Song::default();
+    reread.read_gp(&song.write_gp().unwrap()).unwrap();
+    assert_eq!(song.name, reread.name);
+    assert_eq!(song.tracks.len(), reread.tracks.len());
+    assert_eq!(song.measure_headers.len(), reread.measure_headers.len());
+    for (a, b) in song.tracks.iter().zip(&reread.tracks) {
+        assert_eq!(a.name, b.name);
+        assert_eq!(a.strings, b.strings);
+    }
+}
+
+#[test]
+fn test_gp3_write_gp7_keeps_flams() {
+    // Grace notes on the fret of their main note stay attached to it
+    let mut song = Song::default();
+    song.read_gp3(&read_file(String::from(
+        "test/gamma_ray-heading_for_tomorrow.gp3",
+    )))
+    .unwrap();
+    let mut reread = Song::default();
+    reread.read_gp(&song.write_gp().unwrap()).unwrap();
+    for (a, b) in song.tracks.iter().zip(&reread.tracks) {
+        for (m_a, m_b) in a.measures.iter().zip(&b.measures) {
+            for (v_a, v_b) in m_a.voices.iter().zip(&m_b.voices) {
+                assert_eq!(v_a.beats.len(), v_b.beats.len(), "{}", a.name);
+            }
+        }
+    }
+    let flam = &reread.tracks[3].measures[40].voices[0].beats[8].notes[0];
+    assert_eq!(flam.value, 40);
+    assert_eq!(flam.effect.grace.as_ref().unwrap().fret, 40);
+    assert!(!reread.tracks[3].measures[40].voices[0].beats[8].is_grace);
+}
+
+/// Path of every element of a GPIF tree, properties being told apart by name.
+fn gpif_element_paths(xml: &str) -> std::collections::BTreeSet<String> {
+    use crate::io::xml::XmlElement;
+    fn collect(e: &XmlElement, prefix: &str, paths: &mut std::collections::BTreeSet<String>) {
+        let path = match e.attribute("name") {
+            Some(name) => format!("{}/{}[{}]", prefix, e.name, name),
+            None => format!("{}/{}", prefix, e.name),
+        };
+        for child in &e.children {
+            collect(child, &path, paths);
+        }
+        paths.insert(path);
+    }
+    let mut paths = std::collections::BTreeSet::new();
+    collect(&XmlElement::parse(xml).unwrap(), "", &mut paths);
+    paths
+}
+
+#[test]
+fn test_gp7_write_keeps_unmapped_content() {
+    let mut song = read_gp7("test/keysig.gp");
+    let source = song.gpif_source.clone().unwrap();
+    song.name = String::from("Fixed title");
+    song.artist = String::from("Fixed & artist");
+
+    let written = crate::io::gpx::read_gp(&song.write_gp().unwrap()).unwrap();
+    assert_eq!(written.score.title, "Fixed title");
+    assert_eq!(written.score.artist, "Fixed & artist");
+    let written = written.source.unwrap();
+    let names = |files: &[(String, Vec<u8>)]| -> Vec<String> {
+        let mut names: Vec<String> = files.iter().map(|(n, _)| n.clone()).collect();
+        names.sort();
+        names
+    };
+    assert_eq!(names(&source.files), names(&written.files));
+    assert!(names(&written.files).contains(&String::from("Content/BinaryStylesheet")));
+    for (name, content) in &source.files {
+        let (_, written_content) = written.files.iter().find(|(n, _)| n == name).unwrap();
+        assert_eq!(content, written_content, "{}", name);
+    }
+
+    let source_paths = gpif_element_paths(&source.xml);
+    let written_paths = gpif_element_paths(&written.xml);
+    assert!(source_paths.contains("/GPIF/Score/FirstPageHeader"));
+    let missing: Vec<_> = source_paths.difference(&written_paths).collect();
+    assert!(missing.is_empty(), "Lost elements: {:?}", missing);
+}
+
+#[test]
+fn test_gpif_write_keeps_unmapped_content_all_files() {
+    for path in test_files("gp") {
+        let fname = path.file_name().unwrap().to_str().unwrap().to_string();
+        let mut song = Song::default();
+        song.read_gp(&fs::read(&path).unwrap()).unwrap();
+        let written = crate::io::gpx::read_gp(&song.write_gp().unwrap()).unwrap();
+        let source_paths = gpif_element_paths(&song.gpif_source.unwrap().xml);
+        let written_paths = gpif_element_paths(&written.source.unwrap().xml);
+        let missing: Vec<_> = source_paths.difference(&written_paths).collect();
+        assert!(missing.is_empty(), "{}: lost elements {:?}", fname, missing);
+    }
+}
+
+#[test]
+fn test_gpif_write_drops_removed_content() {
+    let mut song = read_gp7("test/fret-diagram_2instruments.gp");
+    assert_eq!(song.gpif_source.as_ref().unwrap().track_count, 2);
+    for header in song.measure_headers.iter_mut() {
+        header.key_signature.key = 2;
+    }
+    song.tracks.truncate(1);
+
+    let gpif = crate::io::gpx::read_gp(&song.write_gp().unwrap()).unwrap();
+    assert_eq!(gpif.tracks.tracks.len(), 1);
+    for master_bar in &gpif.master_bars.master_bars {
+        assert_eq!(master_bar.key.as_ref().unwrap().accidental_count, 2);
+        assert_eq!(master_bar.bars.split_whitespace().count(), 1);
+    }
+    let files = gpif.source.unwrap().files;
+    let (_, part_configuration) = files
+        .iter()
+        .find(|(name, _)| name == "Content/PartConfiguration")
+        .unwrap();
+    assert_eq!(&part_configuration[0..4], &2i32.to_be_bytes());
+}
+
+#[test]
+fn test_gpif_write_keeps_content_of_moved_items() {
+    use crate::io::xml::XmlElement;
+    // The second track takes the place of the first one with its own instrument and icon
+    let mut song = read_gp7("test/fret-diagram_2instruments.gp");
+    song.tracks.remove(0);
+    let gpif = crate::io::gpx::read_gp(&song.write_gp().unwrap()).unwrap();
+    let xml = XmlElement::parse(&gpif.source.unwrap().xml).unwrap();
+    let tracks = xml_descendants(&xml, "Track");
+    assert_eq!(tracks.len(), 1);
+    let text = |e: &XmlElement, name: &str| e.child(name).map(|c| c.text().trim().to_string());
+    assert_eq!(text(tracks[0], "IconId").as_deref(), Some("2"));
+    assert!(tracks[0].child("AutoBrush").is_none());
+    let instrument_set = tracks[0].child("InstrumentSet").unwrap();
+    assert_eq!(
+        text(instrument_set, "Name").as_deref(),
+        Some("Nylon Guitar")
+    );
+
+    // Timers stay on their beat when the first measure is removed
+    let mut song = read_gp7("test/timer.gp");
+    song.measure_headers.remove(0);
+    song.tracks[0].measures.remove(0);
+    let gpif = crate::io::gpx::read_gp(&song.write_gp().unwrap()).unwrap();
+    let xml = XmlElement::parse(&gpif.source.as_ref().unwrap().xml).unwrap();
+    let timers: Vec<(String, String)> = xml_descendants(&xml, "Beat")
+        .into_iter()
+        .filter_map(|b| Some((b.attribute("id")?.to_string(), text(b, "Timer")?)))
+        .collect();
+    assert_eq!(timers.len(), 2);
+    assert!(timers.iter().all(|(_, timer)| timer != "1"));
+    let (beat, _) = timers.iter().find(|(_, timer)| timer == "9").unwrap();
+    let bar = parse_gpif_ids(&gpif.master_bars.master_bars[3].bars)[0];
+    let voice = parse_gpif_ids(&gpif.bars.bars[bar as usize].voices)[0];
+    let beats = parse_gpif_ids(&gpif.voices.voices[voice as usize].beats);
+    assert_eq!(beats[2].to_string(), *beat);
+}
+
+/// Ids of a GPIF id list.
+fn parse_gpif_ids(list: &str) -> Vec<i32> {
+    list.split_whitespace()
+        .filter_map(|id| id.parse().ok())
+        .collect()
+}
+
+// ==================== MusicXML export tests ====================
+
+fn musicxml_export(song: &Song) -> crate::io::xml::XmlElement {
+    use crate::io::musicxml_export::SongMusicXmlExportOps;
+    let xml = song.write_musicxml().unwrap();
+    assert!(xml.contains("<!DOCTYPE score-partwise"));
+    crate::io::xml::XmlElement::parse(&xml).unwrap()
+}
+
+/// All the descendants of `element` called `name`, in document order.
+fn xml_descendants<'a>(
+    element: &'a crate::io::xml::XmlElement,
+    name: &str,
+) -> Vec<&'a crate::io::xml::XmlElement> {
+    let mut found = Vec::new();
+    for child in &element.children {
+        if child.name == name {
+            found.push(child);
+        }
+        found.extend(xml_descendants(child, name));
+    }
+    found
+}
+
+#[test]
+fn test_musicxml_export_tablature() {
+    let mut song = Song::default();
+    song.read_gp5(&read_file(String::from("test/test.gp5")))
+        .unwrap();
+    let score = musicxml_export(&song);
+    assert_eq!(score.name, "score-partwise");
+    assert_eq!(score.attribute("version"), Some("4.0"));
+    assert_eq!(
+        score
+            .child("work")
+            .unwrap()
+            .child("work-title")
+            .unwrap()
+            .text(),
+        song.name
+    );
+    let parts = xml_descendants(&score, "part");
+    assert_eq!(parts.len(), song.tracks.len());
+    assert_eq!(parts[0].children.len(), song.measure_headers.len());
+
+    let attributes = parts[0].children[0].child("attributes").unwrap();
+    assert_eq!(attributes.child("staves").unwrap().text(), "2");
+    assert!(xml_descendants(attributes, "sign")
+        .iter()
+        .any(|s| s.text() == "TAB"));
+    assert_eq!(
+        xml_descendants(attributes, "staff-tuning").len(),
+        song.tracks[0].strings.len()
+    );
+
+    // Every note is written on both staves with its string and fret
+    let notes = xml_descendants(parts[0], "note");
+    let fretted: Vec<_> = notes
+        .iter()
+        .filter(|n| n.child("rest").is_none() && n.child("grace").is_none())
+        .collect();
+    assert!(!fretted.is_empty());
+    for note in &fretted {
+        let technical = xml_descendants(note, "technical");
+        assert_eq!(technical.len(), 1);
+        assert!(technical[0].child("string").is_some());
+        assert!(technical[0].child("fret").is_some());
+    }
+    let staff_2 = fretted
+        .iter()
+        .filter(|n| n.child("staff").is_some_and(|s| s.text() == "2"))
+        .count();
+    assert_eq!(staff_2 * 2, fretted.len());
+
+    let lyrics = xml_descendants(parts[0], "lyric");
+    assert!(!lyrics.is_empty());
+    assert_eq!(lyrics[0].child("text").unwrap().text(), "bla");
+}
+
+#[test]
+fn test_musicxml_export_repeats() {
+    let mut song = Song::default();
+    song.read_gp5(&read_file(String::from("test/volta.gp5")))
+        .unwrap();
+    let score = musicxml_export(&song);
+    let part = xml_descendants(&score, "part")[0];
+    let repeats = xml_descendants(part, "repeat");
+    let forward = repeats
+        .iter()
+        .filter(|r| r.attribute("direction") == Some("forward"))
+        .count();
+    let backward = repeats.len() - forward;
+    assert_eq!(
+        forward,
+        song.measure_headers
+            .iter()
+            .filter(|h| h.repeat_open)
+            .count()
+    );
+    assert_eq!(
+        backward,
+        song.measure_headers
+            .iter()
+            .filter(|h| h.repeat_close > 0)
+            .count()
+    );
+    let endings = xml_descendants(part, "ending");
+    let starts: Vec<_> = endings
+        .iter()
+        .filter(|e| e.attribute("type") == Some("start"))
+        .collect();
+    assert!(!starts.is_empty());
+    assert_eq!(starts.len(), endings.len() - starts.len());
+    assert_eq!(starts[0].attribute("number"), Some("1"));
+}
+
+#[test]
+fn test_musicxml_export_bends() {
+    let mut song = Song::default();
+    song.read_gp5(&read_file(String::from("test/bend.gp5")))
+        .unwrap();
+    let score = musicxml_export(&song);
+    let bends = xml_descendants(&score, "bend");
+    assert!(!bends.is_empty());
+    assert!(bends.iter().any(|b| b.child("release").is_some()));
+    for bend in bends {
+        let alter: f32 = bend.child("bend-alter").unwrap().text().parse().unwrap();
+        assert!(alter != 0.0 && alter.abs() <= 6.0);
+    }
+}
+
+#[test]
+fn test_musicxml_export_tuplets() {
+    let mut song = Song::default();
+    song.read_gp4(&read_file(String::from("test/testIrrTuplet.gp4")))
+        .unwrap();
+    let score = musicxml_export(&song);
+    assert!(!xml_descendants(&score, "time-modification").is_empty());
+    let tuplets = xml_descendants(&score, "tuplet");
+    let starts = tuplets
+        .iter()
+        .filter(|t| t.attribute("type") == Some("start"))
+        .count();
+    assert!(starts > 0);
+    assert_eq!(starts * 2, tuplets.len());
+}
+
+#[test]
+fn test_musicxml_export_legato() {
+    let song = read_gp7("test/slur_hammer_slur.gp");
+    let score = musicxml_export(&song);
+    let hammers = xml_descendants(&score, "hammer-on");
+    assert!(!hammers.is_empty());
+    let slurs = xml_descendants(&score, "slur");
+    assert_eq!(slurs.len(), hammers.len());
+
+    let mut song = Song::default();
+    song.read_gp5(&read_file(String::from("test/legato-slide.gp5")))
+        .unwrap();
+    let score = musicxml_export(&song);
+    let slides = xml_descendants(&score, "slide");
+    assert!(!slides.is_empty());
+    assert!(slides.iter().any(|s| s.attribute("type") == Some("stop")));
+}
+
+#[test]
+fn test_musicxml_export_measure_durations() {
+    let mut song = Song::default();
+    song.read_gp5(&read_file(String::from("test/Demo v5.gp5")))
+        .unwrap();
+    let score = musicxml_export(&song);
+    // Voices never end past the measure: a backup always returns to the start of the measure
+    for part in xml_descendants(&score, "part") {
+        for measure in &part.children {
+            let mut position = 0i64;
+            for child in &measure.children {
+                let duration =
+                    || -> i64 { child.child("duration").unwrap().text().parse().unwrap() };
+                match child.name.as_str() {
+                    "note" if child.child("chord").is_none() && child.child("grace").is_none() => {
+                        position += duration()
+                    }
+                    "backup" => {
+                        position -= duration();
+                        assert_eq!(position, 0);
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_score_exports_note_pitches() {
+    use crate::io::lilypond_export::SongLilyPondExportOps;
+    use crate::io::musescore_export::SongMuseScoreExportOps;
+    use crate::io::musicxml_export::SongMusicXmlExportOps;
+    // Pitches above 127 are computed without overflow
+    let mut song = Song::default();
+    song.read_gp3(&read_file(String::from("test/high-pitch.gp3")))
+        .unwrap();
+    assert!(song.write_musicxml().is_ok());
+    assert!(song.write_mscx().is_ok());
+    assert!(song.write_lilypond().is_ok());
+
+    // A note on a string the track does not have is an error
+    let string_count = song.tracks[0].strings.len() as i8;
+    let note = song.tracks[0]
+        .measures
+        .iter_mut()
+        .flat_map(|m| &mut m.voices)
+        .flat_map(|v| &mut v.beats)
+        .flat_map(|b| &mut b.notes)
+        .next()
+        .unwrap();
+    note.string = string_count + 1;
+    assert!(song.write_musicxml().is_err());
+    assert!(song.write_mscx().is_err());
+    assert!(song.write_mscz().is_err());
+    assert!(song.write_lilypond().is_err());
+}
+
+// ==================== MusicXML import tests ====================
+
+/// Export a song to MusicXML and read it back.
+fn musicxml_round_trip(song: &Song) -> Song {
+    use crate::io::musicxml_export::SongMusicXmlExportOps;
+    let mut imported = Song::default();
+    imported
+        .read_musicxml(song.write_musicxml().unwrap().as_bytes())
+        .unwrap();
+    imported
+}
+
+/// Duration, strings and frets of the beats of the first voice of every measure.
+fn first_voice_frets(track: &crate::model::track::Track) -> Vec<(u16, Vec<(i8, i16)>)> {
+    track
+        .measures
+        .iter()
+        .flat_map(|m| &m.voices[0].beats)
+        .map(|b| {
+            let frets = b.notes.iter().map(|n| (n.string, n.value)).collect();
+            (b.duration.value, frets)
+        })
+        .collect()
+}
+
+#[test]
+fn test_musicxml_import_round_trip() {
+    let mut song = Song::default();
+    song.read_gp5(&read_file(String::from("test/test.gp5")))
+        .unwrap();
+    let imported = musicxml_round_trip(&song);
+    assert!(imported.read_losses.is_empty());
+    assert_eq!(imported.name, song.name);
+    assert_eq!(imported.measure_headers.len(), song.measure_headers.len());
+    assert_eq!(imported.tracks.len(), song.tracks.len());
+    let (track, original) = (&imported.tracks[0], &song.tracks[0]);
+    assert_eq!(track.strings, original.strings);
+    assert_eq!(first_voice_frets(track), first_voice_frets(original));
+    assert_eq!(imported.lyrics.track_choice, 1);
+    assert_eq!(imported.lyrics.lines.len(), 5);
+    assert_eq!(
+        imported.lyrics.lines[0].2.trim(),
+        song.lyrics.lines[0].2.trim()
+    );
+    assert_eq!(imported.channels.len(), 64);
+    assert!(imported.write((5, 1, 0), None).is_ok());
+}
+
+#[test]
+fn test_musicxml_import_repeats() {
+    let mut song = Song::default();
+    song.read_gp5(&read_file(String::from("test/volta.gp5")))
+        .unwrap();
+    let imported = musicxml_round_trip(&song);
+    let repeats = |s: &Song| -> Vec<(bool, i8, u8)> {
+        s.measure_headers
+            .iter()
+            .map(|h| (h.repeat_open, h.repeat_close, h.repeat_alternative))
+            .collect()
+    };
+    assert_eq!(repeats(&imported), repeats(&song));
+}
+
+#[test]
+fn test_musicxml_import_bends() {
+    let mut song = Song::default();
+    song.read_gp5(&read_file(String::from("test/bend.gp5")))
+        .unwrap();
+    let imported = musicxml_round_trip(&song);
+    let bends = |s: &Song| -> Vec<Vec<i8>> {
+        s.tracks[0]
+            .measures
+            .iter()
+            .flat_map(|m| &m.voices[0].beats)
+            .flat_map(|b| &b.notes)
+            .filter_map(|n| n.effect.bend.as_ref())
+            .map(|b| b.points.iter().map(|p| p.value).collect())
+            .collect()
+    };
+    let (original, imported) = (bends(&song), bends(&imported));
+    assert!(!original.is_empty());
+    assert_eq!(imported.len(), original.len());
+    // The turning points are kept
+    for (a, b) in original.iter().zip(&imported) {
+        assert_eq!(a.iter().max(), b.iter().max());
+    }
+}
+
+#[test]
+fn test_musicxml_import_frets_from_pitch() {
+    use crate::model::beat::Beat;
+    use crate::model::enums::{BeatStatus, NoteType};
+    let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<score-partwise version="3.1">
+  <work><work-title>Pitches</work-title></work>
+  <part-list>
+    <score-part id="P1"><part-name>Guitar</part-name>
+      <midi-instrument id="P1-I1"><midi-channel>3</midi-channel><midi-program>26</midi-program></midi-instrument>
+    </score-part>
+  </part-list>
+  <part id="P1">
+    <measure number="1">
+      <attributes>
+        <divisions>2</divisions>
+        <time><beats>2</beats><beat-type>4</beat-type></time>
+        <transpose><chromatic>0</chromatic><octave-change>-1</octave-change></transpose>
+      </attributes>
+      <note><pitch><step>E</step><octave>3</octave></pitch><duration>2</duration><voice>1</voice><type>quarter</type></note>
+      <note><chord/><pitch><step>B</step><octave>3</octave></pitch><duration>2</duration><voice>1</voice><type>quarter</type></note>
+      <note><pitch><step>A</step><alter>-1</alter><octave>4</octave></pitch><duration>1</duration><voice>1</voice><type>eighth</type><tie type="start"/></note>
+      <note><pitch><step>A</step><alter>-1</alter><octave>4</octave></pitch><duration>1</duration><voice>1</voice><type>eighth</type><tie type="stop"/></note>
+      <backup><duration>4</duration></backup>
+      <forward><duration>2</duration></forward>
+      <note><pitch><step>E</step><octave>3</octave></pitch><duration>2</duration><voice>2</voice><type>quarter</type></note>
+    </measure>
+  </part>
+</score-partwise>"#;
+    let mut song = Song::default();
+    song.read_musicxml(xml.as_bytes()).unwrap();
+    assert_eq!(song.name, "Pitches");
+    let track = &song.tracks[0];
+    assert_eq!(track.channel_index, 2);
+    assert_eq!(song.channels[2].instrument, 25);
+    let voices = &track.measures[0].voices;
+    let frets = |beat: &Beat| -> Vec<(i8, i16)> {
+        beat.notes.iter().map(|n| (n.string, n.value)).collect()
+    };
+    // Written an octave above the sounding pitch: E2 and B2
+    assert_eq!(frets(&voices[0].beats[0]), vec![(6, 0), (5, 2)]);
+    assert_eq!(frets(&voices[0].beats[1]), vec![(3, 1)]);
+    assert_eq!(voices[0].beats[2].notes[0].kind, NoteType::Tie);
+    // The second voice starts with a rest filling the forward
+    assert_eq!(voices[1].beats.len(), 2);
+    assert_eq!(voices[1].beats[0].status, BeatStatus::Rest);
+    assert_eq!(frets(&voices[1].beats[1]), vec![(6, 0)]);
+}
+
+#[test]
+fn test_musicxml_import_losses() {
+    use crate::model::conversion::LossPosition;
+    use crate::model::enums::BeatStatus;
+    let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<score-partwise version="3.1">
+  <part-list><score-part id="P1"><part-name>Guitar</part-name></score-part></part-list>
+  <part id="P1">
+    <measure number="1">
+      <attributes>
+        <divisions>1</divisions>
+        <time><beats>2</beats><beat-type>4</beat-type></time>
+        <transpose><chromatic>0</chromatic><octave-change>-1</octave-change></transpose>
+      </attributes>
+      <note><pitch><step>E</step><octave>3</octave></pitch><duration>1</duration><voice>1</voice><type>quarter</type></note>
+      <note><pitch><step>C</step><octave>2</octave></pitch><duration>1</duration><voice>1</voice><type>quarter</type></note>
+      <backup><duration>2</duration></backup>
+      <note><pitch><step>E</step><octave>4</octave></pitch><duration>2</duration><voice>2</voice><type>half</type></note>
+      <backup><duration>2</duration></backup>
+      <note><pitch><step>E</step><octave>5</octave></pitch><duration>1</duration><voice>3</voice><type>quarter</type></note>
+      <note><pitch><step>G</step><octave>5</octave></pitch><duration>1</duration><voice>3</voice><type>quarter</type></note>
+    </measure>
+  </part>
+</score-partwise>"#;
+    let mut song = Song::default();
+    song.read_musicxml(xml.as_bytes()).unwrap();
+    // C1 is below the low E string: the beat stays a rest instead of a clamped fret
+    let beats = &song.tracks[0].measures[0].voices[0].beats;
+    assert_eq!(beats[1].status, BeatStatus::Rest);
+    assert!(beats[1].notes.is_empty());
+    let losses: Vec<(&str, LossPosition)> = song
+        .read_losses
+        .iter()
+        .map(|l| (l.feature, l.position.clone()))
+        .collect();
+    assert_eq!(
+        losses,
+        vec![
+            ("note that fits on no string", beat_position(1, 1, 1, 2)),
+            (
+                "additional voice",
+                LossPosition {
+                    track: Some(1),
+                    measure: Some(1),
+                    ..Default::default()
+                }
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_musicxml_import_mxl() {
+    use crate::io::musicxml_export::SongMusicXmlExportOps;
+    use std::io::Write;
+    let mut song = Song::default();
+    song.read_gp5(&read_file(String::from("test/test.gp5")))
+        .unwrap();
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let options = zip::write::SimpleFileOptions::default();
+    zip.start_file("META-INF/container.xml", options).unwrap();
+    zip.write_all(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<container><rootfiles><rootfile full-path="score/song.musicxml"/></rootfiles></container>"#,
+    )
+    .unwrap();
+    zip.start_file("score/song.musicxml", options).unwrap();
+    zip.write_all(song.write_musicxml().unwrap().as_bytes())
+        .unwrap();
+    let data = zip.finish().unwrap().into_inner();
+
+    let mut imported = Song::default();
+    imported.read_mxl(&data).unwrap();
+    assert_eq!(imported.tracks.len(), song.tracks.len());
+    assert_eq!(
+        first_voice_frets(&imported.tracks[0]),
+        first_voice_frets(&song.tracks[0])
+    );
+}
+
+// ==================== MIDI export tests ====================
+
+/// Chunks of a Standard MIDI File as (type, data).
+fn midi_chunks(data: &[u8]) -> Vec<(&[u8], &[u8])> {
+    let mut chunks = Vec::new();
+    let mut seek = 0;
+    while seek + 8 <= data.len() {
+        let length = u32::from_be_bytes(data[seek + 4..seek + 8].try_into().unwrap()) as usize;
+        chunks.push((&data[seek..seek + 4], &data[seek + 8..seek + 8 + length]));
+        seek += 8 + length;
+    }
+    assert_eq!(seek, data.len());
+    chunks
+}
+
+/// Events of a track chunk as (absolute tick, status, data). Running status is not used by the writer.
+fn midi_events(track: &[u8]) -> Vec<(u32, u8, Vec<u8>)> {
+    let mut events = Vec::new();
+    let (mut seek, mut tick) = (0, 0u32);
+    let read_vlq = |seek: &mut usize| {
+        let mut value = 0u32;
+        loop {
+            let byte = track[*seek];
+            *seek += 1;
+            value = (value << 7) | u32::from(byte & 0x7f);
+            if byte & 0x80 == 0 {
+                return value;
+            }
+        }
+    };
+    while seek < track.len() {
+        tick += read_vlq(&mut seek);
+        let status = track[seek];
+        seek += 1;
+        let data = match status {
+            0xff => {
+                let kind = track[seek];
+                seek += 1;
+                let length = read_vlq(&mut seek) as usize;
+                let mut data = vec![kind];
+                data.extend(&track[seek..seek + length]);
+                seek += length;
+                data
+            }
+            0xc0..=0xdf => {
+                seek += 1;
+                vec![track[seek - 1]]
+            }
+            _ => {
+                seek += 2;
+                track[seek - 2..seek].to_vec()
+            }
+        };
+        events.push((tick, status, data));
+    }
+    events
+}
+
+#[test]
+fn test_midi_export_structure() {
+    use crate::audio::midi_export::SongMidiExportOps;
+    let mut song = Song::default();
+    song.read_gp5(&read_file(String::from("test/Demo v5.gp5")))
+        .unwrap();
+    let data = song.write_midi().unwrap();
+    let chunks = midi_chunks(&data);
+    assert_eq!(chunks[0].0, b"MThd");
+    // Format 1, conductor track and one track per song track, 960 ticks per quarter
+    assert_eq!(chunks[0].1, [0, 1, 0, 6, 0x03, 0xc0]);
+    assert_eq!(chunks.len(), song.tracks.len() + 2);
+    for (kind, track) in &chunks[1..] {
+        assert_eq!(*kind, b"MTrk");
+        let events = midi_events(track);
+        assert_eq!(events.last().unwrap().2, [0x2f]);
+    }
+
+    let conductor = midi_events(chunks[1].1);
+    let tempo = conductor
+        .iter()
+        .find(|e| e.1 == 0xff && e.2[0] == 0x51)
+        .unwrap();
+    assert_eq!(tempo.0, 0);
+    let micros = u32::from_be_bytes([0, tempo.2[1], tempo.2[2], tempo.2[3]]);
+    assert_eq!(micros, 60_000_000 / song.tempo as u32);
+    let time_signature = conductor
+        .iter()
+        .find(|e| e.1 == 0xff && e.2[0] == 0x58)
+        .unwrap();
+    assert_eq!(time_signature.2[1..3], [4, 2]);
+}
+
+#[test]
+fn test_midi_export_notes() {
+    use crate::audio::midi_export::SongMidiExportOps;
+    let mut song = Song::default();
+    song.read_gp5(&read_file(String::from("test/test.gp5")))
+        .unwrap();
+    let data = song.write_midi().unwrap();
+    let chunks = midi_chunks(&data);
+    let track = &song.tracks[0];
+    let channel = song.channels[track.channel_index];
+    let events = midi_events(chunks[2].1);
+    let program = events.iter().find(|e| e.1 & 0xf0 == 0xc0).unwrap();
+    assert_eq!(program.1 & 0x0f, channel.channel % 16);
+    assert_eq!(i32::from(program.2[0]), channel.instrument);
+    let volume = events
+        .iter()
+        .find(|e| e.1 & 0xf0 == 0xb0 && e.2[0] == 7)
+        .unwrap();
+    assert_eq!(volume.2[1], channel.volume as u8);
+
+    // First note: pitch of the string and fret, at the start of the song
+    let note = &track.measures[0].voices[0].beats[0].notes[0];
+    let pitch = note.real_value(&track.strings).unwrap() as u8;
+    let note_on = events
+        .iter()
+        .find(|e| e.1 & 0xf0 == 0x90 && e.2[1] > 0)
+        .unwrap();
+    assert_eq!((note_on.0, note_on.2[0]), (0, pitch));
+    let note_ons = events.iter().filter(|e| e.1 & 0xf0 == 0x90).count();
+    let note_offs = events.iter().filter(|e| e.1 & 0xf0 == 0x80).count();
+    assert_eq!(note_ons, note_offs);
+}
+
+// ==================== MIDI import tests ====================
+
+/// Standard MIDI File of format 0 at 480 ticks per quarter, events given as (delta, bytes).
+fn midi_file(events: &[(u32, &[u8])]) -> Vec<u8> {
+    let mut track = Vec::new();
+    for (delta, bytes) in events {
+        let mut vlq = vec![(*delta & 0x7f) as u8];
+        let mut value = *delta >> 7;
+        while value > 0 {
+            vlq.insert(0, (value & 0x7f) as u8 | 0x80);
+            value >>= 7;
+        }
+        track.extend(vlq);
+        track.extend(*bytes);
+    }
+    track.extend([0, 0xff, 0x2f, 0]);
+    let mut data = b"MThd".to_vec();
+    data.extend([0, 0, 0, 6, 0, 0, 0, 1, 0x01, 0xe0]);
+    data.extend(b"MTrk");
+    data.extend((track.len() as u32).to_be_bytes());
+    data.extend(track);
+    data
+}
+
+#[test]
+fn test_midi_import_round_trip() {
+    use crate::audio::midi_export::SongMidiExportOps;
+    use crate::audio::midi_import::SongMidiImportOps;
+    let mut song = Song::default();
+    song.read_gp5(&read_file(String::from("test/test.gp5")))
+        .unwrap();
+    let data = song.write_midi().unwrap();
+    let mut imported = Song::default();
+    imported.read_midi(&data).unwrap();
+    assert_eq!(imported.tracks.len(), song.tracks.len());
+    assert_eq!(imported.tempo, song.tempo);
+    assert_eq!(imported.tracks[0].strings, song.tracks[0].strings);
+
+    // Same pitches and durations in the first measure
+    let pitches = |track: &crate::model::track::Track, m_idx: usize| -> Vec<(u16, Vec<i32>)> {
+        track.measures[m_idx].voices[0]
+            .beats
+            .iter()
+            .map(|b| {
+                let mut pitches: Vec<i32> = b
+                    .notes
+                    .iter()
+                    .map(|n| n.real_value(&track.strings).unwrap())
+                    .collect();
+                pitches.sort();
+                (b.duration.value, pitches)
+            })
+            .collect()
+    };
+    assert_eq!(pitches(&imported.tracks[0], 0), pitches(&song.tracks[0], 0));
+    assert!(imported.write((5, 1, 0), None).is_ok());
+}
+
+#[test]
+fn test_midi_import_triplets_and_percussion() {
+    use crate::audio::midi_import::SongMidiImportOps;
+    use crate::model::enums::BeatStatus;
+    // Eighth note triplet E4 F4 G4, then a quarter note A4 with a kick drum on channel 10
+    let data = midi_file(&[
+        (0, &[0xff, 0x51, 3, 0x07, 0xa1, 0x20]),
+        (0, &[0x90, 64, 100]),
+        (160, &[0x80, 64, 0]),
+        (0, &[0x90, 65, 100]),
+        (160, &[65, 0]),
+        (0, &[67, 100]),
+        (160, &[0x80, 67, 0]),
+        (0, &[0x90, 69, 100]),
+        (0, &[0x99, 36, 100]),
+        (480, &[0x89, 36, 0]),
+        (0, &[0x80, 69, 0]),
+    ]);
+    let mut song = Song::default();
+    song.read_midi(&data).unwrap();
+    assert_eq!(song.tempo, 120);
+    assert_eq!(song.tracks.len(), 2);
+    let guitar = &song.tracks[0];
+    let beats = &guitar.measures[0].voices[0].beats;
+    for beat in &beats[..3] {
+        assert_eq!(
+            (
+                beat.duration.value,
+                beat.duration.tuplet_enters,
+                beat.duration.tuplet_times
+            ),
+            (8, 3, 2)
+        );
+    }
+    assert_eq!(
+        (beats[3].duration.value, beats[3].duration.tuplet_enters),
+        (4, 1)
+    );
+    let pitches: Vec<i32> = beats[..4]
+        .iter()
+        .map(|b| b.notes[0].real_value(&guitar.strings).unwrap())
+        .collect();
+    assert_eq!(pitches, [64, 65, 67, 69]);
+    // The rest of the measure is a half note rest
+    assert_eq!(beats[4].status, BeatStatus::Rest);
+    assert_eq!(beats[4].duration.value, 2);
+
+    let drums = &song.tracks[1];
+    assert!(drums.percussion_track);
+    assert_eq!(drums.channel_index, 9);
+    let beats = &drums.measures[0].voices[0].beats;
+    assert_eq!(beats[0].status, BeatStatus::Rest);
+    assert_eq!(beats[1].notes[0].value, 36);
+}
+
+#[test]
+fn test_midi_import_tuning() {
+    use crate::audio::midi_import::SongMidiImportOps;
+    // E1 and A1 half notes, below a guitar tuning
+    let data = midi_file(&[
+        (0, &[0x90, 28, 100]),
+        (960, &[0x80, 28, 0]),
+        (0, &[0x90, 33, 100]),
+        (960, &[0x80, 33, 0]),
+    ]);
+    let bass = [(1, 43), (2, 38), (3, 33), (4, 28)];
+    let mut song = Song::default();
+    song.read_midi_with_tuning(&data, &bass, 20).unwrap();
+    let track = &song.tracks[0];
+    assert_eq!(track.fret_count, 20);
+    let notes: Vec<(i8, i16)> = track.measures[0].voices[0]
+        .beats
+        .iter()
+        .map(|b| (b.notes[0].string, b.notes[0].value))
+        .collect();
+    assert_eq!(notes, [(4, 0), (3, 0)]);
+    assert!(song.read_losses.is_empty());
+
+    // Below the guitar tuning the notes are reported
+    use crate::model::conversion::LossPosition;
+    let mut song = Song::default();
+    song.read_midi(&data).unwrap();
+    assert_eq!(song.read_losses.len(), 2);
+    for loss in &song.read_losses {
+        assert_eq!(loss.feature, "note that fits on no string");
+        assert_eq!(
+            loss.position,
+            LossPosition {
+                track: Some(1),
+                measure: Some(1),
+                ..Default::default()
+            }
+        );
+    }
+}
+
+#[test]
+fn test_fret_positions() {
+    let track = crate::model::track::Track::default();
+    // Open E major chord
+    let chord = track.fret_positions(&[40, 47, 52, 56, 59, 64], None);
+    assert_eq!(
+        chord,
+        [
+            Some((6, 0)),
+            Some((5, 2)),
+            Some((4, 2)),
+            Some((3, 1)),
+            Some((2, 0)),
+            Some((1, 0))
+        ]
+    );
+    // Below the lowest string and more notes than strings
+    assert_eq!(track.fret_positions(&[30], None), [None]);
+    let many: Vec<i32> = (50..58).collect();
+    let positions = track.fret_positions(&many, None);
+    assert_eq!(positions.len(), 8);
+    assert!(positions[6..].iter().all(Option::is_none));
+}
+
+// ==================== MuseScore import tests ====================
+
+#[test]
+fn test_musescore4_import() {
+    use crate::model::enums::{BeatStatus, DirectionSign, NoteType};
+    let mut song = Song::default();
+    song.read_mscx(&read_file(String::from("test/musescore4.mscx")))
+        .unwrap();
+    assert_eq!(song.name, "MuseScore Test");
+    assert_eq!(song.subtitle, "From the frame");
+    assert_eq!(
+        (song.author.as_str(), song.words.as_str()),
+        ("Composer", "Lyricist")
+    );
+    assert_eq!(song.tempo, 90);
+    assert_eq!(song.key.key, 1);
+
+    assert_eq!(song.measure_headers.len(), 3);
+    let headers = &song.measure_headers;
+    assert!(headers[0].repeat_open);
+    assert_eq!(headers[0].marker.as_ref().unwrap().title, "Intro");
+    assert_eq!(
+        (headers[1].repeat_close, headers[1].repeat_alternative),
+        (2, 1)
+    );
+    assert_eq!(headers[2].repeat_alternative, 2);
+    assert_eq!(headers[2].direction, Some(DirectionSign::DaCapoAlFine));
+    assert!(headers[2].double_bar);
+
+    assert_eq!(song.tracks.len(), 1);
+    let track = &song.tracks[0];
+    assert_eq!(
+        (track.name.as_str(), track.short_name.as_str()),
+        ("Electric Guitar", "El. Guit.")
+    );
+    assert_eq!(track.strings[0], (1, 64));
+    assert_eq!(track.fret_count, 22);
+    let channel = &song.channels[track.channel_index];
+    assert_eq!(
+        (channel.instrument, channel.volume, channel.balance),
+        (29, 80, 40)
+    );
+
+    // The tablature staff is read, with its strings and frets
+    let beats = &track.measures[0].voices[0].beats;
+    let notes: Vec<(i8, i16)> = beats
+        .iter()
+        .map(|b| (b.notes[0].string, b.notes[0].value))
+        .collect();
+    assert_eq!(notes, [(2, 5), (2, 8), (1, 5), (1, 7), (4, 2)]);
+    for beat in &beats[1..4] {
+        assert_eq!(
+            (
+                beat.duration.value,
+                beat.duration.tuplet_enters,
+                beat.duration.tuplet_times
+            ),
+            (8, 3, 2)
+        );
+    }
+    assert_eq!(beats[4].duration.value, 2);
+    let second = &track.measures[0].voices[1].beats;
+    assert_eq!(second[0].status, BeatStatus::Rest);
+    assert_eq!(
+        (second[1].duration.value, second[1].notes[0].string),
+        (2, 6)
+    );
+    assert_eq!(
+        track.measures[1].voices[0].beats[0].notes[0].kind,
+        NoteType::Tie
+    );
+    assert_eq!(
+        track.measures[2].voices[0].beats[0].status,
+        BeatStatus::Rest
+    );
+    assert_eq!(track.measures[2].voices[0].beats[0].duration.value, 1);
+
+    assert_eq!(song.lyrics.track_choice, 1);
+    assert_eq!(song.lyrics.lines[0].1, 1);
+    assert_eq!(song.lyrics.lines[0].2.trim_end(), "Hel-lo world");
+    assert!(song.write((5, 1, 0), None).is_ok());
+}
+
+#[test]
+fn test_musescore3_import() {
+    use crate::model::enums::NoteType;
+    let mut song = Song::default();
+    song.read_mscx(&read_file(String::from("test/musescore3.mscx")))
+        .unwrap();
+    assert_eq!(song.name, "Bass and Drums");
+    assert_eq!(song.transcriber, "Arranger");
+    assert_eq!(song.copyright, "Public domain");
+    let headers = &song.measure_headers;
+    assert_eq!(headers[0].time_signature.numerator, 3);
+    assert_eq!(
+        headers
+            .iter()
+            .map(|h| (h.repeat_open, h.repeat_close, h.repeat_alternative))
+            .collect::<Vec<_>>(),
+        [(true, -1, 0), (false, 2, 1), (false, -1, 2)]
+    );
+
+    let bass = &song.tracks[0];
+    assert_eq!(bass.strings, [(1, 43), (2, 38), (3, 33), (4, 28)]);
+    assert_eq!(bass.fret_count, 20);
+    assert!(matches!(
+        bass.measures[0].clef,
+        crate::model::enums::MeasureClef::Bass
+    ));
+    let channel = &song.channels[bass.channel_index];
+    assert_eq!((channel.instrument, channel.volume), (33, 90));
+    // Notes without strings are placed from their pitch
+    let beats = &bass.measures[0].voices[0].beats;
+    let pitches: Vec<i32> = beats
+        .iter()
+        .map(|b| b.notes[0].real_value(&bass.strings).unwrap())
+        .collect();
+    assert_eq!(pitches, [28, 31, 33, 35]);
+    assert!(beats[..3]
+        .iter()
+        .all(|b| (b.duration.value, b.duration.tuplet_enters) == (4, 3)));
+    let next = &bass.measures[1].voices[0].beats[0];
+    assert!(next.duration.dotted);
+    assert_eq!(next.notes[0].kind, NoteType::Tie);
+    assert_eq!(
+        bass.measures[2].voices[0].beats[0].notes[0].kind,
+        NoteType::Dead
+    );
+
+    let drums = &song.tracks[1];
+    assert!(drums.percussion_track);
+    assert_eq!(drums.channel_index, 9);
+    let chord = &drums.measures[0].voices[0].beats[0];
+    assert_eq!(
+        chord.notes.iter().map(|n| n.value).collect::<Vec<_>>(),
+        [36, 42]
+    );
+    assert!(song.write((5, 1, 0), None).is_ok());
+}
+
+#[test]
+fn test_musescore_import_mscz() {
+    use std::io::Write;
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let options = zip::write::SimpleFileOptions::default();
+    zip.start_file("META-INF/container.xml", options).unwrap();
+    zip.write_all(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<container><rootfiles><rootfile full-path="score.mscx"/></rootfiles></container>"#,
+    )
+    .unwrap();
+    zip.start_file("score.mscx", options).unwrap();
+    zip.write_all(&read_file(String::from("test/musescore3.mscx")))
+        .unwrap();
+    let data = zip.finish().unwrap().into_inner();
+
+    let mut song = Song::default();
+    song.read_mscz(&data).unwrap();
+    assert_eq!(song.name, "Bass and Drums");
+    assert_eq!(song.tracks.len(), 2);
+    assert!(Song::default().read_mscx(b"<score-partwise/>").is_err());
+}
+
+#[test]
+fn test_musescore_import_losses() {
+    use crate::model::conversion::LossPosition;
+    let mut song = Song::default();
+    song.read_mscx(&read_file(String::from("test/musescore3.mscx")))
+        .unwrap();
+    assert!(song.read_losses.is_empty());
+
+    // G0 is below the low E string of the bass, a third voice is added to the last measure
+    let mscx = String::from_utf8(read_file(String::from("test/musescore3.mscx"))).unwrap();
+    let mscx = mscx
+        .replacen("<pitch>31</pitch>", "<pitch>19</pitch>", 1)
+        .replacen(
+            "<endSpanner id=\"4\"/>\n        </voice>",
+            "<endSpanner id=\"4\"/>\n        </voice><voice/><voice><Chord>\
+         <durationType>half</durationType><Note><pitch>40</pitch></Note></Chord></voice>",
+            1,
+        );
+    song.read_mscx(mscx.as_bytes()).unwrap();
+    let beats = &song.tracks[0].measures[0].voices[0].beats;
+    assert_eq!(beats.len(), 4);
+    assert!(beats[1].notes.is_empty());
+    let losses: Vec<(&str, LossPosition)> = song
+        .read_losses
+        .iter()
+        .map(|l| (l.feature, l.position.clone()))
+        .collect();
+    assert_eq!(
+        losses,
+        vec![
+            ("note that fits on no string", beat_position(1, 1, 1, 2)),
+            (
+                "additional voice",
+                LossPosition {
+                    track: Some(1),
+                    measure: Some(3),
+                    ..Default::default()
+                }
+            ),
+        ]
+    );
+}
+
+// ==================== MuseScore export tests ====================
+
+/// Export a song to MuseScore and read it back.
+fn musescore_round_trip(song: &Song) -> Song {
+    use crate::io::musescore_export::SongMuseScoreExportOps;
+    let mut imported = Song::default();
+    imported
+        .read_mscx(song.write_mscx().unwrap().as_bytes())
+        .unwrap();
+    imported
+}
+
+/// String and fret of the notes of the first voice of a track.
+fn first_voice_positions(track: &crate::model::track::Track) -> Vec<Vec<(i8, i16)>> {
+    track
+        .measures
+        .iter()
+        .flat_map(|m| &m.voices[0].beats)
+        .filter(|b| !b.notes.is_empty())
+        .map(|b| b.notes.iter().map(|n| (n.string, n.value)).collect())
+        .collect()
+}
+
+#[test]
+fn test_musescore_export_ties() {
+    use crate::model::enums::NoteType;
+    // Tied notes continue the last note of their string, beats on other strings in between
+    let mut song = Song::default();
+    song.read_gp4(&read_file(String::from(
+        "test/led-zeppelin-babe_i_m_gonna_leave_you.gp4",
+    )))
+    .unwrap();
+    let imported = musescore_round_trip(&song);
+    let ties = |track: &crate::model::track::Track| -> Vec<usize> {
+        track
+            .measures
+            .iter()
+            .map(|m| {
+                m.voices
+                    .iter()
+                    .flat_map(|v| &v.beats)
+                    .flat_map(|b| &b.notes)
+                    .filter(|n| n.kind == NoteType::Tie)
+                    .count()
+            })
+            .collect()
+    };
+    assert_eq!(ties(&song.tracks[2])[0], 3);
+    for (a, b) in song.tracks.iter().zip(&imported.tracks) {
+        assert_eq!(ties(a), ties(b), "{}", a.name);
+    }
+}
+
+#[test]
+fn test_musescore_export_tablature() {
+    use crate::io::musescore_export::SongMuseScoreExportOps;
+    let mut song = Song::default();
+    song.read_gp5(&read_file(String::from("test/test.gp5")))
+        .unwrap();
+    let score = crate::io::xml::XmlElement::parse(&song.write_mscx().unwrap()).unwrap();
+    let score = score.child("Score").unwrap();
+    let parts: Vec<_> = score.children.iter().filter(|c| c.name == "Part").collect();
+    assert_eq!(parts.len(), song.tracks.len());
+    // A notation staff and a tablature staff linked to it
+    let staves: Vec<_> = parts[0]
+        .children
+        .iter()
+        .filter(|c| c.name == "Staff")
+        .collect();
+    assert_eq!(staves.len(), 2);
+    assert_eq!(staves[1].child("linkedTo").unwrap().text(), "1");
+    let tab = staves[1].child("StaffType").unwrap();
+    assert_eq!(tab.attribute("group"), Some("tablature"));
+    assert_eq!(
+        tab.child("lines").unwrap().text(),
+        song.tracks[0].strings.len().to_string()
+    );
+    assert_eq!(
+        xml_descendants(parts[0], "string").len(),
+        song.tracks[0].strings.len()
+    );
+
+    let imported = musescore_round_trip(&song);
+    assert_eq!(imported.name, song.name);
+    assert_eq!(imported.measure_headers.len(), song.measure_headers.len());
+    assert_eq!(imported.tracks.len(), song.tracks.len());
+    let (track, original) = (&imported.tracks[0], &song.tracks[0]);
+    assert_eq!(track.strings, original.strings);
+    assert_eq!(
+        first_voice_positions(track),
+        first_voice_positions(original)
+    );
+    assert_eq!(imported.lyrics.track_choice, song.lyrics.track_choice);
+}
+
+#[test]
+fn test_musescore_export_percussion() {
+    use crate::io::musescore_export::SongMuseScoreExportOps;
+    let mut song = Song::default();
+    song.read_gp5(&read_file(String::from("test/all-percussion.gp5")))
+        .unwrap();
+    assert!(song.tracks[0].percussion_track);
+    let score = crate::io::xml::XmlElement::parse(&song.write_mscx().unwrap()).unwrap();
+    let part = xml_descendants(&score, "Part")[0];
+    let staff_types = xml_descendants(part, "StaffType");
+    assert_eq!(staff_types.len(), 1);
+    assert_eq!(staff_types[0].attribute("group"), Some("percussion"));
+    assert!(xml_descendants(part, "useDrumset").len() == 1);
+
+    let imported = musescore_round_trip(&song);
+    assert!(imported.tracks[0].percussion_track);
+    let values = |track: &crate::model::track::Track| -> Vec<i16> {
+        track
+            .measures
+            .iter()
+            .flat_map(|m| &m.voices[0].beats)
+            .flat_map(|b| b.notes.iter().map(|n| n.value))
+            .collect()
+    };
+    assert_eq!(values(&imported.tracks[0]), values(&song.tracks[0]));
+}
+
+#[test]
+fn test_musescore_export_repeats() {
+    let mut song = Song::default();
+    song.read_gp5(&read_file(String::from("test/volta.gp5")))
+        .unwrap();
+    let imported = musescore_round_trip(&song);
+    let repeats = |song: &Song| -> Vec<(bool, bool, u8)> {
+        song.measure_headers
+            .iter()
+            .map(|h| (h.repeat_open, h.repeat_close > 0, h.repeat_alternative))
+            .collect()
+    };
+    assert_eq!(repeats(&imported), repeats(&song));
+    assert!(repeats(&song).iter().any(|r| r.2 != 0));
+}
+
+#[test]
+fn test_musescore_export_mscz() {
+    use crate::io::musescore_export::SongMuseScoreExportOps;
+    let mut song = Song::default();
+    song.read_mscx(&read_file(String::from("test/musescore3.mscx")))
+        .unwrap();
+    let mut imported = Song::default();
+    imported.read_mscz(&song.write_mscz().unwrap()).unwrap();
+    assert_eq!(imported.name, "Bass and Drums");
+    assert_eq!(imported.tracks.len(), 2);
+    assert_eq!(imported.tracks[0].strings, song.tracks[0].strings);
+    assert_eq!(
+        first_voice_positions(&imported.tracks[0]),
+        first_voice_positions(&song.tracks[0])
+    );
+    assert_eq!(imported.measure_headers[1].repeat_close, 2);
+}
+
+// ==================== Power Tab import tests ====================
+
+/// `test/powertab.ptb` is built by hand following the Power Tab Editor 1.7 layout, it was not
+/// saved by Power Tab Editor: a document saved by the editor is still needed to check the reader.
+fn read_powertab() -> Song {
+    use crate::io::powertab_import::SongPowerTabOps;
+    let mut song = Song::default();
+    song.read_ptb(&read_file(String::from("test/powertab.ptb")))
+        .unwrap();
+    song
+}
+
+#[test]
+fn test_powertab_import_song() {
+    use crate::model::enums::DirectionSign;
+    let song = read_powertab();
+    assert_eq!(song.name, "Power Tab Test");
+    assert_eq!(
+        (song.artist.as_str(), song.album.as_str()),
+        ("Test Artist", "Test Album")
+    );
+    assert_eq!(
+        (song.author.as_str(), song.words.as_str()),
+        ("Composer", "Lyricist")
+    );
+    assert_eq!(song.transcriber, "Transcriber");
+    assert_eq!(song.notice, vec!["First note", "Second note"]);
+    assert_eq!(song.lyrics.lines[0].2, "Hello world");
+    assert_eq!(song.lyrics.lines.len(), 5);
+    assert_eq!(song.tempo, 100);
+
+    let headers = &song.measure_headers;
+    assert_eq!(headers.len(), 3);
+    assert!(headers[0].repeat_open);
+    assert_eq!(headers[0].marker.as_ref().unwrap().title, "Intro");
+    assert_eq!(headers[0].key_signature.key, 1);
+    assert_eq!(headers[0].time_signature.numerator, 4);
+    assert_eq!(
+        (headers[1].repeat_close, headers[1].repeat_alternative),
+        (2, 1)
+    );
+    assert_eq!(headers[2].repeat_alternative, 2);
+    assert_eq!(headers[2].direction, Some(DirectionSign::Fine));
+
+    // Guitars of the guitar score, then of the bass score
+    let names: Vec<&str> = song.tracks.iter().map(|t| t.name.as_str()).collect();
+    assert_eq!(names, vec!["Lead", "Rhythm", "Bass"]);
+    assert_eq!(song.tracks[0].offset, 2);
+    assert_eq!(song.tracks[0].strings[5], (6, 40));
+    assert_eq!(
+        song.tracks[2].strings,
+        vec![(1, 43), (2, 38), (3, 33), (4, 28)]
+    );
+    let channels: Vec<usize> = song.tracks.iter().map(|t| t.channel_index).collect();
+    assert_eq!(channels, vec![0, 1, 2]);
+    assert_eq!(song.channels[0].instrument, 29);
+    assert_eq!(song.tracks[2].measures.len(), 3);
+}
+
+#[test]
+fn test_powertab_import_staff() {
+    use crate::model::enums::{BeatStatus, BendType, SlideType};
+    let song = read_powertab();
+    let measures = &song.tracks[0].measures;
+    let beats = &measures[0].voices[0].beats;
+    assert_eq!(beats.len(), 4);
+    assert_eq!((beats[0].notes[0].string, beats[0].notes[0].value), (6, 3));
+    // Dynamics of the staff: mezzo piano
+    assert_eq!(beats[0].notes[0].velocity, 63);
+    let chord = beats[0].effect.chord.as_ref().unwrap();
+    assert_eq!(chord.name, "G");
+    assert_eq!(chord.strings, vec![3, 0, 0, 0, 2, 3]);
+    assert!(beats[1].notes[0].effect.hammer);
+    assert_eq!(beats[2].status, BeatStatus::Rest);
+    assert!(beats[3].notes.iter().all(|n| n.effect.harmonic.is_some()));
+    assert!(beats[3].notes[0].effect.palm_mute);
+
+    let beats = &measures[1].voices[0].beats;
+    assert_eq!(
+        (
+            beats[0].duration.tuplet_enters,
+            beats[0].duration.tuplet_times
+        ),
+        (3, 2)
+    );
+    assert_eq!(beats[2].duration.tuplet_enters, 3);
+    assert_eq!(beats[3].duration.tuplet_enters, 1);
+    let bend = beats[3].notes[0].effect.bend.as_ref().unwrap();
+    assert_eq!(bend.kind, BendType::Bend);
+    assert_eq!(bend.value, 100);
+
+    let beat = &measures[2].voices[0].beats[0];
+    assert_eq!(beat.duration.value, 1);
+    assert_eq!(beat.effect.chord.as_ref().unwrap().name, "Em7");
+    assert_eq!(beat.notes[0].effect.slides, vec![SlideType::OutDownwards]);
+    assert!(matches!(
+        song.tracks[2].measures[0].clef,
+        crate::model::enums::MeasureClef::Bass
+    ));
+}
+
+#[test]
+fn test_powertab_import_rhythm_slashes() {
+    use crate::model::enums::{BeatStatus, BeatStrokeDirection};
+    let song = read_powertab();
+    let measures = &song.tracks[1].measures;
+    let beats = &measures[0].voices[0].beats;
+    assert_eq!(beats.len(), 4);
+    for beat in beats {
+        let frets: Vec<(i8, i16)> = beat.notes.iter().map(|n| (n.string, n.value)).collect();
+        assert_eq!(frets, vec![(1, 3), (2, 0), (3, 0), (4, 0), (5, 2), (6, 3)]);
+    }
+    assert_eq!(beats[0].effect.chord.as_ref().unwrap().name, "G");
+    assert_eq!(beats[1].effect.pick_stroke, BeatStrokeDirection::Down);
+    // No slashes: a rest for the whole measure
+    assert_eq!(measures[1].voices[0].beats[0].status, BeatStatus::Rest);
+}
+
+#[test]
+fn test_powertab_import_invalid() {
+    use crate::io::powertab_import::SongPowerTabOps;
+    let mut song = Song::default();
+    assert!(song.read_ptb(b"not a power tab file").is_err());
+    let mut data = read_file(String::from("test/powertab.ptb"));
+    data[4] = 3;
+    assert!(song.read_ptb(&data).is_err());
+    data[4] = 4;
+    data.truncate(200);
+    assert!(song.read_ptb(&data).is_err());
+}
+
+// ==================== TuxGuitar tests ====================
+
+/// Read a Guitar Pro 5 file, export it to TuxGuitar and read it back.
+fn tuxguitar_round_trip(path: &str) -> (Song, Song) {
+    use crate::io::tuxguitar_export::SongTuxGuitarExportOps;
+    use crate::io::tuxguitar_import::SongTuxGuitarOps;
+    let mut song = Song::default();
+    song.read_gp5(&read_file(String::from(path))).unwrap();
+    let mut imported = Song::default();
+    imported.read_tg(&song.write_tg().unwrap()).unwrap();
+    (song, imported)
+}
+
+#[test]
+fn test_tuxguitar_round_trip() {
+    let (song, imported) = tuxguitar_round_trip("test/test.gp5");
+    assert_eq!(imported.name, song.name);
+    assert_eq!(imported.artist, song.artist);
+    assert_eq!(imported.tempo, song.tempo);
+    assert_eq!(imported.measure_headers.len(), song.measure_headers.len());
+    assert_eq!(imported.tracks.len(), song.tracks.len());
+    for (track, original) in imported.tracks.iter().zip(&song.tracks) {
+        assert_eq!(track.name, original.name);
+        assert_eq!(track.strings, original.strings);
+        assert_eq!(track.offset, original.offset);
+        assert_eq!(
+            first_voice_positions(track),
+            first_voice_positions(original)
+        );
+        let channel = &imported.channels[track.channel_index];
+        let original_channel = &song.channels[original.channel_index];
+        assert_eq!(channel.instrument, original_channel.instrument);
+        assert_eq!(channel.volume, original_channel.volume);
+    }
+    for (header, original) in imported.measure_headers.iter().zip(&song.measure_headers) {
+        assert_eq!(header.length(), original.length());
+        assert_eq!(
+            header.time_signature.numerator,
+            original.time_signature.numerator
+        );
+    }
+}
+
+#[test]
+fn test_tuxguitar_round_trip_effects() {
+    let (song, imported) = tuxguitar_round_trip("test/Effects.gp5");
+    let notes = |s: &Song| -> Vec<crate::model::note::Note> {
+        s.tracks[0]
+            .measures
+            .iter()
+            .flat_map(|m| &m.voices[0].beats)
+            .flat_map(|b| b.notes.clone())
+            .collect()
+    };
+    let (notes, original) = (notes(&imported), notes(&song));
+    assert_eq!(notes.len(), original.len());
+    for (note, original) in notes.iter().zip(&original) {
+        assert_eq!(note.kind, original.kind);
+        assert_eq!(note.velocity, original.velocity);
+        assert_eq!(note.effect.bend.is_some(), original.effect.bend.is_some());
+        assert_eq!(note.effect.grace.is_some(), original.effect.grace.is_some());
+        assert_eq!(
+            note.effect.harmonic.is_some(),
+            original.effect.harmonic.is_some()
+        );
+        assert_eq!(note.effect.palm_mute, original.effect.palm_mute);
+        assert_eq!(note.effect.let_ring, original.effect.let_ring);
+        assert_eq!(note.effect.hammer, original.effect.hammer);
+    }
+}
+
+#[test]
+fn test_tuxguitar_round_trip_voices_and_repeats() {
+    let (song, imported) = tuxguitar_round_trip("test/Voices.gp5");
+    for (track, original) in imported.tracks.iter().zip(&song.tracks) {
+        for (measure, original) in track.measures.iter().zip(&original.measures) {
+            // Start and notes of the beats of the second voice
+            let starts = |m: &crate::model::measure::Measure| -> Vec<(i64, usize)> {
+                let mut start = 0;
+                let mut starts = Vec::new();
+                for beat in &m.voices[1].beats {
+                    if !beat.notes.is_empty() {
+                        starts.push((start, beat.notes.len()));
+                    }
+                    start += crate::io::musicxml_export::duration_ticks(&beat.duration);
+                }
+                starts
+            };
+            assert_eq!(starts(measure), starts(original));
+        }
+    }
+
+    let (song, imported) = tuxguitar_round_trip("test/volta.gp5");
+    for (header, original) in imported.measure_headers.iter().zip(&song.measure_headers) {
+        assert_eq!(header.repeat_open, original.repeat_open);
+        assert_eq!(header.repeat_close, original.repeat_close);
+        assert_eq!(header.repeat_alternative, original.repeat_alternative);
+    }
+}
+
+#[test]
+fn test_tuxguitar_percussion() {
+    let (song, imported) = tuxguitar_round_trip("test/all-percussion.gp5");
+    for track in &imported.tracks {
+        assert!(track.percussion_track);
+        assert_eq!(
+            imported.channels[track.channel_index].channel,
+            crate::audio::midi::DEFAULT_PERCUSSION_CHANNEL
+        );
+    }
+    assert_eq!(
+        first_voice_positions(&imported.tracks[0]),
+        first_voice_positions(&song.tracks[0])
+    );
+}
+
+#[test]
+fn test_tuxguitar_import_invalid() {
+    use crate::io::tuxguitar_export::SongTuxGuitarExportOps;
+    use crate::io::tuxguitar_import::SongTuxGuitarOps;
+    let mut song = Song::default();
+    assert!(song.read_tg(b"not a tuxguitar file").is_err());
+    // A document without tracks
+    assert!(song.read_tg(&song.write_tg().unwrap()).is_err());
+    song.read_gp5(&read_file(String::from("test/test.gp5")))
+        .unwrap();
+    let data = song.write_tg().unwrap();
+    assert!(Song::default().read_tg(&data).is_ok());
+    assert!(Song::default().read_tg(&data[..data.len() - 1]).is_err());
+}
+
+// ==================== ASCII tab import tests ====================
+
+fn read_ascii_tab() -> Song {
+    use crate::io::ascii_tab_import::SongAsciiTabOps;
+    let text = String::from_utf8(read_file(String::from("test/ascii_tab.txt"))).unwrap();
+    let mut song = Song::default();
+    song.read_ascii_tab(&text).unwrap();
+    song
+}
+
+/// Duration values and fret positions of the beats of the first voice of a measure.
+fn ascii_tab_beats(measure: &crate::model::measure::Measure) -> Vec<(u16, Vec<(i8, i16)>)> {
+    measure.voices[0]
+        .beats
+        .iter()
+        .map(|b| {
+            (
+                b.duration.value,
+                b.notes.iter().map(|n| (n.string, n.value)).collect(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn test_ascii_tab_import_staves() {
+    let song = read_ascii_tab();
+    assert_eq!(song.tracks.len(), 2);
+    assert_eq!(song.measure_headers.len(), 4);
+    let guitar = &song.tracks[0];
+    assert_eq!(
+        guitar.strings,
+        vec![(1, 64), (2, 59), (3, 55), (4, 50), (5, 45), (6, 40)]
+    );
+    let bass = &song.tracks[1];
+    assert_eq!(bass.strings, vec![(1, 43), (2, 38), (3, 33), (4, 26)]);
+    assert_eq!(song.channels[bass.channel_index].instrument, 33);
+    assert_eq!(guitar.measures.len(), 4);
+    assert_eq!(bass.measures.len(), 4);
+
+    assert!(song.measure_headers[0].repeat_open);
+    assert!(song.measure_headers[3].repeat_close > 0);
+    assert!(song.measure_headers[3].double_bar);
+}
+
+#[test]
+fn test_ascii_tab_import_rhythm() {
+    let song = read_ascii_tab();
+    let guitar = &song.tracks[0];
+    let power_chord = vec![(4, 2), (5, 0)];
+    assert_eq!(
+        ascii_tab_beats(&guitar.measures[0]),
+        vec![
+            (4, power_chord.clone()),
+            (4, power_chord.clone()),
+            (4, power_chord.clone()),
+            (4, power_chord)
+        ]
+    );
+    // Every measure is filled
+    for measure in song.tracks.iter().flat_map(|t| &t.measures) {
+        let ticks: i64 = measure.voices[0]
+            .beats
+            .iter()
+            .map(|b| crate::io::musicxml_export::duration_ticks(&b.duration))
+            .sum();
+        assert_eq!(ticks, song.measure_headers[0].length());
+    }
+    let bass = &song.tracks[1];
+    let beats = ascii_tab_beats(&bass.measures[1]);
+    assert_eq!(beats[0], (2, vec![(4, 5)]));
+    assert_eq!(beats.last().unwrap().1, vec![(4, 5)]);
+    // Multi-digit frets on the same beat
+    let frets: Vec<Vec<(i8, i16)>> = ascii_tab_beats(&guitar.measures[3])
+        .into_iter()
+        .map(|b| b.1)
+        .filter(|n| !n.is_empty())
+        .collect();
+    assert_eq!(
+        frets,
+        vec![vec![(1, 12)], vec![(1, 10)], vec![(2, 10)], vec![(3, 9)]]
+    );
+}
+
+#[test]
+fn test_ascii_tab_import_techniques() {
+    use crate::model::enums::{NoteType, SlideType};
+    let song = read_ascii_tab();
+    let measures = &song.tracks[0].measures;
+    let notes = |m_idx: usize| -> Vec<crate::model::note::Note> {
+        measures[m_idx].voices[0]
+            .beats
+            .iter()
+            .flat_map(|b| b.notes.clone())
+            .collect()
+    };
+    assert!(notes(0).iter().all(|n| n.effect.palm_mute));
+    let measure = notes(1);
+    assert!(!measure[0].effect.palm_mute);
+    // 5h7 over 3, then 7b9r7
+    assert!(measure.iter().any(|n| n.value == 5 && n.effect.hammer));
+    let bend = measure.iter().find_map(|n| n.effect.bend.as_ref()).unwrap();
+    assert_eq!(bend.points.iter().map(|p| p.value).max(), Some(4));
+    assert_eq!(measure.len(), 4);
+
+    let measure = notes(2);
+    assert!(measure.iter().any(|n| n.value == 7 && n.effect.hammer));
+    assert!(measure
+        .iter()
+        .any(|n| n.value == 5 && n.effect.slides == vec![SlideType::ShiftSlideTo]));
+    assert!(measure.iter().any(|n| n.value == 7 && n.effect.vibrato));
+    assert!(measure.iter().any(|n| n.kind == NoteType::Dead));
+}
+
+#[test]
+fn test_ascii_tab_import_invalid() {
+    use crate::io::ascii_tab_import::SongAsciiTabOps;
+    let mut song = Song::default();
+    assert!(song
+        .read_ascii_tab("Just some lyrics\nwith no tab")
+        .is_err());
+    // Tabs convert to Guitar Pro files
+    let song = read_ascii_tab();
+    assert!(song.write((5, 1, 0), None).is_ok());
+}
+
+// ==================== LilyPond export tests ====================
+
+fn lilypond_export(path: &str) -> String {
+    use crate::io::lilypond_export::SongLilyPondExportOps;
+    let mut song = Song::default();
+    song.read_gp5(&read_file(String::from(path))).unwrap();
+    song.write_lilypond().unwrap()
+}
+
+/// Braces and double angle brackets of a LilyPond document are balanced.
+fn assert_lilypond_balanced(ly: &str) {
+    let code: String = ly
+        .lines()
+        .map(|l| l.split('"').step_by(2).collect::<String>())
+        .collect();
+    assert_eq!(code.matches('{').count(), code.matches('}').count());
+    assert_eq!(code.matches("<<").count(), code.matches(">>").count());
+}
+
+#[test]
+fn test_lilypond_export_staves() {
+    let ly = lilypond_export("test/test.gp5");
+    assert_lilypond_balanced(&ly);
+    assert!(ly.starts_with("\\version \"2.24.0\""));
+    assert!(ly.contains("\\new TabStaff"));
+    assert!(ly.contains("stringTunings = \\stringTuning <e, a, d g b e'>"));
+    assert!(ly.contains("\\new Staff \\with { \\omit StringNumber } << \\global \\trackA >>"));
+    assert!(ly.contains("\\removeWithTag #'notation \\trackA"));
+    assert!(ly.contains("\\time 4/4"));
+    assert!(ly.contains("\\tempo 4 = "));
+}
+
+#[test]
+fn test_lilypond_export_techniques() {
+    let ly = lilypond_export("test/Effects.gp5");
+    assert_lilypond_balanced(&ly);
+    for technique in [
+        "<\\deadNote e,\\6>4",
+        "\\acciaccatura { <g,\\6>16 }",
+        "<\\parenthesize fis,\\6>4",
+        "<a,\\6\\harmonic>4",
+        "<c\\6>4\\bendAfter #+2)",
+        "\\glissando",
+        "\\trill",
+        "<c'\\6>4:32",
+        "^\"P.M.\"",
+        "-.",
+        "\\arpeggio",
+        "\\time 7/8",
+    ] {
+        assert!(ly.contains(technique), "missing {}", technique);
+    }
+
+    let ly = lilypond_export("test/Voices.gp5");
+    assert!(ly.contains("<< { <f,\\6>4 <fis,\\6>4 <g,\\6>4 <gis,\\6>4 } \\\\ { <a,\\6>4."));
+}
+
+#[test]
+fn test_lilypond_export_structure() {
+    let ly = lilypond_export("test/volta.gp5");
+    assert_lilypond_balanced(&ly);
+    assert!(ly.contains("\\time 3/4"));
+    assert!(ly.contains("\\tuplet 3/2 {"));
+    assert!(ly.contains("\\set Score.repeatCommands = #'((volta \"1.\"))"));
+    assert!(ly.contains("\\bar \":|.\" \\set Score.repeatCommands = #'((volta #f) (volta"));
+
+    let ly = lilypond_export("test/all-percussion.gp5");
+    assert_lilypond_balanced(&ly);
+    assert!(ly.contains("trackA = \\drummode {"));
+    assert!(ly.contains("\\new DrumStaff"));
+    assert!(ly.contains("<bda>2 <bd>2"));
+    assert!(!ly.contains("TabStaff"));
+}
+
+// ==================== alphaTex tests ====================
+
+/// Read a Guitar Pro 5 file, export it to alphaTex and read it back.
+fn alphatex_round_trip(path: &str) -> (Song, Song) {
+    use crate::io::alphatex_export::SongAlphaTexExportOps;
+    use crate::io::alphatex_import::SongAlphaTexOps;
+    let mut song = Song::default();
+    song.read_gp5(&read_file(String::from(path))).unwrap();
+    let mut imported = Song::default();
+    imported.read_alphatex(&song.write_alphatex()).unwrap();
+    (song, imported)
+}
+
+/// Duration values of the beats of the first voice.
+fn first_voice_durations(track: &crate::model::track::Track) -> Vec<(u16, bool, u8)> {
+    track
+        .measures
+        .iter()
+        .flat_map(|m| &m.voices[0].beats)
+        .map(|b| {
+            (
+                b.duration.value,
+                b.duration.dotted,
+                b.duration.tuplet_enters,
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn test_alphatex_round_trip() {
+    let (song, imported) = alphatex_round_trip("test/test.gp5");
+    assert_eq!(imported.name, song.name);
+    assert_eq!(imported.artist, song.artist);
+    assert_eq!(imported.tempo, song.tempo);
+    assert_eq!(imported.measure_headers.len(), song.measure_headers.len());
+    assert_eq!(imported.tracks.len(), song.tracks.len());
+    for (track, original) in imported.tracks.iter().zip(&song.tracks) {
+        assert_eq!(track.name, original.name);
+        assert_eq!(track.strings, original.strings);
+        assert_eq!(track.offset, original.offset);
+        assert_eq!(
+            first_voice_positions(track),
+            first_voice_positions(original)
+        );
+        assert_eq!(
+            first_voice_durations(track),
+            first_voice_durations(original)
+        );
+        assert_eq!(
+            imported.channels[track.channel_index].instrument,
+            song.channels[original.channel_index].instrument
+        );
+    }
+    for (header, original) in imported.measure_headers.iter().zip(&song.measure_headers) {
+        assert_eq!(header.time_signature, original.time_signature);
+        assert_eq!(header.key_signature, original.key_signature);
+    }
+}
+
+#[test]
+fn test_alphatex_round_trip_effects() {
+    let (song, imported) = alphatex_round_trip("test/Effects.gp5");
+    let notes = |s: &Song| -> Vec<crate::model::note::Note> {
+        s.tracks[0]
+            .measures
+            .iter()
+            .flat_map(|m| &m.voices[0].beats)
+            .flat_map(|b| b.notes.clone())
+            .collect()
+    };
+    let (notes, original) = (notes(&imported), notes(&song));
+    assert_eq!(notes.len(), original.len());
+    for (note, original) in notes.iter().zip(&original) {
+        assert_eq!(note.kind, original.kind);
+        assert_eq!(note.value, original.value);
+        assert_eq!(note.effect.bend.is_some(), original.effect.bend.is_some());
+        assert_eq!(note.effect.grace.is_some(), original.effect.grace.is_some());
+        assert_eq!(
+            note.effect.harmonic.as_ref().map(|h| &h.kind),
+            original.effect.harmonic.as_ref().map(|h| &h.kind)
+        );
+        assert_eq!(note.effect.slides, original.effect.slides);
+        assert_eq!(note.effect.palm_mute, original.effect.palm_mute);
+        assert_eq!(note.effect.let_ring, original.effect.let_ring);
+        assert_eq!(note.effect.hammer, original.effect.hammer);
+        assert_eq!(note.effect.vibrato, original.effect.vibrato);
+    }
+}
+
+#[test]
+fn test_alphatex_round_trip_harmonics() {
+    use crate::io::alphatex_export::SongAlphaTexExportOps;
+    use crate::io::alphatex_import::SongAlphaTexOps;
+    use crate::model::enums::HarmonicType;
+    let harmonics = |s: &Song| -> Vec<(HarmonicType, Option<i8>)> {
+        s.tracks
+            .iter()
+            .flat_map(|t| &t.measures)
+            .flat_map(|m| &m.voices)
+            .flat_map(|v| &v.beats)
+            .flat_map(|b| &b.notes)
+            .filter_map(|n| n.effect.harmonic.as_ref())
+            .map(|h| (h.kind.clone(), h.fret))
+            .collect()
+    };
+    let (song, imported) = alphatex_round_trip("test/Harmonics.gp5");
+    let tapped = |s: &Song| -> Vec<(HarmonicType, Option<i8>)> {
+        harmonics(s)
+            .into_iter()
+            .filter(|h| h.0 == HarmonicType::Tapped)
+            .collect()
+    };
+    assert!(!tapped(&song).is_empty());
+    assert_eq!(tapped(&imported), tapped(&song));
+    let mut gp5 = Song::default();
+    gp5.read_gp5(&imported.write((5, 1, 0), None).unwrap())
+        .unwrap();
+    assert_eq!(tapped(&gp5), tapped(&song));
+
+    let (_, imported) = alphatex_round_trip("test/Demo v5.gp5");
+    assert!(imported.write((5, 1, 0), None).is_ok());
+    let song = read_gp7("test/artificial-harmonic.gp");
+    let mut imported = Song::default();
+    imported.read_alphatex(&song.write_alphatex()).unwrap();
+    assert_eq!(harmonics(&imported), harmonics(&song));
+    assert!(imported.write((5, 1, 0), None).is_ok());
+
+    // Without a fret a tapped harmonic is played on the note fret
+    let mut song = Song::default();
+    song.read_alphatex("\\tempo 120 . 5.3{th} 7.3{th 19 v} |")
+        .unwrap();
+    assert_eq!(
+        harmonics(&song),
+        [
+            (HarmonicType::Tapped, Some(5)),
+            (HarmonicType::Tapped, Some(19))
+        ]
+    );
+}
+
+#[test]
+fn test_alphatex_round_trip_voices_and_repeats() {
+    let (song, imported) = alphatex_round_trip("test/Voices.gp5");
+    for (track, original) in imported.tracks.iter().zip(&song.tracks) {
+        for (measure, original) in track.measures.iter().zip(&original.measures) {
+            let notes = |m: &crate::model::measure::Measure| -> Vec<usize> {
+                m.voices[1]
+                    .beats
+                    .iter()
+                    .map(|b| b.notes.len())
+                    .filter(|n| *n > 0)
+                    .collect()
+            };
+            assert_eq!(notes(measure), notes(original));
+        }
+    }
+
+    let (song, imported) = alphatex_round_trip("test/volta.gp5");
+    assert_eq!(imported.measure_headers.len(), song.measure_headers.len());
+    for (header, original) in imported.measure_headers.iter().zip(&song.measure_headers) {
+        assert_eq!(header.repeat_open, original.repeat_open);
+        assert_eq!(header.repeat_close, original.repeat_close);
+        assert_eq!(header.repeat_alternative, original.repeat_alternative);
+    }
+
+    let (song, imported) = alphatex_round_trip("test/all-percussion.gp5");
+    assert!(imported.tracks[0].percussion_track);
+    assert_eq!(
+        first_voice_positions(&imported.tracks[0]),
+        first_voice_positions(&song.tracks[0])
+    );
+}
+
+#[test]
+fn test_alphatex_round_trip_tuplets() {
+    use crate::io::alphatex_export::SongAlphaTexExportOps;
+    use crate::io::alphatex_import::SongAlphaTexOps;
+    let tuplets = |s: &Song| -> Vec<(u8, u8)> {
+        s.tracks
+            .iter()
+            .flat_map(|t| &t.measures)
+            .flat_map(|m| &m.voices)
+            .flat_map(|v| &v.beats)
+            .map(|b| (b.duration.tuplet_enters, b.duration.tuplet_times))
+            .filter(|t| *t != (1, 1))
+            .collect()
+    };
+    let song = read_gpx("test/tuplets.gpx");
+    assert!(tuplets(&song).contains(&(15, 8)));
+    let mut imported = Song::default();
+    imported.read_alphatex(&song.write_alphatex()).unwrap();
+    assert_eq!(tuplets(&imported), tuplets(&song));
+}
+
+#[test]
+fn test_alphatex_import() {
+    use crate::io::alphatex_import::SongAlphaTexOps;
+    let text = r#"
+        \title "Etude" \artist "Someone" \tempo 90
+        .
+        \track "Lead" "Ld."
+          \tuning e5 b4 g4 d4 a3 d3
+          \instrument 29
+          \capo 2
+          \chord "G" 3 0 0 0 2 3
+          \lyrics "la la"
+        \ts 3 4 \ks Eminor \ro
+        :4 0.6 (3.1 2.2){ch "G"} 5.3{b (0 4) v}.8 7.3{sl} |
+        \rc 2 \section "Solo" 3.2.16{gr} 5.2.2{tu 3} r.4 // comment
+        | /* empty bar */
+    "#;
+    let mut song = Song::default();
+    song.read_alphatex(text).unwrap();
+    assert_eq!(song.name, "Etude");
+    assert_eq!(song.artist, "Someone");
+    assert_eq!(song.tempo, 90);
+    assert_eq!(song.measure_headers.len(), 2);
+    let header = &song.measure_headers[0];
+    assert_eq!(header.time_signature.numerator, 3);
+    assert_eq!(
+        (header.key_signature.key, header.key_signature.is_minor),
+        (1, true)
+    );
+    assert!(header.repeat_open);
+    assert_eq!(song.measure_headers[1].repeat_close, 2);
+    assert_eq!(
+        song.measure_headers[1].marker.as_ref().unwrap().title,
+        "Solo"
+    );
+
+    let track = &song.tracks[0];
+    assert_eq!(
+        (track.name.as_str(), track.short_name.as_str()),
+        ("Lead", "Ld.")
+    );
+    assert_eq!(track.strings[5], (6, 38));
+    assert_eq!(track.offset, 2);
+    assert_eq!(song.channels[track.channel_index].instrument, 29);
+    assert_eq!(song.lyrics.track_choice, 1);
+    assert_eq!(song.lyrics.lines[0].2, "la la");
+
+    let beats = &track.measures[0].voices[0].beats;
+    assert_eq!(beats.len(), 4);
+    let chord = beats[1].effect.chord.as_ref().unwrap();
+    assert_eq!(chord.name, "G");
+    assert_eq!(chord.strings, vec![3, 0, 0, 0, 2, 3]);
+    let bend = beats[2].notes[0].effect.bend.as_ref().unwrap();
+    assert_eq!(bend.points.last().unwrap().value, 4);
+    assert!(beats[2].notes[0].effect.vibrato);
+    assert_eq!(beats[2].duration.value, 8);
+    assert_eq!(beats[3].duration.value, 8);
+    assert_eq!(
+        beats[3].notes[0].effect.slides,
+        vec![crate::model::enums::SlideType::LegatoSlideTo]
+    );
+
+    let beats = &track.measures[1].voices[0].beats;
+    assert_eq!(beats.len(), 2);
+    let grace = beats[0].notes[0].effect.grace.as_ref().unwrap();
+    assert_eq!((grace.fret, grace.duration), (3, 16));
+    assert_eq!(beats[0].duration.tuplet_enters, 3);
+    assert_eq!(beats[1].status, crate::model::enums::BeatStatus::Rest);
+}
+
+#[test]
+fn test_alphatex_import_invalid() {
+    use crate::io::alphatex_import::SongAlphaTexOps;
+    let mut song = Song::default();
+    assert!(song.read_alphatex("\\title \"No bars\" .").is_err());
+    assert!(song.read_alphatex("\\title \"Open string").is_err());
+    assert!(song.read_alphatex(". 3.9 |").is_err());
+    assert!(song.read_alphatex(". \\tuning q4 . 0.1").is_err());
+    assert!(song.read_alphatex(". 0.1{tu 0} |").is_err());
+    assert!(song.read_alphatex(". 0.1{tu 256} |").is_err());
+    assert!(song.read_alphatex(". 0.1{tu 3 -2} |").is_err());
+    assert!(song.read_alphatex(". 0.1 2.2 |").is_ok());
+}
+
+// ==================== ABC export tests ====================
+
+fn abc_export(path: &str, track: usize, voice: Option<usize>) -> String {
+    use crate::io::abc_export::SongAbcExportOps;
+    let mut song = Song::default();
+    song.read_gp5(&read_file(String::from(path))).unwrap();
+    song.write_abc(track, voice).unwrap()
+}
+
+#[test]
+fn test_abc_export_melody() {
+    let abc = abc_export("test/test.gp5", 0, None);
+    assert!(abc.starts_with("X:1\nT:titre de la chanson\nT:sous-titre\n"));
+    for field in ["M:4/4\n", "L:1/8\n", "Q:1/4=120\n", "K:C\n"] {
+        assert!(abc.contains(field), "missing {}", field);
+    }
+    assert!(abc.contains("E,,2 E,,2 E,,2 E,,2 | B,,2 B,,2 B,,2 B,,2 |"));
+    assert!(abc.contains("\nw:bla bla 1 * * "));
+    assert!(abc.trim_end().lines().any(|l| l.ends_with("|]")));
+
+    // Accidentals come from the key signature, naturals cancel it
+    let abc = abc_export("test/keysig.gp5", 0, None);
+    assert!(abc.contains("| [K:G]G,2 A,2 B,2 C2 |"));
+    assert!(abc.contains("| [K:Bb]G,2 A,2 =B,2 C2 |"));
+    assert!(abc.contains("[K:Em]C,2 D,2 E,2 =F,2 |"));
+}
+
+#[test]
+fn test_abc_export_repeats_and_voices() {
+    let abc = abc_export("test/volta.gp5", 0, None);
+    assert!(abc.contains("M:3/4\n"));
+    assert!(abc.contains("V:1\n"));
+    assert!(abc.contains("V:2\n"));
+    assert!(abc.contains("(3:2:3E B, G, (3:2:3G B, G,"));
+    assert!(abc.contains("|[1 E2 [EB,G,]4 :|[2,3,6,8 z6 |]"));
+
+    let abc = abc_export("test/volta.gp5", 0, Some(1));
+    assert!(!abc.contains("V:"));
+    assert!(abc.contains("E,,6 | B,,6 | E,,6 | E,,6 |"));
+}
+
+#[test]
+fn test_abc_export_invalid() {
+    use crate::io::abc_export::SongAbcExportOps;
+    let mut song = Song::default();
+    song.read_gp5(&read_file(String::from("test/test.gp5")))
+        .unwrap();
+    assert!(song.write_abc(song.tracks.len(), None).is_err());
+    assert!(song.write_abc(0, Some(2)).is_err());
+    let mut song = Song::default();
+    song.read_gp5(&read_file(String::from("test/all-percussion.gp5")))
+        .unwrap();
+    assert!(song.write_abc(0, None).is_err());
+}
+
+// ==================== JSON tests ====================
+
+#[test]
+#[cfg(feature = "serde")]
+fn test_json_round_trip() {
+    use crate::io::json::SongJsonOps;
+    for path in ["test/test.gp5", "test/Effects.gp5", "test/Voices.gp5"] {
+        let mut song = Song::default();
+        song.read_gp5(&read_file(String::from(path))).unwrap();
+        let json = song.write_json().unwrap();
+        let mut imported = Song::default();
+        imported.read_json(&json).unwrap();
+        assert_eq!(imported.name, song.name);
+        assert_eq!(imported.tracks.len(), song.tracks.len());
+        assert_eq!(imported.write_json().unwrap(), json);
+    }
+    let mut song = Song::default();
+    song.read_gp5(&read_file(String::from("test/test.gp5")))
+        .unwrap();
+    let json = song.write_json().unwrap();
+    assert!(json.contains("\"name\":\"titre de la chanson\""));
+    assert!(json.contains("\"strings\":[[1,64],[2,59]"));
+    assert!(!json.contains("current_track"));
+    // Positions in the GPIF document read are not part of the model
+    let json = read_gp7("test/accent.gp").write_json().unwrap();
+    assert!(!json.contains("gpif_index"));
+    assert!(Song::default().read_json("{\"name\": 3}").is_err());
+}
+
+/// The published JSON Schema matches the model, `UPDATE_SCHEMA=1` writes it again.
+#[test]
+#[cfg(feature = "json-schema")]
+fn test_json_schema_published() {
+    let schema = crate::io::json::song_json_schema();
+    let path = "schema/song.schema.json";
+    if std::env::var_os("UPDATE_SCHEMA").is_some() {
+        fs::write(path, format!("{}\n", schema)).unwrap();
+    }
+    let published = fs::read_to_string(path).unwrap();
+    assert_eq!(published.trim_end(), schema);
+    for definition in [
+        "Track",
+        "Measure",
+        "Voice",
+        "Beat",
+        "Note",
+        "NoteEffect",
+        "BendEffect",
+    ] {
+        assert!(schema.contains(&format!("\"{}\": {{", definition)));
+    }
+}
+
+// ==================== GP3/GP4/GP5 writer tests ====================
+
+fn read_gp_binary(song: &mut Song, data: &[u8], extension: &str) -> crate::GpResult<()> {
+    match extension {
+        "gp3" => song.read_gp3(data),
+        "gp4" => song.read_gp4(data),
+        _ => song.read_gp5(data),
+    }
+}
+
+/// Tied notes of a song: track, measure, voice, beat, string and fret.
+fn tied_notes(song: &Song) -> Vec<(usize, usize, usize, usize, i8, i16)> {
+    use crate::model::enums::NoteType;
+    let mut ties = Vec::new();
+    for (t, track) in song.tracks.iter().enumerate() {
+        for (m, measure) in track.measures.iter().enumerate() {
+            for (v, voice) in measure.voices.iter().enumerate() {
+                for (b, beat) in voice.beats.iter().enumerate() {
+                    for note in beat.notes.iter().filter(|n| n.kind == NoteType::Tie) {
+                        ties.push((t, m, v, b, note.string, note.value));
+                    }
+                }
+            }
+        }
+    }
+    ties
+}
+
+#[test]
+fn test_gp_binary_write_round_trip() {
+    for extension in ["gp3", "gp4", "gp5"] {
+        for path in test_files(extension) {
+            let mut song = Song::default();
+            read_gp_binary(&mut song, &fs::read(&path).unwrap(), extension).unwrap();
+            let data = song
+                .write(song.version.number, Some(song.version.clipboard))
+                .unwrap();
+            let mut reread = Song::default();
+            read_gp_binary(&mut reread, &data, extension).unwrap();
+            assert_eq!(tied_notes(&song), tied_notes(&reread), "{}", path.display());
+            assert_eq!(
+                format!("{:?}", song),
+                format!("{:?}", reread),
+                "{}",
+                path.display()
+            );
+        }
+    }
+}
+
+#[test]
+fn test_gp_binary_tied_frets() {
+    use std::collections::HashMap;
+    for (path, extension, count) in [
+        ("test/gamma_ray-heading_for_tomorrow.gp3", "gp3", 416),
+        ("test/led-zeppelin-babe_i_m_gonna_leave_you.gp4", "gp4", 270),
+    ] {
+        let mut song = Song::default();
+        read_gp_binary(&mut song, &read_file(String::from(path)), extension).unwrap();
+        let ties = tied_notes(&song);
+        assert_eq!(ties.len(), count, "{}", path);
+        // A tied note keeps the fret of the previous note on its string
+        for (t, track) in song.tracks.iter().enumerate() {
+            let mut frets: HashMap<(usize, i8), i16> = HashMap::new();
+            for (m, measure) in track.measures.iter().enumerate() {
+                for (v, voice) in measure.voices.iter().enumerate() {
+                    for (b, beat) in voice.beats.iter().enumerate() {
+                        for note in &beat.notes {
+                            if ties.contains(&(t, m, v, b, note.string, note.value)) {
+                                if let Some(fret) = frets.get(&(v, note.string)) {
+                                    assert_eq!(note.value, *fret, "{} {:?}", path, (t, m, v, b));
+                                }
+                            }
+                            frets.insert((v, note.string), note.value);
+                        }
+                    }
+                }
+            }
+        }
+        let data = song
+            .write(song.version.number, Some(song.version.clipboard))
+            .unwrap();
+        let mut reread = Song::default();
+        read_gp_binary(&mut reread, &data, extension).unwrap();
+        assert_eq!(tied_notes(&reread), ties, "{}", path);
+    }
+    // Tied to the last beat of the measure before, not to its first one
+    let mut song = Song::default();
+    song.read_gp3(&read_file(String::from(
+        "test/gamma_ray-heading_for_tomorrow.gp3",
+    )))
+    .unwrap();
+    let note = &song.tracks[0].measures[4].voices[0].beats[0].notes[1];
+    assert_eq!((note.string, note.value), (5, 7));
+}
+
+#[test]
+fn test_gp_binary_write_empty_song() {
+    let song = Song::default();
+    for version in [(3, 0, 0), (4, 0, 6), (5, 0, 0), (5, 1, 0)] {
+        let data = song.write(version, None).unwrap();
+        let mut reread = Song::default();
+        match version.0 {
+            3 => reread.read_gp3(&data).unwrap(),
+            4 => reread.read_gp4(&data).unwrap(),
+            _ => reread.read_gp5(&data).unwrap(),
+        }
+        assert_eq!(reread.version.number, version);
+    }
+    assert!(song.write((6, 0, 0), None).is_err());
+}
+
+// ==================== Conversion tests ====================
+
+fn lost_at(
+    report: &crate::model::conversion::LossReport,
+    feature: &str,
+    position: crate::model::conversion::LossPosition,
+) -> bool {
+    report
+        .items
+        .iter()
+        .any(|i| i.feature == feature && i.position == position)
+}
+
+fn beat_position(
+    track: usize,
+    measure: usize,
+    voice: usize,
+    beat: usize,
+) -> crate::model::conversion::LossPosition {
+    crate::model::conversion::LossPosition {
+        track: Some(track),
+        measure: Some(measure),
+        voice: Some(voice),
+        beat: Some(beat),
+    }
+}
+
+#[test]
+fn test_convert_gp5_to_gp3() {
+    use crate::model::conversion::{LossPosition, SongConversionOps};
+    let mut song = Song::default();
+    song.read_gp5(&read_file(String::from("test/Demo v5.gp5")))
+        .unwrap();
+    let (data, report) = song.convert_to((3, 0, 0)).unwrap();
+    let mut reread = Song::default();
+    reread.read_gp3(&data).unwrap();
+    assert_eq!(reread.tracks.len(), song.tracks.len());
+    assert_eq!(report.version, (3, 0, 0));
+    assert!(lost_at(&report, "page setup", LossPosition::default()));
+    assert!(lost_at(&report, "palm mute", beat_position(1, 1, 1, 2)));
+    assert!(lost_at(
+        &report,
+        "direction",
+        LossPosition {
+            measure: Some(18),
+            ..Default::default()
+        }
+    ));
+    assert!(report
+        .to_string()
+        .contains("track 1, measure 1, voice 1, beat 2: palm mute\n"));
+
+    let (_, report) = song.convert_to((5, 0, 0)).unwrap();
+    assert!(report.is_empty());
+}
+
+#[test]
+fn test_convert_additional_voices() {
+    use crate::model::conversion::SongConversionOps;
+    let mut song = Song::default();
+    song.read_gp5(&read_file(String::from("test/Demo v5.gp5")))
+        .unwrap();
+    let voice = song.tracks[0].measures[0].voices[0].clone();
+    song.tracks[0].measures[0].voices.push(voice);
+    let report = song.loss_report((5, 1, 0));
+    assert!(lost_at(
+        &report,
+        "additional voice",
+        beat_position(1, 1, 3, 1)
+    ));
+    assert!(!report.items.iter().any(|i| i.position.voice == Some(2)));
+}
+
+#[test]
+fn test_convert_gp7_losses() {
+    use crate::model::conversion::SongConversionOps;
+    use crate::model::enums::Octave;
+    let mut song = Song::default();
+    song.read_gp(&read_file(String::from("test/ottava1.gp")))
+        .unwrap();
+    assert_eq!(
+        song.tracks[0].measures[0].voices[0].beats[0].octave,
+        Octave::Ottava
+    );
+    assert!(lost_at(
+        &song.loss_report((4, 0, 6)),
+        "ottava",
+        beat_position(1, 1, 1, 1)
+    ));
+    assert!(!song
+        .loss_report((5, 1, 0))
+        .items
+        .iter()
+        .any(|i| i.feature == "ottava"));
+
+    let mut song = Song::default();
+    song.read_gp(&read_file(String::from("test/crescendo-diminuendo.gp")))
+        .unwrap();
+    let (data, report) = song.convert_to((5, 1, 0)).unwrap();
+    Song::default().read_gp5(&data).unwrap();
+    assert!(lost_at(&report, "hairpin", beat_position(1, 1, 1, 2)));
+
+    let mut song = Song::default();
+    song.read_gp(&read_file(String::from("test/mordents.gp")))
+        .unwrap();
+    assert!(lost_at(
+        &song.loss_report((5, 1, 0)),
+        "ornament",
+        beat_position(1, 1, 1, 1)
+    ));
+
+    // 15:8 cannot be stored, 3:2 can
+    let song = read_gpx("test/tuplets.gpx");
+    let (data, report) = song.convert_to((5, 1, 0)).unwrap();
+    Song::default().read_gp5(&data).unwrap();
+    assert!(lost_at(&report, "tuplet", beat_position(1, 2, 1, 3)));
+    assert!(!lost_at(&report, "tuplet", beat_position(1, 1, 1, 1)));
+}
+
+// ==================== GPIF to GP5 tests ====================
+
+fn note_count(track: &crate::model::track::Track) -> usize {
+    track
+        .measures
+        .iter()
+        .flat_map(|m| m.voices.iter().take(2))
+        .flat_map(|v| v.beats.iter())
+        .map(|b| b.notes.len())
+        .sum()
+}
+
+#[test]
+fn test_gpif_write_gp5() {
+    for extension in ["gp", "gpx"] {
+        for path in test_files(extension) {
+            let data = fs::read(&path).unwrap();
+            let mut song = Song::default();
+            match extension {
+                "gp" => song.read_gp(&data).unwrap(),
+                _ => song.read_gpx(&data).unwrap(),
+            }
+            let mut reread = Song::default();
+            reread
+                .read_gp5(&song.write((5, 1, 0), None).unwrap())
+                .unwrap();
+            let name = path.display();
+            assert_eq!(song.tracks.len(), reread.tracks.len(), "{}", name);
+            assert_eq!(
+                song.measure_headers.len(),
+                reread.measure_headers.len(),
+                "{}",
+                name
+            );
+            for (a, b) in song.tracks.iter().zip(&reread.tracks) {
+                assert_eq!(note_count(a), note_count(b), "{}: {}", name, a.name);
+                assert_eq!(a.strings, b.strings, "{}: {}", name, a.name);
+                assert_eq!(
+                    song.channels[a.channel_index].instrument,
+                    reread.channels[b.channel_index].instrument,
+                    "{}: {}",
+                    name,
+                    a.name
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_gpif_timing_and_channels() {
+    use crate::model::key_signature::{DURATION_QUARTER_TIME, DURATION_SIXTEENTH};
+    let song = read_gpx("test/all-percussion.gpx");
+    let header = &song.measure_headers[0];
+    assert_eq!(header.start, DURATION_QUARTER_TIME);
+    assert_eq!(
+        song.measure_headers[1].start,
+        header.start + header.length()
+    );
+    let song = read_gp7("test/trill.gp");
+    assert_eq!(song.channels.len(), 64);
+    let beats = &song.tracks[0].measures[0].voices[0].beats;
+    assert_eq!(beats[1].start, Some(DURATION_QUARTER_TIME * 2));
+    // the trill of the third beat goes from F to G on the same string
+    let trill = beats[2].notes[0].effect.trill.as_ref().unwrap();
+    assert_eq!(trill.fret, beats[2].notes[0].value as i8 + 2);
+    assert_eq!(trill.duration.value, DURATION_SIXTEENTH as u16);
+}
+
+#[test]
+fn test_gpif_unfretted_notes() {
+    use crate::model::enums::HarmonicType;
+    // GP6 percussion notes only name an element of the drum kit
+    let song = read_gpx("test/all-percussion.gpx");
+    let mut expected = Song::default();
+    expected
+        .read_gp5(&read_file(String::from("test/all-percussion.gp5")))
+        .unwrap();
+    let pitches: Vec<i16> = expected.tracks[0]
+        .measures
+        .iter()
+        .flat_map(|m| m.voices.iter())
+        .flat_map(|v| v.beats.iter())
+        .flat_map(|b| b.notes.iter().map(|n| n.value))
+        .collect();
+    for track in &song.tracks {
+        assert!(track.percussion_track);
+        assert_eq!(song.channels[track.channel_index].channel % 16, 9);
+        for beat in track.measures.iter().flat_map(|m| &m.voices[0].beats) {
+            for note in &beat.notes {
+                assert!(note.string > 0, "{}", track.name);
+                assert!(pitches.contains(&note.value), "{}", track.name);
+            }
+        }
+    }
+    // GP6 keyboard notes have a tone and an octave
+    let song = read_gpx("test/clefs.gpx");
+    assert_eq!(note_count(&song.tracks[0]), 7);
+    // the harmonic fret comes before the harmonic type
+    let song = read_gp7("test/artificial-harmonic.gp");
+    let harmonics: Vec<_> = song.tracks[0]
+        .measures
+        .iter()
+        .flat_map(|m| &m.voices[0].beats)
+        .flat_map(|b| &b.notes)
+        .filter_map(|n| n.effect.harmonic.as_ref())
+        .collect();
+    assert!(harmonics
+        .iter()
+        .any(|h| h.kind == HarmonicType::Tapped && h.fret.is_some()));
+}
+
+#[test]
+fn test_gpif_unfretted_notes_out_of_range() {
+    use crate::io::gpif_import::SongGpifOps;
+    use crate::model::conversion::LossPosition;
+    let mut gpif = crate::io::gpx::read_gpx(&read_file(String::from("test/clefs.gpx"))).unwrap();
+    // A keyboard note two octaves below the guitar tuning
+    let (n_idx, octave) = gpif
+        .notes
+        .notes
+        .iter_mut()
+        .enumerate()
+        .find_map(|(i, n)| {
+            let properties = &mut n.properties.properties;
+            Some((i, properties.iter_mut().find(|p| p.name == "Octave")?))
+        })
+        .unwrap();
+    octave.number = Some(1);
+    let note_id = gpif.notes.notes[n_idx].id.to_string();
+    let beat = gpif
+        .beats
+        .beats
+        .iter()
+        .find(|b| {
+            b.notes
+                .as_deref()
+                .is_some_and(|n| n.split_whitespace().any(|id| id == note_id))
+        })
+        .unwrap()
+        .id
+        .to_string();
+    let voice = gpif
+        .voices
+        .voices
+        .iter()
+        .find(|v| v.beats.split_whitespace().any(|id| id == beat))
+        .unwrap();
+    let beat_number = voice
+        .beats
+        .split_whitespace()
+        .position(|id| id == beat)
+        .unwrap()
+        + 1;
+    let voice = voice.id.to_string();
+    let bar = gpif
+        .bars
+        .bars
+        .iter()
+        .find(|b| b.voices.split_whitespace().any(|id| id == voice))
+        .unwrap()
+        .id
+        .to_string();
+    let measure = gpif
+        .master_bars
+        .master_bars
+        .iter()
+        .position(|m| m.bars.split_whitespace().any(|id| id == bar))
+        .unwrap()
+        + 1;
+
+    let mut song = Song::default();
+    song.read_gpif(&gpif);
+    assert_eq!(note_count(&song.tracks[0]), 6);
+    assert_eq!(song.read_losses.len(), 1);
+    assert_eq!(song.read_losses[0].feature, "note that fits on no string");
+    assert_eq!(
+        song.read_losses[0].position,
+        LossPosition {
+            track: Some(1),
+            measure: Some(measure),
+            voice: Some(1),
+            beat: Some(beat_number),
+        }
+    );
+    // The file as saved has every note on a string
+    assert!(read_gpx("test/clefs.gpx").read_losses.is_empty());
+}
+
+// ==================== GPIF lyrics tests ====================
+
+#[test]
+fn test_gp7_lyrics() {
+    let song = read_gp7("test/test.gp");
+    let mut expected = Song::default();
+    expected
+        .read_gp5(&read_file(String::from("test/test.gp5")))
+        .unwrap();
+    assert_eq!(song.lyrics.track_choice, expected.lyrics.track_choice);
+    assert_eq!(song.lyrics.lines.len(), 5);
+    for (line, expected_line) in song.lyrics.lines.iter().zip(&expected.lyrics.lines) {
+        assert_eq!(line.0, expected_line.0);
+        assert_eq!(line.1, expected_line.1);
+        assert_eq!(line.2.trim(), expected_line.2.trim());
+    }
+    // files without lyrics still have five empty lines
+    let song = read_gp7("test/trill.gp");
+    assert_eq!(song.lyrics.track_choice, 0);
+    assert_eq!(song.lyrics.lines.len(), 5);
+    assert!(song.lyrics.lines.iter().all(|l| l.2.is_empty()));
+}
+
+#[test]
+fn test_gpif_write_lyrics() {
+    let mut song = Song::default();
+    song.read_gp5(&read_file(String::from("test/test.gp5")))
+        .unwrap();
+    let mut reread = Song::default();
+    reread.read_gp(&song.write_gp().unwrap()).unwrap();
+    assert_eq!(reread.lyrics, song.lyrics);
+    let mut reread = Song::default();
+    reread.read_gpx(&song.write_gpx().unwrap()).unwrap();
+    assert_eq!(reread.lyrics, song.lyrics);
+}
+
+// ==================== GPIF chord tests ====================
+
+fn beat_chords(song: &Song) -> Vec<crate::model::chord::Chord> {
+    song.tracks
+        .iter()
+        .flat_map(|t| &t.measures)
+        .flat_map(|m| &m.voices)
+        .flat_map(|v| &v.beats)
+        .filter_map(|b| b.effect.chord.clone())
+        .collect()
+}
+
+#[test]
+fn test_gpif_chord_diagrams() {
+    use crate::model::enums::Fingering;
+    let mut gp5 = Song::default();
+    gp5.read_gp5(&read_file(String::from("test/fret-diagram.gp5")))
+        .unwrap();
+    let expected = beat_chords(&gp5).remove(0);
+    for song in [
+        read_gpx("test/fret-diagram.gpx"),
+        read_gp7("test/fret-diagram.gp"),
+    ] {
+        let chords = beat_chords(&song);
+        assert_eq!(chords.len(), 1);
+        let chord = &chords[0];
+        assert_eq!(chord.name, expected.name);
+        assert_eq!(chord.length, expected.length);
+        assert_eq!(chord.first_fret, expected.first_fret);
+        assert_eq!(chord.strings, expected.strings);
+        assert_eq!(chord.show, expected.show);
+        assert_eq!(
+            chord.fingerings,
+            vec![
+                Fingering::Annular,
+                Fingering::Open,
+                Fingering::Middle,
+                Fingering::Open,
+                Fingering::Open,
+                Fingering::Index,
+                Fingering::Open
+            ]
+        );
+        assert!(chord.barres.is_empty());
+    }
+    // Each track has its own collection, the ids overlap
+    let song = read_gp7("test/fret-diagram_2instruments.gp");
+    for track in &song.tracks {
+        let names: Vec<String> = track
+            .measures
+            .iter()
+            .flat_map(|m| &m.voices)
+            .flat_map(|v| &v.beats)
+            .filter_map(|b| b.effect.chord.as_ref().map(|c| c.name.clone()))
+            .collect();
+        assert_eq!(names, vec!["Em", "Am", "Em"]);
+    }
+    let em = &beat_chords(&song)[0];
+    assert_eq!(em.strings, vec![-1, -1, 0, 2, 2, 0]);
+    // Frets are relative to the base fret, here 3
+    let song = read_gpx("test/rage-against-the-machine_bombtrack-official-2210247.gpx");
+    let power = beat_chords(&song)
+        .into_iter()
+        .find(|c| c.name == "C#5")
+        .unwrap();
+    assert_eq!(power.first_fret, Some(4));
+    assert_eq!(power.strings, vec![-1, -1, 6, 6, 4, -1]);
+}
+
+#[test]
+fn test_gpif_write_chords() {
+    let mut song = Song::default();
+    song.read_gp5(&read_file(String::from("test/fret-diagram.gp5")))
+        .unwrap();
+    let expected = beat_chords(&song).remove(0);
+    let mut gp = Song::default();
+    gp.read_gp(&song.write_gp().unwrap()).unwrap();
+    let mut gpx = Song::default();
+    gpx.read_gpx(&song.write_gpx().unwrap()).unwrap();
+    for reread in [gp, gpx] {
+        let chord = beat_chords(&reread).remove(0);
+        assert_eq!(chord.name, expected.name);
+        assert_eq!(chord.first_fret, expected.first_fret);
+        assert_eq!(chord.strings, expected.strings);
+        assert_eq!(chord.root, expected.root);
+        assert_eq!(chord.bass, expected.bass);
+        assert_eq!(chord.kind, expected.kind);
+        assert_eq!(chord.extension, expected.extension);
+    }
+}
+
+#[test]
+fn test_gpif_chord_names() {
+    use crate::model::enums::ChordType;
+    for song in [
+        read_gpx("test/chordnames_keyboard.gpx"),
+        read_gp7("test/chordnames_keyboard.gp"),
+    ] {
+        let chords = beat_chords(&song);
+        assert!(!chords.is_empty());
+        let chord = &chords[0];
+        assert_eq!(chord.name, "G");
+        assert_eq!(chord.root.as_ref().map(|r| r.value), Some(7));
+        assert_eq!(chord.bass.as_ref().map(|r| r.value), Some(7));
+        assert_eq!(chord.kind, Some(ChordType::Major));
+    }
+}
+
+// ==================== GPIF automation tests ====================
+
+fn mix_table_at(
+    song: &Song,
+    track: usize,
+    measure: usize,
+    beat: usize,
+) -> Option<&crate::model::mix_table::MixTableChange> {
+    song.tracks[track].measures[measure].voices[0]
+        .beats
+        .get(beat)?
+        .effect
+        .mix_table_change
+        .as_ref()
+}
+
+#[test]
+fn test_gpif_tempo_automations() {
+    use crate::audio::midi_export::SongMidiExportOps;
+    use crate::model::key_signature::DURATION_QUARTER_TIME;
+    // Linear ramp from 82 at the start to 77 at the end of bar 8, then 76 at the end of bar 16.
+    // The first track rests in the intro, the ramp goes to the second one.
+    let song = read_gpx("test/rage-against-the-machine_bombtrack-official-2210247.gpx");
+    assert_eq!(song.tempo, 82);
+    assert_eq!(song.measure_headers[0].tempo, 82);
+    assert_eq!(song.measure_headers[7].tempo, 0);
+    let last_beat = |track: usize, measure: usize| {
+        song.tracks[track].measures[measure].voices[0].beats.len() - 1
+    };
+    let ramp = mix_table_at(&song, 1, 0, 0).unwrap().tempo.clone().unwrap();
+    let end = song.tracks[1].measures[7].voices[0].beats[last_beat(1, 7)]
+        .start
+        .unwrap();
+    assert_eq!(ramp.value, 77);
+    assert_eq!(
+        i64::from(ramp.duration),
+        (end - song.measure_headers[0].start) / DURATION_QUARTER_TIME
+    );
+    let tempo = |track: usize, measure: usize| {
+        mix_table_at(&song, track, measure, last_beat(track, measure))
+            .and_then(|m| m.tempo.as_ref())
+            .map(|t| (t.value, t.duration))
+    };
+    assert_eq!(tempo(1, 7), None);
+    assert_eq!(tempo(0, 15), Some((76, 0)));
+
+    // The ramp plays as one tempo change per quarter note, written when the tempo changes
+    let data = song.write_midi().unwrap();
+    let conductor = midi_events(midi_chunks(&data)[1].1);
+    let tempos: Vec<u32> = conductor
+        .iter()
+        .filter(|e| e.1 == 0xff && e.2[0] == 0x51)
+        .map(|e| 60_000_000 / u32::from_be_bytes([0, e.2[1], e.2[2], e.2[3]]))
+        .collect();
+    assert_eq!(tempos, vec![82, 81, 80, 79, 78, 77, 76]);
+}
+
+#[test]
+fn test_gpif_channel_automations() {
+    use crate::audio::midi_export::SongMidiExportOps;
+    use crate::model::mix_table::{MixTableChange, MixTableItem};
+    let song = read_gp7("test/tempo.gp");
+    let channel = song.channels[song.tracks[0].channel_index];
+    assert_eq!(channel.volume, 74);
+    assert_eq!(channel.balance, 64);
+
+    // Volume ramp and pan change inside a bar, kept by the GPIF writers
+    let mut song = Song::default();
+    song.read_gp5(&read_file(String::from("test/test.gp5")))
+        .unwrap();
+    let mix = MixTableChange {
+        volume: Some(MixTableItem {
+            value: 4,
+            duration: 2,
+            all_tracks: false,
+        }),
+        balance: Some(MixTableItem {
+            value: 12,
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    song.tracks[0].measures[1].voices[0].beats[1]
+        .effect
+        .mix_table_change = Some(mix.clone());
+    let mut gp = Song::default();
+    gp.read_gp(&song.write_gp().unwrap()).unwrap();
+    let mut gpx = Song::default();
+    gpx.read_gpx(&song.write_gpx().unwrap()).unwrap();
+    for reread in [gp, gpx] {
+        let change = mix_table_at(&reread, 0, 1, 1).unwrap();
+        assert_eq!(change.volume, mix.volume);
+        assert_eq!(change.balance, mix.balance);
+    }
+
+    // The volume ramp plays as several volume controller changes after the initial one
+    let data = song.write_midi().unwrap();
+    let volumes = midi_events(midi_chunks(&data)[2].1)
+        .iter()
+        .filter(|e| e.1 & 0xf0 == 0xb0 && e.2[0] == 7)
+        .count();
+    assert!(volumes > 2, "{} volume events", volumes);
+}
+
+// ==================== GPIF sound tests ====================
+
+#[test]
+fn test_gpif_sound() {
+    use crate::model::rse::RseEffect;
+    // GP7: the sound selected at the start of the track
+    let song = read_gp7("test/tempo.gp");
+    let track = &song.tracks[0];
+    assert!(track.use_rse);
+    assert_eq!(track.rse.instrument.instrument, 24);
+    assert_eq!(track.rse.instrument.effect, "Nylon");
+    assert_eq!(
+        track.rse.instrument.effect_category,
+        "Stringed/Acoustic Guitars/Nylon Guitar"
+    );
+    assert_eq!(track.rse.sound_role, "Factory");
+    assert_eq!(track.rse.sound_bank_patch, "Concerto-Nylon");
+    assert_eq!(track.rse.effects.len(), 6);
+    assert_eq!(
+        track.rse.effects[1],
+        RseEffect {
+            id: String::from("M07_DynamicClassicDynamic"),
+            parameters: vec![0.5, 0.5, 0.94],
+        }
+    );
+    assert_eq!(track.rse.effects[3], RseEffect::default());
+    // Band gains of the channel strip equalizer
+    assert_eq!(track.rse.equalizer.knobs, [3.8, 5.3, -6.5]);
+    assert_eq!(track.rse.equalizer.gain, 0.0);
+
+    // GP6: the sound bank and the first effect chain of the track
+    let song = read_gpx("test/basic-bend.gpx");
+    let track = &song.tracks[0];
+    assert!(track.use_rse);
+    assert_eq!(track.rse.instrument.instrument, 25);
+    assert_eq!(track.rse.instrument.effect, "Acoustic Guitar (Steel)");
+    assert_eq!(track.rse.sound_bank_patch, "D-Steel");
+    assert_eq!(track.rse.effects.len(), 5);
+    assert_eq!(track.rse.effects[0].id, "E30_EqGEq");
+    assert_eq!(track.rse.effects[0].parameters.len(), 8);
+}
+
+#[test]
+fn test_gpif_sound_conversion() {
+    let same_sound = |a: &Song, b: &Song| {
+        for (a, b) in a.tracks.iter().zip(&b.tracks) {
+            assert_eq!(a.use_rse, b.use_rse);
+            assert_eq!(a.rse.instrument.instrument, b.rse.instrument.instrument);
+            assert_eq!(a.rse.instrument.effect, b.rse.instrument.effect);
+            assert_eq!(
+                a.rse.instrument.effect_category,
+                b.rse.instrument.effect_category
+            );
+            assert_eq!(a.rse.equalizer, b.rse.equalizer);
+        }
+    };
+
+    // GP7 to GP5 and back: the name and path of the sound are kept
+    use crate::model::conversion::{LossPosition, SongConversionOps};
+    let song = read_gp7("test/tempo.gp");
+    assert!(lost_at(
+        &song.loss_report((5, 1, 0)),
+        "RSE sound bank and effect chain",
+        LossPosition {
+            track: Some(1),
+            ..Default::default()
+        }
+    ));
+    let mut gp5 = Song::default();
+    gp5.read_gp5(&song.write((5, 1, 0), None).unwrap()).unwrap();
+    same_sound(&song, &gp5);
+    let mut gp = Song::default();
+    gp.read_gp(&gp5.write_gp().unwrap()).unwrap();
+    same_sound(&song, &gp);
+
+    // GP5 RSE instruments to GP6 and GP7
+    let mut song = Song::default();
+    song.read_gp5(&read_file(String::from("test/RSE.gp5")))
+        .unwrap();
+    let mut gp = Song::default();
+    gp.read_gp(&song.write_gp().unwrap()).unwrap();
+    same_sound(&song, &gp);
+    let mut gpx = Song::default();
+    gpx.read_gpx(&song.write_gpx().unwrap()).unwrap();
+    assert_eq!(gpx.tracks[0].rse.instrument.effect, "Acoustic - Default");
+
+    // GP7 to GP6: the bank patch and the effect chain are kept
+    let song = read_gp7("test/tempo.gp");
+    let mut gpx = Song::default();
+    gpx.read_gpx(&song.write_gpx().unwrap()).unwrap();
+    assert_eq!(gpx.tracks[0].rse.sound_bank_patch, "Concerto-Nylon");
+    assert_eq!(gpx.tracks[0].rse.effects, song.tracks[0].rse.effects);
+
+    // GP5 track equalizer to the channel strip of GP6 and GP7
+    let mut song = Song::default();
+    song.read_gp5(&read_file(String::from("test/RSE.gp5")))
+        .unwrap();
+    song.tracks[0].rse.equalizer.knobs = vec![3.8, 0.0, -6.5];
+    let mut gp = Song::default();
+    gp.read_gp(&song.write_gp().unwrap()).unwrap();
+    assert_eq!(gp.tracks[0].rse.equalizer, song.tracks[0].rse.equalizer);
+    let mut gpx = Song::default();
+    gpx.read_gpx(&song.write_gpx().unwrap()).unwrap();
+    assert_eq!(gpx.tracks[0].rse.equalizer, song.tracks[0].rse.equalizer);
+
+    // Written over its source, the channel strip keeps the parameters the model does not map
+    let song = read_gp7("test/tempo.gp");
+    let gpif = crate::io::gpx::read_gp(&song.write_gp().unwrap()).unwrap();
+    let strip = gpif.tracks.tracks[0].rse.as_ref().unwrap();
+    assert_eq!(
+        strip.channel_strip.as_ref().unwrap().parameters.as_deref(),
+        Some("0.67 0.66 0.75 0.66 0.72 0.5 0.67 0.23 0.56 1 0.5 0.5 0.58 0.5 0.5 0.5")
+    );
+}
+
+// ==================== GPIF staff tests ====================
+
+#[test]
+fn test_gpif_staves() {
+    use crate::model::enums::MeasureClef;
+    let song = read_gp7("test/chordnames_keyboard.gp");
+    assert_eq!(song.tracks.len(), 1);
+    let track = &song.tracks[0];
+    assert_eq!(track.strings[0], (1, 64));
+    assert_eq!(track.staves.len(), 1);
+    assert_eq!(
+        track.staves[0].strings,
+        vec![(1, 43), (2, 38), (3, 33), (4, 28)]
+    );
+    for measure in &track.measures {
+        assert!(matches!(measure.clef, MeasureClef::Treble));
+        assert_eq!(measure.staves.len(), 1);
+        assert!(matches!(measure.staves[0].clef, MeasureClef::Bass));
+    }
+    assert!(!track.measures[0].voices.is_empty());
+}
+
+#[test]
+fn test_gpif_staves_round_trip() {
+    use crate::model::conversion::{LossPosition, SongConversionOps};
+    use crate::model::enums::MeasureClef;
+    let mut song = read_gp7("test/chordnames_keyboard.gp");
+    // Play the notes of the first staff on the lowest string of the bass staff as well
+    for measure in &mut song.tracks[0].measures {
+        let mut voices = measure.voices.clone();
+        for note in voices
+            .iter_mut()
+            .flat_map(|v| &mut v.beats)
+            .flat_map(|b| &mut b.notes)
+        {
+            note.string = 4;
+        }
+        measure.staves[0].voices = voices;
+    }
+
+    let mut gp = Song::default();
+    gp.read_gp(&song.write_gp().unwrap()).unwrap();
+    assert_eq!(gp.tracks.len(), 1);
+    assert_eq!(gp.tracks[0].staves, song.tracks[0].staves);
+    let (measure, source) = (&gp.tracks[0].measures[0], &song.tracks[0].measures[0]);
+    assert!(matches!(measure.staves[0].clef, MeasureClef::Bass));
+    let notes = |voices: &[crate::model::beat::Voice]| -> Vec<(i8, i16)> {
+        voices
+            .iter()
+            .flat_map(|v| &v.beats)
+            .flat_map(|b| &b.notes)
+            .map(|n| (n.string, n.value))
+            .collect()
+    };
+    assert!(!notes(&measure.staves[0].voices).is_empty());
+    assert_eq!(
+        notes(&measure.staves[0].voices),
+        notes(&source.staves[0].voices)
+    );
+
+    // The bass staff plays with its own tuning
+    use crate::audio::midi_export::SongMidiExportOps;
+    let data = song.write_midi().unwrap();
+    let events = midi_events(midi_chunks(&data)[2].1);
+    let keys: Vec<u8> = events
+        .iter()
+        .filter(|e| e.1 & 0xf0 == 0x90 && e.2[1] > 0)
+        .map(|e| e.2[0])
+        .collect();
+    let fret = notes(&source.voices)[0].1;
+    assert!(keys.contains(&((64 + fret) as u8)));
+    assert!(keys.contains(&((28 + fret) as u8)));
+
+    // Guitar Pro 5 has a single staff per track
+    assert!(lost_at(
+        &song.loss_report((5, 1, 0)),
+        "additional staff",
+        LossPosition {
+            track: Some(1),
+            measure: Some(1),
+            ..Default::default()
+        }
+    ));
+}